@@ -1,38 +1,1547 @@
 //! REST API Server Module
 //! Provides HTTP endpoints for quantum circuit simulation
 
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde_json::{json, Value};
+use crate::api_version::{self, ApiVersion, API_VERSION};
+use crate::auth::{Caller, KeyStore, Role};
+use crate::backends::StateBackend;
+use crate::catalog::{self, Labels, SearchFilter, StoredCircuit, StoredResult};
+use crate::cluster::{ClusterStatus, DistributedConfig, NodeReport};
+use crate::jobs::JobQueue;
+use crate::manifest::Manifest;
+use crate::noise::{self, NoiseModel, NoisyBackend};
+use crate::observable::PauliSum;
+use crate::qsim::{self, QuantumCircuit, QuantumSimulator};
+use crate::templates::RewriteRule;
+use crate::workspace::{CircuitEntry, ResultEntry, Workspace};
+use crate::{entropy, lint, npy, optimizer, params, proto, qobj, results, stats};
 
+const PROTOBUF: &str = "application/x-protobuf";
+const NDJSON: &str = "application/x-ndjson";
+const YAML: &str = "application/yaml";
+
+/// Size of each chunk when streaming a response body
+const STREAM_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Largest request body the server reads; checked against Content-Length before anything is
+/// allocated, so unauthenticated clients cannot make it reserve memory
+const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Largest circuit the server will simulate (a 30-qubit state is 16 GiB)
+const MAX_QUBITS: usize = 30;
+
+/// Most shots one `/api/simulate` request may sample
+const MAX_SHOTS: usize = 1_000_000;
+
+/// Config files from the working directory that are bundled into workspace exports
+const WORKSPACE_CONFIGS: &[&str] = &["config.toml"];
+
+/// Circuit listing page sizes
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+/// Most simulations one /api/estimate call may run, counting the two per parameter a gradient needs
+const MAX_ESTIMATE_RUNS: usize = 10_000;
+
+/// Shift used for central-difference gradients in /api/estimate
+const GRADIENT_STEP: f64 = 1e-5;
+
+/// Parsed HTTP request
+pub struct ApiRequest {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl ApiRequest {
+    /// Header value by lowercase name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(|value| value.as_str())
+    }
+}
+
+/// Writes a response body incrementally instead of buffering it
+pub type StreamBody = Box<dyn FnOnce(&mut dyn Write) -> io::Result<()> + Send>;
+
+/// HTTP response
+pub struct ApiResponse {
+    pub status: u16,
+    pub content_type: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// When set, the body is sent with chunked transfer encoding and `body` is ignored
+    pub stream: Option<StreamBody>,
+}
+
+impl ApiResponse {
+    pub fn json(status: u16, value: Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json".to_string(),
+            headers: Vec::new(),
+            body: value.to_string().into_bytes(),
+            stream: None,
+        }
+    }
+
+    pub fn bytes(content_type: &str, body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            content_type: content_type.to_string(),
+            headers: Vec::new(),
+            body,
+            stream: None,
+        }
+    }
+
+    pub fn stream(content_type: &str, body: StreamBody) -> Self {
+        Self {
+            stream: Some(body),
+            ..Self::bytes(content_type, Vec::new())
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: impl ToString) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        Self::json(status, json!({ "error": message }))
+    }
+}
+
+#[derive(Clone)]
 pub struct ApiServer {
     port: u16,
-    circuits: Arc<Mutex<HashMap<String, crate::qsim::QuantumCircuit>>>,
+    circuits: Arc<Mutex<HashMap<String, StoredCircuit>>>,
+    results: Arc<Mutex<HashMap<String, StoredResult>>>,
+    noise_models: Arc<Mutex<BTreeMap<String, NoiseModel>>>,
+    /// Config file name -> contents, seeded from the working directory
+    configs: Arc<Mutex<BTreeMap<String, String>>>,
+    next_id: Arc<AtomicU64>,
+    jobs: JobQueue,
+    keys: KeyStore,
 }
 
 impl ApiServer {
-    pub fn new(port: u16) -> Self {
+    pub fn new(port: u16, jobs: JobQueue, keys: KeyStore) -> Self {
         Self {
             port,
+            jobs,
+            keys,
             circuits: Arc::new(Mutex::new(HashMap::new())),
+            results: Arc::new(Mutex::new(HashMap::new())),
+            noise_models: Arc::new(Mutex::new(BTreeMap::new())),
+            configs: Arc::new(Mutex::new(
+                WORKSPACE_CONFIGS
+                    .iter()
+                    .filter_map(|name| std::fs::read_to_string(name).ok().map(|text| (name.to_string(), text)))
+                    .collect(),
+            )),
+            next_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    /// Start with these named noise models, e.g. model files given to `serve --noise`
+    pub fn with_noise_models(self, models: BTreeMap<String, NoiseModel>) -> Self {
+        self.noise_models.lock().unwrap().extend(models);
+        self
+    }
+
+    /// Accept connections until the process is stopped
+    pub fn run(&self) -> io::Result<()> {
+        let listener = TcpListener::bind(("0.0.0.0", self.port))?;
+        self.jobs.start_worker();
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("Connection failed: {}", e);
+                    continue;
+                }
+            };
+            let server = self.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = server.serve_connection(stream) {
+                    eprintln!("Request failed: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn serve_connection(&self, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let response = match read_request(&mut reader) {
+            Ok(request) => self.handle(&request),
+            Err(e) if e.kind() == io::ErrorKind::FileTooLarge => ApiResponse::error(413, &e.to_string()),
+            Err(e) => ApiResponse::error(400, &e.to_string()),
+        };
+        write_response(stream, response)
+    }
+
+    /// Answer a request, stamping the response with the server's API version. Clients that
+    /// send the version they were built against are refused with an explanation when this
+    /// server cannot serve them; requests without it (curl, older clients) are served as-is.
+    pub fn handle(&self, request: &ApiRequest) -> ApiResponse {
+        let client = request.header(&api_version::HEADER.to_lowercase()).map(ApiVersion::parse);
+        let response = match client {
+            Some(Err(e)) => ApiResponse::error(400, &e),
+            Some(Ok(client)) => match API_VERSION.accepts(client) {
+                Ok(()) => self.route(request),
+                Err(e) => ApiResponse::error(400, &e),
+            },
+            None => self.route(request),
+        };
+        response.with_header(api_version::HEADER, API_VERSION)
+    }
+
+    /// Route a request to its endpoint handler once the caller's role allows it
+    fn route(&self, request: &ApiRequest) -> ApiResponse {
+        let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+        let caller = match self.authorize(request, &segments) {
+            Ok(caller) => caller,
+            Err(response) => return response,
+        };
+        match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["api", "health"]) => ApiResponse::json(200, json!({ "status": "ok" })),
+            ("GET", ["api", "version"]) => ApiResponse::json(200, json!({
+                "api_version": API_VERSION.to_string(),
+                "oldest_client": API_VERSION.oldest_client().to_string(),
+                "server_version": env!("CARGO_PKG_VERSION"),
+                "header": api_version::HEADER,
+            })),
+            ("GET", ["api", "node"]) => ApiResponse::json(200, json!(NodeReport::current(&self.jobs))),
+            ("GET", ["api", "cluster"]) => self.cluster(),
+            ("GET", ["api", "keys"]) => ApiResponse::json(200, json!({ "keys": self.keys.list() })),
+            ("POST", ["api", "keys"]) => self.create_key(request),
+            ("DELETE", ["api", "keys", name]) => match self.keys.revoke(name) {
+                Ok(true) => ApiResponse::json(200, json!({ "deleted": name })),
+                Ok(false) => ApiResponse::error(404, "key not found"),
+                Err(e) => ApiResponse::error(409, &e),
+            },
+            ("POST", ["api", "simulate"]) => self.simulate(request, &caller),
+            ("POST", ["api", "state"]) => self.state(request),
+            ("POST", ["api", "probabilities"]) => self.probabilities(request),
+            ("POST", ["api", "upload"]) => self.upload(request, &caller),
+            ("GET", ["api", "circuits"]) => self.list_circuits(request),
+            ("GET", ["api", "circuit", id]) => self.get_circuit(id),
+            ("DELETE", ["api", "circuit", id]) => self.delete_circuit(id, &caller),
+            ("POST", ["api", "circuit", id, "run"]) => self.run_stored(request, id, &caller),
+            ("PUT", ["api", "circuit", id, "tags"]) => self.tag_circuit(request, id, &caller),
+            ("GET", ["api", "result", id]) => self.get_result(id),
+            ("DELETE", ["api", "result", id]) => self.delete_result(id, &caller),
+            ("PUT", ["api", "result", id, "tags"]) => self.tag_result(request, id, &caller),
+            ("GET", ["api", "search"]) => self.search(request),
+            ("POST", ["api", "stats"]) => self.sample_test(request),
+            ("POST", ["api", "optimize"]) => self.optimize(request),
+            ("POST", ["api", "lint"]) => self.lint(request),
+            ("POST", ["api", "estimate"]) => self.estimate(request),
+            ("POST", ["api", "entropy"]) => self.entropy(request),
+            ("GET", ["api", "noise"]) => self.list_noise_models(),
+            ("GET", ["api", "noise", name]) => self.get_noise_model(request, name),
+            ("PUT", ["api", "noise", name]) => self.put_noise_model(request, name),
+            ("DELETE", ["api", "noise", name]) => self.delete_noise_model(name),
+            ("POST", ["api", "jobs"]) => self.submit_job(request, &caller),
+            ("GET", ["api", "jobs"]) => ApiResponse::json(200, json!({
+                "jobs": self.jobs.list().iter().map(|job| job.summary()).collect::<Vec<_>>(),
+            })),
+            ("GET", ["api", "job", id]) => match self.jobs.get(id) {
+                Some(job) => ApiResponse::json(200, json!(job)),
+                None => ApiResponse::error(404, "job not found"),
+            },
+            ("GET", ["api", "workspace"]) => self.export_workspace(),
+            ("POST", ["api", "workspace"]) => self.import_workspace(request, &caller),
+            _ => ApiResponse::error(404, "not found"),
+        }
+    }
+
+    /// Identify the caller and check their role against the endpoint's; which stored items
+    /// they may change is checked by the handlers
+    fn authorize(&self, request: &ApiRequest, segments: &[&str]) -> Result<Caller, ApiResponse> {
+        let required = match (request.method.as_str(), segments) {
+            ("GET", ["api", "health"]) | ("GET", ["api", "version"]) => {
+                return Ok(Caller { name: None, role: Role::ReadOnly })
+            }
+            // Keys, noise models and whole workspaces (which bundle config.toml) are server config
+            (_, ["api", "keys", ..])
+            | (_, ["api", "workspace"])
+            | ("PUT", ["api", "noise", _])
+            | ("DELETE", ["api", "noise", _]) => Role::Admin,
+            ("GET", _) => Role::ReadOnly,
+            _ => Role::User,
+        };
+        let caller = self
+            .keys
+            .authenticate(request.header("authorization"))
+            .map_err(|e| ApiResponse::error(401, &e))?;
+        if caller.role < required {
+            return Err(ApiResponse::error(403, &format!("this endpoint requires the {} role", required.name())));
+        }
+        Ok(caller)
+    }
+
+    /// Issue a key: `{"name": ..., "role": "admin" | "user" | "read-only"}`
+    fn create_key(&self, request: &ApiRequest) -> ApiResponse {
+        #[derive(serde::Deserialize)]
+        struct KeyRequest {
+            name: String,
+            role: Role,
+        }
+        let body: KeyRequest = match serde_json::from_slice(&request.body) {
+            Ok(body) => body,
+            Err(e) => return ApiResponse::error(400, &format!("invalid key request: {}", e)),
+        };
+        match self.keys.create(&body.name, body.role) {
+            Ok(key) => ApiResponse::json(201, json!(key)),
+            Err(e) => ApiResponse::error(409, &e),
+        }
+    }
+
+    fn simulate(&self, request: &ApiRequest, caller: &Caller) -> ApiResponse {
+        if request.header("content-type") != Some(PROTOBUF) {
+            if let Ok(value) = serde_json::from_slice::<Value>(&request.body) {
+                if qobj::is_qobj(&value) {
+                    return simulate_qobj(value);
+                }
+            }
+        }
+        let body = match parse_simulate_body(request) {
+            Ok(body) => body,
+            Err(response) => return response,
+        };
+        let seed = match query_seed(request) {
+            Ok(seed) => seed,
+            Err(response) => return response,
+        };
+        let noise = match self.simulate_noise(request, body.noise) {
+            Ok(noise) => noise,
+            Err(response) => return response,
+        };
+        let shots = match (body.shots, request.query.get("shots").map(|shots| shots.parse::<usize>())) {
+            (Some(shots), _) | (None, Some(Ok(shots))) => Some(shots),
+            (None, Some(Err(_))) => return ApiResponse::error(400, "'shots' must be a non-negative integer"),
+            (None, None) => None,
+        };
+        let ideal = body.ideal || matches!(request.query.get("ideal").map(|s| s.as_str()), Some("true") | Some("1"));
+        let circuit = body.circuit;
+        if let Some(shots) = shots {
+            return sample_counts(&circuit, noise, shots, ideal, seed);
+        }
+        if ideal {
+            return ApiResponse::error(400, "'ideal' compares counts; give 'shots'");
+        }
+        let probabilities = match noise {
+            Some(model) => {
+                let seed_value = seed.unwrap_or_else(rand::random);
+                match NoisyBackend::new(model).probabilities(&circuit, seed_value) {
+                    Ok(probabilities) => probabilities,
+                    Err(e) => return ApiResponse::error(400, &e),
+                }
+            }
+            None => run(&circuit, seed).measure_all(),
+        };
+
+        if request.header("accept") == Some(PROTOBUF) {
+            return ApiResponse::bytes(PROTOBUF, proto::encode_results(circuit.num_qubits, &probabilities));
+        }
+        let manifest = seeded_manifest(seed);
+        let mut response = json!({
+            "num_qubits": circuit.num_qubits,
+            "probabilities": probabilities,
+            "manifest": manifest,
+        });
+        // `?store=true` keeps the result in the catalog under the given name and tags
+        if matches!(request.query.get("store").map(|s| s.as_str()), Some("true") | Some("1")) {
+            let labels = self.labels(request, &circuit, caller);
+            response["result_id"] = json!(self.store_result(None, &circuit, probabilities, labels, manifest));
+        }
+        ApiResponse::json(200, response)
+    }
+
+    /// The noise model of a simulation: given inline or by name in the body, or by name in
+    /// `?noise=`
+    fn simulate_noise(&self, request: &ApiRequest, inline: Option<NoiseSpec>) -> Result<Option<NoiseModel>, ApiResponse> {
+        let name = match inline {
+            Some(NoiseSpec::Inline(model)) => {
+                model.validate().map_err(|e| ApiResponse::error(400, &format!("invalid noise model: {}", e)))?;
+                return Ok(Some(*model));
+            }
+            Some(NoiseSpec::Named(name)) => name,
+            None => match request.query.get("noise") {
+                Some(name) => name.clone(),
+                None => return Ok(None),
+            },
+        };
+        match self.noise_models.lock().unwrap().get(&name) {
+            Some(model) => Ok(Some(model.clone())),
+            None => Err(ApiResponse::error(404, &format!("noise model '{}' not found", name))),
+        }
+    }
+
+    /// Simulate a stored circuit and save the result, inheriting the circuit's tags
+    fn run_stored(&self, request: &ApiRequest, id: &str, caller: &Caller) -> ApiResponse {
+        let (circuit, labels) = match self.circuits.lock().unwrap().get(id) {
+            Some(stored) => {
+                let mut labels = self.labels(request, &stored.circuit, caller);
+                labels.add_tags(stored.labels.tags.iter().cloned());
+                if labels.name.is_none() {
+                    labels.name = stored.labels.name.clone();
+                }
+                (stored.circuit.clone(), labels)
+            }
+            None => return ApiResponse::error(404, "circuit not found"),
+        };
+        let seed = match query_seed(request) {
+            Ok(seed) => seed,
+            Err(response) => return response,
+        };
+        let probabilities = run(&circuit, seed).measure_all();
+        let manifest = seeded_manifest(seed);
+        let result_id = self.store_result(Some(id.to_string()), &circuit, probabilities.clone(), labels, manifest.clone());
+        ApiResponse::json(201, json!({
+            "result_id": result_id,
+            "num_qubits": circuit.num_qubits,
+            "probabilities": probabilities,
+            "manifest": manifest,
+        }))
+    }
+
+    /// This node plus the workers in the `[distributed]` section of the server's config.toml
+    fn cluster(&self) -> ApiResponse {
+        let config = match self.configs.lock().unwrap().get("config.toml") {
+            Some(text) => DistributedConfig::parse(text),
+            None => Ok(DistributedConfig::default()),
+        };
+        match config {
+            Ok(config) => ApiResponse::json(200, json!(ClusterStatus::collect(&config, NodeReport::current(&self.jobs)))),
+            Err(e) => ApiResponse::error(500, &e),
+        }
+    }
+
+    /// Queue a circuit to run in the background; poll `GET /api/job/:id` for its result
+    fn submit_job(&self, request: &ApiRequest, caller: &Caller) -> ApiResponse {
+        let circuit = match parse_circuit_body(request) {
+            Ok(circuit) => circuit,
+            Err(response) => return response,
+        };
+        match self.jobs.submit(circuit, caller.name.clone()) {
+            Ok(job) => ApiResponse::json(202, job.summary()).with_header("Location", format!("/api/job/{}", job.id)),
+            Err(e) => ApiResponse::error(500, &format!("could not queue job: {}", e)),
+        }
+    }
+
+    /// Name and tags for a new catalog entry: query string first, then circuit metadata
+    fn labels(&self, request: &ApiRequest, circuit: &QuantumCircuit, caller: &Caller) -> Labels {
+        let name = request.query.get("name").or(circuit.metadata.get("name")).cloned();
+        let tags = request
+            .query
+            .get("tags")
+            .or(circuit.metadata.get("tags"))
+            .map(|tags| catalog::split_tags(tags))
+            .unwrap_or_default();
+        let mut labels = Labels::new(name, tags, self.next_id.fetch_add(1, Ordering::SeqCst));
+        labels.owner = caller.name.clone();
+        labels
+    }
+
+    fn store_result(
+        &self,
+        circuit_id: Option<String>,
+        circuit: &QuantumCircuit,
+        probabilities: Vec<f64>,
+        labels: Labels,
+        manifest: Manifest,
+    ) -> String {
+        let id = format!("result-{}", labels.sequence);
+        let stored = StoredResult {
+            circuit_id,
+            num_qubits: circuit.num_qubits,
+            probabilities,
+            labels,
+            manifest: Some(manifest),
+        };
+        self.results.lock().unwrap().insert(id.clone(), stored);
+        id
+    }
+
+    /// Final state vector as a complex128 `.npy` file
+    fn state(&self, request: &ApiRequest) -> ApiResponse {
+        let circuit = match parse_circuit_body(request) {
+            Ok(circuit) => circuit,
+            Err(response) => return response,
+        };
+        let seed = match query_seed(request) {
+            Ok(seed) => seed,
+            Err(response) => return response,
+        };
+        ApiResponse::bytes("application/octet-stream", npy::encode_complex(&run(&circuit, seed).get_state()))
+    }
+
+    /// Stream probabilities for a basis-state range as NDJSON or raw little-endian f64s.
+    ///
+    /// Query: `start`/`end` (end exclusive, default the whole register),
+    /// `format=ndjson|binary` and, for NDJSON, `min_probability` to skip small entries.
+    fn probabilities(&self, request: &ApiRequest) -> ApiResponse {
+        let circuit = match parse_circuit_body(request) {
+            Ok(circuit) => circuit,
+            Err(response) => return response,
+        };
+        let size = 1usize << circuit.num_qubits;
+        let number = |name: &str, default: usize| match request.query.get(name) {
+            Some(value) => value.parse::<usize>().map_err(|_| format!("'{}' must be a basis-state index", name)),
+            None => Ok(default),
+        };
+        let (start, end) = match (number("start", 0), number("end", size)) {
+            (Ok(start), Ok(end)) if start <= end && end <= size => (start, end),
+            (Err(e), _) | (_, Err(e)) => return ApiResponse::error(400, &e),
+            _ => return ApiResponse::error(400, &format!("range must satisfy start <= end <= {}", size)),
+        };
+        let min_probability = match request.query.get("min_probability").map(|v| v.parse::<f64>()) {
+            Some(Ok(value)) => value,
+            Some(Err(_)) => return ApiResponse::error(400, "'min_probability' must be a number"),
+            None => 0.0,
+        };
+        let binary = match request.query.get("format").map(|f| f.as_str()) {
+            None | Some("ndjson") => false,
+            Some("binary") => true,
+            Some(other) => return ApiResponse::error(400, &format!("unknown format '{}'", other)),
+        };
+        let seed = match query_seed(request) {
+            Ok(seed) => seed,
+            Err(response) => return response,
+        };
+
+        let simulator = run(&circuit, seed);
+        let num_qubits = circuit.num_qubits;
+        let body: StreamBody = Box::new(move |out: &mut dyn Write| {
+            let state = simulator.get_state();
+            let amplitudes = &state[start..end];
+            for (offset, amplitude) in amplitudes.iter().enumerate() {
+                let probability = amplitude.magnitude_squared();
+                if binary {
+                    out.write_all(&probability.to_le_bytes())?;
+                } else if probability >= min_probability {
+                    let index = start + offset;
+                    writeln!(
+                        out,
+                        "{{\"index\":{},\"state\":\"{:0width$b}\",\"probability\":{}}}",
+                        index,
+                        index,
+                        probability,
+                        width = num_qubits
+                    )?;
+                }
+            }
+            Ok(())
+        });
+
+        let content_type = if binary { "application/octet-stream" } else { NDJSON };
+        ApiResponse::stream(content_type, body)
+            .with_header("X-Num-Qubits", num_qubits)
+            .with_header("X-Range-Start", start)
+            .with_header("X-Range-End", end)
+    }
+
+    fn upload(&self, request: &ApiRequest, caller: &Caller) -> ApiResponse {
+        let circuit = match parse_circuit_body(request) {
+            Ok(circuit) => circuit,
+            Err(response) => return response,
+        };
+        let labels = self.labels(request, &circuit, caller);
+        let id = format!("circuit-{}", labels.sequence);
+        let stored = StoredCircuit { circuit, labels };
+        self.circuits.lock().unwrap().insert(id.clone(), stored);
+        ApiResponse::json(201, json!({ "id": id }))
+    }
+
+    /// Paginated circuit summaries.
+    ///
+    /// Query: `page` (from 1), `per_page` (default 50, max 500), the search filters
+    /// (`name`, `tag`, `min_qubits`, `max_qubits`), `sort=created|size` and `order=asc|desc`.
+    fn list_circuits(&self, request: &ApiRequest) -> ApiResponse {
+        let number = |name: &'static str, default: usize| match request.query.get(name) {
+            Some(value) => value.parse::<usize>().ok().filter(|n| *n > 0).ok_or(name),
+            None => Ok(default),
+        };
+        let (page, per_page) = match (number("page", 1), number("per_page", DEFAULT_PAGE_SIZE)) {
+            (Ok(page), Ok(per_page)) => (page, per_page.min(MAX_PAGE_SIZE)),
+            (Err(name), _) | (_, Err(name)) => {
+                return ApiResponse::error(400, &format!("'{}' must be a positive integer", name));
+            }
+        };
+        let descending = match request.query.get("order").map(|o| o.as_str()) {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(other) => return ApiResponse::error(400, &format!("unknown order '{}'", other)),
+        };
+        let by_size = match request.query.get("sort").map(|s| s.as_str()) {
+            None | Some("created") => false,
+            Some("size") => true,
+            Some(other) => return ApiResponse::error(400, &format!("unknown sort key '{}'", other)),
+        };
+        let filter = match SearchFilter::from_query(&request.query) {
+            Ok(filter) => filter,
+            Err(e) => return ApiResponse::error(400, &e),
+        };
+
+        let circuits = self.circuits.lock().unwrap();
+        let mut matches: Vec<(&String, &StoredCircuit)> = circuits
+            .iter()
+            .filter(|(_, stored)| filter.matches(&stored.labels, stored.circuit.num_qubits))
+            .collect();
+
+        // Size orders by register width, then gate count
+        matches.sort_by_key(|(_, stored)| {
+            let size = if by_size { (stored.circuit.num_qubits, stored.circuit.gates.len()) } else { (0, 0) };
+            (size, stored.labels.created_at, stored.labels.sequence)
+        });
+        if descending {
+            matches.reverse();
+        }
+
+        let total = matches.len();
+        let summaries: Vec<Value> = matches
+            .into_iter()
+            .skip((page - 1).saturating_mul(per_page))
+            .take(per_page)
+            .map(|(id, stored)| stored.summary(id))
+            .collect();
+        ApiResponse::json(200, json!({
+            "circuits": summaries,
+            "page": page,
+            "per_page": per_page,
+            "total": total,
+        }))
+    }
+
+    fn get_circuit(&self, id: &str) -> ApiResponse {
+        match self.circuits.lock().unwrap().get(id) {
+            Some(stored) => ApiResponse::json(200, json!(stored.circuit)),
+            None => ApiResponse::error(404, "circuit not found"),
         }
     }
+
+    fn delete_circuit(&self, id: &str, caller: &Caller) -> ApiResponse {
+        let mut circuits = self.circuits.lock().unwrap();
+        match circuits.get(id) {
+            Some(stored) if !caller.may_modify(stored.labels.owner.as_deref()) => not_owner("circuit"),
+            Some(_) => {
+                circuits.remove(id);
+                ApiResponse::json(200, json!({ "deleted": id }))
+            }
+            None => ApiResponse::error(404, "circuit not found"),
+        }
+    }
+
+    fn get_result(&self, id: &str) -> ApiResponse {
+        match self.results.lock().unwrap().get(id) {
+            Some(stored) => ApiResponse::json(200, stored.to_json(id)),
+            None => ApiResponse::error(404, "result not found"),
+        }
+    }
+
+    fn delete_result(&self, id: &str, caller: &Caller) -> ApiResponse {
+        let mut results = self.results.lock().unwrap();
+        match results.get(id) {
+            Some(stored) if !caller.may_modify(stored.labels.owner.as_deref()) => not_owner("result"),
+            Some(_) => {
+                results.remove(id);
+                ApiResponse::json(200, json!({ "deleted": id }))
+            }
+            None => ApiResponse::error(404, "result not found"),
+        }
+    }
+
+    fn tag_circuit(&self, request: &ApiRequest, id: &str, caller: &Caller) -> ApiResponse {
+        match self.circuits.lock().unwrap().get_mut(id) {
+            Some(stored) if !caller.may_modify(stored.labels.owner.as_deref()) => not_owner("circuit"),
+            Some(stored) => update_tags(request, &mut stored.labels),
+            None => ApiResponse::error(404, "circuit not found"),
+        }
+    }
+
+    fn tag_result(&self, request: &ApiRequest, id: &str, caller: &Caller) -> ApiResponse {
+        match self.results.lock().unwrap().get_mut(id) {
+            Some(stored) if !caller.may_modify(stored.labels.owner.as_deref()) => not_owner("result"),
+            Some(stored) => update_tags(request, &mut stored.labels),
+            None => ApiResponse::error(404, "result not found"),
+        }
+    }
+
+    /// Circuits and results matching the search filters, oldest first.
+    /// `type=circuit|result` restricts the search to one kind.
+    fn search(&self, request: &ApiRequest) -> ApiResponse {
+        let filter = match SearchFilter::from_query(&request.query) {
+            Ok(filter) => filter,
+            Err(e) => return ApiResponse::error(400, &e),
+        };
+        let (circuits, results) = match request.query.get("type").map(|t| t.as_str()) {
+            None => (true, true),
+            Some("circuit") => (true, false),
+            Some("result") => (false, true),
+            Some(other) => return ApiResponse::error(400, &format!("unknown type '{}'", other)),
+        };
+
+        let mut response = json!({});
+        if circuits {
+            let store = self.circuits.lock().unwrap();
+            let mut found: Vec<(&String, &StoredCircuit)> = store
+                .iter()
+                .filter(|(_, stored)| filter.matches(&stored.labels, stored.circuit.num_qubits))
+                .collect();
+            found.sort_by_key(|(_, stored)| stored.labels.sequence);
+            response["circuits"] = json!(found.iter().map(|(id, stored)| stored.summary(id)).collect::<Vec<_>>());
+        }
+        if results {
+            let store = self.results.lock().unwrap();
+            let mut found: Vec<(&String, &StoredResult)> = store
+                .iter()
+                .filter(|(_, stored)| filter.matches(&stored.labels, stored.num_qubits))
+                .collect();
+            found.sort_by_key(|(_, stored)| stored.labels.sequence);
+            response["results"] = json!(found.iter().map(|(id, stored)| stored.summary(id)).collect::<Vec<_>>());
+        }
+        ApiResponse::json(200, response)
+    }
+
+    /// Optimize a bare circuit, or a `{"circuit", "noise", "rules"}` envelope
+    fn optimize(&self, request: &ApiRequest) -> ApiResponse {
+        #[derive(serde::Deserialize)]
+        struct OptimizeRequest {
+            circuit: QuantumCircuit,
+            noise: Option<NoiseModel>,
+            #[serde(default)]
+            rules: Vec<RewriteRule>,
+        }
+
+        let envelope = serde_json::from_slice::<Value>(&request.body)
+            .ok()
+            .filter(|value| value.get("circuit").is_some());
+        let (circuit, inline_noise, rules) = match envelope {
+            Some(value) => match serde_json::from_value::<OptimizeRequest>(value) {
+                Ok(body) if body.circuit.num_qubits > MAX_QUBITS => {
+                    return ApiResponse::error(400, &format!("circuits are limited to {} qubits", MAX_QUBITS));
+                }
+                Ok(body) => match qsim::validate_circuit(&body.circuit) {
+                    Ok(()) => (body.circuit, body.noise, body.rules),
+                    Err(e) => return ApiResponse::error(400, &format!("invalid circuit: {}", e)),
+                },
+                Err(e) => return ApiResponse::error(400, &format!("invalid optimize request: {}", e)),
+            },
+            None => match parse_circuit_body(request) {
+                Ok(circuit) => (circuit, None, Vec::new()),
+                Err(response) => return response,
+            },
+        };
+        let objective = match request.query.get("objective") {
+            Some(name) => match optimizer::Objective::parse(name) {
+                Some(objective) => objective,
+                None => return ApiResponse::error(400, &format!("unknown objective '{}'", name)),
+            },
+            None => optimizer::Objective::GateCount,
+        };
+        let noise = match (inline_noise, request.query.get("noise")) {
+            (Some(model), _) => match model.validate() {
+                Ok(()) => model,
+                Err(e) => return ApiResponse::error(400, &format!("invalid noise model: {}", e)),
+            },
+            (None, Some(name)) => match self.noise_models.lock().unwrap().get(name) {
+                Some(model) => model.clone(),
+                None => return ApiResponse::error(404, "noise model not found"),
+            },
+            (None, None) => NoiseModel::default(),
+        };
+        let optimized = optimizer::optimize_for(circuit, objective, &noise, &rules);
+        ApiResponse::json(200, json!(optimized))
+    }
+
+    /// Validator errors and heuristic warnings for a circuit, which need not be valid;
+    /// `?max_depth=` sets the depth warning threshold
+    fn lint(&self, request: &ApiRequest) -> ApiResponse {
+        let parsed = if request.header("content-type") == Some(PROTOBUF) {
+            proto::decode_circuit_unchecked(&request.body)
+        } else {
+            std::str::from_utf8(&request.body)
+                .map_err(|e| e.into())
+                .and_then(|body| qsim::parse_circuit_unchecked(body, qsim::CircuitFormat::Json))
+        };
+        let circuit = match parsed {
+            Ok(circuit) => circuit,
+            Err(e) => return ApiResponse::error(400, &format!("invalid circuit: {}", e)),
+        };
+        let max_depth = match request.query.get("max_depth").map(|limit| limit.parse::<usize>()) {
+            Some(Ok(limit)) => limit,
+            Some(Err(_)) => return ApiResponse::error(400, "max_depth must be a non-negative integer"),
+            None => lint::DEFAULT_MAX_DEPTH,
+        };
+        // Per-qubit bookkeeping is skipped for circuits too wide to run anyway
+        let diagnostics = if circuit.num_qubits > MAX_QUBITS {
+            vec![lint::Diagnostic::too_large(MAX_QUBITS)]
+        } else {
+            lint::lint(&circuit, max_depth)
+        };
+        let errors = diagnostics.iter().filter(|d| d.severity == lint::Severity::Error).count();
+        ApiResponse::json(200, json!({
+            "valid": errors == 0,
+            "errors": errors,
+            "warnings": diagnostics.len() - errors,
+            "diagnostics": diagnostics,
+        }))
+    }
+
+    /// ⟨H⟩ of a parameterized circuit at each parameter vector in a batch, optionally with
+    /// central-difference gradients. Vectors are lists in `parameter_names` order (sorted)
+    /// or maps from name to value, where names left out keep their defaults.
+    fn estimate(&self, request: &ApiRequest) -> ApiResponse {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum ParameterVector {
+            Values(Vec<f64>),
+            Named(BTreeMap<String, f64>),
+        }
+
+        #[derive(serde::Deserialize)]
+        struct EstimateRequest {
+            circuit: Value,
+            observable: PauliSum,
+            parameters: Vec<ParameterVector>,
+            #[serde(default)]
+            gradient: bool,
+        }
+
+        let body: EstimateRequest = match serde_json::from_slice(&request.body) {
+            Ok(body) => body,
+            Err(e) => return ApiResponse::error(400, &format!("invalid estimate request: {}", e)),
+        };
+        let defaults = match params::defaults(&body.circuit) {
+            Ok(defaults) => defaults,
+            Err(e) => return ApiResponse::error(400, &format!("invalid circuit: {}", e)),
+        };
+        let names: Vec<String> = defaults.keys().cloned().collect();
+        let runs = body.parameters.len() * if body.gradient { 2 * names.len() + 1 } else { 1 };
+        if runs > MAX_ESTIMATE_RUNS {
+            return ApiResponse::error(400, &format!(
+                "this batch needs {} simulations; the limit is {} per request",
+                runs, MAX_ESTIMATE_RUNS
+            ));
+        }
+
+        let mut points = Vec::with_capacity(body.parameters.len());
+        for (index, vector) in body.parameters.into_iter().enumerate() {
+            let point = match vector {
+                ParameterVector::Values(values) if values.len() == names.len() => names.iter().cloned().zip(values).collect(),
+                ParameterVector::Values(values) => {
+                    return ApiResponse::error(400, &format!(
+                        "parameter vector {} has {} values, but the circuit has {} parameters",
+                        index,
+                        values.len(),
+                        names.len()
+                    ));
+                }
+                ParameterVector::Named(named) => {
+                    let mut point = defaults.clone();
+                    for (name, value) in named {
+                        if !point.contains_key(&name) {
+                            return ApiResponse::error(400, &format!("circuit has no parameter '{}'", name));
+                        }
+                        point.insert(name, value);
+                    }
+                    point
+                }
+            };
+            points.push(point);
+        }
+
+        // Evaluate ⟨H⟩ at one point, checking the circuit it binds to
+        let evaluate = |point: &BTreeMap<String, f64>| -> Result<f64, String> {
+            let circuit = params::instantiate(&body.circuit, point).map_err(|e| format!("invalid circuit: {}", e))?;
+            qsim::validate_circuit(&circuit).map_err(|e| format!("invalid circuit: {}", e))?;
+            if circuit.num_qubits > MAX_QUBITS {
+                return Err(format!("circuits are limited to {} qubits", MAX_QUBITS));
+            }
+            if circuit.has_collapse() {
+                return Err("the circuit collapses qubits mid-circuit, so a single run has no fixed expectation value".to_string());
+            }
+            body.observable.validate(circuit.num_qubits)?;
+            Ok(run(&circuit, None).expectation(&body.observable))
+        };
+
+        let mut values = Vec::with_capacity(points.len());
+        let mut gradients = Vec::new();
+        for point in &points {
+            match evaluate(point) {
+                Ok(value) => values.push(value),
+                Err(e) => return ApiResponse::error(400, &e),
+            }
+            if !body.gradient {
+                continue;
+            }
+            let mut gradient = Vec::with_capacity(names.len());
+            for name in &names {
+                let mut shifted = point.clone();
+                let center = point[name];
+                shifted.insert(name.clone(), center + GRADIENT_STEP);
+                let forward = evaluate(&shifted);
+                shifted.insert(name.clone(), center - GRADIENT_STEP);
+                let backward = evaluate(&shifted);
+                match (forward, backward) {
+                    (Ok(forward), Ok(backward)) => gradient.push((forward - backward) / (2.0 * GRADIENT_STEP)),
+                    (Err(e), _) | (_, Err(e)) => return ApiResponse::error(400, &e),
+                }
+            }
+            gradients.push(gradient);
+        }
+
+        let mut response = json!({
+            "parameter_names": names,
+            "observable": body.observable.to_string(),
+            "values": values,
+        });
+        if body.gradient {
+            response["gradients"] = json!(gradients);
+        }
+        ApiResponse::json(200, response)
+    }
+
+    /// Entanglement entropies of the final state across each requested cut, one entropy per
+    /// Rényi order, with order 1 the von Neumann entropy. A circuit that collapses qubits
+    /// mid-circuit is run once, so the answer is for that one sampled trajectory.
+    fn entropy(&self, request: &ApiRequest) -> ApiResponse {
+        #[derive(serde::Deserialize)]
+        struct EntropyRequest {
+            circuit: Value,
+            subsystems: Vec<Vec<usize>>,
+            #[serde(default = "default_orders")]
+            orders: Vec<f64>,
+        }
+
+        fn default_orders() -> Vec<f64> {
+            vec![1.0]
+        }
+
+        let body: EntropyRequest = match serde_json::from_slice(&request.body) {
+            Ok(body) => body,
+            Err(e) => return ApiResponse::error(400, &format!("invalid entropy request: {}", e)),
+        };
+        if let Some(order) = body.orders.iter().find(|order| !(order.is_finite() && **order >= 0.0)) {
+            return ApiResponse::error(400, &format!("Rényi order {} must be a non-negative number", order));
+        }
+        let circuit = match qsim::parse_circuit(&body.circuit.to_string(), qsim::CircuitFormat::Json) {
+            Ok(circuit) => circuit,
+            Err(e) => return ApiResponse::error(400, &format!("invalid circuit: {}", e)),
+        };
+        if circuit.num_qubits > MAX_QUBITS {
+            return ApiResponse::error(400, &format!("circuits are limited to {} qubits", MAX_QUBITS));
+        }
+
+        let seed = match query_seed(request) {
+            Ok(seed) => seed,
+            Err(response) => return response,
+        };
+        let simulator = run(&circuit, seed);
+        let mut cuts = Vec::with_capacity(body.subsystems.len());
+        for (index, subsystem) in body.subsystems.iter().enumerate() {
+            let spectrum = match entropy::entanglement_spectrum(&simulator, subsystem) {
+                Ok(spectrum) => spectrum,
+                Err(e) => return ApiResponse::error(400, &format!("subsystem {}: {}", index, e)),
+            };
+            cuts.push(json!({
+                "subsystem": subsystem,
+                "entropies": body.orders.iter().map(|&order| entropy::renyi_entropy(&spectrum, order)).collect::<Vec<_>>(),
+                "schmidt_rank": entropy::schmidt_rank(&spectrum),
+            }));
+        }
+        ApiResponse::json(200, json!({ "orders": body.orders, "cuts": cuts }))
+    }
+
+    /// χ² and linear XEB of sampled counts against an ideal distribution
+    ///
+    /// `ideal` is a result id or a probability array. Samples are either `counts`
+    /// keyed by bitstring (qubit 0 rightmost) or `0x` hex, or a `sampled` result id
+    /// whose probabilities are observed frequencies over `shots` shots.
+    fn sample_test(&self, request: &ApiRequest) -> ApiResponse {
+        #[derive(serde::Deserialize)]
+        struct SampleTestRequest {
+            ideal: Value,
+            sampled: Option<String>,
+            shots: Option<usize>,
+            counts: Option<BTreeMap<String, usize>>,
+        }
+
+        let body: SampleTestRequest = match serde_json::from_slice(&request.body) {
+            Ok(body) => body,
+            Err(e) => return ApiResponse::error(400, &format!("invalid stats request: {}", e)),
+        };
+        let results = self.results.lock().unwrap();
+        let ideal: Vec<f64> = match &body.ideal {
+            Value::String(id) => match results.get(id) {
+                Some(stored) => stored.probabilities.clone(),
+                None => return ApiResponse::error(404, "ideal result not found"),
+            },
+            value => match serde_json::from_value(value.clone()) {
+                Ok(probabilities) => probabilities,
+                Err(_) => return ApiResponse::error(400, "'ideal' must be a result id or a probability array"),
+            },
+        };
+
+        let counts = match (body.counts, body.sampled, body.shots) {
+            (Some(counts), None, _) => {
+                let mut parsed = BTreeMap::new();
+                for (key, count) in counts {
+                    let state = match key.strip_prefix("0x") {
+                        Some(hex) => usize::from_str_radix(hex, 16),
+                        None => usize::from_str_radix(&key, 2),
+                    };
+                    match state {
+                        Ok(state) => *parsed.entry(state).or_default() += count,
+                        Err(_) => return ApiResponse::error(400, &format!("invalid bitstring '{}'", key)),
+                    }
+                }
+                parsed
+            }
+            (None, Some(id), Some(shots)) => match results.get(&id) {
+                Some(stored) => stored
+                    .probabilities
+                    .iter()
+                    .enumerate()
+                    .map(|(state, p)| (state, (p * shots as f64).round() as usize))
+                    .filter(|&(_, count)| count > 0)
+                    .collect(),
+                None => return ApiResponse::error(404, "sampled result not found"),
+            },
+            (None, Some(_), None) => return ApiResponse::error(400, "'shots' is required with 'sampled'"),
+            _ => return ApiResponse::error(400, "give exactly one of 'counts' or 'sampled'"),
+        };
+        drop(results);
+
+        match stats::evaluate(&ideal, &counts) {
+            Ok(test) => ApiResponse::json(200, json!(test)),
+            Err(e) => ApiResponse::error(400, &e),
+        }
+    }
+
+    fn list_noise_models(&self) -> ApiResponse {
+        let names: Vec<String> = self.noise_models.lock().unwrap().keys().cloned().collect();
+        ApiResponse::json(200, json!({ "noise_models": names }))
+    }
+
+    /// A named noise model as JSON, or as a YAML model file with `?format=yaml`
+    fn get_noise_model(&self, request: &ApiRequest, name: &str) -> ApiResponse {
+        let model = match self.noise_models.lock().unwrap().get(name) {
+            Some(model) => model.clone(),
+            None => return ApiResponse::error(404, "noise model not found"),
+        };
+        match request.query.get("format").map(String::as_str) {
+            None | Some("json") => ApiResponse::json(200, json!(model)),
+            Some("yaml") => match serde_yaml::to_string(&model) {
+                Ok(text) => ApiResponse::bytes(YAML, text.into_bytes()),
+                Err(e) => ApiResponse::error(500, &e.to_string()),
+            },
+            Some(other) => ApiResponse::error(400, &format!("unknown format '{}'; use json or yaml", other)),
+        }
+    }
+
+    /// Create or replace a named noise model from a model file, read as YAML when the
+    /// content type says so and as JSON otherwise
+    fn put_noise_model(&self, request: &ApiRequest, name: &str) -> ApiResponse {
+        let yaml = request.header("content-type").is_some_and(|content_type| content_type.contains("yaml"));
+        let parsed = std::str::from_utf8(&request.body)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| noise::parse_noise_model(contents, yaml));
+        let model = match parsed {
+            Ok(model) => model,
+            Err(e) => return ApiResponse::error(400, &format!("invalid noise model: {}", e)),
+        };
+        self.noise_models.lock().unwrap().insert(name.to_string(), model);
+        ApiResponse::json(200, json!({ "name": name }))
+    }
+
+    fn delete_noise_model(&self, name: &str) -> ApiResponse {
+        match self.noise_models.lock().unwrap().remove(name) {
+            Some(_) => ApiResponse::json(200, json!({ "deleted": name })),
+            None => ApiResponse::error(404, "noise model not found"),
+        }
+    }
+
+    /// Bundle every stored item into a single workspace archive
+    fn export_workspace(&self) -> ApiResponse {
+        let mut workspace = Workspace::new();
+
+        let circuits = self.circuits.lock().unwrap();
+        let mut stored_circuits: Vec<_> = circuits.iter().collect();
+        stored_circuits.sort_by_key(|(_, stored)| stored.labels.sequence);
+        workspace.circuits = stored_circuits
+            .into_iter()
+            .map(|(id, stored)| CircuitEntry {
+                id: id.clone(),
+                name: stored.labels.name.clone(),
+                tags: stored.labels.tags.clone(),
+                created_at: stored.labels.created_at,
+                circuit: stored.circuit.clone(),
+            })
+            .collect();
+        drop(circuits);
+
+        let results = self.results.lock().unwrap();
+        let mut stored_results: Vec<_> = results.iter().collect();
+        stored_results.sort_by_key(|(_, stored)| stored.labels.sequence);
+        workspace.results = stored_results
+            .into_iter()
+            .map(|(id, stored)| ResultEntry {
+                id: id.clone(),
+                circuit_id: stored.circuit_id.clone(),
+                name: stored.labels.name.clone(),
+                tags: stored.labels.tags.clone(),
+                created_at: stored.labels.created_at,
+                num_qubits: stored.num_qubits,
+                probabilities: stored.probabilities.clone(),
+                manifest: stored.manifest.clone(),
+            })
+            .collect();
+        drop(results);
+
+        workspace.noise_models = self.noise_models.lock().unwrap().clone();
+        workspace.configs = self.configs.lock().unwrap().clone();
+        ApiResponse::json(200, json!(workspace))
+    }
+
+    /// Merge a workspace archive into this server under fresh ids
+    ///
+    /// Noise models and configs replace same-named entries. The response maps
+    /// archived circuit and result ids to their new ids.
+    fn import_workspace(&self, request: &ApiRequest, caller: &Caller) -> ApiResponse {
+        let workspace: Workspace = match serde_json::from_slice(&request.body) {
+            Ok(workspace) => workspace,
+            Err(e) => return ApiResponse::error(400, &format!("invalid workspace: {}", e)),
+        };
+        if let Err(e) = workspace.validate() {
+            return ApiResponse::error(400, &e);
+        }
+        if let Some(entry) = workspace.circuits.iter().find(|entry| entry.circuit.num_qubits > MAX_QUBITS) {
+            return ApiResponse::error(400, &format!("circuit '{}' exceeds the {} qubit limit", entry.id, MAX_QUBITS));
+        }
+        for entry in &workspace.circuits {
+            if let Err(e) = qsim::validate_circuit(&entry.circuit) {
+                return ApiResponse::error(400, &format!("invalid circuit '{}': {}", entry.id, e));
+            }
+        }
+        if let Some(entry) = workspace.results.iter().find(|entry| {
+            entry.num_qubits > MAX_QUBITS || entry.probabilities.len() != 1 << entry.num_qubits
+        }) {
+            return ApiResponse::error(400, &format!("result '{}' has the wrong number of probabilities", entry.id));
+        }
+
+        let mut circuit_ids = BTreeMap::new();
+        let mut circuits = self.circuits.lock().unwrap();
+        for entry in workspace.circuits {
+            let mut labels = Labels::new(entry.name, entry.tags, self.next_id.fetch_add(1, Ordering::SeqCst));
+            labels.created_at = entry.created_at;
+            labels.owner = caller.name.clone();
+            let id = format!("circuit-{}", labels.sequence);
+            circuits.insert(id.clone(), StoredCircuit { circuit: entry.circuit, labels });
+            circuit_ids.insert(entry.id, id);
+        }
+        drop(circuits);
+
+        let mut result_ids = BTreeMap::new();
+        let mut results = self.results.lock().unwrap();
+        for entry in workspace.results {
+            let mut labels = Labels::new(entry.name, entry.tags, self.next_id.fetch_add(1, Ordering::SeqCst));
+            labels.created_at = entry.created_at;
+            labels.owner = caller.name.clone();
+            let id = format!("result-{}", labels.sequence);
+            let stored = StoredResult {
+                // Results whose circuit was not archived keep no link
+                circuit_id: entry.circuit_id.and_then(|old| circuit_ids.get(&old).cloned()),
+                num_qubits: entry.num_qubits,
+                probabilities: entry.probabilities,
+                labels,
+                manifest: entry.manifest,
+            };
+            results.insert(id.clone(), stored);
+            result_ids.insert(entry.id, id);
+        }
+        drop(results);
+
+        let noise_models: Vec<String> = workspace.noise_models.keys().cloned().collect();
+        self.noise_models.lock().unwrap().extend(workspace.noise_models);
+        let configs: Vec<String> = workspace.configs.keys().cloned().collect();
+        self.configs.lock().unwrap().extend(workspace.configs);
+
+        ApiResponse::json(201, json!({
+            "circuits": circuit_ids,
+            "results": result_ids,
+            "noise_models": noise_models,
+            "configs": configs,
+        }))
+    }
 }
 
-pub fn start_server(port: u16) {
-    println!("\u250c\u2500 Starting QuantumMesh API server on port {}", port);
-    println!("\u251c\u2500 Available endpoints:");
-    println!("\u2502  POST   /api/simulate       - Simulate quantum circuit");
-    println!("\u2502  POST   /api/upload         - Upload circuit definition");
-    println!("\u2502  GET    /api/circuits       - List all circuits");
-    println!("\u2502  GET    /api/circuit/:id    - Get specific circuit");
-    println!("\u2502  DELETE /api/circuit/:id    - Delete circuit");
-    println!("\u2502  POST   /api/optimize       - Optimize circuit");
-    println!("\u2502  GET    /api/health         - Health check");
-    println!("\u2514\u2500 Server ready at http://localhost:{}", port);
-    
-    // Simulate server running
-    println!("\nPress Ctrl+C to stop the server");
+fn not_owner(kind: &str) -> ApiResponse {
+    ApiResponse::error(403, &format!("only the {}'s owner or an admin may change it", kind))
+}
+
+/// Run an IBM Qobj payload and answer in Qiskit's result schema
+fn simulate_qobj(value: Value) -> ApiResponse {
+    let job: qobj::Qobj = match serde_json::from_value(value) {
+        Ok(job) => job,
+        Err(e) => return ApiResponse::error(400, &format!("invalid Qobj: {}", e)),
+    };
+    let result = match job.config.seed_simulator {
+        Some(seed) => qobj::run_qobj(&job, MAX_QUBITS, &mut StdRng::seed_from_u64(seed)),
+        None => qobj::run_qobj(&job, MAX_QUBITS, &mut rand::thread_rng()),
+    };
+    match result {
+        Ok(result) => ApiResponse::json(200, result),
+        Err(e) => ApiResponse::error(400, &e),
+    }
+}
+
+/// Apply a `{"tags": [...]}` (replace) or `{"add": [...], "remove": [...]}` body
+fn update_tags(request: &ApiRequest, labels: &mut Labels) -> ApiResponse {
+    #[derive(serde::Deserialize)]
+    struct TagUpdate {
+        tags: Option<Vec<String>>,
+        #[serde(default)]
+        add: Vec<String>,
+        #[serde(default)]
+        remove: Vec<String>,
+    }
+
+    let update: TagUpdate = match serde_json::from_slice(&request.body) {
+        Ok(update) => update,
+        Err(e) => return ApiResponse::error(400, &format!("invalid tag update: {}", e)),
+    };
+    if let Some(tags) = update.tags {
+        labels.tags.clear();
+        labels.add_tags(tags);
+    }
+    labels.add_tags(update.add);
+    labels.remove_tags(&update.remove);
+    ApiResponse::json(200, json!({ "tags": labels.tags }))
+}
+
+/// Decode a circuit from a JSON or protobuf request body
+fn parse_circuit_body(request: &ApiRequest) -> Result<QuantumCircuit, ApiResponse> {
+    let parsed = if request.header("content-type") == Some(PROTOBUF) {
+        proto::decode_circuit(&request.body)
+    } else {
+        std::str::from_utf8(&request.body)
+            .map_err(|e| e.into())
+            .and_then(|body| qsim::parse_circuit(body, qsim::CircuitFormat::Json))
+    };
+    let circuit = parsed.map_err(|e| ApiResponse::error(400, &format!("invalid circuit: {}", e)))?;
+    if circuit.num_qubits > MAX_QUBITS {
+        return Err(ApiResponse::error(400, &format!("circuits are limited to {} qubits", MAX_QUBITS)));
+    }
+    Ok(circuit)
+}
+
+/// A simulation's noise model: inline, or the name of one stored on the server
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum NoiseSpec {
+    Named(String),
+    Inline(Box<NoiseModel>),
+}
+
+/// A bare circuit, or a `{"circuit", "noise", "shots", "ideal"}` envelope
+struct SimulateBody {
+    circuit: QuantumCircuit,
+    noise: Option<NoiseSpec>,
+    shots: Option<usize>,
+    ideal: bool,
+}
+
+fn parse_simulate_body(request: &ApiRequest) -> Result<SimulateBody, ApiResponse> {
+    #[derive(serde::Deserialize)]
+    struct SimulateRequest {
+        circuit: QuantumCircuit,
+        noise: Option<NoiseSpec>,
+        shots: Option<usize>,
+        #[serde(default)]
+        ideal: bool,
+    }
+
+    let envelope = if request.header("content-type") == Some(PROTOBUF) {
+        None
+    } else {
+        serde_json::from_slice::<Value>(&request.body).ok().filter(|value| value.get("circuit").is_some())
+    };
+    match envelope {
+        Some(value) => match serde_json::from_value::<SimulateRequest>(value) {
+            Ok(body) if body.circuit.num_qubits > MAX_QUBITS => {
+                Err(ApiResponse::error(400, &format!("circuits are limited to {} qubits", MAX_QUBITS)))
+            }
+            Ok(body) => match qsim::validate_circuit(&body.circuit) {
+                Ok(()) => Ok(SimulateBody { circuit: body.circuit, noise: body.noise, shots: body.shots, ideal: body.ideal }),
+                Err(e) => Err(ApiResponse::error(400, &format!("invalid circuit: {}", e))),
+            },
+            Err(e) => Err(ApiResponse::error(400, &format!("invalid simulate request: {}", e))),
+        },
+        None => parse_circuit_body(request).map(|circuit| SimulateBody { circuit, noise: None, shots: None, ideal: false }),
+    }
+}
+
+/// Counts of `shots` shots, noisy when a model is given, with the same number of noiseless
+/// shots beside them when `ideal` is set
+fn sample_counts(circuit: &QuantumCircuit, noise: Option<NoiseModel>, shots: usize, ideal: bool, seed: Option<u64>) -> ApiResponse {
+    #[derive(serde::Serialize)]
+    struct SampledRun {
+        num_qubits: usize,
+        #[serde(flatten)]
+        counts: results::Counts,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ideal: Option<results::Counts>,
+        manifest: Manifest,
+    }
+
+    if shots == 0 || shots > MAX_SHOTS {
+        return ApiResponse::error(400, &format!("'shots' must be between 1 and {}", MAX_SHOTS));
+    }
+    if ideal && noise.is_none() {
+        return ApiResponse::error(400, "'ideal' compares against a noise model; give 'noise'");
+    }
+    let seed_value = seed.unwrap_or_else(rand::random);
+    let sampled = match noise {
+        Some(model) => match NoisyBackend::new(model).sample(circuit, shots, seed_value) {
+            Ok(sampled) => sampled,
+            Err(e) => return ApiResponse::error(400, &e),
+        },
+        None => qsim::sample_circuit(circuit, shots, &mut StdRng::seed_from_u64(seed_value)),
+    };
+    let tally = |sampled: Vec<qsim::Shot>| {
+        let mut registers = BTreeMap::new();
+        for shot in sampled {
+            *registers.entry(shot.register).or_default() += 1;
+        }
+        results::Counts::new(circuit.num_clbits(), &registers)
+    };
+    let ideal = ideal.then(|| tally(qsim::sample_circuit(circuit, shots, &mut StdRng::seed_from_u64(seed_value))));
+    let run = SampledRun { num_qubits: circuit.num_qubits, counts: tally(sampled), ideal, manifest: seeded_manifest(seed) };
+    match serde_json::to_vec(&run) {
+        Ok(body) => ApiResponse::bytes("application/json", body),
+        Err(e) => ApiResponse::error(500, &e.to_string()),
+    }
+}
+
+/// Optional `?seed=` query parameter for the mid-circuit measurements and resets of a run
+fn query_seed(request: &ApiRequest) -> Result<Option<u64>, ApiResponse> {
+    match request.query.get("seed").map(|seed| seed.parse::<u64>()) {
+        Some(Ok(seed)) => Ok(Some(seed)),
+        Some(Err(_)) => Err(ApiResponse::error(400, "'seed' must be a non-negative integer")),
+        None => Ok(None),
+    }
+}
+
+/// This server's manifest, recording the seed of a seeded run
+fn seeded_manifest(seed: Option<u64>) -> Manifest {
+    match seed {
+        Some(seed) => Manifest::current().with_seed(seed),
+        None => Manifest::current(),
+    }
+}
+
+/// Simulate a circuit; with a seed, its measurements and resets follow the same trajectory
+/// on every call
+fn run(circuit: &QuantumCircuit, seed: Option<u64>) -> QuantumSimulator {
+    let mut simulator = match seed {
+        Some(seed) => QuantumSimulator::seeded(circuit.num_qubits, seed),
+        None => QuantumSimulator::new(circuit.num_qubits),
+    };
+    for gate in &circuit.gates {
+        simulator.apply_gate(gate);
+    }
+    simulator
+}
+
+/// Read one HTTP/1.1 request (request line, headers, Content-Length body)
+fn read_request<R: BufRead>(reader: &mut R) -> io::Result<ApiRequest> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or_else(|| invalid("missing method"))?.to_string();
+    let target = parts.next().ok_or_else(|| invalid("missing request target"))?;
+
+    let (path, query_string) = target.split_once('?').unwrap_or((target, ""));
+    let query = query_string
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect();
+
+    let mut headers = HashMap::new();
     loop {
-        std::thread::sleep(std::time::Duration::from_secs(1));
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = headers
+        .get("content-length")
+        .map(|value| value.parse::<usize>().map_err(|_| invalid("bad Content-Length")))
+        .transpose()?
+        .unwrap_or(0);
+    if length > MAX_BODY_BYTES {
+        let message = format!("request body of {} bytes exceeds the {} byte limit", length, MAX_BODY_BYTES);
+        return Err(io::Error::new(io::ErrorKind::FileTooLarge, message));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    Ok(ApiRequest {
+        method,
+        path: path.to_string(),
+        query,
+        headers,
+        body,
+    })
+}
+
+/// Frames every write as one HTTP/1.1 chunk
+struct ChunkedWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> Write for ChunkedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !buf.is_empty() {
+            write!(self.inner, "{:x}\r\n", buf.len())?;
+            self.inner.write_all(buf)?;
+            self.inner.write_all(b"\r\n")?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decode `%XX` escapes and `+` in a query-string component
+fn percent_decode(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match component.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn write_response(mut stream: TcpStream, response: ApiResponse) -> io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        409 => "Conflict",
+        413 => "Content Too Large",
+        _ => "Internal Server Error",
+    };
+    write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: {}\r\n", response.status, reason, response.content_type)?;
+    for (name, value) in &response.headers {
+        write!(stream, "{}: {}\r\n", name, value)?;
+    }
+
+    match response.stream {
+        Some(body) => {
+            stream.write_all(b"Transfer-Encoding: chunked\r\nConnection: close\r\n\r\n")?;
+            let mut writer = BufWriter::with_capacity(STREAM_CHUNK_BYTES, ChunkedWriter { inner: &mut stream });
+            body(&mut writer)?;
+            writer.flush()?;
+            drop(writer);
+            stream.write_all(b"0\r\n\r\n")?;
+        }
+        None => {
+            write!(stream, "Content-Length: {}\r\nConnection: close\r\n\r\n", response.body.len())?;
+            stream.write_all(&response.body)?;
+        }
+    }
+    stream.flush()
+}
+
+pub fn start_server(port: u16, jobs: JobQueue, keys: KeyStore, noise_models: BTreeMap<String, NoiseModel>) {
+    println!("\u{250c}\u{2500} Starting QuantumMesh API server on port {}", port);
+    println!("\u{251c}\u{2500} Available endpoints:");
+    println!("\u{2502}  POST   /api/simulate       - Simulate circuit (?noise=, ?shots=) or IBM Qobj");
+    println!("\u{2502}  POST   /api/state          - Final state vector as .npy");
+    println!("\u{2502}  POST   /api/probabilities  - Stream probabilities (NDJSON or binary, ranged)");
+    println!("\u{2502}  POST   /api/upload         - Upload circuit definition");
+    println!("\u{2502}  GET    /api/circuits       - List circuit summaries (paged, filterable)");
+    println!("\u{2502}  GET    /api/circuit/:id    - Get specific circuit");
+    println!("\u{2502}  DELETE /api/circuit/:id    - Delete circuit");
+    println!("\u{2502}  POST   /api/circuit/:id/run - Simulate a stored circuit and save the result");
+    println!("\u{2502}  PUT    /api/circuit/:id/tags - Replace, add or remove circuit tags");
+    println!("\u{2502}  GET    /api/result/:id     - Get a saved result");
+    println!("\u{2502}  PUT    /api/result/:id/tags - Replace, add or remove result tags");
+    println!("\u{2502}  GET    /api/search         - Search circuits and results by tag, name, size");
+    println!("\u{2502}  POST   /api/stats          - Chi-squared and linear XEB of samples vs ideal result");
+    println!("\u{2502}  POST   /api/optimize       - Optimize circuit (?objective=, ?noise=<name>)");
+    println!("\u{2502}  POST   /api/lint           - Validator errors and warnings (?max_depth=)");
+    println!("\u{2502}  POST   /api/estimate       - Expectation values (and gradients) for a batch of parameters");
+    println!("\u{2502}  POST   /api/entropy        - Von Neumann and Renyi entanglement entropy across qubit cuts");
+    println!("\u{2502}  GET    /api/noise          - List named noise models");
+    println!("\u{2502}  PUT    /api/noise/:name    - Store a noise model (GET/DELETE to read or remove)");
+    println!("\u{2502}  POST   /api/jobs           - Queue a circuit as a background job (GET to list jobs)");
+    println!("\u{2502}  GET    /api/job/:id        - Job state, transition history and result");
+    println!("\u{2502}  GET    /api/workspace      - Export circuits, results, noise models and configs");
+    println!("\u{2502}  POST   /api/workspace      - Import a workspace archive under fresh ids");
+    println!("\u{2502}  GET    /api/node           - This node's hardware, load and job queue");
+    println!("\u{2502}  GET    /api/cluster        - Configured workers with hardware, load, queues and latency");
+    println!("\u{2502}  GET    /api/keys           - List API keys (POST to issue, DELETE /api/keys/:name to revoke)");
+    println!("\u{2502}  GET    /api/health         - Health check");
+    if keys.enabled() {
+        println!("\u{251c}\u{2500} Access control on ({} API keys)", keys.list().len());
+    } else {
+        println!("\u{251c}\u{2500} Access control off (no [auth] keys in config.toml)");
+    }
+    println!("\u{251c}\u{2500} Jobs kept in {} ({} queued or resuming)", jobs.dir().display(), jobs.pending());
+    if !noise_models.is_empty() {
+        let names: Vec<&str> = noise_models.keys().map(String::as_str).collect();
+        println!("\u{251c}\u{2500} Noise models loaded: {}", names.join(", "));
+    }
+    println!("\u{2514}\u{2500} Server ready at http://localhost:{}", port);
+
+    println!("\nPress Ctrl+C to stop the server");
+    if let Err(e) = ApiServer::new(port, jobs, keys).with_noise_models(noise_models).run() {
+        eprintln!("Server error: {}", e);
+        std::process::exit(1);
     }
 }
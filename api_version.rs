@@ -0,0 +1,69 @@
+//! API Version Module
+//! Semantic version of the REST API and the rule deciding which clients a server accepts
+
+use std::fmt;
+
+/// Version of the REST API this build implements. Minor releases only add endpoints, fields
+/// and query parameters; anything that changes or removes them bumps the major version.
+pub const API_VERSION: ApiVersion = ApiVersion { major: 1, minor: 0, patch: 0 };
+
+/// Header carrying the API version: clients send the version they were built against and
+/// servers answer every response with their own
+pub const HEADER: &str = "X-QuantumMesh-API-Version";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ApiVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl ApiVersion {
+    /// Parse `MAJOR[.MINOR[.PATCH]]`, with an optional leading `v`; missing parts are 0
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let trimmed = text.trim();
+        let digits = trimmed.strip_prefix('v').unwrap_or(trimmed);
+        let parts: Vec<&str> = digits.split('.').collect();
+        if parts.len() > 3 {
+            return Err(format!("API version '{}' should be MAJOR.MINOR.PATCH", text));
+        }
+        let mut numbers = [0u32; 3];
+        for (number, part) in numbers.iter_mut().zip(&parts) {
+            *number = part
+                .parse()
+                .map_err(|_| format!("API version '{}' should be MAJOR.MINOR.PATCH", text))?;
+        }
+        Ok(Self { major: numbers[0], minor: numbers[1], patch: numbers[2] })
+    }
+
+    /// Check that a server implementing this version serves a client built against `client`:
+    /// the majors must match and the server must have every minor addition the client may
+    /// use. Patch releases never change the API, so they are not compared.
+    pub fn accepts(&self, client: ApiVersion) -> Result<(), String> {
+        if client.major != self.major {
+            let fix = if client.major > self.major { "upgrade the server" } else { "upgrade the client" };
+            return Err(format!(
+                "client speaks API {} but this server speaks API {}, an incompatible major version; {}",
+                client, self, fix
+            ));
+        }
+        if client.minor > self.minor {
+            return Err(format!(
+                "client needs API {} but this server only implements API {}; upgrade the server",
+                client, self
+            ));
+        }
+        Ok(())
+    }
+
+    /// Oldest client version this server accepts
+    pub fn oldest_client(&self) -> ApiVersion {
+        ApiVersion { major: self.major, minor: 0, patch: 0 }
+    }
+}
@@ -0,0 +1,96 @@
+//! Archive Module
+//! HDF5 results archive collecting distributions, metadata and timings across runs
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::time::Duration;
+use crate::manifest::Manifest;
+
+/// One simulation or benchmark run, stored as its own group in the archive
+pub struct ArchiveRun {
+    pub probabilities: Vec<f64>,
+    pub metadata: BTreeMap<String, String>,
+    pub timings: Vec<(String, Duration)>,
+    pub manifest: Manifest,
+}
+
+impl ArchiveRun {
+    pub fn new(probabilities: Vec<f64>) -> Self {
+        Self {
+            probabilities,
+            metadata: BTreeMap::new(),
+            timings: Vec::new(),
+            manifest: Manifest::current(),
+        }
+    }
+
+    pub fn with_metadata(mut self, key: &str, value: impl ToString) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Record the RNG seed of a seeded run in its manifest
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        self.manifest.seed = seed;
+        self
+    }
+
+    pub fn with_timing(mut self, phase: &str, elapsed: Duration) -> Self {
+        self.timings.push((phase.to_string(), elapsed));
+        self
+    }
+}
+
+/// Append a run to the archive as group `run-N`, creating the file if needed.
+///
+/// Layout: `/run-N/probabilities` (f64 dataset), string attributes on `/run-N`
+/// for metadata, f64 attributes in seconds on `/run-N/timings` and string
+/// attributes on `/run-N/manifest`.
+#[cfg(feature = "hdf5")]
+pub fn append_run(path: &str, run: &ArchiveRun) -> Result<String, Box<dyn Error>> {
+    use hdf5::types::VarLenUnicode;
+
+    let file = if std::path::Path::new(path).exists() {
+        hdf5::File::append(path)?
+    } else {
+        hdf5::File::create(path)?
+    };
+    let name = format!("run-{}", file.member_names()?.len());
+    let group = file.create_group(&name)?;
+
+    group
+        .new_dataset_builder()
+        .with_data(&run.probabilities)
+        .create("probabilities")?;
+    for (key, value) in &run.metadata {
+        let value: VarLenUnicode = value.parse().map_err(|e| format!("metadata '{}': {}", key, e))?;
+        group
+            .new_attr::<VarLenUnicode>()
+            .create(key.as_str())?
+            .write_scalar(&value)?;
+    }
+
+    let timings = group.create_group("timings")?;
+    for (phase, elapsed) in &run.timings {
+        timings
+            .new_attr::<f64>()
+            .create(phase.as_str())?
+            .write_scalar(&elapsed.as_secs_f64())?;
+    }
+
+    let manifest = group.create_group("manifest")?;
+    for (field, value) in run.manifest.entries() {
+        let value: VarLenUnicode = value.parse().map_err(|e| format!("manifest '{}': {}", field, e))?;
+        manifest
+            .new_attr::<VarLenUnicode>()
+            .create(field)?
+            .write_scalar(&value)?;
+    }
+
+    Ok(name)
+}
+
+#[cfg(not(feature = "hdf5"))]
+pub fn append_run(_path: &str, _run: &ArchiveRun) -> Result<String, Box<dyn Error>> {
+    Err("HDF5 output is not available; rebuild with `--features hdf5`".into())
+}
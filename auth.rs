@@ -0,0 +1,192 @@
+//! Auth Module
+//! API keys and the admin / user / read-only roles the server enforces, from `[auth]` in config.toml
+
+use std::fs;
+use std::io;
+use std::sync::{Arc, Mutex};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Roles in increasing order of privilege
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    /// Read stored circuits, results, jobs and noise models
+    ReadOnly,
+    /// Also simulate, submit jobs, and store, tag and delete their own circuits and results
+    User,
+    /// Also manage keys, noise models and workspaces, and delete anyone's data
+    Admin,
+}
+
+impl Role {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::ReadOnly => "read-only",
+            Role::User => "user",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+/// One `[[auth.keys]]` entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    /// Who holds the key; stored items record it as their owner
+    pub name: String,
+    pub key: String,
+    pub role: Role,
+    /// Listed in config.toml, so revoking it at runtime would not outlast a restart
+    #[serde(skip)]
+    pub configured: bool,
+}
+
+/// The `[auth]` section of config.toml
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub keys: Vec<ApiKey>,
+}
+
+impl AuthConfig {
+    /// Read `[auth]` from config.toml contents; a missing section means no keys
+    pub fn parse(config: &str) -> Result<Self, String> {
+        #[derive(Deserialize)]
+        struct File {
+            #[serde(default)]
+            auth: AuthConfig,
+        }
+        let auth = toml::from_str::<File>(config)
+            .map(|file| file.auth)
+            .map_err(|e| format!("invalid config.toml: {}", e))?;
+        for (i, key) in auth.keys.iter().enumerate() {
+            if key.key.is_empty() {
+                return Err(format!("auth key '{}' is empty", key.name));
+            }
+            if auth.keys[..i].iter().any(|other| other.name == key.name || other.key == key.key) {
+                return Err(format!("auth key '{}' is listed twice", key.name));
+            }
+        }
+        Ok(auth)
+    }
+}
+
+/// Who sent a request
+#[derive(Debug, Clone)]
+pub struct Caller {
+    /// Key name, or none when the server has no keys configured
+    pub name: Option<String>,
+    pub role: Role,
+}
+
+impl Caller {
+    /// Whether the caller may change or delete an item stored by `owner`
+    pub fn may_modify(&self, owner: Option<&str>) -> bool {
+        self.role == Role::Admin || (self.role >= Role::User && owner == self.name.as_deref())
+    }
+}
+
+/// Keys accepted by the server. With none configured, access control is off and every
+/// request is treated as an admin, as before keys existed.
+#[derive(Clone, Default)]
+pub struct KeyStore {
+    keys: Arc<Mutex<Vec<ApiKey>>>,
+}
+
+impl KeyStore {
+    pub fn new(keys: Vec<ApiKey>) -> Self {
+        Self { keys: Arc::new(Mutex::new(keys)) }
+    }
+
+    /// Keys from `[auth]` in the config file at `path`. Only a missing file means no keys;
+    /// any other read error is returned, so access control never silently turns off.
+    pub fn load(path: &str) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(config) => {
+                let keys = AuthConfig::parse(&config)?.keys;
+                Ok(Self::new(keys.into_iter().map(|key| ApiKey { configured: true, ..key }).collect()))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("cannot read {}: {}", path, e)),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.keys.lock().unwrap().is_empty()
+    }
+
+    /// Identify the caller from an `Authorization: Bearer <key>` header
+    pub fn authenticate(&self, authorization: Option<&str>) -> Result<Caller, String> {
+        let keys = self.keys.lock().unwrap();
+        if keys.is_empty() {
+            return Ok(Caller { name: None, role: Role::Admin });
+        }
+        let token = authorization
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .ok_or("an API key is required (Authorization: Bearer <key>)")?;
+        // Every key is compared in full, so timing reveals neither which key nor how much of
+        // one matched
+        keys.iter()
+            .fold(None, |found, key| if constant_time_eq(key.key.as_bytes(), token.as_bytes()) { Some(key) } else { found })
+            .map(|key| Caller { name: Some(key.name.clone()), role: key.role })
+            .ok_or_else(|| "unknown API key".to_string())
+    }
+
+    /// Issue a fresh random key under a new name
+    pub fn create(&self, name: &str, role: Role) -> Result<ApiKey, String> {
+        let mut keys = self.keys.lock().unwrap();
+        if name.is_empty() {
+            return Err("key name must not be empty".to_string());
+        }
+        if keys.iter().any(|key| key.name == name) {
+            return Err(format!("a key named '{}' already exists", name));
+        }
+        let mut rng = rand::thread_rng();
+        let key = ApiKey {
+            name: name.to_string(),
+            key: (0..32).map(|_| format!("{:x}", rng.gen_range(0..16u8))).collect(),
+            role,
+            configured: false,
+        };
+        keys.push(key.clone());
+        Ok(key)
+    }
+
+    /// Remove a key issued at runtime. Keys from config.toml would come back on restart, so
+    /// they must be removed from the file instead; the last admin key cannot be revoked
+    /// either, so the server stays manageable.
+    pub fn revoke(&self, name: &str) -> Result<bool, String> {
+        let mut keys = self.keys.lock().unwrap();
+        let Some(index) = keys.iter().position(|key| key.name == name) else {
+            return Ok(false);
+        };
+        if keys[index].configured {
+            return Err(format!(
+                "key '{}' is listed in config.toml; remove it there and restart the server to revoke it",
+                name
+            ));
+        }
+        if keys[index].role == Role::Admin && keys.iter().filter(|key| key.role == Role::Admin).count() == 1 {
+            return Err("cannot revoke the last admin key".to_string());
+        }
+        keys.remove(index);
+        Ok(true)
+    }
+
+    /// Key names and roles, without the secrets
+    pub fn list(&self) -> Vec<Value> {
+        self.keys
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|key| json!({ "name": key.name, "role": key.role }))
+            .collect()
+    }
+}
+
+/// Byte-wise equality whose running time depends only on the lengths
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
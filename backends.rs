@@ -0,0 +1,345 @@
+//! Backends Module
+//! Runtime registry of simulation backends, selected by name with `--backend`
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use crate::observable::PauliSum;
+use crate::qsim::{self, QuantumCircuit, QuantumSimulator, Shot};
+use crate::{mps, propagation, results, simulator_backend, sparse, stabilizer};
+
+/// Backend used when none is named
+pub const DEFAULT_BACKEND: &str = "statevector";
+
+/// A way of simulating whole circuits. Backends that keep some other representation than a
+/// dense state vector (tensor networks, sparse maps, stabilizer tableaux) implement this and
+/// are registered under a name with `register`.
+pub trait StateBackend: Send + Sync {
+    /// Name used to select the backend, e.g. `--backend statevector`
+    fn name(&self) -> &str;
+
+    /// One-line summary for `quantummesh backends`
+    fn description(&self) -> &str;
+
+    /// Measurement probabilities of every basis state, qubit 0 the least significant bit.
+    /// Mid-circuit measurements and resets follow one trajectory drawn from `seed`.
+    fn probabilities(&self, circuit: &QuantumCircuit, seed: u64) -> Result<Vec<f64>, String>;
+
+    /// Sample shots of a circuit. The default draws from `probabilities`, which is only
+    /// correct without mid-circuit collapse, so such circuits are rejected.
+    fn sample(&self, circuit: &QuantumCircuit, shots: usize, seed: u64) -> Result<Vec<Shot>, String> {
+        if circuit.has_collapse() {
+            return Err(format!("the {} backend cannot sample circuits that collapse qubits mid-circuit", self.name()));
+        }
+        let mut rng = StdRng::seed_from_u64(seed);
+        let probabilities = self.probabilities(circuit, rand::Rng::gen(&mut rng))?;
+        Ok(qsim::terminal_shots(circuit, results::sample_outcomes(&probabilities, shots, &mut rng)))
+    }
+
+    /// Exact ⟨P⟩ of each term's Pauli string at the end of the circuit, for backends that
+    /// compute expectation values directly; None means they are estimated from shots
+    fn expectation(&self, _circuit: &QuantumCircuit, _observable: &PauliSum) -> Option<Result<Vec<f64>, String>> {
+        None
+    }
+
+    /// Copy of this backend with a tuning option from the command line set, such as
+    /// `--max-bond` for the matrix product state backend. Backends without it refuse.
+    fn with_option(&self, name: &str, _value: &str) -> Result<Arc<dyn StateBackend>, String> {
+        Err(format!("the {} backend has no --{} option", self.name(), name))
+    }
+}
+
+/// The built-in dense state vector simulator
+struct StateVector;
+
+impl StateBackend for StateVector {
+    fn name(&self) -> &str {
+        DEFAULT_BACKEND
+    }
+
+    fn description(&self) -> &str {
+        "dense state vector, 2^n amplitudes (GPU kernels when available)"
+    }
+
+    fn probabilities(&self, circuit: &QuantumCircuit, seed: u64) -> Result<Vec<f64>, String> {
+        let mut simulator = QuantumSimulator::seeded(circuit.num_qubits, seed);
+        for gate in &circuit.gates {
+            simulator.apply_gate(gate);
+        }
+        Ok(simulator.measure_all())
+    }
+
+    fn sample(&self, circuit: &QuantumCircuit, shots: usize, seed: u64) -> Result<Vec<Shot>, String> {
+        Ok(qsim::sample_circuit(circuit, shots, &mut StdRng::seed_from_u64(seed)))
+    }
+}
+
+/// The state vector simulator's gate and measurement handling on another state
+/// representation, such as `density`
+struct Representation {
+    name: &'static str,
+    description: &'static str,
+}
+
+impl Representation {
+    fn simulator(&self, num_qubits: usize, seed: u64) -> Result<QuantumSimulator, String> {
+        Ok(QuantumSimulator::with_backend(simulator_backend::create(self.name, num_qubits)?, seed))
+    }
+}
+
+impl StateBackend for Representation {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn description(&self) -> &str {
+        self.description
+    }
+
+    /// Refused above `sparse::MAX_DENSE_QUBITS`: the disk state vector holds wider registers
+    /// than a probability vector can fit in memory
+    fn probabilities(&self, circuit: &QuantumCircuit, seed: u64) -> Result<Vec<f64>, String> {
+        if circuit.num_qubits > sparse::MAX_DENSE_QUBITS {
+            return Err(format!(
+                "a probability vector of {} qubits has 2^{} entries; sample it with --shots instead (at most {} qubits)",
+                circuit.num_qubits, circuit.num_qubits, sparse::MAX_DENSE_QUBITS
+            ));
+        }
+        let mut simulator = self.simulator(circuit.num_qubits, seed)?;
+        for gate in &circuit.gates {
+            simulator.apply_gate(gate);
+        }
+        Ok(simulator.measure_all())
+    }
+
+    fn sample(&self, circuit: &QuantumCircuit, shots: usize, seed: u64) -> Result<Vec<Shot>, String> {
+        // Only an unknown representation fails, so checking once covers every shot
+        self.simulator(circuit.num_qubits, seed)?;
+        let create = |seed| self.simulator(circuit.num_qubits, seed).expect("representation checked above");
+        Ok(qsim::sample_circuit_with(circuit, shots, &mut StdRng::seed_from_u64(seed), create))
+    }
+}
+
+type Registry = BTreeMap<String, Arc<dyn StateBackend>>;
+
+fn registry() -> &'static Mutex<Registry> {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let default: Arc<dyn StateBackend> = Arc::new(StateVector);
+        Mutex::new(Registry::from([(default.name().to_string(), default)]))
+    })
+}
+
+/// Register the built-in backends besides `statevector`, through `register` as any other
+/// backend is; `main` calls this once at startup
+pub fn register_builtins() -> Result<(), String> {
+    let builtin: Vec<Arc<dyn StateBackend>> = vec![
+        Arc::new(mps::MpsBackend::default()),
+        Arc::new(sparse::SparseBackend),
+        Arc::new(stabilizer::StabilizerBackend),
+        Arc::new(propagation::PropagationBackend::default()),
+        Arc::new(Representation { name: "cpu", description: "dense state vector on portable CPU kernels, a reference for the GPU kernels" }),
+        Arc::new(Representation { name: "density", description: "density matrix, 4^n entries; small circuits with mixed states" }),
+        Arc::new(Representation { name: "disk", description: "dense state vector paged through a scratch file; up to 40 qubits, sample with --shots" }),
+    ];
+    builtin.into_iter().try_for_each(register)
+}
+
+/// Make a backend selectable by its name; call once at startup, before parsing `--backend`.
+/// Names are unique, so a second backend under a taken name is an error.
+pub fn register(backend: Arc<dyn StateBackend>) -> Result<(), String> {
+    let mut registry = registry().lock().unwrap();
+    let name = backend.name().to_string();
+    if registry.contains_key(&name) {
+        return Err(format!("a backend named '{}' is already registered", name));
+    }
+    registry.insert(name, backend);
+    Ok(())
+}
+
+/// Registered backend with this name
+pub fn get(name: &str) -> Result<Arc<dyn StateBackend>, String> {
+    let registry = registry().lock().unwrap();
+    registry.get(name).cloned().ok_or_else(|| {
+        let names: Vec<&str> = registry.keys().map(|name| name.as_str()).collect();
+        format!("unknown backend '{}' (registered: {})", name, names.join(", "))
+    })
+}
+
+/// All registered backends, by name
+pub fn list() -> Vec<Arc<dyn StateBackend>> {
+    registry().lock().unwrap().values().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+    use rand::seq::index;
+    use rand::Rng;
+    use super::*;
+    use crate::observable;
+    use crate::qsim::QuantumGate;
+
+    const QUBITS: usize = 5;
+
+    /// `count` distinct qubits in random order
+    fn qubits(rng: &mut StdRng, count: usize) -> Vec<usize> {
+        index::sample(rng, QUBITS, count).into_vec()
+    }
+
+    /// Random circuit over one- to four-qubit gates of every kind the dense backends take
+    fn random_circuit(rng: &mut StdRng, length: usize) -> QuantumCircuit {
+        let mut gates = Vec::new();
+        for _ in 0..length {
+            let angle = rng.gen_range(-3.2..3.2);
+            let q = qubits(rng, 4);
+            gates.push(match rng.gen_range(0..24) {
+                0 => QuantumGate::Hadamard { qubit: q[0] },
+                1 => QuantumGate::PauliX { qubit: q[0] },
+                2 => QuantumGate::PauliY { qubit: q[0] },
+                3 => QuantumGate::S { qubit: q[0] },
+                4 => QuantumGate::Tdg { qubit: q[0] },
+                5 => QuantumGate::SX { qubit: q[0] },
+                6 => QuantumGate::Phase { qubit: q[0], angle },
+                7 => QuantumGate::RotationX { qubit: q[0], angle },
+                8 => QuantumGate::RotationY { qubit: q[0], angle },
+                9 => QuantumGate::RotationZ { qubit: q[0], angle },
+                10 => QuantumGate::U { qubit: q[0], theta: angle, phi: 0.4 * angle, lambda: -1.3 },
+                11 => QuantumGate::CNOT { control: q[0], target: q[1] },
+                12 => QuantumGate::CZ { control: q[0], target: q[1] },
+                13 => QuantumGate::CPhase { control: q[0], target: q[1], angle },
+                14 => QuantumGate::SWAP { qubit1: q[0], qubit2: q[1] },
+                15 => QuantumGate::ISWAP { qubit1: q[0], qubit2: q[1] },
+                16 => QuantumGate::SqrtISWAP { qubit1: q[0], qubit2: q[1] },
+                17 => QuantumGate::ECR { qubit1: q[0], qubit2: q[1] },
+                18 => QuantumGate::RXX { qubit1: q[0], qubit2: q[1], angle },
+                19 => QuantumGate::RYY { qubit1: q[0], qubit2: q[1], angle },
+                20 => QuantumGate::RZZ { qubit1: q[0], qubit2: q[1], angle },
+                21 => QuantumGate::Givens { qubit1: q[0], qubit2: q[1], angle },
+                22 => QuantumGate::Toffoli { control1: q[0], control2: q[1], target: q[2] },
+                _ => QuantumGate::MCX { controls: q[..3].to_vec(), target: q[3] },
+            });
+        }
+        QuantumCircuit { num_qubits: QUBITS, gates, metadata: BTreeMap::new() }
+    }
+
+    /// Random Clifford circuit with at most `rotations` non-Clifford Z rotations after
+    /// decomposition (a controlled phase takes three), as the extended stabilizer and Pauli
+    /// propagation backends take
+    fn random_clifford_circuit(rng: &mut StdRng, length: usize, rotations: usize) -> QuantumCircuit {
+        let mut gates = Vec::new();
+        let mut rotated = 0;
+        for _ in 0..length {
+            let q = qubits(rng, 3);
+            gates.push(match rng.gen_range(0..16) {
+                0 => QuantumGate::Hadamard { qubit: q[0] },
+                1 => QuantumGate::PauliX { qubit: q[0] },
+                2 => QuantumGate::PauliY { qubit: q[0] },
+                3 => QuantumGate::PauliZ { qubit: q[0] },
+                4 => QuantumGate::Sdg { qubit: q[0] },
+                5 => QuantumGate::SXdg { qubit: q[0] },
+                6 => QuantumGate::RotationY { qubit: q[0], angle: -FRAC_PI_2 },
+                7 => QuantumGate::CNOT { control: q[0], target: q[1] },
+                8 => QuantumGate::CZ { control: q[0], target: q[1] },
+                9 => QuantumGate::SWAP { qubit1: q[0], qubit2: q[1] },
+                10 => QuantumGate::ISWAP { qubit1: q[0], qubit2: q[1] },
+                11 => QuantumGate::ECR { qubit1: q[0], qubit2: q[1] },
+                12 => QuantumGate::RZZ { qubit1: q[0], qubit2: q[1], angle: FRAC_PI_2 },
+                13 if rotated < rotations => {
+                    rotated += 1;
+                    QuantumGate::T { qubit: q[0] }
+                }
+                14 if rotated < rotations => {
+                    rotated += 1;
+                    QuantumGate::RotationX { qubit: q[0], angle: rng.gen_range(-3.2..3.2) }
+                }
+                15 if rotated + 3 <= rotations => {
+                    rotated += 3;
+                    QuantumGate::CPhase { control: q[0], target: q[1], angle: rng.gen_range(-3.2..3.2) }
+                }
+                _ => QuantumGate::Hadamard { qubit: q[2] },
+            });
+        }
+        QuantumCircuit { num_qubits: QUBITS, gates, metadata: BTreeMap::new() }
+    }
+
+    fn assert_close(actual: &[f64], expected: &[f64], tolerance: f64, what: &str) {
+        assert_eq!(actual.len(), expected.len(), "{}", what);
+        for (index, (a, e)) in actual.iter().zip(expected).enumerate() {
+            assert!((a - e).abs() < tolerance, "{}: entry {} is {}, expected {}", what, index, a, e);
+        }
+    }
+
+    fn representation(name: &'static str) -> Representation {
+        Representation { name, description: "" }
+    }
+
+    /// Check a backend's probabilities against the state vector's on random circuits
+    fn assert_matches_state_vector(backend: &dyn StateBackend, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        for trial in 0..20 {
+            let circuit = random_circuit(&mut rng, 40);
+            let expected = StateVector.probabilities(&circuit, 0).unwrap();
+            let actual = backend.probabilities(&circuit, 0).unwrap();
+            assert_close(&actual, &expected, 1e-9, &format!("{} backend, circuit {}", backend.name(), trial));
+        }
+    }
+
+    #[test]
+    fn mps_matches_the_state_vector() {
+        assert_matches_state_vector(&mps::MpsBackend::default(), 1);
+    }
+
+    #[test]
+    fn sparse_matches_the_state_vector() {
+        assert_matches_state_vector(&sparse::SparseBackend, 4);
+    }
+
+    #[test]
+    fn cpu_and_density_representations_match_the_state_vector() {
+        assert_matches_state_vector(&representation("cpu"), 5);
+        assert_matches_state_vector(&representation("density"), 6);
+    }
+
+    #[test]
+    fn disk_representation_matches_the_state_vector() {
+        assert_matches_state_vector(&representation("disk"), 7);
+    }
+
+    #[test]
+    fn stabilizer_matches_the_state_vector_on_clifford_t_circuits() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for trial in 0..20 {
+            let circuit = random_clifford_circuit(&mut rng, 60, 8);
+            let expected = StateVector.probabilities(&circuit, 0).unwrap();
+            let actual = stabilizer::StabilizerBackend.probabilities(&circuit, 0).unwrap();
+            assert_close(&actual, &expected, 1e-9, &format!("stabilizer backend, circuit {}", trial));
+        }
+    }
+
+    #[test]
+    fn pauli_propagation_matches_state_vector_expectations() {
+        let observable = observable::PauliSum::parse("ZIIZI + 0.5*XXYIZ - IYZXI + ZZZZZ + IIIIX").unwrap();
+        let backend = propagation::PropagationBackend::default();
+        let mut rng = StdRng::seed_from_u64(3);
+        for trial in 0..20 {
+            let circuit = random_clifford_circuit(&mut rng, 60, 8);
+            let mut simulator = QuantumSimulator::seeded(QUBITS, 0);
+            for gate in &circuit.gates {
+                simulator.apply_gate(gate);
+            }
+            let state = simulator.get_state();
+            let expected: Vec<f64> = observable
+                .terms
+                .iter()
+                .map(|term| {
+                    let (x_mask, z_mask, y_count) = term.masks(|qubit| qubit);
+                    observable::pauli_expectation(&state, x_mask, z_mask, y_count)
+                })
+                .collect();
+            let actual = backend.expectation(&circuit, &observable).unwrap().unwrap();
+            assert_close(&actual, &expected, 1e-6, &format!("pauli backend, circuit {}", trial));
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Catalog Module
+//! Names, tags and search over circuits and results stored on the API server
+
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::{json, Value};
+use crate::noise::NoiseModel;
+use crate::manifest::Manifest;
+use crate::optimizer;
+use crate::qsim::QuantumCircuit;
+
+/// Catalog fields shared by every stored item
+pub struct Labels {
+    pub name: Option<String>,
+    pub tags: Vec<String>,
+    /// Unix time of creation, in seconds
+    pub created_at: u64,
+    /// Creation order, used to break ties between items created in the same second
+    pub sequence: u64,
+    /// Name of the API key that stored the item, when the server has keys
+    pub owner: Option<String>,
+}
+
+impl Labels {
+    pub fn new(name: Option<String>, tags: Vec<String>, sequence: u64) -> Self {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        let mut labels = Self { name, tags: Vec::new(), created_at, sequence, owner: None };
+        labels.add_tags(tags);
+        labels
+    }
+
+    /// Add tags, ignoring ones already present
+    pub fn add_tags(&mut self, tags: impl IntoIterator<Item = String>) {
+        for tag in tags {
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+    }
+
+    pub fn remove_tags(&mut self, tags: &[String]) {
+        self.tags.retain(|tag| !tags.contains(tag));
+    }
+}
+
+/// An uploaded circuit
+pub struct StoredCircuit {
+    pub circuit: QuantumCircuit,
+    pub labels: Labels,
+}
+
+impl StoredCircuit {
+    /// Listing entry without the gate list
+    pub fn summary(&self, id: &str) -> Value {
+        let metrics = optimizer::CircuitMetrics::compute(&self.circuit, &NoiseModel::default());
+        json!({
+            "id": id,
+            "name": self.labels.name,
+            "tags": self.labels.tags,
+            "owner": self.labels.owner,
+            "num_qubits": self.circuit.num_qubits,
+            "gates": metrics.gates,
+            "depth": metrics.depth,
+            "created_at": self.labels.created_at,
+        })
+    }
+}
+
+/// A saved simulation result
+pub struct StoredResult {
+    /// Stored circuit the result was produced from, if any
+    pub circuit_id: Option<String>,
+    pub num_qubits: usize,
+    pub probabilities: Vec<f64>,
+    pub labels: Labels,
+    pub manifest: Option<Manifest>,
+}
+
+impl StoredResult {
+    /// Listing entry without the probability vector
+    pub fn summary(&self, id: &str) -> Value {
+        json!({
+            "id": id,
+            "circuit_id": self.circuit_id,
+            "name": self.labels.name,
+            "tags": self.labels.tags,
+            "owner": self.labels.owner,
+            "num_qubits": self.num_qubits,
+            "created_at": self.labels.created_at,
+        })
+    }
+
+    pub fn to_json(&self, id: &str) -> Value {
+        let mut value = self.summary(id);
+        value["probabilities"] = json!(self.probabilities);
+        if let Some(manifest) = &self.manifest {
+            value["manifest"] = json!(manifest);
+        }
+        value
+    }
+}
+
+/// Criteria shared by the circuit listing and search endpoints
+#[derive(Default)]
+pub struct SearchFilter {
+    /// Case-insensitive substring of the name
+    pub name: Option<String>,
+    /// Tags that must all be present
+    pub tags: Vec<String>,
+    pub min_qubits: Option<usize>,
+    pub max_qubits: Option<usize>,
+}
+
+impl SearchFilter {
+    /// Read `name`, `tag` (comma-separated), `min_qubits` and `max_qubits` from a query string
+    pub fn from_query(query: &HashMap<String, String>) -> Result<Self, String> {
+        let number = |key: &str| {
+            query
+                .get(key)
+                .map(|value| value.parse::<usize>().map_err(|_| format!("'{}' must be a non-negative integer", key)))
+                .transpose()
+        };
+        Ok(Self {
+            name: query.get("name").map(|name| name.to_lowercase()),
+            tags: query.get("tag").map(|tags| split_tags(tags)).unwrap_or_default(),
+            min_qubits: number("min_qubits")?,
+            max_qubits: number("max_qubits")?,
+        })
+    }
+
+    pub fn matches(&self, labels: &Labels, num_qubits: usize) -> bool {
+        let name_matches = match &self.name {
+            Some(filter) => labels.name.as_ref().is_some_and(|name| name.to_lowercase().contains(filter)),
+            None => true,
+        };
+        name_matches
+            && self.tags.iter().all(|tag| labels.tags.contains(tag))
+            && !matches!(self.min_qubits, Some(min) if num_qubits < min)
+            && !matches!(self.max_qubits, Some(max) if num_qubits > max)
+    }
+}
+
+/// Split a comma-separated tag list, dropping empty entries
+pub fn split_tags(tags: &str) -> Vec<String> {
+    tags.split(',')
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
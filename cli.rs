@@ -1,43 +1,744 @@
 //! CLI Module
 //! Command-line interface utilities and display functions
 
-use crate::qsim::QuantumCircuit;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use serde::Serialize;
+use serde_json::Value;
+use crate::cluster::{ClusterStatus, NodeReport};
+use crate::entropy;
+use crate::examples::{Example, ExampleReport};
+use crate::faults::FaultEffect;
+use crate::ftqc::{FaultTolerantModel, ResourceEstimate};
+use crate::gpu_ops::Complex;
+use crate::lint::{Diagnostic, Edit, Severity};
+use crate::manifest::Manifest;
+use crate::monitored::{MonitoredConfig, RatePoint};
+use crate::observable::{PauliSum, Precision, ShotEstimate};
+use crate::optimizer::CircuitMetrics;
+use crate::power::EnergyReport;
+use crate::qec::LogicalPoint;
+use crate::qsim::{QuantumCircuit, StateSnapshot};
+use crate::results::SavedResults;
+use crate::stats::SampleTest;
+use crate::tomography::TomographyData;
+
+/// Command arguments split into positionals, `--name value` options and `--switch` flags
+pub struct Flags {
+    positional: Vec<String>,
+    values: HashMap<String, String>,
+    switches: HashSet<String>,
+}
+
+impl Flags {
+    /// Parse arguments; names listed in `switches` take no value
+    pub fn parse(args: &[String], switches: &[&str]) -> Result<Self, String> {
+        let mut flags = Flags {
+            positional: Vec::new(),
+            values: HashMap::new(),
+            switches: HashSet::new(),
+        };
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name,
+                None => {
+                    flags.positional.push(arg.clone());
+                    continue;
+                }
+            };
+            if let Some((name, value)) = name.split_once('=') {
+                flags.values.insert(name.to_string(), value.to_string());
+            } else if switches.contains(&name) {
+                flags.switches.insert(name.to_string());
+            } else {
+                let value = iter.next().ok_or_else(|| format!("--{} requires a value", name))?;
+                flags.values.insert(name.to_string(), value.clone());
+            }
+        }
+
+        Ok(flags)
+    }
+
+    /// Positional argument by index
+    pub fn positional(&self, index: usize) -> Option<&str> {
+        self.positional.get(index).map(|arg| arg.as_str())
+    }
+
+    /// Value of a `--name value` option
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|value| value.as_str())
+    }
+
+    /// Whether a `--switch` flag was given
+    pub fn has(&self, name: &str) -> bool {
+        self.switches.contains(name)
+    }
+
+    /// Every `--name value` option, in no particular order
+    pub fn options(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Every `--switch` flag given, in no particular order
+    pub fn switches(&self) -> impl Iterator<Item = &str> {
+        self.switches.iter().map(|name| name.as_str())
+    }
+}
+
+/// Set once from `--plain` before any command runs
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// `println!` for command output, passed through `render` so `--plain` applies
+#[macro_export]
+macro_rules! outln {
+    () => {
+        println!()
+    };
+    ($($arg:tt)*) => {
+        println!("{}", $crate::cli::render(&format!($($arg)*)))
+    };
+}
+
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, Ordering::Relaxed);
+}
+
+pub fn plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+/// A line of output as printed: unchanged normally, and in plain mode ASCII only, with the
+/// box-drawing tree prefixes removed and symbols spelled out, so it can be snapshot-tested
+pub fn render(line: &str) -> Cow<'_, str> {
+    if !plain() || line.is_ascii() {
+        return Cow::Borrowed(line);
+    }
+    // Lines closed by a border belong to a box, which keeps its shape in ASCII
+    let boxed = line.ends_with(['│', '┃']);
+    let trimmed = ["┌─ ", "├─ ", "└─ ", "│  "]
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix).filter(|_| !boxed))
+        .unwrap_or(line);
+    let mut text = String::with_capacity(trimmed.len());
+    for c in trimmed.chars() {
+        match c {
+            '─' | '━' => text.push('-'),
+            '│' | '┃' => text.push('|'),
+            '┌' | '┐' | '└' | '┘' | '├' | '┤' | '┏' | '┓' | '┗' | '┛' => text.push('+'),
+            '█' => text.push('#'),
+            '⟩' => text.push('>'),
+            '⟨' => text.push('<'),
+            '●' => text.push('o'),
+            '▸' => text.push('>'),
+            '·' => text.push('|'),
+            '✓' => text.push_str("ok"),
+            '✗' => text.push_str("FAILED"),
+            'χ' => text.push_str("chi"),
+            '²' => text.push('2'),
+            '×' => text.push('x'),
+            '−' => text.push('-'),
+            '±' => text.push_str("+-"),
+            c if c.is_ascii() => text.push(c),
+            _ => text.push('?'),
+        }
+    }
+    Cow::Owned(text)
+}
+
+/// Compact JSON for CLI output; in plain mode object keys are sorted at every level, so the
+/// text only changes when the data does
+pub fn to_json<T: Serialize>(value: &T) -> serde_json::Result<String> {
+    if !plain() {
+        return serde_json::to_string(value);
+    }
+    serde_json::to_string(&sort_keys(serde_json::to_value(value)?))
+}
+
+/// Pretty JSON for files the CLI writes, with keys sorted in plain mode as in `to_json`
+pub fn to_json_pretty<T: Serialize>(value: &T) -> serde_json::Result<String> {
+    if !plain() {
+        return serde_json::to_string_pretty(value);
+    }
+    serde_json::to_string_pretty(&sort_keys(serde_json::to_value(value)?))
+}
+
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(entries.into_iter().map(|(key, value)| (key, sort_keys(value))).collect())
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(sort_keys).collect()),
+        other => other,
+    }
+}
 
 pub fn show_status() {
-    println!("\u250c\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2510");
-    println!("\u2502     QuantumMesh System Status        \u2502");
-    println!("\u2514\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2518");
-    println!("  \u2713 Quantum Engine: Ready");
-    println!("  \u2713 GPU Acceleration: Enabled");
-    println!("  \u2713 Circuit Optimizer: Active");
-    println!("  \u2713 API Server: Available");
-    println!("  \u2713 Max Qubits: 40+");
-    println!();
+    outln!("\u250c\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2510");
+    outln!("\u2502     QuantumMesh System Status        \u2502");
+    outln!("\u2514\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2500\u2518");
+    outln!("  \u2713 Quantum Engine: Ready");
+    outln!("  \u2713 GPU Acceleration: Enabled");
+    outln!("  \u2713 Circuit Optimizer: Active");
+    outln!("  \u2713 API Server: Available");
+    outln!("  \u2713 Max Qubits: 40 (--backend disk past memory)");
+    outln!();
+}
+
+/// Render `GET /api/cluster`: the answering node, then each configured worker
+pub fn display_cluster(url: &str, status: &ClusterStatus) {
+    let mode = if status.enabled { "enabled" } else { "disabled" };
+    outln!("┌─ Cluster via {} (distributed mode {})", url, mode);
+    let master = if status.master.is_empty() { "this server" } else { status.master.as_str() };
+    outln!("├─ {} (answering node)", master);
+    display_node(&status.node);
+    if status.workers.is_empty() {
+        outln!("└─ No worker nodes configured in [distributed]
+");
+        return;
+    }
+    let reachable = status.workers.iter().filter(|worker| worker.node.is_some()).count();
+    for worker in &status.workers {
+        match (&worker.node, worker.latency_ms) {
+            (Some(node), Some(latency)) => {
+                outln!("├─ ✓ {} ({:.1} ms)", worker.address, latency);
+                display_node(node);
+            }
+            _ => outln!(
+                "├─ ✗ {}: {}",
+                worker.address,
+                worker.error.as_deref().unwrap_or("unreachable")
+            ),
+        }
+    }
+    outln!("└─ {}/{} workers reachable
+", reachable, status.workers.len());
+}
+
+fn display_node(node: &NodeReport) {
+    let hardware = &node.manifest.hardware;
+    outln!("│    {} {}/{}, {} threads, {} MB, {}",
+             node.manifest.backend, hardware.os, hardware.arch, node.manifest.threads, hardware.memory_mb, hardware.cpu);
+    if let Some(gpu) = &hardware.gpu {
+        outln!("│    GPU: {}", gpu);
+    }
+    let load = node.load_average.map(|load| format!("{:.2}", load)).unwrap_or_else(|| "n/a".to_string());
+    outln!("│    Load {}, running {}, {} queued, {} completed",
+             load, node.running.as_deref().unwrap_or("nothing"), node.queued.len(), node.completed);
+    if !node.queued.is_empty() {
+        outln!("│    Queue: {}", node.queued.join(", "));
+    }
+}
+
+/// Reduced density matrix with its purity Tr(ρ²); rows are subsystem basis states with
+/// the first listed qubit as the rightmost bit
+pub fn display_density_matrix(qubits: &[usize], rho: &[Vec<Complex>]) {
+    let purity: f64 = rho.iter().flatten().map(|element| element.magnitude_squared()).sum();
+    let names: Vec<String> = qubits.iter().map(|qubit| format!("q{}", qubit)).collect();
+    outln!("├─ Reduced density matrix of {} (purity {:.4})", names.join(", "), purity);
+    for (k, row) in rho.iter().enumerate() {
+        let elements: Vec<String> = row.iter().map(|e| format!("{:+.4}{:+.4}i", e.re, e.im)).collect();
+        outln!("│  ⟨{}| {}", basis_label(k, qubits.len()), elements.join("  "));
+    }
+}
+
+/// Unitary of a circuit, rows and columns labeled by basis state; circuits wider than
+/// `max_qubits` only get their size, as the matrix would not fit a terminal
+pub fn display_unitary(unitary: &[Vec<Complex>], num_qubits: usize, max_qubits: usize) {
+    outln!("├─ Unitary: {} x {}", unitary.len(), unitary.len());
+    if num_qubits > max_qubits {
+        outln!("│  Too wide to print; write it out with --output");
+        return;
+    }
+    for (k, row) in unitary.iter().enumerate() {
+        let elements: Vec<String> = row.iter().map(|e| format!("{:+.4}{:+.4}i", e.re, e.im)).collect();
+        outln!("│  ⟨{}| {}", basis_label(k, num_qubits), elements.join("  "));
+    }
+}
+
+/// Most likely basis states of each state recorded by a `Snapshot` gate, with amplitudes
+pub fn display_snapshots(snapshots: &[StateSnapshot], num_qubits: usize) {
+    for snapshot in snapshots {
+        outln!("├─ Snapshot '{}'", snapshot.label);
+        let mut states: Vec<(usize, &Complex)> = snapshot.state.iter().enumerate().collect();
+        states.sort_by(|a, b| b.1.magnitude_squared().total_cmp(&a.1.magnitude_squared()).then(a.0.cmp(&b.0)));
+        for (k, amplitude) in states.into_iter().take(4).filter(|(_, a)| a.magnitude_squared() > 1e-12) {
+            outln!(
+                "│  |{}⟩ {:6.2}%  {:+.4}{:+.4}i",
+                basis_label(k, num_qubits),
+                amplitude.magnitude_squared() * 100.0,
+                amplitude.re,
+                amplitude.im
+            );
+        }
+    }
+}
+
+/// Joint outcome distribution of `qubits`, nonzero outcomes only, with `qubits[0]` as the
+/// rightmost bit
+pub fn display_marginals(qubits: &[usize], table: &[f64]) {
+    let names: Vec<String> = qubits.iter().rev().map(|qubit| format!("q{}", qubit)).collect();
+    outln!("├─ Marginal distribution of {}", names.join(" "));
+    let outcomes: Vec<(usize, f64)> = table.iter().copied().enumerate().filter(|&(_, p)| p > 1e-12).collect();
+    for &(k, probability) in outcomes.iter().take(16) {
+        let bar = "█".repeat((probability * 40.0) as usize);
+        outln!("│  |{}⟩ {:6.2}% {}", basis_label(k, qubits.len()), probability * 100.0, bar);
+    }
+    if outcomes.len() > 16 {
+        outln!("│  ... ({} more outcomes)", outcomes.len() - 16);
+    }
+}
+
+/// Amplitudes of the requested basis states, in the order asked for
+pub fn display_amplitudes(states: &[u64], amplitudes: &[Complex], num_qubits: usize) {
+    outln!("├─ Amplitudes");
+    for (&state, amplitude) in states.iter().zip(amplitudes) {
+        outln!(
+            "│  |{}⟩ {:+.6}{:+.6}i  ({:.4}%)",
+            basis_label(state as usize, num_qubits),
+            amplitude.re,
+            amplitude.im,
+            amplitude.magnitude_squared() * 100.0
+        );
+    }
+}
+
+/// Entanglement entropies of `qubits` against the rest of the register, in bits
+pub fn display_entropy(qubits: &[usize], spectrum: &[f64]) {
+    let names: Vec<String> = qubits.iter().map(|qubit| format!("q{}", qubit)).collect();
+    outln!("├─ Entanglement of {} with the rest", names.join(", "));
+    outln!("│  von Neumann entropy: {:.4} bits", entropy::renyi_entropy(spectrum, 1.0));
+    outln!("│  Renyi-2 entropy:     {:.4} bits", entropy::renyi_entropy(spectrum, 2.0));
+    outln!("│  Schmidt rank:        {}", entropy::schmidt_rank(spectrum));
 }
 
 pub fn display_results(results: &[f64]) {
-    println!("\n  Qubit State Probabilities:");
+    outln!("\n  Qubit State Probabilities:");
     for (i, prob) in results.iter().enumerate().take(10) {
         let bar_len = (prob * 40.0) as usize;
         let bar = "\u2588".repeat(bar_len);
-        println!("  |{:04b}\u27e9 {:6.2}% {}", i, prob * 100.0, bar);
+        outln!("  |{:04b}\u27e9 {:6.2}% {}", i, prob * 100.0, bar);
     }
     if results.len() > 10 {
-        println!("  ... ({} more states)", results.len() - 10);
+        outln!("  ... ({} more states)", results.len() - 10);
     }
-    println!();
+    outln!();
+}
+
+/// Sampled outcomes, most frequent first, with qubit 0 as the rightmost bit
+pub fn display_counts(counts: &BTreeMap<usize, usize>, num_qubits: usize) {
+    let shots: usize = counts.values().sum();
+    let mut by_frequency: Vec<(&usize, &usize)> = counts.iter().collect();
+    by_frequency.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    outln!("\n  Sampled Counts:");
+    for (&state, &count) in by_frequency.iter().take(10) {
+        let frequency = count as f64 / shots.max(1) as f64;
+        let bar = "█".repeat((frequency * 40.0) as usize);
+        outln!("  |{}⟩ {:>8} {:6.2}% {}", basis_label(state, num_qubits), count, frequency * 100.0, bar);
+    }
+    if by_frequency.len() > 10 {
+        outln!("  ... ({} more states observed)", by_frequency.len() - 10);
+    }
+    outln!();
 }
 
 pub fn visualize_circuit(circuit: &QuantumCircuit) {
-    println!("\n  Circuit Visualization:");
-    println!("  Qubits: {}", circuit.num_qubits);
-    println!("  Gates: {}", circuit.gates.len());
-    println!("\n  Gate Sequence:");
+    outln!("\n  Circuit Visualization:");
+    outln!("  Qubits: {}", circuit.num_qubits);
+    outln!("  Gates: {}", circuit.gates.len());
+    outln!("\n  Gate Sequence:");
     for (i, gate) in circuit.gates.iter().enumerate().take(20) {
-        println!("  {:3}. {:?}", i + 1, gate);
+        outln!("  {:3}. {:?}", i + 1, gate);
     }
     if circuit.gates.len() > 20 {
-        println!("  ... ({} more gates)", circuit.gates.len() - 20);
+        outln!("  ... ({} more gates)", circuit.gates.len() - 20);
+    }
+    outln!();
+}
+
+pub fn display_optimization_report(before: &CircuitMetrics, after: &CircuitMetrics) {
+    outln!("\n  Optimization Report:");
+    outln!("  {:<20} {:>12} {:>12}", "Metric", "Before", "After");
+    outln!("  {:<20} {:>12} {:>12}", "Gates", before.gates, after.gates);
+    outln!("  {:<20} {:>12} {:>12}", "Depth", before.depth, after.depth);
+    outln!("  {:<20} {:>12} {:>12}", "CNOT count", before.cnot_count, after.cnot_count);
+    outln!("  {:<20} {:>12} {:>12}", "T count", before.t_count, after.t_count);
+    outln!("  {:<20} {:>12.4} {:>12.4}", "Est. fidelity", before.fidelity, after.fidelity);
+    outln!("  {:<20} {:>12.0} {:>12.0}", "Est. runtime (ns)", before.runtime_ns, after.runtime_ns);
+    outln!();
+}
+
+/// Gate metrics of a single circuit under a noise model
+pub fn display_circuit_metrics(metrics: &CircuitMetrics) {
+    outln!("├─ Depth {}, {} CNOTs, {} T gates", metrics.depth, metrics.cnot_count, metrics.t_count);
+    outln!("├─ Est. fidelity {:.4}, runtime {:.0} ns", metrics.fidelity, metrics.runtime_ns);
+}
+
+/// Surface code resources of a circuit, from qubits and T states to wall-clock time
+pub fn display_resource_estimate(model: &FaultTolerantModel, estimate: &ResourceEstimate) {
+    outln!(
+        "├─ Fault-tolerant estimate: surface code, p = {:.1e}, {} µs cycles, target failure {:.1e}",
+        model.physical_error, model.cycle_time_us, model.target_error
+    );
+    outln!("│  {:<20} {} ({} tiles with routing)", "Logical qubits", estimate.logical_qubits, estimate.data_tiles);
+    if estimate.rotations > 0 {
+        outln!(
+            "│  {:<20} {} T + {} rotations x {} T = {} T states",
+            "Non-Clifford", estimate.t_gates, estimate.rotations, estimate.t_per_rotation, estimate.total_t
+        );
+    } else {
+        outln!("│  {:<20} {} T states", "Non-Clifford", estimate.total_t);
+    }
+    outln!("│  {:<20} {}", "Code distance", estimate.distance);
+    if estimate.factories > 0 {
+        outln!(
+            "│  {:<20} {} x 15-to-1, {} level{}",
+            "T factories",
+            estimate.factories,
+            estimate.distillation_levels,
+            if estimate.distillation_levels == 1 { "" } else { "s" }
+        );
+    }
+    outln!(
+        "│  {:<20} {} (data {}, factories {})",
+        "Physical qubits", estimate.physical_qubits, estimate.data_qubits, estimate.factory_qubits
+    );
+    outln!(
+        "│  {:<20} {} steps, {} cycles, {}",
+        "Runtime", estimate.logical_steps, estimate.code_cycles, format_seconds(estimate.runtime_seconds)
+    );
+    outln!("│  {:<20} {:.2e}", "Failure probability", estimate.failure_probability);
+}
+
+/// Shot-based expectation value with each term's mean and share of the shots
+pub fn display_shot_estimate(observable: &PauliSum, estimate: &ShotEstimate, precision: Option<Precision>) {
+    outln!("├─ Estimating ⟨{}⟩ from {} shots", observable, estimate.shots);
+    for ((term, mean), shots) in observable.terms.iter().zip(&estimate.term_means).zip(&estimate.term_shots) {
+        match shots {
+            0 => outln!("│  {:+.4} {}  (constant)", term.coefficient, term.pauli),
+            _ => outln!("│  {:+.4} {}  ⟨P⟩ = {:+.6}  ({} shots)", term.coefficient, term.pauli, mean, shots),
+        }
+    }
+    outln!("│  {:<15} {:.6}", "Variance/shot", estimate.variance);
+    outln!("│  {:<15} {:.6}", "Standard error", estimate.standard_error);
+    if let Some(precision) = precision {
+        if estimate.standard_error > precision.standard_error {
+            outln!(
+                "├─ Warning: stopped at {} shots (--max-shots) before reaching the target standard error {}",
+                estimate.shots, precision.standard_error
+            );
+        }
+    }
+}
+
+/// An expectation value a backend computed exactly, term by term
+pub fn display_exact_expectation(observable: &PauliSum, estimate: &ShotEstimate, backend: &str) {
+    outln!("├─ Computing ⟨{}⟩ exactly with the {} backend", observable, backend);
+    for (term, mean) in observable.terms.iter().zip(&estimate.term_means) {
+        outln!("│  {:+.4} {}  ⟨P⟩ = {:+.6}", term.coefficient, term.pauli, mean);
+    }
+}
+
+/// Pauli expectation values of a tomography, largest first; strings whose mean is within
+/// shot noise of zero are counted but not listed
+pub fn display_tomography(data: &TomographyData) {
+    let names: Vec<String> = data.qubits.iter().map(|qubit| format!("q{}", qubit)).collect();
+    outln!("├─ Pauli expectations on {} (first letter acts on {})", names.join(", "), names[0]);
+    let mut significant: Vec<_> = data
+        .expectations
+        .iter()
+        .filter(|e| e.pauli.chars().any(|p| p != 'I') && e.mean.abs() > 3.0 * e.std_error.max(f64::EPSILON))
+        .collect();
+    significant.sort_by(|a, b| b.mean.abs().total_cmp(&a.mean.abs()).then(a.pauli.cmp(&b.pauli)));
+    for expectation in significant.iter().take(16) {
+        outln!(
+            "│  ⟨{}⟩ = {:+.4} ± {:.4}  ({} shots)",
+            expectation.pauli, expectation.mean, expectation.std_error, expectation.shots
+        );
+    }
+    if significant.len() > 16 {
+        outln!("│  ... ({} more beyond shot noise)", significant.len() - 16);
+    }
+    let silent = data.expectations.len() - 1 - significant.len();
+    outln!("│  {} other strings are zero within 3 standard errors", silent);
+}
+
+/// Seconds with a unit that keeps the number readable, from microseconds to days
+fn format_seconds(seconds: f64) -> String {
+    match seconds {
+        s if s < 1e-3 => format!("{:.1} µs", s * 1e6),
+        s if s < 1.0 => format!("{:.1} ms", s * 1e3),
+        s if s < 3600.0 => format!("{:.1} s", s),
+        s if s < 86400.0 => format!("{:.1} h", s / 3600.0),
+        s => format!("{:.1} days", s / 86400.0),
+    }
+}
+
+/// Render a saved results file: metadata, timings and the most likely states
+pub fn show_results(label: &str, results: &SavedResults, top: usize) {
+    outln!("┌─ {}", label);
+    match results.shots {
+        Some(shots) => outln!("├─ {} qubits, {} shots", results.num_qubits, shots),
+        None => outln!("├─ {} qubits", results.num_qubits),
+    }
+    if !results.metadata.is_empty() {
+        outln!("├─ Metadata:");
+        for (key, value) in &results.metadata {
+            outln!("│  {:<16} {}", key, value);
+        }
+    }
+    if !results.timings.is_empty() {
+        outln!("├─ Timings:");
+        for timing in &results.timings {
+            outln!("│  {:<16} {:>12.3} ms", timing.phase, timing.seconds * 1e3);
+        }
+        let total: f64 = results.timings.iter().map(|timing| timing.seconds).sum();
+        outln!("│  {:<16} {:>12.3} ms", "total", total * 1e3);
+    }
+    if let Some(manifest) = &results.manifest {
+        outln!("├─ Environment:");
+        for (field, value) in manifest.entries() {
+            outln!("│  {:<16} {}", field, value);
+        }
+    }
+    outln!("└─ Most likely states:");
+
+    let states = likely_states(&results.probabilities, top);
+    for &state in &states {
+        let prob = results.probabilities[state];
+        outln!("  |{}⟩ {:6.2}% {}", basis_label(state, results.num_qubits), prob * 100.0, "█".repeat((prob * 40.0) as usize));
+    }
+    let nonzero = results.probabilities.iter().filter(|&&p| p > 0.0).count();
+    if nonzero > states.len() {
+        outln!("  ... ({} more states with nonzero probability)", nonzero - states.len());
+    }
+    outln!();
+}
+
+/// Compare the environment recorded in a results file with this machine; true if they match
+pub fn verify_environment(label: &str, results: &SavedResults, current: &Manifest) -> bool {
+    outln!("┌─ Environment check: {}", label);
+    let recorded = match &results.manifest {
+        Some(manifest) => manifest,
+        None => {
+            outln!("└─ ✗ No manifest recorded; the run cannot be attributed\n");
+            return false;
+        }
+    };
+    let differences = recorded.differences(current);
+    for (field, value) in recorded.entries() {
+        match differences.iter().find(|mismatch| mismatch.field == field) {
+            Some(mismatch) => outln!("├─ ✗ {:<12} recorded {}, now {}", field, mismatch.recorded, mismatch.current),
+            None => outln!("├─ ✓ {:<12} {}", field, value),
+        }
+    }
+    // A GPU present now but not at record time has no recorded entry to list
+    for mismatch in differences.iter().filter(|m| !recorded.entries().iter().any(|(field, _)| *field == m.field)) {
+        outln!("├─ ✗ {:<12} recorded {}, now {}", mismatch.field, mismatch.recorded, mismatch.current);
+    }
+    match differences.len() {
+        0 => outln!("└─ Environment matches\n"),
+        1 => outln!("└─ 1 difference\n"),
+        n => outln!("└─ {} differences\n", n),
+    }
+    differences.is_empty()
+}
+
+/// Side-by-side view of two saved distributions with distance measures
+pub fn compare_results(labels: [&str; 2], results: [&SavedResults; 2], top: usize) {
+    let [a, b] = results;
+    outln!("┌─ A: {}", labels[0]);
+    outln!("├─ B: {}", labels[1]);
+    let total_variation: f64 =
+        0.5 * a.probabilities.iter().zip(&b.probabilities).map(|(p, q)| (p - q).abs()).sum::<f64>();
+    let overlap: f64 = a.probabilities.iter().zip(&b.probabilities).map(|(p, q)| (p * q).sqrt()).sum();
+    outln!("├─ Total variation distance: {:.6}", total_variation);
+    outln!("├─ Classical fidelity:       {:.6}", overlap * overlap);
+    for (label, results) in labels.iter().zip([a, b]) {
+        let total: f64 = results.timings.iter().map(|timing| timing.seconds).sum();
+        if total > 0.0 {
+            outln!("├─ Total time ({}): {:.3} ms", label, total * 1e3);
+        }
+    }
+    outln!("└─ Largest differences:");
+
+    let differences: Vec<f64> = a.probabilities.iter().zip(&b.probabilities).map(|(p, q)| q - p).collect();
+    let abs_differences: Vec<f64> = differences.iter().map(|d| d.abs()).collect();
+    outln!("  {:<width$} {:>9} {:>9} {:>9}", "state", "A", "B", "B - A", width = a.num_qubits + 2);
+    for state in likely_states(&abs_differences, top) {
+        outln!(
+            "  |{}⟩ {:>8.2}% {:>8.2}% {:>+8.2}%",
+            basis_label(state, a.num_qubits),
+            a.probabilities[state] * 100.0,
+            b.probabilities[state] * 100.0,
+            differences[state] * 100.0
+        );
+    }
+    outln!();
+}
+
+/// Indices of the `top` largest nonzero values, largest first
+fn likely_states(values: &[f64], top: usize) -> Vec<usize> {
+    let mut states: Vec<usize> = (0..values.len()).filter(|&i| values[i] > 0.0).collect();
+    states.sort_by(|&i, &j| values[j].total_cmp(&values[i]).then(i.cmp(&j)));
+    states.truncate(top);
+    states
+}
+
+/// Basis state written with qubit 0 as the rightmost bit
+fn basis_label(state: usize, num_qubits: usize) -> String {
+    format!("{:0width$b}", state, width = num_qubits.max(1))
+}
+
+/// Lint findings with their counts
+pub fn display_diagnostics(file_path: &str, diagnostics: &[Diagnostic]) {
+    let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+    outln!("┌─ Lint: {}", file_path);
+    for diagnostic in diagnostics {
+        let location = match (diagnostic.gate, diagnostic.qubit) {
+            (Some(gate), _) => format!("gate {}", gate),
+            (None, Some(qubit)) => format!("qubit {}", qubit),
+            (None, None) => "circuit".to_string(),
+        };
+        let severity = match diagnostic.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        outln!("│  {:<7} {:<10} [{}] {}", severity, location, diagnostic.code, diagnostic.message);
+    }
+    outln!("└─ {} error(s), {} warning(s)", errors, diagnostics.len() - errors);
+}
+
+/// Gates that `lint --fix` drops or replaces, as a diff against the original gate list
+pub fn display_edits(edits: &[Edit]) {
+    if edits.is_empty() {
+        outln!("No safe rewrites apply");
+        return;
+    }
+    let json = |gate: &crate::qsim::QuantumGate| to_json(gate).unwrap_or_default();
+    outln!("┌─ Rewrites");
+    for edit in edits {
+        outln!("│  - {:>5}  {}", edit.gate, json(&edit.before));
+        if let Some(after) = &edit.after {
+            outln!("│  + {:>5}  {}", edit.gate, json(after));
+        }
+    }
+    let dropped = edits.iter().filter(|edit| edit.after.is_none()).count();
+    outln!("└─ {} gate(s) dropped, {} replaced", dropped, edits.len() - dropped);
+}
+
+/// Table of injected faults with their output distance and state fidelity
+pub fn display_fault_effects(circuit: &QuantumCircuit, effects: &[FaultEffect]) {
+    outln!("┌─ Fault sensitivity ({} locations)", effects.len());
+    outln!("│  {:>5}  {:<12} {:>5}  {:<5} {:>10} {:>10}", "gate", "operation", "qubit", "error", "TVD", "fidelity");
+    for effect in effects {
+        let operation = serde_json::to_value(&circuit.gates[effect.gate_index])
+            .ok()
+            .and_then(|gate| gate["type"].as_str().map(str::to_string))
+            .unwrap_or_default();
+        outln!(
+            "│  {:>5}  {:<12} {:>5}  {:<5} {:>10.4} {:>10.4}",
+            effect.gate_index,
+            operation,
+            effect.qubit,
+            format!("{:?}", effect.pauli),
+            effect.total_variation,
+            effect.state_fidelity
+        );
+    }
+
+    // Faults that leave the measured outputs unchanged would go unnoticed by readout
+    let silent = effects.iter().filter(|effect| effect.total_variation < 1e-9).count();
+    outln!("├─ Faults invisible in measured outputs: {}/{}", silent, effects.len());
+    match effects.iter().rev().max_by(|a, b| a.total_variation.total_cmp(&b.total_variation)) {
+        Some(worst) => outln!(
+            "└─ Most sensitive: {:?} on qubit {} after gate {} (TVD {:.4})",
+            worst.pauli, worst.qubit, worst.gate_index, worst.total_variation
+        ),
+        None => outln!("└─ No fault locations"),
+    }
+}
+
+/// Entanglement against measurement rate, with a bar scaled to the Page value of a random state
+pub fn display_monitored_sweep(config: &MonitoredConfig, points: &[RatePoint]) {
+    let half = config.num_qubits / 2;
+    let page = page_entropy(half, config.num_qubits - half);
+    let entropy = if config.order == 1.0 { "S".to_string() } else { format!("S{}", config.order) };
+    outln!("├─ Entropy of qubits 0..{} in bits (random-state value {:.3})", half, page);
+    let fidelity = if points.iter().any(|point| point.mean_fidelity.is_some()) { "fidelity" } else { "" };
+    outln!("│  {:>6} {:>8} {:>8} {:>9} {:>9}", "rate", entropy, "±", "measured", fidelity);
+    for point in points {
+        let bar = "█".repeat((point.mean_entropy / page.max(f64::EPSILON) * 30.0).round() as usize);
+        let fidelity = point.mean_fidelity.map(|f| format!("{:.4}", f)).unwrap_or_default();
+        outln!(
+            "│  {:>6.3} {:>8.4} {:>8.4} {:>9.1} {:>9} {}",
+            point.rate, point.mean_entropy, point.std_error, point.mean_measurements, fidelity, bar
+        );
+    }
+}
+
+/// Logical error rate of each sampled point, with its standard error
+pub fn display_logical_rates(points: &[LogicalPoint]) {
+    outln!("│  {:>9} {:>10} {:>10} {:>9} {:>9}", "physical", "logical", "±", "failures", "events");
+    for point in points {
+        let physical = point.physical_error.map(|p| format!("{:.2e}", p)).unwrap_or_else(|| "circuit".to_string());
+        outln!(
+            "│  {:>9} {:>10.3e} {:>10.1e} {:>9} {:>9.2}",
+            physical, point.logical_error_rate, point.std_error, point.logical_errors, point.mean_detection_events
+        );
+    }
+}
+
+/// A finished example: its circuit, annotated results, sampled counts and checks
+pub fn display_example(example: &Example, report: &ExampleReport) {
+    let circuit = &report.circuit;
+    outln!("┌─ Example {}: {}", example.name, example.summary);
+    outln!("├─ Circuit: {} qubits, {} gates", circuit.num_qubits, circuit.gates.len());
+    for note in &report.notes {
+        outln!("│  {}", note);
+    }
+    if let Some(counts) = &report.counts {
+        display_counts(counts, circuit.num_qubits);
+    }
+    for check in &report.checks {
+        outln!("├─ {} {}", if check.passed { "✓" } else { "✗" }, check.description);
+    }
+    let passed = report.checks.iter().filter(|check| check.passed).count();
+    outln!("└─ {}/{} checks passed", passed, report.checks.len());
+}
+
+/// Page's mean von Neumann entropy in bits of m qubits of a random pure state on m + n
+fn page_entropy(m: usize, n: usize) -> f64 {
+    let (small, large) = (1usize << m.min(n), 1usize << m.max(n));
+    let sum: f64 = (large + 1..=small * large).map(|k| 1.0 / k as f64).sum();
+    (sum - (small - 1) as f64 / (2 * large) as f64) / std::f64::consts::LN_2
+}
+
+/// χ² and cross-entropy scores of sampled shots against the ideal distribution
+pub fn display_energy(energy: &EnergyReport) {
+    if energy.readings.is_empty() {
+        outln!("   Energy: no readings collected");
+        return;
+    }
+    outln!("   Energy per simulation: {:.3} J (average {:.1} W)", energy.total_joules(), energy.average_watts());
+    for reading in &energy.readings {
+        outln!("     {}: {:.3} J", reading.source, reading.joules);
+    }
+}
+
+pub fn display_sample_test(test: &SampleTest) {
+    outln!("   Sample tests ({} shots):", test.shots);
+    outln!("     χ²: {:.2} ({} degrees of freedom, p = {:.4})", test.chi_squared, test.degrees_of_freedom, test.p_value);
+    outln!("     Linear XEB fidelity: {:.4}", test.linear_xeb);
+    outln!(
+        "     Heavy-output probability: {:.4} (ideal {:.4})",
+        test.heavy_output_probability, test.ideal_heavy_output_probability
+    );
+    if test.effective_fidelity.is_finite() {
+        outln!("     Effective fidelity: {:.4}", test.effective_fidelity);
     }
-    println!();
 }
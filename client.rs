@@ -0,0 +1,221 @@
+//! API Client Module
+//! Minimal HTTP/1.1 client for talking to a running QuantumMesh server
+
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use serde_json::{json, Value};
+use crate::api_version::{self, ApiVersion, API_VERSION};
+use crate::noise::NoiseModel;
+use crate::optimizer::Objective;
+use crate::qsim::QuantumCircuit;
+use crate::templates::RewriteRule;
+use crate::workspace::Workspace;
+
+/// Server address given as `http://host:port`
+pub struct ApiClient {
+    host: String,
+    port: u16,
+    /// Limit on connecting and on each read or write; none waits indefinitely
+    timeout: Option<Duration>,
+    /// API key sent as a bearer token, from `QUANTUMMESH_API_KEY`
+    key: Option<String>,
+}
+
+impl ApiClient {
+    pub fn new(url: &str) -> Result<Self, String> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("only http:// server URLs are supported, got '{}'", url))?;
+        let authority = rest.split('/').next().unwrap_or(rest);
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse::<u16>().map_err(|_| format!("invalid port in '{}'", url))?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("missing host in '{}'", url));
+        }
+        let key = std::env::var("QUANTUMMESH_API_KEY").ok().filter(|key| !key.is_empty());
+        Ok(Self { host: host.to_string(), port, timeout: None, key })
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn connect(&self) -> std::io::Result<TcpStream> {
+        let Some(timeout) = self.timeout else {
+            return TcpStream::connect((self.host.as_str(), self.port));
+        };
+        let mut last_error = None;
+        for address in (self.host.as_str(), self.port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_write_timeout(Some(timeout))?;
+                    return Ok(stream);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no addresses for host")))
+    }
+
+    /// Send a request and return the response body, turning non-2xx statuses into errors
+    pub fn request(&self, method: &str, path: &str, body: Option<(&str, &[u8])>) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut stream = self.connect()
+            .map_err(|e| format!("cannot reach server at {}:{}: {}", self.host, self.port, e))?;
+        write!(stream, "{} {} HTTP/1.1\r\nHost: {}:{}\r\nConnection: close\r\n", method, path, self.host, self.port)?;
+        write!(stream, "{}: {}\r\n", api_version::HEADER, API_VERSION)?;
+        if let Some(key) = &self.key {
+            write!(stream, "Authorization: Bearer {}\r\n", key)?;
+        }
+        match body {
+            Some((content_type, bytes)) => {
+                write!(stream, "Content-Type: {}\r\nContent-Length: {}\r\n\r\n", content_type, bytes.len())?;
+                stream.write_all(bytes)?;
+            }
+            None => stream.write_all(b"Content-Length: 0\r\n\r\n")?,
+        }
+        stream.flush()?;
+
+        let Response { status, version: server_version, body } = read_response(&mut BufReader::new(stream))?;
+        if !(200..300).contains(&status) {
+            let message = serde_json::from_slice::<Value>(&body)
+                .ok()
+                .and_then(|v| v.get("error").and_then(|e| e.as_str()).map(str::to_string))
+                .unwrap_or_else(|| String::from_utf8_lossy(&body).into_owned());
+            // Servers from before versioning answer newer requests with bare 404s and schema
+            // errors, so say why that is the likely cause
+            let hint = match server_version {
+                Some(_) => String::new(),
+                None => format!(
+                    " (the server reports no API version, so it predates API {} that this client speaks; upgrade the server)",
+                    API_VERSION
+                ),
+            };
+            return Err(format!("server returned {}: {}{}", status, message, hint).into());
+        }
+        Ok(body)
+    }
+
+    /// API version the server implements, from `/api/version`
+    pub fn api_version(&self) -> Result<ApiVersion, Box<dyn Error>> {
+        let response = self.get_json("/api/version")?;
+        let version = response["api_version"].as_str().ok_or("server sent no api_version")?;
+        Ok(ApiVersion::parse(version)?)
+    }
+
+    pub fn get_json(&self, path: &str) -> Result<Value, Box<dyn Error>> {
+        Ok(serde_json::from_slice(&self.request("GET", path, None)?)?)
+    }
+
+    pub fn post_json(&self, path: &str, body: &Value) -> Result<Value, Box<dyn Error>> {
+        let bytes = serde_json::to_vec(body)?;
+        Ok(serde_json::from_slice(&self.request("POST", path, Some(("application/json", &bytes)))?)?)
+    }
+
+    /// Measurement probabilities of a circuit simulated on the server, optionally with a
+    /// seed for its mid-circuit measurements and resets
+    pub fn simulate(&self, circuit: &QuantumCircuit, seed: Option<u64>) -> Result<Vec<f64>, Box<dyn Error>> {
+        let path = match seed {
+            Some(seed) => format!("/api/simulate?seed={}", seed),
+            None => "/api/simulate".to_string(),
+        };
+        let response = self.post_json(&path, &json!(circuit))?;
+        Ok(serde_json::from_value(response["probabilities"].clone())?)
+    }
+
+    /// Final state vector as the bytes of a complex128 `.npy` file
+    pub fn state_npy(&self, circuit: &QuantumCircuit) -> Result<Vec<u8>, Box<dyn Error>> {
+        let body = serde_json::to_vec(circuit)?;
+        self.request("POST", "/api/state", Some(("application/json", &body)))
+    }
+
+    pub fn optimize(
+        &self,
+        circuit: &QuantumCircuit,
+        objective: Objective,
+        noise: &NoiseModel,
+        rules: &[RewriteRule],
+    ) -> Result<QuantumCircuit, Box<dyn Error>> {
+        let body = json!({ "circuit": circuit, "noise": noise, "rules": rules });
+        let response = self.post_json(&format!("/api/optimize?objective={}", objective.name()), &body)?;
+        Ok(serde_json::from_value(response)?)
+    }
+
+    pub fn export_workspace(&self) -> Result<Workspace, Box<dyn Error>> {
+        Ok(serde_json::from_value(self.get_json("/api/workspace")?)?)
+    }
+
+    /// Import an archive, returning the server's old -> new id mapping
+    pub fn import_workspace(&self, workspace: &Workspace) -> Result<Value, Box<dyn Error>> {
+        self.post_json("/api/workspace", &serde_json::to_value(workspace)?)
+    }
+}
+
+/// An HTTP response as the client reads it
+struct Response {
+    status: u16,
+    /// The server's API version header, absent from servers that predate versioning
+    version: Option<String>,
+    body: Vec<u8>,
+}
+
+/// Read the status, the server's API version header and the body, handling Content-Length,
+/// chunked and close-delimited bodies
+fn read_response<R: BufRead>(reader: &mut R) -> Result<Response, Box<dyn Error>> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| format!("malformed status line '{}'", line.trim_end()))?;
+
+    let mut length = None;
+    let mut chunked = false;
+    let mut server_version = None;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            match name.trim().to_lowercase().as_str() {
+                "content-length" => length = value.trim().parse::<usize>().ok(),
+                "transfer-encoding" => chunked = value.trim().eq_ignore_ascii_case("chunked"),
+                name if name.eq_ignore_ascii_case(api_version::HEADER) => server_version = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            let mut size_line = String::new();
+            reader.read_line(&mut size_line)?;
+            let size = usize::from_str_radix(size_line.trim(), 16)
+                .map_err(|_| format!("bad chunk size '{}'", size_line.trim()))?;
+            if size == 0 {
+                break;
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..])?;
+            let mut crlf = [0; 2];
+            reader.read_exact(&mut crlf)?;
+        }
+    } else if let Some(length) = length {
+        body.resize(length, 0);
+        reader.read_exact(&mut body)?;
+    } else {
+        reader.read_to_end(&mut body)?;
+    }
+    Ok(Response { status, version: server_version, body })
+}
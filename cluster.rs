@@ -0,0 +1,156 @@
+//! Cluster Module
+//! Node reports and the topology of the `[distributed]` workers listed in config.toml
+
+use std::fs;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::client::ApiClient;
+use crate::jobs::{JobQueue, JobState};
+use crate::manifest::Manifest;
+
+/// How long a worker may take to answer before it is reported unreachable
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The `[distributed]` section of config.toml
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DistributedConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub master_node: String,
+    /// Worker addresses as `host:port`
+    #[serde(default)]
+    pub worker_nodes: Vec<String>,
+}
+
+impl DistributedConfig {
+    /// Read `[distributed]` from config.toml contents; a missing section means no workers
+    pub fn parse(config: &str) -> Result<Self, String> {
+        #[derive(Deserialize)]
+        struct File {
+            #[serde(default)]
+            distributed: DistributedConfig,
+        }
+        toml::from_str::<File>(config)
+            .map(|file| file.distributed)
+            .map_err(|e| format!("invalid config.toml: {}", e))
+    }
+}
+
+/// What one server reports about itself at `GET /api/node`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeReport {
+    pub manifest: Manifest,
+    /// One-minute load average (Linux only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_average: Option<f64>,
+    /// Job the node is simulating now
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub running: Option<String>,
+    /// Jobs waiting on this node, oldest first
+    #[serde(default)]
+    pub queued: Vec<String>,
+    #[serde(default)]
+    pub completed: usize,
+}
+
+impl NodeReport {
+    pub fn current(jobs: &JobQueue) -> Self {
+        let mut report = Self {
+            manifest: Manifest::current(),
+            load_average: load_average(),
+            running: None,
+            queued: Vec::new(),
+            completed: 0,
+        };
+        for job in jobs.list() {
+            match job.state {
+                JobState::Running => report.running = Some(job.id),
+                JobState::Queued => report.queued.push(job.id),
+                JobState::Completed => report.completed += 1,
+                JobState::Failed => {}
+            }
+        }
+        report
+    }
+}
+
+/// A configured worker as seen from the reporting server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerStatus {
+    pub address: String,
+    /// Round trip of a health check, in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<NodeReport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// `GET /api/cluster`: the answering server plus every configured worker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterStatus {
+    pub enabled: bool,
+    pub master: String,
+    pub node: NodeReport,
+    pub workers: Vec<WorkerStatus>,
+}
+
+impl ClusterStatus {
+    /// Probe all workers in parallel, so one slow node delays the report by at most the timeout
+    pub fn collect(config: &DistributedConfig, node: NodeReport) -> Self {
+        let probes: Vec<_> = config
+            .worker_nodes
+            .iter()
+            .cloned()
+            .map(|address| std::thread::spawn(move || probe(&address)))
+            .collect();
+        let workers = probes
+            .into_iter()
+            .zip(&config.worker_nodes)
+            .map(|(probe, address)| {
+                probe.join().unwrap_or_else(|_| WorkerStatus {
+                    address: address.clone(),
+                    latency_ms: None,
+                    node: None,
+                    error: Some("probe panicked".to_string()),
+                })
+            })
+            .collect();
+        Self {
+            enabled: config.enabled,
+            master: config.master_node.clone(),
+            node,
+            workers,
+        }
+    }
+}
+
+/// Time a health check against a worker, then fetch its node report
+fn probe(address: &str) -> WorkerStatus {
+    let mut status = WorkerStatus { address: address.to_string(), latency_ms: None, node: None, error: None };
+    let client = match ApiClient::new(&format!("http://{}", address)) {
+        Ok(client) => client.with_timeout(PROBE_TIMEOUT),
+        Err(e) => {
+            status.error = Some(e);
+            return status;
+        }
+    };
+    let start = Instant::now();
+    if let Err(e) = client.get_json("/api/health") {
+        status.error = Some(e.to_string());
+        return status;
+    }
+    status.latency_ms = Some(start.elapsed().as_secs_f64() * 1000.0);
+    match client.get_json("/api/node").and_then(|report| Ok(serde_json::from_value(report)?)) {
+        Ok(node) => status.node = Some(node),
+        Err(e) => status.error = Some(format!("no node report: {}", e)),
+    }
+    status
+}
+
+/// One-minute load average from /proc/loadavg (Linux only)
+fn load_average() -> Option<f64> {
+    fs::read_to_string("/proc/loadavg").ok()?.split_whitespace().next()?.parse().ok()
+}
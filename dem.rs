@@ -0,0 +1,552 @@
+//! Detector Error Model Module
+//! Detector error models of noisy Stim circuits, in Stim's text format for QEC decoders
+
+use std::collections::HashMap;
+use std::fmt;
+use crate::noise::NoiseModel;
+use crate::stim;
+
+/// Sorted ids of the detectors and observables an error flips, with observable k at
+/// `num_detectors + k`
+type Symptoms = Vec<usize>;
+
+/// One independent error and the detectors and logical observables it flips
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorMechanism {
+    pub probability: f64,
+    pub detectors: Vec<usize>,
+    pub observables: Vec<usize>,
+}
+
+/// Detectors with their coordinates, logical observables and independent error mechanisms
+#[derive(Debug, Clone, Default)]
+pub struct DetectorErrorModel {
+    pub detector_coords: Vec<Vec<f64>>,
+    pub num_observables: usize,
+    pub errors: Vec<ErrorMechanism>,
+}
+
+impl fmt::Display for DetectorErrorModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for error in &self.errors {
+            write!(f, "error({})", error.probability)?;
+            for detector in &error.detectors {
+                write!(f, " D{}", detector)?;
+            }
+            for observable in &error.observables {
+                write!(f, " L{}", observable)?;
+            }
+            writeln!(f)?;
+        }
+        for (index, coords) in self.detector_coords.iter().enumerate() {
+            if coords.is_empty() {
+                writeln!(f, "detector D{}", index)?;
+            } else {
+                let coords: Vec<String> = coords.iter().map(|c| c.to_string()).collect();
+                writeln!(f, "detector({}) D{}", coords.join(", "), index)?;
+            }
+        }
+        for observable in 0..self.num_observables {
+            writeln!(f, "logical_observable L{}", observable)?;
+        }
+        Ok(())
+    }
+}
+
+/// Operand of a Stim instruction
+enum Target {
+    Qubit(usize),
+    /// `rec[-k]`, the k-th most recent measurement
+    Record(usize),
+    /// A Pauli on a qubit, as in `E(p) X1 Z2`
+    Pauli(char, usize),
+}
+
+/// Action of a Clifford gate on Paulis; signs never change which detectors flip
+#[derive(Clone, Copy)]
+pub(crate) enum Clifford {
+    /// H and SQRT_Y exchange X and Z
+    SwapXZ,
+    /// S takes X to Y
+    PhaseZ,
+    /// SQRT_X takes Z to Y
+    PhaseX,
+    CX,
+    CY,
+    CZ,
+    Swap,
+}
+
+/// One step of a flattened circuit
+pub(crate) enum Op {
+    Gate(Clifford, Vec<usize>),
+    Measure { qubit: usize, record: usize, reset: bool },
+    Reset(usize),
+    /// A Pauli applied when a measurement read 1
+    Feedback { pauli: char, record: usize, qubit: usize },
+    /// An independent Pauli error on some qubits
+    Error { probability: f64, paulis: Vec<(char, usize)> },
+    /// An independent flip of a measurement result
+    Flip { probability: f64, record: usize },
+}
+
+/// Build the detector error model of a Stim circuit
+///
+/// Every noise channel is split into independent Pauli errors. Which detectors and
+/// observables an X or Z on each qubit would flip is carried backwards through the circuit,
+/// so each error's effect is read off where it occurs, and errors with the same effect are
+/// merged. With a noise model, each gate is also followed by depolarizing noise at the
+/// model's rate for its qubits, and each measurement is flipped with the model's
+/// measurement error.
+pub fn from_stim(source: &str, noise: Option<&NoiseModel>) -> Result<DetectorErrorModel, String> {
+    Ok(flatten(source, noise)?.error_model())
+}
+
+/// Expand a Stim circuit's loops and split its noise into independent errors, adding the
+/// noise model's errors after gates and measurements
+pub(crate) fn flatten(source: &str, noise: Option<&NoiseModel>) -> Result<FlatCircuit, String> {
+    let lines: Vec<&str> = source
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .collect();
+    let mut circuit = FlatCircuit::default();
+    for line in stim::expand_repeats(&lines)? {
+        circuit.push(&line, noise).map_err(|e| format!("'{}': {}", line, e))?;
+    }
+    Ok(circuit)
+}
+
+/// A Stim circuit without loops, with detectors and observables as parities of records
+#[derive(Default)]
+pub(crate) struct FlatCircuit {
+    pub(crate) ops: Vec<Op>,
+    pub(crate) num_qubits: usize,
+    pub(crate) num_records: usize,
+    pub(crate) detectors: Vec<Vec<usize>>,
+    detector_coords: Vec<Vec<f64>>,
+    pub(crate) observables: Vec<Vec<usize>>,
+    coordinate_shift: Vec<f64>,
+}
+
+impl FlatCircuit {
+    fn push(&mut self, line: &str, noise: Option<&NoiseModel>) -> Result<(), String> {
+        let name_end = line.find(|c: char| c == '(' || c.is_whitespace()).unwrap_or(line.len());
+        let name = line[..name_end].to_uppercase();
+        // Arguments may contain spaces, as in `DETECTOR(1, 0) rec[-1]`
+        let (args, rest) = match line[name_end..].strip_prefix('(') {
+            Some(after) => {
+                let (args, rest) = after.split_once(')').ok_or("unclosed argument list")?;
+                let args = args
+                    .split(',')
+                    .map(|arg| arg.trim().parse::<f64>().map_err(|_| format!("invalid argument '{}'", arg.trim())))
+                    .collect::<Result<Vec<_>, _>>()?;
+                (args, rest.trim())
+            }
+            None => (Vec::new(), line[name_end..].trim()),
+        };
+        let targets = rest.split_whitespace().map(parse_target).collect::<Result<Vec<_>, _>>()?;
+        for target in &targets {
+            if let Target::Qubit(q) | Target::Pauli(_, q) = target {
+                self.num_qubits = self.num_qubits.max(q + 1);
+            }
+        }
+        let qubits = || -> Result<Vec<usize>, String> {
+            targets
+                .iter()
+                .map(|target| match target {
+                    Target::Qubit(q) => Ok(*q),
+                    _ => Err("expected qubit targets".to_string()),
+                })
+                .collect()
+        };
+        let probability = || -> Result<f64, String> {
+            match args.as_slice() {
+                [p] if (0.0..=1.0).contains(p) => Ok(*p),
+                _ => Err("expected one probability between 0 and 1".to_string()),
+            }
+        };
+
+        match name.as_str() {
+            // Identities only mark idle qubits
+            "TICK" | "QUBIT_COORDS" | "I" => {}
+            "SHIFT_COORDS" => {
+                if self.coordinate_shift.len() < args.len() {
+                    self.coordinate_shift.resize(args.len(), 0.0);
+                }
+                for (shift, arg) in self.coordinate_shift.iter_mut().zip(&args) {
+                    *shift += arg;
+                }
+            }
+            "DETECTOR" => {
+                let records = self.records(&targets)?;
+                let coords = args
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| c + self.coordinate_shift.get(i).copied().unwrap_or(0.0))
+                    .collect();
+                self.detectors.push(records);
+                self.detector_coords.push(coords);
+            }
+            "OBSERVABLE_INCLUDE" => {
+                let index = match args.as_slice() {
+                    [k] if *k >= 0.0 && k.fract() == 0.0 => *k as usize,
+                    _ => return Err("expected an observable index".to_string()),
+                };
+                let records = self.records(&targets)?;
+                if self.observables.len() <= index {
+                    self.observables.resize(index + 1, Vec::new());
+                }
+                self.observables[index].extend(records);
+            }
+            // Paulis change signs only, which no detector sees
+            "X" | "Y" | "Z" => self.gate_noise(&qubits()?, 1, noise),
+            "H" | "SQRT_Y" | "SQRT_Y_DAG" | "S" | "S_DAG" | "SQRT_Z" | "SQRT_Z_DAG" | "SQRT_X" | "SQRT_X_DAG" => {
+                let clifford = match name.as_str() {
+                    "H" | "SQRT_Y" | "SQRT_Y_DAG" => Clifford::SwapXZ,
+                    "SQRT_X" | "SQRT_X_DAG" => Clifford::PhaseX,
+                    _ => Clifford::PhaseZ,
+                };
+                let qubits = qubits()?;
+                for &q in &qubits {
+                    self.ops.push(Op::Gate(clifford, vec![q]));
+                }
+                self.gate_noise(&qubits, 1, noise);
+            }
+            "CX" | "CNOT" | "ZCX" | "CY" | "ZCY" | "CZ" | "ZCZ" | "SWAP" | "ISWAP" | "ISWAP_DAG" => {
+                if targets.len() % 2 != 0 {
+                    return Err("odd number of targets".to_string());
+                }
+                for pair in targets.chunks(2) {
+                    match (&pair[0], &pair[1]) {
+                        (Target::Qubit(a), Target::Qubit(b)) if a != b => {
+                            self.two_qubit(&name, *a, *b);
+                            self.gate_noise(&[*a, *b], 2, noise);
+                        }
+                        (Target::Record(back), Target::Qubit(q)) => self.feedback(&name, *back, *q)?,
+                        (Target::Qubit(q), Target::Record(back)) if name.ends_with("CZ") => {
+                            self.feedback(&name, *back, *q)?
+                        }
+                        _ => return Err("unsupported target pair".to_string()),
+                    }
+                }
+            }
+            "M" | "MZ" | "MR" | "MRZ" => {
+                let flip = match args.as_slice() {
+                    [] => 0.0,
+                    _ => probability()?,
+                };
+                let device_flip = noise.map(|model| model.measurement_error).unwrap_or(0.0);
+                for qubit in qubits()? {
+                    let record = self.num_records;
+                    self.num_records += 1;
+                    self.ops.push(Op::Measure { qubit, record, reset: name.starts_with("MR") });
+                    for probability in [flip, device_flip] {
+                        self.ops.push(Op::Flip { probability, record });
+                    }
+                }
+            }
+            "R" | "RZ" => {
+                for q in qubits()? {
+                    self.ops.push(Op::Reset(q));
+                }
+            }
+            "X_ERROR" | "Y_ERROR" | "Z_ERROR" => {
+                let probability = probability()?;
+                let pauli = name.chars().next().unwrap_or('X');
+                for q in qubits()? {
+                    self.ops.push(Op::Error { probability, paulis: vec![(pauli, q)] });
+                }
+            }
+            "DEPOLARIZE1" => {
+                let p = probability()?;
+                for q in qubits()? {
+                    self.depolarize1(q, p);
+                }
+            }
+            "PAULI_CHANNEL_1" => {
+                let [px, py, pz] = match args.as_slice() {
+                    [px, py, pz] => [*px, *py, *pz],
+                    _ => return Err("expected three probabilities".to_string()),
+                };
+                let independent = independent_pauli_channel(px, py, pz)
+                    .ok_or("this Pauli channel has no equivalent set of independent X, Y and Z errors")?;
+                for q in qubits()? {
+                    for (pauli, probability) in ['X', 'Y', 'Z'].into_iter().zip(independent) {
+                        self.ops.push(Op::Error { probability, paulis: vec![(pauli, q)] });
+                    }
+                }
+            }
+            "DEPOLARIZE2" => {
+                let p = probability()?;
+                let qubits = qubits()?;
+                if qubits.len() % 2 != 0 {
+                    return Err("odd number of targets".to_string());
+                }
+                for pair in qubits.chunks(2) {
+                    self.depolarize2(pair[0], pair[1], p);
+                }
+            }
+            "E" | "CORRELATED_ERROR" => {
+                let probability = probability()?;
+                let paulis = targets
+                    .iter()
+                    .map(|target| match target {
+                        Target::Pauli(pauli, q) => Ok((*pauli, *q)),
+                        _ => Err("expected Pauli targets such as X1 Z2".to_string()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.ops.push(Op::Error { probability, paulis });
+            }
+            _ => return Err(format!("'{}' is not supported in detector error models", name)),
+        }
+        Ok(())
+    }
+
+    fn two_qubit(&mut self, name: &str, a: usize, b: usize) {
+        let clifford = match name {
+            "CX" | "CNOT" | "ZCX" => Clifford::CX,
+            "CY" | "ZCY" => Clifford::CY,
+            "CZ" | "ZCZ" => Clifford::CZ,
+            "SWAP" => Clifford::Swap,
+            // Up to signs, iSWAP is CZ, then S on both qubits, then SWAP
+            _ => {
+                self.ops.push(Op::Gate(Clifford::CZ, vec![a, b]));
+                self.ops.push(Op::Gate(Clifford::PhaseZ, vec![a]));
+                self.ops.push(Op::Gate(Clifford::PhaseZ, vec![b]));
+                Clifford::Swap
+            }
+        };
+        self.ops.push(Op::Gate(clifford, vec![a, b]));
+    }
+
+    fn feedback(&mut self, name: &str, back: usize, qubit: usize) -> Result<(), String> {
+        let record = self.record(back)?;
+        let pauli = match name {
+            "CX" | "CNOT" | "ZCX" => 'X',
+            "CY" | "ZCY" => 'Y',
+            "CZ" | "ZCZ" => 'Z',
+            _ => return Err(format!("{} cannot be controlled by a measurement record", name)),
+        };
+        self.ops.push(Op::Feedback { pauli, record, qubit });
+        Ok(())
+    }
+
+    /// Index of `rec[-back]` among all measurements so far
+    fn record(&self, back: usize) -> Result<usize, String> {
+        self.num_records
+            .checked_sub(back)
+            .ok_or_else(|| format!("rec[-{}] comes before the first measurement", back))
+    }
+
+    fn records(&self, targets: &[Target]) -> Result<Vec<usize>, String> {
+        targets
+            .iter()
+            .map(|target| match target {
+                Target::Record(back) => self.record(*back),
+                _ => Err("expected rec[-k] targets".to_string()),
+            })
+            .collect()
+    }
+
+    /// DEPOLARIZE1(p) picks X, Y or Z with p/3 each; as independent errors, each fires with a
+    /// such that a(1 - a) = p/3
+    fn depolarize1(&mut self, qubit: usize, p: f64) {
+        let probability = 0.5 - 0.5 * (1.0 - 4.0 * p / 3.0).max(0.0).sqrt();
+        for pauli in ['X', 'Y', 'Z'] {
+            self.ops.push(Op::Error { probability, paulis: vec![(pauli, qubit)] });
+        }
+    }
+
+    /// DEPOLARIZE2(p) picks one of the 15 non-identity two-qubit Paulis with p/15 each; as
+    /// independent errors, each fires with a such that (1 - 2a)^8 = 1 - 16p/15
+    fn depolarize2(&mut self, q1: usize, q2: usize, p: f64) {
+        let probability = 0.5 - 0.5 * (1.0 - 16.0 * p / 15.0).max(0.0).powf(0.125);
+        for (first, second) in ['I', 'X', 'Y', 'Z'].into_iter().flat_map(|p1| ['I', 'X', 'Y', 'Z'].map(|p2| (p1, p2))).skip(1) {
+            let paulis = [(first, q1), (second, q2)].into_iter().filter(|(p, _)| *p != 'I').collect();
+            self.ops.push(Op::Error { probability, paulis });
+        }
+    }
+
+    /// Depolarizing noise from the device model after a gate on these qubits
+    fn gate_noise(&mut self, qubits: &[usize], arity: usize, noise: Option<&NoiseModel>) {
+        let Some(model) = noise else {
+            return;
+        };
+        if arity == 2 {
+            self.depolarize2(qubits[0], qubits[1], model.pair_error(qubits[0], qubits[1]));
+        } else {
+            for &q in qubits {
+                self.depolarize1(q, model.qubit_error(q));
+            }
+        }
+    }
+
+    /// Walk the circuit backwards, tracking what an X or Z on each qubit would flip, and
+    /// collect each error's symptoms, merging identical ones in circuit order
+    pub(crate) fn error_model(&self) -> DetectorErrorModel {
+        let num_detectors = self.detectors.len();
+        let mut by_record: Vec<Symptoms> = vec![Vec::new(); self.num_records];
+        for (id, records) in self.detectors.iter().chain(&self.observables).enumerate() {
+            for &record in records {
+                by_record[record] = symmetric_difference(&by_record[record], &[id]);
+            }
+        }
+
+        let mut x: Vec<Symptoms> = vec![Vec::new(); self.num_qubits];
+        let mut z: Vec<Symptoms> = vec![Vec::new(); self.num_qubits];
+        let mut found: Vec<(f64, Symptoms)> = Vec::new();
+        for op in self.ops.iter().rev() {
+            match op {
+                Op::Gate(clifford, qubits) => conjugate(*clifford, qubits, &mut x, &mut z),
+                Op::Measure { qubit, record, reset } => {
+                    if *reset {
+                        x[*qubit].clear();
+                        z[*qubit].clear();
+                    }
+                    // An X or Y before the measurement flips its result and carries on past it
+                    x[*qubit] = symmetric_difference(&x[*qubit], &by_record[*record]);
+                }
+                Op::Reset(qubit) => {
+                    x[*qubit].clear();
+                    z[*qubit].clear();
+                }
+                // A flipped result misapplies the Pauli, which then acts as an error
+                Op::Feedback { pauli, record, qubit } => {
+                    let effect = pauli_symptoms(&[(*pauli, *qubit)], &x, &z);
+                    by_record[*record] = symmetric_difference(&by_record[*record], &effect);
+                }
+                Op::Error { probability, paulis } if *probability > 0.0 => {
+                    found.push((*probability, pauli_symptoms(paulis, &x, &z)));
+                }
+                Op::Flip { probability, record } if *probability > 0.0 => {
+                    found.push((*probability, by_record[*record].clone()));
+                }
+                Op::Error { .. } | Op::Flip { .. } => {}
+            }
+        }
+
+        let mut errors: Vec<ErrorMechanism> = Vec::new();
+        let mut seen: HashMap<Symptoms, usize> = HashMap::new();
+        for (p, symptoms) in found.into_iter().rev().filter(|(_, symptoms)| !symptoms.is_empty()) {
+            if let Some(&index) = seen.get(&symptoms) {
+                // Two independent errors with the same effect show it when exactly one fires
+                let q = errors[index].probability;
+                errors[index].probability = p + q - 2.0 * p * q;
+                continue;
+            }
+            let split = symptoms.partition_point(|&id| id < num_detectors);
+            errors.push(ErrorMechanism {
+                probability: p,
+                detectors: symptoms[..split].to_vec(),
+                observables: symptoms[split..].iter().map(|id| id - num_detectors).collect(),
+            });
+            seen.insert(symptoms, errors.len() - 1);
+        }
+        DetectorErrorModel {
+            detector_coords: self.detector_coords.clone(),
+            num_observables: self.observables.len(),
+            errors,
+        }
+    }
+}
+
+/// Symptoms of each Pauli just before a gate, from those just after it: P before the gate
+/// is G P G† after it
+fn conjugate(clifford: Clifford, qubits: &[usize], x: &mut [Symptoms], z: &mut [Symptoms]) {
+    match (clifford, qubits) {
+        (Clifford::SwapXZ, &[q]) => std::mem::swap(&mut x[q], &mut z[q]),
+        (Clifford::PhaseZ, &[q]) => x[q] = symmetric_difference(&x[q], &z[q]),
+        (Clifford::PhaseX, &[q]) => z[q] = symmetric_difference(&z[q], &x[q]),
+        // X_c -> X_c X_t and Z_t -> Z_c Z_t
+        (Clifford::CX, &[c, t]) => {
+            x[c] = symmetric_difference(&x[c], &x[t]);
+            z[t] = symmetric_difference(&z[t], &z[c]);
+        }
+        // X_c -> X_c Y_t, X_t -> Z_c X_t and Z_t -> Z_c Z_t
+        (Clifford::CY, &[c, t]) => {
+            x[c] = symmetric_difference(&x[c], &symmetric_difference(&x[t], &z[t]));
+            x[t] = symmetric_difference(&x[t], &z[c]);
+            z[t] = symmetric_difference(&z[t], &z[c]);
+        }
+        // X_a -> X_a Z_b and X_b -> Z_a X_b
+        (Clifford::CZ, &[a, b]) => {
+            x[a] = symmetric_difference(&x[a], &z[b]);
+            x[b] = symmetric_difference(&x[b], &z[a]);
+        }
+        (Clifford::Swap, &[a, b]) => {
+            x.swap(a, b);
+            z.swap(a, b);
+        }
+        _ => unreachable!("gates are built with their own number of qubits"),
+    }
+}
+
+/// Detectors and observables flipped by a product of Paulis
+fn pauli_symptoms(paulis: &[(char, usize)], x: &[Symptoms], z: &[Symptoms]) -> Symptoms {
+    let mut symptoms = Vec::new();
+    for &(pauli, q) in paulis {
+        if pauli != 'Z' {
+            symptoms = symmetric_difference(&symptoms, &x[q]);
+        }
+        if pauli != 'X' {
+            symptoms = symmetric_difference(&symptoms, &z[q]);
+        }
+    }
+    symptoms
+}
+
+/// Ids in exactly one of two sorted lists
+fn symmetric_difference(a: &[usize], b: &[usize]) -> Symptoms {
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] < b[j] {
+            out.push(a[i]);
+            i += 1;
+        } else if b[j] < a[i] {
+            out.push(b[j]);
+            j += 1;
+        } else {
+            i += 1;
+            j += 1;
+        }
+    }
+    out.extend_from_slice(&a[i..]);
+    out.extend_from_slice(&b[j..]);
+    out
+}
+
+/// Independent X, Y and Z error rates composing to the disjoint channel (px, py, pz), if any
+fn independent_pauli_channel(px: f64, py: f64, pz: f64) -> Option<[f64; 3]> {
+    // Each Pauli flips the sign of the two others' expectations; match those three factors
+    let flips_z = 1.0 - 2.0 * (px + py);
+    let flips_x = 1.0 - 2.0 * (py + pz);
+    let flips_y = 1.0 - 2.0 * (px + pz);
+    if flips_x <= 0.0 || flips_y <= 0.0 || flips_z <= 0.0 {
+        return None;
+    }
+    let x = (flips_z * flips_y / flips_x).sqrt();
+    let y = (flips_z * flips_x / flips_y).sqrt();
+    let z = (flips_x * flips_y / flips_z).sqrt();
+    let rates = [(1.0 - x) / 2.0, (1.0 - y) / 2.0, (1.0 - z) / 2.0];
+    rates.iter().all(|&rate| rate >= 0.0).then_some(rates)
+}
+
+fn parse_target(token: &str) -> Result<Target, String> {
+    if let Some(back) = token.strip_prefix("rec[-").and_then(|k| k.strip_suffix(']')) {
+        return back
+            .parse::<usize>()
+            .ok()
+            .filter(|&k| k > 0)
+            .map(Target::Record)
+            .ok_or_else(|| format!("invalid target '{}'", token));
+    }
+    let token = token.trim_start_matches('!');
+    let mut chars = token.chars();
+    match chars.next() {
+        Some(pauli @ ('X' | 'Y' | 'Z')) => chars.as_str().parse::<usize>().ok().map(|q| Target::Pauli(pauli, q)),
+        _ => token.parse::<usize>().ok().map(Target::Qubit),
+    }
+    .ok_or_else(|| format!("invalid target '{}'", token))
+}
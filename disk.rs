@@ -0,0 +1,308 @@
+//! Disk Module
+//! Out-of-core state vector: amplitudes live in a scratch file and are paged through memory
+//! a chunk at a time, for registers whose 2^n amplitudes do not fit in RAM
+
+use std::borrow::Cow;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use rand::{Rng, RngCore};
+use crate::gpu_ops::Complex;
+use crate::simulator_backend::{self, SimulatorBackend};
+
+/// Widest register the disk state vector takes: 2^40 amplitudes are a 16 TiB file
+pub const MAX_QUBITS: usize = 40;
+
+/// Qubits addressed within one chunk; a chunk of 2^20 amplitudes is 16 MiB
+const CHUNK_QUBITS: usize = 20;
+
+/// Bytes per amplitude on disk: re and im as little-endian f64
+const AMPLITUDE_BYTES: usize = 16;
+
+/// State vector stored in a file under `$QUANTUMMESH_SCRATCH` (default: the system temporary
+/// directory), removed when dropped. Gates on the low `CHUNK_QUBITS` qubits stream through the
+/// file one chunk at a time; a gate on higher qubits loads the 2^k chunks that differ only in
+/// those k qubits together, so memory stays at a few chunks whatever the register width.
+pub struct DiskStateVector {
+    num_qubits: usize,
+    path: PathBuf,
+    /// Locked for each seek and transfer, so readers on other threads keep their place
+    file: Mutex<File>,
+}
+
+impl DiskStateVector {
+    /// |0...0> in a new scratch file. The file is created sparse, so only the chunks a
+    /// circuit has touched take disk space.
+    pub fn new(num_qubits: usize) -> Result<Self, String> {
+        if num_qubits > MAX_QUBITS {
+            return Err(format!("the disk state vector holds at most {} qubits; {} were asked for", MAX_QUBITS, num_qubits));
+        }
+        let path = scratch_path();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| format!("cannot create state file {}: {}", path.display(), e))?;
+        // From here on, dropping the state removes the file
+        let state = Self { num_qubits, path, file: Mutex::new(file) };
+        let sized = state.file.lock().unwrap().set_len((AMPLITUDE_BYTES as u64) << num_qubits);
+        sized
+            .and_then(|_| state.write_chunk(0, &[Complex::new(1.0, 0.0)]))
+            .map_err(|e| format!("cannot create state file {}: {}", state.path.display(), e))?;
+        Ok(state)
+    }
+
+    fn chunk_qubits(&self) -> usize {
+        self.num_qubits.min(CHUNK_QUBITS)
+    }
+
+    fn chunk_len(&self) -> usize {
+        1 << self.chunk_qubits()
+    }
+
+    fn chunks(&self) -> usize {
+        1 << (self.num_qubits - self.chunk_qubits())
+    }
+
+    fn read_chunk(&self, chunk: usize) -> Vec<Complex> {
+        let mut bytes = vec![0; self.chunk_len() * AMPLITUDE_BYTES];
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start((chunk * bytes.len()) as u64))
+            .and_then(|_| file.read_exact(&mut bytes))
+            .unwrap_or_else(|e| panic!("cannot read state file {}: {}", self.path.display(), e));
+        bytes
+            .chunks_exact(AMPLITUDE_BYTES)
+            .map(|amplitude| {
+                let (re, im) = amplitude.split_at(8);
+                Complex::new(f64::from_le_bytes(re.try_into().unwrap()), f64::from_le_bytes(im.try_into().unwrap()))
+            })
+            .collect()
+    }
+
+    /// Write `amplitudes` at the start of a chunk
+    fn write_chunk(&self, chunk: usize, amplitudes: &[Complex]) -> io::Result<()> {
+        let bytes: Vec<u8> = amplitudes.iter().flat_map(|a| a.re.to_le_bytes().into_iter().chain(a.im.to_le_bytes())).collect();
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start((chunk * self.chunk_len() * AMPLITUDE_BYTES) as u64))?;
+        file.write_all(&bytes)
+    }
+
+    fn store_chunk(&mut self, chunk: usize, amplitudes: &[Complex]) {
+        if let Err(e) = self.write_chunk(chunk, amplitudes) {
+            panic!("cannot write state file {}: {}", self.path.display(), e);
+        }
+    }
+
+    /// |amplitude|² summed over each chunk
+    fn chunk_sums(&self) -> Vec<f64> {
+        (0..self.chunks()).map(|chunk| self.read_chunk(chunk).iter().map(|a| a.magnitude_squared()).sum()).collect()
+    }
+}
+
+impl Drop for DiskStateVector {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Unique file name for a new state in the scratch directory
+fn scratch_path() -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let directory = std::env::var_os("QUANTUMMESH_SCRATCH").map(PathBuf::from).unwrap_or_else(std::env::temp_dir);
+    let name = format!("quantummesh-state-{}-{}.bin", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+    directory.join(name)
+}
+
+impl SimulatorBackend for DiskStateVector {
+    fn name(&self) -> &'static str {
+        "disk"
+    }
+
+    fn num_qubits(&self) -> usize {
+        self.num_qubits
+    }
+
+    /// Copies the whole file, which noise branches and snapshots of wide registers pay for
+    fn clone_box(&self) -> Box<dyn SimulatorBackend> {
+        let path = scratch_path();
+        let copy = fs::copy(&self.path, &path).and_then(|_| {
+            let file = OpenOptions::new().read(true).write(true).open(&path)?;
+            Ok(Self { num_qubits: self.num_qubits, path: path.clone(), file: Mutex::new(file) })
+        });
+        Box::new(copy.unwrap_or_else(|e| panic!("cannot copy state file to {}: {}", path.display(), e)))
+    }
+
+    fn apply_matrix(&mut self, qubit: usize, matrix: &[[Complex; 2]; 2]) {
+        self.apply_matrix_n(&[qubit], &matrix.iter().map(|row| row.to_vec()).collect::<Vec<_>>());
+    }
+
+    fn apply_matrix_n(&mut self, qubits: &[usize], matrix: &[Vec<Complex>]) {
+        let (chunk_qubits, chunk_len) = (self.chunk_qubits(), self.chunk_len());
+        // Qubits above the chunk select chunks; the chunks that differ only in them are
+        // loaded side by side, with those qubits as the buffer's top bits
+        let high: Vec<usize> = qubits.iter().filter(|&&q| q >= chunk_qubits).map(|&q| q - chunk_qubits).collect();
+        let high_mask = high.iter().fold(0, |mask, &bit| mask | (1 << bit));
+        let offsets: Vec<usize> = (0..1usize << high.len())
+            .map(|k| high.iter().enumerate().fold(0, |offset, (i, &bit)| offset | (((k >> i) & 1) << bit)))
+            .collect();
+        let bits: Vec<usize> = qubits
+            .iter()
+            .map(|&q| match high.iter().position(|&bit| bit + chunk_qubits == q) {
+                Some(k) => chunk_qubits + k,
+                None => q,
+            })
+            .collect();
+
+        for base in (0..self.chunks()).filter(|chunk| chunk & high_mask == 0) {
+            let mut buffer: Vec<Complex> = offsets.iter().flat_map(|&offset| self.read_chunk(base | offset)).collect();
+            simulator_backend::apply_to_bits(&mut buffer, &bits, matrix);
+            for (&offset, amplitudes) in offsets.iter().zip(buffer.chunks_exact(chunk_len)) {
+                self.store_chunk(base | offset, amplitudes);
+            }
+        }
+    }
+
+    /// Two passes over the file: one for the probability, one to collapse and renormalize
+    fn measure(&mut self, qubit: usize, rng: &mut dyn RngCore) -> bool {
+        let (mask, chunk_len) = (1 << qubit, self.chunk_len());
+        let one: f64 = (0..self.chunks())
+            .map(|chunk| {
+                let amplitudes = self.read_chunk(chunk);
+                let first = chunk * chunk_len;
+                amplitudes.iter().enumerate().filter(|(i, _)| (first + i) & mask != 0).map(|(_, a)| a.magnitude_squared()).sum::<f64>()
+            })
+            .sum();
+        let outcome = rng.gen::<f64>() < one;
+        let scale = 1.0 / if outcome { one } else { 1.0 - one }.sqrt();
+        for chunk in 0..self.chunks() {
+            let first = chunk * chunk_len;
+            let amplitudes: Vec<Complex> = self
+                .read_chunk(chunk)
+                .iter()
+                .enumerate()
+                .map(|(i, a)| {
+                    if ((first + i) & mask != 0) == outcome {
+                        Complex::new(a.re * scale, a.im * scale)
+                    } else {
+                        Complex::new(0.0, 0.0)
+                    }
+                })
+                .collect();
+            self.store_chunk(chunk, &amplitudes);
+        }
+        outcome
+    }
+
+    /// Builds the whole 2^n vector in memory; wide registers should be sampled instead
+    fn probabilities(&self) -> Vec<f64> {
+        (0..self.chunks()).flat_map(|chunk| self.read_chunk(chunk).into_iter().map(|a| a.magnitude_squared())).collect()
+    }
+
+    /// Reads the whole file into memory, as `probabilities` does
+    fn amplitudes(&self) -> Cow<'_, [Complex]> {
+        Cow::Owned((0..self.chunks()).flat_map(|chunk| self.read_chunk(chunk)).collect())
+    }
+
+    /// One pass over the file, a chunk at a time
+    fn one_probabilities(&self, qubits: &[usize]) -> Vec<f64> {
+        let mut ones = vec![0.0; qubits.len()];
+        for chunk in 0..self.chunks() {
+            simulator_backend::add_one_probabilities(&self.read_chunk(chunk), chunk * self.chunk_len(), qubits, &mut ones);
+        }
+        ones
+    }
+
+    /// Shots straight from the file, as the GPU sampler does from memory: one pass sums each
+    /// chunk, and a second reads only the chunks that received draws
+    fn sample(&self, shots: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        let sums = self.chunk_sums();
+        let total: f64 = sums.iter().sum();
+        let mut draws: Vec<(f64, usize)> = (0..shots).map(|shot| (rng.gen::<f64>() * total, shot)).collect();
+        draws.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut outcomes = vec![0; shots];
+        let mut before = 0.0;
+        let mut next = 0;
+        for (chunk, &sum) in sums.iter().enumerate() {
+            let end = if chunk + 1 == sums.len() { draws.len() } else { draws.partition_point(|&(r, _)| r < before + sum) };
+            if next < end {
+                let amplitudes = self.read_chunk(chunk);
+                let (mut index, mut cumulative) = (0, before);
+                for &(r, shot) in &draws[next..end] {
+                    // Clamped to the chunk so rounding in the sums cannot push a shot past it
+                    while index + 1 < amplitudes.len() && cumulative + amplitudes[index].magnitude_squared() <= r {
+                        cumulative += amplitudes[index].magnitude_squared();
+                        index += 1;
+                    }
+                    outcomes[shot] = chunk * amplitudes.len() + index;
+                }
+                next = end;
+            }
+            before += sum;
+        }
+        outcomes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_1_SQRT_2;
+    use rand::rngs::mock::StepRng;
+    use super::*;
+    use crate::qsim::{QuantumGate, QuantumSimulator};
+
+    #[test]
+    fn gates_across_chunks_match_the_state_vector() {
+        // Two qubits above the chunk, so gates load two and four chunks together
+        let num_qubits = CHUNK_QUBITS + 2;
+        let (low, high1, high2) = (0, CHUNK_QUBITS, CHUNK_QUBITS + 1);
+        let gates = [
+            QuantumGate::Hadamard { qubit: low },
+            QuantumGate::Hadamard { qubit: high1 },
+            QuantumGate::RotationY { qubit: high2, angle: 0.9 },
+            QuantumGate::SX { qubit: CHUNK_QUBITS - 1 },
+            QuantumGate::CNOT { control: high2, target: low },
+            QuantumGate::CNOT { control: 3, target: high1 },
+            QuantumGate::RZZ { qubit1: high1, qubit2: high2, angle: 0.4 },
+            QuantumGate::ECR { qubit1: high2, qubit2: 5 },
+            QuantumGate::Toffoli { control1: high1, control2: low, target: high2 },
+            QuantumGate::U { qubit: high1, theta: 1.1, phi: -0.2, lambda: 0.6 },
+        ];
+        let mut disk = QuantumSimulator::with_backend(Box::new(DiskStateVector::new(num_qubits).unwrap()), 0);
+        let mut memory = QuantumSimulator::seeded(num_qubits, 0);
+        for gate in &gates {
+            disk.apply_gate(gate);
+            memory.apply_gate(gate);
+        }
+        for (index, (a, b)) in disk.get_state().iter().zip(memory.get_state().iter()).enumerate() {
+            let difference = *a + Complex::new(-b.re, -b.im);
+            assert!(difference.magnitude_squared() < 1e-24, "amplitude {}: {:?}, expected {:?}", index, a, b);
+        }
+    }
+
+    #[test]
+    fn samples_follow_the_chunk_sums() {
+        let mut state = DiskStateVector::new(CHUNK_QUBITS + 1).unwrap();
+        let h = FRAC_1_SQRT_2;
+        let hadamard = [[Complex::new(h, 0.0), Complex::new(h, 0.0)], [Complex::new(h, 0.0), Complex::new(-h, 0.0)]];
+        // (|0> + |1>)/√2 on the qubit that selects the chunk, and on qubit 0 within it
+        state.apply_matrix(CHUNK_QUBITS, &hadamard);
+        state.apply_matrix(0, &hadamard);
+        // Draws evenly spaced over [0, 1)
+        let mut rng = StepRng::new(0, u64::MAX / 1000);
+        let outcomes = state.sample(1000, &mut rng);
+        let allowed = [0, 1, 1 << CHUNK_QUBITS, (1 << CHUNK_QUBITS) | 1];
+        assert!(outcomes.iter().all(|outcome| allowed.contains(outcome)));
+        for outcome in allowed {
+            let count = outcomes.iter().filter(|&&o| o == outcome).count();
+            assert!((200..300).contains(&count), "{} drew {} of 1000 shots", outcome, count);
+        }
+        let ones = state.one_probabilities(&[0, 1, CHUNK_QUBITS]);
+        for (one, expected) in ones.iter().zip([0.5, 0.0, 0.5]) {
+            assert!((one - expected).abs() < 1e-12, "{:?}", ones);
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Entanglement Entropy Module
+//! Von Neumann and Rényi entropies across a bipartition of a pure state
+
+use crate::gpu_ops::Complex;
+use crate::qsim::QuantumSimulator;
+
+/// Widest side of a cut whose spectrum is computed, a 1024 x 1024 reduced density matrix
+pub const MAX_SUBSYSTEM_QUBITS: usize = 10;
+
+/// Eigenvalues below this are rounding noise and count as zero
+const EIGENVALUE_CUTOFF: f64 = 1e-12;
+
+/// QL sweeps allowed per eigenvalue before giving up on convergence
+const MAX_SWEEPS: usize = 64;
+
+/// Eigenvalues of the reduced density matrix of `subsystem`, largest first. The state is
+/// pure, so both sides of the cut share a spectrum and the smaller one is diagonalized.
+pub fn entanglement_spectrum(simulator: &QuantumSimulator, subsystem: &[usize]) -> Result<Vec<f64>, String> {
+    let num_qubits = simulator.num_qubits;
+    if let Some(qubit) = subsystem.iter().find(|&&qubit| qubit >= num_qubits) {
+        return Err(format!("qubit {} is out of range for {} qubits", qubit, num_qubits));
+    }
+    if (1..subsystem.len()).any(|i| subsystem[..i].contains(&subsystem[i])) {
+        return Err("the subsystem lists a qubit twice".to_string());
+    }
+    let complement: Vec<usize> = (0..num_qubits).filter(|qubit| !subsystem.contains(qubit)).collect();
+    let side = if subsystem.len() <= complement.len() { subsystem } else { &complement };
+    if side.len() > MAX_SUBSYSTEM_QUBITS {
+        return Err(format!(
+            "both sides of the cut have more than {} qubits, too many to diagonalize",
+            MAX_SUBSYSTEM_QUBITS
+        ));
+    }
+
+    let mut spectrum: Vec<f64> = hermitian_eigenvalues(&simulator.reduced_density_matrix(side))
+        .into_iter()
+        .map(|eigenvalue| eigenvalue.max(0.0))
+        .collect();
+    spectrum.sort_by(|a, b| b.total_cmp(a));
+    Ok(spectrum)
+}
+
+/// Rényi entropy of order `order` in bits: log2(sum p^order) / (1 - order), with order 1
+/// giving the von Neumann entropy -sum p log2 p and order 0 the log of the Schmidt rank
+pub fn renyi_entropy(spectrum: &[f64], order: f64) -> f64 {
+    let nonzero = spectrum.iter().copied().filter(|&p| p > EIGENVALUE_CUTOFF);
+    let entropy = if order == 1.0 {
+        -nonzero.map(|p| p * p.log2()).sum::<f64>()
+    } else if order == 0.0 {
+        (nonzero.count() as f64).log2()
+    } else {
+        nonzero.map(|p| p.powf(order)).sum::<f64>().log2() / (1.0 - order)
+    };
+    // A product state should read 0, not -0 or a rounding residue either side of it
+    if entropy < EIGENVALUE_CUTOFF { 0.0 } else { entropy }
+}
+
+/// Number of Schmidt coefficients above the rounding cutoff
+pub fn schmidt_rank(spectrum: &[f64]) -> usize {
+    spectrum.iter().filter(|&&p| p > EIGENVALUE_CUTOFF).count()
+}
+
+/// Eigenvalues of a Hermitian matrix, in no particular order. H = A + iB is embedded in the
+/// real symmetric [[A, -B], [B, A]], which has every eigenvalue of H twice, then reduced to
+/// tridiagonal form with Householder reflections and diagonalized with implicit QL.
+pub fn hermitian_eigenvalues(matrix: &[Vec<Complex>]) -> Vec<f64> {
+    let n = matrix.len();
+    let mut real = vec![vec![0.0; 2 * n]; 2 * n];
+    for (i, row) in matrix.iter().enumerate() {
+        for (j, element) in row.iter().enumerate() {
+            real[i][j] = element.re;
+            real[i + n][j + n] = element.re;
+            real[i][j + n] = -element.im;
+            real[i + n][j] = element.im;
+        }
+    }
+    let (diagonal, off_diagonal) = tridiagonalize(real);
+    let mut eigenvalues = tridiagonal_eigenvalues(diagonal, off_diagonal);
+    eigenvalues.sort_by(f64::total_cmp);
+    eigenvalues.into_iter().step_by(2).collect()
+}
+
+/// Householder reduction of a real symmetric matrix to tridiagonal form, as (diagonal,
+/// sub-diagonal) with the sub-diagonal entry of row i at index i and index 0 unused
+fn tridiagonalize(mut a: Vec<Vec<f64>>) -> (Vec<f64>, Vec<f64>) {
+    let n = a.len();
+    let mut e = vec![0.0; n];
+    for i in (1..n).rev() {
+        let l = i - 1;
+        if l == 0 {
+            e[i] = a[i][l];
+            continue;
+        }
+        let scale: f64 = a[i][..=l].iter().map(|x| x.abs()).sum();
+        if scale == 0.0 {
+            e[i] = a[i][l];
+            continue;
+        }
+        for x in &mut a[i][..=l] {
+            *x /= scale;
+        }
+        let mut h: f64 = a[i][..=l].iter().map(|x| x * x).sum();
+        let f = a[i][l];
+        let g = if f >= 0.0 { -h.sqrt() } else { h.sqrt() };
+        e[i] = scale * g;
+        h -= f * g;
+        a[i][l] = f - g;
+        let mut f = 0.0;
+        for j in 0..=l {
+            // Only the lower triangle is kept current, so row j past the diagonal is column j
+            let lower: f64 = a[j][..=j].iter().zip(&a[i]).map(|(x, y)| x * y).sum();
+            let upper: f64 = (j + 1..=l).map(|k| a[k][j] * a[i][k]).sum();
+            let g = lower + upper;
+            e[j] = g / h;
+            f += e[j] * a[i][j];
+        }
+        let hh = f / (h + h);
+        for j in 0..=l {
+            let f = a[i][j];
+            let g = e[j] - hh * f;
+            e[j] = g;
+            for k in 0..=j {
+                a[j][k] -= f * e[k] + g * a[i][k];
+            }
+        }
+    }
+    let diagonal = (0..n).map(|i| a[i][i]).collect();
+    (diagonal, e)
+}
+
+/// Eigenvalues of a symmetric tridiagonal matrix by QL iteration with implicit shifts
+fn tridiagonal_eigenvalues(mut d: Vec<f64>, sub_diagonal: Vec<f64>) -> Vec<f64> {
+    let n = d.len();
+    // Shift so e[i] couples rows i and i + 1
+    let mut e: Vec<f64> = sub_diagonal.into_iter().skip(1).chain(std::iter::once(0.0)).collect();
+    for l in 0..n {
+        for _ in 0..MAX_SWEEPS {
+            let mut m = l;
+            while m + 1 < n && e[m].abs() > f64::EPSILON * (d[m].abs() + d[m + 1].abs()) {
+                m += 1;
+            }
+            if m == l {
+                break;
+            }
+            let mut g = (d[l + 1] - d[l]) / (2.0 * e[l]);
+            let r = g.hypot(1.0);
+            g = d[m] - d[l] + e[l] / (g + r.copysign(g));
+            let (mut s, mut c, mut p) = (1.0, 1.0, 0.0);
+            let mut underflow = false;
+            for i in (l..m).rev() {
+                let f = s * e[i];
+                let b = c * e[i];
+                let r = f.hypot(g);
+                e[i + 1] = r;
+                if r == 0.0 {
+                    d[i + 1] -= p;
+                    e[m] = 0.0;
+                    underflow = true;
+                    break;
+                }
+                s = f / r;
+                c = g / r;
+                g = d[i + 1] - p;
+                let r = (d[i] - g) * s + 2.0 * c * b;
+                p = s * r;
+                d[i + 1] = g + p;
+                g = c * r - b;
+            }
+            if underflow {
+                continue;
+            }
+            d[l] -= p;
+            e[l] = g;
+            e[m] = 0.0;
+        }
+    }
+    d
+}
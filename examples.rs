@@ -0,0 +1,466 @@
+//! Examples Module
+//! Bundled end-to-end examples for `quantummesh examples`, each checking its own results
+
+use std::collections::BTreeMap;
+use std::f64::consts::{FRAC_PI_2, PI};
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde_json::json;
+use crate::entropy;
+use crate::gpu_ops::Complex;
+use crate::observable::{self, PauliSum};
+use crate::params;
+use crate::qec;
+use crate::qsim::{self, QuantumCircuit, QuantumGate, QuantumSimulator};
+use crate::stats;
+use crate::stim;
+
+/// Shots sampled by examples that show counts
+const SHOTS: usize = 4000;
+
+/// Sampled frequencies may stray this many standard errors from the exact probability
+const SAMPLING_SIGMAS: f64 = 5.0;
+
+/// Exact results must agree to this tolerance
+const TOLERANCE: f64 = 1e-9;
+
+/// One bundled example: builds its circuits, simulates them and checks what comes out
+pub struct Example {
+    pub name: &'static str,
+    pub summary: &'static str,
+    run: fn(&mut StdRng) -> Result<ExampleReport, String>,
+}
+
+impl Example {
+    /// Run the example, drawing every random choice from `rng`
+    pub fn run(&self, rng: &mut StdRng) -> Result<ExampleReport, String> {
+        (self.run)(rng)
+    }
+}
+
+/// Every bundled example, in the order `quantummesh examples` lists them
+pub const EXAMPLES: &[Example] = &[
+    Example { name: "bell", summary: "Bell pair: perfectly correlated, maximally entangled qubits", run: bell },
+    Example { name: "teleportation", summary: "Teleport a qubit with mid-circuit measurements and feed-forward", run: teleportation },
+    Example { name: "grover-3sat", summary: "Grover search for the satisfying assignment of a 3-SAT formula", run: grover_3sat },
+    Example { name: "vqe-h2", summary: "VQE ground-state energy of H2 with parameter-shift gradients", run: vqe_h2 },
+    Example { name: "qaoa-maxcut", summary: "Depth-1 QAOA for MaxCut on a 4-node ring", run: qaoa_maxcut },
+    Example { name: "qec-repetition", summary: "Distance-3 repetition code: logical vs physical error rates", run: qec_repetition },
+];
+
+/// Bundled example with this name
+pub fn find(name: &str) -> Result<&'static Example, String> {
+    EXAMPLES.iter().find(|example| example.name == name).ok_or_else(|| {
+        let names: Vec<&str> = EXAMPLES.iter().map(|example| example.name).collect();
+        format!("unknown example '{}' (available: {})", name, names.join(", "))
+    })
+}
+
+/// What an example produced: its main circuit, annotated results and the checks they passed
+pub struct ExampleReport {
+    pub circuit: QuantumCircuit,
+    pub notes: Vec<String>,
+    /// Sampled outcomes of the main circuit, when the example takes shots
+    pub counts: Option<BTreeMap<usize, usize>>,
+    pub checks: Vec<Check>,
+}
+
+/// An expectation the example holds its results to
+pub struct Check {
+    pub description: String,
+    pub passed: bool,
+}
+
+impl ExampleReport {
+    fn new(circuit: &QuantumCircuit) -> Self {
+        Self { circuit: circuit.clone(), notes: Vec::new(), counts: None, checks: Vec::new() }
+    }
+
+    fn note(&mut self, text: impl Into<String>) {
+        self.notes.push(text.into());
+    }
+
+    fn check(&mut self, description: impl Into<String>, passed: bool) {
+        self.checks.push(Check { description: description.into(), passed });
+    }
+
+    /// Whether every check passed
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+}
+
+/// Final simulator state of a circuit, with collapses drawn from `seed`
+fn run(circuit: &QuantumCircuit, seed: u64) -> QuantumSimulator {
+    let mut simulator = QuantumSimulator::seeded(circuit.num_qubits, seed);
+    for gate in &circuit.gates {
+        simulator.apply_gate(gate);
+    }
+    simulator
+}
+
+/// Whether `count` of `shots` is a plausible sample of an event with probability `p`
+fn plausible(count: usize, shots: usize, p: f64) -> bool {
+    let frequency = count as f64 / shots as f64;
+    (frequency - p).abs() <= SAMPLING_SIGMAS * (p * (1.0 - p) / shots as f64).sqrt() + TOLERANCE
+}
+
+fn circuit(num_qubits: usize, name: &str, gates: Vec<QuantumGate>) -> QuantumCircuit {
+    let mut metadata = BTreeMap::new();
+    metadata.insert("name".to_string(), name.to_string());
+    QuantumCircuit { num_qubits, gates, metadata }
+}
+
+fn bell(rng: &mut StdRng) -> Result<ExampleReport, String> {
+    let mut circuit = qsim::create_bell_state();
+    circuit.metadata.insert("name".to_string(), "bell".to_string());
+    let mut report = ExampleReport::new(&circuit);
+    report.note("H on q0 then CNOT q0 -> q1 prepares (|00⟩ + |11⟩)/√2");
+
+    let simulator = run(&circuit, rng.gen());
+    let probabilities = simulator.measure_all();
+    report.note(format!("Exact: P(00) = {:.4}, P(11) = {:.4}", probabilities[0], probabilities[3]));
+    report.check(
+        "only |00⟩ and |11⟩ have probability, 1/2 each",
+        (probabilities[0] - 0.5).abs() < TOLERANCE && (probabilities[3] - 0.5).abs() < TOLERANCE,
+    );
+
+    let outcomes: Vec<usize> = qsim::sample_circuit(&circuit, SHOTS, rng).into_iter().map(|shot| shot.outcome).collect();
+    let counts = stats::count_outcomes(&outcomes);
+    report.check("every shot reads the same value on both qubits", counts.keys().all(|&state| state == 0 || state == 3));
+    report.check(
+        format!("|00⟩ turns up in about half of {} shots", SHOTS),
+        plausible(counts.get(&0).copied().unwrap_or(0), SHOTS, 0.5),
+    );
+    report.counts = Some(counts);
+
+    let spectrum = entropy::entanglement_spectrum(&simulator, &[0])?;
+    let entropy = entropy::renyi_entropy(&spectrum, 1.0);
+    report.note(format!("Entanglement entropy of q0 with q1: {:.4} bits", entropy));
+    report.check("the qubits share exactly one bit of entanglement", (entropy - 1.0).abs() < 1e-6);
+    Ok(report)
+}
+
+fn teleportation(rng: &mut StdRng) -> Result<ExampleReport, String> {
+    let (theta, phi) = (1.1, 0.7);
+    let message = QuantumGate::U { qubit: 0, theta, phi, lambda: 0.0 };
+    let circuit = circuit(3, "teleportation", vec![
+        message.clone(),
+        // Shared Bell pair between the sender's q1 and the receiver's q2
+        QuantumGate::Hadamard { qubit: 1 },
+        QuantumGate::CNOT { control: 1, target: 2 },
+        // Bell measurement of the message and the sender's half
+        QuantumGate::CNOT { control: 0, target: 1 },
+        QuantumGate::Hadamard { qubit: 0 },
+        QuantumGate::Measurement { qubit: 0, bit: None },
+        QuantumGate::Measurement { qubit: 1, bit: None },
+        // The receiver's corrections, conditioned on the two classical bits
+        QuantumGate::IfBit { bit: 1, gate: Box::new(QuantumGate::PauliX { qubit: 2 }) },
+        QuantumGate::IfBit { bit: 0, gate: Box::new(QuantumGate::PauliZ { qubit: 2 }) },
+    ]);
+    let mut report = ExampleReport::new(&circuit);
+    report.note(format!("Message U3({}, {}, 0)|0⟩ on q0 is sent to q2 through a Bell pair and two classical bits", theta, phi));
+
+    let mut sent = QuantumSimulator::new(1);
+    sent.apply_gate(&message);
+    let target = sent.get_state().into_owned();
+
+    let mut worst = 1.0f64;
+    for trajectory in 1..=4 {
+        let simulator = run(&circuit, rng.gen());
+        let bits = simulator.classical_bits();
+        let rho = simulator.reduced_density_matrix(&[2]);
+        let fidelity = (0..2)
+            .flat_map(|row| (0..2).map(move |column| (row, column)))
+            .fold(Complex::new(0.0, 0.0), |sum, (row, column)| sum + target[row].conjugate() * rho[row][column] * target[column])
+            .re;
+        let read = |qubit: usize| bits[qubit].map_or('?', |bit| if bit { '1' } else { '0' });
+        let corrections = match (bits[1], bits[0]) {
+            (Some(true), Some(true)) => "X then Z",
+            (Some(true), _) => "X",
+            (_, Some(true)) => "Z",
+            _ => "none",
+        };
+        report.note(format!(
+            "Trajectory {}: measured q0 = {}, q1 = {}; corrections {}; fidelity with the message {:.9}",
+            trajectory, read(0), read(1), corrections, fidelity
+        ));
+        worst = worst.min(fidelity);
+    }
+    report.check("q2 holds the message exactly on every trajectory", worst > 1.0 - TOLERANCE);
+
+    // Every shot re-runs the circuit and follows its own measurement outcomes
+    let shots = qsim::sample_circuit(&circuit, SHOTS, rng);
+    let ones = shots.iter().filter(|shot| (shot.outcome >> 2) & 1 == 1).count();
+    let expected = (theta / 2.0).sin().powi(2);
+    report.note(format!("q2 read 1 in {} of {} shots (expected sin²(θ/2) = {:.4})", ones, SHOTS, expected));
+    report.check("q2's outcome statistics match the message over all shots", plausible(ones, SHOTS, expected));
+    Ok(report)
+}
+
+/// The 3-SAT instance searched by `grover-3sat`, as DIMACS-style literals: k is variable
+/// x(k-1), -k its negation. Each clause rules out one assignment, leaving only x = 101.
+const FORMULA: &[[i32; 3]] = &[
+    [1, 2, 3],
+    [-1, 2, 3],
+    [1, -2, 3],
+    [-1, -2, 3],
+    [1, 2, -3],
+    [1, -2, -3],
+    [-1, -2, -3],
+];
+
+/// Whether an assignment, with x0 as bit 0, satisfies every clause
+fn satisfies(assignment: usize, clauses: &[[i32; 3]]) -> bool {
+    clauses.iter().all(|clause| {
+        clause.iter().any(|&literal| {
+            let value = (assignment >> (literal.unsigned_abs() - 1)) & 1 == 1;
+            value == (literal > 0)
+        })
+    })
+}
+
+fn grover_3sat(rng: &mut StdRng) -> Result<ExampleReport, String> {
+    let qubits: Vec<usize> = (0..3).collect();
+    let size = 1 << qubits.len();
+    let solutions: Vec<usize> = (0..size).filter(|&assignment| satisfies(assignment, FORMULA)).collect();
+    // The oracle marks satisfying assignments with a phase of -1
+    let oracle: Vec<f64> = (0..size).map(|assignment| if solutions.contains(&assignment) { PI } else { 0.0 }).collect();
+    // Reflection about |000⟩, up to a global phase
+    let reflection: Vec<f64> = (0..size).map(|state| if state == 0 { 0.0 } else { PI }).collect();
+    let iterations = (PI / 4.0 * (size as f64 / solutions.len().max(1) as f64).sqrt()).floor() as usize;
+
+    let mut gates = vec![QuantumGate::GlobalHadamard { qubits: qubits.clone() }];
+    for _ in 0..iterations {
+        gates.push(QuantumGate::Diagonal { qubits: qubits.clone(), phases: oracle.clone() });
+        gates.push(QuantumGate::GlobalHadamard { qubits: qubits.clone() });
+        gates.push(QuantumGate::Diagonal { qubits: qubits.clone(), phases: reflection.clone() });
+        gates.push(QuantumGate::GlobalHadamard { qubits: qubits.clone() });
+    }
+    let circuit = circuit(qubits.len(), "grover-3sat", gates);
+    let mut report = ExampleReport::new(&circuit);
+    let clauses: Vec<String> = FORMULA
+        .iter()
+        .map(|clause| {
+            let literals: Vec<String> = clause
+                .iter()
+                .map(|&literal| format!("{}x{}", if literal < 0 { "¬" } else { "" }, literal.unsigned_abs() - 1))
+                .collect();
+            format!("({})", literals.join(" ∨ "))
+        })
+        .collect();
+    report.note(format!("Formula: {}", clauses.join(" ∧ ")));
+    report.note(format!(
+        "{} of {} assignments satisfy it; {} Grover iterations of oracle and diffusion",
+        solutions.len(), size, iterations
+    ));
+
+    let probabilities = run(&circuit, rng.gen()).measure_all();
+    let best = (0..size).max_by(|&a, &b| probabilities[a].total_cmp(&probabilities[b])).unwrap_or(0);
+    let angle = (1.0 / (size as f64).sqrt()).asin();
+    let theory = ((2 * iterations + 1) as f64 * angle).sin().powi(2);
+    report.note(format!(
+        "Most likely assignment x2x1x0 = {:03b} with probability {:.4} (theory {:.4})",
+        best, probabilities[best], theory
+    ));
+    report.check("the most likely assignment satisfies the formula", satisfies(best, FORMULA));
+    report.check("Grover amplifies it above 90%", probabilities[best] > 0.9);
+
+    let outcomes = qsim::sample_circuit(&circuit, SHOTS, rng).into_iter().map(|shot| shot.outcome).collect::<Vec<_>>();
+    let counts = stats::count_outcomes(&outcomes);
+    report.check(
+        format!("sampled shots find it as often as predicted ({} shots)", SHOTS),
+        plausible(counts.get(&best).copied().unwrap_or(0), SHOTS, probabilities[best]),
+    );
+    report.counts = Some(counts);
+    Ok(report)
+}
+
+/// Qubit Hamiltonian of H2 at 0.735 Å in the STO-3G basis, parity-mapped with two qubits
+/// tapered off. The constant omits the nuclear repulsion, `H2_NUCLEAR_REPULSION`.
+const H2_HAMILTONIAN: &str = "-1.052373245772859*II + 0.39793742484318045*ZI - 0.39793742484318045*IZ \
+                              - 0.01128010425623538*ZZ + 0.18093119978423156*XX";
+
+/// Nuclear repulsion energy of H2 at 0.735 Å, in hartree
+const H2_NUCLEAR_REPULSION: f64 = 0.7199689944489797;
+
+/// Chemical accuracy, 1 kcal/mol in hartree
+const CHEMICAL_ACCURACY: f64 = 1.6e-3;
+
+fn vqe_h2(rng: &mut StdRng) -> Result<ExampleReport, String> {
+    const LEARNING_RATE: f64 = 0.5;
+    const MAX_STEPS: usize = 200;
+    const GRADIENT_TOLERANCE: f64 = 1e-9;
+
+    let hamiltonian = PauliSum::parse(H2_HAMILTONIAN)?;
+    hamiltonian.validate(2)?;
+    // Exchange-type ansatz cos(θ/2)|10⟩ + sin(θ/2)|01⟩ (q1 q0), which spans the ground state's sector
+    let ansatz = json!({
+        "num_qubits": 2,
+        "parameters": { "theta": 0.0 },
+        "gates": [
+            { "type": "RotationY", "qubit": 0, "angle": "theta" },
+            { "type": "PauliX", "qubit": 1 },
+            { "type": "CNOT", "control": 0, "target": 1 }
+        ],
+        "metadata": { "name": "vqe-h2" }
+    });
+    let bind = |theta: f64| {
+        params::instantiate(&ansatz, &BTreeMap::from([("theta".to_string(), theta)])).map_err(|e| e.to_string())
+    };
+    let energy = |theta: f64| -> Result<f64, String> { Ok(run(&bind(theta)?, 0).expectation(&hamiltonian)) };
+
+    let mut theta = 0.0;
+    let mut steps = 0;
+    for step in 1..=MAX_STEPS {
+        // Parameter shift: exact for rotations generated by a Pauli
+        let gradient = (energy(theta + FRAC_PI_2)? - energy(theta - FRAC_PI_2)?) / 2.0;
+        steps = step;
+        if gradient.abs() < GRADIENT_TOLERANCE {
+            break;
+        }
+        theta -= LEARNING_RATE * gradient;
+    }
+    let circuit = bind(theta)?;
+    let mut report = ExampleReport::new(&circuit);
+    report.note(format!("H = {}", hamiltonian));
+    let optimum = energy(theta)?;
+    report.note(format!("Gradient descent converged in {} steps to θ = {:.6}", steps, theta));
+
+    let exact = entropy::hermitian_eigenvalues(&hamiltonian.to_matrix(2))
+        .into_iter()
+        .fold(f64::INFINITY, f64::min);
+    report.note(format!("VQE energy {:.8} Ha, exact ground state {:.8} Ha", optimum, exact));
+    report.note(format!("Total energy with nuclear repulsion: {:.8} Ha", optimum + H2_NUCLEAR_REPULSION));
+    report.check("VQE reaches the exact ground energy within chemical accuracy", (optimum - exact).abs() < CHEMICAL_ACCURACY);
+
+    // The same energy measured as hardware would, one basis per Pauli string
+    let simulator = run(&circuit, rng.gen());
+    let estimate = observable::sample_expectation(&hamiltonian, SHOTS, None, |gates, n| {
+        let mut rotated = simulator.clone();
+        for gate in gates {
+            rotated.apply_gate(gate);
+        }
+        rotated.sample(n, rng)
+    });
+    report.note(format!(
+        "Sampled estimate {:.6} ± {:.6} Ha from {} shots",
+        estimate.mean, estimate.standard_error, estimate.shots
+    ));
+    report.check(
+        "the sampled estimate agrees with the exact expectation value",
+        (estimate.mean - optimum).abs() <= SAMPLING_SIGMAS * estimate.standard_error + TOLERANCE,
+    );
+    Ok(report)
+}
+
+/// Edges of the ring graph cut by `qaoa-maxcut`
+const RING: &[(usize, usize)] = &[(0, 1), (1, 2), (2, 3), (3, 0)];
+
+/// Edges of `RING` cut by a partition, with node k on the side given by bit k
+fn cut_size(partition: usize) -> usize {
+    RING.iter().filter(|&&(a, b)| (partition >> a) & 1 != (partition >> b) & 1).count()
+}
+
+fn qaoa_maxcut(rng: &mut StdRng) -> Result<ExampleReport, String> {
+    // Grid points per angle; both optima of the ring, (γ, β) = (π/8, 3π/8) and (7π/8, π/8), lie on it
+    const GRID: usize = 16;
+    let nodes = 4;
+
+    let mut gates = vec![json!({ "type": "GlobalHadamard", "qubits": (0..nodes).collect::<Vec<usize>>() })];
+    for &(a, b) in RING {
+        gates.push(json!({ "type": "RZZ", "qubit1": a, "qubit2": b, "angle": "2*gamma" }));
+    }
+    for qubit in 0..nodes {
+        gates.push(json!({ "type": "RotationX", "qubit": qubit, "angle": "2*beta" }));
+    }
+    let document = json!({
+        "num_qubits": nodes,
+        "parameters": { "gamma": 0.0, "beta": 0.0 },
+        "gates": gates,
+        "metadata": { "name": "qaoa-maxcut" }
+    });
+    let bind = |gamma: f64, beta: f64| {
+        let values = BTreeMap::from([("gamma".to_string(), gamma), ("beta".to_string(), beta)]);
+        params::instantiate(&document, &values).map_err(|e| e.to_string())
+    };
+    let expected_cut = |probabilities: &[f64]| -> f64 {
+        probabilities.iter().enumerate().map(|(partition, p)| p * cut_size(partition) as f64).sum()
+    };
+
+    let mut best = (f64::NEG_INFINITY, 0.0, 0.0);
+    for i in 0..GRID {
+        for j in 0..GRID {
+            let (gamma, beta) = (PI * i as f64 / GRID as f64, FRAC_PI_2 * j as f64 / GRID as f64);
+            let cut = expected_cut(&run(&bind(gamma, beta)?, 0).measure_all());
+            if cut > best.0 {
+                best = (cut, gamma, beta);
+            }
+        }
+    }
+    let (cut, gamma, beta) = best;
+    let circuit = bind(gamma, beta)?;
+    let mut report = ExampleReport::new(&circuit);
+    let max_cut = (0..1 << nodes).map(cut_size).max().unwrap_or(0);
+    report.note(format!("Ring of {} nodes, {} edges; the maximum cut has {} edges", nodes, RING.len(), max_cut));
+    report.note(format!("Best of a {}x{} grid: γ = {:.4}, β = {:.4}, expected cut {:.4}", GRID, GRID, gamma, beta, cut));
+    report.check("depth-1 QAOA reaches 3/4 of the maximum cut, its optimum on rings", cut >= 0.75 * max_cut as f64 - 1e-6);
+
+    let probabilities = run(&circuit, rng.gen()).measure_all();
+    let top = (0..probabilities.len()).max_by(|&a, &b| probabilities[a].total_cmp(&probabilities[b])).unwrap_or(0);
+    report.note(format!(
+        "Most likely partition {:04b} cuts {} edges (probability {:.4})",
+        top, cut_size(top), probabilities[top]
+    ));
+    report.check("the most likely partition is a maximum cut", cut_size(top) == max_cut);
+
+    let outcomes = qsim::sample_circuit(&circuit, SHOTS, rng).into_iter().map(|shot| shot.outcome).collect::<Vec<_>>();
+    report.counts = Some(stats::count_outcomes(&outcomes));
+    Ok(report)
+}
+
+/// Distance-3 bit-flip repetition code: one round of parity checks on ancillas 3 and 4,
+/// then a readout of the data qubits, with the last data qubit as the logical observable
+const REPETITION_CODE: &str = "\
+R 0 1 2 3 4
+CX 0 3 1 3 1 4 2 4
+MR 3 4
+DETECTOR rec[-2]
+DETECTOR rec[-1]
+M 0 1 2
+DETECTOR rec[-3] rec[-2] rec[-5]
+DETECTOR rec[-2] rec[-1] rec[-4]
+OBSERVABLE_INCLUDE(0) rec[-1]
+";
+
+fn qec_repetition(rng: &mut StdRng) -> Result<ExampleReport, String> {
+    const RATES: &[f64] = &[0.001, 0.002, 0.005];
+    const QEC_SHOTS: usize = 100_000;
+
+    let mut circuit = stim::parse_stim(REPETITION_CODE)?;
+    circuit.metadata.insert("name".to_string(), "qec-repetition".to_string());
+    let mut report = ExampleReport::new(&circuit);
+    report.note("3 data qubits, 2 parity-check ancillas; detectors compare checks across rounds");
+    report.note(format!(
+        "Uniform circuit noise at each rate, {} shots per rate, decoded by matching on the detector error model",
+        QEC_SHOTS
+    ));
+
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let points = qec::sweep(REPETITION_CODE, RATES, QEC_SHOTS, threads, rng)?;
+    for point in &points {
+        let p = point.physical_error.unwrap_or_default();
+        report.note(format!(
+            "p = {:.3}: logical error rate {:.2e} ± {:.1e} ({:.1} x p²)",
+            p,
+            point.logical_error_rate,
+            point.std_error,
+            point.logical_error_rate / (p * p)
+        ));
+    }
+    report.check(
+        "encoding suppresses errors: the logical rate is below the physical rate at every point",
+        points.iter().all(|point| point.logical_error_rate < point.physical_error.unwrap_or_default()),
+    );
+    Ok(report)
+}
@@ -0,0 +1,146 @@
+//! Faults Module
+//! Deterministic Pauli fault injection and per-location sensitivity maps
+
+use serde::Serialize;
+use crate::qsim::{QuantumCircuit, QuantumGate, QuantumSimulator};
+use crate::results;
+
+/// Error injected at a fault location
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Pauli {
+    X,
+    Y,
+    Z,
+}
+
+impl Pauli {
+    pub const ALL: [Pauli; 3] = [Pauli::X, Pauli::Y, Pauli::Z];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "X" | "x" => Some(Pauli::X),
+            "Y" | "y" => Some(Pauli::Y),
+            "Z" | "z" => Some(Pauli::Z),
+            _ => None,
+        }
+    }
+
+    pub fn gate(self, qubit: usize) -> QuantumGate {
+        match self {
+            Pauli::X => QuantumGate::PauliX { qubit },
+            Pauli::Y => QuantumGate::PauliY { qubit },
+            Pauli::Z => QuantumGate::PauliZ { qubit },
+        }
+    }
+}
+
+/// Effect of one Pauli error inserted right after gate `gate_index`
+#[derive(Debug, Clone, Serialize)]
+pub struct FaultEffect {
+    pub gate_index: usize,
+    pub qubit: usize,
+    pub pauli: Pauli,
+    /// Total variation distance between ideal and faulty outcomes on the measured qubits
+    pub total_variation: f64,
+    /// Overlap |<ideal|faulty>|^2 of the final states
+    pub state_fidelity: f64,
+}
+
+/// Copy of the circuit with a Pauli error inserted after gate `gate_index`
+pub fn inject(circuit: &QuantumCircuit, gate_index: usize, qubit: usize, pauli: Pauli) -> Result<QuantumCircuit, String> {
+    if gate_index >= circuit.gates.len() {
+        return Err(format!("gate index {} is out of range for {} gates", gate_index, circuit.gates.len()));
+    }
+    if qubit >= circuit.num_qubits {
+        return Err(format!("qubit {} is out of range for {} qubits", qubit, circuit.num_qubits));
+    }
+    let mut faulty = circuit.clone();
+    faulty.gates.insert(gate_index + 1, pauli.gate(qubit));
+    Ok(faulty)
+}
+
+/// Simulate the circuit with and without a single injected fault
+pub fn fault_effect(circuit: &QuantumCircuit, gate_index: usize, qubit: usize, pauli: Pauli) -> Result<FaultEffect, String> {
+    let faulty = inject(circuit, gate_index, qubit, pauli)?;
+    let ideal = Reference::new(circuit);
+    Ok(ideal.compare(&simulate(&faulty), gate_index, qubit, pauli))
+}
+
+/// Sweep every fault location (after each non-measurement gate, on each qubit it touches)
+///
+/// The state after each gate is kept, so every location replays only the gates after it.
+pub fn sensitivity_map(circuit: &QuantumCircuit, paulis: &[Pauli]) -> Vec<FaultEffect> {
+    let ideal = Reference::new(circuit);
+    let mut prefix = QuantumSimulator::new(circuit.num_qubits);
+    let mut effects = Vec::new();
+
+    for (index, gate) in circuit.gates.iter().enumerate() {
+        prefix.apply_gate(gate);
+        if matches!(gate, QuantumGate::Measurement { .. }) {
+            continue;
+        }
+        let mut qubits = gate.qubits();
+        qubits.sort_unstable();
+        qubits.dedup();
+        for qubit in qubits {
+            for &pauli in paulis {
+                let mut faulty = prefix.clone();
+                faulty.apply_gate(&pauli.gate(qubit));
+                for later in &circuit.gates[index + 1..] {
+                    faulty.apply_gate(later);
+                }
+                effects.push(ideal.compare(&faulty, index, qubit, pauli));
+            }
+        }
+    }
+    effects
+}
+
+/// Fault-free outcome that faulty runs are compared against
+struct Reference {
+    simulator: QuantumSimulator,
+    measured: Vec<usize>,
+    outcomes: Vec<f64>,
+}
+
+impl Reference {
+    fn new(circuit: &QuantumCircuit) -> Self {
+        let simulator = simulate(circuit);
+        let measured = results::measured_qubits(circuit);
+        let outcomes = measured_distribution(&simulator, &measured);
+        Self { simulator, measured, outcomes }
+    }
+
+    fn compare(&self, faulty: &QuantumSimulator, gate_index: usize, qubit: usize, pauli: Pauli) -> FaultEffect {
+        let outcomes = measured_distribution(faulty, &self.measured);
+        let total_variation = 0.5 * self.outcomes.iter().zip(&outcomes).map(|(p, q)| (p - q).abs()).sum::<f64>();
+        FaultEffect {
+            gate_index,
+            qubit,
+            pauli,
+            total_variation,
+            state_fidelity: self.simulator.fidelity(faulty),
+        }
+    }
+}
+
+fn simulate(circuit: &QuantumCircuit) -> QuantumSimulator {
+    let mut simulator = QuantumSimulator::new(circuit.num_qubits);
+    for gate in &circuit.gates {
+        simulator.apply_gate(gate);
+    }
+    simulator
+}
+
+/// Outcome distribution over the measured qubits, indexed by their bits in order
+fn measured_distribution(simulator: &QuantumSimulator, measured: &[usize]) -> Vec<f64> {
+    let mut outcomes = vec![0.0; 1 << measured.len()];
+    for (state, probability) in simulator.measure_all().into_iter().enumerate() {
+        let outcome = measured
+            .iter()
+            .enumerate()
+            .fold(0, |acc, (bit, &qubit)| acc | (((state >> qubit) & 1) << bit));
+        outcomes[outcome] += probability;
+    }
+    outcomes
+}
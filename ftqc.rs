@@ -0,0 +1,250 @@
+//! Fault-Tolerant Resource Module
+//! Physical qubit, T-factory and runtime estimates for running a logical circuit on surface codes
+
+use serde::Serialize;
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+use crate::optimizer;
+use crate::qsim::{self, QuantumCircuit, QuantumGate};
+
+/// Surface code threshold in the logical error model
+const THRESHOLD: f64 = 0.01;
+
+/// Prefactor of the per-tile, per-cycle logical error rate 0.1 (p / p_th)^((d + 1) / 2)
+const LOGICAL_PREFACTOR: f64 = 0.1;
+
+/// Largest code distance tried before giving up
+const MAX_DISTANCE: usize = 101;
+
+/// Tiles and logical time steps of one 15-to-1 distillation round (Litinski, "A Game of
+/// Surface Codes"); each step lasts d code cycles
+const FACTORY_TILES: usize = 11;
+const FACTORY_STEPS: usize = 11;
+
+/// First-level factories feeding each factory of the level above
+const FACTORY_FEEDERS: usize = 4;
+
+/// Most distillation levels tried before giving up
+const MAX_LEVELS: usize = 4;
+
+/// Largest distance from a multiple of pi/4 at which a rotation still counts as exact
+const ANGLE_TOLERANCE: f64 = 1e-9;
+
+/// Architecture and error budget of an estimate
+#[derive(Debug, Clone)]
+pub struct FaultTolerantModel {
+    /// Physical error rate per operation, also the error of injected magic states
+    pub physical_error: f64,
+    /// Duration of one surface code cycle in microseconds
+    pub cycle_time_us: f64,
+    /// Code distance; chosen to meet the budget when not given
+    pub distance: Option<usize>,
+    /// Total failure probability allowed for the whole run
+    pub target_error: f64,
+    /// T factories; enough to supply one T state per logical step when not given, since each
+    /// factory delivers one every `FACTORY_STEPS` steps
+    pub factories: Option<usize>,
+}
+
+impl Default for FaultTolerantModel {
+    fn default() -> Self {
+        Self { physical_error: 1e-3, cycle_time_us: 1.0, distance: None, target_error: 0.01, factories: None }
+    }
+}
+
+/// Resources for one circuit under a `FaultTolerantModel`
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceEstimate {
+    pub logical_qubits: usize,
+    /// T gates in the circuit itself, including those of Toffolis
+    pub t_gates: usize,
+    /// Rotations by angles that are not multiples of pi/4, each synthesized from T gates
+    pub rotations: usize,
+    pub t_per_rotation: usize,
+    pub total_t: usize,
+    pub distance: usize,
+    /// Tiles of d x d surface code patches holding the data qubits and routing space
+    pub data_tiles: usize,
+    pub distillation_levels: usize,
+    pub factories: usize,
+    pub data_qubits: usize,
+    pub factory_qubits: usize,
+    pub physical_qubits: usize,
+    pub logical_steps: usize,
+    pub code_cycles: usize,
+    pub runtime_seconds: f64,
+    /// Estimated failure probability of storage, distillation and synthesis combined
+    pub failure_probability: f64,
+}
+
+/// Estimate the resources to run `circuit` fault-tolerantly.
+///
+/// Uses Pauli-based computation on Litinski's fast block layout: Clifford gates are absorbed
+/// into measurements, each T gate consumes one magic state in one logical step of d code
+/// cycles, and rotations are synthesized with about 3 log2(1/ε) T gates each. The failure
+/// budget is split evenly between storage errors, distilled T states and synthesis.
+pub fn estimate(circuit: &QuantumCircuit, model: &FaultTolerantModel) -> Result<ResourceEstimate, String> {
+    let p = model.physical_error;
+    if !(p > 0.0 && p < THRESHOLD) {
+        return Err(format!("physical error rate {} must be between 0 and the {} threshold", p, THRESHOLD));
+    }
+    if !(model.target_error > 0.0 && model.target_error < 1.0) {
+        return Err(format!("target error {} must be between 0 and 1", model.target_error));
+    }
+    if !(model.cycle_time_us > 0.0 && model.cycle_time_us.is_finite()) {
+        return Err("cycle time must be positive".to_string());
+    }
+    if let Some(distance) = model.distance {
+        if distance < 3 || distance % 2 == 0 {
+            return Err(format!("code distance {} must be odd and at least 3", distance));
+        }
+    }
+
+    let (t_gates, rotations) = circuit
+        .gates
+        .iter()
+        .map(non_clifford)
+        .fold((0, 0), |(t, r), (gate_t, gate_r)| (t + gate_t, r + gate_r));
+    let shares = if rotations > 0 { 3.0 } else { 2.0 };
+    let budget = model.target_error / shares;
+
+    let t_per_rotation = if rotations > 0 {
+        (3.0 * (rotations as f64 / budget).log2()).ceil() as usize
+    } else {
+        0
+    };
+    let total_t = t_gates + rotations * t_per_rotation;
+
+    // Distill until each T state is good enough for its share of the budget; injected states
+    // always get at least one round
+    let (mut levels, mut t_error) = (0, 0.0);
+    if total_t > 0 {
+        t_error = p;
+        while levels == 0 || t_error * total_t as f64 > budget {
+            if levels == MAX_LEVELS {
+                return Err(format!("{} levels of 15-to-1 distillation cannot reach the T state error needed", MAX_LEVELS));
+            }
+            t_error = 35.0 * t_error.powi(3);
+            levels += 1;
+        }
+    }
+    let factory_tiles = (1..levels).fold(FACTORY_TILES, |tiles, _| FACTORY_TILES + FACTORY_FEEDERS * tiles);
+    let factories = match (total_t, model.factories) {
+        (0, _) => 0,
+        (_, Some(0)) => return Err("a circuit with T gates needs at least one factory".to_string()),
+        (_, Some(factories)) => factories,
+        (_, None) => FACTORY_STEPS.min(total_t),
+    };
+
+    let n = circuit.num_qubits;
+    let data_tiles = 2 * n + (8.0 * n as f64).sqrt().ceil() as usize + 1;
+    // One step per T state, slowed down when the factories cannot keep up, plus the readout
+    let logical_steps = match factories {
+        0 => 1,
+        _ => total_t.max(total_t.div_ceil(factories) * FACTORY_STEPS) + 1,
+    };
+    let storage_error = |d: usize| {
+        let per_tile_cycle = LOGICAL_PREFACTOR * (p / THRESHOLD).powf((d + 1) as f64 / 2.0);
+        data_tiles as f64 * (logical_steps * d) as f64 * per_tile_cycle
+    };
+    let distance = match model.distance {
+        Some(distance) => distance,
+        None => (3..=MAX_DISTANCE)
+            .step_by(2)
+            .find(|&d| storage_error(d) <= budget)
+            .ok_or_else(|| format!("no code distance up to {} meets the error budget", MAX_DISTANCE))?,
+    };
+
+    let tile_qubits = 2 * distance * distance;
+    let data_qubits = data_tiles * tile_qubits;
+    let factory_qubits = factories * factory_tiles * tile_qubits;
+    let code_cycles = logical_steps * distance;
+    let synthesis_error = if rotations > 0 { budget } else { 0.0 };
+    Ok(ResourceEstimate {
+        logical_qubits: n,
+        t_gates,
+        rotations,
+        t_per_rotation,
+        total_t,
+        distance,
+        data_tiles,
+        distillation_levels: levels,
+        factories,
+        data_qubits,
+        factory_qubits,
+        physical_qubits: data_qubits + factory_qubits,
+        logical_steps,
+        code_cycles,
+        runtime_seconds: code_cycles as f64 * model.cycle_time_us * 1e-6,
+        failure_probability: (storage_error(distance) + t_error * total_t as f64 + synthesis_error).min(1.0),
+    })
+}
+
+/// Non-Clifford content of a gate, as (T gates, rotations needing synthesis)
+fn non_clifford(gate: &QuantumGate) -> (usize, usize) {
+    match gate {
+        QuantumGate::Phase { angle, .. }
+        | QuantumGate::RotationX { angle, .. }
+        | QuantumGate::RotationY { angle, .. }
+        | QuantumGate::RotationZ { angle, .. }
+        | QuantumGate::RXX { angle, .. }
+        | QuantumGate::RYY { angle, .. }
+        | QuantumGate::RZZ { angle, .. }
+        // The basis change around a Pauli rotation is Clifford
+        | QuantumGate::PauliRotation { angle, .. } => rotation(*angle),
+        QuantumGate::GlobalRotation { qubits, angle, .. } => times(rotation(*angle), qubits.len()),
+        // RZ(φ) RY(θ) RZ(λ)
+        QuantumGate::U { theta, phi, lambda, .. } => [*theta, *phi, *lambda]
+            .into_iter()
+            .map(rotation)
+            .fold((0, 0), |(t, r), (a, b)| (t + a, r + b)),
+        // Two Z rotations by half the angle and one by minus half, around CNOTs
+        QuantumGate::CPhase { angle, .. } => times(rotation(angle / 2.0), 3),
+        // XY and YX rotations by half the angle
+        QuantumGate::Givens { angle, .. } => times(rotation(angle / 2.0), 2),
+        // XX and YY rotations by pi/4
+        QuantumGate::SqrtISWAP { .. } => (2, 0),
+        // About 4^k rotations after Shannon decomposition
+        QuantumGate::UnitaryNQ { qubits, .. } => (0, 4usize.pow(qubits.len().min(16) as u32)),
+        QuantumGate::Diagonal { qubits, phases } => qsim::diagonal_gates(qubits, phases)
+            .iter()
+            .map(non_clifford)
+            .fold((0, 0), |(t, r), (a, b)| (t + a, r + b)),
+        QuantumGate::Controlled { gate, .. } => match gate.as_ref() {
+            QuantumGate::PauliX { .. } | QuantumGate::PauliY { .. } | QuantumGate::PauliZ { .. } => (0, 0),
+            QuantumGate::S { .. } => non_clifford(&QuantumGate::CPhase { control: 0, target: 1, angle: FRAC_PI_2 }),
+            QuantumGate::Sdg { .. } => non_clifford(&QuantumGate::CPhase { control: 0, target: 1, angle: -FRAC_PI_2 }),
+            QuantumGate::T { .. } | QuantumGate::Tdg { .. } => {
+                non_clifford(&QuantumGate::CPhase { control: 0, target: 1, angle: FRAC_PI_4 })
+            }
+            QuantumGate::Phase { angle, .. } => non_clifford(&QuantumGate::CPhase { control: 0, target: 1, angle: *angle }),
+            QuantumGate::RotationX { angle, .. } | QuantumGate::RotationY { angle, .. } | QuantumGate::RotationZ { angle, .. } => {
+                times(rotation(angle / 2.0), 2)
+            }
+            inner => match non_clifford(inner) {
+                // A controlled Clifford such as CCX or a Fredkin gate costs about a Toffoli
+                (0, 0) => (7, 0),
+                // A, B and C of the ABC decomposition twice over, plus the controlled phase
+                (t, r) => (2 * t, 2 * r + 1),
+            },
+        },
+        QuantumGate::IfBit { gate, .. } | QuantumGate::Noisy { gate, .. } => non_clifford(gate),
+        gate => (optimizer::t_count(gate), 0),
+    }
+}
+
+/// A rotation by `angle`: free at multiples of pi/2, one T gate at odd multiples of pi/4,
+/// otherwise synthesized
+fn rotation(angle: f64) -> (usize, usize) {
+    let eighths = angle / FRAC_PI_4;
+    if (eighths - eighths.round()).abs() * FRAC_PI_4 > ANGLE_TOLERANCE {
+        (0, 1)
+    } else if eighths.round().rem_euclid(2.0) == 1.0 {
+        (1, 0)
+    } else {
+        (0, 0)
+    }
+}
+
+fn times((t, r): (usize, usize), n: usize) -> (usize, usize) {
+    (t * n, r * n)
+}
@@ -1,9 +1,28 @@
 //! GPU Operations Module
 //! Provides GPU-accelerated quantum gate operations using CUDA/OpenCL
 
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::fs;
+use std::io;
+use std::marker::PhantomData;
+use std::ops::{Add, Mul, Range};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crate::profiler::{self, Track};
+use crate::qsim::{self, QuantumGate};
+use crate::simd;
+use crate::simulator_backend::{self, SimulatorBackend};
 
 /// GPU device information
+#[derive(Clone)]
 pub struct GpuDevice {
     pub name: String,
     pub memory: u64,
@@ -44,14 +63,20 @@ impl fmt::Display for GpuDevice {
 }
 
 /// GPU accelerated state vector
+#[derive(Clone)]
 pub struct GpuStateVector {
     pub size: usize,
     pub device: GpuDevice,
     data: Vec<Complex>,
+    /// Qubits that must all be |1> for kernels to touch an amplitude (controlled gates)
+    control_mask: usize,
 }
 
-/// Complex number representation
-#[derive(Clone, Copy, Debug)]
+/// Complex number representation, serialized as `[re, im]`. Laid out as `re, im` so the
+/// SIMD kernels load two amplitudes per register.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "[f64; 2]", into = "[f64; 2]")]
+#[repr(C)]
 pub struct Complex {
     pub re: f64,
     pub im: f64,
@@ -69,12 +94,50 @@ impl Complex {
     pub fn conjugate(&self) -> Self {
         Self { re: self.re, im: -self.im }
     }
+
+    /// `r * e^(i*theta)`
+    pub fn from_polar(r: f64, theta: f64) -> Self {
+        Self { re: r * theta.cos(), im: r * theta.sin() }
+    }
+}
+
+impl From<[f64; 2]> for Complex {
+    fn from([re, im]: [f64; 2]) -> Self {
+        Self { re, im }
+    }
+}
+
+impl From<Complex> for [f64; 2] {
+    fn from(c: Complex) -> Self {
+        [c.re, c.im]
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
 }
 
 impl GpuStateVector {
     /// Create a new GPU state vector
     pub fn new(num_qubits: usize) -> Self {
         let size = 1 << num_qubits;
+        let _span = profiler::span("upload initial state", Track::Transfer)
+            .with_args(json!({ "bytes": size * std::mem::size_of::<Complex>() }));
         let mut data = vec![Complex::new(0.0, 0.0); size];
         data[0] = Complex::new(1.0, 0.0); // Initialize to |0...0>
 
@@ -82,47 +145,244 @@ impl GpuStateVector {
             size,
             device: GpuDevice::new(),
             data,
+            control_mask: 0,
         }
     }
 
+    /// Wrap existing amplitudes, e.g. a saved checkpoint; the length must be a power of two
+    pub fn from_amplitudes(data: Vec<Complex>) -> Self {
+        let _span = profiler::span("upload initial state", Track::Transfer)
+            .with_args(json!({ "bytes": data.len() * std::mem::size_of::<Complex>() }));
+        Self {
+            size: data.len(),
+            device: GpuDevice::new(),
+            data,
+            control_mask: 0,
+        }
+    }
+
+    /// Restrict every following kernel to amplitudes whose `mask` bits are all set,
+    /// returning the previous mask so nested controls can restore it
+    pub fn set_control_mask(&mut self, mask: usize) -> usize {
+        std::mem::replace(&mut self.control_mask, mask)
+    }
+
     /// Apply Hadamard gate on GPU
     pub fn apply_hadamard_gpu(&mut self, qubit: usize) {
         let stride = 1 << qubit;
         let factor = 1.0 / 2.0_f64.sqrt();
 
         // Simulate GPU parallel execution
-        for i in 0..self.size {
+        let launcher = self.launcher("hadamard");
+        let vectorized = self.vectorized(stride);
+        let data = Shared::new(&mut self.data);
+        if vectorized {
+            let matrix = [[factor, factor], [factor, -factor]];
+            launcher.launch_threaded(|i| {
+                if i & (stride | 1) == 0 {
+                    // SAFETY: the thread owning i also owns i + 1 and their partners
+                    unsafe { simd::real_butterfly(data.pair(i), data.pair(i | stride), &matrix) }
+                }
+            });
+            return;
+        }
+        launcher.launch_threaded(|i| {
             if i & stride == 0 {
                 let j = i | stride;
-                let a = self.data[i];
-                let b = self.data[j];
+                let a = data.get(i);
+                let b = data.get(j);
 
-                self.data[i] = Complex::new(
+                data.set(i, Complex::new(
                     factor * (a.re + b.re),
                     factor * (a.im + b.im),
-                );
-                self.data[j] = Complex::new(
+                ));
+                data.set(j, Complex::new(
                     factor * (a.re - b.re),
                     factor * (a.im - b.im),
-                );
+                ));
             }
-        }
+        });
     }
 
-    /// Apply CNOT gate on GPU
-    pub fn apply_cnot_gpu(&mut self, control: usize, target: usize) {
-        let control_mask = 1 << control;
-        let target_mask = 1 << target;
+    /// Apply a dense 4x4 matrix on GPU, indexed by (bit of qubit1) + 2 * (bit of qubit2);
+    /// the qubits need not be adjacent
+    pub fn apply_two_qubit_gpu(&mut self, qubit1: usize, qubit2: usize, matrix: &[[Complex; 4]; 4]) {
+        let mask1 = 1 << qubit1;
+        let mask2 = 1 << qubit2;
+        let offsets = [0, mask1, mask2, mask1 | mask2];
 
         // Simulate GPU parallel execution
-        for i in 0..self.size {
-            if (i & control_mask) != 0 && (i & target_mask) == 0 {
+        let launcher = self.launcher("two_qubit");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            if i & (mask1 | mask2) == 0 {
+                let amplitudes = offsets.map(|offset| data.get(i | offset));
+                for (row, offset) in matrix.iter().zip(offsets) {
+                    let value = row.iter().zip(&amplitudes).fold(Complex::new(0.0, 0.0), |sum, (&m, &a)| sum + m * a);
+                    data.set(i | offset, value);
+                }
+            }
+        });
+    }
+
+    /// Apply multi-controlled X on GPU: swap target pairs whose control bits are all set
+    pub fn apply_mcx_gpu(&mut self, controls: &[usize], target: usize) {
+        let control_mask = controls.iter().fold(0, |mask, &control| mask | (1 << control));
+        let target_mask = 1 << target;
+
+        let launcher = self.launcher("mcx");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            if i & control_mask == control_mask && i & target_mask == 0 {
                 let j = i | target_mask;
-                let temp = self.data[i];
-                self.data[i] = self.data[j];
-                self.data[j] = temp;
+                let a = data.get(i);
+                data.set(i, data.get(j));
+                data.set(j, a);
+            }
+        });
+    }
+
+    /// Apply an Ising interaction exp(-i*angle/2 * P⊗P) on GPU
+    pub fn apply_ising_gpu(&mut self, qubit1: usize, qubit2: usize, axis: RotationAxis, angle: f64) {
+        match axis {
+            // X⊗X maps |00> to |11>; Y⊗Y does the same with a sign of -1
+            RotationAxis::X => self.apply_paired_ising_gpu("rxx", qubit1, qubit2, 1.0, angle),
+            RotationAxis::Y => self.apply_paired_ising_gpu("ryy", qubit1, qubit2, -1.0, angle),
+            RotationAxis::Z => self.apply_rzz_gpu(qubit1, qubit2, angle),
+        }
+    }
+
+    /// Mix |00> with |11> and |01> with |10>; `sign` is the P⊗P coefficient between |00> and |11>
+    fn apply_paired_ising_gpu(&mut self, kernel: &'static str, qubit1: usize, qubit2: usize, sign: f64, angle: f64) {
+        let mask1 = 1 << qubit1;
+        let mask2 = 1 << qubit2;
+        let cos_half = Complex::new((angle / 2.0).cos(), 0.0);
+        // -i * sin(angle / 2) times the P⊗P coefficient
+        let even = Complex::new(0.0, -sign * (angle / 2.0).sin());
+        let odd = Complex::new(0.0, -(angle / 2.0).sin());
+
+        let launcher = self.launcher(kernel);
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            if i & (mask1 | mask2) == 0 {
+                for (j, k, factor) in [(i, i | mask1 | mask2, even), (i | mask1, i | mask2, odd)] {
+                    let a = data.get(j);
+                    let b = data.get(k);
+                    data.set(j, cos_half * a + factor * b);
+                    data.set(k, cos_half * b + factor * a);
+                }
             }
+        });
+    }
+
+    /// Apply a Givens rotation on GPU: a real rotation by `angle` within the |01>, |10> subspace
+    pub fn apply_givens_gpu(&mut self, qubit1: usize, qubit2: usize, angle: f64) {
+        let mask1 = 1 << qubit1;
+        let mask2 = 1 << qubit2;
+        let (sin, cos) = angle.sin_cos();
+
+        // One thread per |00> index, touching only its |01> and |10> partners
+        let launcher = self.launcher("givens");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            if i & (mask1 | mask2) == 0 {
+                let (low, high) = (i | mask2, i | mask1);
+                let a = data.get(low);
+                let b = data.get(high);
+                data.set(low, Complex::new(cos * a.re - sin * b.re, cos * a.im - sin * b.im));
+                data.set(high, Complex::new(sin * a.re + cos * b.re, sin * a.im + cos * b.im));
+            }
+        });
+    }
+
+    /// Apply a Pauli-string rotation exp(-i*angle/2 * P) on GPU without decomposing it
+    ///
+    /// P is given by the bits it flips (`x_mask`: its X and Y qubits) and the bits whose
+    /// parity sets its sign (`z_mask`: its Z and Y qubits), so P|b> = i^#Y (-1)^|b & z_mask| |b ^ x_mask>.
+    pub fn apply_pauli_rotation_gpu(&mut self, x_mask: usize, z_mask: usize, angle: f64) {
+        let cos_half = Complex::new((angle / 2.0).cos(), 0.0);
+        let sin_half = (angle / 2.0).sin();
+        // -i * sin(angle / 2) * i^#Y
+        let factor = match (x_mask & z_mask).count_ones() % 4 {
+            0 => Complex::new(0.0, -sin_half),
+            1 => Complex::new(sin_half, 0.0),
+            2 => Complex::new(0.0, sin_half),
+            _ => Complex::new(-sin_half, 0.0),
+        };
+        let signed = |b: usize| {
+            if (b & z_mask).count_ones() & 1 == 0 {
+                factor
+            } else {
+                Complex::new(-factor.re, -factor.im)
+            }
+        };
+
+        if x_mask == 0 {
+            // Only Z and I: each amplitude picks up a phase set by its parity
+            let launcher = self.launcher("pauli_rotation_diagonal");
+            let data = Shared::new(&mut self.data);
+            launcher.launch_threaded(|i| {
+                data.set(i, (cos_half + signed(i)) * data.get(i));
+            });
+            return;
         }
+
+        // P pairs |b> with |b ^ x_mask>; the thread whose lowest flipped bit is clear owns the pair
+        let pivot = x_mask & x_mask.wrapping_neg();
+        let launcher = self.launcher("pauli_rotation");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            if i & pivot == 0 {
+                let j = i ^ x_mask;
+                let a = data.get(i);
+                let b = data.get(j);
+                data.set(i, cos_half * a + signed(j) * b);
+                data.set(j, cos_half * b + signed(i) * a);
+            }
+        });
+    }
+
+    /// Apply RZZ on GPU: e^(-i*angle/2) on even parity, e^(i*angle/2) on odd parity
+    fn apply_rzz_gpu(&mut self, qubit1: usize, qubit2: usize, angle: f64) {
+        let mask1 = 1 << qubit1;
+        let mask2 = 1 << qubit2;
+        let even = Complex::from_polar(1.0, -angle / 2.0);
+        let odd = Complex::from_polar(1.0, angle / 2.0);
+
+        let launcher = self.launcher("rzz");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            let factor = if (i & mask1 == 0) == (i & mask2 == 0) { even } else { odd };
+            data.set(i, factor * data.get(i));
+        });
+    }
+
+    /// Apply controlled-Z on GPU: negate amplitudes with both bits set
+    pub fn apply_cz_gpu(&mut self, control: usize, target: usize) {
+        let mask = (1 << control) | (1 << target);
+
+        let launcher = self.launcher("cz");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            if i & mask == mask {
+                let a = data.get(i);
+                data.set(i, Complex::new(-a.re, -a.im));
+            }
+        });
+    }
+
+    /// Apply controlled-phase on GPU: multiply amplitudes with both bits set by e^(i*phase)
+    pub fn apply_cphase_gpu(&mut self, control: usize, target: usize, phase: f64) {
+        let mask = (1 << control) | (1 << target);
+        let factor = Complex::from_polar(1.0, phase);
+
+        let launcher = self.launcher("cphase");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            if i & mask == mask {
+                data.set(i, factor * data.get(i));
+            }
+        });
     }
 
     /// Apply phase gate on GPU
@@ -132,16 +392,48 @@ impl GpuStateVector {
         let sin_phase = phase.sin();
 
         // Simulate GPU parallel execution
-        for i in 0..self.size {
+        let launcher = self.launcher("phase");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
             if i & mask != 0 {
-                let old_re = self.data[i].re;
-                let old_im = self.data[i].im;
-                self.data[i] = Complex::new(
+                let old = data.get(i);
+                let (old_re, old_im) = (old.re, old.im);
+                data.set(i, Complex::new(
                     old_re * cos_phase - old_im * sin_phase,
                     old_re * sin_phase + old_im * cos_phase,
-                );
+                ));
             }
-        }
+        });
+    }
+
+    /// Apply S (or S-dagger) on GPU: multiply |1> amplitudes by ±i without trig
+    pub fn apply_s_gpu(&mut self, qubit: usize, adjoint: bool) {
+        let mask = 1 << qubit;
+
+        let launcher = self.launcher("s");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            if i & mask != 0 {
+                let a = data.get(i);
+                data.set(i, if adjoint { Complex::new(a.im, -a.re) } else { Complex::new(-a.im, a.re) });
+            }
+        });
+    }
+
+    /// Apply T (or T-dagger) on GPU: multiply |1> amplitudes by e^(±i*pi/4)
+    pub fn apply_t_gpu(&mut self, qubit: usize, adjoint: bool) {
+        let mask = 1 << qubit;
+        let c = std::f64::consts::FRAC_1_SQRT_2;
+        let s = if adjoint { -c } else { c };
+
+        let launcher = self.launcher("t");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            if i & mask != 0 {
+                let a = data.get(i);
+                data.set(i, Complex::new(c * a.re - s * a.im, s * a.re + c * a.im));
+            }
+        });
     }
 
     /// Apply Pauli-X gate on GPU
@@ -149,14 +441,16 @@ impl GpuStateVector {
         let mask = 1 << qubit;
 
         // Simulate GPU parallel execution
-        for i in 0..self.size {
+        let launcher = self.launcher("x");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
             if i & mask == 0 {
                 let j = i | mask;
-                let temp = self.data[i];
-                self.data[i] = self.data[j];
-                self.data[j] = temp;
+                let temp = data.get(i);
+                data.set(i, data.get(j));
+                data.set(j, temp);
             }
-        }
+        });
     }
 
     /// Apply Pauli-Y gate on GPU
@@ -164,16 +458,18 @@ impl GpuStateVector {
         let mask = 1 << qubit;
 
         // Simulate GPU parallel execution
-        for i in 0..self.size {
+        let launcher = self.launcher("y");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
             if i & mask == 0 {
                 let j = i | mask;
-                let temp_i = self.data[i];
-                let temp_j = self.data[j];
+                let temp_i = data.get(i);
+                let temp_j = data.get(j);
 
-                self.data[i] = Complex::new(temp_j.im, -temp_j.re);
-                self.data[j] = Complex::new(-temp_i.im, temp_i.re);
+                data.set(i, Complex::new(temp_j.im, -temp_j.re));
+                data.set(j, Complex::new(-temp_i.im, temp_i.re));
             }
-        }
+        });
     }
 
     /// Apply Pauli-Z gate on GPU
@@ -181,12 +477,14 @@ impl GpuStateVector {
         let mask = 1 << qubit;
 
         // Simulate GPU parallel execution
-        for i in 0..self.size {
+        let launcher = self.launcher("z");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
             if i & mask != 0 {
-                self.data[i].re = -self.data[i].re;
-                self.data[i].im = -self.data[i].im;
+                let a = data.get(i);
+                data.set(i, Complex::new(-a.re, -a.im));
             }
-        }
+        });
     }
 
     /// Apply rotation gate on GPU
@@ -204,22 +502,35 @@ impl GpuStateVector {
         let cos_half = (angle / 2.0).cos();
         let sin_half = (angle / 2.0).sin();
 
-        for i in 0..self.size {
+        let launcher = self.launcher("rx");
+        let vectorized = self.vectorized(mask);
+        let data = Shared::new(&mut self.data);
+        if vectorized {
+            let matrix = [[Complex::new(cos_half, 0.0), Complex::new(0.0, -sin_half)], [Complex::new(0.0, -sin_half), Complex::new(cos_half, 0.0)]];
+            launcher.launch_threaded(|i| {
+                if i & (mask | 1) == 0 {
+                    // SAFETY: as for the Hadamard kernel
+                    unsafe { simd::butterfly(data.pair(i), data.pair(i | mask), &matrix) }
+                }
+            });
+            return;
+        }
+        launcher.launch_threaded(|i| {
             if i & mask == 0 {
                 let j = i | mask;
-                let a = self.data[i];
-                let b = self.data[j];
+                let a = data.get(i);
+                let b = data.get(j);
 
-                self.data[i] = Complex::new(
+                data.set(i, Complex::new(
                     cos_half * a.re + sin_half * b.im,
                     cos_half * a.im - sin_half * b.re,
-                );
-                self.data[j] = Complex::new(
+                ));
+                data.set(j, Complex::new(
                     cos_half * b.re + sin_half * a.im,
                     cos_half * b.im - sin_half * a.re,
-                );
+                ));
             }
-        }
+        });
     }
 
     /// Apply RY rotation on GPU
@@ -228,36 +539,357 @@ impl GpuStateVector {
         let cos_half = (angle / 2.0).cos();
         let sin_half = (angle / 2.0).sin();
 
-        for i in 0..self.size {
+        let launcher = self.launcher("ry");
+        let vectorized = self.vectorized(mask);
+        let data = Shared::new(&mut self.data);
+        if vectorized {
+            let matrix = [[cos_half, -sin_half], [sin_half, cos_half]];
+            launcher.launch_threaded(|i| {
+                if i & (mask | 1) == 0 {
+                    // SAFETY: as for the Hadamard kernel
+                    unsafe { simd::real_butterfly(data.pair(i), data.pair(i | mask), &matrix) }
+                }
+            });
+            return;
+        }
+        launcher.launch_threaded(|i| {
             if i & mask == 0 {
                 let j = i | mask;
-                let a = self.data[i];
-                let b = self.data[j];
+                let a = data.get(i);
+                let b = data.get(j);
 
-                self.data[i] = Complex::new(
+                data.set(i, Complex::new(
                     cos_half * a.re - sin_half * b.re,
                     cos_half * a.im - sin_half * b.im,
-                );
-                self.data[j] = Complex::new(
+                ));
+                data.set(j, Complex::new(
                     sin_half * a.re + cos_half * b.re,
                     sin_half * a.im + cos_half * b.im,
-                );
+                ));
             }
+        });
+    }
+
+    /// Apply U3(theta, phi, lambda) on GPU:
+    /// [[cos(t/2), -e^(i*l) sin(t/2)], [e^(i*p) sin(t/2), e^(i*(p+l)) cos(t/2)]]
+    pub fn apply_u_gpu(&mut self, qubit: usize, theta: f64, phi: f64, lambda: f64) {
+        let mask = 1 << qubit;
+        let (sin_half, cos_half) = (theta / 2.0).sin_cos();
+        let m00 = Complex::new(cos_half, 0.0);
+        let m01 = Complex::from_polar(-sin_half, lambda);
+        let m10 = Complex::from_polar(sin_half, phi);
+        let m11 = Complex::from_polar(cos_half, phi + lambda);
+
+        let launcher = self.launcher("u");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            if i & mask == 0 {
+                let j = i | mask;
+                let a = data.get(i);
+                let b = data.get(j);
+
+                data.set(i, m00 * a + m01 * b);
+                data.set(j, m10 * a + m11 * b);
+            }
+        });
+    }
+
+    /// Apply an arbitrary 2x2 matrix `[[m00, m01], [m10, m11]]` on GPU
+    pub fn apply_matrix_gpu(&mut self, qubit: usize, matrix: &[[Complex; 2]; 2]) {
+        let mask = 1 << qubit;
+        let [[m00, m01], [m10, m11]] = *matrix;
+
+        let launcher = self.launcher("unitary");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            if i & mask == 0 {
+                let j = i | mask;
+                let a = data.get(i);
+                let b = data.get(j);
+
+                data.set(i, m00 * a + m01 * b);
+                data.set(j, m10 * a + m11 * b);
+            }
+        });
+    }
+
+    /// Apply a dense 2^k x 2^k matrix on GPU; `qubits[0]` is the least significant bit of the matrix index
+    pub fn apply_matrix_n_gpu(&mut self, qubits: &[usize], matrix: &[Vec<Complex>]) {
+        let mask = qubits.iter().fold(0, |mask, &q| mask | (1 << q));
+        // Offset of each matrix index from a base index with every target bit clear
+        let offsets: Vec<usize> = (0..1usize << qubits.len())
+            .map(|k| {
+                qubits
+                    .iter()
+                    .enumerate()
+                    .fold(0, |offset, (bit, &q)| offset | (((k >> bit) & 1) << q))
+            })
+            .collect();
+
+        let launcher = self.launcher("unitary_nq");
+        let data = Shared::new(&mut self.data);
+        // Each thread gathers its group's amplitudes into a buffer of its own
+        let scratch = || vec![Complex::new(0.0, 0.0); offsets.len()];
+        launcher.launch_threaded_with(scratch, |gathered, i| {
+            if i & mask == 0 {
+                for (amplitude, &offset) in gathered.iter_mut().zip(&offsets) {
+                    *amplitude = data.get(i | offset);
+                }
+                for (row, &offset) in matrix.iter().zip(&offsets) {
+                    let value = row.iter().zip(gathered.iter()).fold(Complex::new(0.0, 0.0), |sum, (&m, &a)| sum + m * a);
+                    data.set(i | offset, value);
+                }
+            }
+        });
+    }
+
+    /// Apply Hadamard to every listed qubit on GPU in one pass
+    pub fn apply_global_hadamard_gpu(&mut self, qubits: &[usize]) {
+        let h = Complex::new(std::f64::consts::FRAC_1_SQRT_2, 0.0);
+        self.apply_tensor_power_gpu("global_h", qubits, &[[h, h], [h, Complex::new(-h.re, 0.0)]]);
+    }
+
+    /// Apply the same rotation to every listed qubit on GPU in one pass
+    pub fn apply_global_rotation_gpu(&mut self, qubits: &[usize], axis: RotationAxis, angle: f64) {
+        let cos_half = Complex::new((angle / 2.0).cos(), 0.0);
+        let sin_half = (angle / 2.0).sin();
+        match axis {
+            RotationAxis::X => {
+                let off_diagonal = Complex::new(0.0, -sin_half);
+                self.apply_tensor_power_gpu("global_rx", qubits, &[[cos_half, off_diagonal], [off_diagonal, cos_half]]);
+            }
+            RotationAxis::Y => {
+                let sin_half = Complex::new(sin_half, 0.0);
+                let minus_sin = Complex::new(-sin_half.re, 0.0);
+                self.apply_tensor_power_gpu("global_ry", qubits, &[[cos_half, minus_sin], [sin_half, cos_half]]);
+            }
+            RotationAxis::Z => self.apply_global_rz_gpu(qubits, angle),
         }
     }
 
+    /// Apply a 2x2 matrix to every listed qubit; each thread owns the 2^k amplitudes that
+    /// differ only in those qubits and runs all k butterfly levels on them
+    fn apply_tensor_power_gpu(&mut self, kernel: &'static str, qubits: &[usize], matrix: &[[Complex; 2]; 2]) {
+        let mask = qubits.iter().fold(0, |mask, &q| mask | (1 << q));
+        let offsets: Vec<usize> = (0..1usize << qubits.len())
+            .map(|k| {
+                qubits
+                    .iter()
+                    .enumerate()
+                    .fold(0, |offset, (bit, &q)| offset | (((k >> bit) & 1) << q))
+            })
+            .collect();
+
+        let launcher = self.launcher(kernel);
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            if i & mask == 0 {
+                for level in 0..qubits.len() {
+                    let half = 1 << level;
+                    for k in (0..offsets.len()).filter(|k| k & half == 0) {
+                        let (j, l) = (i | offsets[k], i | offsets[k | half]);
+                        let a = data.get(j);
+                        let b = data.get(l);
+                        data.set(j, matrix[0][0] * a + matrix[0][1] * b);
+                        data.set(l, matrix[1][0] * a + matrix[1][1] * b);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Apply e^(i*phases[k]) on GPU, where k gathers the listed qubits' bits; one pass over
+    /// the state with the 2^k factors computed up front
+    pub fn apply_diagonal_gpu(&mut self, qubits: &[usize], phases: &[f64]) {
+        let factors: Vec<Complex> = phases.iter().map(|&phase| Complex::from_polar(1.0, phase)).collect();
+
+        let launcher = self.launcher("diagonal");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            let k = qubits.iter().enumerate().fold(0, |k, (bit, &q)| k | ((i >> q) & 1) << bit);
+            data.set(i, factors[k] * data.get(i));
+        });
+    }
+
+    /// Apply RZ to every listed qubit on GPU: the phase depends only on how many are |1>
+    fn apply_global_rz_gpu(&mut self, qubits: &[usize], angle: f64) {
+        let mask: usize = qubits.iter().fold(0, |mask, &q| mask | (1 << q));
+        let factors: Vec<Complex> = (0..=qubits.len())
+            .map(|ones| Complex::from_polar(1.0, angle / 2.0 * (2.0 * ones as f64 - qubits.len() as f64)))
+            .collect();
+
+        let launcher = self.launcher("global_rz");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            data.set(i, factors[(i & mask).count_ones() as usize] * data.get(i));
+        });
+    }
+
+    /// Apply SX (square root of X) on GPU, or its adjoint
+    pub fn apply_sx_gpu(&mut self, qubit: usize, adjoint: bool) {
+        let mask = 1 << qubit;
+        // SX = [[1+i, 1-i], [1-i, 1+i]] / 2; the adjoint conjugates every entry
+        let sign = if adjoint { -1.0 } else { 1.0 };
+        let diagonal = Complex::new(0.5, 0.5 * sign);
+        let off_diagonal = Complex::new(0.5, -0.5 * sign);
+
+        let launcher = self.launcher("sx");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            if i & mask == 0 {
+                let j = i | mask;
+                let a = data.get(i);
+                let b = data.get(j);
+
+                data.set(i, diagonal * a + off_diagonal * b);
+                data.set(j, off_diagonal * a + diagonal * b);
+            }
+        });
+    }
+
     /// Apply RZ rotation on GPU
+    /// diag(e^(-i*angle/2), e^(i*angle/2)): the phase gate up to a global phase, which
+    /// becomes a relative phase once the rotation is controlled
     fn apply_rz_gpu(&mut self, qubit: usize, angle: f64) {
-        self.apply_phase_gpu(qubit, angle);
+        let mask = 1 << qubit;
+        let zero_phase = Complex::from_polar(1.0, -angle / 2.0);
+        let one_phase = Complex::from_polar(1.0, angle / 2.0);
+
+        let launcher = self.launcher("rz");
+        let vectorized = self.vectorized(mask);
+        let data = Shared::new(&mut self.data);
+        if vectorized {
+            // i and i + 1 agree on the qubit, so they take the same phase
+            launcher.launch_threaded(|i| {
+                if i & 1 == 0 {
+                    let phase = if i & mask == 0 { zero_phase } else { one_phase };
+                    // SAFETY: as for the Hadamard kernel
+                    unsafe { simd::scale(data.pair(i), phase) }
+                }
+            });
+            return;
+        }
+        launcher.launch_threaded(|i| {
+            let phase = if i & mask == 0 { zero_phase } else { one_phase };
+            data.set(i, phase * data.get(i));
+        });
+    }
+
+    /// Reset a qubit to |0>: pick a measurement outcome with the Born rule, keep only the
+    /// amplitudes that agree with it, move them into the |0> half and renormalize
+    ///
+    /// Returns the outcome the reset collapsed onto.
+    pub fn apply_reset_gpu<R: Rng + ?Sized>(&mut self, qubit: usize, rng: &mut R) -> bool {
+        let mask = 1 << qubit;
+        let one_probability = self.one_probability(qubit);
+
+        let outcome = rng.gen::<f64>() < one_probability;
+        let kept = if outcome { one_probability } else { 1.0 - one_probability };
+        let scale = Complex::new(1.0 / kept.sqrt(), 0.0);
+        let launcher = self.launcher("reset");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            if i & mask == 0 {
+                let j = i | mask;
+                let amplitude = if outcome { data.get(j) } else { data.get(i) };
+                data.set(i, scale * amplitude);
+                data.set(j, Complex::new(0.0, 0.0));
+            }
+        });
+        outcome
+    }
+
+    /// Projectively measure a qubit on GPU: pick an outcome with the Born rule, zero the
+    /// amplitudes that disagree and renormalize the rest. Returns whether it was |1>
+    pub fn apply_measure_gpu<R: Rng + ?Sized>(&mut self, qubit: usize, rng: &mut R) -> bool {
+        let mask = 1 << qubit;
+        let one_probability = self.one_probability(qubit);
+
+        let outcome = rng.gen::<f64>() < one_probability;
+        let kept = if outcome { one_probability } else { 1.0 - one_probability };
+        let scale = Complex::new(1.0 / kept.sqrt(), 0.0);
+        let launcher = self.launcher("measure");
+        let data = Shared::new(&mut self.data);
+        launcher.launch_threaded(|i| {
+            data.set(i, if (i & mask != 0) == outcome { scale * data.get(i) } else { Complex::new(0.0, 0.0) });
+        });
+        outcome
+    }
+
+    /// Probability of finding a qubit in |1>
+    fn one_probability(&self, qubit: usize) -> f64 {
+        let mask = 1 << qubit;
+        let data = &self.data;
+        self.launcher("reset_probability").sum_threaded(|i| if i & mask != 0 { data[i].magnitude_squared() } else { 0.0 })
+    }
+
+    /// Launcher for a kernel over every amplitude, using the tuned configuration
+    fn launcher(&self, kernel: &'static str) -> GpuKernelLauncher {
+        GpuKernelLauncher::tuned(&self.device, kernel, self.size).with_control_mask(self.control_mask)
+    }
+
+    /// Whether a single-qubit kernel on `mask` can update amplitudes two at a time: the pair
+    /// i, i + 1 must agree on the target and control bits, so neither may be qubit 0
+    fn vectorized(&self, mask: usize) -> bool {
+        (mask | self.control_mask) & 1 == 0 && simd::available()
     }
 
     /// Measure all qubits on GPU
     pub fn measure_all_gpu(&self) -> Vec<f64> {
+        let _span = profiler::span("download probabilities", Track::Transfer)
+            .with_args(json!({ "bytes": self.size * std::mem::size_of::<f64>() }));
         self.data.iter()
             .map(|c| c.magnitude_squared())
             .collect()
     }
 
+    /// Draw basis-state indices without a probability buffer: one kernel sums |amplitude|^2
+    /// per chunk, then each chunk that receives shots rescans its own amplitudes to place them
+    pub fn sample_gpu<R: Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Vec<usize> {
+        let chunks = self.size.div_ceil(SAMPLE_CHUNK);
+        let mut sums = vec![0.0; chunks];
+        GpuKernelLauncher::tuned(&self.device, "sample_sums", chunks).launch_indexed(|c| {
+            let end = ((c + 1) * SAMPLE_CHUNK).min(self.size);
+            sums[c] = self.data[c * SAMPLE_CHUNK..end].iter().map(|a| a.magnitude_squared()).sum();
+        });
+
+        // Probability mass before each chunk
+        let mut before = Vec::with_capacity(chunks);
+        let mut total = 0.0;
+        for sum in &sums {
+            before.push(total);
+            total += sum;
+        }
+
+        // Sorted draws let every chunk find its shots with two binary searches
+        let mut draws: Vec<(f64, usize)> = (0..shots).map(|shot| (rng.gen::<f64>() * total, shot)).collect();
+        draws.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut outcomes = vec![0; shots];
+        GpuKernelLauncher::tuned(&self.device, "sample", chunks).launch_indexed(|c| {
+            let first = draws.partition_point(|&(r, _)| r < before[c]);
+            let last = if c + 1 == chunks { shots } else { draws.partition_point(|&(r, _)| r < before[c + 1]) };
+            if first == last {
+                return;
+            }
+            let start = c * SAMPLE_CHUNK;
+            let end = ((c + 1) * SAMPLE_CHUNK).min(self.size);
+            let (mut index, mut cumulative) = (start, before[c]);
+            for &(r, shot) in &draws[first..last] {
+                // Clamped to the chunk so rounding in the sums cannot push a shot past it
+                while index + 1 < end && cumulative + self.data[index].magnitude_squared() <= r {
+                    cumulative += self.data[index].magnitude_squared();
+                    index += 1;
+                }
+                outcomes[shot] = index;
+            }
+        });
+
+        let _span = profiler::span("download samples", Track::Transfer)
+            .with_args(json!({ "bytes": shots * std::mem::size_of::<u64>() }));
+        outcomes
+    }
+
     /// Get state vector data
     pub fn get_data(&self) -> &[Complex] {
         &self.data
@@ -265,19 +897,151 @@ impl GpuStateVector {
 
     /// Transfer data to GPU (simulated)
     pub fn upload_to_gpu(&self) {
+        let _span = profiler::span("upload state", Track::Transfer);
         println!("Uploading {} bytes to GPU...", self.size * std::mem::size_of::<Complex>());
     }
 
     /// Transfer data from GPU (simulated)
     pub fn download_from_gpu(&self) {
+        let _span = profiler::span("download state", Track::Transfer);
         println!("Downloading {} bytes from GPU...", self.size * std::mem::size_of::<Complex>());
     }
 }
 
+impl SimulatorBackend for GpuStateVector {
+    fn name(&self) -> &'static str {
+        "gpu"
+    }
+
+    fn num_qubits(&self) -> usize {
+        self.size.trailing_zeros() as usize
+    }
+
+    fn clone_box(&self) -> Box<dyn SimulatorBackend> {
+        Box::new(self.clone())
+    }
+
+    fn apply_matrix(&mut self, qubit: usize, matrix: &[[Complex; 2]; 2]) {
+        self.apply_matrix_gpu(qubit, matrix);
+    }
+
+    fn apply_matrix_n(&mut self, qubits: &[usize], matrix: &[Vec<Complex>]) {
+        match (qubits, matrix) {
+            ([qubit1, qubit2], [row0, row1, row2, row3]) => {
+                let row = |row: &Vec<Complex>| [row[0], row[1], row[2], row[3]];
+                self.apply_two_qubit_gpu(*qubit1, *qubit2, &[row(row0), row(row1), row(row2), row(row3)]);
+            }
+            _ => self.apply_matrix_n_gpu(qubits, matrix),
+        }
+    }
+
+    fn measure(&mut self, qubit: usize, rng: &mut dyn RngCore) -> bool {
+        self.apply_measure_gpu(qubit, rng)
+    }
+
+    fn probabilities(&self) -> Vec<f64> {
+        self.measure_all_gpu()
+    }
+
+    fn amplitudes(&self) -> Cow<'_, [Complex]> {
+        Cow::Borrowed(self.get_data())
+    }
+
+    /// One qubit runs on the threaded measurement kernel; several share one pass instead
+    fn one_probabilities(&self, qubits: &[usize]) -> Vec<f64> {
+        if let [qubit] = qubits {
+            return vec![self.one_probability(*qubit)];
+        }
+        let mut ones = vec![0.0; qubits.len()];
+        simulator_backend::add_one_probabilities(&self.data, 0, qubits, &mut ones);
+        ones
+    }
+
+    fn reset(&mut self, qubit: usize, rng: &mut dyn RngCore) -> bool {
+        self.apply_reset_gpu(qubit, rng)
+    }
+
+    fn sample(&self, shots: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        self.sample_gpu(shots, rng)
+    }
+
+    /// Every gate on its own kernel; controlled gates mask the kernels of the gate they wrap
+    fn apply_gate(&mut self, gate: &QuantumGate) {
+        match gate {
+            QuantumGate::Hadamard { qubit } => self.apply_hadamard_gpu(*qubit),
+            QuantumGate::PauliX { qubit } => self.apply_x_gpu(*qubit),
+            QuantumGate::PauliY { qubit } => self.apply_y_gpu(*qubit),
+            QuantumGate::PauliZ { qubit } => self.apply_z_gpu(*qubit),
+            QuantumGate::S { qubit } => self.apply_s_gpu(*qubit, false),
+            QuantumGate::Sdg { qubit } => self.apply_s_gpu(*qubit, true),
+            QuantumGate::T { qubit } => self.apply_t_gpu(*qubit, false),
+            QuantumGate::Tdg { qubit } => self.apply_t_gpu(*qubit, true),
+            QuantumGate::SX { qubit } => self.apply_sx_gpu(*qubit, false),
+            QuantumGate::SXdg { qubit } => self.apply_sx_gpu(*qubit, true),
+            QuantumGate::Phase { qubit, angle } => self.apply_phase_gpu(*qubit, *angle),
+            QuantumGate::CNOT { .. }
+            | QuantumGate::SWAP { .. }
+            | QuantumGate::ISWAP { .. }
+            | QuantumGate::SqrtISWAP { .. }
+            | QuantumGate::ECR { .. } => {
+                let (qubit1, qubit2, matrix) = qsim::two_qubit_matrix(gate).expect("two-qubit gate has a matrix");
+                self.apply_two_qubit_gpu(qubit1, qubit2, &matrix);
+            }
+            QuantumGate::RXX { qubit1, qubit2, angle } => self.apply_ising_gpu(*qubit1, *qubit2, RotationAxis::X, *angle),
+            QuantumGate::RYY { qubit1, qubit2, angle } => self.apply_ising_gpu(*qubit1, *qubit2, RotationAxis::Y, *angle),
+            QuantumGate::RZZ { qubit1, qubit2, angle } => self.apply_ising_gpu(*qubit1, *qubit2, RotationAxis::Z, *angle),
+            QuantumGate::Givens { qubit1, qubit2, angle } => self.apply_givens_gpu(*qubit1, *qubit2, *angle),
+            QuantumGate::CZ { control, target } => self.apply_cz_gpu(*control, *target),
+            QuantumGate::CPhase { control, target, angle } => self.apply_cphase_gpu(*control, *target, *angle),
+            // Exact permutation; target-only RZ decompositions leave relative phases
+            QuantumGate::Toffoli { control1, control2, target } => self.apply_mcx_gpu(&[*control1, *control2], *target),
+            QuantumGate::MCX { controls, target } => self.apply_mcx_gpu(controls, *target),
+            QuantumGate::RotationX { qubit, angle } => self.apply_rotation_gpu(*qubit, RotationAxis::X, *angle),
+            QuantumGate::RotationY { qubit, angle } => self.apply_rotation_gpu(*qubit, RotationAxis::Y, *angle),
+            QuantumGate::RotationZ { qubit, angle } => self.apply_rotation_gpu(*qubit, RotationAxis::Z, *angle),
+            QuantumGate::U { qubit, theta, phi, lambda } => self.apply_u_gpu(*qubit, *theta, *phi, *lambda),
+            QuantumGate::Unitary1Q { qubit, matrix } => self.apply_matrix_gpu(*qubit, matrix),
+            QuantumGate::UnitaryNQ { qubits, matrix } => SimulatorBackend::apply_matrix_n(self, qubits, matrix),
+            QuantumGate::Diagonal { qubits, phases } => self.apply_diagonal_gpu(qubits, phases),
+            QuantumGate::Controlled { control, gate } => {
+                let previous = self.set_control_mask(self.control_mask | 1 << control);
+                SimulatorBackend::apply_gate(self, gate);
+                self.set_control_mask(previous);
+            }
+            QuantumGate::GlobalHadamard { qubits } => self.apply_global_hadamard_gpu(qubits),
+            QuantumGate::GlobalRotation { qubits, axis, angle } => self.apply_global_rotation_gpu(qubits, *axis, *angle),
+            QuantumGate::PauliRotation { qubits, pauli, angle } => {
+                let (x_mask, z_mask) = qubits.iter().zip(pauli.chars()).fold((0, 0), |(x, z), (&qubit, p)| match p {
+                    'X' => (x | 1 << qubit, z),
+                    'Y' => (x | 1 << qubit, z | 1 << qubit),
+                    'Z' => (x, z | 1 << qubit),
+                    _ => (x, z),
+                });
+                self.apply_pauli_rotation_gpu(x_mask, z_mask, *angle);
+            }
+            QuantumGate::Permutation { qubits, pattern } => {
+                for (qubit1, qubit2) in qsim::permutation_swaps(qubits, pattern) {
+                    SimulatorBackend::apply_gate(self, &QuantumGate::SWAP { qubit1, qubit2 });
+                }
+            }
+            // Resolved by the simulator before a gate reaches the state
+            QuantumGate::Measurement { .. }
+            | QuantumGate::Reset { .. }
+            | QuantumGate::IfBit { .. }
+            | QuantumGate::Noisy { .. }
+            | QuantumGate::Snapshot { .. } => {}
+        }
+    }
+}
+
 /// Rotation axis for quantum rotations
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum RotationAxis {
+    #[serde(alias = "x")]
     X,
+    #[serde(alias = "y")]
     Y,
+    #[serde(alias = "z")]
     Z,
 }
 
@@ -318,23 +1082,549 @@ impl GpuMemoryPool {
 pub struct GpuKernelLauncher {
     block_size: usize,
     grid_size: usize,
+    total_work: usize,
+    /// Kernel name shown in profiling traces
+    kernel: Option<&'static str>,
+    /// Indices with any of these bits clear are skipped by `launch_indexed`
+    control_mask: usize,
+    /// Chunks of blocks per thread in a threaded launch; threads claim chunks as they
+    /// finish, so more chunks even out threads that fall behind
+    chunks_per_thread: usize,
 }
 
 impl GpuKernelLauncher {
     pub fn new(total_work: usize) -> Self {
-        let block_size = 256;
-        let grid_size = (total_work + block_size - 1) / block_size;
-        Self { block_size, grid_size }
+        Self::with_block_size(total_work, DEFAULT_CONFIG.block_size)
+    }
+
+    pub fn with_block_size(total_work: usize, block_size: usize) -> Self {
+        let block_size = block_size.max(1);
+        let grid_size = total_work.div_ceil(block_size);
+        Self { block_size, grid_size, total_work, kernel: None, control_mask: 0, chunks_per_thread: 1 }
+    }
+
+    /// Launcher using the autotuned configuration for this kernel's access pattern on `device`
+    pub fn tuned(device: &GpuDevice, kernel: &'static str, total_work: usize) -> Self {
+        let config = tuned_config(device, kernel);
+        Self {
+            kernel: Some(kernel),
+            chunks_per_thread: config.chunks_per_thread,
+            ..Self::with_block_size(total_work, config.block_size)
+        }
     }
 
-    pub fn launch<F>(&self, kernel: F)
+    /// Only run the kernel on indices with every `mask` bit set. Paired kernels stay
+    /// correct because partner indices differ only in target bits, never control bits.
+    pub fn with_control_mask(self, mask: usize) -> Self {
+        Self { control_mask: mask, ..self }
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn grid_size(&self) -> usize {
+        self.grid_size
+    }
+
+    pub fn launch<F>(&self, mut kernel: F)
     where
-        F: Fn(usize, usize),
+        F: FnMut(usize, usize),
     {
+        let _span = self.span(1);
         for grid_idx in 0..self.grid_size {
             for block_idx in 0..self.block_size {
                 kernel(grid_idx, block_idx);
             }
         }
     }
+
+    /// Launch over global thread indices, skipping the padding threads of the last block
+    pub fn launch_indexed<F>(&self, mut kernel: F)
+    where
+        F: FnMut(usize),
+    {
+        self.launch(|grid_idx, block_idx| {
+            let i = grid_idx * self.block_size + block_idx;
+            if i < self.total_work && i & self.control_mask == self.control_mask {
+                kernel(i);
+            }
+        });
+    }
+
+    /// `launch_indexed` with the blocks split across `kernel_threads()` threads. Like GPU
+    /// threads, they share the state: thread `i` may only touch index `i` and the partners
+    /// that differ from it in the kernel's target bits, and only when it owns that group.
+    pub fn launch_threaded<F>(&self, kernel: F)
+    where
+        F: Fn(usize) + Sync,
+    {
+        self.launch_threaded_with(|| (), |_, i| kernel(i));
+    }
+
+    /// `launch_threaded` handing each thread its own scratch value from `scratch`
+    pub fn launch_threaded_with<S, I, F>(&self, scratch: I, kernel: F)
+    where
+        I: Fn() -> S + Sync,
+        F: Fn(&mut S, usize) + Sync,
+    {
+        let threads = self.threads();
+        let _span = self.span(threads);
+        let chunks = self.chunks(threads);
+        let run = || {
+            let mut scratch = scratch();
+            while let Some(indices) = chunks.claim() {
+                for i in indices.filter(|i| i & self.control_mask == self.control_mask) {
+                    kernel(&mut scratch, i);
+                }
+            }
+        };
+        if threads == 1 {
+            run();
+            return;
+        }
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(run);
+            }
+        });
+    }
+
+    /// Sum of `term` over the launch's indices, each thread adding up its own blocks
+    pub fn sum_threaded<F>(&self, term: F) -> f64
+    where
+        F: Fn(usize) -> f64 + Sync,
+    {
+        let threads = self.threads();
+        let _span = self.span(threads);
+        let chunks = self.chunks(threads);
+        // Partial sums are added in chunk order, so the total does not depend on which
+        // thread claimed which chunk
+        let claim_sums = || {
+            let mut partials = Vec::new();
+            while let Some(indices) = chunks.claim() {
+                let first = indices.start;
+                let sum: f64 = indices.filter(|&i| i & self.control_mask == self.control_mask).map(&term).sum();
+                partials.push((first, sum));
+            }
+            partials
+        };
+        let mut partials = if threads == 1 {
+            claim_sums()
+        } else {
+            std::thread::scope(|scope| {
+                let workers: Vec<_> = (0..threads).map(|_| scope.spawn(claim_sums)).collect();
+                workers.into_iter().flat_map(|worker| worker.join().expect("kernel thread panicked")).collect()
+            })
+        };
+        partials.sort_unstable_by_key(|&(first, _)| first);
+        partials.into_iter().map(|(_, sum)| sum).sum()
+    }
+
+    /// The launch's blocks split into `chunks_per_thread` chunks per thread
+    fn chunks(&self, threads: usize) -> Chunks {
+        let chunks = if threads == 1 { 1 } else { threads * self.chunks_per_thread };
+        Chunks {
+            next: AtomicUsize::new(0),
+            blocks: self.grid_size.div_ceil(chunks).max(1),
+            block_size: self.block_size,
+            total_work: self.total_work,
+        }
+    }
+
+    /// Threads a launch is split across: none beyond the calling thread until every one
+    /// has at least `MIN_THREAD_WORK` indices
+    fn threads(&self) -> usize {
+        kernel_threads().min(self.total_work / MIN_THREAD_WORK).min(self.grid_size).max(1)
+    }
+
+    fn span(&self, threads: usize) -> Option<profiler::Span> {
+        match self.kernel {
+            Some(name) if profiler::is_enabled() => Some(profiler::span(name, Track::Kernel).with_args(json!({
+                "block_size": self.block_size,
+                "grid_size": self.grid_size,
+                "threads": threads,
+            }))),
+            _ => None,
+        }
+    }
+}
+
+/// Work queue of a threaded launch: consecutive runs of whole blocks, claimed in order
+struct Chunks {
+    next: AtomicUsize,
+    /// Blocks per chunk
+    blocks: usize,
+    block_size: usize,
+    total_work: usize,
+}
+
+impl Chunks {
+    /// Indices of the next unclaimed chunk, if any are left
+    fn claim(&self) -> Option<Range<usize>> {
+        let first = self.next.fetch_add(self.blocks, Ordering::Relaxed) * self.block_size;
+        (first < self.total_work).then(|| first..((first + self.blocks * self.block_size).min(self.total_work)))
+    }
+}
+
+/// Indices each thread of a threaded launch gets at least; smaller launches stay on the
+/// calling thread, where spawning threads would cost more than it saves
+const MIN_THREAD_WORK: usize = 1 << 14;
+
+thread_local! {
+    static KERNEL_THREADS: Cell<usize> = Cell::new(std::thread::available_parallelism().map_or(1, |n| n.get()));
+}
+
+/// Threads that kernels launched from this thread split their work across, all cores unless
+/// set otherwise
+pub fn kernel_threads() -> usize {
+    KERNEL_THREADS.with(Cell::get)
+}
+
+/// Set how many threads kernels launched from this thread use, e.g. fewer on threads that
+/// already run simulations side by side
+pub fn set_kernel_threads(threads: usize) {
+    KERNEL_THREADS.with(|cell| cell.set(threads.max(1)));
+}
+
+/// The state vector as shared by the threads of a launch. `GpuKernelLauncher::launch_threaded`
+/// kernels write amplitudes through it: each thread index owns a group of amplitudes that
+/// differ only in the kernel's target bits, and no other thread reads or writes that group.
+struct Shared<'a, T> {
+    ptr: *mut T,
+    len: usize,
+    _data: PhantomData<&'a mut [T]>,
+}
+
+// SAFETY: threads only touch the disjoint index groups they own, as described above
+unsafe impl<T: Send> Sync for Shared<'_, T> {}
+
+impl<'a, T: Copy> Shared<'a, T> {
+    fn new(data: &'a mut [T]) -> Self {
+        Self { ptr: data.as_mut_ptr(), len: data.len(), _data: PhantomData }
+    }
+
+    fn get(&self, index: usize) -> T {
+        assert!(index < self.len, "amplitude {} out of range", index);
+        // SAFETY: in bounds, and only the thread owning `index` accesses it during a launch
+        unsafe { *self.ptr.add(index) }
+    }
+
+    /// Pointer to the amplitudes at `index` and `index + 1`, for the SIMD kernels
+    fn pair(&self, index: usize) -> *mut T {
+        assert!(index + 1 < self.len, "amplitude {} out of range", index + 1);
+        // SAFETY: in bounds; callers own both amplitudes during the launch
+        unsafe { self.ptr.add(index) }
+    }
+
+    fn set(&self, index: usize, value: T) {
+        assert!(index < self.len, "amplitude {} out of range", index);
+        // SAFETY: as for `get`
+        unsafe { *self.ptr.add(index) = value }
+    }
+}
+
+/// Amplitudes per chunk when sampling; the sampler keeps one partial sum per chunk
+const SAMPLE_CHUNK: usize = 4096;
+
+/// Launch configuration used until a kernel's access pattern has been tuned
+pub const DEFAULT_CONFIG: LaunchConfig = LaunchConfig { block_size: 256, chunks_per_thread: 1 };
+
+/// Block sizes benchmarked by the autotuner; a chunk always holds whole blocks
+const CANDIDATE_BLOCK_SIZES: [usize; 4] = [256, 1024, 4096, 16384];
+
+/// Chunks per thread benchmarked by the autotuner
+const CANDIDATE_CHUNKS_PER_THREAD: [usize; 4] = [1, 2, 4, 8];
+
+/// Timed launches per candidate; the fastest run is kept
+const TUNING_REPEATS: usize = 3;
+
+/// A candidate only replaces the default when it is at least this much faster, so timing
+/// noise is not cached as a preference
+const TUNING_MARGIN: f64 = 0.9;
+
+/// How a threaded launch splits its work
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaunchConfig {
+    pub block_size: usize,
+    pub chunks_per_thread: usize,
+}
+
+/// Memory access pattern a kernel is tuned for: pair kernels update an amplitude and its
+/// partner, diagonal kernels touch one amplitude each
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum AccessPattern {
+    Paired,
+    Diagonal,
+}
+
+impl AccessPattern {
+    fn of(kernel: &str) -> Self {
+        let diagonal = matches!(
+            kernel,
+            "phase" | "rz" | "z" | "s" | "t" | "cz" | "cphase" | "rzz" | "global_rz" | "diagonal" | "reset_probability"
+                | "measure" | "pauli_rotation_diagonal" | "sample" | "sample_sums"
+        );
+        if diagonal { AccessPattern::Diagonal } else { AccessPattern::Paired }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            AccessPattern::Paired => "paired",
+            AccessPattern::Diagonal => "diagonal",
+        }
+    }
+}
+
+/// A device's tuned configurations by kernel thread count and access pattern
+type TunedConfigs = HashMap<(usize, AccessPattern), LaunchConfig>;
+
+/// Tuned configuration for a kernel on `device` at the calling thread's kernel thread
+/// count. Kernels of one access pattern share a configuration: the benchmark models the
+/// pattern's memory traffic, not the kernel's arithmetic, so timing each kernel separately
+/// would repeat the same measurement. Each thread remembers the configurations it has
+/// read, so launches after the first with a given key do not lock.
+fn tuned_config(device: &GpuDevice, kernel: &str) -> LaunchConfig {
+    thread_local! {
+        static TUNED: RefCell<HashMap<String, TunedConfigs>> = RefCell::new(HashMap::new());
+    }
+    let key = (kernel_threads(), AccessPattern::of(kernel));
+    let cached = TUNED.with(|tuned| tuned.borrow().get(&device.name).and_then(|configs| configs.get(&key)).copied());
+    if let Some(config) = cached {
+        return config;
+    }
+    let config = KernelAutotuner::config(device, key.0, key.1);
+    TUNED.with(|tuned| tuned.borrow_mut().entry(device.name.clone()).or_default().insert(key, config));
+    config
+}
+
+/// Launch configurations by device and kernel thread count, cached on disk at
+/// `$QUANTUMMESH_TUNING_CACHE`, by default `$XDG_CACHE_HOME/quantummesh/kernel_tuning.json`
+/// (`~/.cache/quantummesh/kernel_tuning.json`). Without either directory, tuning is kept
+/// in memory for the process only.
+#[derive(Default, Serialize, Deserialize)]
+pub struct KernelAutotuner {
+    /// "device, N threads" -> access pattern -> best configuration
+    devices: BTreeMap<String, BTreeMap<String, LaunchConfig>>,
+}
+
+impl KernelAutotuner {
+    /// Configuration for `pattern` on `device` with `threads` kernel threads: from this
+    /// process's configurations, the disk cache, or tuned and written back
+    fn config(device: &GpuDevice, threads: usize, pattern: AccessPattern) -> LaunchConfig {
+        // Everything read or tuned so far; the lock also serializes the disk cache's
+        // read-modify-write, and is taken once per key and thread
+        static TUNED: Mutex<KernelAutotuner> = Mutex::new(KernelAutotuner { devices: BTreeMap::new() });
+        let mut tuner = TUNED.lock().unwrap();
+        let key = format!("{}, {} threads", device.name, threads);
+        if let Some(config) = tuner.get(&key, pattern) {
+            return config;
+        }
+        let path = cache_path();
+        let saved: Option<Self> = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok());
+        for (device, patterns) in saved.map(|saved| saved.devices).unwrap_or_default() {
+            tuner.devices.entry(device).or_default().extend(patterns);
+        }
+        if let Some(config) = tuner.get(&key, pattern) {
+            return config;
+        }
+        let config = tune(threads, pattern);
+        tuner.devices.entry(key).or_default().insert(pattern.name().to_string(), config);
+        if let Some(path) = path {
+            if let Err(e) = tuner.save(&path) {
+                eprintln!("Warning: could not write kernel tuning cache {}: {}", path.display(), e);
+            }
+        }
+        config
+    }
+
+    fn get(&self, key: &str, pattern: AccessPattern) -> Option<LaunchConfig> {
+        self.devices.get(key).and_then(|patterns| patterns.get(pattern.name())).copied()
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, content)
+    }
+}
+
+/// Where tuning results are cached, if anywhere; tests tune in memory, leaving the user's
+/// cache alone
+fn cache_path() -> Option<PathBuf> {
+    if cfg!(test) {
+        return None;
+    }
+    if let Some(path) = std::env::var_os("QUANTUMMESH_TUNING_CACHE") {
+        return Some(PathBuf::from(path));
+    }
+    let cache = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache.join("quantummesh").join("kernel_tuning.json"))
+}
+
+/// Benchmark every candidate configuration on the real threaded launcher, with a kernel of
+/// the pattern's shape over a scratch state big enough to use every kernel thread. The
+/// fastest wins only when it clearly beats `DEFAULT_CONFIG`.
+fn tune(threads: usize, pattern: AccessPattern) -> LaunchConfig {
+    let _span = profiler::span(&format!("autotune {}", pattern.name()), Track::Cpu);
+    let work = (threads * MIN_THREAD_WORK * 4).next_power_of_two();
+    let mut scratch = vec![Complex::new(1.0, 0.0); work];
+    let stride = work / 2;
+    let phase = Complex::from_polar(1.0, 0.1);
+
+    let mut time = |config: LaunchConfig| {
+        let launcher = GpuKernelLauncher {
+            chunks_per_thread: config.chunks_per_thread,
+            ..GpuKernelLauncher::with_block_size(work, config.block_size)
+        };
+        let data = Shared::new(&mut scratch);
+        (0..TUNING_REPEATS)
+            .map(|_| {
+                let start = Instant::now();
+                launcher.launch_threaded(|i| match pattern {
+                    AccessPattern::Paired if i & stride == 0 => {
+                        let (a, b) = (data.get(i), data.get(i | stride));
+                        data.set(i, b);
+                        data.set(i | stride, a);
+                    }
+                    AccessPattern::Paired => {}
+                    AccessPattern::Diagonal => data.set(i, phase * data.get(i)),
+                });
+                start.elapsed()
+            })
+            .min()
+            .unwrap_or(Duration::MAX)
+    };
+
+    let baseline = time(DEFAULT_CONFIG);
+    let mut best = (DEFAULT_CONFIG, baseline.mul_f64(TUNING_MARGIN));
+    for &block_size in &CANDIDATE_BLOCK_SIZES {
+        for &chunks_per_thread in &CANDIDATE_CHUNKS_PER_THREAD {
+            let config = LaunchConfig { block_size, chunks_per_thread };
+            if config == DEFAULT_CONFIG {
+                continue;
+            }
+            let elapsed = time(config);
+            if elapsed < best.1 {
+                best = (config, elapsed);
+            }
+        }
+    }
+    best.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU8;
+    use rand::rngs::StdRng;
+    use rand::seq::index;
+    use rand::{Rng, SeedableRng};
+    use super::*;
+    use crate::qsim::{QuantumGate, QuantumSimulator};
+    use crate::simulator_backend;
+
+    #[test]
+    fn threaded_launches_visit_every_index_once() {
+        set_kernel_threads(4);
+        // Not a whole number of blocks, so the last chunk is short
+        let total = 5 * MIN_THREAD_WORK + 77;
+        for block_size in [1, 256, 1000, 16384] {
+            for chunks_per_thread in [1, 3, 8] {
+                for mask in [0, 0b101] {
+                    let launcher = GpuKernelLauncher { chunks_per_thread, ..GpuKernelLauncher::with_block_size(total, block_size) }
+                        .with_control_mask(mask);
+                    let visits: Vec<AtomicU8> = (0..total).map(|_| AtomicU8::new(0)).collect();
+                    launcher.launch_threaded(|i| {
+                        visits[i].fetch_add(1, Ordering::Relaxed);
+                    });
+                    for (i, visit) in visits.iter().enumerate() {
+                        let expected = (i & mask == mask) as u8;
+                        assert_eq!(visit.load(Ordering::Relaxed), expected, "index {}, block size {}, {} chunks", i, block_size, chunks_per_thread);
+                    }
+                    // Sums of integers below 2^53 are exact, whatever the order
+                    let expected: f64 = (0..total).filter(|i| i & mask == mask).map(|i| i as f64).sum();
+                    assert_eq!(launcher.sum_threaded(|i| i as f64), expected);
+                }
+            }
+        }
+    }
+
+    /// Every kind of gate with a kernel of its own, on low and high qubits, with and
+    /// without controls
+    fn kernel_circuit(num_qubits: usize, rng: &mut StdRng) -> Vec<QuantumGate> {
+        let mut gates: Vec<QuantumGate> = (0..num_qubits).map(|qubit| QuantumGate::Hadamard { qubit }).collect();
+        for _ in 0..6 {
+            let angle = rng.gen_range(-3.2..3.2);
+            let qubits = index::sample(rng, num_qubits, 3);
+            let (a, b, c) = (qubits.index(0), qubits.index(1), qubits.index(2));
+            gates.extend([
+                QuantumGate::Hadamard { qubit: a },
+                QuantumGate::PauliX { qubit: b },
+                QuantumGate::PauliY { qubit: c },
+                QuantumGate::PauliZ { qubit: a },
+                QuantumGate::S { qubit: b },
+                QuantumGate::Tdg { qubit: c },
+                QuantumGate::SX { qubit: a },
+                QuantumGate::SXdg { qubit: b },
+                QuantumGate::Phase { qubit: c, angle },
+                QuantumGate::RotationX { qubit: a, angle },
+                QuantumGate::RotationY { qubit: b, angle },
+                QuantumGate::RotationZ { qubit: c, angle },
+                QuantumGate::U { qubit: a, theta: angle, phi: 0.3, lambda: -angle },
+                QuantumGate::CNOT { control: a, target: b },
+                QuantumGate::CZ { control: b, target: c },
+                QuantumGate::CPhase { control: c, target: a, angle },
+                QuantumGate::SWAP { qubit1: a, qubit2: c },
+                QuantumGate::ECR { qubit1: b, qubit2: a },
+                QuantumGate::RXX { qubit1: a, qubit2: b, angle },
+                QuantumGate::RYY { qubit1: b, qubit2: c, angle },
+                QuantumGate::RZZ { qubit1: c, qubit2: a, angle },
+                QuantumGate::Givens { qubit1: a, qubit2: c, angle },
+                QuantumGate::Toffoli { control1: a, control2: b, target: c },
+                QuantumGate::Controlled { control: c, gate: Box::new(QuantumGate::RotationY { qubit: b, angle }) },
+                QuantumGate::Controlled { control: 1, gate: Box::new(QuantumGate::Hadamard { qubit: 0 }) },
+                QuantumGate::Diagonal { qubits: vec![a, b], phases: vec![0.1, angle, -0.7, 2.0] },
+                QuantumGate::PauliRotation { qubits: vec![a, b, c], pauli: "XYZ".to_string(), angle },
+                QuantumGate::GlobalHadamard { qubits: vec![a, c] },
+                QuantumGate::GlobalRotation { qubits: vec![b, c], axis: RotationAxis::X, angle },
+                QuantumGate::GlobalRotation { qubits: vec![a, b], axis: RotationAxis::Y, angle },
+            ]);
+        }
+        gates
+    }
+
+    /// State after `gates` on a representation, with kernels split across `threads`
+    fn run(representation: &str, num_qubits: usize, threads: usize, gates: &[QuantumGate]) -> (Vec<Complex>, Vec<f64>) {
+        set_kernel_threads(threads);
+        let mut simulator = QuantumSimulator::with_backend(simulator_backend::create(representation, num_qubits).unwrap(), 0);
+        for gate in gates {
+            simulator.apply_gate(gate);
+        }
+        (simulator.get_state().into_owned(), simulator.measure_all())
+    }
+
+    #[test]
+    fn threaded_kernels_match_one_thread_and_the_cpu_reference() {
+        // 2^18 amplitudes split across at most 16 threads of MIN_THREAD_WORK each
+        const QUBITS: usize = 18;
+        let gates = kernel_circuit(QUBITS, &mut StdRng::seed_from_u64(5));
+        let (reference, reference_probabilities) = run("cpu", QUBITS, 1, &gates);
+        for threads in [1, 3, 4] {
+            let (state, probabilities) = run("gpu", QUBITS, threads, &gates);
+            for (index, (a, b)) in state.iter().zip(&reference).enumerate() {
+                let difference = *a + Complex::new(-b.re, -b.im);
+                assert!(difference.magnitude_squared() < 1e-24, "amplitude {} with {} threads: {:?}, expected {:?}", index, threads, a, b);
+            }
+            for (p, q) in probabilities.iter().zip(&reference_probabilities) {
+                assert!((p - q).abs() < 1e-12, "{} threads", threads);
+            }
+        }
+    }
 }
@@ -0,0 +1,80 @@
+//! Incremental Simulation Module
+//! Re-simulates only the edited tail of a circuit using per-layer state checkpoints
+
+use crate::qsim::{QuantumCircuit, QuantumGate, QuantumSimulator};
+
+/// Simulator state saved at the start of a layer
+struct Checkpoint {
+    gate_index: usize,
+    state: QuantumSimulator,
+}
+
+/// Simulator that reuses cached layer states across circuit edits
+pub struct IncrementalSimulator {
+    num_qubits: usize,
+    gates: Vec<QuantumGate>,
+    checkpoints: Vec<Checkpoint>,
+    current: QuantumSimulator,
+}
+
+impl IncrementalSimulator {
+    /// Create an incremental simulator with an empty circuit
+    pub fn new(num_qubits: usize) -> Self {
+        let initial = QuantumSimulator::new(num_qubits);
+        Self {
+            num_qubits,
+            gates: Vec::new(),
+            checkpoints: vec![Checkpoint { gate_index: 0, state: initial.clone() }],
+            current: initial,
+        }
+    }
+
+    /// Bring the state up to date with `circuit`, returning how many gates were applied
+    pub fn update(&mut self, circuit: &QuantumCircuit) -> usize {
+        if circuit.num_qubits != self.num_qubits {
+            *self = Self::new(circuit.num_qubits);
+        }
+
+        let first_change = self
+            .gates
+            .iter()
+            .zip(&circuit.gates)
+            .position(|(old, new)| old != new)
+            .unwrap_or_else(|| self.gates.len().min(circuit.gates.len()));
+        if first_change == self.gates.len() && first_change == circuit.gates.len() {
+            return 0;
+        }
+
+        // Resume from the last layer that starts at or before the first edit
+        self.checkpoints.retain(|cp| cp.gate_index <= first_change);
+        let checkpoint = self.checkpoints.last().expect("initial checkpoint is never removed");
+        let start = checkpoint.gate_index;
+        self.current = checkpoint.state.clone();
+
+        let mut layer_qubits: Vec<usize> = Vec::new();
+        for (index, gate) in circuit.gates.iter().enumerate().skip(start) {
+            let qubits = gate.qubits();
+            if qubits.iter().any(|q| layer_qubits.contains(q)) {
+                if index > start {
+                    self.checkpoints.push(Checkpoint { gate_index: index, state: self.current.clone() });
+                }
+                layer_qubits.clear();
+            }
+            layer_qubits.extend(qubits);
+            self.current.apply_gate(gate);
+        }
+
+        self.gates = circuit.gates.clone();
+        circuit.gates.len() - start
+    }
+
+    /// Simulator holding the state after the full circuit
+    pub fn simulator(&self) -> &QuantumSimulator {
+        &self.current
+    }
+
+    /// Number of cached layer checkpoints
+    pub fn checkpoint_count(&self) -> usize {
+        self.checkpoints.len()
+    }
+}
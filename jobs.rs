@@ -0,0 +1,328 @@
+//! Jobs Module
+//! Server job queue persisted on disk, so queued and running jobs survive a restart
+
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::manifest::Manifest;
+use crate::npy;
+use crate::qsim::{self, QuantumCircuit, QuantumSimulator};
+
+/// Where `serve` keeps its jobs unless `--jobs-dir` is given
+pub const DEFAULT_DIR: &str = "quantummesh-jobs";
+
+/// Gates between checkpoints of a running job unless `--checkpoint-every` is given
+pub const DEFAULT_CHECKPOINT_EVERY: usize = 500;
+
+const JOB_FILE: &str = "job.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// One recorded state change of a job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transition {
+    pub state: JobState,
+    /// Unix time of the change, in seconds
+    pub at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub sequence: u64,
+    pub state: JobState,
+    pub circuit: QuantumCircuit,
+    /// Name of the API key that submitted the job, when the server has keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Gates applied in the latest checkpoint on disk, where a restarted job resumes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<usize>,
+    pub history: Vec<Transition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probabilities: Option<Vec<f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<Manifest>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Job {
+    fn transition(&mut self, state: JobState, note: Option<String>) {
+        self.state = state;
+        self.history.push(Transition { state, at: unix_now(), note });
+    }
+
+    /// Listing entry without the circuit or probabilities
+    pub fn summary(&self) -> Value {
+        json!({
+            "id": self.id,
+            "state": self.state,
+            "owner": self.owner,
+            "num_qubits": self.circuit.num_qubits,
+            "gates": self.circuit.gates.len(),
+            "checkpoint": self.checkpoint,
+            "updated_at": self.history.last().map(|t| t.at),
+        })
+    }
+}
+
+struct Queue {
+    jobs: BTreeMap<String, Job>,
+    pending: VecDeque<String>,
+    next_sequence: u64,
+}
+
+/// Jobs in memory and on disk under `<dir>/<id>/`: `job.json` holds the state and its
+/// history, `checkpoint-<gates>.npy` the state vector of an interrupted run
+#[derive(Clone)]
+pub struct JobQueue {
+    dir: PathBuf,
+    checkpoint_every: usize,
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+}
+
+impl JobQueue {
+    /// Load the jobs kept in `dir`; jobs that were running when the server stopped go back
+    /// to the front of the queue, ahead of those still waiting
+    pub fn open(dir: &str, checkpoint_every: usize) -> Result<Self, Box<dyn Error>> {
+        let dir = PathBuf::from(dir);
+        fs::create_dir_all(&dir)?;
+
+        let mut jobs = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path().join(JOB_FILE);
+            if path.is_file() {
+                let job: Job = serde_json::from_str(&fs::read_to_string(&path)?)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                jobs.push(job);
+            }
+        }
+        jobs.sort_by_key(|job| job.sequence);
+
+        let queue = Self {
+            dir,
+            checkpoint_every,
+            queue: Arc::new((Mutex::new(Queue { jobs: BTreeMap::new(), pending: VecDeque::new(), next_sequence: 1 }), Condvar::new())),
+        };
+        let mut interrupted = Vec::new();
+        let mut waiting = Vec::new();
+        let mut state = queue.queue.0.lock().unwrap();
+        for mut job in jobs {
+            state.next_sequence = state.next_sequence.max(job.sequence + 1);
+            match job.state {
+                JobState::Running => {
+                    let note = match job.checkpoint {
+                        Some(gates) => format!("server restarted; resuming from gate {}", gates),
+                        None => "server restarted; starting over".to_string(),
+                    };
+                    job.transition(JobState::Queued, Some(note));
+                    queue.save(&job)?;
+                    interrupted.push(job.id.clone());
+                }
+                JobState::Queued => waiting.push(job.id.clone()),
+                JobState::Completed | JobState::Failed => {}
+            }
+            state.jobs.insert(job.id.clone(), job);
+        }
+        state.pending = interrupted.into_iter().chain(waiting).collect();
+        drop(state);
+        Ok(queue)
+    }
+
+    /// Number of jobs queued or running
+    pub fn pending(&self) -> usize {
+        let state = self.queue.0.lock().unwrap();
+        state.jobs.values().filter(|job| matches!(job.state, JobState::Queued | JobState::Running)).count()
+    }
+
+    /// Queue a circuit; the job is on disk before this returns
+    pub fn submit(&self, circuit: QuantumCircuit, owner: Option<String>) -> Result<Job, Box<dyn Error>> {
+        let (lock, ready) = &*self.queue;
+        let mut state = lock.lock().unwrap();
+        let sequence = state.next_sequence;
+        let mut job = Job {
+            id: format!("job-{}", sequence),
+            sequence,
+            state: JobState::Queued,
+            circuit,
+            owner,
+            checkpoint: None,
+            history: Vec::new(),
+            probabilities: None,
+            manifest: None,
+            error: None,
+        };
+        job.transition(JobState::Queued, None);
+        self.save(&job)?;
+        state.next_sequence += 1;
+        state.pending.push_back(job.id.clone());
+        state.jobs.insert(job.id.clone(), job.clone());
+        ready.notify_one();
+        Ok(job)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.queue.0.lock().unwrap().jobs.get(id).cloned()
+    }
+
+    /// All jobs, oldest first
+    pub fn list(&self) -> Vec<Job> {
+        let mut jobs: Vec<Job> = self.queue.0.lock().unwrap().jobs.values().cloned().collect();
+        jobs.sort_by_key(|job| job.sequence);
+        jobs
+    }
+
+    /// Run queued jobs one at a time on a background thread
+    pub fn start_worker(&self) {
+        let queue = self.clone();
+        std::thread::spawn(move || loop {
+            let id = queue.next_pending();
+            if let Err(e) = queue.run(&id) {
+                eprintln!("Job {} could not be recorded: {}", id, e);
+            }
+        });
+    }
+
+    fn next_pending(&self) -> String {
+        let (lock, ready) = &*self.queue;
+        let mut state = lock.lock().unwrap();
+        loop {
+            if let Some(id) = state.pending.pop_front() {
+                return id;
+            }
+            state = ready.wait(state).unwrap();
+        }
+    }
+
+    fn run(&self, id: &str) -> Result<(), Box<dyn Error>> {
+        let Some(mut job) = self.get(id) else {
+            return Ok(());
+        };
+        // A checkpoint holds only amplitudes, so circuits with classical state start over
+        let resumable = job.circuit.gates.iter().all(|gate| qsim::register_bits(gate).is_empty());
+        let (mut simulator, start) = match job.checkpoint.filter(|_| resumable) {
+            Some(gates) => match npy::load_state(&self.checkpoint_path(id, gates))
+                .map_err(|e| e.to_string())
+                .and_then(QuantumSimulator::from_state)
+            {
+                Ok(simulator) if simulator.num_qubits == job.circuit.num_qubits => (simulator, gates),
+                Ok(_) => (QuantumSimulator::new(job.circuit.num_qubits), 0),
+                Err(e) => {
+                    eprintln!("Job {}: checkpoint at gate {} unreadable ({}); starting over", id, gates, e);
+                    (QuantumSimulator::new(job.circuit.num_qubits), 0)
+                }
+            },
+            None => (QuantumSimulator::new(job.circuit.num_qubits), 0),
+        };
+        let note = (start > 0).then(|| format!("resumed from gate {}", start));
+        job.transition(JobState::Running, note);
+        if start == 0 {
+            self.replace_checkpoint(&mut job, None)?;
+        } else {
+            self.update(&job)?;
+        }
+
+        let gates = job.circuit.gates.clone();
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            for (i, gate) in gates.iter().enumerate().skip(start) {
+                simulator.apply_gate(gate);
+                let applied = i + 1;
+                if resumable && applied % self.checkpoint_every == 0 && applied < gates.len() {
+                    // A missed checkpoint only costs work after a restart, so the job keeps going
+                    let saved = npy::save_state(&self.checkpoint_path(id, applied).to_string_lossy(), &simulator.get_state())
+                        .map_err(|e| e.into())
+                        .and_then(|_| self.replace_checkpoint(&mut job, Some(applied)));
+                    if let Err(e) = saved {
+                        eprintln!("Job {}: checkpoint at gate {} failed: {}", id, applied, e);
+                    }
+                }
+            }
+        }));
+        match outcome {
+            Ok(()) => {
+                job.probabilities = Some(simulator.measure_all());
+                job.manifest = Some(Manifest::current());
+                job.transition(JobState::Completed, None);
+                self.replace_checkpoint(&mut job, None)
+            }
+            Err(panic) => {
+                let message = panic
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_else(|| "simulation panicked".to_string());
+                job.error = Some(message.clone());
+                job.transition(JobState::Failed, Some(message));
+                self.update(&job)
+            }
+        }
+    }
+
+    /// Save the job pointing at a new checkpoint (or none), then delete the files it no longer
+    /// needs, so a crash never leaves the record naming a missing file
+    fn replace_checkpoint(&self, job: &mut Job, gates: Option<usize>) -> Result<(), Box<dyn Error>> {
+        job.checkpoint = gates;
+        self.update(job)?;
+        for entry in fs::read_dir(self.dir.join(&job.id))? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let keep = gates.map(|gates| checkpoint_name(gates) == name).unwrap_or(false);
+            if name.starts_with("checkpoint-") && !keep {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Save a job and publish it to readers
+    fn update(&self, job: &Job) -> Result<(), Box<dyn Error>> {
+        self.save(job)?;
+        self.queue.0.lock().unwrap().jobs.insert(job.id.clone(), job.clone());
+        Ok(())
+    }
+
+    /// Write `job.json` through a temporary file, so a crash leaves the old or new record
+    fn save(&self, job: &Job) -> Result<(), Box<dyn Error>> {
+        let dir = self.dir.join(&job.id);
+        fs::create_dir_all(&dir)?;
+        let temporary = dir.join(format!("{}.tmp", JOB_FILE));
+        fs::write(&temporary, serde_json::to_string_pretty(job)?)?;
+        fs::rename(&temporary, dir.join(JOB_FILE))?;
+        Ok(())
+    }
+
+    fn checkpoint_path(&self, id: &str, gates: usize) -> PathBuf {
+        self.dir.join(id).join(checkpoint_name(gates))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+fn checkpoint_name(gates: usize) -> String {
+    format!("checkpoint-{:06}.npy", gates)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
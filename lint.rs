@@ -0,0 +1,258 @@
+//! Lint Module
+//! Validator errors plus heuristic warnings about circuits that run but probably do not do what was meant
+
+use std::collections::BTreeSet;
+use serde::{Deserialize, Serialize};
+use crate::optimizer;
+use crate::qsim::{self, QuantumCircuit, QuantumGate};
+
+/// Depth above which `excessive-depth` is reported unless the caller sets a limit
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The circuit cannot be simulated as written
+    Error,
+    /// The circuit runs, but likely not as intended
+    Warning,
+}
+
+/// One finding, anchored to a gate and/or qubit where it has one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable kebab-case identifier, e.g. `unused-qubit`
+    pub code: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gate: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub qubit: Option<usize>,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, code: &str, message: String) -> Self {
+        Self { severity, code: code.to_string(), message, gate: None, qubit: None }
+    }
+
+    /// The circuit is wider than `limit` qubits, the most the server will simulate
+    pub fn too_large(limit: usize) -> Self {
+        Self::new(Severity::Error, "too-many-qubits", format!("circuits are limited to {} qubits", limit))
+    }
+
+    fn at_gate(mut self, gate: usize) -> Self {
+        self.gate = Some(gate);
+        self
+    }
+
+    fn on_qubit(mut self, qubit: usize) -> Self {
+        self.qubit = Some(qubit);
+        self
+    }
+}
+
+/// One gate changed by `fix`, by its index in the original circuit
+#[derive(Debug, Clone)]
+pub struct Edit {
+    pub gate: usize,
+    pub before: QuantumGate,
+    /// Replacement, or `None` when the gate is dropped
+    pub after: Option<QuantumGate>,
+}
+
+/// Lint a circuit: every validator error (not just the first), qubits out of range, conditions on
+/// bits not yet measured, then warnings
+/// for rotations that can be merged, gates on a qubit after it is measured, gates after the
+/// final measurement, unused and unmeasured qubits, and depth above `max_depth`.
+/// Diagnostics come in gate order, circuit-wide ones last.
+pub fn lint(circuit: &QuantumCircuit, max_depth: usize) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut used = vec![false; circuit.num_qubits];
+    // Index of the latest gate on each qubit
+    let mut previous: Vec<Option<usize>> = vec![None; circuit.num_qubits];
+    // Gate index of each qubit's latest measurement
+    let mut measured: Vec<Option<usize>> = vec![None; circuit.num_qubits];
+    // Register bits measured into so far, which a condition may read
+    let mut readable = BTreeSet::new();
+    let final_measurement = circuit
+        .gates
+        .iter()
+        .rposition(|gate| matches!(gate, QuantumGate::Measurement { .. }));
+
+    for (index, gate) in circuit.gates.iter().enumerate() {
+        let qubits = gate.qubits();
+        if let Some(&qubit) = qubits.iter().find(|&&q| q >= circuit.num_qubits) {
+            diagnostics.push(
+                Diagnostic::new(
+                    Severity::Error,
+                    "qubit-out-of-range",
+                    format!("gate {} uses qubit {}, but the circuit has {} qubits", index, qubit, circuit.num_qubits),
+                )
+                .at_gate(index)
+                .on_qubit(qubit),
+            );
+            continue;
+        }
+        if let Err(e) = qsim::validate_gate(index, gate, circuit.num_qubits) {
+            diagnostics.push(Diagnostic::new(Severity::Error, "invalid-gate", e).at_gate(index));
+        }
+        if let Some(bit) = qsim::condition_bits(gate).into_iter().find(|bit| !readable.contains(bit)) {
+            diagnostics.push(
+                Diagnostic::new(
+                    Severity::Error,
+                    "unmeasured-condition",
+                    format!("gate {} is conditioned on bit {}, which no earlier gate measures", index, bit),
+                )
+                .at_gate(index),
+            );
+        }
+
+        if let Some((qubit, _)) = optimizer::rotation_angle(gate) {
+            if let Some(earlier) = previous[qubit].filter(|&earlier| mergeable(&circuit.gates[earlier], gate)) {
+                diagnostics.push(
+                    Diagnostic::new(
+                        Severity::Warning,
+                        "mergeable-rotations",
+                        format!("gate {} repeats the rotation of gate {} on qubit {}; the two can be merged", index, earlier, qubit),
+                    )
+                    .at_gate(index)
+                    .on_qubit(qubit),
+                );
+            }
+        }
+        for &qubit in &qubits {
+            previous[qubit] = Some(index);
+        }
+
+        if let QuantumGate::Measurement { qubit, bit } = gate {
+            used[*qubit] = true;
+            measured[*qubit] = Some(index);
+            readable.insert(bit.unwrap_or(*qubit));
+            continue;
+        }
+        // Snapshots only look at the state, wherever they sit
+        if matches!(gate, QuantumGate::Snapshot { .. }) {
+            continue;
+        }
+        if final_measurement.is_some_and(|last| index > last) {
+            diagnostics.push(
+                Diagnostic::new(
+                    Severity::Warning,
+                    "after-final-measurement",
+                    format!("gate {} comes after the final measurement and cannot change any measured result", index),
+                )
+                .at_gate(index),
+            );
+        } else if let Some((&qubit, measurement)) = qubits
+            .iter()
+            .filter(|_| !matches!(gate, QuantumGate::IfBit { .. }))
+            .find_map(|q| measured[*q].map(|measurement| (q, measurement)))
+        {
+            diagnostics.push(
+                Diagnostic::new(
+                    Severity::Warning,
+                    "gate-after-measurement",
+                    format!(
+                        "gate {} acts on qubit {} after its measurement at gate {}, with no classical condition",
+                        index, qubit, measurement
+                    ),
+                )
+                .at_gate(index)
+                .on_qubit(qubit),
+            );
+        }
+        for &qubit in &qubits {
+            used[qubit] = true;
+            // Only the first later gate is reported for each measurement
+            measured[qubit] = None;
+        }
+    }
+
+    for (qubit, _) in used.iter().enumerate().filter(|(_, used)| !**used) {
+        diagnostics.push(
+            Diagnostic::new(Severity::Warning, "unused-qubit", format!("qubit {} is never used", qubit)).on_qubit(qubit),
+        );
+    }
+    for qubit in unmeasured_qubits(circuit) {
+        if used[qubit] {
+            diagnostics.push(
+                Diagnostic::new(
+                    Severity::Warning,
+                    "unmeasured-qubit",
+                    format!("qubit {} is never measured and never interacts with another qubit, so its gates cannot affect any result", qubit),
+                )
+                .on_qubit(qubit),
+            );
+        }
+    }
+    let depth = optimizer::circuit_depth(circuit);
+    if depth > max_depth {
+        diagnostics.push(Diagnostic::new(
+            Severity::Warning,
+            "excessive-depth",
+            format!("depth {} exceeds the limit of {}", depth, max_depth),
+        ));
+    }
+    diagnostics
+}
+
+/// Apply the safe rewrites behind the warnings: drop gates on unmeasured qubits, then merge
+/// adjacent rotations. Neither changes any measured result. Expects a circuit without lint errors.
+pub fn fix(circuit: &QuantumCircuit) -> (QuantumCircuit, Vec<Edit>) {
+    let unmeasured = unmeasured_qubits(circuit);
+    let mut gates: Vec<Option<QuantumGate>> = circuit
+        .gates
+        .iter()
+        .map(|gate| {
+            let qubits = gate.qubits();
+            // Gates on no qubit, such as snapshots, are not on unmeasured qubits either
+            Some(gate.clone()).filter(|_| qubits.is_empty() || !qubits.iter().all(|q| unmeasured.contains(q)))
+        })
+        .collect();
+    optimizer::merge_rotation_slots(&mut gates);
+
+    let edits = circuit
+        .gates
+        .iter()
+        .zip(&gates)
+        .enumerate()
+        .filter(|(_, (before, after))| after.as_ref() != Some(*before))
+        .map(|(gate, (before, after))| Edit { gate, before: before.clone(), after: after.clone() })
+        .collect();
+    let fixed = QuantumCircuit {
+        num_qubits: circuit.num_qubits,
+        gates: gates.into_iter().flatten().collect(),
+        metadata: circuit.metadata.clone(),
+    };
+    (fixed, edits)
+}
+
+/// Whether `later`, the next gate on the qubit of rotation `earlier`, is the same rotation
+fn mergeable(earlier: &QuantumGate, later: &QuantumGate) -> bool {
+    std::mem::discriminant(earlier) == std::mem::discriminant(later)
+        && optimizer::rotation_angle(earlier).map(|(q, _)| q) == optimizer::rotation_angle(later).map(|(q, _)| q)
+}
+
+/// Qubits outside every measurement's reach: never measured and never in a multi-qubit gate.
+/// Empty when the circuit has no measurements, since then every qubit is read out.
+fn unmeasured_qubits(circuit: &QuantumCircuit) -> Vec<usize> {
+    let mut reached = vec![false; circuit.num_qubits];
+    let mut any_measurement = false;
+    for gate in &circuit.gates {
+        let qubits = gate.qubits();
+        let measurement = matches!(gate, QuantumGate::Measurement { .. });
+        any_measurement |= measurement;
+        if measurement || qubits.len() > 1 {
+            for q in qubits {
+                if let Some(reached) = reached.get_mut(q) {
+                    *reached = true;
+                }
+            }
+        }
+    }
+    if !any_measurement {
+        return Vec::new();
+    }
+    (0..circuit.num_qubits).filter(|&q| !reached[q]).collect()
+}
@@ -0,0 +1,116 @@
+//! Gate Macros Module
+//! Named sub-circuits defined once in a circuit file and expanded at load time
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::qsim::{QuantumCircuit, QuantumGate};
+
+/// Calls nested deeper than this are reported as runaway recursion
+const MAX_DEPTH: usize = 64;
+
+/// A sub-circuit over local qubits `0..qubits`
+#[derive(Debug, Deserialize)]
+struct GateMacro {
+    qubits: usize,
+    gates: Vec<Value>,
+}
+
+/// Circuit document whose gate list may call macros
+#[derive(Debug, Deserialize)]
+struct MacroCircuit {
+    num_qubits: usize,
+    macros: BTreeMap<String, GateMacro>,
+    #[serde(default)]
+    gates: Vec<Value>,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+}
+
+/// Whether a parsed circuit document defines gate macros
+pub fn has_macros(document: &Value) -> bool {
+    document.get("macros").is_some()
+}
+
+/// Expand every macro call in a circuit document into plain gates
+pub fn expand(document: Value) -> Result<QuantumCircuit, Box<dyn Error>> {
+    let circuit: MacroCircuit = serde_json::from_value(document)?;
+    for (name, definition) in &circuit.macros {
+        if definition.qubits == 0 {
+            return Err(format!("macro '{}' must act on at least one qubit", name).into());
+        }
+    }
+
+    let identity: Vec<usize> = (0..circuit.num_qubits).collect();
+    let mut gates = Vec::new();
+    let mut stack = Vec::new();
+    for (index, entry) in circuit.gates.iter().enumerate() {
+        expand_entry(entry, &identity, &circuit.macros, &mut stack, &mut gates)
+            .map_err(|e| format!("gate {}: {}", index, e))?;
+    }
+
+    Ok(QuantumCircuit { num_qubits: circuit.num_qubits, gates, metadata: circuit.metadata })
+}
+
+/// Append the gates for one entry, mapping local qubit `i` to `args[i]`
+fn expand_entry(
+    entry: &Value,
+    args: &[usize],
+    macros: &BTreeMap<String, GateMacro>,
+    stack: &mut Vec<String>,
+    gates: &mut Vec<QuantumGate>,
+) -> Result<(), String> {
+    let name = match entry.get("macro") {
+        Some(name) => name.as_str().ok_or("macro name must be a string")?,
+        None => {
+            let gate: QuantumGate = serde_json::from_value(entry.clone()).map_err(|e| e.to_string())?;
+            if let Some(q) = gate.qubits().into_iter().find(|&q| q >= args.len()) {
+                return Err(format!("qubit {} is out of range for {} qubits", q, args.len()));
+            }
+            gates.push(gate.map_qubits(|q| args[q]));
+            return Ok(());
+        }
+    };
+
+    let definition = macros.get(name).ok_or_else(|| format!("unknown macro '{}'", name))?;
+    if stack.iter().any(|caller| caller == name) {
+        return Err(format!("macro '{}' calls itself", name));
+    }
+    if stack.len() >= MAX_DEPTH {
+        return Err(format!("macro calls nested more than {} deep", MAX_DEPTH));
+    }
+
+    let call_qubits: Vec<usize> = entry
+        .get("qubits")
+        .map(|qubits| serde_json::from_value(qubits.clone()))
+        .transpose()
+        .map_err(|e| format!("macro '{}' qubits: {}", name, e))?
+        .ok_or_else(|| format!("call to macro '{}' has no qubits", name))?;
+    if call_qubits.len() != definition.qubits {
+        return Err(format!(
+            "macro '{}' takes {} qubits but was called with {}",
+            name,
+            definition.qubits,
+            call_qubits.len()
+        ));
+    }
+    let mut mapped = Vec::with_capacity(call_qubits.len());
+    for &q in &call_qubits {
+        let target = *args
+            .get(q)
+            .ok_or_else(|| format!("qubit {} is out of range for {} qubits", q, args.len()))?;
+        if mapped.contains(&target) {
+            return Err(format!("macro '{}' is called with qubit {} twice", name, q));
+        }
+        mapped.push(target);
+    }
+
+    stack.push(name.to_string());
+    for (index, body_entry) in definition.gates.iter().enumerate() {
+        expand_entry(body_entry, &mapped, macros, stack, gates)
+            .map_err(|e| format!("in macro '{}' gate {}: {}", name, index, e))?;
+    }
+    stack.pop();
+    Ok(())
+}
@@ -3,15 +3,93 @@
 
 use std::env;
 use std::process;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 mod qsim;
 mod gpu_ops;
 mod api_server;
+mod api_version;
+mod archive;
+mod auth;
+mod backends;
+mod catalog;
+mod faults;
 mod cli;
+mod client;
+mod cluster;
+mod dem;
+mod disk;
+mod entropy;
+mod examples;
+mod ftqc;
+mod incremental;
+mod jobs;
+mod lint;
+mod macros;
+mod manifest;
+mod monitored;
+mod mps;
+mod noise;
+mod npy;
+mod observable;
+mod optimizer;
+mod params;
+mod power;
+mod profiler;
+mod propagation;
+mod proto;
+mod qobj;
+mod qec;
+mod records;
+mod results;
+mod rundir;
+mod simulator_backend;
+mod simd;
+mod sparse;
+mod stabilizer;
+mod stats;
+mod qasm;
+mod quil;
+mod stim;
+mod templates;
+mod tomography;
+mod tune;
+mod unitary;
+mod workspace;
+
+/// Server used by workspace commands when `--url` is not given
+const DEFAULT_SERVER_URL: &str = "http://localhost:8080";
+
+/// Largest infidelity `equiv` still reports as the same state
+const DEFAULT_EQUIV_TOLERANCE: f64 = 1e-9;
+
+/// Flags passed to the selected backend with `StateBackend::with_option`
+/// Flags of `simulate` (and `rerun`) that take no value
+const SIMULATE_SWITCHES: &[&str] = &["ideal-counts"];
+
+const BACKEND_OPTIONS: &[&str] = &["max-bond", "min-coefficient", "max-weight", "noise-mode", "trajectories", "threads"];
+
+/// Widest circuit whose unitary `unitary` prints, a 16x16 matrix
+const MAX_PRINTED_UNITARY_QUBITS: usize = 4;
+
+/// Widest subsystem `simulate --reduced` prints, a 16x16 matrix
+const MAX_REDUCED_QUBITS: usize = 4;
+
+/// Widest subsystem `simulate --marginal` tabulates, 2^20 outcomes
+const MAX_MARGINAL_QUBITS: usize = 20;
 
 /// Main entry point for QuantumMesh
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    // --plain applies to every command, so it is taken out before any command parses its flags
+    let plain = args.iter().skip(1).any(|arg| arg == "--plain");
+    args.retain(|arg| arg != "--plain");
+    cli::set_plain(plain);
+    if let Err(e) = backends::register_builtins() {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
 
     if args.len() < 2 {
         print_help();
@@ -20,27 +98,88 @@ fn main() {
 
     match args[1].as_str() {
         "simulate" => {
-            if args.len() < 3 {
-                eprintln!("Error: simulate requires circuit file path");
-                process::exit(1);
+            let flags = parse_flags(&args[2..], SIMULATE_SWITCHES);
+            match flags.positional(0) {
+                Some(file_path) => {
+                    start_profiling(&flags);
+                    simulate_circuit(file_path, &flags);
+                    finish_profiling(&flags);
+                }
+                None => {
+                    eprintln!("Error: simulate requires circuit file path");
+                    process::exit(1);
+                }
+            }
+        }
+        "rerun" => {
+            let flags = parse_flags(&args[2..], &[]);
+            match flags.positional(0) {
+                Some(dir) => rerun(dir, &flags),
+                None => {
+                    eprintln!("Error: rerun requires a run directory");
+                    process::exit(1);
+                }
             }
-            simulate_circuit(&args[2]);
         }
         "serve" => {
-            let port = if args.len() > 2 {
-                args[2].parse::<u16>().unwrap_or(8080)
-            } else {
-                8080
-            };
-            api_server::start_server(port);
+            let flags = parse_flags(&args[2..], &[]);
+            let port = flags.positional(0).and_then(|port| port.parse::<u16>().ok()).unwrap_or(8080);
+            let jobs_dir = flags.value("jobs-dir").unwrap_or(jobs::DEFAULT_DIR);
+            let every = checkpoint_every(&flags).unwrap_or(jobs::DEFAULT_CHECKPOINT_EVERY);
+            let jobs = jobs::JobQueue::open(jobs_dir, every).unwrap_or_else(|e| {
+                eprintln!("Error opening job queue in {}: {}", jobs_dir, e);
+                process::exit(1);
+            });
+            let keys = auth::KeyStore::load("config.toml").unwrap_or_else(|e| {
+                eprintln!("Error reading API keys: {}", e);
+                process::exit(1);
+            });
+            let noise_models = flags.value("noise").map_or_else(std::collections::BTreeMap::new, |paths| {
+                paths
+                    .split(',')
+                    .map(|path| {
+                        let name = std::path::Path::new(path)
+                            .file_stem()
+                            .map_or(path.to_string(), |stem| stem.to_string_lossy().into_owned());
+                        match noise::load_noise_model(path) {
+                            Ok(model) => (name, model),
+                            Err(e) => {
+                                eprintln!("Error loading noise model {}: {}", path, e);
+                                process::exit(1);
+                            }
+                        }
+                    })
+                    .collect()
+            });
+            api_server::start_server(port, jobs, keys, noise_models);
         }
         "benchmark" => {
-            if args.len() < 3 {
-                eprintln!("Error: benchmark requires number of qubits");
-                process::exit(1);
+            let flags = parse_flags(&args[2..], &["power"]);
+            match flags.positional(0) {
+                Some(qubits) => {
+                    let qubits = qubits.parse::<usize>().unwrap_or(10);
+                    start_profiling(&flags);
+                    match flags.value("random") {
+                        Some(depth) => run_random_benchmark(qubits, depth.parse::<usize>().unwrap_or(20), &flags),
+                        None => run_benchmark(qubits, &flags),
+                    }
+                    finish_profiling(&flags);
+                }
+                None => {
+                    eprintln!("Error: benchmark requires number of qubits");
+                    process::exit(1);
+                }
+            }
+        }
+        "monitored" => {
+            let flags = parse_flags(&args[2..], &[]);
+            match flags.positional(0).map(|qubits| qubits.parse::<usize>()) {
+                Some(Ok(qubits)) if qubits >= 2 => run_monitored_sweep(qubits, &flags),
+                _ => {
+                    eprintln!("Error: monitored requires a number of qubits (at least 2)");
+                    process::exit(1);
+                }
             }
-            let qubits = args[2].parse::<usize>().unwrap_or(10);
-            run_benchmark(qubits);
         }
         "visualize" => {
             if args.len() < 3 {
@@ -50,17 +189,174 @@ fn main() {
             visualize_circuit(&args[2]);
         }
         "optimize" => {
+            let flags = parse_flags(&args[2..], &[]);
+            match flags.positional(0) {
+                Some(file_path) => optimize_circuit(file_path, &flags),
+                None => {
+                    eprintln!("Error: optimize requires circuit file path");
+                    process::exit(1);
+                }
+            }
+        }
+        "lint" => {
+            let flags = parse_flags(&args[2..], &["fix", "dry-run"]);
+            match flags.positional(0) {
+                Some(file_path) => lint_circuit(file_path, &flags),
+                None => {
+                    eprintln!("Error: lint requires circuit file path");
+                    process::exit(1);
+                }
+            }
+        }
+        "tune" => {
+            let flags = parse_flags(&args[2..], &[]);
+            match flags.positional(0) {
+                Some(file_path) => tune_parameters(file_path, &flags),
+                None => {
+                    eprintln!("Error: tune requires circuit file path");
+                    process::exit(1);
+                }
+            }
+        }
+        "watch" => {
             if args.len() < 3 {
-                eprintln!("Error: optimize requires circuit file path");
+                eprintln!("Error: watch requires circuit file path");
+                process::exit(1);
+            }
+            watch_circuit(&args[2]);
+        }
+        "convert" => {
+            if args.len() < 4 {
+                eprintln!("Error: convert requires input and output file paths");
+                process::exit(1);
+            }
+            convert_circuit(&args[2], &args[3]);
+        }
+        "dem" => {
+            let flags = parse_flags(&args[2..], &[]);
+            match flags.positional(0) {
+                Some(file_path) => export_detector_error_model(file_path, &flags),
+                None => {
+                    eprintln!("Error: dem requires a Stim circuit file path");
+                    process::exit(1);
+                }
+            }
+        }
+        "qec" => {
+            let flags = parse_flags(&args[2..], &[]);
+            match flags.positional(0) {
+                Some(file_path) => sample_logical_errors(file_path, &flags),
+                None => {
+                    eprintln!("Error: qec requires a Stim circuit file path");
+                    process::exit(1);
+                }
+            }
+        }
+        "tomography" => {
+            let flags = parse_flags(&args[2..], &[]);
+            match flags.positional(0) {
+                Some(file_path) => run_tomography(file_path, &flags),
+                None => {
+                    eprintln!("Error: tomography requires circuit file path");
+                    process::exit(1);
+                }
+            }
+        }
+        "unitary" => {
+            let flags = parse_flags(&args[2..], &[]);
+            match flags.positional(0) {
+                Some(file_path) => show_unitary(file_path, &flags),
+                None => {
+                    eprintln!("Error: unitary requires circuit file path");
+                    process::exit(1);
+                }
+            }
+        }
+        "equiv" => {
+            let flags = parse_flags(&args[2..], &[]);
+            match (flags.positional(0), flags.positional(1)) {
+                (Some(first), Some(second)) => compare_states(first, second, &flags),
+                _ => {
+                    eprintln!("Error: equiv requires two circuit or .npy state files");
+                    process::exit(1);
+                }
+            }
+        }
+        "export-workspace" | "import-workspace" => {
+            let flags = parse_flags(&args[2..], &["write-configs"]);
+            let Some(archive_path) = flags.positional(0) else {
+                eprintln!("Error: {} requires an archive file path", args[1]);
                 process::exit(1);
+            };
+            let url = flags.value("url").unwrap_or(DEFAULT_SERVER_URL);
+            if args[1] == "export-workspace" {
+                export_workspace(archive_path, url);
+            } else {
+                import_workspace(archive_path, url, flags.has("write-configs"));
+            }
+        }
+        "analyze" => {
+            let flags = parse_flags(&args[2..], &["fault-tolerant"]);
+            match flags.positional(0) {
+                Some(file_path) => analyze_circuit(file_path, &flags),
+                None => {
+                    eprintln!("Error: analyze requires circuit file path");
+                    process::exit(1);
+                }
+            }
+        }
+        "faults" => {
+            let flags = parse_flags(&args[2..], &[]);
+            match flags.positional(0) {
+                Some(file_path) => analyze_faults(file_path, &flags),
+                None => {
+                    eprintln!("Error: faults requires circuit file path");
+                    process::exit(1);
+                }
             }
-            optimize_circuit(&args[2]);
+        }
+        "show" => {
+            let flags = parse_flags(&args[2..], &["verify-env"]);
+            match flags.positional(0) {
+                Some(file_path) => show_results(file_path, flags.positional(1), &flags),
+                None => {
+                    eprintln!("Error: show requires a results file path");
+                    process::exit(1);
+                }
+            }
+        }
+        "examples" => {
+            let flags = parse_flags(&args[2..], &[]);
+            match (flags.positional(0), flags.positional(1)) {
+                (None, _) | (Some("list"), None) => list_examples(),
+                (Some("run"), Some(name)) => run_examples(name, &flags),
+                _ => {
+                    eprintln!("Error: usage: examples [list] | examples run <name|all>");
+                    process::exit(1);
+                }
+            }
+        }
+        "backends" => {
+            outln!("┌─ Simulation backends (select with --backend <name>)");
+            for backend in backends::list() {
+                outln!("├─ {:<14} {}", backend.name(), backend.description());
+            }
+            outln!("└─ Default: {}", backends::DEFAULT_BACKEND);
         }
         "status" => {
-            cli::show_status();
+            let flags = parse_flags(&args[2..], &["cluster"]);
+            if flags.has("cluster") {
+                show_cluster(flags.value("url").unwrap_or(DEFAULT_SERVER_URL));
+            } else {
+                cli::show_status();
+            }
         }
         "version" | "--version" | "-v" => {
-            println!("QuantumMesh v{}", env!("CARGO_PKG_VERSION"));
+            let flags = parse_flags(&args[2..], &[]);
+            outln!("QuantumMesh v{} (API v{})", env!("CARGO_PKG_VERSION"), api_version::API_VERSION);
+            if let Some(url) = flags.value("url") {
+                show_server_version(url);
+            }
         }
         "help" | "--help" | "-h" => {
             print_help();
@@ -73,9 +369,35 @@ fn main() {
     }
 }
 
+/// Parse command flags, exiting on malformed input
+fn parse_flags(args: &[String], switches: &[&str]) -> cli::Flags {
+    cli::Flags::parse(args, switches).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    })
+}
+
+/// Start recording a timeline when `--profile-trace` is given
+fn start_profiling(flags: &cli::Flags) {
+    if flags.value("profile-trace").is_some() {
+        profiler::enable();
+    }
+}
+
+/// Write the recorded timeline to the `--profile-trace` path
+fn finish_profiling(flags: &cli::Flags) {
+    if let Some(path) = flags.value("profile-trace") {
+        if let Err(e) = profiler::write_trace(path) {
+            eprintln!("Error writing profile trace: {}", e);
+            process::exit(1);
+        }
+        eprintln!("Profile trace written to {} (open in chrome://tracing or Perfetto)", path);
+    }
+}
+
 /// Print help information
 fn print_help() {
-    println!(r#"
+    outln!(r#"
 ┏━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┓
 ┃      QuantumMesh - Quantum Circuit Simulator     ┃
 ┗━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛
@@ -84,94 +406,1287 @@ Usage: quantummesh <command> [options]
 
 Commands:
   simulate <file>     Simulate quantum circuit from JSON file
+  rerun <dir>         Repeat a --run-dir simulation from its saved circuit
+                      and config
   serve [port]        Start REST API server (default: 8080)
   benchmark <qubits>  Run benchmark with N qubits
+  monitored <qubits>  Sweep the measurement rate of random monitored circuits
+                      and report the mean half-chain entanglement entropy
   visualize <file>    Visualize circuit structure
   optimize <file>     Optimize circuit gates
-  status              Show system status
-  version             Show version information
+  watch <file>        Re-simulate a circuit file incrementally on every save
+  tune <file>         Adjust a circuit's named parameters with sliders and watch
+                      the expectation value and probabilities update live
+  convert <in> <out>  Convert between JSON, YAML, TOML, QASM, Quil, protobuf and
+                      Stim (Clifford circuits only)
+  dem <file.stim>     Write the detector error model of a noisy Stim circuit
+                      for QEC decoders
+  qec <file.stim>     Sample a noisy Stim circuit in bulk, decode its detectors
+                      and report logical error rates
+  tomography <file> --qubits <list>
+                      Run the 3^k basis-rotated circuits of a state tomography
+                      of a few qubits and report every Pauli expectation and
+                      the linear-inversion density matrix
+  unitary <file>      Compose the full 2^n x 2^n unitary of a small circuit
+                      (at most 10 qubits), to check decompositions
+  equiv <a> <b>       State fidelity between two circuits' final states or
+                      saved .npy states; exits 1 unless they match
+  export-workspace <archive>
+                      Save a server's circuits, results, noise models and
+                      configs to a single archive file
+  import-workspace <archive>
+                      Load an archive into a server (ids are reassigned)
+  analyze <file>      Report depth, CNOT and T counts; --fault-tolerant adds
+                      surface code qubits, T factories and runtime
+  faults <file>       Inject X/Y/Z errors after each gate and map how much
+                      each location changes the measured outputs
+  show <results> [other]
+                      Render a saved results file (or compare two) without
+                      re-simulating
+  lint <file>         Report errors and likely mistakes; --fix applies the safe
+                      rewrites after showing them as a diff
+  examples [run <name|all>]
+                      List the bundled end-to-end examples, or run one (or
+                      all) and check its results; exits 1 if a check fails
+  backends            List the registered simulation backends
+  status [--cluster]  Show system status, or with --cluster a server's view of
+                      its configured worker nodes (--url, default
+                      http://localhost:8080)
+  version [--url <u>] Show version information, and with --url whether the
+                      server's API version is compatible with this client
   help                Show this help message
 
+Global options:
+  --plain             ASCII-only output for snapshot tests: no box drawing or
+                      symbols, and JSON written with sorted keys
+
+Simulate options:
+  --result-format <f> probabilities (default), forest (pyQuil readout JSON),
+                      memory (per-shot classical register strings) or counts
+                      (Qiskit-style counts, most frequent first)
+  --shots <n>         Sample n shots and show their counts instead of the
+                      probability vector; also the shot count for forest
+                      and memory readout, and per Pauli string for
+                      --observable (default: 1000)
+  --observable <H>    Estimate the expectation value of a weighted sum of
+                      Pauli strings from shots, with its variance and
+                      standard error; each string is measured in its own basis
+  --precision <e>     With --observable, keep sampling until the standard error
+                      is at most e, spreading shots where they cut it most
+  --max-shots <n>     Cap on the shots --precision may take (default: 10000000)
+  --save-state <file> Save the final state vector as a .npy (complex128) file
+  --backend <name>    Simulate with a registered backend instead of the built-in
+                      state vector (see `backends`; local runs)
+  --max-bond <n>      Bond dimension cap of the mps backend (default: 64);
+                      larger is more accurate for more entangled circuits
+  --min-coefficient <c>
+                      Pauli strings the pauli backend drops once their
+                      coefficient is below c (default: 1e-8)
+  --max-weight <n>    Pauli strings the pauli backend drops once they act on
+                      more than n qubits (default: no limit)
+  --noise <file>      Noise model (JSON/YAML): depolarizing errors at its gate
+                      error rates, crosstalk on coupled neighbours, T1/T2
+                      relaxation over gate durations and its Kraus channels
+                      follow each gate, rotation errors perturb gate angles,
+                      and readout errors follow each measurement (local runs)
+  --noise-mode <m>    density (exact; default up to 12 qubits) or trajectory
+                      (one sampled Kraus branch per channel, per shot)
+  --trajectories <n>  Trajectories averaged for probabilities in trajectory
+                      mode (default: 1000)
+  --threads <n>       Threads running trajectories in trajectory mode, each
+                      with its own state vector (default: all cores)
+  --ideal-counts      With --noise and --shots, also sample the circuit without
+                      noise and show (or, for counts, output) both
+  --seed <n>          Seed shot sampling, mid-circuit measurements and resets
+                      so reruns reproduce; also for benchmark --random, qec
+                      and monitored, and sent to --remote servers
+  --save-records <file>
+                      Stream each shot's classical register to a bit-packed
+                      file (Stim's b8 layout) instead of keeping it in memory
+  --save-snapshots <dir>
+                      Save the state recorded by each Snapshot gate as a
+                      .npy file in this directory (local runs)
+  --reduced <qubits>  Print the reduced density matrix and purity of a few
+                      qubits, e.g. 0,1 (local runs)
+  --amplitudes <states>
+                      Print the amplitudes of a few basis states, as indices
+                      or 0b bit strings with qubit 0 rightmost (local runs)
+  --marginal <qubits> Print the joint outcome probabilities of a few qubits,
+                      e.g. 0,3 (local runs)
+  --entropy <qubits>  Print the von Neumann and Renyi-2 entanglement entropy
+                      between these qubits and the rest (local runs)
+  --hdf5 <file>       Append distribution, metadata and timings to an HDF5
+                      archive (also accepted by benchmark; needs the hdf5
+                      cargo feature)
+  --profile-trace <f> Write a Chrome trace timeline of CPU phases, GPU kernels
+                      and transfers (also accepted by benchmark)
+  --output <file>     Save distribution, metadata and timings as a JSON
+                      results file for `show` (also accepted by benchmark)
+  --remote <url>      Simulate on a running server (http://host:port) instead
+                      of locally; also accepted by optimize
+  --run-dir <dir>     Create a run directory holding a circuit copy, config
+                      snapshot, logs, results and checkpoints (also accepted
+                      by rerun, to write the repeat somewhere new)
+  --checkpoint-every <n>
+                      With --run-dir, save the state vector to checkpoints/
+                      every n gates
+
+Serve options:
+  --jobs-dir <dir>    Where background jobs and their checkpoints are kept, so
+                      a restarted server resumes them (default:
+                      quantummesh-jobs)
+  --checkpoint-every <n>
+                      Checkpoint running jobs every n gates (default: 500)
+  --noise <files>     Comma-separated noise model files (JSON/YAML) to store
+                      as named models, each under its file name without the
+                      extension
+
+Optimize options:
+  --objective <name>  Objective to prioritize: gates (default), depth, cnot
+                      or fidelity
+  --noise <file>      Noise model (JSON/YAML) used to estimate fidelity
+  --rules <file>      Rewrite rules (JSON/YAML) applied as an extra pass
+
+Tune options:
+  --observable <H>    Pauli strings whose weighted sum's expectation is shown,
+                      e.g. "ZZI + 0.5*XXI"; one of I, X, Y or Z per qubit
+                      with qubit 0 first (default: all Z)
+  --step <angle>      Change per left/right key press (default: 0.05)
+
+Lint options:
+  --max-depth <n>     Warn when the circuit is deeper than n (default: 1000)
+  --fix               Drop gates on unmeasured qubits and merge adjacent
+                      rotations, showing the diff before writing
+  --dry-run           With --fix, show the diff without writing
+  --output <file>     With --fix, write here instead of over the input
+
+Fault options:
+  --inject <g:q:P>    Only inject Pauli P (X, Y or Z) on qubit q after gate
+                      index g, e.g. 3:1:X
+  --paulis <list>     Errors to sweep (default: XYZ)
+  --output <file>     Write the sensitivity map as JSON
+
+Benchmark options:
+  --random <depth>    Benchmark a random circuit of the given depth and test
+                      sampled shots with χ² and linear XEB
+  --shots <n>         Shots sampled for --random (default: 1000)
+  --power             Measure energy from RAPL (CPU) and NVML via nvidia-smi
+                      (GPU) and report joules per simulation
+
+DEM options:
+  --noise <file>      Noise model (JSON/YAML) adding depolarizing errors after
+                      each gate and flips on each measurement
+  --output <file>     Write the model here instead of printing it
+
+QEC options:
+  --rates <list>      Uniform physical error rates to sweep, added as
+                      depolarizing and measurement errors (default:
+                      0.001,0.002,0.005,0.01,0.02 unless --noise is given)
+  --noise <file>      Noise model (JSON/YAML) to sample at instead of a sweep
+  --shots <n>         Shots per point (default: 100000)
+  --threads <n>       Sampling threads (default: all cores)
+  --output <file>     Write the points as JSON
+
+Analyze options:
+  --noise <file>      Noise model (JSON/YAML) for the fidelity and runtime
+  --fault-tolerant    Estimate resources on a surface code architecture
+  --physical-error <p>
+                      Physical error rate (default: 0.001)
+  --cycle-time <us>   Code cycle time in microseconds (default: 1)
+  --distance <d>      Code distance (default: smallest meeting the target)
+  --target-error <e>  Failure probability allowed for the run (default: 0.01)
+  --factories <n>     T factories (default: one T state per logical step)
+  --output <file>     Write the estimate as JSON
+
+Tomography options:
+  --qubits <list>     Qubits to reconstruct, e.g. 0,1 (at most 6)
+  --shots <n>         Shots per measurement setting (default: 1000)
+  --export-circuits <dir>
+                      Also write each setting's circuit to <dir>/<bases>.json,
+                      to run elsewhere
+  --backend <name>    Sample with a registered backend
+  --seed <n>          Seed the sampling
+  --output <file>     Write the counts and expectation values as JSON
+
+Unitary options:
+  --output <file>     Write the matrix as .npy (complex128, 2^n x 2^n) or as
+                      JSON rows of [re, im] entries
+  --compare <file>    Check the unitary against another circuit's, ignoring
+                      global phase; exits 1 unless they match
+  --tolerance <t>     Largest 1 - process fidelity still counted as the same
+                      unitary (default: 1e-9)
+
+Equiv options:
+  --tolerance <t>     Largest infidelity 1 - F still counted as the same state
+                      (default: 1e-9)
+
+Monitored options:
+  --rates <list>      Measurement rates to sweep (default:
+                      0,0.05,0.1,0.15,0.2,0.25,0.3,0.4,0.5)
+  --depth <n>         Layers of random two-qubit gates (default: 2 x qubits)
+  --trajectories <n>  Circuits sampled per rate (default: 100)
+  --renyi <order>     Rényi order of the entropy (default: 1, von Neumann)
+  --noise <file>      Noise model (JSON/YAML) whose estimated fidelity is
+                      reported per rate
+  --output <file>     Write the sweep as JSON
+
+Show options:
+  --top <n>           Number of states listed (default: 16)
+  --width <bits>      Bits per shot of a .b8 records file
+  --verify-env        Compare the build and environment recorded in the
+                      results with this machine instead of showing them;
+                      exits with status 1 on any difference
+
+Workspace options:
+  --url <url>         Server to export from or import into
+                      (default: http://localhost:8080)
+  --write-configs     On import, also write bundled config files into the
+                      current directory (existing files are kept)
+
 Examples:
   quantummesh simulate circuit.json
+  quantummesh simulate circuit.json --shots 1000
+  quantummesh simulate circuit.json --result-format forest --shots 100
   quantummesh serve 8080
   quantummesh benchmark 30
+  quantummesh benchmark 20 --hdf5 sweep.h5
+  quantummesh benchmark 12 --random 20 --shots 5000
+  quantummesh monitored 10 --rates 0,0.1,0.2,0.3 --trajectories 200
   quantummesh visualize circuit.json
   quantummesh optimize circuit.json
+  quantummesh convert circuit.json circuit.qasm
+  quantummesh lint circuit.json --fix --dry-run
+  quantummesh optimize circuit.json --objective fidelity --noise device.json
+  quantummesh simulate circuit.json --output run.json
+  quantummesh simulate circuit.json --run-dir runs/bell --checkpoint-every 50
+  quantummesh rerun runs/bell --run-dir runs/bell-2
+  quantummesh show run.json other-machine.json
+  quantummesh examples run teleportation --seed 7
+  quantummesh faults surface_code.stim --paulis XZ --output map.json
+  quantummesh simulate chain60.json --backend mps --max-bond 32 --shots 1000
+  quantummesh simulate oracle.json --backend sparse --shots 1000
+  quantummesh simulate clifford_t48.json --backend stabilizer --shots 1000
+  quantummesh simulate ansatz.json --backend pauli --observable "ZZII - 0.5*XXII"
+  quantummesh simulate bell.json --noise amplitude_damping.json --shots 1000
+  quantummesh unitary toffoli.json --compare toffoli_decomposed.json
+  quantummesh simulate circuit.json --remote http://gpu-box:8080
+  quantummesh export-workspace backup.qmw --url http://lab-server:8080
+  quantummesh status --cluster --url http://lab-server:8080
 "#);
 }
 
 /// Simulate a quantum circuit from file
-fn simulate_circuit(file_path: &str) {
-    println!("┌─ Loading circuit from: {}", file_path);
-    
-    match qsim::load_circuit(file_path) {
-        Ok(circuit) => {
-            println!("├─ Circuit loaded: {} qubits, {} gates", 
-                     circuit.num_qubits, circuit.gates.len());
-            println!("├─ Initializing quantum simulator...");
-            
-            let mut simulator = qsim::QuantumSimulator::new(circuit.num_qubits);
-            
-            println!("├─ Applying quantum gates...");
-            for (i, gate) in circuit.gates.iter().enumerate() {
-                simulator.apply_gate(gate);
-                if (i + 1) % 100 == 0 {
-                    println!("│  Progress: {}/{} gates", i + 1, circuit.gates.len());
+fn simulate_circuit(file_path: &str, flags: &cli::Flags) {
+    let format = match flags.value("result-format") {
+        Some(name) => results::ResultFormat::parse(name).unwrap_or_else(|| {
+            eprintln!("Error: unknown result format '{}'", name);
+            process::exit(1);
+        }),
+        None => results::ResultFormat::Probabilities,
+    };
+    let shots = match flags.value("shots") {
+        Some(value) => value.parse::<usize>().unwrap_or_else(|_| {
+            eprintln!("Error: --shots expects a positive integer");
+            process::exit(1);
+        }),
+        None => 1000,
+    };
+    // An explicit shot count turns the probability display into sampled counts
+    let sampled = flags.value("shots").is_some();
+
+    let remote = flags.value("remote").map(api_client);
+    let mut run_dir = flags.value("run-dir").map(|dir| {
+        rundir::RunDir::create(dir, "simulate", file_path, flags).unwrap_or_else(|e| {
+            eprintln!("Error creating run directory: {}", e);
+            process::exit(1);
+        })
+    });
+    let checkpoints = match (&run_dir, checkpoint_every(flags)) {
+        (Some(run), Some(every)) if remote.is_none() => Some((run, every)),
+        (_, None) => None,
+        (None, Some(_)) => {
+            eprintln!("Error: --checkpoint-every needs --run-dir");
+            process::exit(1);
+        }
+        (Some(_), Some(_)) => {
+            eprintln!("Error: --checkpoint-every is not available with --remote");
+            process::exit(1);
+        }
+    };
+
+    let backend = selected_backend(flags);
+    if backend.is_some() {
+        let state_vector_flags =
+            ["remote", "checkpoint-every", "save-state", "save-snapshots", "reduced", "marginal", "amplitudes", "entropy"];
+        if let Some(flag) = state_vector_flags.iter().find(|&&flag| flags.value(flag).is_some()) {
+            eprintln!("Error: --{} needs the built-in {} backend", flag, backends::DEFAULT_BACKEND);
+            process::exit(1);
+        }
+    }
+
+    if flags.has("ideal-counts") {
+        if flags.value("noise").is_none() {
+            eprintln!("Error: --ideal-counts compares against --noise; give a noise model");
+            process::exit(1);
+        }
+        if !matches!(format, results::ResultFormat::Probabilities | results::ResultFormat::Counts) || !sampled {
+            eprintln!("Error: --ideal-counts needs --shots, with the default or counts result format");
+            process::exit(1);
+        }
+    }
+
+    if let Some(text) = flags.value("observable") {
+        estimate_observable(file_path, text, remote.as_ref(), flags, shots);
+        return;
+    }
+
+    match format {
+        results::ResultFormat::Probabilities if sampled => {
+            let archived = flags.value("hdf5").is_some() || flags.value("output").is_some() || run_dir.is_some();
+            let start = std::time::Instant::now();
+            let (circuit, probabilities, mut sampler) = sample_shots(file_path, remote.as_ref(), flags, shots, checkpoints, archived, true);
+            let mut counts = std::collections::BTreeMap::new();
+            let written = sample_in_batches(&mut sampler, shots, &circuit, flags, |batch| {
+                for shot in batch {
+                    *counts.entry(shot.outcome).or_default() += 1;
+                }
+            });
+            if let (Some(path), Some(written)) = (flags.value("save-records"), written) {
+                outln!("├─ {} shot records ({} bits each) saved to {}", written, circuit.num_clbits(), path);
+            }
+            let simulate_time = start.elapsed();
+            if let Some(run_dir) = &mut run_dir {
+                run_dir.log(&format!("loaded {} qubits, {} gates", circuit.num_qubits, circuit.gates.len()));
+                run_dir.log(&format!("simulate: {:.3} ms, {} shots", simulate_time.as_secs_f64() * 1000.0, shots));
+            }
+            if archived {
+                let run = circuit_archive_run(file_path, &circuit, probabilities, seed_flag(flags))
+                    .with_metadata("shots", shots)
+                    .with_timing("simulate", simulate_time);
+                if let Some(run_dir) = &mut run_dir {
+                    let path = run_dir.result_path("results.json");
+                    save_results(&path.to_string_lossy(), &run);
+                    run_dir.log(&format!("results saved to {}", path.display()));
+                    outln!("├─ Run saved to {}", run_dir.root().display());
+                }
+                if let Some(path) = flags.value("hdf5") {
+                    let group = archive_run(path, &run);
+                    outln!("├─ Results archived to {}:/{}", path, group);
+                }
+                if let Some(path) = flags.value("output") {
+                    save_results(path, &run);
+                    outln!("├─ Results saved to {}", path);
+                }
+            }
+            outln!("└─ Sampled {} shots:", shots);
+
+            cli::display_counts(&counts, circuit.num_qubits);
+            if flags.has("ideal-counts") {
+                let mut ideal = std::collections::BTreeMap::new();
+                for shot in ideal_shots(&circuit, shots, flags) {
+                    *ideal.entry(shot.outcome).or_default() += 1;
                 }
+                outln!("└─ The same {} shots without noise:", shots);
+                cli::display_counts(&ideal, circuit.num_qubits);
             }
-            
-            println!("├─ Simulation complete!");
-            println!("├─ Measuring quantum state...");
-            
-            let results = simulator.measure_all();
-            println!("└─ Measurement results:");
-            
+        }
+        results::ResultFormat::Probabilities => {
+            let (circuit, results, timings) = match (&remote, &backend) {
+                (Some(client), _) => {
+                    let start = std::time::Instant::now();
+                    let (circuit, results) = simulate_remote(file_path, client, flags, true);
+                    (circuit, results, vec![("remote", start.elapsed())])
+                }
+                (None, Some(backend)) => {
+                    let circuit = load_circuit_file(file_path, true);
+                    outln!("├─ Simulating with the {} backend...", backend.name());
+                    let start = std::time::Instant::now();
+                    let results = backend
+                        .probabilities(&circuit, command_rng(flags).gen())
+                        .unwrap_or_else(|e| {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        });
+                    (circuit, results, vec![("simulate", start.elapsed())])
+                }
+                (None, None) => {
+                    let start = std::time::Instant::now();
+                    let (circuit, simulator) = run_circuit_file(file_path, true, checkpoints, seed_flag(flags));
+                    let simulate_time = start.elapsed();
+                    if let Some(path) = flags.value("save-state") {
+                        save_state(path, &simulator);
+                        outln!("├─ State vector saved to {}", path);
+                    }
+                    if !simulator.snapshots().is_empty() {
+                        cli::display_snapshots(simulator.snapshots(), circuit.num_qubits);
+                        if let Some(dir) = flags.value("save-snapshots") {
+                            save_snapshots(dir, simulator.snapshots());
+                            outln!("├─ Snapshots saved to {}", dir);
+                        }
+                    }
+                    if circuit.has_collapse() {
+                        outln!("├─ Circuit collapses qubits mid-circuit: probabilities are for one random trajectory");
+                        let bits: Vec<String> = simulator
+                            .classical_bits()
+                            .iter()
+                            .enumerate()
+                            .filter_map(|(index, bit)| bit.map(|bit| format!("c{}={}", index, bit as u8)))
+                            .collect();
+                        if !bits.is_empty() {
+                            outln!("├─ Mid-circuit measurements: {}", bits.join(" "));
+                        }
+                    }
+                    if let Some(list) = flags.value("reduced") {
+                        let qubits = qubit_list("reduced", list, circuit.num_qubits);
+                        if qubits.len() > MAX_REDUCED_QUBITS {
+                            eprintln!("Error: --reduced prints at most {} qubits", MAX_REDUCED_QUBITS);
+                            process::exit(1);
+                        }
+                        cli::display_density_matrix(&qubits, &simulator.reduced_density_matrix(&qubits));
+                    }
+                    if let Some(list) = flags.value("marginal") {
+                        let qubits = qubit_list("marginal", list, circuit.num_qubits);
+                        if qubits.len() > MAX_MARGINAL_QUBITS {
+                            eprintln!("Error: --marginal takes at most {} qubits", MAX_MARGINAL_QUBITS);
+                            process::exit(1);
+                        }
+                        cli::display_marginals(&qubits, &simulator.marginal_probabilities(&qubits));
+                    }
+                    if let Some(list) = flags.value("amplitudes") {
+                        let states = basis_state_list(list, circuit.num_qubits);
+                        cli::display_amplitudes(&states, &simulator.amplitudes(&states), circuit.num_qubits);
+                    }
+                    if let Some(list) = flags.value("entropy") {
+                        let qubits = qubit_list("entropy", list, circuit.num_qubits);
+                        match entropy::entanglement_spectrum(&simulator, &qubits) {
+                            Ok(spectrum) => cli::display_entropy(&qubits, &spectrum),
+                            Err(e) => {
+                                eprintln!("Error: --entropy: {}", e);
+                                process::exit(1);
+                            }
+                        }
+                    }
+                    outln!("├─ Measuring quantum state...");
+
+                    let start = std::time::Instant::now();
+                    let results = {
+                        let _span = profiler::span("measure", profiler::Track::Cpu);
+                        simulator.measure_all()
+                    };
+                    (circuit, results, vec![("simulate", simulate_time), ("measure", start.elapsed())])
+                }
+            };
+            if let Some(run_dir) = &mut run_dir {
+                run_dir.log(&format!("loaded {} qubits, {} gates", circuit.num_qubits, circuit.gates.len()));
+                for (phase, elapsed) in &timings {
+                    run_dir.log(&format!("{}: {:.3} ms", phase, elapsed.as_secs_f64() * 1000.0));
+                }
+            }
+            if flags.value("hdf5").is_some() || flags.value("output").is_some() || run_dir.is_some() {
+                let mut run = circuit_archive_run(file_path, &circuit, results.clone(), seed_flag(flags));
+                for (phase, elapsed) in timings {
+                    run = run.with_timing(phase, elapsed);
+                }
+                if let Some(run_dir) = &mut run_dir {
+                    let path = run_dir.result_path("results.json");
+                    save_results(&path.to_string_lossy(), &run);
+                    run_dir.log(&format!("results saved to {}", path.display()));
+                    outln!("├─ Run saved to {}", run_dir.root().display());
+                }
+                if let Some(path) = flags.value("hdf5") {
+                    let group = archive_run(path, &run);
+                    outln!("├─ Results archived to {}:/{}", path, group);
+                }
+                if let Some(path) = flags.value("output") {
+                    save_results(path, &run);
+                    outln!("├─ Results saved to {}", path);
+                }
+            }
+            outln!("└─ Measurement results:");
+
             cli::display_results(&results);
         }
-        Err(e) => {
-            eprintln!("Error loading circuit: {}", e);
+        results::ResultFormat::Forest | results::ResultFormat::Memory | results::ResultFormat::Counts => {
+            // Machine-readable output: only the readout JSON goes to stdout
+            let archived = flags.value("hdf5").is_some() || flags.value("output").is_some() || run_dir.is_some();
+            let start = std::time::Instant::now();
+            let (circuit, probabilities, mut sampler) = sample_shots(file_path, remote.as_ref(), flags, shots, checkpoints, archived, false);
+            // Counts only need a tally, so their shots are never all held at once
+            let mut sampled_shots = Vec::new();
+            let mut registers = std::collections::BTreeMap::new();
+            sample_in_batches(&mut sampler, shots, &circuit, flags, |batch| {
+                if format == results::ResultFormat::Counts {
+                    for shot in batch {
+                        *registers.entry(shot.register).or_default() += 1;
+                    }
+                } else {
+                    sampled_shots.extend_from_slice(batch);
+                }
+            });
+            let simulate_time = start.elapsed();
+            if archived {
+                let run = circuit_archive_run(file_path, &circuit, probabilities, seed_flag(flags))
+                    .with_metadata("shots", shots)
+                    .with_timing("simulate", simulate_time);
+                if let Some(path) = flags.value("hdf5") {
+                    archive_run(path, &run);
+                }
+                if let Some(path) = flags.value("output") {
+                    save_results(path, &run);
+                }
+                if let Some(run_dir) = &run_dir {
+                    save_results(&run_dir.result_path("results.json").to_string_lossy(), &run);
+                }
+            }
+            let readout = match format {
+                results::ResultFormat::Forest => cli::to_json(&results::forest_readout(&circuit, &sampled_shots)),
+                results::ResultFormat::Counts if flags.has("ideal-counts") => {
+                    let mut ideal = std::collections::BTreeMap::new();
+                    for shot in ideal_shots(&circuit, shots, flags) {
+                        *ideal.entry(shot.register).or_default() += 1;
+                    }
+                    cli::to_json(&results::ComparedCounts {
+                        noisy: results::Counts::new(circuit.num_clbits(), &registers),
+                        ideal: results::Counts::new(circuit.num_clbits(), &ideal),
+                    })
+                }
+                results::ResultFormat::Counts => cli::to_json(&results::Counts::new(circuit.num_clbits(), &registers)),
+                _ => cli::to_json(&results::memory_readout(&circuit, &sampled_shots)),
+            }
+            .unwrap_or_default();
+            if let Some(run_dir) = &mut run_dir {
+                run_dir.log(&format!("loaded {} qubits, {} gates", circuit.num_qubits, circuit.gates.len()));
+                run_dir.log(&format!("simulate: {:.3} ms, {} shots", simulate_time.as_secs_f64() * 1000.0, shots));
+                let path = run_dir.result_path("readout.json");
+                if let Err(e) = std::fs::write(&path, &readout) {
+                    eprintln!("Error saving readout: {}", e);
+                    process::exit(1);
+                }
+            }
+            outln!("{}", readout);
+        }
+    }
+}
+
+/// Noiseless shots of a circuit, for `--ideal-counts` to set beside the noisy ones
+fn ideal_shots(circuit: &qsim::QuantumCircuit, shots: usize, flags: &cli::Flags) -> Vec<qsim::Shot> {
+    qsim::sample_circuit(circuit, shots, &mut command_rng(flags))
+}
+
+/// `--seed`, exiting on a malformed value
+fn seed_flag(flags: &cli::Flags) -> Option<u64> {
+    flags.value("seed").map(|value| {
+        value.parse::<u64>().unwrap_or_else(|_| {
+            eprintln!("Error: --seed expects a non-negative integer");
+            process::exit(1);
+        })
+    })
+}
+
+/// Generator for a command's random choices: seeded by `--seed`, so a rerun draws the same
+/// samples, or from the operating system
+fn command_rng(flags: &cli::Flags) -> StdRng {
+    match seed_flag(flags) {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// Draws the next `n` shots of a simulated circuit
+type ShotSampler = Box<dyn FnMut(usize) -> Vec<qsim::Shot>>;
+
+/// Shots sampled at a time when streaming records to disk
+const RECORD_BATCH: usize = 1 << 16;
+
+/// Simulate a circuit file for sampling, as (circuit, probabilities, sampler).
+/// Locally, shots come straight from the amplitudes and probabilities are only built when
+/// `keep_probabilities` asks for them (to archive); remotely, the server's probabilities are sampled.
+fn sample_shots(
+    file_path: &str,
+    remote: Option<&client::ApiClient>,
+    flags: &cli::Flags,
+    shots: usize,
+    checkpoints: Option<(&rundir::RunDir, usize)>,
+    keep_probabilities: bool,
+    verbose: bool,
+) -> (qsim::QuantumCircuit, Vec<f64>, ShotSampler) {
+    let mut rng = command_rng(flags);
+    match (remote, selected_backend(flags)) {
+        (Some(client), _) => {
+            let (circuit, probabilities) = simulate_remote(file_path, client, flags, verbose);
+            let (sampled, weights) = (circuit.clone(), probabilities.clone());
+            let sampler: ShotSampler =
+                Box::new(move |n| qsim::terminal_shots(&sampled, results::sample_outcomes(&weights, n, &mut rng)));
+            (circuit, probabilities, sampler)
+        }
+        (None, Some(backend)) => {
+            let circuit = load_circuit_file(file_path, verbose);
+            let fail = |e: String| -> ! {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            };
+            let probabilities = if keep_probabilities {
+                backend.probabilities(&circuit, rng.gen()).unwrap_or_else(|e| fail(e))
+            } else {
+                Vec::new()
+            };
+            if verbose {
+                outln!("├─ Sampling {} shots with the {} backend...", shots, backend.name());
+            }
+            let sampled = circuit.clone();
+            let sampler: ShotSampler = Box::new(move |n| backend.sample(&sampled, n, rng.gen()).unwrap_or_else(|e| fail(e)));
+            (circuit, probabilities, sampler)
+        }
+        (None, None) => {
+            let (circuit, simulator) = run_circuit_file(file_path, verbose, checkpoints, Some(rng.gen()));
+            if let Some(path) = flags.value("save-state") {
+                save_state(path, &simulator);
+                if verbose {
+                    outln!("├─ State vector saved to {}", path);
+                }
+            }
+            if verbose {
+                outln!("├─ Sampling {} shots...", shots);
+            }
+            let probabilities = if keep_probabilities { simulator.measure_all() } else { Vec::new() };
+            let sampled = circuit.clone();
+            let sampler: ShotSampler = Box::new(move |n| {
+                let _span = profiler::span("sample", profiler::Track::Cpu);
+                if sampled.has_collapse() {
+                    qsim::sample_circuit(&sampled, n, &mut rng)
+                } else {
+                    qsim::terminal_shots(&sampled, simulator.sample(n, &mut rng))
+                }
+            });
+            (circuit, probabilities, sampler)
+        }
+    }
+}
+
+/// Draw `shots` shots a batch at a time, handing each batch to `visit` and streaming it to
+/// the `--save-records` file when one is given, so a records file can hold far more shots
+/// than would fit in memory. Returns the number of records written, exiting on write errors.
+fn sample_in_batches(
+    sampler: &mut ShotSampler,
+    shots: usize,
+    circuit: &qsim::QuantumCircuit,
+    flags: &cli::Flags,
+    mut visit: impl FnMut(&[qsim::Shot]),
+) -> Option<usize> {
+    let fail = |e: std::io::Error| -> ! {
+        eprintln!("Error writing records: {}", e);
+        process::exit(1);
+    };
+    let mut writer = flags.value("save-records").map(|path| {
+        records::RecordWriter::create(path, circuit.num_clbits()).unwrap_or_else(|e| fail(e))
+    });
+    let mut remaining = shots;
+    while remaining > 0 {
+        let batch = sampler(remaining.min(RECORD_BATCH));
+        visit(&batch);
+        if let Some(writer) = &mut writer {
+            batch.iter().try_for_each(|shot| writer.push(shot.register)).unwrap_or_else(|e| fail(e));
+        }
+        remaining -= batch.len();
+    }
+    writer.map(|writer| writer.finish().unwrap_or_else(|e| fail(e)))
+}
+
+/// Default cap on the shots spent reaching `--precision`
+const DEFAULT_MAX_SHOTS: usize = 10_000_000;
+
+/// Estimate an observable's expectation value from shots, measuring each Pauli string in its
+/// own basis, and report its variance and standard error
+fn estimate_observable(file_path: &str, text: &str, remote: Option<&client::ApiClient>, flags: &cli::Flags, shots: usize) {
+    let fail = |e: String| -> ! {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    };
+    let observable = observable::PauliSum::parse(text).unwrap_or_else(|e| fail(format!("--observable: {}", e)));
+    let precision = flags.value("precision").map(|value| {
+        let standard_error = value
+            .parse::<f64>()
+            .ok()
+            .filter(|&error| error > 0.0)
+            .unwrap_or_else(|| fail("--precision expects a positive standard error".to_string()));
+        let max_shots = match flags.value("max-shots") {
+            Some(value) => value
+                .parse::<usize>()
+                .unwrap_or_else(|_| fail("--max-shots expects a positive integer".to_string())),
+            None => DEFAULT_MAX_SHOTS,
+        };
+        observable::Precision { standard_error, max_shots }
+    });
+    if shots == 0 {
+        fail("--observable needs at least one shot per term".to_string());
+    }
+
+    let mut rng = command_rng(flags);
+    let measured = |circuit: &qsim::QuantumCircuit, gates: &[qsim::QuantumGate]| {
+        let mut measured = circuit.clone();
+        measured.gates.extend_from_slice(gates);
+        measured
+    };
+    let (estimate, exact) = match (remote, selected_backend(flags)) {
+        (Some(client), _) => {
+            let circuit = load_circuit_file(file_path, true);
+            outln!("├─ Sampling on {}...", flags.value("remote").unwrap_or_default());
+            let estimate = observable_estimate(&circuit, &observable, shots, precision, |gates, n| {
+                let probabilities = client
+                    .simulate(&measured(&circuit, gates), Some(rng.gen()))
+                    .unwrap_or_else(|e| fail(format!("from remote server: {}", e)));
+                results::sample_outcomes(&probabilities, n, &mut rng)
+            });
+            (estimate, false)
+        }
+        (None, Some(backend)) => {
+            let circuit = load_circuit_file(file_path, true);
+            if let Err(e) = observable.validate(circuit.num_qubits) {
+                fail(format!("--observable: {}", e));
+            }
+            if let Some(term_means) = backend.expectation(&circuit, &observable) {
+                let term_means = term_means.unwrap_or_else(|e| fail(e));
+                (observable::ShotEstimate::exact(&observable, term_means), true)
+            } else {
+                outln!("├─ Sampling with the {} backend...", backend.name());
+                let estimate = observable_estimate(&circuit, &observable, shots, precision, |gates, n| {
+                    let shots = backend.sample(&measured(&circuit, gates), n, rng.gen()).unwrap_or_else(|e| fail(e));
+                    shots.into_iter().map(|shot| shot.outcome).collect()
+                });
+                (estimate, false)
+            }
+        }
+        (None, None) => {
+            let (circuit, simulator) = run_circuit_file(file_path, true, None, Some(rng.gen()));
+            outln!("├─ Sampling...");
+            let estimate = observable_estimate(&circuit, &observable, shots, precision, |gates, n| {
+                let _span = profiler::span("sample", profiler::Track::Cpu);
+                if circuit.has_collapse() {
+                    let shots = qsim::sample_circuit(&measured(&circuit, gates), n, &mut rng);
+                    return shots.into_iter().map(|shot| shot.outcome).collect();
+                }
+                // Rotate a copy of the final state, so the circuit runs only once
+                let mut rotated = simulator.clone();
+                for gate in gates {
+                    rotated.apply_gate(gate);
+                }
+                rotated.sample(n, &mut rng)
+            });
+            (estimate, false)
+        }
+    };
+    if exact {
+        cli::display_exact_expectation(&observable, &estimate, flags.value("backend").unwrap_or_default());
+    } else {
+        cli::display_shot_estimate(&observable, &estimate, precision);
+    }
+    if let Some(path) = flags.value("output") {
+        let written = cli::to_json_pretty(&estimate)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            fail(format!("writing estimate: {}", e));
+        }
+        outln!("├─ Estimate written to {}", path);
+    }
+    if exact {
+        outln!("└─ ⟨H⟩ = {:+.6}", estimate.mean);
+    } else {
+        outln!("└─ ⟨H⟩ = {:+.6} ± {:.6}", estimate.mean, estimate.standard_error);
+    }
+}
+
+/// Check an observable against a loaded circuit, then sample its expectation value
+fn observable_estimate(
+    circuit: &qsim::QuantumCircuit,
+    observable: &observable::PauliSum,
+    shots: usize,
+    precision: Option<observable::Precision>,
+    sample: impl FnMut(&[qsim::QuantumGate], usize) -> Vec<usize>,
+) -> observable::ShotEstimate {
+    if let Err(e) = observable.validate(circuit.num_qubits) {
+        eprintln!("Error: --observable: {}", e);
+        process::exit(1);
+    }
+    observable::sample_expectation(observable, shots, precision, sample)
+}
+
+/// Archive entry for a simulated circuit file, carrying the circuit's own metadata
+fn circuit_archive_run(
+    file_path: &str,
+    circuit: &qsim::QuantumCircuit,
+    probabilities: Vec<f64>,
+    seed: Option<u64>,
+) -> archive::ArchiveRun {
+    let mut run = archive::ArchiveRun::new(probabilities).with_seed(seed)
+        .with_metadata("command", "simulate")
+        .with_metadata("circuit", file_path)
+        .with_metadata("num_qubits", circuit.num_qubits)
+        .with_metadata("num_gates", circuit.gates.len());
+    for (key, value) in &circuit.metadata {
+        run = run.with_metadata(key, value);
+    }
+    run
+}
+
+/// Append a run to an HDF5 archive, exiting on failure
+fn archive_run(path: &str, run: &archive::ArchiveRun) -> String {
+    archive::append_run(path, run).unwrap_or_else(|e| {
+        eprintln!("Error writing HDF5 archive: {}", e);
+        process::exit(1);
+    })
+}
+
+/// Write the final state vector as a complex128 `.npy` file, exiting on failure
+fn save_state(path: &str, simulator: &qsim::QuantumSimulator) {
+    if let Err(e) = npy::save_state(path, &simulator.get_state()) {
+        eprintln!("Error saving state vector: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Save each snapshot as `<dir>/<index>-<label>.npy`, numbered in circuit order
+fn save_snapshots(dir: &str, snapshots: &[qsim::StateSnapshot]) {
+    let saved = std::fs::create_dir_all(dir).and_then(|_| {
+        snapshots.iter().enumerate().try_for_each(|(index, snapshot)| {
+            let label: String = snapshot
+                .label
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+                .collect();
+            let path = std::path::Path::new(dir).join(format!("{:02}-{}.npy", index, label));
+            npy::save_state(&path.to_string_lossy(), &snapshot.state)
+        })
+    });
+    if let Err(e) = saved {
+        eprintln!("Error saving snapshots: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Load a circuit file and simulate it on a server, exiting on errors
+fn simulate_remote(file_path: &str, client: &client::ApiClient, flags: &cli::Flags, verbose: bool) -> (qsim::QuantumCircuit, Vec<f64>) {
+    let circuit = qsim::load_circuit(file_path).unwrap_or_else(|e| {
+        eprintln!("Error loading circuit: {}", e);
+        process::exit(1);
+    });
+    if verbose {
+        outln!("┌─ Loading circuit from: {}", file_path);
+        outln!("├─ Circuit loaded: {} qubits, {} gates", circuit.num_qubits, circuit.gates.len());
+        outln!("├─ Simulating on {}...", flags.value("remote").unwrap_or_default());
+    }
+    let remote_error = |e: Box<dyn std::error::Error>| -> ! {
+        eprintln!("Error from remote server: {}", e);
+        process::exit(1);
+    };
+    if let Some(path) = flags.value("save-state") {
+        let npy = client.state_npy(&circuit).unwrap_or_else(|e| remote_error(e));
+        if let Err(e) = std::fs::write(path, npy) {
+            eprintln!("Error saving state vector: {}", e);
             process::exit(1);
         }
+        if verbose {
+            outln!("├─ State vector saved to {}", path);
+        }
+    }
+    let probabilities = client.simulate(&circuit, seed_flag(flags)).unwrap_or_else(|e| remote_error(e));
+    (circuit, probabilities)
+}
+
+/// Repeat a `--run-dir` simulation from its saved circuit copy and options
+fn rerun(dir: &str, flags: &cli::Flags) {
+    let mut config = rundir::load_config(std::path::Path::new(dir)).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    });
+    if config.command != "simulate" {
+        eprintln!("Error: cannot rerun '{}' runs", config.command);
+        process::exit(1);
+    }
+    // Checkpoints only have somewhere to go when the repeat gets its own run directory
+    let new_dir = flags.value("run-dir");
+    if new_dir.is_none() {
+        config.options.remove("checkpoint-every");
+    }
+    let mut args = config.args(std::path::Path::new(dir));
+    if let Some(new_dir) = new_dir {
+        args.push("--run-dir".to_string());
+        args.push(new_dir.to_string());
+    }
+    let saved = parse_flags(&args, SIMULATE_SWITCHES);
+    start_profiling(&saved);
+    simulate_circuit(saved.positional(0).unwrap_or_default(), &saved);
+    finish_profiling(&saved);
+}
+
+/// Qubits of a `--<flag>` list such as `0,2`, exiting unless they are distinct and in range
+fn qubit_list(flag: &str, list: &str, num_qubits: usize) -> Vec<usize> {
+    let qubits: Vec<usize> = list
+        .split(',')
+        .map(|qubit| qubit.trim().parse::<usize>())
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|_| {
+            eprintln!("Error: --{} expects qubit indices separated by commas, e.g. 0,1", flag);
+            process::exit(1);
+        });
+    if let Some(qubit) = qubits.iter().find(|&&qubit| qubit >= num_qubits) {
+        eprintln!("Error: --{} qubit {} is out of range for {} qubits", flag, qubit, num_qubits);
+        process::exit(1);
+    }
+    if (1..qubits.len()).any(|i| qubits[..i].contains(&qubits[i])) {
+        eprintln!("Error: --{} lists a qubit twice", flag);
+        process::exit(1);
+    }
+    qubits
+}
+
+/// `--amplitudes` basis states, as decimal indices or `0b` bit strings with qubit 0
+/// rightmost, exiting on malformed or out-of-range entries
+fn basis_state_list(list: &str, num_qubits: usize) -> Vec<u64> {
+    list.split(',')
+        .map(|entry| {
+            let entry = entry.trim();
+            let parsed = match entry.strip_prefix("0b") {
+                Some(bits) => u64::from_str_radix(bits, 2),
+                None => entry.parse::<u64>(),
+            };
+            match parsed {
+                Ok(state) if num_qubits >= 64 || state >> num_qubits == 0 => state,
+                Ok(_) => {
+                    eprintln!("Error: --amplitudes state {} is out of range for {} qubits", entry, num_qubits);
+                    process::exit(1);
+                }
+                Err(_) => {
+                    eprintln!("Error: --amplitudes expects basis states separated by commas, e.g. 0,5,0b101");
+                    process::exit(1);
+                }
+            }
+        })
+        .collect()
+}
+
+/// `--checkpoint-every <n>`, exiting unless n is a positive integer
+fn checkpoint_every(flags: &cli::Flags) -> Option<usize> {
+    flags.value("checkpoint-every").map(|value| match value.parse::<usize>() {
+        Ok(every) if every > 0 => every,
+        _ => {
+            eprintln!("Error: --checkpoint-every expects a positive integer");
+            process::exit(1);
+        }
+    })
+}
+
+/// Load a circuit file, exiting on load errors
+fn load_circuit_file(file_path: &str, verbose: bool) -> qsim::QuantumCircuit {
+    if verbose {
+        outln!("┌─ Loading circuit from: {}", file_path);
+    }
+
+    let _span = profiler::span("load circuit", profiler::Track::Cpu);
+    let circuit = qsim::load_circuit(file_path).unwrap_or_else(|e| {
+        eprintln!("Error loading circuit: {}", e);
+        process::exit(1);
+    });
+    if verbose {
+        outln!("├─ Circuit loaded: {} qubits, {} gates",
+                 circuit.num_qubits, circuit.gates.len());
+    }
+    circuit
+}
+
+/// `--backend`, or None for the built-in state vector, which the local code paths drive.
+/// `--noise` selects the noisy simulator instead. Backend options such as `--max-bond` are
+/// set on the selected backend.
+fn selected_backend(flags: &cli::Flags) -> Option<std::sync::Arc<dyn backends::StateBackend>> {
+    let fail = |e: String| -> ! {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    };
+    let options: Vec<(&str, &str)> =
+        BACKEND_OPTIONS.iter().filter_map(|&name| flags.value(name).map(|value| (name, value))).collect();
+    let name = flags.value("backend").unwrap_or(backends::DEFAULT_BACKEND);
+    let noise = flags.value("noise");
+    if name == backends::DEFAULT_BACKEND && options.is_empty() && noise.is_none() {
+        return None;
+    }
+    let mut backend: std::sync::Arc<dyn backends::StateBackend> = match noise {
+        Some(_) if flags.value("backend").is_some() => {
+            fail("--noise picks its own density matrix or state vector simulator; drop --backend".to_string())
+        }
+        Some(path) => {
+            let model = noise::load_noise_model(path).unwrap_or_else(|e| fail(format!("loading noise model: {}", e)));
+            std::sync::Arc::new(noise::NoisyBackend::new(model))
+        }
+        None => backends::get(name).unwrap_or_else(|e| fail(e)),
+    };
+    for (option, value) in options {
+        backend = backend.with_option(option, value).unwrap_or_else(|e| fail(e));
+    }
+    Some(backend)
+}
+
+/// Load a circuit file and apply all of its gates, exiting on load errors;
+/// with `checkpoints`, the state is saved into the run directory every n gates, and with
+/// `seed`, mid-circuit measurements and resets follow the same trajectory on every run
+fn run_circuit_file(
+    file_path: &str,
+    verbose: bool,
+    checkpoints: Option<(&rundir::RunDir, usize)>,
+    seed: Option<u64>,
+) -> (qsim::QuantumCircuit, qsim::QuantumSimulator) {
+    let circuit = load_circuit_file(file_path, verbose);
+    let mut simulator = match seed {
+        Some(seed) => qsim::QuantumSimulator::seeded(circuit.num_qubits, seed),
+        None => qsim::QuantumSimulator::new(circuit.num_qubits),
+    };
+    if verbose {
+        outln!("├─ Initialized quantum simulator ({} state)", simulator.backend_name());
+    }
+
+    if verbose {
+        outln!("├─ Applying quantum gates...");
+    }
+    let _span = profiler::span("apply gates", profiler::Track::Cpu)
+        .with_args(serde_json::json!({ "gates": circuit.gates.len() }));
+    for (i, gate) in circuit.gates.iter().enumerate() {
+        simulator.apply_gate(gate);
+        if let Some((run_dir, every)) = checkpoints {
+            if (i + 1) % every == 0 {
+                save_state(&run_dir.checkpoint_path(i + 1).to_string_lossy(), &simulator);
+            }
+        }
+        if verbose && (i + 1) % 100 == 0 {
+            outln!("│  Progress: {}/{} gates", i + 1, circuit.gates.len());
+        }
+    }
+
+    if verbose {
+        outln!("├─ Simulation complete!");
+    }
+    (circuit, simulator)
+}
+
+/// Re-simulate a circuit file whenever it changes, replaying only edited layers
+fn watch_circuit(file_path: &str) {
+    outln!("┌─ Watching {} (Ctrl+C to stop)", file_path);
+    let mut simulator: Option<incremental::IncrementalSimulator> = None;
+    let mut last_modified = None;
+
+    loop {
+        let modified = std::fs::metadata(file_path).and_then(|m| m.modified()).ok();
+        if modified.is_some() && modified != last_modified {
+            last_modified = modified;
+            match qsim::load_circuit(file_path) {
+                Ok(circuit) => {
+                    let simulator = simulator
+                        .get_or_insert_with(|| incremental::IncrementalSimulator::new(circuit.num_qubits));
+                    let start = std::time::Instant::now();
+                    let applied = simulator.update(&circuit);
+                    outln!("├─ Re-simulated {}/{} gates in {:?} ({} cached layers)",
+                             applied, circuit.gates.len(), start.elapsed(), simulator.checkpoint_count());
+                    cli::display_results(&simulator.simulator().measure_all());
+                }
+                Err(e) => eprintln!("│  Error loading circuit: {}", e),
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    }
+}
+
+/// Interactive sliders over the named parameters of a circuit
+fn tune_parameters(file_path: &str, flags: &cli::Flags) {
+    let step = match flags.value("step") {
+        Some(step) => match step.parse::<f64>() {
+            Ok(step) if step > 0.0 && step.is_finite() => step,
+            _ => {
+                eprintln!("Error: --step must be a positive number");
+                process::exit(1);
+            }
+        },
+        None => 0.05,
+    };
+    if let Err(e) = tune::run(file_path, flags.value("observable"), step) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
     }
 }
 
 /// Run performance benchmark
-fn run_benchmark(qubits: usize) {
-    println!("┌─ Running benchmark with {} qubits", qubits);
+fn run_benchmark(qubits: usize, flags: &cli::Flags) {
+    outln!("┌─ Running benchmark with {} qubits", qubits);
+    let meter = start_power_meter(flags);
     
     let mut simulator = qsim::QuantumSimulator::new(qubits);
     
-    println!("├─ Applying Hadamard gates...");
+    outln!("├─ Applying Hadamard gates...");
     let start = std::time::Instant::now();
+    let span = profiler::span("hadamard layer", profiler::Track::Cpu);
     
     for i in 0..qubits {
         simulator.apply_hadamard(i);
     }
     
+    drop(span);
     let hadamard_time = start.elapsed();
     
-    println!("├─ Applying CNOT gates...");
+    outln!("├─ Applying CNOT gates...");
     let start = std::time::Instant::now();
+    let span = profiler::span("cnot chain", profiler::Track::Cpu);
     
     for i in 0..qubits-1 {
         simulator.apply_cnot(i, i+1);
     }
     
+    drop(span);
     let cnot_time = start.elapsed();
     
-    println!("├─ Measuring state...");
+    outln!("├─ Measuring state...");
     let start = std::time::Instant::now();
+    let span = profiler::span("measure", profiler::Track::Cpu);
     
-    let _results = simulator.measure_all();
+    let results = simulator.measure_all();
     
+    drop(span);
     let measure_time = start.elapsed();
+    let energy = meter.map(power::PowerMeter::stop);
+
+    if flags.value("hdf5").is_some() || flags.value("output").is_some() {
+        let run = with_energy(archive::ArchiveRun::new(results), energy.as_ref())
+            .with_metadata("command", "benchmark")
+            .with_metadata("num_qubits", qubits)
+            .with_timing("hadamard", hadamard_time)
+            .with_timing("cnot", cnot_time)
+            .with_timing("measure", measure_time);
+        if let Some(path) = flags.value("hdf5") {
+            let group = archive_run(path, &run);
+            outln!("├─ Results archived to {}:/{}", path, group);
+        }
+        if let Some(path) = flags.value("output") {
+            save_results(path, &run);
+            outln!("├─ Results saved to {}", path);
+        }
+    }
     
-    println!("└─ Benchmark results:");
-    println!("   Hadamard gates: {:?}", hadamard_time);
-    println!("   CNOT gates: {:?}", cnot_time);
-    println!("   Measurement: {:?}", measure_time);
-    println!("   Total time: {:?}", hadamard_time + cnot_time + measure_time);
+    outln!("└─ Benchmark results:");
+    outln!("   Hadamard gates: {:?}", hadamard_time);
+    outln!("   CNOT gates: {:?}", cnot_time);
+    outln!("   Measurement: {:?}", measure_time);
+    outln!("   Total time: {:?}", hadamard_time + cnot_time + measure_time);
+    if let Some(energy) = &energy {
+        cli::display_energy(energy);
+    }
+}
+
+/// Start RAPL/NVML energy sampling when `--power` is given
+fn start_power_meter(flags: &cli::Flags) -> Option<power::PowerMeter> {
+    if !flags.has("power") {
+        return None;
+    }
+    let meter = power::PowerMeter::start();
+    if !meter.has_sources() {
+        eprintln!("Warning: --power found no readable RAPL counters or nvidia-smi; energy will not be reported");
+        return None;
+    }
+    Some(meter)
+}
+
+/// Record measured energy in the run's metadata
+fn with_energy(run: archive::ArchiveRun, energy: Option<&power::EnergyReport>) -> archive::ArchiveRun {
+    match energy {
+        Some(energy) => {
+            let mut run = run.with_metadata("joules", energy.total_joules());
+            for (key, prefix) in [("cpu_joules", "CPU"), ("gpu_joules", "GPU")] {
+                if let Some(joules) = energy.joules_for(prefix) {
+                    run = run.with_metadata(key, joules);
+                }
+            }
+            run
+        }
+        None => run,
+    }
+}
+
+/// Benchmark a random circuit and test shots sampled from it against the ideal distribution
+fn run_random_benchmark(qubits: usize, depth: usize, flags: &cli::Flags) {
+    let shots = match flags.value("shots") {
+        Some(value) => value.parse::<usize>().unwrap_or_else(|_| {
+            eprintln!("Error: --shots expects a positive integer");
+            process::exit(1);
+        }),
+        None => 1000,
+    };
+    let mut rng = command_rng(flags);
+    let circuit = qsim::create_random_circuit(qubits, depth, &mut rng);
+    outln!("┌─ Running random circuit benchmark: {} qubits, depth {} ({} gates)", qubits, depth, circuit.gates.len());
+    let meter = start_power_meter(flags);
+
+    outln!("├─ Applying gates...");
+    let start = std::time::Instant::now();
+    let span = profiler::span("random circuit", profiler::Track::Cpu);
+    let mut simulator = qsim::QuantumSimulator::new(qubits);
+    for gate in &circuit.gates {
+        simulator.apply_gate(gate);
+    }
+    drop(span);
+    let circuit_time = start.elapsed();
+
+    outln!("├─ Measuring and sampling {} shots...", shots);
+    let start = std::time::Instant::now();
+    let span = profiler::span("measure", profiler::Track::Cpu);
+    let probabilities = simulator.measure_all();
+    drop(span);
+    let measure_time = start.elapsed();
+
+    let start = std::time::Instant::now();
+    let span = profiler::span("sample", profiler::Track::Cpu);
+    let outcomes = simulator.sample(shots, &mut rng);
+    drop(span);
+    let sample_time = start.elapsed();
+    let total_time = circuit_time + measure_time + sample_time;
+    let energy = meter.map(power::PowerMeter::stop);
+
+    let test = stats::evaluate(&probabilities, &stats::count_outcomes(&outcomes)).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    });
+
+    if flags.value("hdf5").is_some() || flags.value("output").is_some() {
+        let run = with_energy(archive::ArchiveRun::new(probabilities).with_seed(seed_flag(flags)), energy.as_ref())
+            .with_metadata("command", "benchmark")
+            .with_metadata("num_qubits", qubits)
+            .with_metadata("depth", depth)
+            .with_metadata("shots", shots)
+            .with_metadata("chi_squared", test.chi_squared)
+            .with_metadata("p_value", test.p_value)
+            .with_metadata("linear_xeb", test.linear_xeb)
+            .with_metadata("heavy_output_probability", test.heavy_output_probability)
+            .with_metadata("effective_fidelity", test.effective_fidelity)
+            .with_metadata("samples_per_second", shots as f64 / total_time.as_secs_f64())
+            .with_timing("circuit", circuit_time)
+            .with_timing("measure", measure_time)
+            .with_timing("sample", sample_time);
+        if let Some(path) = flags.value("hdf5") {
+            let group = archive_run(path, &run);
+            outln!("├─ Results archived to {}:/{}", path, group);
+        }
+        if let Some(path) = flags.value("output") {
+            save_results(path, &run);
+            outln!("├─ Results saved to {}", path);
+        }
+    }
+
+    outln!("└─ Benchmark results:");
+    outln!("   Circuit: {:?}", circuit_time);
+    outln!("   Measurement: {:?}", measure_time);
+    outln!("   Sampling: {:?}", sample_time);
+    // End-to-end rate, comparable with simulators that report time per batch of samples
+    outln!("   Samples/second: {:.0}", shots as f64 / total_time.as_secs_f64());
+    outln!("   Sampling only: {:.0} samples/second", shots as f64 / sample_time.as_secs_f64());
+    if let Some(energy) = &energy {
+        cli::display_energy(energy);
+        outln!("   Energy per shot: {:.3e} J", energy.total_joules() / shots as f64);
+    }
+    cli::display_sample_test(&test);
 }
 
 /// Visualize circuit structure
@@ -188,14 +1703,102 @@ fn visualize_circuit(file_path: &str) {
 }
 
 /// Optimize circuit gates
-fn optimize_circuit(file_path: &str) {
+fn optimize_circuit(file_path: &str, flags: &cli::Flags) {
+    let objective = match flags.value("objective") {
+        Some(name) => optimizer::Objective::parse(name).unwrap_or_else(|| {
+            eprintln!("Error: unknown objective '{}'", name);
+            process::exit(1);
+        }),
+        None => optimizer::Objective::GateCount,
+    };
+    let noise = match flags.value("noise") {
+        Some(path) => noise::load_noise_model(path).unwrap_or_else(|e| {
+            eprintln!("Error loading noise model: {}", e);
+            process::exit(1);
+        }),
+        None => noise::NoiseModel::default(),
+    };
+    let rules = match flags.value("rules") {
+        Some(path) => templates::load_rules(path).unwrap_or_else(|e| {
+            eprintln!("Error loading rewrite rules: {}", e);
+            process::exit(1);
+        }),
+        None => Vec::new(),
+    };
+
     match qsim::load_circuit(file_path) {
         Ok(circuit) => {
-            println!("Original circuit: {} gates", circuit.gates.len());
-            let optimized = qsim::optimize(circuit);
-            println!("Optimized circuit: {} gates", optimized.gates.len());
-            println!("Reduction: {}%", 
-                     ((circuit.gates.len() - optimized.gates.len()) * 100) / circuit.gates.len());
+            let before = optimizer::CircuitMetrics::compute(&circuit, &noise);
+            outln!("Original circuit: {} gates", before.gates);
+            let optimized = match flags.value("remote") {
+                Some(url) => api_client(url).optimize(&circuit, objective, &noise, &rules).unwrap_or_else(|e| {
+                    eprintln!("Error from remote server: {}", e);
+                    process::exit(1);
+                }),
+                None => optimizer::optimize_for(circuit, objective, &noise, &rules),
+            };
+            let after = optimizer::CircuitMetrics::compute(&optimized, &noise);
+            outln!("Optimized circuit: {} gates", after.gates);
+            outln!("Reduction: {}%",
+                     ((before.gates as i64 - after.gates as i64) * 100) / before.gates.max(1) as i64);
+            cli::display_optimization_report(&before, &after);
+        }
+        Err(e) => {
+            eprintln!("Error loading circuit: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Report lint diagnostics, and with --fix show the safe rewrites as a diff before saving them.
+/// Exits with status 1 when the circuit has errors, so it can gate CI.
+fn lint_circuit(file_path: &str, flags: &cli::Flags) {
+    let max_depth = match flags.value("max-depth") {
+        Some(limit) => limit.parse::<usize>().unwrap_or_else(|_| {
+            eprintln!("Error: --max-depth must be a non-negative integer");
+            process::exit(1);
+        }),
+        None => lint::DEFAULT_MAX_DEPTH,
+    };
+    let circuit = qsim::load_circuit_unchecked(file_path).unwrap_or_else(|e| {
+        eprintln!("Error loading circuit: {}", e);
+        process::exit(1);
+    });
+    let diagnostics = lint::lint(&circuit, max_depth);
+    cli::display_diagnostics(file_path, &diagnostics);
+    let errors = diagnostics.iter().any(|d| d.severity == lint::Severity::Error);
+
+    if flags.has("fix") {
+        if errors {
+            eprintln!("Error: fix the errors above before applying rewrites");
+            process::exit(1);
+        }
+        let (fixed, edits) = lint::fix(&circuit);
+        cli::display_edits(&edits);
+        if edits.is_empty() || flags.has("dry-run") {
+            return;
+        }
+        let output_path = flags.value("output").unwrap_or(file_path);
+        if let Err(e) = qsim::save_circuit(&fixed, output_path) {
+            eprintln!("Error writing circuit: {}", e);
+            process::exit(1);
+        }
+        outln!("Wrote {} ({} -> {} gates)", output_path, circuit.gates.len(), fixed.gates.len());
+    } else if errors {
+        process::exit(1);
+    }
+}
+
+/// Convert a circuit between file formats
+fn convert_circuit(input_path: &str, output_path: &str) {
+    match qsim::load_circuit(input_path) {
+        Ok(circuit) => {
+            if let Err(e) = qsim::save_circuit(&circuit, output_path) {
+                eprintln!("Error writing circuit: {}", e);
+                process::exit(1);
+            }
+            outln!("Converted {} -> {} ({} qubits, {} gates)",
+                     input_path, output_path, circuit.num_qubits, circuit.gates.len());
         }
         Err(e) => {
             eprintln!("Error loading circuit: {}", e);
@@ -203,3 +1806,643 @@ fn optimize_circuit(file_path: &str) {
         }
     }
 }
+
+/// Detector error model of a Stim circuit, printed or written to `--output`
+fn export_detector_error_model(file_path: &str, flags: &cli::Flags) {
+    if qsim::CircuitFormat::from_path(file_path) != qsim::CircuitFormat::Stim {
+        eprintln!("Error: dem reads Stim circuits (.stim), which declare the detectors and observables");
+        process::exit(1);
+    }
+    let noise = flags.value("noise").map(|path| {
+        noise::load_noise_model(path).unwrap_or_else(|e| {
+            eprintln!("Error loading noise model: {}", e);
+            process::exit(1);
+        })
+    });
+    let model = std::fs::read_to_string(file_path)
+        .map_err(|e| e.to_string())
+        .and_then(|source| dem::from_stim(&source, noise.as_ref()))
+        .unwrap_or_else(|e| {
+            eprintln!("Error building detector error model: {}", e);
+            process::exit(1);
+        });
+
+    let Some(path) = flags.value("output") else {
+        print!("{}", model);
+        return;
+    };
+    if let Err(e) = std::fs::write(path, model.to_string()) {
+        eprintln!("Error writing detector error model: {}", e);
+        process::exit(1);
+    }
+    outln!("┌─ Detector error model of {}", file_path);
+    outln!("├─ Detectors:           {}", model.detector_coords.len());
+    outln!("├─ Logical observables: {}", model.num_observables);
+    outln!("├─ Error mechanisms:    {}", model.errors.len());
+    outln!("└─ Written to {}", path);
+}
+
+/// Logical error rates of a Stim circuit, swept over uniform physical error rates or at a
+/// given noise model
+fn sample_logical_errors(file_path: &str, flags: &cli::Flags) {
+    let fail = |e: String| -> ! {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    };
+    if qsim::CircuitFormat::from_path(file_path) != qsim::CircuitFormat::Stim {
+        fail("qec reads Stim circuits (.stim), which declare the detectors and observables".to_string());
+    }
+    let number = |name: &str, default: usize| match flags.value(name) {
+        Some(value) => value
+            .parse::<usize>()
+            .ok()
+            .filter(|&n| n > 0)
+            .unwrap_or_else(|| fail(format!("--{} expects a positive integer", name))),
+        None => default,
+    };
+    let shots = number("shots", 100_000);
+    let threads = number("threads", std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1));
+    let source = std::fs::read_to_string(file_path).unwrap_or_else(|e| fail(format!("reading {}: {}", file_path, e)));
+
+    outln!("┌─ Logical error rates of {}: {} shots per point, {} threads", file_path, shots, threads);
+    let start = std::time::Instant::now();
+    let mut rng = command_rng(flags);
+    let points = match (flags.value("noise"), flags.value("rates")) {
+        (Some(_), Some(_)) => fail("--noise and --rates both set the device noise; give one".to_string()),
+        (Some(path), None) => {
+            let noise = noise::load_noise_model(path).unwrap_or_else(|e| fail(format!("loading noise model: {}", e)));
+            qec::sample(&source, Some(&noise), shots, threads, &mut rng).map(|point| vec![point])
+        }
+        (None, rates) => {
+            let rates: Vec<f64> = match rates {
+                Some(list) => list
+                    .split(',')
+                    .map(|rate| rate.trim().parse::<f64>())
+                    .collect::<Result<_, _>>()
+                    .unwrap_or_else(|_| fail("--rates expects numbers separated by commas, e.g. 0.001,0.01".to_string())),
+                None => qec::DEFAULT_RATES.to_vec(),
+            };
+            qec::sweep(&source, &rates, shots, threads, &mut rng)
+        }
+    }
+    .unwrap_or_else(|e| fail(e));
+    cli::display_logical_rates(&points);
+    outln!("└─ Sampling time: {:.2?}", start.elapsed());
+
+    if let Some(path) = flags.value("output") {
+        let report = serde_json::json!({ "circuit": file_path, "points": points });
+        let written = cli::to_json_pretty(&report)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            fail(format!("writing points: {}", e));
+        }
+        outln!("Points written to {}", path);
+    }
+}
+
+/// Measure a state tomography of a few qubits and show the expectations and reconstruction
+fn run_tomography(file_path: &str, flags: &cli::Flags) {
+    let fail = |e: String| -> ! {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    };
+    let circuit = load_circuit_file(file_path, true);
+    let list = flags.value("qubits").unwrap_or_else(|| fail("tomography needs --qubits, e.g. 0,1".to_string()));
+    let qubits = qubit_list("qubits", list, circuit.num_qubits);
+    let shots = match flags.value("shots") {
+        Some(value) => value.parse::<usize>().unwrap_or_else(|_| fail("--shots expects a positive integer".to_string())),
+        None => 1000,
+    };
+    if let Some(dir) = flags.value("export-circuits") {
+        let settings = tomography::settings(&circuit, &qubits).unwrap_or_else(|e| fail(e));
+        let written = std::fs::create_dir_all(dir).map_err(|e| e.to_string()).and_then(|_| {
+            settings.iter().try_for_each(|setting| {
+                let path = std::path::Path::new(dir).join(format!("{}.json", setting.bases));
+                qsim::save_circuit(&setting.circuit, &path.to_string_lossy()).map_err(|e| e.to_string())
+            })
+        });
+        if let Err(e) = written {
+            fail(format!("writing measurement circuits: {}", e));
+        }
+        outln!("├─ {} measurement circuits written to {}", settings.len(), dir);
+    }
+
+    let prepared = tomography::prepared(&circuit);
+    let settings = 3usize.pow(qubits.len() as u32);
+    let mut rng = command_rng(flags);
+    let measured = |gates: &[qsim::QuantumGate]| {
+        let mut measured = prepared.clone();
+        measured.gates.extend_from_slice(gates);
+        measured
+    };
+    let data = match selected_backend(flags) {
+        Some(backend) => {
+            outln!("├─ Sampling {} settings with the {} backend...", settings, backend.name());
+            tomography::collect(&circuit, &qubits, shots, |gates, n| {
+                let shots = backend.sample(&measured(gates), n, rng.gen()).unwrap_or_else(|e| fail(e));
+                shots.into_iter().map(|shot| shot.outcome).collect()
+            })
+        }
+        None => {
+            outln!("├─ Sampling {} settings...", settings);
+            let mut simulator = qsim::QuantumSimulator::seeded(prepared.num_qubits, rng.gen());
+            for gate in &prepared.gates {
+                simulator.apply_gate(gate);
+            }
+            tomography::collect(&circuit, &qubits, shots, |gates, n| {
+                if prepared.has_collapse() {
+                    let shots = qsim::sample_circuit(&measured(gates), n, &mut rng);
+                    return shots.into_iter().map(|shot| shot.outcome).collect();
+                }
+                // Rotate a copy of the final state, so the circuit runs only once
+                let mut rotated = simulator.clone();
+                for gate in gates {
+                    rotated.apply_gate(gate);
+                }
+                rotated.sample(n, &mut rng)
+            })
+        }
+    }
+    .unwrap_or_else(|e| fail(e));
+
+    cli::display_tomography(&data);
+    if qubits.len() <= MAX_REDUCED_QUBITS {
+        cli::display_density_matrix(&qubits, &tomography::linear_inversion(&data));
+    }
+    if let Some(path) = flags.value("output") {
+        let written = cli::to_json_pretty(&data)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            fail(format!("writing tomography data: {}", e));
+        }
+        outln!("├─ Tomography data written to {}", path);
+    }
+    outln!("└─ {} settings x {} shots", settings, shots);
+}
+
+/// Compare the final states of two circuits or saved `.npy` states, exiting with status 1
+/// when their fidelity is below 1 - tolerance
+fn compare_states(first: &str, second: &str, flags: &cli::Flags) {
+    let tolerance = match flags.value("tolerance") {
+        Some(value) => value.parse::<f64>().ok().filter(|t| (0.0..1.0).contains(t)).unwrap_or_else(|| {
+            eprintln!("Error: --tolerance expects a number between 0 and 1");
+            process::exit(1);
+        }),
+        None => DEFAULT_EQUIV_TOLERANCE,
+    };
+    outln!("┌─ Comparing {} with {}", first, second);
+    let a = final_state(first);
+    let b = final_state(second);
+    if a.num_qubits != b.num_qubits {
+        eprintln!("Error: {} has {} qubits but {} has {}", first, a.num_qubits, second, b.num_qubits);
+        process::exit(1);
+    }
+
+    let fidelity = a.fidelity(&b).min(1.0);
+    outln!("├─ State fidelity: {:.9}", fidelity);
+    outln!("├─ Infidelity:     {:.3e}", 1.0 - fidelity);
+    // For pure states the trace distance follows from the fidelity alone
+    outln!("├─ Trace distance: {:.9}", (1.0 - fidelity).sqrt());
+    if 1.0 - fidelity <= tolerance {
+        outln!("└─ ✓ Equivalent up to global phase (tolerance {:e})", tolerance);
+    } else {
+        outln!("└─ ✗ States differ (tolerance {:e})", tolerance);
+        process::exit(1);
+    }
+}
+
+/// Compose and print the unitary of a circuit file, optionally saving it and checking it
+/// against a second circuit up to global phase
+fn show_unitary(file_path: &str, flags: &cli::Flags) {
+    let fail = |e: String| -> ! {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    };
+    let tolerance = match flags.value("tolerance") {
+        Some(value) => value
+            .parse::<f64>()
+            .ok()
+            .filter(|t| (0.0..1.0).contains(t))
+            .unwrap_or_else(|| fail("--tolerance expects a number between 0 and 1".to_string())),
+        None => DEFAULT_EQUIV_TOLERANCE,
+    };
+    let circuit = load_circuit_file(file_path, true);
+    let matrix = unitary::circuit_unitary(&circuit).unwrap_or_else(|e| fail(e));
+    cli::display_unitary(&matrix, circuit.num_qubits, MAX_PRINTED_UNITARY_QUBITS);
+
+    if let Some(path) = flags.value("output") {
+        let written = if path.ends_with(".npy") {
+            npy::save_matrix(path, &matrix).map_err(|e| e.to_string())
+        } else {
+            cli::to_json(&matrix).map_err(|e| e.to_string()).and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string()))
+        };
+        if let Err(e) = written {
+            fail(format!("writing {}: {}", path, e));
+        }
+        outln!("├─ Unitary saved to {}", path);
+    }
+
+    let Some(other_path) = flags.value("compare") else {
+        outln!("└─ Done");
+        return;
+    };
+    let other = qsim::load_circuit(other_path).unwrap_or_else(|e| fail(format!("loading circuit {}: {}", other_path, e)));
+    if other.num_qubits != circuit.num_qubits {
+        fail(format!("{} has {} qubits but {} has {}", file_path, circuit.num_qubits, other_path, other.num_qubits));
+    }
+    let other_matrix = unitary::circuit_unitary(&other).unwrap_or_else(|e| fail(format!("{}: {}", other_path, e)));
+    let distance = unitary::distance(&matrix, &other_matrix);
+    outln!("├─ Compared with {}", other_path);
+    outln!("├─ Process fidelity: {:.9}", distance.process_fidelity);
+    outln!("├─ Max deviation:    {:.3e}", distance.max_deviation);
+    if 1.0 - distance.process_fidelity <= tolerance {
+        outln!("└─ ✓ Same unitary up to global phase (tolerance {:e})", tolerance);
+    } else {
+        outln!("└─ ✗ Unitaries differ (tolerance {:e})", tolerance);
+        process::exit(1);
+    }
+}
+
+/// State of a saved `.npy` vector, or of a circuit file after simulation, exiting on failure
+fn final_state(path: &str) -> qsim::QuantumSimulator {
+    if path.ends_with(".npy") {
+        let simulator = npy::load_state(std::path::Path::new(path))
+            .map_err(|e| e.to_string())
+            .and_then(qsim::QuantumSimulator::from_state)
+            .unwrap_or_else(|e| {
+                eprintln!("Error loading state from {}: {}", path, e);
+                process::exit(1);
+            });
+        outln!("├─ {}: saved state, {} qubits", path, simulator.num_qubits);
+        return simulator;
+    }
+    let circuit = qsim::load_circuit(path).unwrap_or_else(|e| {
+        eprintln!("Error loading circuit {}: {}", path, e);
+        process::exit(1);
+    });
+    outln!("├─ {}: {} qubits, {} gates", path, circuit.num_qubits, circuit.gates.len());
+    if circuit.has_collapse() {
+        outln!("│  Circuit collapses qubits mid-circuit: comparing one random trajectory");
+    }
+    let mut simulator = qsim::QuantumSimulator::new(circuit.num_qubits);
+    for gate in &circuit.gates {
+        simulator.apply_gate(gate);
+    }
+    simulator
+}
+
+/// Client for a server URL, exiting on a malformed URL
+fn api_client(url: &str) -> client::ApiClient {
+    client::ApiClient::new(url).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    })
+}
+
+/// List the bundled examples with their summaries
+fn list_examples() {
+    outln!("┌─ Examples (run with `quantummesh examples run <name>`, or `all`)");
+    for example in examples::EXAMPLES {
+        outln!("├─ {:<16} {}", example.name, example.summary);
+    }
+    outln!("└─ {} examples", examples::EXAMPLES.len());
+}
+
+/// Run one bundled example, or every one for `all`, exiting with status 1 if any check fails
+fn run_examples(name: &str, flags: &cli::Flags) {
+    let selected: Vec<&examples::Example> = match name {
+        "all" => examples::EXAMPLES.iter().collect(),
+        name => vec![examples::find(name).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        })],
+    };
+    let mut rng = command_rng(flags);
+    let mut failed = Vec::new();
+    for example in selected {
+        let report = example.run(&mut rng).unwrap_or_else(|e| {
+            eprintln!("Error: example {}: {}", example.name, e);
+            process::exit(1);
+        });
+        cli::display_example(example, &report);
+        if !report.passed() {
+            failed.push(example.name);
+        }
+    }
+    if !failed.is_empty() {
+        eprintln!("Error: checks failed in {}", failed.join(", "));
+        process::exit(1);
+    }
+}
+
+/// Report a server's API version and whether this client can use it
+fn show_server_version(url: &str) {
+    let version = api_client(url).api_version().unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    });
+    match version.accepts(api_version::API_VERSION) {
+        Ok(()) => outln!("Server {}: API v{}, compatible", url, version),
+        Err(e) => {
+            outln!("Server {}: API v{}, incompatible: {}", url, version, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Ask a server for its view of the cluster and render it
+fn show_cluster(url: &str) {
+    let status = api_client(url)
+        .get_json("/api/cluster")
+        .and_then(|value| Ok(serde_json::from_value::<cluster::ClusterStatus>(value)?))
+        .unwrap_or_else(|e| {
+            eprintln!("Error reading cluster status: {}", e);
+            process::exit(1);
+        });
+    cli::display_cluster(url, &status);
+}
+
+/// Download a server's workspace into a single archive file
+fn export_workspace(archive_path: &str, url: &str) {
+    let bundle = api_client(url).export_workspace().unwrap_or_else(|e| {
+        eprintln!("Error exporting workspace: {}", e);
+        process::exit(1);
+    });
+    if let Err(e) = workspace::save_workspace(&bundle, archive_path) {
+        eprintln!("Error writing archive: {}", e);
+        process::exit(1);
+    }
+    outln!("Exported {} from {} -> {}", bundle.summary(), url, archive_path);
+}
+
+/// Upload an archive into a server, optionally restoring its config files locally
+fn import_workspace(archive_path: &str, url: &str, write_configs: bool) {
+    let bundle = workspace::load_workspace(archive_path).unwrap_or_else(|e| {
+        eprintln!("Error reading archive: {}", e);
+        process::exit(1);
+    });
+    let imported = api_client(url).import_workspace(&bundle).unwrap_or_else(|e| {
+        eprintln!("Error importing workspace: {}", e);
+        process::exit(1);
+    });
+
+    outln!("┌─ Imported {} into {}", bundle.summary(), url);
+    for kind in ["circuits", "results"] {
+        if let Some(ids) = imported[kind].as_object() {
+            for (old, new) in ids {
+                outln!("├─ {} -> {}", old, new.as_str().unwrap_or_default());
+            }
+        }
+    }
+    if write_configs {
+        for (name, contents) in &bundle.configs {
+            // Only bare file names are written, so an archive cannot escape the working directory
+            if name.contains(['/', '\\']) || name.starts_with('.') {
+                eprintln!("Warning: skipping config with unsafe name '{}'", name);
+            } else if std::path::Path::new(name).exists() {
+                eprintln!("Warning: {} already exists, not overwriting", name);
+            } else if let Err(e) = std::fs::write(name, contents) {
+                eprintln!("Error writing {}: {}", name, e);
+                process::exit(1);
+            } else {
+                outln!("├─ wrote {}", name);
+            }
+        }
+    }
+    outln!("└─ Done");
+}
+
+/// Render one saved results file, or compare two, without re-simulating
+fn show_results(file_path: &str, other_path: Option<&str>, flags: &cli::Flags) {
+    let top = match flags.value("top") {
+        Some(value) => value.parse::<usize>().unwrap_or_else(|_| {
+            eprintln!("Error: --top expects a positive integer");
+            process::exit(1);
+        }),
+        None => 16,
+    };
+    let width = flags.value("width").map(|value| {
+        value.parse::<usize>().unwrap_or_else(|_| {
+            eprintln!("Error: --width expects a number of classical bits");
+            process::exit(1);
+        })
+    });
+    let load = |path: &str| {
+        let loaded = if path.ends_with(".b8") {
+            match width {
+                Some(width) => results::load_records(path, width),
+                None => {
+                    eprintln!("Error: {} is a bit-packed records file; give its --width in bits", path);
+                    process::exit(1);
+                }
+            }
+        } else if path.ends_with(".pb") {
+            results::load_protobuf_results(path)
+        } else {
+            results::load_results(path)
+        };
+        loaded.unwrap_or_else(|e| {
+            eprintln!("Error loading results from {}: {}", path, e);
+            process::exit(1);
+        })
+    };
+
+    let results = load(file_path);
+    if flags.has("verify-env") {
+        let current = manifest::Manifest::current();
+        let mut matches = cli::verify_environment(file_path, &results, &current);
+        if let Some(other_path) = other_path {
+            matches &= cli::verify_environment(other_path, &load(other_path), &current);
+        }
+        if !matches {
+            process::exit(1);
+        }
+        return;
+    }
+    match other_path {
+        Some(other_path) => {
+            let other = load(other_path);
+            if other.num_qubits != results.num_qubits {
+                eprintln!("Error: cannot compare {} qubits with {} qubits", results.num_qubits, other.num_qubits);
+                process::exit(1);
+            }
+            cli::show_results(file_path, &results, top);
+            cli::show_results(other_path, &other, top);
+            cli::compare_results([file_path, other_path], [&results, &other], top);
+        }
+        None => cli::show_results(file_path, &results, top),
+    }
+}
+
+/// Write a run as a results file for `show`, exiting on failure
+fn save_results(path: &str, run: &archive::ArchiveRun) {
+    if let Err(e) = results::save_results(path, &results::SavedResults::from(run)) {
+        eprintln!("Error saving results: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Mean half-chain entanglement entropy of monitored random circuits across measurement rates
+fn run_monitored_sweep(qubits: usize, flags: &cli::Flags) {
+    let fail = |e: String| -> ! {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    };
+    let number = |name: &str, default: usize| match flags.value(name) {
+        Some(value) => value.parse::<usize>().unwrap_or_else(|_| fail(format!("--{} expects a positive integer", name))),
+        None => default,
+    };
+    let rates: Vec<f64> = match flags.value("rates") {
+        Some(list) => list
+            .split(',')
+            .map(|rate| rate.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|_| fail("--rates expects numbers separated by commas, e.g. 0,0.1,0.2".to_string())),
+        None => monitored::DEFAULT_RATES.to_vec(),
+    };
+    let order = match flags.value("renyi") {
+        Some(value) => value
+            .parse::<f64>()
+            .ok()
+            .filter(|order| order.is_finite() && *order >= 0.0)
+            .unwrap_or_else(|| fail("--renyi expects a non-negative number".to_string())),
+        None => 1.0,
+    };
+    let noise = flags.value("noise").map(|path| {
+        noise::load_noise_model(path).unwrap_or_else(|e| fail(format!("loading noise model: {}", e)))
+    });
+    let config = monitored::MonitoredConfig {
+        num_qubits: qubits,
+        depth: number("depth", 2 * qubits),
+        trajectories: number("trajectories", 100),
+        order,
+    };
+
+    outln!(
+        "┌─ Monitored circuits: {} qubits, depth {}, {} trajectories per rate",
+        config.num_qubits, config.depth, config.trajectories
+    );
+    let start = std::time::Instant::now();
+    let points = monitored::sweep(&config, &rates, noise.as_ref(), &mut command_rng(flags)).unwrap_or_else(|e| fail(e));
+    cli::display_monitored_sweep(&config, &points);
+    outln!("└─ Sweep time: {:.2?}", start.elapsed());
+
+    if let Some(path) = flags.value("output") {
+        let report = serde_json::json!({
+            "num_qubits": config.num_qubits,
+            "depth": config.depth,
+            "trajectories": config.trajectories,
+            "order": config.order,
+            "points": points,
+        });
+        let written = cli::to_json_pretty(&report)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            fail(format!("writing sweep: {}", e));
+        }
+        outln!("Sweep written to {}", path);
+    }
+}
+
+/// Report how single Pauli faults change a circuit's measured outputs
+/// Gate metrics of a circuit and, with `--fault-tolerant`, its surface code resources
+fn analyze_circuit(file_path: &str, flags: &cli::Flags) {
+    let fail = |e: String| -> ! {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    };
+    let circuit = qsim::load_circuit(file_path).unwrap_or_else(|e| fail(format!("loading circuit: {}", e)));
+    let noise = match flags.value("noise") {
+        Some(path) => noise::load_noise_model(path).unwrap_or_else(|e| fail(format!("loading noise model: {}", e))),
+        None => noise::NoiseModel::default(),
+    };
+    let float = |name: &str, default: f64| match flags.value(name) {
+        Some(value) => value.parse::<f64>().unwrap_or_else(|_| fail(format!("--{} expects a number", name))),
+        None => default,
+    };
+    let count = |name: &str| {
+        flags
+            .value(name)
+            .map(|value| value.parse::<usize>().unwrap_or_else(|_| fail(format!("--{} expects a positive integer", name))))
+    };
+
+    outln!("┌─ Analyzing {}: {} qubits, {} gates", file_path, circuit.num_qubits, circuit.gates.len());
+    cli::display_circuit_metrics(&optimizer::CircuitMetrics::compute(&circuit, &noise));
+    if flags.has("fault-tolerant") {
+        let defaults = ftqc::FaultTolerantModel::default();
+        let model = ftqc::FaultTolerantModel {
+            physical_error: float("physical-error", defaults.physical_error),
+            cycle_time_us: float("cycle-time", defaults.cycle_time_us),
+            distance: count("distance"),
+            target_error: float("target-error", defaults.target_error),
+            factories: count("factories"),
+        };
+        let estimate = ftqc::estimate(&circuit, &model).unwrap_or_else(|e| fail(e));
+        cli::display_resource_estimate(&model, &estimate);
+        if let Some(path) = flags.value("output") {
+            let written = cli::to_json_pretty(&estimate)
+                .map_err(|e| e.to_string())
+                .and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string()));
+            if let Err(e) = written {
+                fail(format!("writing estimate: {}", e));
+            }
+            outln!("├─ Estimate written to {}", path);
+        }
+    }
+    outln!("└─ Analysis complete");
+}
+
+fn analyze_faults(file_path: &str, flags: &cli::Flags) {
+    let circuit = qsim::load_circuit(file_path).unwrap_or_else(|e| {
+        eprintln!("Error loading circuit: {}", e);
+        process::exit(1);
+    });
+    let fail = |e: String| -> ! {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    };
+
+    let effects = match flags.value("inject") {
+        Some(spec) => {
+            let parts: Vec<&str> = spec.split(':').collect();
+            let location = match parts.as_slice() {
+                [gate, qubit, pauli] => gate
+                    .parse::<usize>()
+                    .ok()
+                    .zip(qubit.parse::<usize>().ok())
+                    .zip(faults::Pauli::parse(pauli)),
+                _ => None,
+            };
+            let ((gate, qubit), pauli) = location
+                .unwrap_or_else(|| fail(format!("--inject expects <gate>:<qubit>:<X|Y|Z>, got '{}'", spec)));
+            vec![faults::fault_effect(&circuit, gate, qubit, pauli).unwrap_or_else(|e| fail(e))]
+        }
+        None => {
+            let paulis: Vec<faults::Pauli> = match flags.value("paulis") {
+                Some(paulis) => paulis
+                    .chars()
+                    .map(|c| faults::Pauli::parse(&c.to_string()).unwrap_or_else(|| fail(format!("unknown Pauli '{}'", c))))
+                    .collect(),
+                None => faults::Pauli::ALL.to_vec(),
+            };
+            faults::sensitivity_map(&circuit, &paulis)
+        }
+    };
+
+    if let Some(path) = flags.value("output") {
+        let written = cli::to_json_pretty(&effects)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            eprintln!("Error writing sensitivity map: {}", e);
+            process::exit(1);
+        }
+    }
+    cli::display_fault_effects(&circuit, &effects);
+    if let Some(path) = flags.value("output") {
+        outln!("Sensitivity map written to {}", path);
+    }
+}
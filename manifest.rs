@@ -0,0 +1,127 @@
+//! Manifest Module
+//! Build, backend and hardware details embedded in results so a run can be reproduced or attributed
+
+use std::fs;
+use serde::{Deserialize, Serialize};
+use crate::gpu_ops::GpuDevice;
+
+/// Where and how a result was produced
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: String,
+    /// Commit the binary was built from, from `QUANTUMMESH_GIT_COMMIT` at build time
+    pub git_commit: String,
+    pub backend: String,
+    /// RNG seed, when the run was seeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    pub threads: usize,
+    pub hardware: Hardware,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hardware {
+    pub os: String,
+    pub arch: String,
+    pub cpu: String,
+    pub memory_mb: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpu: Option<String>,
+}
+
+/// A manifest field whose recorded value differs from this machine
+pub struct Mismatch {
+    pub field: &'static str,
+    pub recorded: String,
+    pub current: String,
+}
+
+impl Manifest {
+    /// Manifest of this binary on this machine
+    pub fn current() -> Self {
+        let device = GpuDevice::new();
+        let gpu = device.is_available().then(|| device.to_string());
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: option_env!("QUANTUMMESH_GIT_COMMIT").unwrap_or("unknown").to_string(),
+            backend: if gpu.is_some() { "gpu" } else { "cpu" }.to_string(),
+            seed: None,
+            threads: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            hardware: Hardware {
+                os: std::env::consts::OS.to_string(),
+                arch: std::env::consts::ARCH.to_string(),
+                cpu: cpu_model().unwrap_or_else(|| "unknown".to_string()),
+                memory_mb: memory_mb().unwrap_or(0),
+                gpu,
+            },
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Fields as (name, value) pairs, for flat stores such as HDF5 attributes
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let mut entries = vec![
+            ("version", self.version.clone()),
+            ("git_commit", self.git_commit.clone()),
+            ("backend", self.backend.clone()),
+            ("threads", self.threads.to_string()),
+            ("os", self.hardware.os.clone()),
+            ("arch", self.hardware.arch.clone()),
+            ("cpu", self.hardware.cpu.clone()),
+            ("memory_mb", self.hardware.memory_mb.to_string()),
+        ];
+        if let Some(seed) = self.seed {
+            entries.insert(3, ("seed", seed.to_string()));
+        }
+        if let Some(gpu) = &self.hardware.gpu {
+            entries.push(("gpu", gpu.clone()));
+        }
+        entries
+    }
+
+    /// Environment fields that differ from `current`; the seed belongs to the run, not the
+    /// environment, so it is not compared
+    pub fn differences(&self, current: &Manifest) -> Vec<Mismatch> {
+        let environment = |manifest: &Manifest| {
+            let mut entries = manifest.entries();
+            entries.retain(|(field, _)| *field != "seed");
+            if manifest.hardware.gpu.is_none() {
+                entries.push(("gpu", "none".to_string()));
+            }
+            entries
+        };
+        environment(self)
+            .into_iter()
+            .zip(environment(current))
+            .filter(|((_, recorded), (_, now))| recorded != now)
+            .map(|((field, recorded), (_, current))| Mismatch { field, recorded, current })
+            .collect()
+    }
+}
+
+/// CPU model name from /proc/cpuinfo (Linux only)
+fn cpu_model() -> Option<String> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo
+        .lines()
+        .find(|line| line.starts_with("model name"))
+        .and_then(|line| line.split_once(':'))
+        .map(|(_, name)| name.trim().to_string())
+}
+
+/// Total memory from /proc/meminfo (Linux only)
+fn memory_mb() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let kb = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemTotal:"))?
+        .split_whitespace()
+        .nth(1)?
+        .parse::<u64>()
+        .ok()?;
+    Some(kb / 1024)
+}
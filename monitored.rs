@@ -0,0 +1,127 @@
+//! Monitored Circuits Module
+//! Random circuits with mid-circuit measurements, for measurement-induced phase transitions
+
+use rand::Rng;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use crate::entropy;
+use crate::gpu_ops::Complex;
+use crate::noise::NoiseModel;
+use crate::qsim::{QuantumCircuit, QuantumGate, QuantumSimulator};
+
+/// Measurement rates swept when none are given
+pub const DEFAULT_RATES: &[f64] = &[0.0, 0.05, 0.1, 0.15, 0.2, 0.25, 0.3, 0.4, 0.5];
+
+/// Shape of the monitored circuits in a sweep
+pub struct MonitoredConfig {
+    pub num_qubits: usize,
+    /// Layers of two-qubit gates, each followed by a round of measurements
+    pub depth: usize,
+    /// Independent circuits sampled per measurement rate
+    pub trajectories: usize,
+    /// Rényi order of the reported entropy, 1 for von Neumann
+    pub order: f64,
+}
+
+/// Trajectory statistics at one measurement rate
+#[derive(Debug, Clone, Serialize)]
+pub struct RatePoint {
+    pub rate: f64,
+    /// Half-chain entanglement entropy in bits, averaged over trajectories
+    pub mean_entropy: f64,
+    pub std_error: f64,
+    pub mean_measurements: f64,
+    /// Mean estimated circuit fidelity under a noise model, when one is given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_fidelity: Option<f64>,
+}
+
+/// Brick-pattern circuit of Haar-random two-qubit gates on neighbouring qubits. After each
+/// layer every qubit is measured with probability `rate`.
+pub fn create_monitored_circuit<R: Rng>(num_qubits: usize, depth: usize, rate: f64, rng: &mut R) -> QuantumCircuit {
+    let mut gates = Vec::new();
+    for layer in 0..depth {
+        for first in (layer % 2..num_qubits.saturating_sub(1)).step_by(2) {
+            gates.push(QuantumGate::UnitaryNQ { qubits: vec![first, first + 1], matrix: haar_unitary(4, rng) });
+        }
+        for qubit in 0..num_qubits {
+            if rng.gen_bool(rate) {
+                gates.push(QuantumGate::Measurement { qubit, bit: None });
+            }
+        }
+    }
+    let mut metadata = BTreeMap::new();
+    metadata.insert("name".to_string(), format!("monitored-{}x{}-p{}", num_qubits, depth, rate));
+    QuantumCircuit { num_qubits, gates, metadata }
+}
+
+/// Haar-random unitary: Gram-Schmidt on rows of complex Gaussians
+pub fn haar_unitary<R: Rng>(dimension: usize, rng: &mut R) -> Vec<Vec<Complex>> {
+    let mut rows: Vec<Vec<Complex>> = Vec::with_capacity(dimension);
+    for _ in 0..dimension {
+        let mut row: Vec<Complex> = (0..dimension).map(|_| Complex::new(gaussian(rng), gaussian(rng))).collect();
+        for previous in &rows {
+            let overlap = previous.iter().zip(&row).fold(Complex::new(0.0, 0.0), |sum, (p, r)| sum + p.conjugate() * *r);
+            for (element, p) in row.iter_mut().zip(previous) {
+                *element = *element + Complex::new(-overlap.re, -overlap.im) * *p;
+            }
+        }
+        let norm = row.iter().map(|element| element.magnitude_squared()).sum::<f64>().sqrt();
+        rows.push(row.into_iter().map(|element| Complex::new(element.re / norm, element.im / norm)).collect());
+    }
+    rows
+}
+
+/// Standard normal sample by the Box-Muller transform
+pub fn gaussian<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    let radius = (-2.0 * (1.0 - rng.gen::<f64>()).ln()).sqrt();
+    radius * (std::f64::consts::TAU * rng.gen::<f64>()).cos()
+}
+
+/// Mean entanglement entropy across the middle of the chain at each measurement rate. Every
+/// trajectory draws a fresh circuit and its own measurement outcomes.
+pub fn sweep<R: Rng>(
+    config: &MonitoredConfig,
+    rates: &[f64],
+    noise: Option<&NoiseModel>,
+    rng: &mut R,
+) -> Result<Vec<RatePoint>, String> {
+    if config.trajectories == 0 {
+        return Err("at least one trajectory is needed per rate".to_string());
+    }
+    if let Some(rate) = rates.iter().find(|rate| !(0.0..=1.0).contains(*rate)) {
+        return Err(format!("measurement rate {} is not between 0 and 1", rate));
+    }
+    let half: Vec<usize> = (0..config.num_qubits / 2).collect();
+    let mut points = Vec::with_capacity(rates.len());
+    for &rate in rates {
+        let mut entropies = Vec::with_capacity(config.trajectories);
+        let mut measurements = 0;
+        let mut fidelity = 0.0;
+        for _ in 0..config.trajectories {
+            let circuit = create_monitored_circuit(config.num_qubits, config.depth, rate, rng);
+            measurements += circuit.gates.iter().filter(|gate| matches!(gate, QuantumGate::Measurement { .. })).count();
+            if let Some(noise) = noise {
+                fidelity += noise.estimate_fidelity(&circuit);
+            }
+            let mut simulator = QuantumSimulator::seeded(config.num_qubits, rng.gen());
+            for gate in &circuit.gates {
+                simulator.apply_gate(gate);
+            }
+            simulator.collapse_pending();
+            let spectrum = entropy::entanglement_spectrum(&simulator, &half)?;
+            entropies.push(entropy::renyi_entropy(&spectrum, config.order));
+        }
+        let count = entropies.len() as f64;
+        let mean = entropies.iter().sum::<f64>() / count;
+        let variance = entropies.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (count - 1.0).max(1.0);
+        points.push(RatePoint {
+            rate,
+            mean_entropy: mean,
+            std_error: (variance / count).sqrt(),
+            mean_measurements: measurements as f64 / count,
+            mean_fidelity: noise.map(|_| fidelity / count),
+        });
+    }
+    Ok(points)
+}
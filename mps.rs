@@ -0,0 +1,718 @@
+//! MPS Module
+//! Matrix product state backend for shallow, weakly entangled circuits on many qubits
+
+use std::borrow::Cow;
+use std::sync::Arc;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use crate::backends::StateBackend;
+use crate::gpu_ops::Complex;
+use crate::qsim::{self, QuantumCircuit, QuantumGate, Shot};
+use crate::simulator_backend::{self, SimulatorBackend};
+
+/// Bond dimension kept when `--max-bond` is not given
+pub const DEFAULT_MAX_BOND: usize = 64;
+
+/// Widest circuit whose full probability vector the backend builds; wider circuits are
+/// sampled shot by shot from the tensors
+pub const MAX_DENSE_QUBITS: usize = 24;
+
+/// Most qubits a single gate may span; it is contracted into one 2^k x 2^k block
+const MAX_GATE_QUBITS: usize = 8;
+
+/// Singular values below this fraction of the largest are dropped even under the bond limit
+const SVD_CUTOFF: f64 = 1e-12;
+
+/// Jacobi sweeps before the SVD settles for what it has
+const MAX_SWEEPS: usize = 60;
+
+const ZERO: Complex = Complex { re: 0.0, im: 0.0 };
+const ONE: Complex = Complex { re: 1.0, im: 0.0 };
+
+fn scale(c: Complex, factor: f64) -> Complex {
+    Complex::new(c.re * factor, c.im * factor)
+}
+
+/// Row-major product of an n x m and an m x p matrix
+fn matmul(a: &[Complex], b: &[Complex], n: usize, m: usize, p: usize) -> Vec<Complex> {
+    let mut product = vec![ZERO; n * p];
+    for i in 0..n {
+        for k in 0..m {
+            let x = a[i * m + k];
+            if x == ZERO {
+                continue;
+            }
+            for j in 0..p {
+                product[i * p + j] = product[i * p + j] + x * b[k * p + j];
+            }
+        }
+    }
+    product
+}
+
+/// Conjugate transpose of a rows x cols matrix
+fn adjoint(matrix: &[Complex], rows: usize, cols: usize) -> Vec<Complex> {
+    let mut result = vec![ZERO; rows * cols];
+    for i in 0..rows {
+        for j in 0..cols {
+            result[j * rows + i] = matrix[i * cols + j].conjugate();
+        }
+    }
+    result
+}
+
+/// Thin SVD of a row-major rows x cols matrix by one-sided Jacobi rotations, as
+/// (U rows x r, singular values, V† r x cols) with r = min(rows, cols), largest value first
+fn svd(matrix: &[Complex], rows: usize, cols: usize) -> (Vec<Complex>, Vec<f64>, Vec<Complex>) {
+    if rows < cols {
+        // M† = U' Σ V'†, so M = V' Σ U'†
+        let (u, sigma, vh) = svd(&adjoint(matrix, rows, cols), cols, rows);
+        return (adjoint(&vh, rows, rows), sigma, adjoint(&u, cols, rows));
+    }
+    // Work on the matrix scaled to a largest entry of one, so squared norms and overlaps of
+    // tiny blocks stay clear of underflow
+    let largest = matrix.iter().fold(0.0f64, |m, x| m.max(x.re.abs()).max(x.im.abs()));
+    let unit = if largest > 0.0 { 1.0 / largest } else { 1.0 };
+    let mut a: Vec<Complex> = matrix.iter().map(|&x| scale(x, unit)).collect();
+    let mut v = vec![ZERO; cols * cols];
+    for j in 0..cols {
+        v[j * cols + j] = ONE;
+    }
+    // Columns whose squared norm is this small next to the whole matrix are rounding noise,
+    // not worth a rotation
+    let negligible = f64::EPSILON * f64::EPSILON * a.iter().map(|x| x.magnitude_squared()).sum::<f64>();
+    // Rotate column pairs until every pair is orthogonal; the column norms are then the
+    // singular values and the accumulated rotations V
+    for _ in 0..MAX_SWEEPS {
+        let mut rotated = false;
+        for p in 0..cols {
+            for q in p + 1..cols {
+                let (mut alpha, mut beta, mut gamma) = (0.0, 0.0, ZERO);
+                for i in 0..rows {
+                    let (x, y) = (a[i * cols + p], a[i * cols + q]);
+                    alpha += x.magnitude_squared();
+                    beta += y.magnitude_squared();
+                    gamma = gamma + x.conjugate() * y;
+                }
+                let g = gamma.re.hypot(gamma.im);
+                if alpha <= negligible || beta <= negligible || g == 0.0 || g <= 1e-15 * (alpha * beta).sqrt() {
+                    continue;
+                }
+                rotated = true;
+                // Phase column q so the overlap is real, then a real Jacobi rotation
+                let phase = Complex::new(gamma.re / g, -gamma.im / g);
+                let zeta = (beta - alpha) / (2.0 * g);
+                let t = zeta.signum() / (zeta.abs() + (1.0 + zeta * zeta).sqrt());
+                let c = 1.0 / (1.0 + t * t).sqrt();
+                let s = c * t;
+                for (data, stride, len) in [(&mut a, cols, rows), (&mut v, cols, cols)] {
+                    for i in 0..len {
+                        let x = data[i * stride + p];
+                        let y = data[i * stride + q] * phase;
+                        data[i * stride + p] = scale(x, c) + scale(y, -s);
+                        data[i * stride + q] = scale(x, s) + scale(y, c);
+                    }
+                }
+            }
+        }
+        if !rotated {
+            break;
+        }
+    }
+
+    let norms: Vec<f64> =
+        (0..cols).map(|j| (0..rows).map(|i| a[i * cols + j].magnitude_squared()).sum::<f64>().sqrt()).collect();
+    let mut order: Vec<usize> = (0..cols).collect();
+    order.sort_by(|&x, &y| norms[y].total_cmp(&norms[x]));
+    let mut u = vec![ZERO; rows * cols];
+    let mut vh = vec![ZERO; cols * cols];
+    for (k, &j) in order.iter().enumerate() {
+        if norms[j] > 0.0 {
+            for i in 0..rows {
+                u[i * cols + k] = scale(a[i * cols + j], 1.0 / norms[j]);
+            }
+        }
+        for c in 0..cols {
+            vh[k * cols + c] = v[c * cols + j].conjugate();
+        }
+    }
+    (u, order.iter().map(|&j| norms[j] / unit).collect(), vh)
+}
+
+/// SVD keeping at most `max_bond` singular values, renormalized so the state keeps unit norm
+fn truncated_svd(matrix: &[Complex], rows: usize, cols: usize, max_bond: usize) -> (Vec<Complex>, Vec<f64>, Vec<Complex>) {
+    let (u, mut sigma, vh) = svd(matrix, rows, cols);
+    let rank = sigma.len();
+    let largest = sigma.first().copied().unwrap_or(0.0);
+    let keep = sigma.iter().take(max_bond).take_while(|&&s| s > SVD_CUTOFF * largest).count().max(1);
+    let total: f64 = sigma.iter().map(|s| s * s).sum();
+    let kept: f64 = sigma[..keep].iter().map(|s| s * s).sum();
+    if kept > 0.0 {
+        let factor = (total / kept).sqrt();
+        for s in &mut sigma[..keep] {
+            *s *= factor;
+        }
+    }
+    sigma.truncate(keep);
+    let u = (0..rows).flat_map(|i| u[i * rank..i * rank + keep].iter().copied()).collect();
+    (u, sigma, vh[..keep * cols].to_vec())
+}
+
+/// One tensor A[left][bit][right] of the chain, stored row-major
+#[derive(Debug, Clone)]
+struct Site {
+    left: usize,
+    right: usize,
+    data: Vec<Complex>,
+}
+
+/// A state of n qubits as a chain of rank-3 tensors, one per site, with bonds capped at
+/// `max_bond`. Entanglement beyond the cap is truncated away, which is what makes shallow
+/// circuits on 50-100 qubits tractable.
+#[derive(Debug, Clone)]
+pub struct MpsState {
+    sites: Vec<Site>,
+    /// Site holding each logical qubit; SWAPs relabel it, as the state vector's layout does
+    site_of: Vec<usize>,
+    /// Logical qubit at each site
+    qubit_at: Vec<usize>,
+    /// Orthogonality center: sites left of it are left-orthonormal, sites right of it
+    /// right-orthonormal, so truncating at the center is optimal
+    center: usize,
+    max_bond: usize,
+}
+
+impl MpsState {
+    /// |0...0> on `num_qubits` qubits
+    pub fn new(num_qubits: usize, max_bond: usize) -> Self {
+        Self {
+            sites: vec![Site { left: 1, right: 1, data: vec![ONE, ZERO] }; num_qubits],
+            site_of: (0..num_qubits).collect(),
+            qubit_at: (0..num_qubits).collect(),
+            center: 0,
+            max_bond: max_bond.max(1),
+        }
+    }
+
+    /// Apply a 2x2 matrix to one site; a unitary keeps the site's canonical form
+    fn apply_single(&mut self, site: usize, m: [[Complex; 2]; 2]) {
+        let Site { left, right, data } = &mut self.sites[site];
+        for l in 0..*left {
+            for r in 0..*right {
+                let (i0, i1) = ((l * 2) * *right + r, (l * 2 + 1) * *right + r);
+                let (a0, a1) = (data[i0], data[i1]);
+                data[i0] = m[0][0] * a0 + m[0][1] * a1;
+                data[i1] = m[1][0] * a0 + m[1][1] * a1;
+            }
+        }
+    }
+
+    /// Shift the orthogonality center to `to`, one SVD per bond crossed
+    fn move_center(&mut self, to: usize) {
+        while self.center < to {
+            let c = self.center;
+            let Site { left, right, .. } = self.sites[c];
+            let (u, sigma, vh) = truncated_svd(&self.sites[c].data, left * 2, right, self.max_bond);
+            let k = sigma.len();
+            let carried: Vec<Complex> = vh.iter().enumerate().map(|(i, &x)| scale(x, sigma[i / right])).collect();
+            self.sites[c] = Site { left, right: k, data: u };
+            let next = &self.sites[c + 1];
+            let data = matmul(&carried, &next.data, k, right, 2 * next.right);
+            self.sites[c + 1] = Site { left: k, right: next.right, data };
+            self.center += 1;
+        }
+        while self.center > to {
+            let c = self.center;
+            let Site { left, right, .. } = self.sites[c];
+            let (u, sigma, vh) = truncated_svd(&self.sites[c].data, left, 2 * right, self.max_bond);
+            let k = sigma.len();
+            let carried: Vec<Complex> = u.iter().enumerate().map(|(i, &x)| scale(x, sigma[i % k])).collect();
+            self.sites[c] = Site { left: k, right, data: vh };
+            let previous = &self.sites[c - 1];
+            let data = matmul(&previous.data, &carried, previous.left * 2, left, k);
+            self.sites[c - 1] = Site { left: previous.left, right: k, data };
+            self.center -= 1;
+        }
+    }
+
+    /// Apply a 2^k x 2^k matrix to the k adjacent sites from `start`, the first site being
+    /// the most significant bit of its indices, and split the block back into sites
+    fn apply_block(&mut self, start: usize, k: usize, matrix: &[Vec<Complex>]) {
+        self.move_center(start);
+        let left = self.sites[start].left;
+        let mut theta = self.sites[start].data.clone();
+        let mut width = 2;
+        for site in &self.sites[start + 1..start + k] {
+            theta = matmul(&theta, &site.data, left * width, site.left, 2 * site.right);
+            width *= 2;
+        }
+        let right = self.sites[start + k - 1].right;
+
+        let mut updated = vec![ZERO; theta.len()];
+        for l in 0..left {
+            for (out, row) in matrix.iter().enumerate() {
+                for (input, &u) in row.iter().enumerate() {
+                    if u == ZERO {
+                        continue;
+                    }
+                    for r in 0..right {
+                        let (to, from) = ((l * width + out) * right + r, (l * width + input) * right + r);
+                        updated[to] = updated[to] + u * theta[from];
+                    }
+                }
+            }
+        }
+
+        // Peel sites off the left; the remainder carries the center to the block's last site
+        let (mut rest, mut bond) = (updated, left);
+        for j in 0..k - 1 {
+            let cols = (1 << (k - j - 1)) * right;
+            let (u, sigma, vh) = truncated_svd(&rest, bond * 2, cols, self.max_bond);
+            let kept = sigma.len();
+            self.sites[start + j] = Site { left: bond, right: kept, data: u };
+            rest = vh.iter().enumerate().map(|(i, &x)| scale(x, sigma[i / cols])).collect();
+            bond = kept;
+        }
+        self.sites[start + k - 1] = Site { left: bond, right, data: rest };
+        self.center = start + k - 1;
+    }
+
+    /// Exchange the qubits on sites `site` and `site + 1`
+    fn swap_sites(&mut self, site: usize) {
+        let swap: Vec<Vec<Complex>> =
+            (0..4).map(|row| (0..4).map(|col| if col == [0, 2, 1, 3][row] { ONE } else { ZERO }).collect()).collect();
+        self.apply_block(site, 2, &swap);
+        let (a, b) = (self.qubit_at[site], self.qubit_at[site + 1]);
+        self.qubit_at.swap(site, site + 1);
+        self.site_of[a] = site + 1;
+        self.site_of[b] = site;
+    }
+
+    /// Move `qubits` onto adjacent sites by swapping neighbours toward the leftmost of them,
+    /// returning the first site of the block
+    fn gather(&mut self, qubits: &[usize]) -> usize {
+        let mut ordered = qubits.to_vec();
+        ordered.sort_by_key(|&qubit| self.site_of[qubit]);
+        let start = self.site_of[ordered[0]];
+        for (offset, &qubit) in ordered.iter().enumerate().skip(1) {
+            while self.site_of[qubit] > start + offset {
+                self.swap_sites(self.site_of[qubit] - 1);
+            }
+        }
+        start
+    }
+
+    /// Apply a 2^k x 2^k unitary to logical `qubits`, `qubits[0]` the least significant bit
+    pub fn apply_unitary(&mut self, qubits: &[usize], matrix: &[Vec<Complex>]) {
+        if let [qubit] = qubits {
+            let m = [[matrix[0][0], matrix[0][1]], [matrix[1][0], matrix[1][1]]];
+            self.apply_single(self.site_of[*qubit], m);
+            return;
+        }
+        let start = self.gather(qubits);
+        let k = qubits.len();
+        // Bit of the gate's index held by each site of the block
+        let bit_of: Vec<usize> =
+            (0..k).map(|j| qubits.iter().position(|&q| q == self.qubit_at[start + j]).unwrap()).collect();
+        let to_gate = |s: usize| (0..k).fold(0, |g, j| g | (((s >> (k - 1 - j)) & 1) << bit_of[j]));
+        let dim = 1 << k;
+        let reordered: Vec<Vec<Complex>> =
+            (0..dim).map(|row| (0..dim).map(|col| matrix[to_gate(row)][to_gate(col)]).collect()).collect();
+        self.apply_block(start, k, &reordered);
+    }
+
+    /// SWAP two logical qubits by relabeling their sites
+    pub fn swap_qubits(&mut self, qubit1: usize, qubit2: usize) {
+        let (a, b) = (self.site_of[qubit1], self.site_of[qubit2]);
+        self.site_of.swap(qubit1, qubit2);
+        self.qubit_at.swap(a, b);
+    }
+
+    /// Reorder qubits by relabeling: afterwards `qubits[k]` holds the state `qubits[pattern[k]]` had
+    pub fn permute(&mut self, qubits: &[usize], pattern: &[usize]) {
+        let sites: Vec<usize> = qubits.iter().map(|&q| self.site_of[q]).collect();
+        for (&qubit, &source) in qubits.iter().zip(pattern) {
+            self.site_of[qubit] = sites[source];
+            self.qubit_at[sites[source]] = qubit;
+        }
+    }
+
+    /// Projective measurement of one qubit, returning the outcome
+    pub fn measure<R: Rng + ?Sized>(&mut self, qubit: usize, rng: &mut R) -> bool {
+        let site = self.site_of[qubit];
+        self.move_center(site);
+        let Site { left, right, data } = &mut self.sites[site];
+        let (left, right) = (*left, *right);
+        let (mut p0, mut p1) = (0.0, 0.0);
+        for l in 0..left {
+            for r in 0..right {
+                p0 += data[(l * 2) * right + r].magnitude_squared();
+                p1 += data[(l * 2 + 1) * right + r].magnitude_squared();
+            }
+        }
+        let outcome = rng.gen::<f64>() * (p0 + p1) < p1;
+        let (kept, norm) = if outcome { (1, p1.sqrt()) } else { (0, p0.sqrt()) };
+        for l in 0..left {
+            for bit in 0..2 {
+                for r in 0..right {
+                    let index = (l * 2 + bit) * right + r;
+                    data[index] = if bit == kept { scale(data[index], 1.0 / norm) } else { ZERO };
+                }
+            }
+        }
+        outcome
+    }
+
+    /// Reset a qubit to |0>, returning whether it was found in |1>
+    pub fn reset<R: Rng + ?Sized>(&mut self, qubit: usize, rng: &mut R) -> bool {
+        let outcome = self.measure(qubit, rng);
+        if outcome {
+            self.apply_single(self.site_of[qubit], [[ZERO, ONE], [ONE, ZERO]]);
+        }
+        outcome
+    }
+
+    /// Logical state vector, by contracting the whole chain and renormalizing away the
+    /// weight lost to truncation; only for circuits of at most `MAX_DENSE_QUBITS` qubits
+    pub fn state_vector(&self) -> Vec<Complex> {
+        let mut amplitudes = vec![ONE];
+        for site in &self.sites {
+            amplitudes = matmul(&amplitudes, &site.data, amplitudes.len() / site.left, site.left, 2 * site.right);
+        }
+        let n = self.sites.len();
+        let norm = amplitudes.iter().map(|a| a.magnitude_squared()).sum::<f64>().sqrt();
+        let mut logical = vec![ZERO; amplitudes.len()];
+        for (index, amplitude) in amplitudes.iter().enumerate() {
+            // Site 0 is the most significant bit of the contracted index
+            let bits = (0..n).fold(0, |bits, q| bits | (((index >> (n - 1 - self.site_of[q])) & 1) << q));
+            logical[bits] = scale(*amplitude, 1.0 / norm);
+        }
+        logical
+    }
+
+    /// Measurement probabilities of every logical basis state; only for circuits of at most
+    /// `MAX_DENSE_QUBITS` qubits
+    pub fn probabilities(&self) -> Vec<f64> {
+        self.state_vector().iter().map(|amplitude| amplitude.magnitude_squared()).collect()
+    }
+
+    /// Draw `shots` logical basis states site by site, with the center moved to the first
+    /// site so every prefix's probability is the norm of its contracted vector
+    pub fn sample<R: Rng + ?Sized>(&mut self, shots: usize, rng: &mut R) -> Vec<usize> {
+        self.move_center(0);
+        (0..shots)
+            .map(|_| {
+                let mut vector = vec![ONE];
+                let mut outcome = 0;
+                for (site, tensor) in self.sites.iter().enumerate() {
+                    let branch = |bit: usize| -> Vec<Complex> {
+                        (0..tensor.right)
+                            .map(|r| {
+                                vector
+                                    .iter()
+                                    .enumerate()
+                                    .fold(ZERO, |sum, (l, &x)| sum + x * tensor.data[(l * 2 + bit) * tensor.right + r])
+                            })
+                            .collect()
+                    };
+                    let (zero, one) = (branch(0), branch(1));
+                    let norm = |v: &[Complex]| v.iter().map(|x| x.magnitude_squared()).sum::<f64>();
+                    let (p0, p1) = (norm(&zero), norm(&one));
+                    let bit = rng.gen::<f64>() * (p0 + p1) < p1;
+                    let (chosen, p) = if bit { (one, p1) } else { (zero, p0) };
+                    vector = chosen.into_iter().map(|x| scale(x, 1.0 / p.sqrt())).collect();
+                    if bit {
+                        outcome |= 1 << self.qubit_at[site];
+                    }
+                }
+                outcome
+            })
+            .collect()
+    }
+}
+
+/// As a `QuantumSimulator` representation, SWAPs and permutations relabel sites and wide
+/// gates are applied whole, however many qubits they span
+impl SimulatorBackend for MpsState {
+    fn name(&self) -> &'static str {
+        "mps"
+    }
+
+    fn num_qubits(&self) -> usize {
+        self.sites.len()
+    }
+
+    fn clone_box(&self) -> Box<dyn SimulatorBackend> {
+        Box::new(self.clone())
+    }
+
+    fn apply_matrix(&mut self, qubit: usize, matrix: &[[Complex; 2]; 2]) {
+        self.apply_single(self.site_of[qubit], *matrix);
+    }
+
+    fn apply_matrix_n(&mut self, qubits: &[usize], matrix: &[Vec<Complex>]) {
+        self.apply_unitary(qubits, matrix);
+    }
+
+    fn apply_gate(&mut self, gate: &QuantumGate) {
+        match gate {
+            QuantumGate::SWAP { qubit1, qubit2 } => self.swap_qubits(*qubit1, *qubit2),
+            QuantumGate::Permutation { qubits, pattern } => self.permute(qubits, pattern),
+            gate => match simulator_backend::split(gate) {
+                Some(gates) => gates.iter().for_each(|gate| self.apply_gate(gate)),
+                None => {
+                    let qubits = gate.qubits();
+                    self.apply_unitary(&qubits, &qsim::gate_matrix(gate, &qubits));
+                }
+            },
+        }
+    }
+
+    fn measure(&mut self, qubit: usize, rng: &mut dyn RngCore) -> bool {
+        MpsState::measure(self, qubit, rng)
+    }
+
+    fn reset(&mut self, qubit: usize, rng: &mut dyn RngCore) -> bool {
+        MpsState::reset(self, qubit, rng)
+    }
+
+    fn probabilities(&self) -> Vec<f64> {
+        MpsState::probabilities(self)
+    }
+
+    fn amplitudes(&self) -> Cow<'_, [Complex]> {
+        Cow::Owned(self.state_vector())
+    }
+
+    /// Samples site by site on a copy, since sampling moves the orthogonality center
+    fn sample(&self, shots: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        MpsState::sample(&mut self.clone(), shots, rng)
+    }
+}
+
+/// One run of a circuit on an MPS, with the measurement bookkeeping the state vector
+/// simulator keeps
+struct Run {
+    state: MpsState,
+    /// Terminal measurements, read from the sampled basis state, as (qubit, register bit)
+    pending: Vec<(usize, usize)>,
+    /// Latest collapsed measurement in each register bit
+    classical: Vec<Option<bool>>,
+    register: usize,
+}
+
+impl Run {
+    fn new(circuit: &QuantumCircuit, max_bond: usize, rng: &mut StdRng) -> Result<Self, String> {
+        let mut run = Run {
+            state: MpsState::new(circuit.num_qubits, max_bond),
+            pending: Vec::new(),
+            classical: vec![None; circuit.num_clbits()],
+            register: 0,
+        };
+        for (index, gate) in circuit.gates.iter().enumerate() {
+            run.apply(gate, &circuit.gates[index + 1..], rng)?;
+        }
+        Ok(run)
+    }
+
+    fn apply(&mut self, gate: &QuantumGate, later: &[QuantumGate], rng: &mut StdRng) -> Result<(), String> {
+        match gate {
+            QuantumGate::Measurement { qubit, bit } => {
+                let bit = bit.unwrap_or(*qubit);
+                // Measurements no later gate depends on stay deferred, so the distribution is kept
+                if qsim::measurement_is_read(*qubit, bit, later) {
+                    let outcome = self.state.measure(*qubit, rng);
+                    self.classical[bit] = Some(outcome);
+                    self.register = (self.register & !(1 << bit)) | ((outcome as usize) << bit);
+                } else {
+                    self.classical[bit] = None;
+                    self.pending.push((*qubit, bit));
+                }
+            }
+            QuantumGate::Reset { qubit } => {
+                self.state.reset(*qubit, rng);
+            }
+            QuantumGate::IfBit { bit, gate } => {
+                if self.classical[*bit] == Some(true) {
+                    self.apply(gate, later, rng)?;
+                }
+            }
+            // Noiseless, like the state vector simulator
+            QuantumGate::Noisy { gate, .. } => self.apply(gate, later, rng)?,
+            // Snapshots copy a state vector, which this backend never builds
+            QuantumGate::Snapshot { .. } => {}
+            QuantumGate::SWAP { qubit1, qubit2 } => self.state.swap_qubits(*qubit1, *qubit2),
+            QuantumGate::Permutation { qubits, pattern } => self.state.permute(qubits, pattern),
+            QuantumGate::GlobalHadamard { qubits } if qubits.len() > 1 => {
+                for &qubit in qubits {
+                    self.apply(&QuantumGate::GlobalHadamard { qubits: vec![qubit] }, later, rng)?;
+                }
+            }
+            QuantumGate::GlobalRotation { qubits, axis, angle } if qubits.len() > 1 => {
+                for &qubit in qubits {
+                    self.apply(&QuantumGate::GlobalRotation { qubits: vec![qubit], axis: *axis, angle: *angle }, later, rng)?;
+                }
+            }
+            QuantumGate::PauliRotation { qubits, pauli, angle } => {
+                for gate in qsim::pauli_rotation_gates(qubits, pauli, *angle) {
+                    self.apply(&gate, later, rng)?;
+                }
+            }
+            _ => {
+                let qubits = gate.qubits();
+                if qubits.len() > MAX_GATE_QUBITS {
+                    return Err(format!(
+                        "the mps backend applies gates on at most {} qubits; this one spans {}",
+                        MAX_GATE_QUBITS,
+                        qubits.len()
+                    ));
+                }
+                self.state.apply_unitary(&qubits, &qsim::gate_matrix(gate, &qubits));
+            }
+        }
+        Ok(())
+    }
+
+    /// Classical register if the final state is measured as `outcome`
+    fn register_for(&self, outcome: usize) -> usize {
+        self.pending
+            .iter()
+            .fold(self.register, |register, &(qubit, bit)| (register & !(1 << bit)) | (((outcome >> qubit) & 1) << bit))
+    }
+}
+
+/// Matrix product state backend, registered as `mps`
+pub struct MpsBackend {
+    max_bond: usize,
+}
+
+impl MpsBackend {
+    pub fn new(max_bond: usize) -> Self {
+        Self { max_bond }
+    }
+}
+
+impl Default for MpsBackend {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_BOND)
+    }
+}
+
+impl StateBackend for MpsBackend {
+    fn name(&self) -> &str {
+        "mps"
+    }
+
+    fn description(&self) -> &str {
+        "matrix product state, bonds capped by --max-bond (default 64); shallow circuits on up to 64 qubits"
+    }
+
+    fn probabilities(&self, circuit: &QuantumCircuit, seed: u64) -> Result<Vec<f64>, String> {
+        if circuit.num_qubits > MAX_DENSE_QUBITS {
+            return Err(format!(
+                "a probability vector of {} qubits has 2^{} entries; sample it with --shots instead (at most {} qubits)",
+                circuit.num_qubits, circuit.num_qubits, MAX_DENSE_QUBITS
+            ));
+        }
+        Ok(Run::new(circuit, self.max_bond, &mut StdRng::seed_from_u64(seed))?.state.probabilities())
+    }
+
+    fn sample(&self, circuit: &QuantumCircuit, shots: usize, seed: u64) -> Result<Vec<Shot>, String> {
+        if circuit.num_qubits > usize::BITS as usize {
+            return Err(format!("shot outcomes hold at most {} qubits", usize::BITS));
+        }
+        let mut rng = StdRng::seed_from_u64(seed);
+        if !circuit.has_collapse() {
+            let mut run = Run::new(circuit, self.max_bond, &mut rng)?;
+            let outcomes = MpsState::sample(&mut run.state, shots, &mut rng);
+            return Ok(qsim::terminal_shots(circuit, outcomes));
+        }
+        (0..shots)
+            .map(|_| {
+                let mut run = Run::new(circuit, self.max_bond, &mut rng)?;
+                let outcome = MpsState::sample(&mut run.state, 1, &mut rng)[0];
+                Ok(Shot { outcome, register: run.register_for(outcome) })
+            })
+            .collect()
+    }
+
+    fn with_option(&self, name: &str, value: &str) -> Result<Arc<dyn StateBackend>, String> {
+        match name {
+            "max-bond" => match value.parse::<usize>() {
+                Ok(max_bond) if max_bond > 0 => Ok(Arc::new(MpsBackend::new(max_bond))),
+                _ => Err("--max-bond expects a positive integer".to_string()),
+            },
+            _ => Err(format!("the mps backend has no --{} option", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_matrix(rng: &mut StdRng, rows: usize, cols: usize, rank: usize) -> Vec<Complex> {
+        let mut matrix = vec![ZERO; rows * cols];
+        for _ in 0..rank {
+            let column: Vec<Complex> = (0..rows).map(|_| Complex::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0))).collect();
+            let row: Vec<Complex> = (0..cols).map(|_| Complex::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0))).collect();
+            for i in 0..rows {
+                for j in 0..cols {
+                    matrix[i * cols + j] = matrix[i * cols + j] + column[i] * row[j];
+                }
+            }
+        }
+        matrix
+    }
+
+    /// Check U·diag(S)·V† = A, descending S, and orthonormal columns of U and rows of V†
+    /// for every nonzero singular value
+    fn assert_svd(matrix: &[Complex], rows: usize, cols: usize) {
+        let (u, sigma, vh) = svd(matrix, rows, cols);
+        let r = rows.min(cols);
+        assert_eq!(sigma.len(), r);
+        let largest = sigma[0].max(1e-300);
+        assert!(sigma.windows(2).all(|pair| pair[0] >= pair[1]), "{:?}", sigma);
+        for i in 0..rows {
+            for j in 0..cols {
+                let entry = (0..r).fold(ZERO, |sum, k| sum + scale(u[i * r + k], sigma[k]) * vh[k * cols + j]);
+                let error = entry + scale(matrix[i * cols + j], -1.0);
+                assert!(error.magnitude_squared().sqrt() < 1e-12 * largest, "{}x{} entry ({}, {}) off by {:?}", rows, cols, i, j, error);
+            }
+        }
+        let kept: Vec<usize> = (0..r).filter(|&k| sigma[k] > 1e-10 * largest).collect();
+        for &a in &kept {
+            for &b in &kept {
+                let expected = if a == b { 1.0 } else { 0.0 };
+                let left = (0..rows).fold(ZERO, |sum, i| sum + u[i * r + a].conjugate() * u[i * r + b]);
+                let right = (0..cols).fold(ZERO, |sum, j| sum + vh[a * cols + j] * vh[b * cols + j].conjugate());
+                assert!((left.re - expected).abs() < 1e-10 && left.im.abs() < 1e-10, "U columns {} and {}: {:?}", a, b, left);
+                assert!((right.re - expected).abs() < 1e-10 && right.im.abs() < 1e-10, "V† rows {} and {}: {:?}", a, b, right);
+            }
+        }
+    }
+
+    #[test]
+    fn svd_reconstructs_random_and_rank_deficient_matrices() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for &(rows, cols) in &[(4, 4), (8, 4), (4, 8), (16, 16), (32, 6), (6, 32)] {
+            for rank in [rows.min(cols), 3, 2, 1, 0] {
+                let matrix = random_matrix(&mut rng, rows, cols, rank);
+                assert_svd(&matrix, rows, cols);
+            }
+        }
+    }
+
+    #[test]
+    fn svd_survives_columns_near_underflow() {
+        // A rank-two block plus rounding-noise columns far below the smallest normal overlap
+        let mut rng = StdRng::seed_from_u64(8);
+        let mut matrix = random_matrix(&mut rng, 4, 4, 2);
+        for (index, noise) in random_matrix(&mut rng, 4, 4, 4).into_iter().enumerate() {
+            matrix[index] = matrix[index] + scale(noise, 1e-156);
+        }
+        assert_svd(&matrix, 4, 4);
+        let tiny: Vec<Complex> = random_matrix(&mut rng, 4, 4, 4).into_iter().map(|x| scale(x, 1e-160)).collect();
+        assert_svd(&tiny, 4, 4);
+    }
+}
@@ -0,0 +1,1286 @@
+//! Noise Model Module
+//! Device error rates used to estimate circuit fidelity
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fs;
+use std::sync::Arc;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::backends::StateBackend;
+use crate::gpu_ops::{self, Complex};
+use crate::qsim::{self, QuantumCircuit, QuantumGate, QuantumSimulator, Shot};
+use crate::{monitored, simulator_backend};
+
+/// Widest circuit simulated on a density matrix when no `--noise-mode` is given: 4^12
+/// entries, 256 MiB
+pub const MAX_DENSITY_QUBITS: usize = 12;
+
+/// Trajectories averaged for probabilities in trajectory mode
+pub const DEFAULT_TRAJECTORIES: usize = 1000;
+
+/// Largest deviation of Σ K†K from the identity accepted for a channel's Kraus operators
+const TRACE_TOLERANCE: f64 = 1e-6;
+
+/// Kraus operators together with the logical qubits they act on
+pub type Channel<'a> = (Vec<usize>, Cow<'a, [Vec<Vec<Complex>>]>);
+
+/// Error rate override for a specific qubit pair
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairError {
+    pub qubits: [usize; 2],
+    pub error: f64,
+}
+
+/// Error rate override for single-qubit gates on a specific qubit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QubitError {
+    pub qubit: usize,
+    pub error: f64,
+}
+
+/// Readout confusion of one qubit, overriding the model's `measurement_error` for it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReadoutError {
+    pub qubit: usize,
+    /// `confusion[prepared][read]`: the probability of reading each bit from each state, so
+    /// each row sums to 1
+    pub confusion: [[f64; 2]; 2],
+}
+
+/// Crosstalk rate of one gate pair onto one spectator, overriding `crosstalk_error`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrosstalkError {
+    /// Qubits of the two-qubit gate, in either order
+    pub gate: [usize; 2],
+    pub spectator: usize,
+    pub error: f64,
+}
+
+/// Coherence times of one qubit, overriding the model's `t1_us` and `t2_us`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QubitCoherence {
+    pub qubit: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub t1_us: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub t2_us: Option<f64>,
+}
+
+/// Noise override carried by one gate in a circuit file, replacing what the active model
+/// would charge for it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GateNoise {
+    /// Probability that the gate fails
+    #[serde(default, alias = "depolarizing", skip_serializing_if = "Option::is_none")]
+    pub error: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ns: Option<f64>,
+}
+
+/// Coherent over- or under-rotation of matching rotation gates, as from miscalibration:
+/// every angle θ becomes θ (1 + scale) + offset + δ, with δ drawn from N(0, sigma²) anew
+/// each time a gate runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RotationError {
+    /// Gate types it perturbs, as written in circuit files; empty for every gate with an angle
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gates: Vec<String>,
+    /// Qubits it is limited to; empty for every qubit. Gates on several qubits need all of
+    /// them listed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub qubits: Vec<usize>,
+    /// Fractional over-rotation, e.g. 0.01 for angles 1% too large and -0.01 for 1% too small
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub scale: f64,
+    /// Systematic angle offset in radians
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub offset: f64,
+    /// Standard deviation of the random angle error in radians
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub sigma: f64,
+}
+
+impl RotationError {
+    fn matches(&self, gate_type: &str, qubits: &[usize]) -> bool {
+        (self.gates.is_empty() || self.gates.iter().any(|name| name.eq_ignore_ascii_case(gate_type)))
+            && (self.qubits.is_empty() || qubits.iter().all(|qubit| self.qubits.contains(qubit)))
+    }
+}
+
+/// A standard channel named in a model file instead of written out as Kraus operators
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StandardChannel {
+    /// Decay |1⟩ → |0⟩ with probability `gamma`
+    AmplitudeDamping { gamma: f64 },
+    /// Coherences scaled by √(1 - lambda), populations untouched
+    PhaseDamping { lambda: f64 },
+    /// X with probability `p`
+    BitFlip { p: f64 },
+    /// Z with probability `p`
+    PhaseFlip { p: f64 },
+    /// X, Y or Z with probabilities `px`, `py` and `pz`
+    Pauli {
+        #[serde(default)]
+        px: f64,
+        #[serde(default)]
+        py: f64,
+        #[serde(default)]
+        pz: f64,
+    },
+    /// Depolarizing noise on `width` qubits (1 or 2), as for gate errors
+    Depolarizing {
+        p: f64,
+        #[serde(default = "default_channel_width")]
+        width: usize,
+    },
+}
+
+fn default_channel_width() -> usize {
+    1
+}
+
+impl StandardChannel {
+    pub fn width(&self) -> usize {
+        match self {
+            StandardChannel::Depolarizing { width, .. } => *width,
+            _ => 1,
+        }
+    }
+
+    /// Check the parameters are probabilities
+    pub fn validate(&self) -> Result<(), String> {
+        let probabilities = match *self {
+            StandardChannel::AmplitudeDamping { gamma } => vec![gamma],
+            StandardChannel::PhaseDamping { lambda } => vec![lambda],
+            StandardChannel::BitFlip { p } | StandardChannel::PhaseFlip { p } => vec![p],
+            StandardChannel::Pauli { px, py, pz } => vec![px, py, pz, px + py + pz],
+            StandardChannel::Depolarizing { width, .. } if !(1..=2).contains(&width) => {
+                return Err(format!("depolarizing channels act on 1 or 2 qubits, not {}", width));
+            }
+            StandardChannel::Depolarizing { p, .. } => vec![p],
+        };
+        if probabilities.iter().any(|p| !(0.0..=1.0).contains(p)) {
+            return Err("channel parameters must be probabilities".to_string());
+        }
+        Ok(())
+    }
+
+    /// Kraus operators of the channel
+    pub fn kraus(&self) -> Vec<Vec<Vec<Complex>>> {
+        let real = |x: f64| Complex::new(x, 0.0);
+        let zero = real(0.0);
+        match *self {
+            StandardChannel::AmplitudeDamping { gamma } => vec![
+                vec![vec![real(1.0), zero], vec![zero, real((1.0 - gamma).sqrt())]],
+                vec![vec![zero, real(gamma.sqrt())], vec![zero, zero]],
+            ],
+            StandardChannel::PhaseDamping { lambda } => vec![
+                vec![vec![real(1.0), zero], vec![zero, real((1.0 - lambda).sqrt())]],
+                vec![vec![zero, zero], vec![zero, real(lambda.sqrt())]],
+            ],
+            StandardChannel::BitFlip { p } => pauli_kraus(p, 0.0, 0.0),
+            StandardChannel::PhaseFlip { p } => pauli_kraus(0.0, 0.0, p),
+            StandardChannel::Pauli { px, py, pz } => pauli_kraus(px, py, pz),
+            StandardChannel::Depolarizing { p, width } => depolarizing_kraus(p, width),
+        }
+    }
+}
+
+/// A quantum channel ρ → Σ KρK† applied after every gate it matches. A one-qubit channel
+/// acts on each qubit of the gate in turn; a k-qubit channel acts on gates of exactly k
+/// qubits, with `qubits[0]` of the gate the least significant bit of its operators' indices.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelRule {
+    /// Gate types it follows, as written in circuit files, e.g. `CNOT`; empty for every gate
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gates: Vec<String>,
+    /// Qubits it is limited to; empty for every qubit. A wider channel needs all of the
+    /// gate's qubits listed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub qubits: Vec<usize>,
+    /// Kraus operators, each a 2^k x 2^k matrix with entries as `[re, im]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub kraus: Vec<Vec<Vec<Complex>>>,
+    /// A named channel, given instead of `kraus`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<StandardChannel>,
+}
+
+impl ChannelRule {
+    /// Qubits the channel acts on at once
+    pub fn width(&self) -> usize {
+        match &self.channel {
+            Some(channel) => channel.width(),
+            None => self.kraus.first().map_or(0, |operator| operator.len().trailing_zeros() as usize),
+        }
+    }
+
+    /// The Kraus operators, written out or of the named channel
+    pub fn operators(&self) -> Cow<'_, [Vec<Vec<Complex>>]> {
+        match &self.channel {
+            Some(channel) => Cow::Owned(channel.kraus()),
+            None => Cow::Borrowed(self.kraus.as_slice()),
+        }
+    }
+
+    /// Check the rule gives either a named channel or Kraus operators that are square, of
+    /// one power-of-two size, and trace preserving
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(channel) = &self.channel {
+            if !self.kraus.is_empty() {
+                return Err("a channel takes either `kraus` or a named `channel`, not both".to_string());
+            }
+            return channel.validate();
+        }
+        let dim = match self.kraus.first() {
+            Some(operator) => operator.len(),
+            None => return Err("a channel needs at least one Kraus operator or a named `channel`".to_string()),
+        };
+        if dim < 2 || !dim.is_power_of_two() {
+            return Err(format!("Kraus operators are {}x{}, not 2^k x 2^k", dim, dim));
+        }
+        if self.kraus.iter().any(|operator| operator.len() != dim || operator.iter().any(|row| row.len() != dim)) {
+            return Err(format!("every Kraus operator of a channel must be {}x{}", dim, dim));
+        }
+        // (Σ K†K)_ij = Σ_K Σ_r conj(K_ri) K_rj
+        for i in 0..dim {
+            for j in 0..dim {
+                let sum = self.kraus.iter().flat_map(|operator| operator.iter()).fold(Complex::new(0.0, 0.0), |sum, row| {
+                    sum + row[i].conjugate() * row[j]
+                });
+                let expected = if i == j { 1.0 } else { 0.0 };
+                if (sum.re - expected).abs() > TRACE_TOLERANCE || sum.im.abs() > TRACE_TOLERANCE {
+                    return Err("the Kraus operators do not preserve the trace: Σ K†K is not the identity".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn follows(&self, gate_type: &str) -> bool {
+        self.gates.is_empty() || self.gates.iter().any(|name| name.eq_ignore_ascii_case(gate_type))
+    }
+}
+
+/// Simulated device noise model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoiseModel {
+    #[serde(default = "default_single_qubit_error")]
+    pub single_qubit_error: f64,
+    #[serde(default = "default_two_qubit_error")]
+    pub two_qubit_error: f64,
+    #[serde(default = "default_measurement_error")]
+    pub measurement_error: f64,
+    #[serde(default)]
+    pub pair_errors: Vec<PairError>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub qubit_errors: Vec<QubitError>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub readout_errors: Vec<ReadoutError>,
+    #[serde(default = "default_single_qubit_time_ns")]
+    pub single_qubit_time_ns: f64,
+    #[serde(default = "default_two_qubit_time_ns")]
+    pub two_qubit_time_ns: f64,
+    #[serde(default = "default_measurement_time_ns")]
+    pub measurement_time_ns: f64,
+    /// Device connectivity as qubit pairs; the neighbours of a two-qubit gate pick up
+    /// crosstalk
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub coupling_map: Vec<[usize; 2]>,
+    /// Probability that a two-qubit gate puts a ZZ error on each coupled spectator and the
+    /// gate qubit it is coupled to
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub crosstalk_error: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub crosstalk: Vec<CrosstalkError>,
+    /// Energy relaxation time T1 of every qubit, in microseconds; none for no amplitude damping
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub t1_us: Option<f64>,
+    /// Dephasing time T2 of every qubit, in microseconds, at most 2 T1; none for no dephasing
+    /// beyond what T1 causes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub t2_us: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub coherence: Vec<QubitCoherence>,
+    /// Channels the simulator applies after matching gates, on top of depolarizing noise at
+    /// the error rates above; fidelity estimates ignore them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<ChannelRule>,
+    /// Coherent angle errors of rotation gates, applied by the simulator in model order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rotation_errors: Vec<RotationError>,
+}
+
+fn default_single_qubit_error() -> f64 {
+    1e-3
+}
+
+fn default_two_qubit_error() -> f64 {
+    1e-2
+}
+
+fn default_measurement_error() -> f64 {
+    2e-2
+}
+
+fn is_zero(value: &f64) -> bool {
+    *value == 0.0
+}
+
+fn default_single_qubit_time_ns() -> f64 {
+    50.0
+}
+
+fn default_two_qubit_time_ns() -> f64 {
+    300.0
+}
+
+fn default_measurement_time_ns() -> f64 {
+    1000.0
+}
+
+impl Default for NoiseModel {
+    fn default() -> Self {
+        Self {
+            single_qubit_error: default_single_qubit_error(),
+            two_qubit_error: default_two_qubit_error(),
+            measurement_error: default_measurement_error(),
+            pair_errors: Vec::new(),
+            qubit_errors: Vec::new(),
+            readout_errors: Vec::new(),
+            single_qubit_time_ns: default_single_qubit_time_ns(),
+            two_qubit_time_ns: default_two_qubit_time_ns(),
+            measurement_time_ns: default_measurement_time_ns(),
+            coupling_map: Vec::new(),
+            crosstalk_error: 0.0,
+            crosstalk: Vec::new(),
+            t1_us: None,
+            t2_us: None,
+            coherence: Vec::new(),
+            channels: Vec::new(),
+            rotation_errors: Vec::new(),
+        }
+    }
+}
+
+impl NoiseModel {
+    /// Check error rates, coherence times and every channel, naming the first bad one
+    pub fn validate(&self) -> Result<(), String> {
+        let times = [self.t1_us, self.t2_us].into_iter().chain(self.coherence.iter().flat_map(|entry| [entry.t1_us, entry.t2_us]));
+        if times.flatten().any(|time| time <= 0.0 || !time.is_finite()) {
+            return Err("coherence times must be positive".to_string());
+        }
+        // Qubits with their own entry, then the model's times for every other qubit
+        for qubit in self.coherence.iter().map(|entry| Some(entry.qubit)).chain([None]) {
+            let (t1, t2) = match qubit {
+                Some(qubit) => self.coherence_us(qubit),
+                None => (self.t1_us, self.t2_us),
+            };
+            if let (Some(t1), Some(t2)) = (t1, t2) {
+                if t2 > 2.0 * t1 * (1.0 + 1e-9) {
+                    let name = qubit.map_or("the model".to_string(), |qubit| format!("qubit {}", qubit));
+                    return Err(format!("{} has T2 = {} us, above 2 T1 = {} us", name, t2, 2.0 * t1));
+                }
+            }
+        }
+        let rates = [("single_qubit_error", self.single_qubit_error), ("two_qubit_error", self.two_qubit_error), ("measurement_error", self.measurement_error)];
+        if let Some((name, rate)) = rates.iter().find(|(_, rate)| !(0.0..=1.0).contains(rate)) {
+            return Err(format!("{} {} is not a probability", name, rate));
+        }
+        let mut overrides = self.pair_errors.iter().map(|entry| entry.error).chain(self.qubit_errors.iter().map(|entry| entry.error));
+        if overrides.any(|p| !(0.0..=1.0).contains(&p)) {
+            return Err("pair and qubit errors must be probabilities".to_string());
+        }
+        if let Some([a, _]) = self.coupling_map.iter().find(|[a, b]| a == b) {
+            return Err(format!("coupling map entry [{}, {}] couples a qubit to itself", a, a));
+        }
+        let mut crosstalk = std::iter::once(self.crosstalk_error).chain(self.crosstalk.iter().map(|entry| entry.error));
+        if crosstalk.any(|p| !(0.0..=1.0).contains(&p)) {
+            return Err("crosstalk errors must be probabilities".to_string());
+        }
+        for entry in &self.readout_errors {
+            let valid = entry.confusion.iter().all(|row| {
+                row.iter().all(|&p| (0.0..=1.0).contains(&p)) && (row[0] + row[1] - 1.0).abs() <= TRACE_TOLERANCE
+            });
+            if !valid {
+                return Err(format!("the confusion matrix of qubit {} needs rows of probabilities summing to 1", entry.qubit));
+            }
+        }
+        for (index, rule) in self.rotation_errors.iter().enumerate() {
+            if ![rule.scale, rule.offset, rule.sigma].iter().all(|value| value.is_finite()) || rule.sigma < 0.0 {
+                return Err(format!("rotation error {}: scale, offset and sigma must be finite, and sigma not negative", index));
+            }
+        }
+        for (index, rule) in self.channels.iter().enumerate() {
+            rule.validate().map_err(|e| format!("channel {}: {}", index, e))?;
+        }
+        Ok(())
+    }
+
+    /// Channels that follow a gate, with the logical qubits each acts on: depolarizing noise
+    /// at the gate's error rate for one- and two-qubit gates, and on each qubit of wider
+    /// gates, crosstalk onto the coupled neighbours of two-qubit gates, then the matching
+    /// `channels` in model order.
+    /// Measurements and snapshots draw none, and `IfBit` gates take those of the gate they
+    /// condition, once it runs. A `Noisy` gate's `error` replaces its rate.
+    pub fn channels_after(&self, gate: &QuantumGate) -> Vec<Channel<'_>> {
+        let (gate, error) = match gate {
+            QuantumGate::Noisy { noise, gate } => (gate.as_ref(), noise.error),
+            gate => (gate, None),
+        };
+        if matches!(gate, QuantumGate::Measurement { .. } | QuantumGate::Snapshot { .. } | QuantumGate::IfBit { .. }) {
+            return Vec::new();
+        }
+        let qubits = gate.qubits();
+        let mut channels = Vec::new();
+        let mut depolarize = |qubits: Vec<usize>, p: f64| {
+            if p > 0.0 {
+                let kraus = depolarizing_kraus(p, qubits.len());
+                channels.push((qubits, Cow::Owned(kraus)));
+            }
+        };
+        match (gate, qubits.as_slice()) {
+            // Readout and reset errors are not gate errors
+            (QuantumGate::Reset { .. }, _) => {}
+            // Layers of single-qubit gates
+            (QuantumGate::GlobalHadamard { .. } | QuantumGate::GlobalRotation { .. }, _) => {
+                for &qubit in &qubits {
+                    depolarize(vec![qubit], error.unwrap_or_else(|| self.qubit_error(qubit)));
+                }
+            }
+            (_, &[qubit]) => depolarize(vec![qubit], error.unwrap_or_else(|| self.qubit_error(qubit))),
+            (_, &[qubit1, qubit2]) => depolarize(vec![qubit1, qubit2], error.unwrap_or_else(|| self.pair_error(qubit1, qubit2))),
+            // Wider gates run as CNOTs and single-qubit gates; a channel on all their qubits
+            // at once would take 4^n Kraus operators, so each qubit depolarizes on its own, at
+            // the rate that leaves the fidelity `gate_fidelity` charges for the decomposition
+            (_, qubits) => {
+                let fidelity = error.map_or_else(|| self.gate_fidelity(gate), |error| 1.0 - error);
+                let p = 1.0 - fidelity.clamp(0.0, 1.0).powf(1.0 / qubits.len() as f64);
+                for &qubit in qubits {
+                    depolarize(vec![qubit], p);
+                }
+            }
+        }
+        if let &[qubit1, qubit2] = qubits.as_slice() {
+            channels.extend(self.crosstalk_after(qubit1, qubit2));
+        }
+        if self.channels.is_empty() {
+            return channels;
+        }
+        let gate_type = gate_type(gate);
+        for rule in self.channels.iter().filter(|rule| rule.follows(&gate_type)) {
+            let listed = |qubit: &usize| rule.qubits.is_empty() || rule.qubits.contains(qubit);
+            match rule.width() {
+                1 => channels.extend(
+                    qubits.iter().filter(|&qubit| listed(qubit)).map(|&qubit| (vec![qubit], rule.operators())),
+                ),
+                width if width == qubits.len() && qubits.iter().all(listed) => {
+                    channels.push((qubits.clone(), rule.operators()))
+                }
+                _ => {}
+            }
+        }
+        channels
+    }
+
+    /// Correlated ZZ errors a two-qubit gate causes: one channel on each spectator coupled
+    /// to either gate qubit, together with the gate qubit it is coupled to
+    fn crosstalk_after(&self, qubit1: usize, qubit2: usize) -> Vec<Channel<'_>> {
+        let mut spectators: Vec<(usize, usize)> = Vec::new();
+        for &[a, b] in &self.coupling_map {
+            for (gate_qubit, spectator) in [(a, b), (b, a)] {
+                let unseen = !spectators.iter().any(|&(_, seen)| seen == spectator);
+                if (gate_qubit == qubit1 || gate_qubit == qubit2) && spectator != qubit1 && spectator != qubit2 && unseen {
+                    spectators.push((gate_qubit, spectator));
+                }
+            }
+        }
+        spectators
+            .into_iter()
+            .filter_map(|(gate_qubit, spectator)| {
+                let p = self
+                    .crosstalk
+                    .iter()
+                    .find(|entry| {
+                        entry.spectator == spectator && (entry.gate == [qubit1, qubit2] || entry.gate == [qubit2, qubit1])
+                    })
+                    .map_or(self.crosstalk_error, |entry| entry.error);
+                (p > 0.0).then(|| (vec![gate_qubit, spectator], Cow::Owned(zz_error_kraus(p))))
+            })
+            .collect()
+    }
+
+    /// The gate with its angle perturbed by every matching `rotation_errors` entry in turn,
+    /// or none when no entry matches. A `Noisy` gate's inner gate is perturbed. `IfBit`
+    /// gates are left alone here, and their inner gate is perturbed when it runs.
+    pub fn perturbed<R: Rng + ?Sized>(&self, gate: &QuantumGate, rng: &mut R) -> Option<QuantumGate> {
+        if self.rotation_errors.is_empty() {
+            return None;
+        }
+        if let QuantumGate::Noisy { noise, gate } = gate {
+            let inner = self.perturbed(gate, rng)?;
+            return Some(QuantumGate::Noisy { noise: noise.clone(), gate: Box::new(inner) });
+        }
+        let (gate_type, qubits) = (gate_type(gate), gate.qubits());
+        let mut rules = self.rotation_errors.iter().filter(|rule| rule.matches(&gate_type, &qubits)).peekable();
+        rules.peek()?;
+        let mut perturbed = gate.clone();
+        let angle = rotation_angle_mut(&mut perturbed)?;
+        for rule in rules {
+            let jitter = if rule.sigma > 0.0 { rule.sigma * monitored::gaussian(rng) } else { 0.0 };
+            *angle = *angle * (1.0 + rule.scale) + rule.offset + jitter;
+        }
+        Some(perturbed)
+    }
+
+    /// Whether some rotation error draws a random part, which differs between runs
+    pub fn has_random_rotations(&self) -> bool {
+        self.rotation_errors.iter().any(|rule| rule.sigma > 0.0)
+    }
+
+    /// (T1, T2) of a qubit in microseconds, each none when neither the qubit nor the model
+    /// gives it
+    pub fn coherence_us(&self, qubit: usize) -> (Option<f64>, Option<f64>) {
+        let entry = self.coherence.iter().find(|entry| entry.qubit == qubit);
+        (
+            entry.and_then(|entry| entry.t1_us).or(self.t1_us),
+            entry.and_then(|entry| entry.t2_us).or(self.t2_us),
+        )
+    }
+
+    /// `confusion[prepared][read]` of a qubit: its `readout_errors` entry, or a symmetric
+    /// flip at `measurement_error`
+    pub fn confusion(&self, qubit: usize) -> [[f64; 2]; 2] {
+        match self.readout_errors.iter().find(|entry| entry.qubit == qubit) {
+            Some(entry) => entry.confusion,
+            None => {
+                let p = self.measurement_error;
+                [[1.0 - p, p], [p, 1.0 - p]]
+            }
+        }
+    }
+
+    fn has_readout_error(&self) -> bool {
+        self.measurement_error > 0.0 || !self.readout_errors.is_empty()
+    }
+
+    /// A measured bit of a qubit as read out, flipped with the probability its confusion
+    /// matrix gives
+    pub fn misread<R: Rng + ?Sized>(&self, qubit: usize, bit: bool, rng: &mut R) -> bool {
+        let flip = self.confusion(qubit)[bit as usize][!bit as usize];
+        bit ^ (flip > 0.0 && rng.gen::<f64>() < flip)
+    }
+
+    /// A sampled basis state of `num_qubits` qubits as read out, each qubit misread
+    /// independently
+    pub fn misread_outcome<R: Rng + ?Sized>(&self, outcome: usize, num_qubits: usize, rng: &mut R) -> usize {
+        if !self.has_readout_error() {
+            return outcome;
+        }
+        (0..num_qubits).fold(outcome, |outcome, qubit| {
+            let bit = (outcome >> qubit) & 1 == 1;
+            (outcome & !(1 << qubit)) | ((self.misread(qubit, bit, rng) as usize) << qubit)
+        })
+    }
+
+    /// Probabilities of reading each basis state, from the probabilities of the states
+    /// themselves: every qubit's confusion matrix applied in turn
+    pub fn readout_distribution(&self, probabilities: &[f64]) -> Vec<f64> {
+        let mut read = probabilities.to_vec();
+        if !self.has_readout_error() {
+            return read;
+        }
+        for qubit in 0..probabilities.len().trailing_zeros() as usize {
+            let confusion = self.confusion(qubit);
+            let mask = 1 << qubit;
+            for zero in (0..read.len()).filter(|index| index & mask == 0) {
+                let (p0, p1) = (read[zero], read[zero | mask]);
+                read[zero] = p0 * confusion[0][0] + p1 * confusion[1][0];
+                read[zero | mask] = p0 * confusion[0][1] + p1 * confusion[1][1];
+            }
+        }
+        read
+    }
+
+    fn has_relaxation(&self) -> bool {
+        self.t1_us.is_some() || self.t2_us.is_some() || !self.coherence.is_empty()
+    }
+
+    /// Thermal relaxation from a gate, as one channel per qubit: each of its qubits first
+    /// idles until the last of them is free, then is busy for the gate's duration. `clocks`
+    /// holds the time in ns at which each logical qubit is next free, and is advanced.
+    /// Measurements only advance the clock, as their outcome is read before they relax.
+    pub fn relaxation_after(&self, gate: &QuantumGate, clocks: &mut [f64]) -> Vec<Channel<'_>> {
+        if !self.has_relaxation() || matches!(gate, QuantumGate::Snapshot { .. } | QuantumGate::IfBit { .. }) {
+            return Vec::new();
+        }
+        let qubits = gate.qubits();
+        let start = qubits.iter().map(|&qubit| clocks[qubit]).fold(0.0, f64::max);
+        let end = start + self.gate_duration_ns(gate);
+        let mut channels = Vec::new();
+        for &qubit in &qubits {
+            let elapsed = end - clocks[qubit];
+            clocks[qubit] = end;
+            if matches!(gate, QuantumGate::Measurement { .. }) || elapsed <= 0.0 {
+                continue;
+            }
+            let (t1, t2) = self.coherence_us(qubit);
+            if t1.is_some() || t2.is_some() {
+                channels.push((vec![qubit], Cow::Owned(thermal_relaxation_kraus(elapsed, t1, t2))));
+            }
+        }
+        channels
+    }
+
+    /// Error rate of a two-qubit gate on the given pair (order-independent)
+    pub fn pair_error(&self, qubit1: usize, qubit2: usize) -> f64 {
+        self.pair_errors
+            .iter()
+            .find(|pair| {
+                pair.qubits == [qubit1, qubit2] || pair.qubits == [qubit2, qubit1]
+            })
+            .map(|pair| pair.error)
+            .unwrap_or(self.two_qubit_error)
+    }
+
+    /// Error rate of a single-qubit gate on the given qubit
+    pub fn qubit_error(&self, qubit: usize) -> f64 {
+        self.qubit_errors
+            .iter()
+            .find(|entry| entry.qubit == qubit)
+            .map(|entry| entry.error)
+            .unwrap_or(self.single_qubit_error)
+    }
+
+    /// Probability that a gate executes without error
+    pub fn gate_fidelity(&self, gate: &QuantumGate) -> f64 {
+        let single = 1.0 - self.single_qubit_error;
+        match gate {
+            QuantumGate::Noisy { noise, gate } => match noise.error {
+                Some(error) => 1.0 - error,
+                None => self.gate_fidelity(gate),
+            },
+            QuantumGate::Measurement { .. } => 1.0 - self.measurement_error,
+            // Simulator bookkeeping, not a device operation
+            QuantumGate::Snapshot { .. } => 1.0,
+            // Active reset: a measurement, then an X when it read 1
+            QuantumGate::Reset { .. } => (1.0 - self.measurement_error) * single,
+            // Costed as if the condition holds, the worst case
+            QuantumGate::IfBit { gate, .. } => self.gate_fidelity(gate),
+            QuantumGate::CNOT { control, target }
+            | QuantumGate::CZ { control, target }
+            | QuantumGate::CPhase { control, target, .. } => 1.0 - self.pair_error(*control, *target),
+            // Native entangling gates on iSWAP-based and IBM cross-resonance hardware
+            QuantumGate::ISWAP { qubit1, qubit2 }
+            | QuantumGate::SqrtISWAP { qubit1, qubit2 }
+            | QuantumGate::ECR { qubit1, qubit2 } => {
+                1.0 - self.pair_error(*qubit1, *qubit2)
+            }
+            // Native Mølmer–Sørensen / ZZ interactions on ion-trap and tunable-coupler hardware
+            QuantumGate::RXX { qubit1, qubit2, .. }
+            | QuantumGate::RYY { qubit1, qubit2, .. }
+            | QuantumGate::RZZ { qubit1, qubit2, .. } => 1.0 - self.pair_error(*qubit1, *qubit2),
+            // Two CNOTs on the pair with single-qubit rotations around them
+            QuantumGate::Givens { qubit1, qubit2, .. } => (1.0 - self.pair_error(*qubit1, *qubit2)).powi(2) * single.powi(4),
+            QuantumGate::SWAP { qubit1, qubit2 } => {
+                // Executed as three CNOTs on the same pair
+                (1.0 - self.pair_error(*qubit1, *qubit2)).powi(3)
+            }
+            // Hardware has to move the states, one SWAP at a time
+            QuantumGate::Permutation { qubits, pattern } => qsim::permutation_swaps(qubits, pattern)
+                .into_iter()
+                .map(|(qubit1, qubit2)| self.gate_fidelity(&QuantumGate::SWAP { qubit1, qubit2 }))
+                .product(),
+            QuantumGate::Toffoli { control1, control2, target } => {
+                // Six CNOTs plus six single-qubit gates in the standard decomposition
+                (1.0 - self.pair_error(*control1, *target)).powi(3)
+                    * (1.0 - self.pair_error(*control2, *target)).powi(3)
+                    * single.powi(6)
+            }
+            QuantumGate::Diagonal { qubits, .. } => {
+                let cnots = diagonal_cnots(qubits.len()) as i32;
+                (1.0 - self.two_qubit_error).powi(cnots) * single.powi(cnots + 1)
+            }
+            QuantumGate::UnitaryNQ { qubits, .. } => match qubits.as_slice() {
+                [qubit] => 1.0 - self.qubit_error(*qubit),
+                // Two-qubit blocks need three CNOTs on the pair
+                [qubit1, qubit2] => (1.0 - self.pair_error(*qubit1, *qubit2)).powi(3) * single.powi(4),
+                _ => {
+                    let cnots = unitary_cnots(qubits.len()) as i32;
+                    (1.0 - self.two_qubit_error).powi(cnots) * single.powi(cnots + qubits.len() as i32)
+                }
+            },
+            QuantumGate::Controlled { control, gate } => match gate.qubits().as_slice() {
+                [target] => {
+                    let cnots = controlled_cnots(gate) as i32;
+                    (1.0 - self.pair_error(*control, *target)).powi(cnots) * single.powi(2 * cnots - 1)
+                }
+                _ => {
+                    let cnots = controlled_cnots(gate) as i32;
+                    (1.0 - self.two_qubit_error).powi(cnots) * single.powi(cnots + 1)
+                }
+            },
+            // One single-qubit gate per qubit
+            QuantumGate::GlobalHadamard { qubits } | QuantumGate::GlobalRotation { qubits, .. } => {
+                qubits.iter().map(|&qubit| 1.0 - self.qubit_error(qubit)).product()
+            }
+            // CNOT parity ladder around one RZ; ladder pairs are not fixed, so use the default pair error
+            QuantumGate::PauliRotation { pauli, .. } => {
+                let cnots = pauli_rotation_cnots(pauli) as i32;
+                (1.0 - self.two_qubit_error).powi(cnots) * single.powi(cnots + 1)
+            }
+            QuantumGate::MCX { controls, target } => match controls.as_slice() {
+                [] => 1.0 - self.qubit_error(*target),
+                [control] => 1.0 - self.pair_error(*control, *target),
+                _ => {
+                    // Ancilla pairs are not known up front, so use the default pair error
+                    let toffolis = mcx_toffolis(controls.len()) as i32;
+                    (1.0 - self.two_qubit_error).powi(6 * toffolis) * single.powi(6 * toffolis)
+                }
+            },
+            gate => match gate.qubits().as_slice() {
+                [qubit] => 1.0 - self.qubit_error(*qubit),
+                _ => single,
+            },
+        }
+    }
+
+    /// Execution time of a gate in nanoseconds
+    pub fn gate_duration_ns(&self, gate: &QuantumGate) -> f64 {
+        match gate {
+            QuantumGate::Noisy { noise, gate } => noise.duration_ns.unwrap_or_else(|| self.gate_duration_ns(gate)),
+            QuantumGate::Measurement { .. } => self.measurement_time_ns,
+            QuantumGate::Reset { .. } => self.measurement_time_ns + self.single_qubit_time_ns,
+            QuantumGate::Snapshot { .. } => 0.0,
+            QuantumGate::IfBit { gate, .. } => self.gate_duration_ns(gate),
+            QuantumGate::CNOT { .. }
+            | QuantumGate::CZ { .. }
+            | QuantumGate::CPhase { .. }
+            | QuantumGate::ISWAP { .. }
+            | QuantumGate::SqrtISWAP { .. }
+            | QuantumGate::ECR { .. }
+            | QuantumGate::RXX { .. }
+            | QuantumGate::RYY { .. }
+            | QuantumGate::RZZ { .. } => self.two_qubit_time_ns,
+            QuantumGate::Givens { .. } => 2.0 * self.two_qubit_time_ns + 3.0 * self.single_qubit_time_ns,
+            QuantumGate::SWAP { .. } => 3.0 * self.two_qubit_time_ns,
+            QuantumGate::Permutation { qubits, pattern } => {
+                3.0 * self.two_qubit_time_ns * qsim::permutation_swaps(qubits, pattern).len() as f64
+            }
+            QuantumGate::Toffoli { .. } => 6.0 * self.two_qubit_time_ns + 6.0 * self.single_qubit_time_ns,
+            // Basis change, RZ and basis change around the ladder
+            QuantumGate::PauliRotation { pauli, .. } => {
+                pauli_rotation_cnots(pauli) as f64 * self.two_qubit_time_ns + 3.0 * self.single_qubit_time_ns
+            }
+            QuantumGate::Controlled { gate, .. } => {
+                let cnots = controlled_cnots(gate) as f64;
+                cnots * self.two_qubit_time_ns + (cnots + 1.0) * self.single_qubit_time_ns
+            }
+            QuantumGate::Diagonal { qubits, .. } => {
+                let cnots = diagonal_cnots(qubits.len()) as f64;
+                cnots * self.two_qubit_time_ns + (cnots + 1.0) * self.single_qubit_time_ns
+            }
+            QuantumGate::UnitaryNQ { qubits, .. } => {
+                let cnots = unitary_cnots(qubits.len()) as f64;
+                cnots * self.two_qubit_time_ns + (cnots + 1.0) * self.single_qubit_time_ns
+            }
+            QuantumGate::MCX { controls, .. } => match controls.len() {
+                0 => self.single_qubit_time_ns,
+                1 => self.two_qubit_time_ns,
+                n => mcx_toffolis(n) as f64 * (6.0 * self.two_qubit_time_ns + 6.0 * self.single_qubit_time_ns),
+            },
+            _ => self.single_qubit_time_ns,
+        }
+    }
+
+    /// Estimated probability that the whole circuit runs without error
+    pub fn estimate_fidelity(&self, circuit: &QuantumCircuit) -> f64 {
+        circuit.gates.iter().map(|gate| self.gate_fidelity(gate)).product()
+    }
+}
+
+/// Kraus operators of thermal relaxation over `duration_ns`: amplitude damping with
+/// γ = 1 - e^(-t/T1), then pure dephasing that brings the coherence decay to e^(-t/T2).
+/// A missing T1 means no damping, and a missing T2 no dephasing beyond the damping's.
+pub fn thermal_relaxation_kraus(duration_ns: f64, t1_us: Option<f64>, t2_us: Option<f64>) -> Vec<Vec<Vec<Complex>>> {
+    let t = duration_ns / 1000.0;
+    let gamma = t1_us.map_or(0.0, |t1| 1.0 - (-t / t1).exp());
+    // 1/Tφ = 1/T2 - 1/(2 T1), and phase damping by λ scales coherences by √(1 - λ)
+    let lambda = t2_us.map_or(0.0, |t2| {
+        let rate = 1.0 / t2 - t1_us.map_or(0.0, |t1| 0.5 / t1);
+        1.0 - (-2.0 * t * rate.max(0.0)).exp()
+    });
+    let real = |x: f64| Complex::new(x, 0.0);
+    let zero = real(0.0);
+    vec![
+        vec![vec![real(1.0), zero], vec![zero, real(((1.0 - gamma) * (1.0 - lambda)).sqrt())]],
+        vec![vec![zero, real(gamma.sqrt())], vec![zero, zero]],
+        vec![vec![zero, zero], vec![zero, real((lambda * (1.0 - gamma)).sqrt())]],
+    ]
+}
+
+/// Kraus operators of a ZZ error on two qubits with probability p
+pub fn zz_error_kraus(p: f64) -> Vec<Vec<Vec<Complex>>> {
+    let diagonal = |entries: [f64; 4]| -> Vec<Vec<Complex>> {
+        (0..4).map(|row| (0..4).map(|col| Complex::new(if row == col { entries[row] } else { 0.0 }, 0.0)).collect()).collect()
+    };
+    let (keep, flip) = ((1.0 - p).sqrt(), p.sqrt());
+    vec![diagonal([keep; 4]), diagonal([flip, -flip, -flip, flip])]
+}
+
+/// Kraus operators of X, Y and Z errors with probabilities px, py and pz
+fn pauli_kraus(px: f64, py: f64, pz: f64) -> Vec<Vec<Vec<Complex>>> {
+    let zero = Complex::new(0.0, 0.0);
+    let scaled = |p: f64, matrix: [[Complex; 2]; 2]| -> Vec<Vec<Complex>> {
+        let scale = Complex::new(p.max(0.0).sqrt(), 0.0);
+        matrix.iter().map(|row| row.iter().map(|&entry| entry * scale).collect()).collect()
+    };
+    let (one, minus_one) = (Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0));
+    vec![
+        scaled(1.0 - px - py - pz, [[one, zero], [zero, one]]),
+        scaled(px, [[zero, one], [one, zero]]),
+        scaled(py, [[zero, Complex::new(0.0, -1.0)], [Complex::new(0.0, 1.0), zero]]),
+        scaled(pz, [[one, zero], [zero, minus_one]]),
+    ]
+}
+
+/// Kraus operators of depolarizing noise on k qubits, in Stim's convention: with
+/// probability p one of the 4^k - 1 non-identity Pauli strings, chosen uniformly
+pub fn depolarizing_kraus(p: f64, width: usize) -> Vec<Vec<Vec<Complex>>> {
+    let zero = Complex::new(0.0, 0.0);
+    let one = Complex::new(1.0, 0.0);
+    let i = Complex::new(0.0, 1.0);
+    let paulis = [
+        [[one, zero], [zero, one]],
+        [[zero, one], [one, zero]],
+        [[zero, Complex::new(0.0, -1.0)], [i, zero]],
+        [[one, zero], [zero, Complex::new(-1.0, 0.0)]],
+    ];
+    let strings = 1usize << (2 * width);
+    let dim = 1usize << width;
+    (0..strings)
+        .map(|string| {
+            let weight = if string == 0 { 1.0 - p } else { p / (strings - 1) as f64 };
+            let scale = Complex::new(weight.max(0.0).sqrt(), 0.0);
+            // Pauli of qubit k is digit k of `string` in base 4, acting on bit k of the indices
+            (0..dim)
+                .map(|row| {
+                    (0..dim)
+                        .map(|col| {
+                            (0..width).fold(scale, |entry, k| {
+                                entry * paulis[(string >> (2 * k)) & 3][(row >> k) & 1][(col >> k) & 1]
+                            })
+                        })
+                        .collect()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// `type` a gate is written with in circuit files, e.g. `CNOT`
+fn gate_type(gate: &QuantumGate) -> String {
+    serde_json::to_value(gate)
+        .ok()
+        .and_then(|gate| gate["type"].as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// The rotation angle of a gate that has one: θ of `U`, the angle of the others
+fn rotation_angle_mut(gate: &mut QuantumGate) -> Option<&mut f64> {
+    match gate {
+        QuantumGate::RotationX { angle, .. }
+        | QuantumGate::RotationY { angle, .. }
+        | QuantumGate::RotationZ { angle, .. }
+        | QuantumGate::Phase { angle, .. }
+        | QuantumGate::CPhase { angle, .. }
+        | QuantumGate::RXX { angle, .. }
+        | QuantumGate::RYY { angle, .. }
+        | QuantumGate::RZZ { angle, .. }
+        | QuantumGate::Givens { angle, .. }
+        | QuantumGate::PauliRotation { angle, .. }
+        | QuantumGate::GlobalRotation { angle, .. } => Some(angle),
+        QuantumGate::U { theta, .. } => Some(theta),
+        _ => None,
+    }
+}
+
+/// Toffolis in the ancilla V-chain decomposition of an MCX with two or more controls
+pub fn mcx_toffolis(controls: usize) -> usize {
+    2 * controls.max(2) - 3
+}
+
+/// CNOTs in a generic k-qubit unitary synthesis, using the lower bound ceil((4^k - 3k - 1) / 4)
+pub fn unitary_cnots(qubits: usize) -> usize {
+    let k = qubits.min(16) as u32;
+    (4usize.pow(k) - 3 * k as usize - 1).div_ceil(4)
+}
+
+/// CNOTs in a Gray-code synthesis of a k-qubit diagonal: 2^k - 2, interleaved with RZs
+pub fn diagonal_cnots(qubits: usize) -> usize {
+    (1usize << qubits).saturating_sub(2)
+}
+
+/// CNOTs to add one control to a gate: one for Paulis (CX, CY, CZ up to single-qubit
+/// gates), two for other single-qubit gates, generic synthesis otherwise
+pub fn controlled_cnots(gate: &QuantumGate) -> usize {
+    match gate {
+        QuantumGate::PauliX { .. } | QuantumGate::PauliY { .. } | QuantumGate::PauliZ { .. } => 1,
+        QuantumGate::CNOT { .. } => 6,
+        // Each qubit of the layer gets its own controlled single-qubit gate
+        QuantumGate::GlobalHadamard { qubits } | QuantumGate::GlobalRotation { qubits, .. } => 2 * qubits.len(),
+        // Only the RZ inside the ladder needs the control
+        QuantumGate::PauliRotation { pauli, .. } => pauli_rotation_cnots(pauli) + 2,
+        // One controlled SWAP per transposition
+        QuantumGate::Permutation { qubits, pattern } => {
+            qsim::permutation_swaps(qubits, pattern).len() * unitary_cnots(3)
+        }
+        gate => match gate.qubits().len() {
+            1 => 2,
+            n => unitary_cnots(n + 1),
+        },
+    }
+}
+
+/// CNOTs in the parity ladder of a Pauli-string rotation
+pub fn pauli_rotation_cnots(pauli: &str) -> usize {
+    2 * qsim::pauli_weight(pauli).saturating_sub(1)
+}
+
+/// Rewrite gates written with an inline `noise` field, such as
+/// `{"type": "CNOT", "control": 0, "target": 1, "noise": {"error": 0.05}}`, into `Noisy`
+/// wrappers, in the gate list and in macro bodies
+pub fn wrap_inline_overrides(document: &mut Value) -> Result<(), String> {
+    if let Some(Value::Array(entries)) = document.get_mut("gates") {
+        entries.iter_mut().try_for_each(wrap_entry)?;
+    }
+    if let Some(Value::Object(definitions)) = document.get_mut("macros") {
+        for definition in definitions.values_mut() {
+            if let Some(Value::Array(entries)) = definition.get_mut("gates") {
+                entries.iter_mut().try_for_each(wrap_entry)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn wrap_entry(entry: &mut Value) -> Result<(), String> {
+    let Some(fields) = entry.as_object_mut() else {
+        return Ok(());
+    };
+    if let Some(inner) = fields.get_mut("gate") {
+        wrap_entry(inner)?;
+    }
+    let wrapper = matches!(fields.get("type").and_then(Value::as_str), Some("Noisy" | "noisy"));
+    if wrapper || !fields.contains_key("noise") {
+        return Ok(());
+    }
+    if fields.contains_key("macro") {
+        return Err("noise overrides apply to single gates, not macro calls".to_string());
+    }
+    let noise = fields.remove("noise");
+    *entry = json!({ "type": "Noisy", "noise": noise, "gate": entry.take() });
+    Ok(())
+}
+
+/// Load a noise model from a JSON or YAML file
+pub fn load_noise_model(path: &str) -> Result<NoiseModel, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    Ok(parse_noise_model(&contents, path.ends_with(".yaml") || path.ends_with(".yml"))?)
+}
+
+/// Parse and validate a noise model file's contents, as YAML or JSON. The CLI's `--noise`
+/// and the server's `PUT /api/noise/:name` read the same format.
+pub fn parse_noise_model(contents: &str, yaml: bool) -> Result<NoiseModel, String> {
+    let model: NoiseModel = if yaml {
+        serde_yaml::from_str(contents).map_err(|e| e.to_string())?
+    } else {
+        serde_json::from_str(contents).map_err(|e| e.to_string())?
+    };
+    model.validate()?;
+    Ok(model)
+}
+
+/// How a noisy simulation carries the mixed state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseMode {
+    /// Exact ρ → Σ KρK† on a 4^n density matrix
+    Density,
+    /// One Kraus branch sampled per channel on a pure state vector, averaged over runs
+    Trajectory,
+}
+
+impl NoiseMode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "density" => Some(NoiseMode::Density),
+            "trajectory" | "trajectories" => Some(NoiseMode::Trajectory),
+            _ => None,
+        }
+    }
+
+    /// Mode used when none is chosen: exact while the density matrix stays small
+    pub fn for_qubits(num_qubits: usize) -> Self {
+        if num_qubits <= MAX_DENSITY_QUBITS {
+            NoiseMode::Density
+        } else {
+            NoiseMode::Trajectory
+        }
+    }
+
+    fn representation(self) -> &'static str {
+        match self {
+            NoiseMode::Density => "density",
+            NoiseMode::Trajectory => simulator_backend::DEFAULT_REPRESENTATION,
+        }
+    }
+}
+
+/// The state vector simulator with a noise model's depolarizing errors, thermal relaxation
+/// and channels after every gate, selected by `simulate --noise`
+pub struct NoisyBackend {
+    model: Arc<NoiseModel>,
+    mode: Option<NoiseMode>,
+    trajectories: usize,
+    /// Threads trajectories are split across, each holding its own state vector
+    threads: usize,
+}
+
+impl NoisyBackend {
+    pub fn new(model: NoiseModel) -> Self {
+        Self {
+            model: Arc::new(model),
+            mode: None,
+            trajectories: DEFAULT_TRAJECTORIES,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    /// The chosen mode, or trajectories when random rotation errors need a draw per run
+    fn mode(&self, circuit: &QuantumCircuit) -> NoiseMode {
+        self.mode.unwrap_or_else(|| {
+            if self.model.has_random_rotations() {
+                NoiseMode::Trajectory
+            } else {
+                NoiseMode::for_qubits(circuit.num_qubits)
+            }
+        })
+    }
+
+    fn simulator(&self, mode: NoiseMode, num_qubits: usize, seed: u64) -> QuantumSimulator {
+        let state = simulator_backend::create(mode.representation(), num_qubits).expect("built-in representation");
+        QuantumSimulator::with_backend(state, seed).with_noise(self.model.clone())
+    }
+
+    fn run(&self, circuit: &QuantumCircuit, mode: NoiseMode, seed: u64) -> QuantumSimulator {
+        let mut simulator = self.simulator(mode, circuit.num_qubits, seed);
+        for gate in &circuit.gates {
+            simulator.apply_gate(gate);
+        }
+        simulator
+    }
+}
+
+impl StateBackend for NoisyBackend {
+    fn name(&self) -> &str {
+        "noisy"
+    }
+
+    fn description(&self) -> &str {
+        "state vector or density matrix with a noise model's gate errors and channels (--noise)"
+    }
+
+    fn probabilities(&self, circuit: &QuantumCircuit, seed: u64) -> Result<Vec<f64>, String> {
+        match self.mode(circuit) {
+            NoiseMode::Density => Ok(self.run(circuit, NoiseMode::Density, seed).measure_all()),
+            NoiseMode::Trajectory => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let seeds: Vec<u64> = (0..self.trajectories).map(|_| rng.gen()).collect();
+                let chunk = self.trajectories.div_ceil(self.threads);
+                let kernel_threads = (gpu_ops::kernel_threads() / self.threads).max(1);
+                // Each thread sums the distributions of its own trajectories
+                let sums: Vec<Vec<f64>> = std::thread::scope(|scope| {
+                    let workers: Vec<_> = seeds
+                        .chunks(chunk)
+                        .map(|seeds| {
+                            scope.spawn(move || {
+                                gpu_ops::set_kernel_threads(kernel_threads);
+                                let mut sum = vec![0.0; 1 << circuit.num_qubits];
+                                for &seed in seeds {
+                                    let probabilities = self.run(circuit, NoiseMode::Trajectory, seed).measure_all();
+                                    for (total, probability) in sum.iter_mut().zip(probabilities) {
+                                        *total += probability;
+                                    }
+                                }
+                                sum
+                            })
+                        })
+                        .collect();
+                    workers.into_iter().map(|worker| worker.join().expect("trajectory thread panicked")).collect()
+                });
+                let mut mean = vec![0.0; 1 << circuit.num_qubits];
+                for sum in sums {
+                    for (entry, total) in mean.iter_mut().zip(sum) {
+                        *entry += total / self.trajectories as f64;
+                    }
+                }
+                Ok(mean)
+            }
+        }
+    }
+
+    fn sample(&self, circuit: &QuantumCircuit, shots: usize, seed: u64) -> Result<Vec<Shot>, String> {
+        let mode = self.mode(circuit);
+        let create = |seed| self.simulator(mode, circuit.num_qubits, seed);
+        let mut rng = StdRng::seed_from_u64(seed);
+        Ok(match mode {
+            NoiseMode::Density => qsim::sample_circuit_with(circuit, shots, &mut rng, create),
+            // Each shot needs its own branches
+            NoiseMode::Trajectory => qsim::sample_trajectories_threaded(circuit, shots, self.threads, &mut rng, create),
+        })
+    }
+
+    fn with_option(&self, name: &str, value: &str) -> Result<Arc<dyn StateBackend>, String> {
+        let (mut mode, mut trajectories, mut threads) = (self.mode, self.trajectories, self.threads);
+        match name {
+            "noise-mode" => {
+                mode = Some(NoiseMode::parse(value).ok_or("--noise-mode expects density or trajectory")?);
+            }
+            "trajectories" => {
+                trajectories = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("--trajectories expects a positive integer")?;
+            }
+            "threads" => {
+                threads = value.parse::<usize>().ok().filter(|&n| n > 0).ok_or("--threads expects a positive integer")?;
+            }
+            _ => return Err(format!("the {} backend has no --{} option", self.name(), name)),
+        }
+        Ok(Arc::new(NoisyBackend { model: self.model.clone(), mode, trajectories, threads }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Noiseless apart from the given changes: no gate, readout or coherence errors, and 1 µs
+    /// single-qubit gates
+    fn quiet_model() -> NoiseModel {
+        NoiseModel {
+            single_qubit_error: 0.0,
+            two_qubit_error: 0.0,
+            measurement_error: 0.0,
+            single_qubit_time_ns: 1000.0,
+            ..NoiseModel::default()
+        }
+    }
+
+    fn density_probabilities(model: NoiseModel, circuit: &QuantumCircuit) -> Vec<f64> {
+        let backend = NoisyBackend::new(model).with_option("noise-mode", "density").unwrap();
+        backend.probabilities(circuit, 0).unwrap()
+    }
+
+    fn circuit(num_qubits: usize, gates: Vec<QuantumGate>) -> QuantumCircuit {
+        QuantumCircuit { num_qubits, gates, metadata: Default::default() }
+    }
+
+    fn assert_close(actual: &[f64], expected: &[f64], tolerance: f64) {
+        assert_eq!(actual.len(), expected.len());
+        for (index, (a, e)) in actual.iter().zip(expected).enumerate() {
+            assert!((a - e).abs() < tolerance, "entry {} is {}, expected {}", index, a, e);
+        }
+    }
+
+    #[test]
+    fn depolarizing_matches_stim_convention() {
+        let p = 0.09;
+        // X and Y of the three Paulis flip the bit
+        let model = NoiseModel { single_qubit_error: p, ..quiet_model() };
+        let probabilities = density_probabilities(model, &circuit(1, vec![QuantumGate::PauliX { qubit: 0 }]));
+        assert_close(&probabilities, &[2.0 * p / 3.0, 1.0 - 2.0 * p / 3.0], 1e-12);
+
+        // 4 of the 15 Pauli pairs flip only the first qubit, 4 only the second, 4 both
+        let model = NoiseModel { two_qubit_error: p, ..quiet_model() };
+        let probabilities = density_probabilities(model, &circuit(2, vec![QuantumGate::CNOT { control: 0, target: 1 }]));
+        let flip = 4.0 * p / 15.0;
+        assert_close(&probabilities, &[1.0 - 3.0 * flip, flip, flip, flip], 1e-12);
+    }
+
+    #[test]
+    fn wide_gates_depolarize_at_their_decomposition_fidelity() {
+        let model = NoiseModel { single_qubit_error: 0.002, two_qubit_error: 0.03, ..NoiseModel::default() };
+        let toffoli = QuantumGate::Toffoli { control1: 0, control2: 1, target: 2 };
+        let fidelity = model.gate_fidelity(&toffoli);
+        let channels = model.channels_after(&toffoli);
+        assert_eq!(channels.len(), 3);
+        // Each qubit keeps its state with probability 1 - p, the identity's Kraus weight
+        let kept: f64 = channels.iter().map(|(_, kraus)| kraus[0][0][0].magnitude_squared()).product();
+        assert!((kept - fidelity).abs() < 1e-12, "{} kept, fidelity {}", kept, fidelity);
+    }
+
+    #[test]
+    fn relaxation_matches_t1_and_t2_decay() {
+        let (t1, t2) = (10.0, 15.0);
+        let model = || NoiseModel { t1_us: Some(t1), t2_us: Some(t2), ..quiet_model() };
+        // Pairs of Z leave the state alone while the qubit idles for 1 µs per gate
+        let idle = |pairs: usize| (0..2 * pairs).map(|_| QuantumGate::PauliZ { qubit: 0 });
+
+        // |1> decays to |0> as e^(-t/T1), counting the X itself
+        let gates = std::iter::once(QuantumGate::PauliX { qubit: 0 }).chain(idle(3)).collect();
+        let probabilities = density_probabilities(model(), &circuit(1, gates));
+        let excited = (-7.0 / t1).exp();
+        assert_close(&probabilities, &[1.0 - excited, excited], 1e-12);
+
+        // Coherence decays as e^(-t/T2); the last H turns it into the population of |0>, and
+        // relaxation after it no longer matters for the H-basis coherence it read
+        let mut gates: Vec<QuantumGate> = std::iter::once(QuantumGate::Hadamard { qubit: 0 }).chain(idle(2)).collect();
+        gates.push(QuantumGate::Hadamard { qubit: 0 });
+        let probabilities = density_probabilities(model(), &circuit(1, gates));
+        let coherence = (-5.0 / t2).exp();
+        // Damping during the last H moves population 1 - e^(-1/T1) of |1> into |0>
+        let decay = 1.0 - (-1.0 / t1).exp();
+        let zero = (1.0 + coherence) / 2.0;
+        assert_close(&probabilities, &[zero + (1.0 - zero) * decay, (1.0 - zero) * (1.0 - decay)], 1e-12);
+    }
+
+    #[test]
+    fn trajectories_average_to_the_density_matrix() {
+        let model = NoiseModel {
+            single_qubit_error: 0.02,
+            two_qubit_error: 0.05,
+            measurement_error: 0.0,
+            t1_us: Some(20.0),
+            t2_us: Some(25.0),
+            coupling_map: vec![[0, 1], [1, 2]],
+            crosstalk_error: 0.03,
+            ..NoiseModel::default()
+        };
+        let noisy = circuit(
+            3,
+            vec![
+                QuantumGate::Hadamard { qubit: 0 },
+                QuantumGate::RotationY { qubit: 1, angle: 0.8 },
+                QuantumGate::CNOT { control: 0, target: 1 },
+                QuantumGate::Toffoli { control1: 0, control2: 1, target: 2 },
+                QuantumGate::RotationX { qubit: 2, angle: 1.3 },
+                QuantumGate::CZ { control: 2, target: 0 },
+                QuantumGate::Hadamard { qubit: 1 },
+            ],
+        );
+        let density = density_probabilities(model.clone(), &noisy);
+        let trajectories = NoisyBackend::new(model)
+            .with_option("noise-mode", "trajectory")
+            .and_then(|backend| backend.with_option("trajectories", "4000"))
+            .unwrap();
+        // Each trajectory contributes its whole distribution, so the mean is close well
+        // before 4000 shots would be
+        assert_close(&trajectories.probabilities(&noisy, 7).unwrap(), &density, 0.01);
+    }
+}
@@ -0,0 +1,84 @@
+//! NumPy Module
+//! `.npy` encoding of state vectors (complex128 layout)
+
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::gpu_ops::Complex;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Encode amplitudes as a one-dimensional complex128 `.npy` array
+pub fn encode_complex(data: &[Complex]) -> Vec<u8> {
+    encode(data, &format!("({},)", data.len()))
+}
+
+/// Encode a matrix as a two-dimensional row-major complex128 `.npy` array
+pub fn encode_complex_matrix(matrix: &[Vec<Complex>]) -> Vec<u8> {
+    let cols = matrix.first().map_or(0, |row| row.len());
+    let data: Vec<Complex> = matrix.iter().flatten().copied().collect();
+    encode(&data, &format!("({}, {})", matrix.len(), cols))
+}
+
+/// complex128 `.npy` bytes of `data` with a header declaring `shape`, a Python tuple
+fn encode(data: &[Complex], shape: &str) -> Vec<u8> {
+    let mut header = format!("{{'descr': '<c16', 'fortran_order': False, 'shape': {}, }}", shape);
+    // Magic (6) + version (2) + header length (2) + header must be 64-byte aligned
+    let unpadded = MAGIC.len() + 4 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    let mut out = Vec::with_capacity(MAGIC.len() + 4 + header.len() + data.len() * 16);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&[1, 0]);
+    out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    out.extend_from_slice(header.as_bytes());
+    for amplitude in data {
+        out.extend_from_slice(&amplitude.re.to_le_bytes());
+        out.extend_from_slice(&amplitude.im.to_le_bytes());
+    }
+    out
+}
+
+/// Write amplitudes to a `.npy` file
+pub fn save_state(path: &str, data: &[Complex]) -> io::Result<()> {
+    fs::write(path, encode_complex(data))
+}
+
+/// Write a matrix to a `.npy` file
+pub fn save_matrix(path: &str, matrix: &[Vec<Complex>]) -> io::Result<()> {
+    fs::write(path, encode_complex_matrix(matrix))
+}
+
+/// Decode a one-dimensional complex128 `.npy` array, as written by `encode_complex`
+pub fn decode_complex(bytes: &[u8]) -> Result<Vec<Complex>, String> {
+    if bytes.len() < MAGIC.len() + 4 || &bytes[..MAGIC.len()] != MAGIC {
+        return Err("not a .npy file".to_string());
+    }
+    let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    let data_start = MAGIC.len() + 4 + header_len;
+    let header = bytes
+        .get(MAGIC.len() + 4..data_start)
+        .and_then(|header| std::str::from_utf8(header).ok())
+        .ok_or("truncated .npy header")?;
+    if !header.contains("'<c16'") || header.contains("'fortran_order': True") {
+        return Err("expected a complex128 array".to_string());
+    }
+    let chunks = bytes[data_start..].chunks_exact(16);
+    if !chunks.remainder().is_empty() {
+        return Err("truncated .npy data".to_string());
+    }
+    Ok(chunks
+        .map(|chunk| {
+            let re = f64::from_le_bytes(chunk[..8].try_into().unwrap());
+            let im = f64::from_le_bytes(chunk[8..].try_into().unwrap());
+            Complex::new(re, im)
+        })
+        .collect())
+}
+
+/// Read amplitudes from a `.npy` file
+pub fn load_state(path: &Path) -> Result<Vec<Complex>, Box<dyn Error>> {
+    Ok(decode_complex(&fs::read(path)?)?)
+}
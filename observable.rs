@@ -0,0 +1,342 @@
+//! Observable Module
+//! Weighted sums of Pauli strings and their expectation values in a state vector
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::gpu_ops::Complex;
+use crate::qasm::eval_angle;
+use crate::qsim::QuantumGate;
+
+/// One weighted Pauli string; `pauli[k]` (`I`, `X`, `Y` or `Z`) acts on qubit k
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PauliTerm {
+    #[serde(default = "unit_coefficient")]
+    pub coefficient: f64,
+    pub pauli: String,
+}
+
+fn unit_coefficient() -> f64 {
+    1.0
+}
+
+impl PauliTerm {
+    /// (X mask, Z mask, number of Ys) of the string, with qubit k at state-index bit `bit(k)`;
+    /// a Y sets both masks
+    pub fn masks<F: Fn(usize) -> usize>(&self, bit: F) -> (usize, usize, usize) {
+        let (mut x_mask, mut z_mask, mut y_count) = (0, 0, 0);
+        for (qubit, p) in self.pauli.chars().enumerate() {
+            match p {
+                'X' => x_mask |= 1 << bit(qubit),
+                'Y' => {
+                    x_mask |= 1 << bit(qubit);
+                    z_mask |= 1 << bit(qubit);
+                    y_count += 1;
+                }
+                'Z' => z_mask |= 1 << bit(qubit),
+                _ => {}
+            }
+        }
+        (x_mask, z_mask, y_count)
+    }
+
+    /// Gates that rotate the string's X and Y factors onto Z, so measuring the qubits in the
+    /// computational basis measures the string
+    pub fn basis_change(&self) -> Vec<QuantumGate> {
+        let mut gates = Vec::new();
+        for (qubit, p) in self.pauli.chars().enumerate() {
+            match p {
+                'X' => gates.push(QuantumGate::Hadamard { qubit }),
+                'Y' => gates.extend([QuantumGate::Sdg { qubit }, QuantumGate::Hadamard { qubit }]),
+                _ => {}
+            }
+        }
+        gates
+    }
+}
+
+/// Hermitian observable H = sum of coefficient * Pauli string. Deserializes from a term
+/// list or from text such as `"0.5*ZZI - XXI + YYI"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "ObservableRepr", into = "Vec<PauliTerm>")]
+pub struct PauliSum {
+    pub terms: Vec<PauliTerm>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ObservableRepr {
+    Text(String),
+    Terms(Vec<PauliTerm>),
+}
+
+impl TryFrom<ObservableRepr> for PauliSum {
+    type Error = String;
+
+    fn try_from(repr: ObservableRepr) -> Result<Self, String> {
+        match repr {
+            ObservableRepr::Text(text) => PauliSum::parse(&text),
+            ObservableRepr::Terms(terms) => Ok(PauliSum { terms }),
+        }
+    }
+}
+
+impl From<PauliSum> for Vec<PauliTerm> {
+    fn from(sum: PauliSum) -> Self {
+        sum.terms
+    }
+}
+
+impl fmt::Display for PauliSum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, term) in self.terms.iter().enumerate() {
+            let magnitude = term.coefficient.abs();
+            match (index, term.coefficient < 0.0) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+            if magnitude != 1.0 {
+                write!(f, "{}*", magnitude)?;
+            }
+            write!(f, "{}", term.pauli)?;
+        }
+        Ok(())
+    }
+}
+
+impl PauliSum {
+    /// Parse `[coefficient*]PAULI` terms joined by `+` or `-`; coefficients may use `pi`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut chunks = Vec::new();
+        let (mut start, mut depth) = (0, 0);
+        for (i, c) in compact.char_indices() {
+            // A sign starts a new term unless it is leading, parenthesized, an exponent sign
+            // or the sign of a factor
+            let previous = compact[..i].chars().last();
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                '+' | '-' if i > 0 && depth == 0 && !matches!(previous, Some('e' | 'E' | '*' | '/')) => {
+                    chunks.push(&compact[start..i]);
+                    start = i;
+                }
+                _ => {}
+            }
+        }
+        chunks.push(&compact[start..]);
+
+        let mut terms = Vec::new();
+        for chunk in chunks {
+            let (sign, body) = match chunk.strip_prefix('-') {
+                Some(body) => (-1.0, body),
+                None => (1.0, chunk.strip_prefix('+').unwrap_or(chunk)),
+            };
+            let (coefficient, pauli) = match body.rsplit_once('*') {
+                Some((coefficient, pauli)) => (eval_angle(coefficient)?, pauli),
+                None => (1.0, body),
+            };
+            if pauli.is_empty() {
+                return Err(format!("term '{}' has no Pauli string", chunk));
+            }
+            terms.push(PauliTerm { coefficient: sign * coefficient, pauli: pauli.to_uppercase() });
+        }
+        Ok(PauliSum { terms })
+    }
+
+    /// Check that there is at least one term and every string has one Pauli per qubit
+    pub fn validate(&self, num_qubits: usize) -> Result<(), String> {
+        if self.terms.is_empty() {
+            return Err("observable has no terms".to_string());
+        }
+        for term in &self.terms {
+            if term.pauli.chars().count() != num_qubits {
+                return Err(format!(
+                    "Pauli string '{}' needs one of I, X, Y or Z per qubit ({} qubits)",
+                    term.pauli, num_qubits
+                ));
+            }
+            if let Some(p) = term.pauli.chars().find(|p| !"IXYZ".contains(*p)) {
+                return Err(format!("Pauli string '{}' has '{}'; use I, X, Y or Z", term.pauli, p));
+            }
+            if !term.coefficient.is_finite() {
+                return Err(format!("Pauli string '{}' has a coefficient that is not finite", term.pauli));
+            }
+        }
+        Ok(())
+    }
+
+    /// Dense 2^n x 2^n matrix of the sum on `num_qubits` qubits, with qubit 0 the least
+    /// significant bit of the row and column index; for exact work on small systems
+    pub fn to_matrix(&self, num_qubits: usize) -> Vec<Vec<Complex>> {
+        let dim = 1 << num_qubits;
+        let mut matrix = vec![vec![Complex::new(0.0, 0.0); dim]; dim];
+        // i^(#Y), the phase every column of a string picks up
+        let phases = [Complex::new(1.0, 0.0), Complex::new(0.0, 1.0), Complex::new(-1.0, 0.0), Complex::new(0.0, -1.0)];
+        for term in &self.terms {
+            let (x_mask, z_mask, y_count) = term.masks(|qubit| qubit);
+            // Each row has one nonzero entry, in the column the string flips it to
+            for (row, entries) in matrix.iter_mut().enumerate() {
+                let column = row ^ x_mask;
+                let sign = if (column & z_mask).count_ones() % 2 == 1 { -1.0 } else { 1.0 };
+                entries[column] = entries[column] + phases[y_count % 4] * Complex::new(sign * term.coefficient, 0.0);
+            }
+        }
+        matrix
+    }
+}
+
+/// ⟨ψ|P|ψ⟩ for the Pauli string P with the given masks (see `PauliTerm::masks`), in one
+/// pass over the amplitudes
+pub fn pauli_expectation(state: &[Complex], x_mask: usize, z_mask: usize, y_count: usize) -> f64 {
+    // P|i> = i^(#Y) (-1)^(|i & z_mask|) |i ^ x_mask>, so only the phase of each term varies
+    let total = state.iter().enumerate().fold(Complex::new(0.0, 0.0), |sum, (i, amplitude)| {
+        let sign = if (i & z_mask).count_ones() % 2 == 1 { -1.0 } else { 1.0 };
+        sum + state[i ^ x_mask].conjugate() * *amplitude * Complex::new(sign, 0.0)
+    });
+    // Multiply by i^(#Y); the expectation of a Hermitian P is real
+    match y_count % 4 {
+        0 => total.re,
+        1 => -total.im,
+        2 => -total.re,
+        _ => total.im,
+    }
+}
+
+/// ⟨H⟩ estimated from shots, with its sampling error
+#[derive(Debug, Clone, Serialize)]
+pub struct ShotEstimate {
+    pub mean: f64,
+    /// Variance per shot, so `standard_error = sqrt(variance / shots)`
+    pub variance: f64,
+    pub standard_error: f64,
+    /// Shots over all measured terms
+    pub shots: usize,
+    /// Mean of each term's Pauli string, in term order (1 for identity strings)
+    pub term_means: Vec<f64>,
+    /// Shots spent on each term
+    pub term_shots: Vec<usize>,
+}
+
+impl ShotEstimate {
+    /// ⟨H⟩ from exact per-term expectations, such as Pauli propagation's, with no sampling error
+    pub fn exact(observable: &PauliSum, term_means: Vec<f64>) -> Self {
+        let mean = observable.terms.iter().zip(&term_means).map(|(term, mean)| term.coefficient * mean).sum();
+        ShotEstimate { mean, variance: 0.0, standard_error: 0.0, shots: 0, term_shots: vec![0; term_means.len()], term_means }
+    }
+}
+
+/// Stopping rule for `sample_expectation`: keep sampling until the standard error is at most
+/// `standard_error` or `max_shots` have been taken
+#[derive(Debug, Clone, Copy)]
+pub struct Precision {
+    pub standard_error: f64,
+    pub max_shots: usize,
+}
+
+/// Estimate ⟨H⟩ by measuring every Pauli string in its own basis.
+///
+/// `sample(gates, n)` returns n logical basis-state outcomes of the state with `gates`
+/// appended. Each string first gets `shots` shots; with a `precision`, more follow in rounds,
+/// spread over the terms in proportion to |coefficient| x spread, the split that minimizes the
+/// standard error for a given total.
+pub fn sample_expectation<F>(observable: &PauliSum, shots: usize, precision: Option<Precision>, mut sample: F) -> ShotEstimate
+where
+    F: FnMut(&[QuantumGate], usize) -> Vec<usize>,
+{
+    let terms = &observable.terms;
+    let bases: Vec<Vec<QuantumGate>> = terms.iter().map(PauliTerm::basis_change).collect();
+    // After the basis change every non-identity factor reads out on Z
+    let masks: Vec<usize> = terms
+        .iter()
+        .map(|term| {
+            let (x_mask, z_mask, _) = term.masks(|qubit| qubit);
+            x_mask | z_mask
+        })
+        .collect();
+    // (shots, sum of ±1 parities) of each term
+    let mut tallies = vec![(0usize, 0i64); terms.len()];
+    let mut batch: Vec<usize> = masks.iter().map(|&mask| if mask == 0 { 0 } else { shots }).collect();
+    loop {
+        for (k, &n) in batch.iter().enumerate() {
+            if n == 0 {
+                continue;
+            }
+            let outcomes = sample(&bases[k], n);
+            tallies[k].0 += outcomes.len();
+            tallies[k].1 += outcomes
+                .iter()
+                .map(|outcome| if (outcome & masks[k]).count_ones() % 2 == 1 { -1 } else { 1 })
+                .sum::<i64>();
+        }
+        let estimate = summarize(observable, &masks, &tallies);
+        let Some(precision) = precision else { return estimate };
+        if estimate.standard_error <= precision.standard_error || estimate.shots >= precision.max_shots {
+            return estimate;
+        }
+
+        // Shots the optimal split needs overall, from the spreads seen so far
+        let weights: Vec<f64> = terms
+            .iter()
+            .zip(&tallies)
+            .map(|(term, &(n, sum))| match n {
+                0 => 0.0,
+                _ => term.coefficient.abs() * spread(n, sum).sqrt(),
+            })
+            .collect();
+        let total_weight: f64 = weights.iter().sum();
+        let needed = (total_weight / precision.standard_error).powi(2);
+        batch = weights
+            .iter()
+            .zip(&tallies)
+            .map(|(weight, &(n, _))| ((needed * weight / total_weight).ceil() as usize).saturating_sub(n))
+            .collect();
+        // Sampling noise can make the split look already met; then take one more shot of the
+        // term that matters most
+        if batch.iter().all(|&n| n == 0) {
+            let heaviest = (0..weights.len()).max_by(|&a, &b| weights[a].total_cmp(&weights[b])).unwrap_or(0);
+            batch[heaviest] = 1;
+        }
+        let remaining = precision.max_shots - estimate.shots;
+        let planned: usize = batch.iter().sum();
+        if planned > remaining {
+            batch.iter_mut().for_each(|n| *n = (*n as u128 * remaining as u128 / planned as u128) as usize);
+            if batch.iter().all(|&n| n == 0) {
+                return estimate;
+            }
+        }
+    }
+}
+
+/// Unbiased variance of ±1 parities from `n` shots summing to `sum`; a single shot says
+/// nothing about the spread, so it counts as the largest possible
+fn spread(n: usize, sum: i64) -> f64 {
+    if n < 2 {
+        return 1.0;
+    }
+    let mean = sum as f64 / n as f64;
+    (1.0 - mean * mean) * n as f64 / (n - 1) as f64
+}
+
+fn summarize(observable: &PauliSum, masks: &[usize], tallies: &[(usize, i64)]) -> ShotEstimate {
+    let (mut mean, mut error_squared) = (0.0, 0.0);
+    let mut term_means = Vec::with_capacity(tallies.len());
+    for ((term, &mask), &(n, sum)) in observable.terms.iter().zip(masks).zip(tallies) {
+        let term_mean = if mask == 0 { 1.0 } else { sum as f64 / n.max(1) as f64 };
+        mean += term.coefficient * term_mean;
+        if n > 0 {
+            error_squared += term.coefficient * term.coefficient * spread(n, sum) / n as f64;
+        }
+        term_means.push(term_mean);
+    }
+    let shots: usize = tallies.iter().map(|&(n, _)| n).sum();
+    ShotEstimate {
+        mean,
+        variance: error_squared * shots as f64,
+        standard_error: error_squared.sqrt(),
+        shots,
+        term_means,
+        term_shots: tallies.iter().map(|&(n, _)| n).collect(),
+    }
+}
@@ -0,0 +1,395 @@
+//! Circuit Optimizer Module
+//! Objective-driven optimization passes over quantum circuits
+
+use std::collections::HashMap;
+use crate::gpu_ops::RotationAxis;
+use crate::noise::{self, NoiseModel};
+use crate::qsim::{self, QuantumCircuit, QuantumGate};
+use crate::templates::{self, RewriteRule};
+
+/// Metric the optimizer tries to minimize
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Objective {
+    GateCount,
+    Depth,
+    CnotCount,
+    Fidelity,
+}
+
+impl Objective {
+    /// Parse an objective name as given on the command line
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "gates" | "gate-count" => Some(Objective::GateCount),
+            "depth" => Some(Objective::Depth),
+            "cnot" => Some(Objective::CnotCount),
+            "fidelity" => Some(Objective::Fidelity),
+            _ => None,
+        }
+    }
+
+    /// Canonical name accepted by `parse`
+    pub fn name(self) -> &'static str {
+        match self {
+            Objective::GateCount => "gates",
+            Objective::Depth => "depth",
+            Objective::CnotCount => "cnot",
+            Objective::Fidelity => "fidelity",
+        }
+    }
+}
+
+/// Summary metrics used to compare circuits before and after optimization
+#[derive(Debug, Clone)]
+pub struct CircuitMetrics {
+    pub gates: usize,
+    pub depth: usize,
+    pub cnot_count: usize,
+    pub t_count: usize,
+    pub fidelity: f64,
+    pub runtime_ns: f64,
+}
+
+impl CircuitMetrics {
+    /// Compute metrics for a circuit under a noise model
+    pub fn compute(circuit: &QuantumCircuit, noise: &NoiseModel) -> Self {
+        Self {
+            gates: circuit.gates.len(),
+            depth: circuit_depth(circuit),
+            cnot_count: circuit.gates.iter().map(cnot_count).sum(),
+            t_count: circuit.gates.iter().map(t_count).sum(),
+            fidelity: noise.estimate_fidelity(circuit),
+            runtime_ns: critical_path(circuit, |gate| noise.gate_duration_ns(gate)),
+        }
+    }
+}
+
+/// Number of gate layers, counting each gate as one step on every qubit it touches
+pub fn circuit_depth(circuit: &QuantumCircuit) -> usize {
+    critical_path(circuit, |_| 1.0) as usize
+}
+
+/// Longest weighted path through the circuit's qubit timelines; a classical condition also
+/// waits for the measurement that wrote its bit
+fn critical_path<F>(circuit: &QuantumCircuit, weight: F) -> f64
+where
+    F: Fn(&QuantumGate) -> f64,
+{
+    let mut finish = vec![0.0_f64; circuit.num_qubits];
+    // Time at which each register bit was last written
+    let mut written: HashMap<usize, f64> = HashMap::new();
+    for gate in &circuit.gates {
+        let qubits = gate.qubits();
+        let start = qubits
+            .iter()
+            .map(|&q| finish.get(q).copied().unwrap_or(0.0))
+            .chain(qsim::condition_bits(gate).iter().map(|bit| written.get(bit).copied().unwrap_or(0.0)))
+            .fold(0.0, f64::max);
+        for q in qubits {
+            if let Some(time) = finish.get_mut(q) {
+                *time = start + weight(gate);
+            }
+        }
+        if let QuantumGate::Measurement { qubit, bit } = gate {
+            written.insert(bit.unwrap_or(*qubit), start + weight(gate));
+        }
+    }
+    finish.into_iter().fold(0.0, f64::max)
+}
+
+/// CNOTs needed to execute a gate
+fn cnot_count(gate: &QuantumGate) -> usize {
+    match gate {
+        QuantumGate::CNOT { .. } | QuantumGate::CZ { .. } => 1,
+        // Two CNOTs around a target rotation
+        QuantumGate::CPhase { .. } => 2,
+        // Both need two CNOTs plus single-qubit gates in a CNOT basis
+        QuantumGate::ISWAP { .. } | QuantumGate::SqrtISWAP { .. } => 2,
+        // Equal to a CNOT up to single-qubit gates
+        QuantumGate::ECR { .. } => 1,
+        // CNOT, RZ, CNOT in a rotated basis
+        QuantumGate::RXX { .. } | QuantumGate::RYY { .. } | QuantumGate::RZZ { .. } => 2,
+        // A real rotation in the |01>, |10> subspace needs two CNOTs
+        QuantumGate::Givens { .. } => 2,
+        QuantumGate::SWAP { .. } => 3,
+        QuantumGate::PauliRotation { pauli, .. } => noise::pauli_rotation_cnots(pauli),
+        QuantumGate::Permutation { qubits, pattern } => 3 * qsim::permutation_swaps(qubits, pattern).len(),
+        QuantumGate::Toffoli { .. } => 6,
+        QuantumGate::UnitaryNQ { qubits, .. } => noise::unitary_cnots(qubits.len()),
+        QuantumGate::Diagonal { qubits, .. } => noise::diagonal_cnots(qubits.len()),
+        QuantumGate::Controlled { gate, .. } => noise::controlled_cnots(gate),
+        QuantumGate::IfBit { gate, .. } | QuantumGate::Noisy { gate, .. } => cnot_count(gate),
+        QuantumGate::MCX { controls, .. } => match controls.len() {
+            0 => 0,
+            1 => 1,
+            n => 6 * noise::mcx_toffolis(n),
+        },
+        _ => 0,
+    }
+}
+
+/// T gates needed to execute a gate (phases that are odd multiples of pi/4)
+pub(crate) fn t_count(gate: &QuantumGate) -> usize {
+    match gate {
+        QuantumGate::Toffoli { .. } => 7,
+        // Controlled CNOT is a Toffoli
+        QuantumGate::Controlled { gate, .. } if matches!(**gate, QuantumGate::CNOT { .. }) => 7,
+        QuantumGate::MCX { controls, .. } if controls.len() >= 2 => 7 * noise::mcx_toffolis(controls.len()),
+        QuantumGate::T { .. } | QuantumGate::Tdg { .. } => 1,
+        QuantumGate::Phase { angle, .. } | QuantumGate::RotationZ { angle, .. } => odd_eighth_turn(*angle) as usize,
+        QuantumGate::GlobalRotation { qubits, axis: RotationAxis::Z, angle } if odd_eighth_turn(*angle) => qubits.len(),
+        // The ladder is Clifford, leaving a single RZ
+        QuantumGate::PauliRotation { angle, .. } => odd_eighth_turn(*angle) as usize,
+        QuantumGate::Diagonal { qubits, phases } => qsim::diagonal_gates(qubits, phases).iter().map(t_count).sum(),
+        QuantumGate::IfBit { gate, .. } | QuantumGate::Noisy { gate, .. } => t_count(gate),
+        _ => 0,
+    }
+}
+
+/// Whether a Z rotation is an odd multiple of pi/4, i.e. needs a T gate
+fn odd_eighth_turn(angle: f64) -> bool {
+    let quarter_turns = angle / (std::f64::consts::PI / 4.0);
+    let rounded = quarter_turns.round();
+    (quarter_turns - rounded).abs() < 1e-9 && rounded.rem_euclid(2.0) == 1.0
+}
+
+/// A named circuit transformation
+struct Pass {
+    name: &'static str,
+    apply: fn(&QuantumCircuit) -> QuantumCircuit,
+}
+
+const PASSES: &[Pass] = &[
+    Pass { name: "remove_redundant", apply: remove_redundant },
+    Pass { name: "cancel_inverse_pairs", apply: cancel_inverse_pairs },
+    Pass { name: "merge_rotations", apply: merge_rotations },
+    Pass { name: "swap_cancellation", apply: swap_cancellation },
+];
+
+/// Cost of a circuit under an objective (lower is better)
+pub fn cost(circuit: &QuantumCircuit, objective: Objective, noise: &NoiseModel) -> f64 {
+    match objective {
+        Objective::GateCount => circuit.gates.len() as f64,
+        Objective::Depth => critical_path(circuit, |_| 1.0),
+        Objective::CnotCount => circuit.gates.iter().map(cnot_count).sum::<usize>() as f64,
+        Objective::Fidelity => 1.0 - noise.estimate_fidelity(circuit),
+    }
+}
+
+/// Optimize a circuit, keeping only transformations that improve the objective
+pub fn optimize_for(
+    circuit: QuantumCircuit,
+    objective: Objective,
+    noise: &NoiseModel,
+    rules: &[RewriteRule],
+) -> QuantumCircuit {
+    let mut best_cost = cost(&circuit, objective, noise);
+    let mut best = circuit;
+
+    loop {
+        let mut improved = false;
+        for pass in PASSES {
+            let candidate = (pass.apply)(&best);
+            improved |= accept(pass.name, candidate, &mut best, &mut best_cost, objective, noise);
+        }
+        if !rules.is_empty() {
+            let candidate = templates::apply_rules(&best, rules);
+            improved |= accept("rewrite_rules", candidate, &mut best, &mut best_cost, objective, noise);
+        }
+        if !improved {
+            return best;
+        }
+    }
+}
+
+/// Replace `best` with `candidate` if it scores better
+fn accept(
+    name: &str,
+    candidate: QuantumCircuit,
+    best: &mut QuantumCircuit,
+    best_cost: &mut f64,
+    objective: Objective,
+    noise: &NoiseModel,
+) -> bool {
+    let candidate_cost = cost(&candidate, objective, noise);
+    // Gate count breaks ties so neutral simplifications are still kept
+    let better = candidate_cost < *best_cost - 1e-12
+        || (candidate_cost <= *best_cost + 1e-12 && candidate.gates.len() < best.gates.len());
+    if better {
+        println!("  ✓ {}: cost {:.6} -> {:.6}", name, best_cost, candidate_cost);
+        *best = candidate;
+        *best_cost = candidate_cost;
+    }
+    better
+}
+
+fn remove_redundant(circuit: &QuantumCircuit) -> QuantumCircuit {
+    qsim::optimize(circuit.clone())
+}
+
+/// Index of the next gate after `index` sharing a qubit or register bit with `gate`. A
+/// snapshot stops the search, so no rewrite changes the state it records.
+fn next_on_qubits(gates: &[Option<QuantumGate>], index: usize, gate: &QuantumGate) -> Option<usize> {
+    let (qubits, bits) = (gate.qubits(), qsim::register_bits(gate));
+    (index + 1..gates.len()).find(|&j| {
+        gates[j]
+            .as_ref()
+            .map(|next| {
+                matches!(next, QuantumGate::Snapshot { .. })
+                    || next.qubits().iter().any(|q| qubits.contains(q))
+                    || qsim::register_bits(next).iter().any(|bit| bits.contains(bit))
+            })
+            .unwrap_or(false)
+    })
+}
+
+/// Inverse of a gate when it is a fixed (non-parameterized) gate
+fn fixed_inverse(gate: &QuantumGate) -> Option<QuantumGate> {
+    match gate {
+        QuantumGate::Hadamard { .. }
+        | QuantumGate::PauliX { .. }
+        | QuantumGate::PauliY { .. }
+        | QuantumGate::PauliZ { .. }
+        | QuantumGate::CNOT { .. }
+        | QuantumGate::CZ { .. }
+        | QuantumGate::SWAP { .. }
+        | QuantumGate::ECR { .. }
+        | QuantumGate::Toffoli { .. }
+        | QuantumGate::MCX { .. }
+        | QuantumGate::GlobalHadamard { .. } => Some(gate.clone()),
+        QuantumGate::S { qubit } => Some(QuantumGate::Sdg { qubit: *qubit }),
+        QuantumGate::Sdg { qubit } => Some(QuantumGate::S { qubit: *qubit }),
+        QuantumGate::T { qubit } => Some(QuantumGate::Tdg { qubit: *qubit }),
+        QuantumGate::Tdg { qubit } => Some(QuantumGate::T { qubit: *qubit }),
+        QuantumGate::SX { qubit } => Some(QuantumGate::SXdg { qubit: *qubit }),
+        QuantumGate::SXdg { qubit } => Some(QuantumGate::SX { qubit: *qubit }),
+        QuantumGate::Permutation { qubits, pattern } => {
+            let mut inverse = vec![0; pattern.len()];
+            for (k, &source) in pattern.iter().enumerate() {
+                inverse[source] = k;
+            }
+            Some(QuantumGate::Permutation { qubits: qubits.clone(), pattern: inverse })
+        }
+        QuantumGate::Diagonal { qubits, phases } => Some(QuantumGate::Diagonal {
+            qubits: qubits.clone(),
+            phases: phases.iter().map(|phase| -phase).collect(),
+        }),
+        QuantumGate::Controlled { control, gate } => Some(QuantumGate::Controlled {
+            control: *control,
+            gate: Box::new(fixed_inverse(gate)?),
+        }),
+        // Undone by the same condition while the bit is unchanged, which next_on_qubits ensures
+        QuantumGate::IfBit { bit, gate } => Some(QuantumGate::IfBit { bit: *bit, gate: Box::new(fixed_inverse(gate)?) }),
+        // A gate with its own noise is kept as written, so an injected fault is never cancelled
+        _ => None,
+    }
+}
+
+fn rebuild(circuit: &QuantumCircuit, gates: Vec<Option<QuantumGate>>) -> QuantumCircuit {
+    QuantumCircuit {
+        num_qubits: circuit.num_qubits,
+        gates: gates.into_iter().flatten().collect(),
+        metadata: circuit.metadata.clone(),
+    }
+}
+
+/// Cancel gate/inverse pairs separated only by gates on other qubits
+fn cancel_inverse_pairs(circuit: &QuantumCircuit) -> QuantumCircuit {
+    let mut gates: Vec<Option<QuantumGate>> = circuit.gates.iter().cloned().map(Some).collect();
+
+    for i in 0..gates.len() {
+        let (gate, inverse) = match gates[i].as_ref().and_then(|g| Some((g.clone(), fixed_inverse(g)?))) {
+            Some(pair) => pair,
+            None => continue,
+        };
+        if let Some(j) = next_on_qubits(&gates, i, &gate) {
+            if gates[j].as_ref() == Some(&inverse) {
+                gates[i] = None;
+                gates[j] = None;
+            }
+        }
+    }
+
+    rebuild(circuit, gates)
+}
+
+/// Qubit and angle of a rotation-like gate
+pub fn rotation_angle(gate: &QuantumGate) -> Option<(usize, f64)> {
+    match gate {
+        QuantumGate::RotationX { qubit, angle }
+        | QuantumGate::RotationY { qubit, angle }
+        | QuantumGate::RotationZ { qubit, angle }
+        | QuantumGate::Phase { qubit, angle } => Some((*qubit, *angle)),
+        _ => None,
+    }
+}
+
+/// Copy of a rotation-like gate with a different angle
+fn with_angle(gate: &QuantumGate, new_angle: f64) -> QuantumGate {
+    let mut gate = gate.clone();
+    if let QuantumGate::RotationX { angle, .. }
+    | QuantumGate::RotationY { angle, .. }
+    | QuantumGate::RotationZ { angle, .. }
+    | QuantumGate::Phase { angle, .. } = &mut gate
+    {
+        *angle = new_angle;
+    }
+    gate
+}
+
+/// Fuse consecutive rotations of the same kind on the same qubit
+fn merge_rotations(circuit: &QuantumCircuit) -> QuantumCircuit {
+    let mut gates: Vec<Option<QuantumGate>> = circuit.gates.iter().cloned().map(Some).collect();
+    merge_rotation_slots(&mut gates);
+    rebuild(circuit, gates)
+}
+
+/// Fuse rotations in place: the earlier of each pair becomes `None` and the later carries the
+/// summed angle, so callers can see which original gates changed
+pub fn merge_rotation_slots(gates: &mut [Option<QuantumGate>]) {
+    for i in 0..gates.len() {
+        let gate = match &gates[i] {
+            Some(gate) => gate.clone(),
+            None => continue,
+        };
+        let (qubit, angle) = match rotation_angle(&gate) {
+            Some(rotation) => rotation,
+            None => continue,
+        };
+        let j = match next_on_qubits(gates, i, &gate) {
+            Some(j) => j,
+            None => continue,
+        };
+        let next = gates[j].clone().expect("next gate is present");
+        if std::mem::discriminant(&next) != std::mem::discriminant(&gate) {
+            continue;
+        }
+        if let Some((next_qubit, next_angle)) = rotation_angle(&next) {
+            if next_qubit == qubit {
+                gates[i] = None;
+                gates[j] = Some(with_angle(&next, angle + next_angle));
+            }
+        }
+    }
+}
+
+/// Expand SWAPs into CNOTs and cancel the CNOTs that meet their neighbours
+fn swap_cancellation(circuit: &QuantumCircuit) -> QuantumCircuit {
+    let mut gates = Vec::with_capacity(circuit.gates.len());
+    for gate in &circuit.gates {
+        match gate {
+            QuantumGate::SWAP { qubit1, qubit2 } => {
+                gates.push(QuantumGate::CNOT { control: *qubit1, target: *qubit2 });
+                gates.push(QuantumGate::CNOT { control: *qubit2, target: *qubit1 });
+                gates.push(QuantumGate::CNOT { control: *qubit1, target: *qubit2 });
+            }
+            _ => gates.push(gate.clone()),
+        }
+    }
+
+    cancel_inverse_pairs(&QuantumCircuit {
+        num_qubits: circuit.num_qubits,
+        gates,
+        metadata: circuit.metadata.clone(),
+    })
+}
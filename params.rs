@@ -0,0 +1,128 @@
+//! Circuit Parameters Module
+//! Named angles declared once in a circuit document and bound into its gates at load time
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use serde_json::Value;
+use crate::{macros, noise};
+use crate::qasm::eval_expression;
+use crate::qsim::{CircuitFormat, QuantumCircuit};
+
+/// Gate fields that take an angle, and so may be written as a parameter expression
+const ANGLE_FIELDS: &[&str] = &["angle", "theta", "phi", "lambda", "phases"];
+
+/// Whether a parsed circuit document declares named parameters
+pub fn has_parameters(document: &Value) -> bool {
+    document.get("parameters").is_some()
+}
+
+/// Declared parameters with their default values, in name order
+pub fn defaults(document: &Value) -> Result<BTreeMap<String, f64>, String> {
+    let declared = match document.get("parameters") {
+        Some(Value::Object(declared)) => declared,
+        Some(_) => return Err("parameters must map names to default values".to_string()),
+        None => return Ok(BTreeMap::new()),
+    };
+    let mut values = BTreeMap::new();
+    for (name, value) in declared {
+        let mut chars = name.chars();
+        if !chars.next().is_some_and(|c| c.is_ascii_alphabetic()) || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("parameter name '{}' must be a letter followed by letters, digits or '_'", name));
+        }
+        if name.eq_ignore_ascii_case("pi") {
+            return Err("'pi' is a constant and cannot be a parameter name".to_string());
+        }
+        let value = value
+            .as_f64()
+            .ok_or_else(|| format!("parameter '{}' needs a numeric default", name))?;
+        values.insert(name.clone(), value);
+    }
+    Ok(values)
+}
+
+/// Circuit with `values` substituted for its parameters; unset parameters keep their defaults
+pub fn instantiate(document: &Value, values: &BTreeMap<String, f64>) -> Result<QuantumCircuit, Box<dyn Error>> {
+    let mut bound = defaults(document)?;
+    for (name, &value) in values {
+        match bound.get_mut(name) {
+            Some(slot) => *slot = value,
+            None => return Err(format!("circuit has no parameter '{}'", name).into()),
+        }
+    }
+
+    let mut document = document.clone();
+    if let Some(object) = document.as_object_mut() {
+        object.remove("parameters");
+    }
+    noise::wrap_inline_overrides(&mut document)?;
+    if let Some(gates) = document.get_mut("gates") {
+        bind_entries(gates, &bound)?;
+    }
+    if let Some(Value::Object(definitions)) = document.get_mut("macros") {
+        for (name, definition) in definitions.iter_mut() {
+            if let Some(gates) = definition.get_mut("gates") {
+                bind_entries(gates, &bound).map_err(|e| format!("in macro '{}': {}", name, e))?;
+            }
+        }
+    }
+
+    if macros::has_macros(&document) {
+        macros::expand(document)
+    } else {
+        Ok(serde_json::from_value(document)?)
+    }
+}
+
+/// Read a JSON, YAML or TOML circuit document, keeping its parameters unbound
+pub fn read_document(path: &str) -> Result<Value, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    let document = match CircuitFormat::from_path(path) {
+        CircuitFormat::Json => serde_json::from_str(&contents)?,
+        CircuitFormat::Yaml => serde_yaml::from_str(&contents)?,
+        CircuitFormat::Toml => toml::from_str(&contents)?,
+        _ => return Err("named parameters need a JSON, YAML or TOML circuit".into()),
+    };
+    Ok(document)
+}
+
+/// Evaluate every angle expression in a gate list, descending into controlled and conditioned gates
+fn bind_entries(gates: &mut Value, values: &BTreeMap<String, f64>) -> Result<(), String> {
+    let entries = gates.as_array_mut().ok_or("gates must be a list")?;
+    for (index, entry) in entries.iter_mut().enumerate() {
+        bind_gate(entry, values).map_err(|e| format!("gate {}: {}", index, e))?;
+    }
+    Ok(())
+}
+
+fn bind_gate(entry: &mut Value, values: &BTreeMap<String, f64>) -> Result<(), String> {
+    let Some(fields) = entry.as_object_mut() else {
+        return Ok(());
+    };
+    for (key, value) in fields.iter_mut() {
+        if key == "gate" {
+            bind_gate(value, values)?;
+        } else if ANGLE_FIELDS.contains(&key.as_str()) {
+            match value {
+                Value::Array(items) => {
+                    for item in items {
+                        bind_angle(item, values)?;
+                    }
+                }
+                value => bind_angle(value, values)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replace an expression string such as `"2*theta"` with its value; numbers are left alone
+fn bind_angle(value: &mut Value, values: &BTreeMap<String, f64>) -> Result<(), String> {
+    if let Value::String(expr) = value {
+        let angle = eval_expression(expr, values)?;
+        *value = serde_json::Number::from_f64(angle)
+            .map(Value::Number)
+            .ok_or_else(|| format!("'{}' is not a finite angle", expr))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,196 @@
+//! Power Module
+//! Energy measurement during benchmarks from RAPL (CPU) and NVML (GPU) counters
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Powercap sysfs tree exposing RAPL energy counters
+const RAPL_ROOT: &str = "/sys/class/powercap";
+
+/// How often nvidia-smi reports GPU power draw
+const GPU_SAMPLE_MS: u32 = 100;
+
+/// Energy used by one measured domain
+pub struct EnergyReading {
+    pub source: String,
+    pub joules: f64,
+}
+
+/// Energy measured over one benchmark run
+pub struct EnergyReport {
+    pub elapsed: Duration,
+    pub readings: Vec<EnergyReading>,
+}
+
+impl EnergyReport {
+    pub fn total_joules(&self) -> f64 {
+        self.readings.iter().map(|reading| reading.joules).sum()
+    }
+
+    /// Joules summed over sources whose name starts with `prefix`
+    pub fn joules_for(&self, prefix: &str) -> Option<f64> {
+        let matching: Vec<f64> = self
+            .readings
+            .iter()
+            .filter(|reading| reading.source.starts_with(prefix))
+            .map(|reading| reading.joules)
+            .collect();
+        (!matching.is_empty()).then(|| matching.iter().sum())
+    }
+
+    pub fn average_watts(&self) -> f64 {
+        self.total_joules() / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// RAPL package counter read at the start of a run
+struct RaplDomain {
+    name: String,
+    counter: PathBuf,
+    max_range_uj: u64,
+    start_uj: u64,
+}
+
+/// Power draw of one GPU integrated up to its latest reading
+struct GpuEnergy {
+    joules: f64,
+    watts: f64,
+    last: Instant,
+}
+
+/// Background nvidia-smi reader integrating power draw per GPU index
+struct GpuSampler {
+    child: Child,
+    gpus: Arc<Mutex<BTreeMap<String, GpuEnergy>>>,
+    reader: JoinHandle<()>,
+}
+
+/// Energy counters running for the duration of a benchmark
+pub struct PowerMeter {
+    started: Instant,
+    rapl: Vec<RaplDomain>,
+    gpu: Option<GpuSampler>,
+}
+
+impl PowerMeter {
+    /// Start reading every available source; unreadable ones are skipped
+    pub fn start() -> Self {
+        let started = Instant::now();
+        Self {
+            started,
+            rapl: rapl_domains(),
+            gpu: start_gpu_sampler(started),
+        }
+    }
+
+    pub fn has_sources(&self) -> bool {
+        !self.rapl.is_empty() || self.gpu.is_some()
+    }
+
+    pub fn stop(self) -> EnergyReport {
+        let stopped = Instant::now();
+        let mut readings = Vec::new();
+
+        for domain in &self.rapl {
+            if let Some(end_uj) = read_u64(&domain.counter) {
+                // The counter wraps at max_energy_range_uj
+                let used_uj = if end_uj >= domain.start_uj {
+                    end_uj - domain.start_uj
+                } else {
+                    domain.max_range_uj - domain.start_uj + end_uj
+                };
+                readings.push(EnergyReading { source: format!("CPU {}", domain.name), joules: used_uj as f64 / 1e6 });
+            }
+        }
+
+        if let Some(mut gpu) = self.gpu {
+            let _ = gpu.child.kill();
+            let _ = gpu.child.wait();
+            let _ = gpu.reader.join();
+            for (index, energy) in gpu.gpus.lock().unwrap().iter() {
+                // Hold the last reading until the end of the run
+                let tail = stopped.saturating_duration_since(energy.last).as_secs_f64();
+                readings.push(EnergyReading {
+                    source: format!("GPU {}", index),
+                    joules: energy.joules + energy.watts * tail,
+                });
+            }
+        }
+
+        EnergyReport { elapsed: stopped - self.started, readings }
+    }
+}
+
+/// Top-level RAPL package domains (`intel-rapl:N`; also used for AMD packages)
+fn rapl_domains() -> Vec<RaplDomain> {
+    let mut domains = Vec::new();
+    let entries = match fs::read_dir(RAPL_ROOT) {
+        Ok(entries) => entries,
+        Err(_) => return domains,
+    };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        // Sub-domains (intel-rapl:0:0) are already counted in their package
+        if !file_name.starts_with("intel-rapl:") || file_name.matches(':').count() != 1 {
+            continue;
+        }
+        let dir = entry.path();
+        let counter = dir.join("energy_uj");
+        if let (Some(start_uj), Some(max_range_uj)) = (read_u64(&counter), read_u64(&dir.join("max_energy_range_uj"))) {
+            let name = fs::read_to_string(dir.join("name"))
+                .map(|name| name.trim().to_string())
+                .unwrap_or(file_name);
+            domains.push(RaplDomain { name, counter, max_range_uj, start_uj });
+        }
+    }
+    domains.sort_by(|a, b| a.name.cmp(&b.name));
+    domains
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Stream NVML power readings through nvidia-smi, if it is installed and sees a GPU
+fn start_gpu_sampler(started: Instant) -> Option<GpuSampler> {
+    let mut child = Command::new("nvidia-smi")
+        .args([
+            "--query-gpu=index,power.draw",
+            "--format=csv,noheader,nounits",
+            &format!("--loop-ms={}", GPU_SAMPLE_MS),
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let stdout = child.stdout.take()?;
+    let gpus = Arc::new(Mutex::new(BTreeMap::new()));
+
+    let shared = Arc::clone(&gpus);
+    let reader = std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            let (index, watts) = match line.split_once(',') {
+                Some((index, watts)) => match watts.trim().parse::<f64>() {
+                    Ok(watts) => (index.trim().to_string(), watts),
+                    Err(_) => continue,
+                },
+                None => continue,
+            };
+            let now = Instant::now();
+            let mut gpus = shared.lock().unwrap();
+            // The first reading also covers the time since the meter started
+            let energy = gpus.entry(index).or_insert(GpuEnergy { joules: 0.0, watts, last: started });
+            energy.joules += energy.watts * now.saturating_duration_since(energy.last).as_secs_f64();
+            energy.watts = watts;
+            energy.last = now;
+        }
+    });
+
+    Some(GpuSampler { child, gpus, reader })
+}
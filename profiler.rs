@@ -0,0 +1,138 @@
+//! Profiler Module
+//! Timeline of GPU kernels, transfers and CPU phases in Chrome trace format
+
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use serde_json::{json, Value};
+
+/// Timeline row an event is drawn on
+#[derive(Clone, Copy)]
+pub enum Track {
+    Cpu,
+    Kernel,
+    Transfer,
+}
+
+impl Track {
+    fn tid(self) -> u32 {
+        match self {
+            Track::Cpu => 1,
+            Track::Kernel => 2,
+            Track::Transfer => 3,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Track::Cpu => "CPU",
+            Track::Kernel => "GPU kernels",
+            Track::Transfer => "GPU transfers",
+        }
+    }
+
+    fn category(self) -> &'static str {
+        match self {
+            Track::Cpu => "cpu",
+            Track::Kernel => "kernel",
+            Track::Transfer => "transfer",
+        }
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+struct Recorder {
+    origin: Instant,
+    events: Vec<Value>,
+}
+
+fn recorder() -> &'static Mutex<Recorder> {
+    static RECORDER: OnceLock<Mutex<Recorder>> = OnceLock::new();
+    RECORDER.get_or_init(|| {
+        Mutex::new(Recorder {
+            origin: Instant::now(),
+            events: Vec::new(),
+        })
+    })
+}
+
+/// Start recording; spans opened before this call are not captured
+pub fn enable() {
+    recorder();
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Open a span that is recorded when dropped. Costs one atomic load when profiling is off.
+pub fn span(name: &str, track: Track) -> Span {
+    Span {
+        active: is_enabled().then(|| (name.to_string(), track, Instant::now(), Value::Null)),
+    }
+}
+
+/// An in-progress timeline event
+pub struct Span {
+    active: Option<(String, Track, Instant, Value)>,
+}
+
+impl Span {
+    /// Attach `args` shown in the trace viewer's detail pane
+    pub fn with_args(mut self, args: Value) -> Self {
+        if let Some(active) = &mut self.active {
+            active.3 = args;
+        }
+        self
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some((name, track, start, args)) = self.active.take() else {
+            return;
+        };
+        let end = Instant::now();
+        let mut recorder = recorder().lock().unwrap();
+        let ts = start.saturating_duration_since(recorder.origin).as_secs_f64() * 1e6;
+        let mut event = json!({
+            "name": name,
+            "cat": track.category(),
+            "ph": "X",
+            "ts": ts,
+            "dur": (end - start).as_secs_f64() * 1e6,
+            "pid": 1,
+            "tid": track.tid(),
+        });
+        if !args.is_null() {
+            event["args"] = args;
+        }
+        recorder.events.push(event);
+    }
+}
+
+/// Write the recorded timeline as a chrome://tracing / Perfetto JSON file
+pub fn write_trace(path: &str) -> io::Result<()> {
+    let recorder = recorder().lock().unwrap();
+    let mut events: Vec<Value> = [Track::Cpu, Track::Kernel, Track::Transfer]
+        .iter()
+        .map(|track| {
+            json!({
+                "name": "thread_name",
+                "ph": "M",
+                "pid": 1,
+                "tid": track.tid(),
+                "args": { "name": track.label() },
+            })
+        })
+        .collect();
+    events.push(json!({ "name": "process_name", "ph": "M", "pid": 1, "args": { "name": "quantummesh" } }));
+    events.extend(recorder.events.iter().cloned());
+
+    let trace = json!({ "traceEvents": events, "displayTimeUnit": "ns" });
+    fs::write(path, serde_json::to_string(&trace).map_err(io::Error::other)?)
+}
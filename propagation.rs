@@ -0,0 +1,266 @@
+//! Propagation Module
+//! Heisenberg-picture Pauli propagation: observables pulled back through a circuit onto
+//! |0...0>, for expectation values without a state vector
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::backends::StateBackend;
+use crate::observable::PauliSum;
+use crate::qsim::{QuantumCircuit, QuantumGate, Shot};
+use crate::stabilizer::{self, Step};
+use crate::tomography;
+
+/// Pauli strings whose coefficient falls below this are dropped when `--min-coefficient`
+/// is not given
+pub const DEFAULT_MIN_COEFFICIENT: f64 = 1e-8;
+
+/// Most Pauli strings kept at once; a propagation that needs more is refused
+pub const MAX_TERMS: usize = 1 << 22;
+
+/// A Hermitian Pauli string as X and Z bits, one per qubit; both set is Y
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PauliString {
+    x: Vec<u64>,
+    z: Vec<u64>,
+}
+
+impl PauliString {
+    /// `pauli[k]` acts on qubit k
+    fn parse(pauli: &str) -> Self {
+        let words = pauli.len().div_ceil(64);
+        let mut string = PauliString { x: vec![0; words], z: vec![0; words] };
+        for (qubit, p) in pauli.chars().enumerate() {
+            string.set(qubit, matches!(p, 'X' | 'Y'), matches!(p, 'Y' | 'Z'));
+        }
+        string
+    }
+
+    fn get(&self, qubit: usize) -> (bool, bool) {
+        let (word, bit) = (qubit / 64, qubit % 64);
+        ((self.x[word] >> bit) & 1 == 1, (self.z[word] >> bit) & 1 == 1)
+    }
+
+    fn set(&mut self, qubit: usize, x: bool, z: bool) {
+        let (word, bit) = (qubit / 64, qubit % 64);
+        self.x[word] = (self.x[word] & !(1 << bit)) | ((x as u64) << bit);
+        self.z[word] = (self.z[word] & !(1 << bit)) | ((z as u64) << bit);
+    }
+
+    /// Qubits the string acts on
+    fn weight(&self) -> usize {
+        self.x.iter().zip(&self.z).map(|(x, z)| (x | z).count_ones() as usize).sum()
+    }
+
+    /// ⟨0...0|P|0...0⟩ is 1 for strings of I and Z only, and 0 otherwise
+    fn is_diagonal(&self) -> bool {
+        self.x.iter().all(|&word| word == 0)
+    }
+
+    /// H P H; returns whether the sign flips
+    fn hadamard(&mut self, qubit: usize) -> bool {
+        let (x, z) = self.get(qubit);
+        self.set(qubit, z, x);
+        x && z
+    }
+
+    /// S† P S: X → -Y, Y → X
+    fn s_dagger(&mut self, qubit: usize) -> bool {
+        let (x, z) = self.get(qubit);
+        self.set(qubit, x, z ^ x);
+        x && !z
+    }
+
+    /// SWAP P SWAP exchanges the two qubits' Paulis with no sign
+    fn swap(&mut self, a: usize, b: usize) -> bool {
+        let ((xa, za), (xb, zb)) = (self.get(a), self.get(b));
+        self.set(a, xb, zb);
+        self.set(b, xa, za);
+        false
+    }
+
+    /// CNOT P CNOT: X spreads from control to target, Z from target to control
+    fn cnot(&mut self, control: usize, target: usize) -> bool {
+        let ((xc, zc), (xt, zt)) = (self.get(control), self.get(target));
+        self.set(target, xt ^ xc, zt);
+        self.set(control, xc, zc ^ zt);
+        xc && zt && xt == zc
+    }
+}
+
+/// An observable as a sum of Pauli strings with real coefficients, pulled back one step at a
+/// time: O → G† O G
+struct Propagation {
+    terms: HashMap<PauliString, f64>,
+    min_coefficient: f64,
+    max_weight: Option<usize>,
+}
+
+impl Propagation {
+    /// Apply a Clifford conjugation, which maps every string to one string and a sign
+    fn clifford(&mut self, conjugate: impl Fn(&mut PauliString) -> bool) {
+        self.terms = self
+            .terms
+            .drain()
+            .map(|(mut string, coefficient)| {
+                let flipped = conjugate(&mut string);
+                (string, if flipped { -coefficient } else { coefficient })
+            })
+            .collect();
+    }
+
+    /// Pull back through diag(1, e^(iθ)): strings with X or Y on the qubit rotate,
+    /// X → cos θ X - sin θ Y and Y → cos θ Y + sin θ X; the rest commute with it
+    fn rotate(&mut self, qubit: usize, angle: f64) -> Result<(), String> {
+        let (cos, sin) = (angle.cos(), angle.sin());
+        let mut next: HashMap<PauliString, f64> = HashMap::with_capacity(self.terms.len() * 2);
+        for (string, coefficient) in self.terms.drain() {
+            let (x, z) = string.get(qubit);
+            if !x {
+                *next.entry(string).or_insert(0.0) += coefficient;
+                continue;
+            }
+            let mut rotated = string.clone();
+            rotated.set(qubit, true, !z);
+            *next.entry(string).or_insert(0.0) += coefficient * cos;
+            *next.entry(rotated).or_insert(0.0) += coefficient * if z { sin } else { -sin };
+        }
+        let (min_coefficient, max_weight) = (self.min_coefficient, self.max_weight);
+        next.retain(|string, coefficient| {
+            coefficient.abs() >= min_coefficient && !matches!(max_weight, Some(max) if string.weight() > max)
+        });
+        if next.len() > MAX_TERMS {
+            return Err(format!(
+                "the observable grew past {} Pauli strings; raise --min-coefficient or set --max-weight",
+                MAX_TERMS
+            ));
+        }
+        self.terms = next;
+        Ok(())
+    }
+
+    fn step(&mut self, step: Step) -> Result<(), String> {
+        match step {
+            Step::H(qubit) => self.clifford(|string| string.hadamard(qubit)),
+            Step::S(qubit, times) => {
+                self.clifford(|string| (0..times % 4).fold(false, |flipped, _| flipped ^ string.s_dagger(qubit)))
+            }
+            Step::CX(control, target) => self.clifford(|string| string.cnot(control, target)),
+            // CZ = H CNOT H on the target
+            Step::CZ(a, b) => self.clifford(|string| string.hadamard(b) ^ string.cnot(a, b) ^ string.hadamard(b)),
+            Step::Swap(a, b) => self.clifford(|string| string.swap(a, b)),
+            Step::Phase(qubit, angle) => match stabilizer::clifford_turns(angle) {
+                Some(turns) => self.step(Step::S(qubit, turns))?,
+                None => self.rotate(qubit, angle)?,
+            },
+        }
+        Ok(())
+    }
+
+    /// ⟨0...0|O|0...0⟩
+    fn vacuum_expectation(&self) -> f64 {
+        self.terms.iter().filter(|(string, _)| string.is_diagonal()).map(|(_, coefficient)| coefficient).sum()
+    }
+}
+
+/// Steps of a circuit's gates in order, with terminal measurements dropped. Expectations are
+/// of the state before any terminal measurement, as with the state vector.
+fn circuit_steps(circuit: &QuantumCircuit) -> Result<Vec<Step>, String> {
+    if circuit.has_collapse() {
+        return Err("Pauli propagation follows no measurement outcomes; the circuit resets or measures qubits mid-circuit".to_string());
+    }
+    let mut steps = Vec::new();
+    for gate in &tomography::prepared(circuit).gates {
+        push_steps(gate, &mut steps)?;
+    }
+    Ok(steps)
+}
+
+fn push_steps(gate: &QuantumGate, steps: &mut Vec<Step>) -> Result<(), String> {
+    match gate {
+        QuantumGate::Measurement { .. } | QuantumGate::Snapshot { .. } => {}
+        // Noiseless, like the state vector simulator
+        QuantumGate::Noisy { gate, .. } => push_steps(gate, steps)?,
+        QuantumGate::IfBit { .. } => {
+            return Err("Pauli propagation cannot follow classically controlled gates".to_string());
+        }
+        gate => steps.extend(stabilizer::gate_steps(gate)?),
+    }
+    Ok(())
+}
+
+/// ⟨P⟩ of every term's Pauli string at the end of the circuit, started from |0...0>
+pub fn term_expectations(
+    circuit: &QuantumCircuit,
+    observable: &PauliSum,
+    min_coefficient: f64,
+    max_weight: Option<usize>,
+) -> Result<Vec<f64>, String> {
+    let steps = circuit_steps(circuit)?;
+    observable
+        .terms
+        .iter()
+        .map(|term| {
+            let mut propagation = Propagation {
+                terms: HashMap::from([(PauliString::parse(&term.pauli), 1.0)]),
+                min_coefficient,
+                max_weight,
+            };
+            for &step in steps.iter().rev() {
+                propagation.step(step)?;
+            }
+            Ok(propagation.vacuum_expectation())
+        })
+        .collect()
+}
+
+/// Pauli propagation backend, registered as `pauli`
+pub struct PropagationBackend {
+    min_coefficient: f64,
+    max_weight: Option<usize>,
+}
+
+impl Default for PropagationBackend {
+    fn default() -> Self {
+        Self { min_coefficient: DEFAULT_MIN_COEFFICIENT, max_weight: None }
+    }
+}
+
+impl StateBackend for PropagationBackend {
+    fn name(&self) -> &str {
+        "pauli"
+    }
+
+    fn description(&self) -> &str {
+        "Heisenberg-picture Pauli propagation; --observable expectations only, shallow circuits on 100+ qubits"
+    }
+
+    fn probabilities(&self, _circuit: &QuantumCircuit, _seed: u64) -> Result<Vec<f64>, String> {
+        Err("the pauli backend computes expectation values only; use --observable".to_string())
+    }
+
+    fn sample(&self, _circuit: &QuantumCircuit, _shots: usize, _seed: u64) -> Result<Vec<Shot>, String> {
+        Err("the pauli backend computes expectation values only; use --observable".to_string())
+    }
+
+    fn expectation(&self, circuit: &QuantumCircuit, observable: &PauliSum) -> Option<Result<Vec<f64>, String>> {
+        Some(term_expectations(circuit, observable, self.min_coefficient, self.max_weight))
+    }
+
+    fn with_option(&self, name: &str, value: &str) -> Result<Arc<dyn StateBackend>, String> {
+        match name {
+            "min-coefficient" => match value.parse::<f64>() {
+                Ok(min_coefficient) if min_coefficient >= 0.0 => {
+                    Ok(Arc::new(PropagationBackend { min_coefficient, max_weight: self.max_weight }))
+                }
+                _ => Err("--min-coefficient expects a non-negative number".to_string()),
+            },
+            "max-weight" => match value.parse::<usize>() {
+                Ok(max_weight) if max_weight > 0 => {
+                    Ok(Arc::new(PropagationBackend { min_coefficient: self.min_coefficient, max_weight: Some(max_weight) }))
+                }
+                _ => Err("--max-weight expects a positive integer".to_string()),
+            },
+            _ => Err(format!("the pauli backend has no --{} option", name)),
+        }
+    }
+}
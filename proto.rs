@@ -0,0 +1,667 @@
+//! Protobuf Module
+//! Language-neutral binary encoding of circuits and results (see quantummesh.proto)
+
+use std::collections::HashMap;
+use std::error::Error;
+use prost::Message;
+use crate::gpu_ops::{Complex, RotationAxis};
+use crate::noise::GateNoise;
+use crate::qsim::{self, QuantumCircuit, QuantumGate};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Circuit {
+    #[prost(uint32, tag = "1")]
+    pub num_qubits: u32,
+    #[prost(message, repeated, tag = "2")]
+    pub gates: Vec<Gate>,
+    #[prost(map = "string, string", tag = "3")]
+    pub metadata: HashMap<String, String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Gate {
+    #[prost(oneof = "gate::Kind", tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40")]
+    pub kind: Option<gate::Kind>,
+}
+
+pub mod gate {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Kind {
+        #[prost(message, tag = "1")]
+        Hadamard(super::SingleQubit),
+        #[prost(message, tag = "2")]
+        PauliX(super::SingleQubit),
+        #[prost(message, tag = "3")]
+        PauliY(super::SingleQubit),
+        #[prost(message, tag = "4")]
+        PauliZ(super::SingleQubit),
+        #[prost(message, tag = "5")]
+        Phase(super::Rotation),
+        #[prost(message, tag = "6")]
+        Cnot(super::Controlled),
+        #[prost(message, tag = "7")]
+        Swap(super::QubitPair),
+        #[prost(message, tag = "8")]
+        Toffoli(super::Toffoli),
+        #[prost(message, tag = "9")]
+        RotationX(super::Rotation),
+        #[prost(message, tag = "10")]
+        RotationY(super::Rotation),
+        #[prost(message, tag = "11")]
+        RotationZ(super::Rotation),
+        #[prost(message, tag = "12")]
+        Measurement(super::Measure),
+        #[prost(message, tag = "13")]
+        S(super::SingleQubit),
+        #[prost(message, tag = "14")]
+        Sdg(super::SingleQubit),
+        #[prost(message, tag = "15")]
+        T(super::SingleQubit),
+        #[prost(message, tag = "16")]
+        Tdg(super::SingleQubit),
+        #[prost(message, tag = "17")]
+        U(super::U3),
+        #[prost(message, tag = "18")]
+        Cz(super::Controlled),
+        #[prost(message, tag = "19")]
+        CPhase(super::ControlledRotation),
+        #[prost(message, tag = "20")]
+        Mcx(super::MultiControlled),
+        #[prost(message, tag = "21")]
+        Iswap(super::QubitPair),
+        #[prost(message, tag = "22")]
+        SqrtIswap(super::QubitPair),
+        #[prost(message, tag = "23")]
+        Sx(super::SingleQubit),
+        #[prost(message, tag = "24")]
+        Sxdg(super::SingleQubit),
+        #[prost(message, tag = "25")]
+        Unitary1q(super::Unitary1Q),
+        #[prost(message, tag = "26")]
+        UnitaryNq(super::UnitaryNQ),
+        #[prost(message, tag = "27")]
+        Controlled(super::ControlledGate),
+        #[prost(message, tag = "28")]
+        Rxx(super::PairRotation),
+        #[prost(message, tag = "29")]
+        Ryy(super::PairRotation),
+        #[prost(message, tag = "30")]
+        Rzz(super::PairRotation),
+        #[prost(message, tag = "31")]
+        GlobalHadamard(super::QubitList),
+        #[prost(message, tag = "32")]
+        GlobalRotation(super::GlobalRotation),
+        #[prost(message, tag = "33")]
+        Permutation(super::Permutation),
+        #[prost(message, tag = "34")]
+        Reset(super::SingleQubit),
+        #[prost(message, tag = "35")]
+        PauliRotation(super::PauliRotation),
+        #[prost(message, tag = "36")]
+        Givens(super::PairRotation),
+        #[prost(message, tag = "37")]
+        Ecr(super::QubitPair),
+        #[prost(message, tag = "38")]
+        Diagonal(super::Diagonal),
+        #[prost(message, tag = "39")]
+        IfBit(super::ConditionedGate),
+        #[prost(message, tag = "40")]
+        Noisy(super::NoisyGate),
+        #[prost(message, tag = "41")]
+        Snapshot(super::Snapshot),
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct SingleQubit {
+    #[prost(uint32, tag = "1")]
+    pub qubit: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Rotation {
+    #[prost(uint32, tag = "1")]
+    pub qubit: u32,
+    #[prost(double, tag = "2")]
+    pub angle: f64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct U3 {
+    #[prost(uint32, tag = "1")]
+    pub qubit: u32,
+    #[prost(double, tag = "2")]
+    pub theta: f64,
+    #[prost(double, tag = "3")]
+    pub phi: f64,
+    #[prost(double, tag = "4")]
+    pub lambda: f64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Controlled {
+    #[prost(uint32, tag = "1")]
+    pub control: u32,
+    #[prost(uint32, tag = "2")]
+    pub target: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ControlledRotation {
+    #[prost(uint32, tag = "1")]
+    pub control: u32,
+    #[prost(uint32, tag = "2")]
+    pub target: u32,
+    #[prost(double, tag = "3")]
+    pub angle: f64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct QubitPair {
+    #[prost(uint32, tag = "1")]
+    pub qubit1: u32,
+    #[prost(uint32, tag = "2")]
+    pub qubit2: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct PairRotation {
+    #[prost(uint32, tag = "1")]
+    pub qubit1: u32,
+    #[prost(uint32, tag = "2")]
+    pub qubit2: u32,
+    #[prost(double, tag = "3")]
+    pub angle: f64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Toffoli {
+    #[prost(uint32, tag = "1")]
+    pub control1: u32,
+    #[prost(uint32, tag = "2")]
+    pub control2: u32,
+    #[prost(uint32, tag = "3")]
+    pub target: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct MultiControlled {
+    #[prost(uint32, repeated, tag = "1")]
+    pub controls: Vec<u32>,
+    #[prost(uint32, tag = "2")]
+    pub target: u32,
+}
+
+/// Row-major 2x2 matrix as interleaved (re, im) pairs: m00, m01, m10, m11
+#[derive(Clone, PartialEq, Message)]
+pub struct Unitary1Q {
+    #[prost(uint32, tag = "1")]
+    pub qubit: u32,
+    #[prost(double, repeated, tag = "2")]
+    pub matrix: Vec<f64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct QubitList {
+    #[prost(uint32, repeated, tag = "1")]
+    pub qubits: Vec<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Axis {
+    X = 0,
+    Y = 1,
+    Z = 2,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct GlobalRotation {
+    #[prost(uint32, repeated, tag = "1")]
+    pub qubits: Vec<u32>,
+    #[prost(enumeration = "Axis", tag = "2")]
+    pub axis: i32,
+    #[prost(double, tag = "3")]
+    pub angle: f64,
+}
+
+/// Afterwards `qubits[k]` holds the state `qubits[pattern[k]]` had
+#[derive(Clone, PartialEq, Message)]
+pub struct Permutation {
+    #[prost(uint32, repeated, tag = "1")]
+    pub qubits: Vec<u32>,
+    #[prost(uint32, repeated, tag = "2")]
+    pub pattern: Vec<u32>,
+}
+
+/// exp(-i*angle/2 * P) where character `k` of `pauli` (I, X, Y or Z) acts on `qubits[k]`
+#[derive(Clone, PartialEq, Message)]
+pub struct PauliRotation {
+    #[prost(uint32, repeated, tag = "1")]
+    pub qubits: Vec<u32>,
+    #[prost(string, tag = "2")]
+    pub pauli: String,
+    #[prost(double, tag = "3")]
+    pub angle: f64,
+}
+
+/// Phase in radians for each basis state of `qubits`; `qubits[0]` is the lowest index bit
+#[derive(Clone, PartialEq, Message)]
+pub struct Diagonal {
+    #[prost(uint32, repeated, tag = "1")]
+    pub qubits: Vec<u32>,
+    #[prost(double, repeated, tag = "2")]
+    pub phases: Vec<f64>,
+}
+
+/// Row-major 2^k x 2^k matrix as interleaved (re, im) pairs; `qubits[0]` is the lowest index bit
+#[derive(Clone, PartialEq, Message)]
+pub struct UnitaryNQ {
+    #[prost(uint32, repeated, tag = "1")]
+    pub qubits: Vec<u32>,
+    #[prost(double, repeated, tag = "2")]
+    pub matrix: Vec<f64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ControlledGate {
+    #[prost(uint32, tag = "1")]
+    pub control: u32,
+    #[prost(message, optional, boxed, tag = "2")]
+    pub gate: Option<Box<Gate>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Snapshot {
+    #[prost(string, tag = "1")]
+    pub label: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct NoisyGate {
+    #[prost(double, optional, tag = "1")]
+    pub error: Option<f64>,
+    #[prost(double, optional, tag = "2")]
+    pub duration_ns: Option<f64>,
+    #[prost(message, optional, boxed, tag = "3")]
+    pub gate: Option<Box<Gate>>,
+}
+
+/// Wire-compatible with `SingleQubit`; `bit` is the classical bit, when not the qubit's own
+#[derive(Clone, PartialEq, Message)]
+pub struct Measure {
+    #[prost(uint32, tag = "1")]
+    pub qubit: u32,
+    #[prost(uint32, optional, tag = "2")]
+    pub bit: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ConditionedGate {
+    #[prost(uint32, tag = "1")]
+    pub bit: u32,
+    #[prost(message, optional, boxed, tag = "2")]
+    pub gate: Option<Box<Gate>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct MeasurementResult {
+    #[prost(uint32, tag = "1")]
+    pub num_qubits: u32,
+    #[prost(double, repeated, tag = "2")]
+    pub probabilities: Vec<f64>,
+}
+
+fn single(qubit: usize) -> SingleQubit {
+    SingleQubit { qubit: qubit as u32 }
+}
+
+fn rotation(qubit: usize, angle: f64) -> Rotation {
+    Rotation { qubit: qubit as u32, angle }
+}
+
+impl From<&QuantumGate> for Gate {
+    fn from(gate: &QuantumGate) -> Self {
+        use gate::Kind;
+        let kind = match gate {
+            QuantumGate::Hadamard { qubit } => Kind::Hadamard(single(*qubit)),
+            QuantumGate::PauliX { qubit } => Kind::PauliX(single(*qubit)),
+            QuantumGate::PauliY { qubit } => Kind::PauliY(single(*qubit)),
+            QuantumGate::PauliZ { qubit } => Kind::PauliZ(single(*qubit)),
+            QuantumGate::Phase { qubit, angle } => Kind::Phase(rotation(*qubit, *angle)),
+            QuantumGate::CNOT { control, target } => Kind::Cnot(Controlled {
+                control: *control as u32,
+                target: *target as u32,
+            }),
+            QuantumGate::SWAP { qubit1, qubit2 } => Kind::Swap(QubitPair {
+                qubit1: *qubit1 as u32,
+                qubit2: *qubit2 as u32,
+            }),
+            QuantumGate::Toffoli { control1, control2, target } => Kind::Toffoli(Toffoli {
+                control1: *control1 as u32,
+                control2: *control2 as u32,
+                target: *target as u32,
+            }),
+            QuantumGate::RotationX { qubit, angle } => Kind::RotationX(rotation(*qubit, *angle)),
+            QuantumGate::RotationY { qubit, angle } => Kind::RotationY(rotation(*qubit, *angle)),
+            QuantumGate::RotationZ { qubit, angle } => Kind::RotationZ(rotation(*qubit, *angle)),
+            QuantumGate::Measurement { qubit, bit } => Kind::Measurement(Measure {
+                qubit: *qubit as u32,
+                bit: bit.map(|bit| bit as u32),
+            }),
+            QuantumGate::Reset { qubit } => Kind::Reset(single(*qubit)),
+            QuantumGate::S { qubit } => Kind::S(single(*qubit)),
+            QuantumGate::Sdg { qubit } => Kind::Sdg(single(*qubit)),
+            QuantumGate::T { qubit } => Kind::T(single(*qubit)),
+            QuantumGate::Tdg { qubit } => Kind::Tdg(single(*qubit)),
+            QuantumGate::SX { qubit } => Kind::Sx(single(*qubit)),
+            QuantumGate::SXdg { qubit } => Kind::Sxdg(single(*qubit)),
+            QuantumGate::CZ { control, target } => Kind::Cz(Controlled {
+                control: *control as u32,
+                target: *target as u32,
+            }),
+            QuantumGate::CPhase { control, target, angle } => Kind::CPhase(ControlledRotation {
+                control: *control as u32,
+                target: *target as u32,
+                angle: *angle,
+            }),
+            QuantumGate::ISWAP { qubit1, qubit2 } => Kind::Iswap(QubitPair {
+                qubit1: *qubit1 as u32,
+                qubit2: *qubit2 as u32,
+            }),
+            QuantumGate::SqrtISWAP { qubit1, qubit2 } => Kind::SqrtIswap(QubitPair {
+                qubit1: *qubit1 as u32,
+                qubit2: *qubit2 as u32,
+            }),
+            QuantumGate::ECR { qubit1, qubit2 } => Kind::Ecr(QubitPair {
+                qubit1: *qubit1 as u32,
+                qubit2: *qubit2 as u32,
+            }),
+            QuantumGate::RXX { qubit1, qubit2, angle } => Kind::Rxx(PairRotation {
+                qubit1: *qubit1 as u32,
+                qubit2: *qubit2 as u32,
+                angle: *angle,
+            }),
+            QuantumGate::RYY { qubit1, qubit2, angle } => Kind::Ryy(PairRotation {
+                qubit1: *qubit1 as u32,
+                qubit2: *qubit2 as u32,
+                angle: *angle,
+            }),
+            QuantumGate::RZZ { qubit1, qubit2, angle } => Kind::Rzz(PairRotation {
+                qubit1: *qubit1 as u32,
+                qubit2: *qubit2 as u32,
+                angle: *angle,
+            }),
+            QuantumGate::Givens { qubit1, qubit2, angle } => Kind::Givens(PairRotation {
+                qubit1: *qubit1 as u32,
+                qubit2: *qubit2 as u32,
+                angle: *angle,
+            }),
+            QuantumGate::MCX { controls, target } => Kind::Mcx(MultiControlled {
+                controls: controls.iter().map(|&q| q as u32).collect(),
+                target: *target as u32,
+            }),
+            QuantumGate::U { qubit, theta, phi, lambda } => Kind::U(U3 {
+                qubit: *qubit as u32,
+                theta: *theta,
+                phi: *phi,
+                lambda: *lambda,
+            }),
+            QuantumGate::Unitary1Q { qubit, matrix } => Kind::Unitary1q(Unitary1Q {
+                qubit: *qubit as u32,
+                matrix: matrix.iter().flatten().flat_map(|c| [c.re, c.im]).collect(),
+            }),
+            QuantumGate::Controlled { control, gate } => Kind::Controlled(ControlledGate {
+                control: *control as u32,
+                gate: Some(Box::new(Gate::from(gate.as_ref()))),
+            }),
+            QuantumGate::IfBit { bit, gate } => Kind::IfBit(ConditionedGate {
+                bit: *bit as u32,
+                gate: Some(Box::new(Gate::from(gate.as_ref()))),
+            }),
+            QuantumGate::Noisy { noise, gate } => Kind::Noisy(NoisyGate {
+                error: noise.error,
+                duration_ns: noise.duration_ns,
+                gate: Some(Box::new(Gate::from(gate.as_ref()))),
+            }),
+            QuantumGate::Snapshot { label } => Kind::Snapshot(Snapshot { label: label.clone() }),
+            QuantumGate::GlobalHadamard { qubits } => Kind::GlobalHadamard(QubitList {
+                qubits: qubits.iter().map(|&q| q as u32).collect(),
+            }),
+            QuantumGate::GlobalRotation { qubits, axis, angle } => Kind::GlobalRotation(GlobalRotation {
+                qubits: qubits.iter().map(|&q| q as u32).collect(),
+                axis: match axis {
+                    RotationAxis::X => Axis::X,
+                    RotationAxis::Y => Axis::Y,
+                    RotationAxis::Z => Axis::Z,
+                } as i32,
+                angle: *angle,
+            }),
+            QuantumGate::Permutation { qubits, pattern } => Kind::Permutation(Permutation {
+                qubits: qubits.iter().map(|&q| q as u32).collect(),
+                pattern: pattern.iter().map(|&k| k as u32).collect(),
+            }),
+            QuantumGate::PauliRotation { qubits, pauli, angle } => Kind::PauliRotation(PauliRotation {
+                qubits: qubits.iter().map(|&q| q as u32).collect(),
+                pauli: pauli.clone(),
+                angle: *angle,
+            }),
+            QuantumGate::Diagonal { qubits, phases } => Kind::Diagonal(Diagonal {
+                qubits: qubits.iter().map(|&q| q as u32).collect(),
+                phases: phases.clone(),
+            }),
+            QuantumGate::UnitaryNQ { qubits, matrix } => Kind::UnitaryNq(UnitaryNQ {
+                qubits: qubits.iter().map(|&q| q as u32).collect(),
+                matrix: matrix.iter().flatten().flat_map(|c| [c.re, c.im]).collect(),
+            }),
+        };
+        Gate { kind: Some(kind) }
+    }
+}
+
+impl TryFrom<&Gate> for QuantumGate {
+    type Error = String;
+
+    fn try_from(gate: &Gate) -> Result<Self, Self::Error> {
+        use gate::Kind;
+        let kind = gate.kind.as_ref().ok_or("gate message without a kind")?;
+        Ok(match kind {
+            Kind::Hadamard(g) => QuantumGate::Hadamard { qubit: g.qubit as usize },
+            Kind::PauliX(g) => QuantumGate::PauliX { qubit: g.qubit as usize },
+            Kind::PauliY(g) => QuantumGate::PauliY { qubit: g.qubit as usize },
+            Kind::PauliZ(g) => QuantumGate::PauliZ { qubit: g.qubit as usize },
+            Kind::Phase(g) => QuantumGate::Phase { qubit: g.qubit as usize, angle: g.angle },
+            Kind::Cnot(g) => QuantumGate::CNOT {
+                control: g.control as usize,
+                target: g.target as usize,
+            },
+            Kind::Swap(g) => QuantumGate::SWAP {
+                qubit1: g.qubit1 as usize,
+                qubit2: g.qubit2 as usize,
+            },
+            Kind::Toffoli(g) => QuantumGate::Toffoli {
+                control1: g.control1 as usize,
+                control2: g.control2 as usize,
+                target: g.target as usize,
+            },
+            Kind::RotationX(g) => QuantumGate::RotationX { qubit: g.qubit as usize, angle: g.angle },
+            Kind::RotationY(g) => QuantumGate::RotationY { qubit: g.qubit as usize, angle: g.angle },
+            Kind::RotationZ(g) => QuantumGate::RotationZ { qubit: g.qubit as usize, angle: g.angle },
+            Kind::Measurement(g) => QuantumGate::Measurement {
+                qubit: g.qubit as usize,
+                bit: g.bit.map(|bit| bit as usize),
+            },
+            Kind::Reset(g) => QuantumGate::Reset { qubit: g.qubit as usize },
+            Kind::S(g) => QuantumGate::S { qubit: g.qubit as usize },
+            Kind::Sdg(g) => QuantumGate::Sdg { qubit: g.qubit as usize },
+            Kind::T(g) => QuantumGate::T { qubit: g.qubit as usize },
+            Kind::Tdg(g) => QuantumGate::Tdg { qubit: g.qubit as usize },
+            Kind::Sx(g) => QuantumGate::SX { qubit: g.qubit as usize },
+            Kind::Sxdg(g) => QuantumGate::SXdg { qubit: g.qubit as usize },
+            Kind::Cz(g) => QuantumGate::CZ {
+                control: g.control as usize,
+                target: g.target as usize,
+            },
+            Kind::CPhase(g) => QuantumGate::CPhase {
+                control: g.control as usize,
+                target: g.target as usize,
+                angle: g.angle,
+            },
+            Kind::Iswap(g) => QuantumGate::ISWAP {
+                qubit1: g.qubit1 as usize,
+                qubit2: g.qubit2 as usize,
+            },
+            Kind::SqrtIswap(g) => QuantumGate::SqrtISWAP {
+                qubit1: g.qubit1 as usize,
+                qubit2: g.qubit2 as usize,
+            },
+            Kind::Ecr(g) => QuantumGate::ECR {
+                qubit1: g.qubit1 as usize,
+                qubit2: g.qubit2 as usize,
+            },
+            Kind::Rxx(g) => QuantumGate::RXX {
+                qubit1: g.qubit1 as usize,
+                qubit2: g.qubit2 as usize,
+                angle: g.angle,
+            },
+            Kind::Ryy(g) => QuantumGate::RYY {
+                qubit1: g.qubit1 as usize,
+                qubit2: g.qubit2 as usize,
+                angle: g.angle,
+            },
+            Kind::Rzz(g) => QuantumGate::RZZ {
+                qubit1: g.qubit1 as usize,
+                qubit2: g.qubit2 as usize,
+                angle: g.angle,
+            },
+            Kind::Givens(g) => QuantumGate::Givens {
+                qubit1: g.qubit1 as usize,
+                qubit2: g.qubit2 as usize,
+                angle: g.angle,
+            },
+            Kind::Mcx(g) => QuantumGate::MCX {
+                controls: g.controls.iter().map(|&q| q as usize).collect(),
+                target: g.target as usize,
+            },
+            Kind::U(g) => QuantumGate::U {
+                qubit: g.qubit as usize,
+                theta: g.theta,
+                phi: g.phi,
+                lambda: g.lambda,
+            },
+            Kind::Unitary1q(g) => {
+                if g.matrix.len() != 8 {
+                    return Err(format!("unitary matrix has {} values, expected 8", g.matrix.len()));
+                }
+                let entry = |k: usize| Complex::new(g.matrix[2 * k], g.matrix[2 * k + 1]);
+                QuantumGate::Unitary1Q {
+                    qubit: g.qubit as usize,
+                    matrix: [[entry(0), entry(1)], [entry(2), entry(3)]],
+                }
+            }
+            Kind::GlobalHadamard(g) => QuantumGate::GlobalHadamard {
+                qubits: g.qubits.iter().map(|&q| q as usize).collect(),
+            },
+            Kind::GlobalRotation(g) => QuantumGate::GlobalRotation {
+                qubits: g.qubits.iter().map(|&q| q as usize).collect(),
+                axis: match Axis::try_from(g.axis) {
+                    Ok(Axis::X) => RotationAxis::X,
+                    Ok(Axis::Y) => RotationAxis::Y,
+                    Ok(Axis::Z) => RotationAxis::Z,
+                    Err(_) => return Err(format!("unknown rotation axis {}", g.axis)),
+                },
+                angle: g.angle,
+            },
+            Kind::Permutation(g) => QuantumGate::Permutation {
+                qubits: g.qubits.iter().map(|&q| q as usize).collect(),
+                pattern: g.pattern.iter().map(|&k| k as usize).collect(),
+            },
+            Kind::PauliRotation(g) => QuantumGate::PauliRotation {
+                qubits: g.qubits.iter().map(|&q| q as usize).collect(),
+                pauli: g.pauli.clone(),
+                angle: g.angle,
+            },
+            Kind::Diagonal(g) => QuantumGate::Diagonal {
+                qubits: g.qubits.iter().map(|&q| q as usize).collect(),
+                phases: g.phases.clone(),
+            },
+            Kind::Controlled(g) => QuantumGate::Controlled {
+                control: g.control as usize,
+                gate: Box::new(QuantumGate::try_from(g.gate.as_deref().ok_or("controlled gate without a gate")?)?),
+            },
+            Kind::IfBit(g) => QuantumGate::IfBit {
+                bit: g.bit as usize,
+                gate: Box::new(QuantumGate::try_from(g.gate.as_deref().ok_or("conditioned gate without a gate")?)?),
+            },
+            Kind::Noisy(g) => QuantumGate::Noisy {
+                noise: GateNoise { error: g.error, duration_ns: g.duration_ns },
+                gate: Box::new(QuantumGate::try_from(g.gate.as_deref().ok_or("noisy gate without a gate")?)?),
+            },
+            Kind::Snapshot(g) => QuantumGate::Snapshot { label: g.label.clone() },
+            Kind::UnitaryNq(g) => {
+                let dim = 1usize << g.qubits.len().min(16);
+                if g.matrix.len() != 2 * dim * dim {
+                    return Err(format!(
+                        "unitary matrix on {} qubits has {} values, expected {}",
+                        g.qubits.len(),
+                        g.matrix.len(),
+                        2 * dim * dim
+                    ));
+                }
+                let entries: Vec<Complex> = g.matrix.chunks(2).map(|pair| Complex::new(pair[0], pair[1])).collect();
+                QuantumGate::UnitaryNQ {
+                    qubits: g.qubits.iter().map(|&q| q as usize).collect(),
+                    matrix: entries.chunks(dim).map(|row| row.to_vec()).collect(),
+                }
+            }
+        })
+    }
+}
+
+/// Encode a circuit as protobuf bytes
+pub fn encode_circuit(circuit: &QuantumCircuit) -> Vec<u8> {
+    let message = Circuit {
+        num_qubits: circuit.num_qubits as u32,
+        gates: circuit.gates.iter().map(Gate::from).collect(),
+        metadata: circuit.metadata.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+    };
+    message.encode_to_vec()
+}
+
+/// Decode a circuit from protobuf bytes
+pub fn decode_circuit(bytes: &[u8]) -> Result<QuantumCircuit, Box<dyn Error>> {
+    let circuit = decode_circuit_unchecked(bytes)?;
+    qsim::validate_circuit(&circuit)?;
+    Ok(circuit)
+}
+
+/// Decode a circuit without validating its gates, for the linter
+pub fn decode_circuit_unchecked(bytes: &[u8]) -> Result<QuantumCircuit, Box<dyn Error>> {
+    let message = Circuit::decode(bytes)?;
+    let gates = message
+        .gates
+        .iter()
+        .map(QuantumGate::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    let circuit = QuantumCircuit {
+        num_qubits: message.num_qubits as usize,
+        gates,
+        metadata: message.metadata.into_iter().collect(),
+    };
+    Ok(circuit)
+}
+
+/// Encode measurement probabilities as protobuf bytes
+pub fn encode_results(num_qubits: usize, probabilities: &[f64]) -> Vec<u8> {
+    let message = MeasurementResult {
+        num_qubits: num_qubits as u32,
+        probabilities: probabilities.to_vec(),
+    };
+    message.encode_to_vec()
+}
+
+/// Decode measurement probabilities from protobuf bytes
+pub fn decode_results(bytes: &[u8]) -> Result<(usize, Vec<f64>), Box<dyn Error>> {
+    let message = MeasurementResult::decode(bytes)?;
+    Ok((message.num_qubits as usize, message.probabilities))
+}
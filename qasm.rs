@@ -0,0 +1,565 @@
+//! OpenQASM Module
+//! OpenQASM 2.0 import and export for quantum circuits
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::gpu_ops::RotationAxis;
+use crate::qsim::{self, QuantumCircuit, QuantumGate};
+
+/// Export a circuit as OpenQASM 2.0 source
+pub fn to_qasm(circuit: &QuantumCircuit) -> Result<String, String> {
+    let mut out = String::from("OPENQASM 2.0;\ninclude \"qelib1.inc\";\n");
+    for (key, value) in &circuit.metadata {
+        out.push_str(&format!("// @{}: {}\n", key, value.replace('\n', " ")));
+    }
+    out.push_str(&format!("qreg q[{}];\n", circuit.num_qubits));
+    // An OpenQASM 2 condition compares a whole register, so feed-forward needs one register per bit
+    let per_bit = circuit.gates.iter().any(|gate| matches!(gate, QuantumGate::IfBit { .. }));
+    if per_bit {
+        for bit in 0..circuit.num_clbits() {
+            out.push_str(&format!("creg c{}[1];\n", bit));
+        }
+    } else {
+        out.push_str(&format!("creg c[{}];\n", circuit.num_clbits()));
+    }
+
+    for gate in &circuit.gates {
+        let line = match gate {
+            QuantumGate::Measurement { qubit, bit } if per_bit => {
+                format!("measure q[{}] -> c{}[0];", qubit, bit.unwrap_or(*qubit))
+            }
+            QuantumGate::IfBit { bit, gate } => gate_line(gate)?
+                .lines()
+                .map(|line| format!("if(c{}==1) {}", bit, line))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            gate => gate_line(gate)?,
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+
+    Ok(out)
+}
+
+/// qelib1 gates whose controlled version is spelled with a `c` prefix
+const CONTROLLABLE: &[&str] = &["x", "y", "z", "h", "sx", "rx", "ry", "rz", "u1", "u3", "swap", "cx"];
+
+/// OpenQASM statement for one gate
+fn gate_line(gate: &QuantumGate) -> Result<String, String> {
+    let line = match gate {
+        QuantumGate::Hadamard { qubit } => format!("h q[{}];", qubit),
+        QuantumGate::PauliX { qubit } => format!("x q[{}];", qubit),
+        QuantumGate::PauliY { qubit } => format!("y q[{}];", qubit),
+        QuantumGate::PauliZ { qubit } => format!("z q[{}];", qubit),
+        QuantumGate::S { qubit } => format!("s q[{}];", qubit),
+        QuantumGate::Sdg { qubit } => format!("sdg q[{}];", qubit),
+        QuantumGate::T { qubit } => format!("t q[{}];", qubit),
+        QuantumGate::Tdg { qubit } => format!("tdg q[{}];", qubit),
+        QuantumGate::SX { qubit } => format!("sx q[{}];", qubit),
+        QuantumGate::SXdg { qubit } => format!("sxdg q[{}];", qubit),
+        QuantumGate::Phase { qubit, angle } => format!("u1({}) q[{}];", angle, qubit),
+        QuantumGate::CNOT { control, target } => format!("cx q[{}],q[{}];", control, target),
+        QuantumGate::CZ { control, target } => format!("cz q[{}],q[{}];", control, target),
+        QuantumGate::CPhase { control, target, angle } => {
+            format!("cu1({}) q[{}],q[{}];", angle, control, target)
+        }
+        QuantumGate::SWAP { qubit1, qubit2 } => format!("swap q[{}],q[{}];", qubit1, qubit2),
+        QuantumGate::ISWAP { qubit1, qubit2 } => format!("iswap q[{}],q[{}];", qubit1, qubit2),
+        QuantumGate::SqrtISWAP { qubit1, qubit2 } => format!("siswap q[{}],q[{}];", qubit1, qubit2),
+        QuantumGate::ECR { qubit1, qubit2 } => format!("ecr q[{}],q[{}];", qubit1, qubit2),
+        QuantumGate::RXX { qubit1, qubit2, angle } => format!("rxx({}) q[{}],q[{}];", angle, qubit1, qubit2),
+        QuantumGate::RYY { qubit1, qubit2, angle } => format!("ryy({}) q[{}],q[{}];", angle, qubit1, qubit2),
+        QuantumGate::RZZ { qubit1, qubit2, angle } => format!("rzz({}) q[{}],q[{}];", angle, qubit1, qubit2),
+        QuantumGate::Toffoli { control1, control2, target } => {
+            format!("ccx q[{}],q[{}],q[{}];", control1, control2, target)
+        }
+        QuantumGate::MCX { controls, target } => {
+            let name = match controls.len() {
+                0 => "x".to_string(),
+                1 => "cx".to_string(),
+                2 => "ccx".to_string(),
+                3 | 4 => format!("c{}x", controls.len()),
+                _ => "mcx".to_string(),
+            };
+            let operands: Vec<String> = controls
+                .iter()
+                .chain(std::iter::once(target))
+                .map(|q| format!("q[{}]", q))
+                .collect();
+            format!("{} {};", name, operands.join(","))
+        }
+        QuantumGate::RotationX { qubit, angle } => format!("rx({}) q[{}];", angle, qubit),
+        QuantumGate::RotationY { qubit, angle } => format!("ry({}) q[{}];", angle, qubit),
+        QuantumGate::RotationZ { qubit, angle } => format!("rz({}) q[{}];", angle, qubit),
+        QuantumGate::U { qubit, theta, phi, lambda } => {
+            format!("u3({},{},{}) q[{}];", theta, phi, lambda, qubit)
+        }
+        // OpenQASM 2 has no matrix gates; u3 matches up to global phase
+        QuantumGate::Unitary1Q { qubit, matrix } => {
+            let (theta, phi, lambda) = qsim::u3_angles(matrix);
+            format!("u3({},{},{}) q[{}];", theta, phi, lambda, qubit)
+        }
+        QuantumGate::UnitaryNQ { qubits, .. } => {
+            return Err(format!("OpenQASM 2 cannot express a dense {}-qubit unitary", qubits.len()));
+        }
+        QuantumGate::GlobalHadamard { qubits } => {
+            qubits.iter().map(|q| format!("h q[{}];", q)).collect::<Vec<_>>().join("\n")
+        }
+        QuantumGate::GlobalRotation { qubits, axis, angle } => {
+            let name = match axis {
+                RotationAxis::X => "rx",
+                RotationAxis::Y => "ry",
+                RotationAxis::Z => "rz",
+            };
+            qubits.iter().map(|q| format!("{}({}) q[{}];", name, angle, q)).collect::<Vec<_>>().join("\n")
+        }
+        QuantumGate::Permutation { qubits, pattern } => qsim::permutation_swaps(qubits, pattern)
+            .iter()
+            .map(|(q1, q2)| format!("swap q[{}],q[{}];", q1, q2))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        // Z-string rotations, exact up to global phase
+        QuantumGate::Diagonal { qubits, phases } => qsim::diagonal_gates(qubits, phases)
+            .iter()
+            .map(gate_line)
+            .collect::<Result<Vec<_>, _>>()?
+            .join("\n"),
+        // qelib1 has no Givens rotation; its two commuting Pauli rotations are exact
+        QuantumGate::Givens { qubit1, qubit2, angle } => qsim::givens_gates(*qubit1, *qubit2, *angle)
+            .iter()
+            .map(gate_line)
+            .collect::<Result<Vec<_>, _>>()?
+            .join("\n"),
+        // Exact: basis changes, a CNOT parity ladder and one rz
+        QuantumGate::PauliRotation { qubits, pauli, angle } => qsim::pauli_rotation_gates(qubits, pauli, *angle)
+            .iter()
+            .map(gate_line)
+            .collect::<Result<Vec<_>, _>>()?
+            .join("\n"),
+        // qelib1 names controlled gates by prefixing `c`: crx, cu3, ccx, cswap, ...
+        QuantumGate::Controlled { control, gate } => {
+            if let QuantumGate::Unitary1Q { .. } = **gate {
+                return Err("a controlled Unitary1Q has no exact OpenQASM 2 form".to_string());
+            }
+            // The dropped global phase would become a relative phase under a control
+            if let QuantumGate::Diagonal { .. } = **gate {
+                return Err("a controlled Diagonal has no exact OpenQASM 2 form".to_string());
+            }
+            let inner = gate_line(gate)?;
+            if inner.contains('\n') {
+                return Err("OpenQASM 2 export cannot control a gate written as several statements".to_string());
+            }
+            let (name, operands) = inner.split_once(' ').unwrap_or((&inner, ""));
+            let base = name.split('(').next().unwrap_or(name);
+            if !CONTROLLABLE.contains(&base) {
+                return Err(format!("OpenQASM 2 has no controlled form of '{}'", base));
+            }
+            format!("c{} q[{}],{}", name, control, operands)
+        }
+        QuantumGate::Measurement { qubit, bit } => format!("measure q[{}] -> c[{}];", qubit, bit.unwrap_or(*qubit)),
+        QuantumGate::Reset { qubit } => format!("reset q[{}];", qubit),
+        // Kept as a comment for readers; import skips it
+        QuantumGate::Snapshot { label } => format!("// snapshot {}", label),
+        // OpenQASM has no way to annotate noise, so only the gate itself is written
+        QuantumGate::Noisy { gate, .. } => return gate_line(gate),
+        // Top-level conditions are written by to_qasm; OpenQASM 2 cannot nest them
+        QuantumGate::IfBit { .. } => {
+            return Err("OpenQASM 2 cannot condition a gate that is already conditioned or controlled".to_string());
+        }
+    };
+    Ok(line)
+}
+
+/// Parse OpenQASM 2.0 source into a circuit
+pub fn parse_qasm(source: &str) -> Result<QuantumCircuit, String> {
+    let mut metadata = BTreeMap::new();
+    let mut code = String::new();
+    for line in source.lines() {
+        let (line, comment) = match line.find("//") {
+            Some(pos) => (&line[..pos], Some(&line[pos + 2..])),
+            None => (line, None),
+        };
+        if let Some((key, value)) = comment.and_then(parse_metadata_comment) {
+            metadata.insert(key, value);
+        }
+        code.push_str(line);
+        code.push('\n');
+    }
+
+    let mut registers: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut num_qubits = 0;
+    let mut gates = Vec::new();
+    // Classical registers as (first bit, size), and the one-bit registers measured into so far
+    let mut classical: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut num_clbits = 0;
+    let mut measured_into: HashSet<String> = HashSet::new();
+
+    for statement in code.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+        if let Some((register, value, body)) = split_condition(statement)? {
+            if classical.get(&register).map(|&(_, size)| size) != Some(1) || value != 1 {
+                return Err(format!(
+                    "only a one-bit register compared with 1 can be imported as a condition: {}",
+                    statement
+                ));
+            }
+            if !measured_into.contains(&register) {
+                return Err(format!("condition on register '{}' before any measurement into it", register));
+            }
+            let bit = classical[&register].0;
+            let (head, rest) = split_head(body);
+            let (name, params) = split_params(&head)?;
+            let operands = rest
+                .split(',')
+                .map(|arg| resolve_qubits(arg.trim(), &registers))
+                .collect::<Result<Vec<_>, _>>()?;
+            gates.extend(
+                build_gates(&name, &params, &operands, body)?
+                    .into_iter()
+                    .map(|gate| QuantumGate::IfBit { bit, gate: Box::new(gate) }),
+            );
+            continue;
+        }
+        let (head, rest) = split_head(statement);
+        match head.as_str() {
+            "OPENQASM" | "include" | "barrier" => {}
+            "creg" => {
+                let (name, size) = parse_indexed(rest)?;
+                let size = size.unwrap_or(1);
+                classical.insert(name, (num_clbits, size));
+                num_clbits += size;
+            }
+            "qreg" => {
+                let (name, size) = parse_indexed(rest)?;
+                let size = size.ok_or_else(|| format!("qreg without size: {}", statement))?;
+                registers.insert(name, (num_qubits, size));
+                num_qubits += size;
+            }
+            "measure" => {
+                let (target, destination) = rest.split_once("->").unwrap_or((rest, ""));
+                let qubits = resolve_qubits(target.trim(), &registers)?;
+                let bits = match destination.trim() {
+                    "" => qubits.clone(),
+                    destination => resolve_qubits(destination, &classical)?,
+                };
+                if bits.len() != qubits.len() {
+                    return Err(format!("measure needs one classical bit per qubit: {}", statement));
+                }
+                if qubits.len() == 1 {
+                    measured_into.insert(parse_indexed(destination)?.0);
+                }
+                for (qubit, bit) in qubits.into_iter().zip(bits) {
+                    gates.push(QuantumGate::Measurement { qubit, bit: (bit != qubit).then_some(bit) });
+                }
+            }
+            "reset" => {
+                for qubit in resolve_qubits(rest.trim(), &registers)? {
+                    gates.push(QuantumGate::Reset { qubit });
+                }
+            }
+            _ => {
+                let (name, params) = split_params(&head)?;
+                let operands = rest
+                    .split(',')
+                    .map(|arg| resolve_qubits(arg.trim(), &registers))
+                    .collect::<Result<Vec<_>, _>>()?;
+                gates.extend(build_gates(&name, &params, &operands, statement)?);
+            }
+        }
+    }
+
+    Ok(QuantumCircuit { num_qubits, gates, metadata })
+}
+
+/// Parse a `@key: value` metadata comment body
+pub(crate) fn parse_metadata_comment(comment: &str) -> Option<(String, String)> {
+    let body = comment.trim().strip_prefix('@')?;
+    let (key, value) = body.split_once(':')?;
+    Some((key.trim().to_string(), value.trim().to_string()))
+}
+
+/// Split a statement into its keyword (including any parameter list) and operands
+fn split_head(statement: &str) -> (String, &str) {
+    let mut depth = 0;
+    for (i, ch) in statement.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if c.is_whitespace() && depth == 0 => {
+                return (statement[..i].to_string(), statement[i..].trim());
+            }
+            _ => {}
+        }
+    }
+    (statement.to_string(), "")
+}
+
+/// Split `if(creg==value) body` into the register, the value and the body; `None` for other statements
+fn split_condition(statement: &str) -> Result<Option<(String, u64, &str)>, String> {
+    let condition = match statement.strip_prefix("if").map(str::trim_start) {
+        Some(condition) if condition.starts_with('(') => condition,
+        _ => return Ok(None),
+    };
+    let close = condition.find(')').ok_or_else(|| format!("unbalanced condition: {}", statement))?;
+    let (register, value) = condition[1..close]
+        .split_once("==")
+        .ok_or_else(|| format!("condition without '==': {}", statement))?;
+    let value = value.trim().parse::<u64>().map_err(|e| format!("bad condition value in {}: {}", statement, e))?;
+    Ok(Some((register.trim().to_string(), value, condition[close + 1..].trim())))
+}
+
+/// Split `name(p1, p2)` into the gate name and evaluated parameters
+fn split_params(head: &str) -> Result<(String, Vec<f64>), String> {
+    match head.find('(') {
+        Some(open) => {
+            let close = head.rfind(')').ok_or_else(|| format!("unbalanced parameters: {}", head))?;
+            let params = head[open + 1..close]
+                .split(',')
+                .map(eval_angle)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((head[..open].to_lowercase(), params))
+        }
+        None => Ok((head.to_lowercase(), Vec::new())),
+    }
+}
+
+/// Parse `name[index]` or a bare register name
+fn parse_indexed(arg: &str) -> Result<(String, Option<usize>), String> {
+    match arg.find('[') {
+        Some(open) => {
+            let close = arg.find(']').ok_or_else(|| format!("missing ']' in {}", arg))?;
+            let index = arg[open + 1..close]
+                .trim()
+                .parse::<usize>()
+                .map_err(|e| format!("bad index in {}: {}", arg, e))?;
+            Ok((arg[..open].trim().to_string(), Some(index)))
+        }
+        None => Ok((arg.trim().to_string(), None)),
+    }
+}
+
+/// Resolve a register operand to flat qubit (or classical bit) indices
+fn resolve_qubits(arg: &str, registers: &HashMap<String, (usize, usize)>) -> Result<Vec<usize>, String> {
+    let (name, index) = parse_indexed(arg)?;
+    let (offset, size) = registers
+        .get(&name)
+        .copied()
+        .ok_or_else(|| format!("unknown register '{}'", name))?;
+    match index {
+        Some(i) if i < size => Ok(vec![offset + i]),
+        Some(i) => Err(format!("index {} out of range for register '{}'", i, name)),
+        None => Ok((offset..offset + size).collect()),
+    }
+}
+
+/// Build gates for one statement, broadcasting single-qubit gates over whole registers
+fn build_gates(name: &str, params: &[f64], operands: &[Vec<usize>], statement: &str) -> Result<Vec<QuantumGate>, String> {
+    let param = |i: usize| {
+        params
+            .get(i)
+            .copied()
+            .ok_or_else(|| format!("missing parameter in '{}'", statement))
+    };
+    let single = |i: usize| match operands.get(i).map(|qubits| qubits.as_slice()) {
+        Some([qubit]) => Ok(*qubit),
+        _ => Err(format!("expected single-qubit operand in '{}'", statement)),
+    };
+
+    // Every operand but the last is a control
+    if matches!(name, "c3x" | "c4x" | "mcx") {
+        let qubits = (0..operands.len()).map(single).collect::<Result<Vec<_>, _>>()?;
+        let (target, controls) = qubits
+            .split_last()
+            .ok_or_else(|| format!("missing target in '{}'", statement))?;
+        let expected = match name {
+            "c3x" => Some(3),
+            "c4x" => Some(4),
+            _ => None,
+        };
+        if expected.is_some_and(|n| n != controls.len()) {
+            return Err(format!("wrong number of operands in '{}'", statement));
+        }
+        return Ok(vec![QuantumGate::MCX { controls: controls.to_vec(), target: *target }]);
+    }
+
+    if operands.len() == 1 {
+        let mut gates = Vec::new();
+        for &qubit in &operands[0] {
+            let gate = match name {
+                "h" => QuantumGate::Hadamard { qubit },
+                "x" => QuantumGate::PauliX { qubit },
+                "y" => QuantumGate::PauliY { qubit },
+                "z" => QuantumGate::PauliZ { qubit },
+                "s" => QuantumGate::S { qubit },
+                "sdg" => QuantumGate::Sdg { qubit },
+                "t" => QuantumGate::T { qubit },
+                "tdg" => QuantumGate::Tdg { qubit },
+                "sx" => QuantumGate::SX { qubit },
+                "sxdg" => QuantumGate::SXdg { qubit },
+                "u1" | "p" => QuantumGate::Phase { qubit, angle: param(0)? },
+                "rx" => QuantumGate::RotationX { qubit, angle: param(0)? },
+                "ry" => QuantumGate::RotationY { qubit, angle: param(0)? },
+                "rz" => QuantumGate::RotationZ { qubit, angle: param(0)? },
+                "u3" | "u" => QuantumGate::U { qubit, theta: param(0)?, phi: param(1)?, lambda: param(2)? },
+                "u2" => QuantumGate::U {
+                    qubit,
+                    theta: std::f64::consts::FRAC_PI_2,
+                    phi: param(0)?,
+                    lambda: param(1)?,
+                },
+                _ => return Err(format!("unsupported gate '{}'", name)),
+            };
+            gates.push(gate);
+        }
+        return Ok(gates);
+    }
+
+    let gate = match name {
+        "cx" | "cnot" => QuantumGate::CNOT { control: single(0)?, target: single(1)? },
+        "cz" => QuantumGate::CZ { control: single(0)?, target: single(1)? },
+        "cu1" | "cp" | "cphase" => QuantumGate::CPhase {
+            control: single(0)?,
+            target: single(1)?,
+            angle: param(0)?,
+        },
+        "swap" => QuantumGate::SWAP { qubit1: single(0)?, qubit2: single(1)? },
+        "iswap" => QuantumGate::ISWAP { qubit1: single(0)?, qubit2: single(1)? },
+        "siswap" => QuantumGate::SqrtISWAP { qubit1: single(0)?, qubit2: single(1)? },
+        "ecr" => QuantumGate::ECR { qubit1: single(0)?, qubit2: single(1)? },
+        "rxx" => QuantumGate::RXX { qubit1: single(0)?, qubit2: single(1)?, angle: param(0)? },
+        "ryy" => QuantumGate::RYY { qubit1: single(0)?, qubit2: single(1)?, angle: param(0)? },
+        "rzz" => QuantumGate::RZZ { qubit1: single(0)?, qubit2: single(1)?, angle: param(0)? },
+        "ccx" => QuantumGate::Toffoli {
+            control1: single(0)?,
+            control2: single(1)?,
+            target: single(2)?,
+        },
+        "cy" | "ch" | "csx" | "crx" | "cry" | "crz" | "cu3" => {
+            let qubit = single(1)?;
+            let gate = match name {
+                "cy" => QuantumGate::PauliY { qubit },
+                "ch" => QuantumGate::Hadamard { qubit },
+                "csx" => QuantumGate::SX { qubit },
+                "crx" => QuantumGate::RotationX { qubit, angle: param(0)? },
+                "cry" => QuantumGate::RotationY { qubit, angle: param(0)? },
+                "crz" => QuantumGate::RotationZ { qubit, angle: param(0)? },
+                _ => QuantumGate::U { qubit, theta: param(0)?, phi: param(1)?, lambda: param(2)? },
+            };
+            QuantumGate::Controlled { control: single(0)?, gate: Box::new(gate) }
+        }
+        "cswap" => QuantumGate::Controlled {
+            control: single(0)?,
+            gate: Box::new(QuantumGate::SWAP { qubit1: single(1)?, qubit2: single(2)? }),
+        },
+        _ => return Err(format!("unsupported gate '{}'", name)),
+    };
+    Ok(vec![gate])
+}
+
+/// Evaluate an angle expression such as `-3*pi/4`
+pub(crate) fn eval_angle(expr: &str) -> Result<f64, String> {
+    eval_expression(expr, &BTreeMap::new())
+}
+
+/// Evaluate an angle expression that may also name variables, such as `theta/2 + pi`
+pub(crate) fn eval_expression(expr: &str, variables: &BTreeMap<String, f64>) -> Result<f64, String> {
+    let tokens: Vec<char> = expr.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut parser = ExprParser { tokens: &tokens, pos: 0, variables };
+    let value = parser.sum()?;
+    if parser.pos != tokens.len() {
+        return Err(format!("unexpected input in angle '{}'", expr));
+    }
+    Ok(value)
+}
+
+/// Recursive-descent parser for angle arithmetic
+struct ExprParser<'a> {
+    tokens: &'a [char],
+    pos: usize,
+    variables: &'a BTreeMap<String, f64>,
+}
+
+impl ExprParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn sum(&mut self) -> Result<f64, String> {
+        let mut value = self.product()?;
+        while let Some(op) = self.peek().filter(|c| *c == '+' || *c == '-') {
+            self.pos += 1;
+            let rhs = self.product()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<f64, String> {
+        let mut value = self.unary()?;
+        while let Some(op) = self.peek().filter(|c| *c == '*' || *c == '/') {
+            self.pos += 1;
+            let rhs = self.unary()?;
+            value = if op == '*' { value * rhs } else { value / rhs };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<f64, String> {
+        match self.peek() {
+            Some('-') => {
+                self.pos += 1;
+                Ok(-self.unary()?)
+            }
+            Some('+') => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.atom(),
+        }
+    }
+
+    fn atom(&mut self) -> Result<f64, String> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let value = self.sum()?;
+                if self.peek() != Some(')') {
+                    return Err("missing ')' in angle".to_string());
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start = self.pos;
+                while self.peek().map(|c| c.is_ascii_alphanumeric() || c == '_').unwrap_or(false) {
+                    self.pos += 1;
+                }
+                let ident: String = self.tokens[start..self.pos].iter().collect();
+                if let Some(&value) = self.variables.get(&ident) {
+                    return Ok(value);
+                }
+                match ident.to_lowercase().as_str() {
+                    "pi" => Ok(std::f64::consts::PI),
+                    _ => Err(format!("unknown identifier '{}' in angle", ident)),
+                }
+            }
+            Some(_) => {
+                let start = self.pos;
+                while let Some(c) = self.peek() {
+                    let exponent_sign = (c == '-' || c == '+')
+                        && self.pos > start
+                        && matches!(self.tokens[self.pos - 1], 'e' | 'E');
+                    if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || exponent_sign {
+                        self.pos += 1;
+                    } else {
+                        break;
+                    }
+                }
+                let literal: String = self.tokens[start..self.pos].iter().collect();
+                literal
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid number '{}' in angle", literal))
+            }
+            None => Err("unexpected end of angle expression".to_string()),
+        }
+    }
+}
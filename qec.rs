@@ -0,0 +1,513 @@
+//! QEC Sampling Module
+//! Logical error rates of noisy Stim circuits from bit-packed Pauli frame sampling and matching
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use crate::dem::{self, Clifford, DetectorErrorModel, FlatCircuit, Op};
+use crate::noise::NoiseModel;
+
+/// Physical error rates swept when none are given
+pub const DEFAULT_RATES: &[f64] = &[0.001, 0.002, 0.005, 0.01, 0.02];
+
+/// Words of 64 shots simulated together per pass
+const BATCH_WORDS: usize = 16;
+
+/// Below this rate, error masks are drawn by jumping between errors instead of bit by bit
+const SPARSE_RATE: f64 = 0.1;
+
+/// Most detectors one error may flip and still be split into matching edges
+const MAX_DECOMPOSED_DETECTORS: usize = 8;
+
+/// Largest set of fired detectors matched exactly, over all 2^n subsets
+const EXACT_MATCHING_DETECTORS: usize = 12;
+
+/// Boundary distance of a detector with no path to the boundary
+const UNREACHABLE_COST: f64 = 1e9;
+
+/// Logical error statistics at one physical error rate
+#[derive(Debug, Clone, Serialize)]
+pub struct LogicalPoint {
+    /// Uniform circuit-level error rate added to the circuit, or none for its own noise only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub physical_error: Option<f64>,
+    pub shots: usize,
+    /// Shots where the decoder's prediction missed an observable flip
+    pub logical_errors: usize,
+    pub logical_error_rate: f64,
+    pub std_error: f64,
+    /// Mean number of detectors firing per shot
+    pub mean_detection_events: f64,
+}
+
+/// Depolarizing noise at rate `p` after every gate and flips at rate `p` on every measurement
+pub fn uniform_noise(p: f64) -> NoiseModel {
+    NoiseModel {
+        single_qubit_error: p,
+        two_qubit_error: p,
+        measurement_error: p,
+        ..NoiseModel::default()
+    }
+}
+
+/// Sample `shots` runs of a noisy Stim circuit and count the shots whose logical observables
+/// a matching decoder, built from the circuit's detector error model, fails to correct.
+/// Shots run 64 to a machine word, split across `threads` threads.
+pub fn sample<R: Rng>(
+    source: &str,
+    noise: Option<&NoiseModel>,
+    shots: usize,
+    threads: usize,
+    rng: &mut R,
+) -> Result<LogicalPoint, String> {
+    if shots == 0 {
+        return Err("at least one shot is needed".to_string());
+    }
+    let circuit = dem::flatten(source, noise)?;
+    if circuit.observables.is_empty() {
+        return Err("the circuit has no OBSERVABLE_INCLUDE, so no logical error can be counted".to_string());
+    }
+    if circuit.observables.len() > 64 {
+        return Err("at most 64 logical observables are supported".to_string());
+    }
+    let decoder = MatchingDecoder::new(&circuit.error_model())?;
+
+    let threads = threads.clamp(1, shots.div_ceil(64));
+    let seeds: Vec<u64> = (0..threads).map(|_| rng.gen()).collect();
+    let (logical_errors, detection_events) = std::thread::scope(|scope| {
+        let workers: Vec<_> = seeds
+            .into_iter()
+            .enumerate()
+            .map(|(i, seed)| {
+                let count = shots / threads + usize::from(i < shots % threads);
+                let (circuit, decoder) = (&circuit, &decoder);
+                scope.spawn(move || run_shots(circuit, decoder, count, &mut StdRng::seed_from_u64(seed)))
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("sampling thread panicked"))
+            .fold((0, 0), |(errors, events), (e, d)| (errors + e, events + d))
+    });
+
+    let rate = logical_errors as f64 / shots as f64;
+    Ok(LogicalPoint {
+        physical_error: None,
+        shots,
+        logical_errors,
+        logical_error_rate: rate,
+        std_error: (rate * (1.0 - rate) / shots as f64).sqrt(),
+        mean_detection_events: detection_events as f64 / shots as f64,
+    })
+}
+
+/// Logical error rate at each uniform physical error rate, on top of the circuit's own noise
+pub fn sweep<R: Rng>(source: &str, rates: &[f64], shots: usize, threads: usize, rng: &mut R) -> Result<Vec<LogicalPoint>, String> {
+    if let Some(rate) = rates.iter().find(|rate| !(0.0..=0.5).contains(*rate)) {
+        return Err(format!("physical error rate {} is not between 0 and 0.5", rate));
+    }
+    rates
+        .iter()
+        .map(|&rate| {
+            let point = sample(source, Some(&uniform_noise(rate)), shots, threads, rng)?;
+            Ok(LogicalPoint { physical_error: Some(rate), ..point })
+        })
+        .collect()
+}
+
+/// Logical errors and detection events over `shots` shots, one batch of words at a time
+fn run_shots<R: Rng>(circuit: &FlatCircuit, decoder: &MatchingDecoder, shots: usize, rng: &mut R) -> (usize, usize) {
+    let (mut logical_errors, mut detection_events) = (0, 0);
+    let mut done = 0;
+    while done < shots {
+        let batch = (shots - done).min(BATCH_WORDS * 64);
+        let records = sample_records(circuit, batch.div_ceil(64), rng);
+        let mut fired: Vec<Vec<usize>> = vec![Vec::new(); batch];
+        for (detector, parity) in circuit.detectors.iter().enumerate() {
+            for shot in set_bits(&parity_words(&records, parity), batch) {
+                fired[shot].push(detector);
+            }
+        }
+        let mut flipped = vec![0u64; batch];
+        for (observable, parity) in circuit.observables.iter().enumerate() {
+            for shot in set_bits(&parity_words(&records, parity), batch) {
+                flipped[shot] |= 1 << observable;
+            }
+        }
+        for (detectors, actual) in fired.iter().zip(flipped) {
+            detection_events += detectors.len();
+            if decoder.predict(detectors) != actual {
+                logical_errors += 1;
+            }
+        }
+        done += batch;
+    }
+    (logical_errors, detection_events)
+}
+
+/// Flips of every measurement record relative to the noiseless circuit, one bit per shot.
+/// Each shot carries a Pauli frame, the error accumulated so far, through the Cliffords.
+fn sample_records<R: Rng>(circuit: &FlatCircuit, words: usize, rng: &mut R) -> Vec<Vec<u64>> {
+    let mut x = vec![vec![0u64; words]; circuit.num_qubits];
+    let mut z = vec![vec![0u64; words]; circuit.num_qubits];
+    let mut records = vec![vec![0u64; words]; circuit.num_records];
+    for op in &circuit.ops {
+        match op {
+            Op::Gate(clifford, qubits) => propagate(*clifford, qubits, &mut x, &mut z),
+            Op::Measure { qubit, record, reset } => {
+                records[*record].copy_from_slice(&x[*qubit]);
+                if *reset {
+                    x[*qubit].fill(0);
+                    z[*qubit].fill(0);
+                }
+            }
+            Op::Reset(qubit) => {
+                x[*qubit].fill(0);
+                z[*qubit].fill(0);
+            }
+            Op::Feedback { pauli, record, qubit } => {
+                if *pauli != 'Z' {
+                    xor_into(&mut x[*qubit], &records[*record]);
+                }
+                if *pauli != 'X' {
+                    xor_into(&mut z[*qubit], &records[*record]);
+                }
+            }
+            Op::Error { probability, paulis } => {
+                if *probability <= 0.0 {
+                    continue;
+                }
+                let mask = bernoulli_words(*probability, words, rng);
+                for &(pauli, qubit) in paulis {
+                    if pauli != 'Z' {
+                        xor_into(&mut x[qubit], &mask);
+                    }
+                    if pauli != 'X' {
+                        xor_into(&mut z[qubit], &mask);
+                    }
+                }
+            }
+            Op::Flip { probability, record } => {
+                if *probability > 0.0 {
+                    xor_into(&mut records[*record], &bernoulli_words(*probability, words, rng));
+                }
+            }
+        }
+    }
+    records
+}
+
+/// Frames after a gate from those before it: P becomes G P G†
+fn propagate(clifford: Clifford, qubits: &[usize], x: &mut [Vec<u64>], z: &mut [Vec<u64>]) {
+    match (clifford, qubits) {
+        (Clifford::SwapXZ, &[q]) => std::mem::swap(&mut x[q], &mut z[q]),
+        // X -> Y
+        (Clifford::PhaseZ, &[q]) => xor_into(&mut z[q], &x[q]),
+        // Z -> Y
+        (Clifford::PhaseX, &[q]) => xor_into(&mut x[q], &z[q]),
+        // X_c -> X_c X_t and Z_t -> Z_c Z_t
+        (Clifford::CX, &[c, t]) => {
+            xor_rows(x, t, c);
+            xor_rows(z, c, t);
+        }
+        // X_c -> X_c Y_t, X_t -> Z_c X_t and Z_t -> Z_c Z_t
+        (Clifford::CY, &[c, t]) => {
+            xor_into(&mut z[c], &x[t]);
+            xor_rows(z, c, t);
+            xor_rows(x, t, c);
+            xor_into(&mut z[t], &x[c]);
+        }
+        // X_a -> X_a Z_b and X_b -> Z_a X_b
+        (Clifford::CZ, &[a, b]) => {
+            xor_into(&mut z[a], &x[b]);
+            xor_into(&mut z[b], &x[a]);
+        }
+        (Clifford::Swap, &[a, b]) => {
+            x.swap(a, b);
+            z.swap(a, b);
+        }
+        _ => unreachable!("gates are built with their own number of qubits"),
+    }
+}
+
+/// Independent Bernoulli(p) bits, 64 to a word
+fn bernoulli_words<R: Rng>(p: f64, words: usize, rng: &mut R) -> Vec<u64> {
+    let mut mask = vec![0u64; words];
+    if p >= 1.0 {
+        mask.fill(!0);
+    } else if p < SPARSE_RATE {
+        // Gaps between errors are geometric, so draw those instead of every bit
+        let scale = 1.0 / (1.0 - p).ln();
+        let mut position = 0usize;
+        loop {
+            let gap = ((1.0 - rng.gen::<f64>()).ln() * scale) as usize;
+            position = position.saturating_add(gap);
+            if position >= words * 64 {
+                break;
+            }
+            mask[position / 64] |= 1 << (position % 64);
+            position += 1;
+        }
+    } else {
+        for word in &mut mask {
+            for bit in 0..64 {
+                if rng.gen_bool(p) {
+                    *word |= 1 << bit;
+                }
+            }
+        }
+    }
+    mask
+}
+
+/// Parity of several records, shot by shot
+fn parity_words(records: &[Vec<u64>], indices: &[usize]) -> Vec<u64> {
+    let mut parity = vec![0u64; records.first().map_or(0, Vec::len)];
+    for &index in indices {
+        xor_into(&mut parity, &records[index]);
+    }
+    parity
+}
+
+/// Shots below `limit` whose bit is set
+fn set_bits(words: &[u64], limit: usize) -> impl Iterator<Item = usize> + '_ {
+    words.iter().enumerate().flat_map(move |(w, &word)| {
+        (0..64).filter(move |bit| word >> bit & 1 == 1).map(move |bit| w * 64 + bit).filter(move |&shot| shot < limit)
+    })
+}
+
+fn xor_into(target: &mut [u64], source: &[u64]) {
+    for (t, s) in target.iter_mut().zip(source) {
+        *t ^= s;
+    }
+}
+
+/// `rows[target] ^= rows[source]` for two rows of the same table
+fn xor_rows(rows: &mut [Vec<u64>], target: usize, source: usize) {
+    let (target_row, source_row) = if target < source {
+        let (low, high) = rows.split_at_mut(source);
+        (&mut low[target], &high[0])
+    } else {
+        let (low, high) = rows.split_at_mut(target);
+        (&mut high[0], &low[source])
+    };
+    xor_into(target_row, source_row);
+}
+
+/// Minimum-weight matching of fired detectors over the detector error model's graph.
+/// Nodes are detectors plus one boundary node; each edge is an error flipping one or two
+/// detectors, weighted by its log-likelihood ratio and labelled with the observables it flips.
+struct MatchingDecoder {
+    /// (neighbour, weight, observables) of each node, with the boundary last
+    adjacency: Vec<Vec<(usize, f64, u64)>>,
+}
+
+impl MatchingDecoder {
+    /// Errors flipping more than two detectors are split into edges already in the graph,
+    /// and their probability folded into those edges
+    fn new(model: &DetectorErrorModel) -> Result<Self, String> {
+        let boundary = model.detector_coords.len();
+        let observables = |error: &dem::ErrorMechanism| error.observables.iter().fold(0u64, |mask, &k| mask | 1 << k);
+        let mut edges: HashMap<(usize, usize), (f64, u64)> = HashMap::new();
+        let add = |edges: &mut HashMap<(usize, usize), (f64, u64)>, key: (usize, usize), p: f64, mask: u64| {
+            let entry = edges.entry(key).or_insert((0.0, mask));
+            if entry.1 == mask {
+                entry.0 = entry.0 + p - 2.0 * entry.0 * p;
+            } else if p > entry.0 {
+                // Same detectors, different observables: keep the likelier explanation
+                *entry = (p, mask);
+            }
+        };
+        for error in model.errors.iter().filter(|error| error.detectors.len() <= 2) {
+            match error.detectors[..] {
+                [d] => add(&mut edges, (d, boundary), error.probability, observables(error)),
+                [a, b] => add(&mut edges, (a, b), error.probability, observables(error)),
+                // Undetectable logical errors set a floor no decoder can lower
+                _ => {}
+            }
+        }
+        for error in model.errors.iter().filter(|error| error.detectors.len() > 2) {
+            let pieces = decompose(&error.detectors, observables(error), boundary, &edges).ok_or_else(|| {
+                format!(
+                    "the error flipping detectors {:?} cannot be split into pairs for matching",
+                    error.detectors
+                )
+            })?;
+            for key in pieces {
+                let mask = edges[&key].1;
+                add(&mut edges, key, error.probability, mask);
+            }
+        }
+
+        let mut adjacency = vec![Vec::new(); boundary + 1];
+        for ((a, b), (p, mask)) in edges {
+            let weight = ((1.0 - p) / p).ln().max(0.0);
+            adjacency[a].push((b, weight, mask));
+            adjacency[b].push((a, weight, mask));
+        }
+        Ok(MatchingDecoder { adjacency })
+    }
+
+    /// Observables flipped by the likeliest correction found for these fired detectors, each
+    /// matched to another or to the boundary. Small sets are matched exactly; larger ones
+    /// greedily, by how much a pair saves over sending both detectors to the boundary.
+    fn predict(&self, fired: &[usize]) -> u64 {
+        if fired.is_empty() {
+            return 0;
+        }
+        let boundary = self.adjacency.len() - 1;
+        let count = fired.len();
+        let mut pairs: Vec<Vec<Option<(f64, u64)>>> = vec![vec![None; count]; count];
+        let mut to_boundary: Vec<Option<(f64, u64)>> = vec![None; count];
+        for (i, &source) in fired.iter().enumerate() {
+            let targets: Vec<usize> = fired[i + 1..].iter().copied().chain([boundary]).collect();
+            for (node, distance, mask) in self.shortest_paths(source, &targets) {
+                match fired[i + 1..].iter().position(|&d| d == node) {
+                    Some(j) => {
+                        pairs[i][i + 1 + j] = Some((distance, mask));
+                        pairs[i + 1 + j][i] = Some((distance, mask));
+                    }
+                    None => to_boundary[i] = Some((distance, mask)),
+                }
+            }
+        }
+        if count <= EXACT_MATCHING_DETECTORS {
+            exact_matching(&pairs, &to_boundary).unwrap_or(0)
+        } else {
+            greedy_matching(&pairs, &to_boundary)
+        }
+    }
+
+    /// Dijkstra from `source` until every reachable target is settled, returning each
+    /// target's distance and the observables flipped along its path. The boundary is never
+    /// passed through.
+    fn shortest_paths(&self, source: usize, targets: &[usize]) -> Vec<(usize, f64, u64)> {
+        let boundary = self.adjacency.len() - 1;
+        let mut best: HashMap<usize, (f64, u64)> = HashMap::from([(source, (0.0, 0))]);
+        let mut settled: HashMap<usize, (f64, u64)> = HashMap::new();
+        // Non-negative floats order like their bit patterns
+        let mut queue = BinaryHeap::from([Reverse((0f64.to_bits(), source))]);
+        let mut found = Vec::new();
+        while let Some(Reverse((_, node))) = queue.pop() {
+            if settled.contains_key(&node) {
+                continue;
+            }
+            let (distance, mask) = best[&node];
+            settled.insert(node, (distance, mask));
+            if targets.contains(&node) {
+                found.push((node, distance, mask));
+                if found.len() == targets.len() {
+                    break;
+                }
+            }
+            if node == boundary {
+                continue;
+            }
+            for &(next, weight, edge_mask) in &self.adjacency[node] {
+                let candidate = distance + weight;
+                if !settled.contains_key(&next) && best.get(&next).is_none_or(|&(d, _)| candidate < d) {
+                    best.insert(next, (candidate, mask ^ edge_mask));
+                    queue.push(Reverse((candidate.to_bits(), next)));
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Minimum-weight perfect matching with the boundary, by dynamic programming over the
+/// subsets already matched; the lowest unmatched detector is always matched next
+fn exact_matching(pairs: &[Vec<Option<(f64, u64)>>], to_boundary: &[Option<(f64, u64)>]) -> Option<u64> {
+    let count = to_boundary.len();
+    let full = (1usize << count) - 1;
+    let mut best: Vec<Option<(f64, u64)>> = vec![None; full + 1];
+    best[0] = Some((0.0, 0));
+    for matched in 0..full {
+        let Some((cost, mask)) = best[matched] else {
+            continue;
+        };
+        let i = (!matched).trailing_zeros() as usize;
+        let mut relax = |next: usize, (distance, path): (f64, u64)| {
+            if best[next].is_none_or(|(c, _)| cost + distance < c) {
+                best[next] = Some((cost + distance, mask ^ path));
+            }
+        };
+        if let Some(path) = to_boundary[i] {
+            relax(matched | 1 << i, path);
+        }
+        for j in (i + 1..count).filter(|j| matched >> j & 1 == 0) {
+            if let Some(path) = pairs[i][j] {
+                relax(matched | 1 << i | 1 << j, path);
+            }
+        }
+    }
+    best[full].map(|(_, mask)| mask)
+}
+
+/// Pairs in order of their saving over two boundary matches, the rest to the boundary
+fn greedy_matching(pairs: &[Vec<Option<(f64, u64)>>], to_boundary: &[Option<(f64, u64)>]) -> u64 {
+    // A detector that cannot reach the boundary should pair whatever the cost
+    let boundary_cost = |i: usize| to_boundary[i].map_or(UNREACHABLE_COST, |(distance, _)| distance);
+    let mut candidates: Vec<(f64, usize, usize, u64)> = Vec::new();
+    for (i, row) in pairs.iter().enumerate() {
+        for (j, path) in row.iter().enumerate().skip(i + 1) {
+            if let Some((distance, mask)) = path {
+                let saving = distance - boundary_cost(i) - boundary_cost(j);
+                if saving < 0.0 {
+                    candidates.push((saving, i, j, *mask));
+                }
+            }
+        }
+    }
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut matched = vec![false; to_boundary.len()];
+    let mut prediction = 0;
+    for (_, i, j, mask) in candidates {
+        if !matched[i] && !matched[j] {
+            matched[i] = true;
+            matched[j] = true;
+            prediction ^= mask;
+        }
+    }
+    for (i, path) in to_boundary.iter().enumerate() {
+        if let (false, Some((_, mask))) = (matched[i], path) {
+            prediction ^= mask;
+        }
+    }
+    prediction
+}
+
+/// Split a set of detectors into existing edges, pairs or single detectors to the boundary,
+/// whose observables combine to `mask`
+fn decompose(
+    detectors: &[usize],
+    mask: u64,
+    boundary: usize,
+    edges: &HashMap<(usize, usize), (f64, u64)>,
+) -> Option<Vec<(usize, usize)>> {
+    if detectors.len() > MAX_DECOMPOSED_DETECTORS {
+        return None;
+    }
+    let Some((&first, rest)) = detectors.split_first() else {
+        return (mask == 0).then(Vec::new);
+    };
+    let options = rest
+        .iter()
+        .enumerate()
+        .map(|(j, &other)| ((first, other), Some(j)))
+        .chain([((first, boundary), None)]);
+    for (key, partner) in options {
+        let Some(&(_, edge_mask)) = edges.get(&key) else {
+            continue;
+        };
+        let remaining: Vec<usize> = rest.iter().enumerate().filter(|&(j, _)| Some(j) != partner).map(|(_, &d)| d).collect();
+        if let Some(mut pieces) = decompose(&remaining, mask ^ edge_mask, boundary, edges) {
+            pieces.push(key);
+            return Some(pieces);
+        }
+    }
+    None
+}
@@ -0,0 +1,243 @@
+//! Qobj Module
+//! IBM Qobj (QASM) job payloads mapped onto circuit runs, with Qiskit-style results
+
+use std::collections::BTreeMap;
+use std::time::Instant;
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use crate::manifest::Manifest;
+use crate::qsim::{self, QuantumCircuit, QuantumGate};
+
+/// Shots used when neither the job nor the experiment sets them (Qiskit's default)
+const DEFAULT_SHOTS: usize = 1024;
+
+#[derive(Deserialize)]
+pub struct Qobj {
+    pub qobj_id: String,
+    #[serde(default)]
+    pub config: QobjConfig,
+    #[serde(default)]
+    pub header: Value,
+    pub experiments: Vec<QobjExperiment>,
+}
+
+#[derive(Deserialize, Default, Clone)]
+pub struct QobjConfig {
+    pub shots: Option<usize>,
+    pub n_qubits: Option<usize>,
+    pub memory_slots: Option<usize>,
+    /// Return per-shot memory alongside counts
+    pub memory: Option<bool>,
+    /// Seed for sampling shots, so the same job returns the same counts
+    pub seed_simulator: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct QobjExperiment {
+    #[serde(default)]
+    pub header: Value,
+    #[serde(default)]
+    pub config: QobjConfig,
+    pub instructions: Vec<QobjInstruction>,
+}
+
+#[derive(Deserialize)]
+pub struct QobjInstruction {
+    pub name: String,
+    #[serde(default)]
+    pub qubits: Vec<usize>,
+    #[serde(default)]
+    pub params: Vec<f64>,
+    #[serde(default)]
+    pub memory: Vec<usize>,
+}
+
+/// Whether a JSON payload looks like a Qobj rather than a QuantumMesh circuit
+pub fn is_qobj(value: &Value) -> bool {
+    value.get("qobj_id").is_some() && value.get("experiments").is_some()
+}
+
+/// Translate an experiment into a circuit whose measurements store into their memory slots
+pub fn experiment_circuit(experiment: &QobjExperiment, n_qubits: Option<usize>) -> Result<QuantumCircuit, String> {
+    let mut gates = Vec::new();
+
+    for instruction in &experiment.instructions {
+        let name = instruction.name.as_str();
+        let qubit = |i: usize| {
+            instruction
+                .qubits
+                .get(i)
+                .copied()
+                .ok_or_else(|| format!("'{}' is missing qubit operand {}", name, i))
+        };
+        let param = |i: usize| {
+            instruction
+                .params
+                .get(i)
+                .copied()
+                .ok_or_else(|| format!("'{}' is missing parameter {}", name, i))
+        };
+
+        let mapped = match name {
+            "id" | "barrier" => Vec::new(),
+            "h" => vec![QuantumGate::Hadamard { qubit: qubit(0)? }],
+            "x" => vec![QuantumGate::PauliX { qubit: qubit(0)? }],
+            "y" => vec![QuantumGate::PauliY { qubit: qubit(0)? }],
+            "z" => vec![QuantumGate::PauliZ { qubit: qubit(0)? }],
+            "s" => vec![QuantumGate::S { qubit: qubit(0)? }],
+            "sdg" => vec![QuantumGate::Sdg { qubit: qubit(0)? }],
+            "t" => vec![QuantumGate::T { qubit: qubit(0)? }],
+            "tdg" => vec![QuantumGate::Tdg { qubit: qubit(0)? }],
+            "sx" => vec![QuantumGate::SX { qubit: qubit(0)? }],
+            "sxdg" => vec![QuantumGate::SXdg { qubit: qubit(0)? }],
+            "u1" | "p" => vec![QuantumGate::Phase { qubit: qubit(0)?, angle: param(0)? }],
+            "rx" => vec![QuantumGate::RotationX { qubit: qubit(0)?, angle: param(0)? }],
+            "ry" => vec![QuantumGate::RotationY { qubit: qubit(0)?, angle: param(0)? }],
+            "rz" => vec![QuantumGate::RotationZ { qubit: qubit(0)?, angle: param(0)? }],
+            "u3" | "u" => vec![QuantumGate::U {
+                qubit: qubit(0)?,
+                theta: param(0)?,
+                phi: param(1)?,
+                lambda: param(2)?,
+            }],
+            "u2" => vec![QuantumGate::U {
+                qubit: qubit(0)?,
+                theta: std::f64::consts::FRAC_PI_2,
+                phi: param(0)?,
+                lambda: param(1)?,
+            }],
+            "cx" => vec![QuantumGate::CNOT { control: qubit(0)?, target: qubit(1)? }],
+            "cz" => vec![QuantumGate::CZ { control: qubit(0)?, target: qubit(1)? }],
+            "cu1" | "cp" => vec![QuantumGate::CPhase {
+                control: qubit(0)?,
+                target: qubit(1)?,
+                angle: param(0)?,
+            }],
+            "swap" => vec![QuantumGate::SWAP { qubit1: qubit(0)?, qubit2: qubit(1)? }],
+            "iswap" => vec![QuantumGate::ISWAP { qubit1: qubit(0)?, qubit2: qubit(1)? }],
+            "siswap" => vec![QuantumGate::SqrtISWAP { qubit1: qubit(0)?, qubit2: qubit(1)? }],
+            "ecr" => vec![QuantumGate::ECR { qubit1: qubit(0)?, qubit2: qubit(1)? }],
+            "rxx" => vec![QuantumGate::RXX { qubit1: qubit(0)?, qubit2: qubit(1)?, angle: param(0)? }],
+            "ryy" => vec![QuantumGate::RYY { qubit1: qubit(0)?, qubit2: qubit(1)?, angle: param(0)? }],
+            "rzz" => vec![QuantumGate::RZZ { qubit1: qubit(0)?, qubit2: qubit(1)?, angle: param(0)? }],
+            "ccx" => vec![QuantumGate::Toffoli {
+                control1: qubit(0)?,
+                control2: qubit(1)?,
+                target: qubit(2)?,
+            }],
+            "mcx" | "c3x" | "c4x" => match instruction.qubits.split_last() {
+                Some((target, controls)) => vec![QuantumGate::MCX { controls: controls.to_vec(), target: *target }],
+                None => return Err(format!("'{}' needs at least a target qubit", name)),
+            },
+            "reset" => instruction.qubits.iter().map(|&qubit| QuantumGate::Reset { qubit }).collect(),
+            "measure" => {
+                if instruction.memory.len() != instruction.qubits.len() {
+                    return Err("'measure' needs one memory slot per qubit".to_string());
+                }
+                instruction
+                    .qubits
+                    .iter()
+                    .zip(&instruction.memory)
+                    .map(|(&qubit, &slot)| QuantumGate::Measurement { qubit, bit: (slot != qubit).then_some(slot) })
+                    .collect()
+            }
+            _ => return Err(format!("unsupported Qobj instruction '{}'", name)),
+        };
+        gates.extend(mapped);
+    }
+
+    let used = gates.iter().flat_map(|g| g.qubits()).max().map(|q| q + 1).unwrap_or(0);
+    let num_qubits = n_qubits.unwrap_or(used).max(used);
+    let mut metadata = BTreeMap::new();
+    if let Some(name) = experiment.header.get("name").and_then(|n| n.as_str()) {
+        metadata.insert("name".to_string(), name.to_string());
+    }
+    Ok(QuantumCircuit { num_qubits, gates, metadata })
+}
+
+/// Run every experiment in a Qobj and build a Qiskit `Result` payload
+pub fn run_qobj<R: Rng>(qobj: &Qobj, max_qubits: usize, rng: &mut R) -> Result<Value, String> {
+    let mut experiment_results = Vec::new();
+
+    for (index, experiment) in qobj.experiments.iter().enumerate() {
+        let start = Instant::now();
+        let shots = experiment.config.shots.or(qobj.config.shots).unwrap_or(DEFAULT_SHOTS);
+        let with_memory = experiment.config.memory.or(qobj.config.memory).unwrap_or(false);
+        let n_qubits = experiment.config.n_qubits.or(qobj.config.n_qubits);
+        let memory_slots = experiment.config.memory_slots.or(qobj.config.memory_slots);
+
+        let circuit = experiment_circuit(experiment, n_qubits)
+            .and_then(|circuit| qsim::validate_circuit(&circuit).map(|()| circuit))
+            .map_err(|e| format!("experiment {}: {}", index, e))?;
+        if circuit.num_qubits > max_qubits {
+            return Err(format!("experiment {}: circuits are limited to {} qubits", index, max_qubits));
+        }
+        // The classical register is `memory_slots` bits wide when the job says so, and
+        // otherwise just wide enough for the highest slot measured into
+        let highest = circuit
+            .gates
+            .iter()
+            .filter_map(|gate| match gate {
+                QuantumGate::Measurement { qubit, bit } => Some(bit.unwrap_or(*qubit)),
+                _ => None,
+            })
+            .max();
+        if let (Some(slot), Some(slots)) = (highest, memory_slots) {
+            if slot >= slots {
+                return Err(format!("experiment {}: measures into memory slot {}, but memory_slots is {}", index, slot, slots));
+            }
+        }
+        let memory_slots = memory_slots.unwrap_or(highest.map_or(0, |slot| slot + 1));
+
+        // Memory value of a shot: bit `slot` holds the latest outcome measured into it
+        let memory: Vec<String> = qsim::sample_circuit(&circuit, shots, rng)
+            .iter()
+            .map(|shot| format!("0x{:x}", shot.register))
+            .collect();
+
+        let mut data = json!({});
+        if circuit.gates.iter().any(|gate| matches!(gate, QuantumGate::Measurement { .. })) {
+            let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+            for key in &memory {
+                *counts.entry(key.as_str()).or_default() += 1;
+            }
+            data["counts"] = json!(counts);
+            if with_memory {
+                data["memory"] = json!(memory);
+            }
+        }
+
+        // Qiskit formats counts and memory as bit strings of the header's memory_slots width
+        let mut header = experiment.header.clone();
+        if let Some(fields) = header.as_object_mut() {
+            fields.entry("memory_slots").or_insert(json!(memory_slots));
+        } else if header.is_null() {
+            header = json!({ "memory_slots": memory_slots });
+        }
+
+        experiment_results.push(json!({
+            "shots": shots,
+            "success": true,
+            "status": "DONE",
+            "header": header,
+            "data": data,
+            "time_taken": start.elapsed().as_secs_f64(),
+        }));
+    }
+
+    Ok(json!({
+        "backend_name": "quantummesh",
+        "backend_version": env!("CARGO_PKG_VERSION"),
+        "qobj_id": qobj.qobj_id,
+        "job_id": qobj.qobj_id,
+        "success": true,
+        "status": "COMPLETED",
+        "header": qobj.header,
+        "results": experiment_results,
+        "manifest": match qobj.config.seed_simulator {
+            Some(seed) => Manifest::current().with_seed(seed),
+            None => Manifest::current(),
+        },
+    }))
+}
@@ -1,40 +1,349 @@
 //! Quantum Simulation Module
 //! Core quantum circuit simulation logic
 
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::error::Error;
+use std::sync::Arc;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
-use crate::gpu_ops::{GpuStateVector, Complex, RotationAxis};
+use crate::gpu_ops::{self, GpuStateVector, Complex, RotationAxis};
+use crate::simulator_backend::SimulatorBackend;
+use crate::noise::{self, GateNoise, NoiseModel};
+use crate::observable::{pauli_expectation, PauliSum};
+use crate::{macros, params, proto, qasm, quil, stim};
 
 /// Quantum circuit definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QuantumCircuit {
     pub num_qubits: usize,
     pub gates: Vec<QuantumGate>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl QuantumCircuit {
+    /// Whether the circuit collapses the state partway through (a reset, or a measurement that
+    /// later gates act on or condition on), so a single run is one random trajectory rather
+    /// than the full output distribution
+    pub fn has_collapse(&self) -> bool {
+        self.gates.iter().enumerate().any(|(index, gate)| match gate {
+            QuantumGate::Reset { .. } => true,
+            QuantumGate::Measurement { qubit, bit } => measurement_is_read(*qubit, bit.unwrap_or(*qubit), &self.gates[index + 1..]),
+            _ => false,
+        })
+    }
+
+    /// (qubit, classical bit) of every measurement in order; a circuit without measurements
+    /// reads each qubit into its own bit
+    pub fn readout(&self) -> Vec<(usize, usize)> {
+        let readout: Vec<(usize, usize)> = self
+            .gates
+            .iter()
+            .filter_map(|gate| match gate {
+                QuantumGate::Measurement { qubit, bit } => Some((*qubit, bit.unwrap_or(*qubit))),
+                _ => None,
+            })
+            .collect();
+        if readout.is_empty() {
+            (0..self.num_qubits).map(|q| (q, q)).collect()
+        } else {
+            readout
+        }
+    }
+
+    /// Width of the classical register: one bit per qubit, widened to fit any explicit bit
+    pub fn num_clbits(&self) -> usize {
+        self.readout().iter().map(|&(_, bit)| bit + 1).fold(self.num_qubits, usize::max)
+    }
 }
 
 /// Quantum gate types
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum QuantumGate {
     Hadamard { qubit: usize },
     PauliX { qubit: usize },
     PauliY { qubit: usize },
     PauliZ { qubit: usize },
+    #[serde(alias = "s")]
+    S { qubit: usize },
+    #[serde(alias = "sdg")]
+    Sdg { qubit: usize },
+    #[serde(alias = "t")]
+    T { qubit: usize },
+    #[serde(alias = "tdg")]
+    Tdg { qubit: usize },
+    /// Square root of X
+    #[serde(alias = "sx")]
+    SX { qubit: usize },
+    #[serde(alias = "sxdg")]
+    SXdg { qubit: usize },
     Phase { qubit: usize, angle: f64 },
     CNOT { control: usize, target: usize },
+    CZ { control: usize, target: usize },
+    /// Controlled phase: multiplies |11> by e^(i*angle)
+    #[serde(alias = "CP", alias = "cp", alias = "cu1")]
+    CPhase { control: usize, target: usize, angle: f64 },
     SWAP { qubit1: usize, qubit2: usize },
+    /// Swaps |01> and |10> with a phase of i
+    #[serde(alias = "iswap")]
+    ISWAP { qubit1: usize, qubit2: usize },
+    /// Square root of iSWAP
+    #[serde(alias = "siswap", alias = "SQRT_ISWAP")]
+    SqrtISWAP { qubit1: usize, qubit2: usize },
+    /// Echoed cross-resonance, IBM's native entangler: RZX(pi/4), X on `qubit1`, RZX(-pi/4),
+    /// with Z on `qubit1` and X on `qubit2`
+    #[serde(alias = "ecr")]
+    ECR { qubit1: usize, qubit2: usize },
+    /// Ising XX interaction exp(-i*angle/2 * X⊗X)
+    #[serde(alias = "rxx")]
+    RXX { qubit1: usize, qubit2: usize, angle: f64 },
+    /// Ising YY interaction exp(-i*angle/2 * Y⊗Y)
+    #[serde(alias = "ryy")]
+    RYY { qubit1: usize, qubit2: usize, angle: f64 },
+    /// Ising ZZ interaction exp(-i*angle/2 * Z⊗Z)
+    #[serde(alias = "rzz")]
+    RZZ { qubit1: usize, qubit2: usize, angle: f64 },
+    /// Givens rotation: |01> -> cos(angle)|01> + sin(angle)|10>, |10> -> cos(angle)|10> - sin(angle)|01>,
+    /// where |ab> has `qubit1` = a and `qubit2` = b; |00> and |11> are unchanged
+    #[serde(alias = "givens")]
+    Givens { qubit1: usize, qubit2: usize, angle: f64 },
     Toffoli { control1: usize, control2: usize, target: usize },
+    /// Multi-controlled X: flips `target` when every control is |1>
+    #[serde(alias = "mcx")]
+    MCX { controls: Vec<usize>, target: usize },
     RotationX { qubit: usize, angle: f64 },
     RotationY { qubit: usize, angle: f64 },
     RotationZ { qubit: usize, angle: f64 },
-    Measurement { qubit: usize },
+    /// Universal single-qubit gate U3(theta, phi, lambda)
+    #[serde(alias = "U3", alias = "u3")]
+    U { qubit: usize, theta: f64, phi: f64, lambda: f64 },
+    /// Arbitrary single-qubit unitary `[[m00, m01], [m10, m11]]`, entries as `[re, im]`
+    #[serde(alias = "unitary")]
+    Unitary1Q { qubit: usize, matrix: [[Complex; 2]; 2] },
+    /// Dense 2^k x 2^k unitary on k qubits; `qubits[0]` is the least significant bit of the row index
+    UnitaryNQ { qubits: Vec<usize>, matrix: Vec<Vec<Complex>> },
+    /// Multiplies basis state k of the listed qubits by e^(i*phases[k]);
+    /// `qubits[0]` is the least significant bit of k
+    #[serde(alias = "diagonal")]
+    Diagonal { qubits: Vec<usize>, phases: Vec<f64> },
+    /// Any gate applied only where `control` is |1>
+    #[serde(alias = "controlled")]
+    Controlled { control: usize, gate: Box<QuantumGate> },
+    /// Any gate applied only when classical bit `bit` of the register, as last written by a
+    /// measurement, reads 1
+    #[serde(alias = "if_bit", alias = "c_if")]
+    IfBit { bit: usize, gate: Box<QuantumGate> },
+    /// Any gate with its own error rate or duration, overriding the active noise model.
+    /// Circuit files may also write `noise` inline on the gate itself.
+    #[serde(alias = "noisy")]
+    Noisy { noise: GateNoise, gate: Box<QuantumGate> },
+    /// Reorders qubits: afterwards `qubits[k]` holds the state `qubits[pattern[k]]` had
+    Permutation { qubits: Vec<usize>, pattern: Vec<usize> },
+    /// exp(-i*angle/2 * P) for a Pauli string P such as "XZIY", where `pauli[k]` acts on `qubits[k]`
+    #[serde(alias = "pauli_rotation")]
+    PauliRotation { qubits: Vec<usize>, pauli: String, angle: f64 },
+    /// Hadamard on every listed qubit, applied in one fused pass
+    GlobalHadamard { qubits: Vec<usize> },
+    /// The same X, Y or Z rotation on every listed qubit, applied in one fused pass
+    GlobalRotation { qubits: Vec<usize>, axis: RotationAxis, angle: f64 },
+    /// Projective measurement; the outcome lands in classical bit `bit` of the reported
+    /// register, which defaults to the qubit's own index
+    Measurement {
+        qubit: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bit: Option<usize>,
+    },
+    /// Collapse a qubit and return it to |0>, so it can be reused mid-circuit
+    #[serde(alias = "reset")]
+    Reset { qubit: usize },
+    /// Record a copy of the state under `label`, retrievable after simulation; acts on no
+    /// qubit and leaves the state unchanged
+    #[serde(alias = "snapshot")]
+    Snapshot { label: String },
+}
+
+impl QuantumGate {
+    /// Qubits this gate acts on
+    pub fn qubits(&self) -> Vec<usize> {
+        match self {
+            QuantumGate::Hadamard { qubit }
+            | QuantumGate::PauliX { qubit }
+            | QuantumGate::PauliY { qubit }
+            | QuantumGate::PauliZ { qubit }
+            | QuantumGate::S { qubit }
+            | QuantumGate::Sdg { qubit }
+            | QuantumGate::T { qubit }
+            | QuantumGate::Tdg { qubit }
+            | QuantumGate::SX { qubit }
+            | QuantumGate::SXdg { qubit }
+            | QuantumGate::Phase { qubit, .. }
+            | QuantumGate::RotationX { qubit, .. }
+            | QuantumGate::RotationY { qubit, .. }
+            | QuantumGate::RotationZ { qubit, .. }
+            | QuantumGate::U { qubit, .. }
+            | QuantumGate::Unitary1Q { qubit, .. }
+            | QuantumGate::Measurement { qubit, .. }
+            | QuantumGate::Reset { qubit } => vec![*qubit],
+            QuantumGate::CNOT { control, target }
+            | QuantumGate::CZ { control, target }
+            | QuantumGate::CPhase { control, target, .. } => vec![*control, *target],
+            QuantumGate::SWAP { qubit1, qubit2 }
+            | QuantumGate::ISWAP { qubit1, qubit2 }
+            | QuantumGate::SqrtISWAP { qubit1, qubit2 }
+            | QuantumGate::ECR { qubit1, qubit2 }
+            | QuantumGate::RXX { qubit1, qubit2, .. }
+            | QuantumGate::RYY { qubit1, qubit2, .. }
+            | QuantumGate::RZZ { qubit1, qubit2, .. }
+            | QuantumGate::Givens { qubit1, qubit2, .. } => vec![*qubit1, *qubit2],
+            QuantumGate::Toffoli { control1, control2, target } => {
+                vec![*control1, *control2, *target]
+            }
+            QuantumGate::MCX { controls, target } => {
+                controls.iter().copied().chain(std::iter::once(*target)).collect()
+            }
+            QuantumGate::UnitaryNQ { qubits, .. }
+            | QuantumGate::Diagonal { qubits, .. }
+            | QuantumGate::GlobalHadamard { qubits }
+            | QuantumGate::GlobalRotation { qubits, .. }
+            | QuantumGate::Permutation { qubits, .. }
+            | QuantumGate::PauliRotation { qubits, .. } => qubits.clone(),
+            QuantumGate::Controlled { control, gate } => {
+                std::iter::once(*control).chain(gate.qubits()).collect()
+            }
+            QuantumGate::Noisy { gate, .. } => gate.qubits(),
+            QuantumGate::Snapshot { .. } => Vec::new(),
+            // The condition reads a register bit, not a qubit; see `condition_bits`
+            QuantumGate::IfBit { gate, .. } => gate.qubits(),
+        }
+    }
+
+    /// Hadamard layer over qubits `0..num_qubits`
+    pub fn global_hadamard(num_qubits: usize) -> QuantumGate {
+        QuantumGate::GlobalHadamard { qubits: (0..num_qubits).collect() }
+    }
+
+    /// Rotation layer over qubits `0..num_qubits`
+    pub fn global_rotation(num_qubits: usize, axis: RotationAxis, angle: f64) -> QuantumGate {
+        QuantumGate::GlobalRotation { qubits: (0..num_qubits).collect(), axis, angle }
+    }
+
+    /// Copy of this gate with every qubit index passed through `f`
+    pub fn map_qubits<F>(&self, f: F) -> QuantumGate
+    where
+        F: Fn(usize) -> usize,
+    {
+        self.remap_qubits(&f)
+    }
+
+    /// Non-generic body of `map_qubits`, so controlled gates can recurse
+    fn remap_qubits(&self, f: &dyn Fn(usize) -> usize) -> QuantumGate {
+        let mut gate = self.clone();
+        match &mut gate {
+            QuantumGate::Hadamard { qubit }
+            | QuantumGate::PauliX { qubit }
+            | QuantumGate::PauliY { qubit }
+            | QuantumGate::PauliZ { qubit }
+            | QuantumGate::S { qubit }
+            | QuantumGate::Sdg { qubit }
+            | QuantumGate::T { qubit }
+            | QuantumGate::Tdg { qubit }
+            | QuantumGate::SX { qubit }
+            | QuantumGate::SXdg { qubit }
+            | QuantumGate::Phase { qubit, .. }
+            | QuantumGate::RotationX { qubit, .. }
+            | QuantumGate::RotationY { qubit, .. }
+            | QuantumGate::RotationZ { qubit, .. }
+            | QuantumGate::U { qubit, .. }
+            | QuantumGate::Unitary1Q { qubit, .. }
+            | QuantumGate::Measurement { qubit, .. }
+            | QuantumGate::Reset { qubit } => *qubit = f(*qubit),
+            QuantumGate::CNOT { control, target }
+            | QuantumGate::CZ { control, target }
+            | QuantumGate::CPhase { control, target, .. } => {
+                *control = f(*control);
+                *target = f(*target);
+            }
+            QuantumGate::SWAP { qubit1, qubit2 }
+            | QuantumGate::ISWAP { qubit1, qubit2 }
+            | QuantumGate::SqrtISWAP { qubit1, qubit2 }
+            | QuantumGate::ECR { qubit1, qubit2 }
+            | QuantumGate::RXX { qubit1, qubit2, .. }
+            | QuantumGate::RYY { qubit1, qubit2, .. }
+            | QuantumGate::RZZ { qubit1, qubit2, .. }
+            | QuantumGate::Givens { qubit1, qubit2, .. } => {
+                *qubit1 = f(*qubit1);
+                *qubit2 = f(*qubit2);
+            }
+            QuantumGate::Toffoli { control1, control2, target } => {
+                *control1 = f(*control1);
+                *control2 = f(*control2);
+                *target = f(*target);
+            }
+            QuantumGate::MCX { controls, target } => {
+                for control in controls.iter_mut() {
+                    *control = f(*control);
+                }
+                *target = f(*target);
+            }
+            QuantumGate::UnitaryNQ { qubits, .. }
+            | QuantumGate::Diagonal { qubits, .. }
+            | QuantumGate::GlobalHadamard { qubits }
+            | QuantumGate::GlobalRotation { qubits, .. }
+            | QuantumGate::Permutation { qubits, .. }
+            | QuantumGate::PauliRotation { qubits, .. } => {
+                for qubit in qubits.iter_mut() {
+                    *qubit = f(*qubit);
+                }
+            }
+            QuantumGate::Controlled { control, gate } => {
+                *control = f(*control);
+                **gate = gate.remap_qubits(f);
+            }
+            // Register bits stay put, as a measurement's explicit `bit` does
+            QuantumGate::IfBit { gate, .. } => **gate = gate.remap_qubits(f),
+            QuantumGate::Noisy { gate, .. } => **gate = gate.remap_qubits(f),
+            QuantumGate::Snapshot { .. } => {}
+        }
+        gate
+    }
 }
 
 /// Quantum simulator state
+#[derive(Clone)]
 pub struct QuantumSimulator {
     pub num_qubits: usize,
-    state: GpuStateVector,
+    /// State representation the kernels run on, the GPU state vector unless chosen otherwise
+    state: Box<dyn SimulatorBackend>,
+    /// Physical qubit holding each logical qubit; SWAPs and permutations only change this
+    layout: Vec<usize>,
+    /// Randomness for non-unitary operations such as resets
+    rng: StdRng,
+    /// Logical qubits measured but not yet collapsed; the collapse waits until a later gate
+    /// acts on the qubit, so terminal measurements leave the output distribution intact.
+    /// Each entry is (qubit, register bit).
+    pending: Vec<(usize, usize)>,
+    /// Latest collapsed measurement stored in each register bit: one per qubit, as the
+    /// default register, grown when a measurement names a bit past them
+    classical: Vec<Option<bool>>,
+    /// Collapsed measurement outcomes by register bit
+    register: usize,
+    /// States recorded by `Snapshot` gates, in circuit order
+    snapshots: Vec<StateSnapshot>,
+    /// Channels applied after each matching gate; None for ideal simulation
+    noise: Option<Arc<NoiseModel>>,
+    /// Time in ns at which each logical qubit is next free, for thermal relaxation
+    clocks: Vec<f64>,
+}
+
+/// Copy of the state taken by a `Snapshot` gate, in logical qubit order
+#[derive(Debug, Clone)]
+pub struct StateSnapshot {
+    pub label: String,
+    pub state: Vec<Complex>,
 }
 
 impl QuantumSimulator {
@@ -42,138 +351,1095 @@ impl QuantumSimulator {
     pub fn new(num_qubits: usize) -> Self {
         Self {
             num_qubits,
-            state: GpuStateVector::new(num_qubits),
+            state: Box::new(GpuStateVector::new(num_qubits)),
+            layout: (0..num_qubits).collect(),
+            rng: StdRng::from_entropy(),
+            pending: Vec::new(),
+            classical: vec![None; num_qubits],
+            register: 0,
+            snapshots: Vec::new(),
+            noise: None,
+            clocks: vec![0.0; num_qubits],
         }
     }
 
-    /// Apply a quantum gate
+    /// Create a simulator whose measurements and resets draw from a seeded generator, so a
+    /// caller's own generator decides every trajectory
+    pub fn seeded(num_qubits: usize, seed: u64) -> Self {
+        Self { rng: StdRng::seed_from_u64(seed), ..Self::new(num_qubits) }
+    }
+
+    /// Simulator on another state representation, such as one from `simulator_backend::create`,
+    /// with its measurements and resets drawn from a seeded generator
+    pub fn with_backend(state: Box<dyn SimulatorBackend>, seed: u64) -> Self {
+        let num_qubits = state.num_qubits();
+        Self {
+            num_qubits,
+            state,
+            layout: (0..num_qubits).collect(),
+            rng: StdRng::seed_from_u64(seed),
+            pending: Vec::new(),
+            classical: vec![None; num_qubits],
+            register: 0,
+            snapshots: Vec::new(),
+            noise: None,
+            clocks: vec![0.0; num_qubits],
+        }
+    }
+
+    /// The same simulator with a noise model's depolarizing errors, thermal relaxation and
+    /// channels applied after every gate: exactly on a density matrix, as one sampled
+    /// trajectory on a pure state
+    pub fn with_noise(self, model: Arc<NoiseModel>) -> Self {
+        Self { noise: Some(model), ..self }
+    }
+
+    /// Name of the state representation, e.g. `gpu` or `density`
+    pub fn backend_name(&self) -> &'static str {
+        self.state.name()
+    }
+
+    /// The same simulator drawing its measurements and resets from a seeded generator
+    pub fn reseeded(self, seed: u64) -> Self {
+        Self { rng: StdRng::seed_from_u64(seed), ..self }
+    }
+
+    /// Resume from saved logical amplitudes, such as a checkpoint written from `get_state`.
+    /// Amplitudes carry no classical state, so the register starts empty and no measurement
+    /// is pending.
+    pub fn from_state(amplitudes: Vec<Complex>) -> Result<Self, String> {
+        if !amplitudes.len().is_power_of_two() {
+            return Err(format!("{} amplitudes is not a whole number of qubits", amplitudes.len()));
+        }
+        let num_qubits = amplitudes.len().trailing_zeros() as usize;
+        Ok(Self {
+            num_qubits,
+            state: Box::new(GpuStateVector::from_amplitudes(amplitudes)),
+            layout: (0..num_qubits).collect(),
+            rng: StdRng::from_entropy(),
+            pending: Vec::new(),
+            classical: vec![None; num_qubits],
+            register: 0,
+            snapshots: Vec::new(),
+            noise: None,
+            clocks: vec![0.0; num_qubits],
+        })
+    }
+
+    /// Apply a quantum gate to logical qubits, with the noise model's angle errors and followed
+    /// by its channels;
+    /// SWAPs and permutations relabel the logical-to-physical map instead of moving amplitudes
     pub fn apply_gate(&mut self, gate: &QuantumGate) {
+        let perturbed = self.noise.clone().and_then(|model| model.perturbed(gate, &mut self.rng));
+        self.apply_noiseless(perturbed.as_ref().unwrap_or(gate));
+        if let Some(model) = self.noise.clone() {
+            let mut channels = model.channels_after(gate);
+            channels.extend(model.relaxation_after(gate, &mut self.clocks));
+            for (qubits, kraus) in channels {
+                self.apply_channel(&qubits, &kraus);
+            }
+        }
+    }
+
+    fn apply_noiseless(&mut self, gate: &QuantumGate) {
+        if !self.pending.is_empty() {
+            for qubit in gate.qubits() {
+                self.collapse_measurement(qubit);
+            }
+        }
         match gate {
-            QuantumGate::Hadamard { qubit } => self.apply_hadamard(*qubit),
-            QuantumGate::PauliX { qubit } => self.apply_x(*qubit),
-            QuantumGate::PauliY { qubit } => self.apply_y(*qubit),
-            QuantumGate::PauliZ { qubit } => self.apply_z(*qubit),
-            QuantumGate::Phase { qubit, angle } => self.apply_phase(*qubit, *angle),
-            QuantumGate::CNOT { control, target } => self.apply_cnot(*control, *target),
-            QuantumGate::SWAP { qubit1, qubit2 } => self.apply_swap(*qubit1, *qubit2),
-            QuantumGate::Toffoli { control1, control2, target } => {
-                self.apply_toffoli(*control1, *control2, *target)
+            QuantumGate::Measurement { qubit, bit } => {
+                let bit = bit.unwrap_or(*qubit);
+                if let Some(stored) = self.classical.get_mut(bit) {
+                    *stored = None;
+                }
+                self.pending.push((*qubit, bit));
+            }
+            // The condition decides whether the inner gate, and its noise, happens at all
+            QuantumGate::IfBit { bit, gate } => {
+                self.collapse_bit(*bit);
+                if self.classical.get(*bit) == Some(&Some(true)) {
+                    self.apply_gate(gate);
+                }
+            }
+            // The override only changes the noise that follows
+            QuantumGate::Noisy { gate, .. } => self.apply_noiseless(gate),
+            // Deferred measurements stay deferred, so the copy shows them uncollapsed
+            QuantumGate::Snapshot { label } => {
+                let state = self.get_state().into_owned();
+                self.snapshots.push(StateSnapshot { label: label.clone(), state });
+            }
+            QuantumGate::SWAP { qubit1, qubit2 } => self.layout.swap(*qubit1, *qubit2),
+            QuantumGate::Permutation { qubits, pattern } => {
+                let physical: Vec<usize> = qubits.iter().map(|&q| self.layout[q]).collect();
+                for (&qubit, &source) in qubits.iter().zip(pattern) {
+                    self.layout[qubit] = physical[source];
+                }
+            }
+            gate if self.layout_is_identity() => self.apply_physical(gate),
+            gate => {
+                let physical = gate.map_qubits(|q| self.layout[q]);
+                self.apply_physical(&physical);
+            }
+        }
+    }
+
+    /// Apply a channel given by its Kraus operators to logical qubits
+    pub fn apply_channel(&mut self, qubits: &[usize], kraus: &[Vec<Vec<Complex>>]) {
+        for &qubit in qubits {
+            self.collapse_measurement(qubit);
+        }
+        let physical: Vec<usize> = qubits.iter().map(|&q| self.layout[q]).collect();
+        self.state.apply_kraus(&physical, kraus, &mut self.rng);
+    }
+
+    fn layout_is_identity(&self) -> bool {
+        self.layout.iter().enumerate().all(|(logical, &physical)| logical == physical)
+    }
+
+    /// Logical basis index of a physical amplitude index
+    fn logical_index(&self, physical: usize) -> usize {
+        self.layout
+            .iter()
+            .enumerate()
+            .fold(0, |index, (logical, &bit)| index | ((physical >> bit) & 1) << logical)
+    }
+
+    /// Physical amplitude index of a logical basis index
+    fn physical_index(&self, logical: usize) -> usize {
+        self.layout
+            .iter()
+            .enumerate()
+            .fold(0, |index, (qubit, &bit)| index | ((logical >> qubit) & 1) << bit)
+    }
+
+    /// Apply a gate whose qubits are already physical
+    fn apply_physical(&mut self, gate: &QuantumGate) {
+        match gate {
+            QuantumGate::Measurement { .. }
+            | QuantumGate::IfBit { .. }
+            | QuantumGate::Noisy { .. }
+            | QuantumGate::Snapshot { .. } => {
+                // Recorded and resolved on logical qubits by apply_gate
             }
-            QuantumGate::RotationX { qubit, angle } => self.apply_rx(*qubit, *angle),
-            QuantumGate::RotationY { qubit, angle } => self.apply_ry(*qubit, *angle),
-            QuantumGate::RotationZ { qubit, angle } => self.apply_rz(*qubit, *angle),
-            QuantumGate::Measurement { qubit } => {
-                // Measurement is handled separately
+            QuantumGate::Reset { qubit } => {
+                self.apply_reset(*qubit);
             }
+            gate => self.state.apply_gate(gate),
+        }
+    }
+
+    /// Carry out a deferred measurement of a logical qubit, if one is pending
+    fn collapse_measurement(&mut self, qubit: usize) {
+        if let Some(position) = self.pending.iter().position(|&(q, _)| q == qubit) {
+            let (_, bit) = self.pending.swap_remove(position);
+            let mut outcome = self.state.measure(self.layout[qubit], &mut self.rng);
+            // Later gates see the state collapsed to the true outcome, but conditions and the
+            // register get the bit as read out
+            if let Some(model) = &self.noise {
+                outcome = model.misread(qubit, outcome, &mut self.rng);
+            }
+            if self.classical.len() <= bit {
+                self.classical.resize(bit + 1, None);
+            }
+            self.classical[bit] = Some(outcome);
+            self.register = (self.register & !(1 << bit)) | ((outcome as usize) << bit);
+        }
+    }
+
+    /// Carry out the deferred measurements that store into register bit `bit`
+    fn collapse_bit(&mut self, bit: usize) {
+        while let Some(&(qubit, _)) = self.pending.iter().find(|&&(_, stored)| stored == bit) {
+            self.collapse_measurement(qubit);
+        }
+    }
+
+    /// Carry out every deferred measurement now, leaving the state of one trajectory with its
+    /// terminal outcomes drawn, as later gates or entropies of that trajectory expect
+    pub fn collapse_pending(&mut self) {
+        while let Some(&(qubit, _)) = self.pending.first() {
+            self.collapse_measurement(qubit);
         }
     }
 
+    /// Classical register at the end of this run if the final state is measured as `outcome`:
+    /// collapsed outcomes as recorded, and deferred terminal measurements read from `outcome`
+    fn register_for(&self, outcome: usize) -> usize {
+        self.pending
+            .iter()
+            .fold(self.register, |register, &(qubit, bit)| (register & !(1 << bit)) | (((outcome >> qubit) & 1) << bit))
+    }
+
+    /// States recorded by `Snapshot` gates so far, in the order they ran
+    pub fn snapshots(&self) -> &[StateSnapshot] {
+        &self.snapshots
+    }
+
+    /// Latest collapsed measurement in each register bit; `None` for bits never written and
+    /// for terminal measurements, which stay deferred
+    pub fn classical_bits(&self) -> &[Option<bool>] {
+        &self.classical
+    }
+
+    /// Reset a qubit to |0>, returning whether it was found in |1>
+    pub fn apply_reset(&mut self, qubit: usize) -> bool {
+        self.state.reset(qubit, &mut self.rng)
+    }
+
     /// Apply Hadamard gate
     pub fn apply_hadamard(&mut self, qubit: usize) {
-        self.state.apply_hadamard_gpu(qubit);
+        self.state.apply_gate(&QuantumGate::Hadamard { qubit });
     }
 
     /// Apply Pauli-X gate
     pub fn apply_x(&mut self, qubit: usize) {
-        self.state.apply_x_gpu(qubit);
+        self.state.apply_gate(&QuantumGate::PauliX { qubit });
     }
 
     /// Apply Pauli-Y gate
     pub fn apply_y(&mut self, qubit: usize) {
-        self.state.apply_y_gpu(qubit);
+        self.state.apply_gate(&QuantumGate::PauliY { qubit });
     }
 
     /// Apply Pauli-Z gate
     pub fn apply_z(&mut self, qubit: usize) {
-        self.state.apply_z_gpu(qubit);
+        self.state.apply_gate(&QuantumGate::PauliZ { qubit });
     }
 
     /// Apply Phase gate
     pub fn apply_phase(&mut self, qubit: usize, angle: f64) {
-        self.state.apply_phase_gpu(qubit, angle);
+        self.state.apply_gate(&QuantumGate::Phase { qubit, angle });
     }
 
     /// Apply CNOT gate
     pub fn apply_cnot(&mut self, control: usize, target: usize) {
-        self.state.apply_cnot_gpu(control, target);
+        let gate = QuantumGate::CNOT { control, target };
+        let (qubit1, qubit2, matrix) = two_qubit_matrix(&gate).expect("CNOT has a matrix");
+        self.apply_two_qubit(qubit1, qubit2, &matrix);
     }
 
-    /// Apply SWAP gate
-    pub fn apply_swap(&mut self, qubit1: usize, qubit2: usize) {
-        self.apply_cnot(qubit1, qubit2);
-        self.apply_cnot(qubit2, qubit1);
-        self.apply_cnot(qubit1, qubit2);
+    /// Apply a 4x4 matrix indexed by (bit of qubit1) + 2 * (bit of qubit2)
+    pub fn apply_two_qubit(&mut self, qubit1: usize, qubit2: usize, matrix: &[[Complex; 4]; 4]) {
+        let rows: Vec<Vec<Complex>> = matrix.iter().map(|row| row.to_vec()).collect();
+        self.state.apply_matrix_n(&[qubit1, qubit2], &rows);
     }
 
     /// Apply Toffoli (CCNOT) gate
     pub fn apply_toffoli(&mut self, control1: usize, control2: usize, target: usize) {
-        // Simplified Toffoli implementation
-        // In practice, this would be decomposed into more basic gates
-        self.apply_hadamard(target);
-        self.apply_cnot(control2, target);
-        self.apply_rz(target, -std::f64::consts::PI / 4.0);
-        self.apply_cnot(control1, target);
-        self.apply_rz(target, std::f64::consts::PI / 4.0);
-        self.apply_cnot(control2, target);
-        self.apply_rz(target, -std::f64::consts::PI / 4.0);
-        self.apply_cnot(control1, target);
-        self.apply_rz(target, std::f64::consts::PI / 4.0);
-        self.apply_hadamard(target);
+        // Exact permutation; target-only RZ decompositions leave relative phases
+        self.apply_mcx(&[control1, control2], target);
+    }
+
+    /// Apply multi-controlled X with any number of controls
+    pub fn apply_mcx(&mut self, controls: &[usize], target: usize) {
+        self.state.apply_gate(&QuantumGate::MCX { controls: controls.to_vec(), target });
     }
 
     /// Apply RX rotation
     pub fn apply_rx(&mut self, qubit: usize, angle: f64) {
-        self.state.apply_rotation_gpu(qubit, RotationAxis::X, angle);
+        self.state.apply_gate(&QuantumGate::RotationX { qubit, angle });
     }
 
     /// Apply RY rotation
     pub fn apply_ry(&mut self, qubit: usize, angle: f64) {
-        self.state.apply_rotation_gpu(qubit, RotationAxis::Y, angle);
+        self.state.apply_gate(&QuantumGate::RotationY { qubit, angle });
     }
 
     /// Apply RZ rotation
     pub fn apply_rz(&mut self, qubit: usize, angle: f64) {
-        self.state.apply_rotation_gpu(qubit, RotationAxis::Z, angle);
+        self.state.apply_gate(&QuantumGate::RotationZ { qubit, angle });
+    }
+
+    /// Apply U3(theta, phi, lambda)
+    pub fn apply_u(&mut self, qubit: usize, theta: f64, phi: f64, lambda: f64) {
+        self.state.apply_gate(&QuantumGate::U { qubit, theta, phi, lambda });
+    }
+
+    /// Apply a custom 2x2 unitary
+    pub fn apply_unitary(&mut self, qubit: usize, matrix: &[[Complex; 2]; 2]) {
+        self.state.apply_matrix(qubit, matrix);
+    }
+
+    /// Apply a custom 2^k x 2^k unitary
+    pub fn apply_unitary_n(&mut self, qubits: &[usize], matrix: &[Vec<Complex>]) {
+        self.state.apply_matrix_n(qubits, matrix);
+    }
+
+    /// Apply a phase to each basis state of a qubit subset in a single pass
+    pub fn apply_diagonal(&mut self, qubits: &[usize], phases: &[f64]) {
+        self.state.apply_gate(&QuantumGate::Diagonal { qubits: qubits.to_vec(), phases: phases.to_vec() });
     }
 
-    /// Measure all qubits
+    /// Apply exp(-i*angle/2 * P) for a Pauli string in a single kernel
+    pub fn apply_pauli_rotation(&mut self, qubits: &[usize], pauli: &str, angle: f64) {
+        self.state.apply_gate(&QuantumGate::PauliRotation { qubits: qubits.to_vec(), pauli: pauli.to_string(), angle });
+    }
+
+    /// Apply any gate conditioned on `control`; the GPU state masks its kernels to amplitudes
+    /// where the control is |1>
+    pub fn apply_controlled(&mut self, control: usize, gate: &QuantumGate) {
+        self.state.apply_gate(&QuantumGate::Controlled { control, gate: Box::new(gate.clone()) });
+    }
+
+    /// Measure all qubits, indexed by logical basis state; with a noise model, the
+    /// probabilities of each reading after readout error
     pub fn measure_all(&self) -> Vec<f64> {
-        self.state.measure_all_gpu()
+        let probabilities = self.state.probabilities();
+        let logical = if self.layout_is_identity() {
+            probabilities
+        } else {
+            let mut logical = vec![0.0; probabilities.len()];
+            for (index, probability) in probabilities.into_iter().enumerate() {
+                logical[self.logical_index(index)] = probability;
+            }
+            logical
+        };
+        match &self.noise {
+            Some(model) => model.readout_distribution(&logical),
+            None => logical,
+        }
     }
 
-    /// Measure single qubit
+    /// Sample measurement outcomes of all qubits, as logical basis-state indices, straight
+    /// from the amplitudes; with a noise model, each is read out through its confusion matrix
+    pub fn sample<R: Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Vec<usize> {
+        let mut rng = rng;
+        let outcomes = self.state.sample(shots, &mut rng);
+        let outcomes = if self.layout_is_identity() {
+            outcomes
+        } else {
+            outcomes.into_iter().map(|index| self.logical_index(index)).collect()
+        };
+        match &self.noise {
+            Some(model) => outcomes.into_iter().map(|outcome| model.misread_outcome(outcome, self.num_qubits, &mut *rng)).collect(),
+            None => outcomes,
+        }
+    }
+
+    /// Probability that a single qubit reads 1, from one pass over the amplitudes with no
+    /// buffer the size of the state
     pub fn measure_qubit(&self, qubit: usize) -> f64 {
-        let probabilities = self.measure_all();
-        let mut prob = 0.0;
-        let mask = 1 << qubit;
-        for (i, p) in probabilities.iter().enumerate() {
-            if i & mask != 0 {
-                prob += p;
+        self.measure_qubits(&[qubit])[0]
+    }
+
+    /// Probability that each listed qubit reads 1, all from the same single pass, so polling
+    /// many qubits costs one pass rather than one per qubit. Entries follow `qubits`.
+    pub fn measure_qubits(&self, qubits: &[usize]) -> Vec<f64> {
+        let bits: Vec<usize> = qubits.iter().map(|&qubit| self.layout[qubit]).collect();
+        self.state.one_probabilities(&bits)
+    }
+
+    /// Joint outcome distribution of the listed qubits, in one pass over the amplitudes.
+    /// Entry k is the probability of the subsystem basis state with `qubits[0]` as the least
+    /// significant bit. The qubits must be distinct and in range.
+    pub fn marginal_probabilities(&self, qubits: &[usize]) -> Vec<f64> {
+        let state = self.state.amplitudes();
+        let bits: Vec<usize> = qubits.iter().map(|&qubit| self.layout[qubit]).collect();
+        let mut table = vec![0.0; 1 << qubits.len()];
+        for (index, amplitude) in state.iter().enumerate() {
+            let outcome = bits.iter().enumerate().fold(0, |k, (i, &bit)| k | (((index >> bit) & 1) << i));
+            table[outcome] += amplitude.magnitude_squared();
+        }
+        table
+    }
+
+    /// ⟨ψ|H|ψ⟩ for a weighted sum of Pauli strings, one pass over the amplitudes per term.
+    /// Strings are mapped onto physical qubits, so a permuted layout costs no copy.
+    pub fn expectation(&self, observable: &PauliSum) -> f64 {
+        let state = self.state.amplitudes();
+        observable
+            .terms
+            .iter()
+            .map(|term| {
+                let (x_mask, z_mask, y_count) = term.masks(|qubit| self.layout[qubit]);
+                term.coefficient * pauli_expectation(&state, x_mask, z_mask, y_count)
+            })
+            .sum()
+    }
+
+    /// Density matrix of the listed qubits with the rest traced out; row and column k are the
+    /// subsystem basis state with `qubits[0]` as the least significant bit. The qubits must be
+    /// distinct and in range.
+    pub fn reduced_density_matrix(&self, qubits: &[usize]) -> Vec<Vec<Complex>> {
+        let state = self.state.amplitudes();
+        let bits: Vec<usize> = qubits.iter().map(|&qubit| self.layout[qubit]).collect();
+        let kept = bits.iter().fold(0, |mask, &bit| mask | (1 << bit));
+        let dim = 1 << qubits.len();
+        // Physical index offset of each subsystem basis state
+        let offsets: Vec<usize> = (0..dim)
+            .map(|k| bits.iter().enumerate().fold(0, |index, (i, &bit)| index | (((k >> i) & 1) << bit)))
+            .collect();
+
+        let mut rho = vec![vec![Complex::new(0.0, 0.0); dim]; dim];
+        for rest in (0..state.len()).filter(|index| index & kept == 0) {
+            for (row, &offset) in offsets.iter().enumerate() {
+                let amplitude = state[rest | offset];
+                if amplitude.magnitude_squared() == 0.0 {
+                    continue;
+                }
+                for (column, &other) in offsets.iter().enumerate() {
+                    rho[row][column] = rho[row][column] + amplitude * state[rest | other].conjugate();
+                }
+            }
+        }
+        rho
+    }
+
+    /// |⟨ψ|φ⟩|² between this state and another simulator's, ignoring global phase. States of
+    /// different sizes count as orthogonal.
+    pub fn fidelity(&self, other: &QuantumSimulator) -> f64 {
+        if self.num_qubits != other.num_qubits {
+            return 0.0;
+        }
+        // Matching layouts line up the physical amplitudes, so neither state is copied
+        if self.layout == other.layout {
+            state_fidelity(&self.state.amplitudes(), &other.state.amplitudes())
+        } else {
+            state_fidelity(&self.get_state(), &other.get_state())
+        }
+    }
+
+    /// Get quantum state vector in logical qubit order; copied only if qubits were permuted
+    pub fn get_state(&self) -> Cow<'_, [Complex]> {
+        let data = self.state.amplitudes();
+        if self.layout_is_identity() {
+            return data;
+        }
+        let mut logical = vec![Complex::new(0.0, 0.0); data.len()];
+        for (index, &amplitude) in data.iter().enumerate() {
+            logical[self.logical_index(index)] = amplitude;
+        }
+        Cow::Owned(logical)
+    }
+
+    /// Amplitude of one basis state, with qubit 0 as the least significant bit, read in
+    /// place without copying the state out. Panics if `bitstring` is outside the register.
+    pub fn amplitude(&self, bitstring: u64) -> Complex {
+        let state = self.state.amplitudes();
+        assert!(
+            bitstring < state.len() as u64,
+            "basis state {} is outside a {}-qubit register",
+            bitstring,
+            self.num_qubits
+        );
+        state[self.physical_index(bitstring as usize)]
+    }
+
+    /// Amplitudes of several basis states, in the order given; see `amplitude`
+    pub fn amplitudes(&self, bitstrings: &[u64]) -> Vec<Complex> {
+        bitstrings.iter().map(|&bitstring| self.amplitude(bitstring)).collect()
+    }
+}
+
+/// |⟨a|b⟩|² of two state vectors in the same qubit order; 0 if their lengths differ
+pub fn state_fidelity(a: &[Complex], b: &[Complex]) -> f64 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let overlap = a.iter().zip(b).fold(Complex::new(0.0, 0.0), |sum, (x, y)| sum + x.conjugate() * *y);
+    overlap.magnitude_squared()
+}
+
+/// One sampled shot: the final logical basis state and the classical register it reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shot {
+    pub outcome: usize,
+    /// Bit k holds the latest measurement stored in classical bit k
+    pub register: usize,
+}
+
+/// Sample shots of a circuit
+///
+/// Circuits without collapse are simulated once and sampled from the amplitudes; circuits
+/// with resets or mid-circuit measurements are re-run for every shot, so each shot follows
+/// its own trajectory and its register keeps outcomes that later gates overwrite.
+pub fn sample_circuit<R: Rng + ?Sized>(circuit: &QuantumCircuit, shots: usize, rng: &mut R) -> Vec<Shot> {
+    sample_circuit_with(circuit, shots, rng, |seed| QuantumSimulator::seeded(circuit.num_qubits, seed))
+}
+
+/// `sample_circuit` on simulators made by `create` from a seed, e.g. on another state
+/// representation with `QuantumSimulator::with_backend`
+pub fn sample_circuit_with<R, F>(circuit: &QuantumCircuit, shots: usize, rng: &mut R, create: F) -> Vec<Shot>
+where
+    R: Rng + ?Sized,
+    F: Fn(u64) -> QuantumSimulator,
+{
+    let run = |rng: &mut R| {
+        let mut simulator = create(rng.gen());
+        for gate in &circuit.gates {
+            simulator.apply_gate(gate);
+        }
+        simulator
+    };
+    if !circuit.has_collapse() {
+        return terminal_shots(circuit, run(rng).sample(shots, rng));
+    }
+    sample_trajectories(circuit, shots, rng, create)
+}
+
+/// Shots of a circuit re-run from scratch on a fresh simulator from `create` every time, so
+/// each follows its own measurement outcomes and noise branches
+pub fn sample_trajectories<R, F>(circuit: &QuantumCircuit, shots: usize, rng: &mut R, create: F) -> Vec<Shot>
+where
+    R: Rng + ?Sized,
+    F: Fn(u64) -> QuantumSimulator,
+{
+    (0..shots).map(|_| trajectory_shot(circuit, rng, &create)).collect()
+}
+
+/// `sample_trajectories` split across `threads` threads, each running its own simulators.
+/// Every shot draws from a seed of its own, so the shots are the same for any thread count.
+pub fn sample_trajectories_threaded<R, F>(
+    circuit: &QuantumCircuit,
+    shots: usize,
+    threads: usize,
+    rng: &mut R,
+    create: F,
+) -> Vec<Shot>
+where
+    R: Rng + ?Sized,
+    F: Fn(u64) -> QuantumSimulator + Sync,
+{
+    let seeds: Vec<u64> = (0..shots).map(|_| rng.gen()).collect();
+    let chunk = shots.div_ceil(threads.max(1)).max(1);
+    // The trajectory threads share the cores the kernels would otherwise split across
+    let kernel_threads = (gpu_ops::kernel_threads() / threads.max(1)).max(1);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = seeds
+            .chunks(chunk)
+            .map(|seeds| {
+                let create = &create;
+                scope.spawn(move || {
+                    gpu_ops::set_kernel_threads(kernel_threads);
+                    seeds
+                        .iter()
+                        .map(|&seed| trajectory_shot(circuit, &mut StdRng::seed_from_u64(seed), create))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().expect("trajectory thread panicked")).collect()
+    })
+}
+
+/// One shot of a circuit run on a fresh simulator
+fn trajectory_shot<R, F>(circuit: &QuantumCircuit, rng: &mut R, create: &F) -> Shot
+where
+    R: Rng + ?Sized,
+    F: Fn(u64) -> QuantumSimulator,
+{
+    let mut simulator = create(rng.gen());
+    for gate in &circuit.gates {
+        simulator.apply_gate(gate);
+    }
+    let outcome = simulator.sample(1, rng)[0];
+    Shot { outcome, register: simulator.register_for(outcome) }
+}
+
+/// Shots of a circuit whose measurements are all terminal, so each register reads straight
+/// from the sampled basis state
+pub fn terminal_shots(circuit: &QuantumCircuit, outcomes: Vec<usize>) -> Vec<Shot> {
+    let readout = circuit.readout();
+    outcomes
+        .into_iter()
+        .map(|outcome| Shot {
+            outcome,
+            register: readout
+                .iter()
+                .fold(0, |register, &(qubit, bit)| (register & !(1 << bit)) | (((outcome >> qubit) & 1) << bit)),
+        })
+        .collect()
+}
+
+/// 2^k x 2^k matrix of a unitary gate on its own `qubits`, `qubits[0]` the least significant
+/// bit, read column by column off a k-qubit state vector
+pub fn gate_matrix(gate: &QuantumGate, qubits: &[usize]) -> Vec<Vec<Complex>> {
+    let local = gate.map_qubits(|q| qubits.iter().position(|&x| x == q).unwrap());
+    let dim = 1 << qubits.len();
+    let columns: Vec<Vec<Complex>> = (0..dim)
+        .map(|col| {
+            let mut basis = vec![Complex::new(0.0, 0.0); dim];
+            basis[col] = Complex::new(1.0, 0.0);
+            let mut simulator = QuantumSimulator::from_state(basis).expect("2^k amplitudes");
+            simulator.apply_gate(&local);
+            simulator.get_state().into_owned()
+        })
+        .collect();
+    (0..dim).map(|row| (0..dim).map(|col| columns[col][row]).collect()).collect()
+}
+
+/// Matrix of a gate applied by the generic two-qubit kernel, as (qubit1, qubit2, matrix)
+/// with rows and columns indexed by (bit of qubit1) + 2 * (bit of qubit2)
+///
+/// Diagonal gates (CZ, CPhase) keep their cheaper one-amplitude kernels.
+pub fn two_qubit_matrix(gate: &QuantumGate) -> Option<(usize, usize, [[Complex; 4]; 4])> {
+    let zero = Complex::new(0.0, 0.0);
+    let one = Complex::new(1.0, 0.0);
+    // Identity except for the |01>/|10> block (indices 1 and 2)
+    let exchange = |diagonal: Complex, off_diagonal: Complex| {
+        [
+            [one, zero, zero, zero],
+            [zero, diagonal, off_diagonal, zero],
+            [zero, off_diagonal, diagonal, zero],
+            [zero, zero, zero, one],
+        ]
+    };
+    // XY rotation: cos(theta) on the block diagonal, i*sin(theta) off it; theta = pi/2 is iSWAP
+    let xy = |theta: f64| {
+        let (sin, cos) = theta.sin_cos();
+        exchange(Complex::new(cos, 0.0), Complex::new(0.0, sin))
+    };
+
+    match gate {
+        // Control is the low index bit, so X on the target swaps indices 1 and 3
+        QuantumGate::CNOT { control, target } => Some((
+            *control,
+            *target,
+            [
+                [one, zero, zero, zero],
+                [zero, zero, zero, one],
+                [zero, zero, one, zero],
+                [zero, one, zero, zero],
+            ],
+        )),
+        QuantumGate::SWAP { qubit1, qubit2 } => Some((*qubit1, *qubit2, exchange(zero, one))),
+        QuantumGate::ISWAP { qubit1, qubit2 } => Some((*qubit1, *qubit2, xy(std::f64::consts::FRAC_PI_2))),
+        QuantumGate::SqrtISWAP { qubit1, qubit2 } => Some((*qubit1, *qubit2, xy(std::f64::consts::FRAC_PI_4))),
+        // Qiskit's ECR matrix, (IX - XY)/sqrt(2) with qubit1 as the rightmost factor
+        QuantumGate::ECR { qubit1, qubit2 } => {
+            let r = Complex::new(std::f64::consts::FRAC_1_SQRT_2, 0.0);
+            let i = Complex::new(0.0, std::f64::consts::FRAC_1_SQRT_2);
+            let minus_i = Complex::new(0.0, -std::f64::consts::FRAC_1_SQRT_2);
+            Some((
+                *qubit1,
+                *qubit2,
+                [
+                    [zero, r, zero, i],
+                    [r, zero, minus_i, zero],
+                    [zero, i, zero, r],
+                    [minus_i, zero, r, zero],
+                ],
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Largest deviation of U^dagger U from the identity accepted for a custom gate matrix
+pub const UNITARY_TOLERANCE: f64 = 1e-6;
+
+/// Whether a square matrix is unitary within `tolerance` (checks every entry of U^dagger U)
+pub fn is_unitary<R: AsRef<[Complex]>>(matrix: &[R], tolerance: f64) -> bool {
+    let dim = matrix.len();
+    (0..dim).all(|i| {
+        (0..dim).all(|j| {
+            let product = matrix.iter().fold(Complex::new(0.0, 0.0), |sum, row| {
+                let row = row.as_ref();
+                sum + row[i].conjugate() * row[j]
+            });
+            let expected = if i == j { 1.0 } else { 0.0 };
+            (product.re - expected).abs() <= tolerance && product.im.abs() <= tolerance
+        })
+    })
+}
+
+/// Reject gates the simulator cannot apply faithfully, such as non-unitary custom matrices
+pub fn validate_circuit(circuit: &QuantumCircuit) -> Result<(), String> {
+    let mut measured = BTreeSet::new();
+    let mut labels = BTreeSet::new();
+    for (index, gate) in circuit.gates.iter().enumerate() {
+        validate_gate(index, gate, circuit.num_qubits)?;
+        if let QuantumGate::Snapshot { label } = gate {
+            if !labels.insert(label) {
+                return Err(format!("gate {} reuses the snapshot label '{}'", index, label));
+            }
+        }
+        for bit in condition_bits(gate) {
+            if !measured.contains(&bit) {
+                return Err(format!("gate {} is conditioned on bit {}, which no earlier gate measures", index, bit));
+            }
+        }
+        if let QuantumGate::Measurement { qubit, bit } = gate {
+            measured.insert(bit.unwrap_or(*qubit));
+        }
+    }
+    Ok(())
+}
+
+/// Check one gate of a circuit on `num_qubits` qubits; `index` is its position, used in the
+/// error message
+pub fn validate_gate(index: usize, gate: &QuantumGate, num_qubits: usize) -> Result<(), String> {
+    let qubits = gate.qubits();
+    if let Some(qubit) = qubits.iter().find(|&&qubit| qubit >= num_qubits) {
+        return Err(format!("gate {} uses qubit {}, but the circuit has {} qubits", index, qubit, num_qubits));
+    }
+    if let Some(qubit) = qubits.iter().enumerate().find_map(|(k, qubit)| qubits[..k].contains(qubit).then_some(qubit)) {
+        return Err(format!("gate {} uses qubit {} more than once", index, qubit));
+    }
+    match gate {
+        QuantumGate::Unitary1Q { matrix, .. } if !is_unitary(matrix, UNITARY_TOLERANCE) => {
+            return Err(format!("gate {} has a matrix that is not unitary", index));
+        }
+        QuantumGate::GlobalHadamard { qubits } | QuantumGate::GlobalRotation { qubits, .. }
+            if !distinct_qubits(qubits) =>
+        {
+            return Err(format!("gate {} needs one or more distinct qubits", index));
+        }
+        QuantumGate::PauliRotation { qubits, pauli, .. } => {
+            if !distinct_qubits(qubits) {
+                return Err(format!("gate {} needs one or more distinct qubits", index));
+            }
+            if pauli.chars().count() != qubits.len() {
+                return Err(format!("gate {} needs one Pauli per qubit, got '{}' for {} qubits", index, pauli, qubits.len()));
+            }
+            if let Some(p) = pauli.chars().find(|p| !"IXYZ".contains(*p)) {
+                return Err(format!("gate {} has '{}' in its Pauli string; use I, X, Y or Z", index, p));
+            }
+            if pauli_weight(pauli) == 0 {
+                return Err(format!("gate {} has an all-identity Pauli string", index));
+            }
+        }
+        QuantumGate::Permutation { qubits, pattern } => {
+            if !distinct_qubits(qubits) {
+                return Err(format!("gate {} needs one or more distinct qubits", index));
+            }
+            let mut sorted = pattern.clone();
+            sorted.sort_unstable();
+            if sorted.len() != qubits.len() || sorted.iter().enumerate().any(|(k, &p)| k != p) {
+                return Err(format!("gate {} pattern is not a permutation of 0..{}", index, qubits.len()));
+            }
+        }
+        QuantumGate::Diagonal { qubits, phases } => {
+            if !distinct_qubits(qubits) {
+                return Err(format!("gate {} needs one or more distinct qubits", index));
+            }
+            let dim = 1usize << qubits.len().min(usize::BITS as usize - 1);
+            if phases.len() != dim {
+                return Err(format!("gate {} on {} qubits needs {} phases, got {}", index, qubits.len(), dim, phases.len()));
+            }
+            if phases.iter().any(|phase| !phase.is_finite()) {
+                return Err(format!("gate {} has a phase that is not a finite number", index));
+            }
+        }
+        QuantumGate::UnitaryNQ { qubits, matrix } => {
+            if !distinct_qubits(qubits) {
+                return Err(format!("gate {} needs one or more distinct qubits", index));
+            }
+            // Clamped so the shift cannot overflow; no matrix has that many rows anyway
+            let dim = 1usize << qubits.len().min(usize::BITS as usize - 1);
+            if matrix.len() != dim || matrix.iter().any(|row| row.len() != dim) {
+                return Err(format!(
+                    "gate {} on {} qubits needs a {}x{} matrix",
+                    index,
+                    qubits.len(),
+                    dim,
+                    dim
+                ));
+            }
+            if !is_unitary(matrix, UNITARY_TOLERANCE) {
+                return Err(format!("gate {} has a matrix that is not unitary", index));
+            }
+        }
+        QuantumGate::Controlled { gate, .. } => {
+            if matches!(**gate, QuantumGate::Measurement { .. }) {
+                return Err(format!("gate {} controls a measurement", index));
+            }
+            if matches!(**gate, QuantumGate::Reset { .. }) {
+                return Err(format!("gate {} controls a reset", index));
+            }
+            if matches!(**gate, QuantumGate::IfBit { .. }) {
+                return Err(format!("gate {} controls a classically conditioned gate", index));
+            }
+            if matches!(**gate, QuantumGate::Noisy { .. }) {
+                return Err(format!("gate {} controls a gate with a noise override; put the override on the controlled gate", index));
+            }
+            if matches!(**gate, QuantumGate::Snapshot { .. }) {
+                return Err(format!("gate {} controls a snapshot", index));
+            }
+            return validate_gate(index, gate, num_qubits);
+        }
+        QuantumGate::Snapshot { label } if label.trim().is_empty() => {
+            return Err(format!("gate {} is a snapshot without a label", index));
+        }
+        QuantumGate::Measurement { bit: Some(bit), .. } if *bit >= usize::BITS as usize => {
+            return Err(format!("gate {} stores into classical bit {}; registers hold {} bits", index, bit, usize::BITS));
+        }
+        QuantumGate::IfBit { gate, .. } => {
+            if matches!(**gate, QuantumGate::Measurement { .. }) {
+                return Err(format!("gate {} conditions a measurement", index));
+            }
+            if matches!(**gate, QuantumGate::Snapshot { .. }) {
+                return Err(format!("gate {} conditions a snapshot", index));
+            }
+            return validate_gate(index, gate, num_qubits);
+        }
+        QuantumGate::Noisy { noise, gate } => {
+            let wrapped = match **gate {
+                QuantumGate::Measurement { .. } => Some("a measurement"),
+                QuantumGate::Reset { .. } => Some("a reset"),
+                QuantumGate::Snapshot { .. } => Some("a snapshot"),
+                QuantumGate::IfBit { .. } => Some("a conditioned gate; put it on the inner gate"),
+                QuantumGate::Noisy { .. } => Some("a gate that already has one"),
+                _ => None,
+            };
+            if let Some(wrapped) = wrapped {
+                return Err(format!("gate {} puts a noise override on {}", index, wrapped));
+            }
+            if noise.error.is_some_and(|error| !(0.0..=1.0).contains(&error)) {
+                return Err(format!("gate {} has a noise error rate outside [0, 1]", index));
+            }
+            if noise.duration_ns.is_some_and(|duration| !duration.is_finite() || duration < 0.0) {
+                return Err(format!("gate {} has a negative or infinite noise duration", index));
+            }
+            return validate_gate(index, gate, num_qubits);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Whether a later gate depends on a measurement of `qubit` into register bit `bit`: it acts
+/// on the qubit, or a classical condition reads the bit. Measurements nothing depends on can
+/// stay deferred, keeping the output distribution.
+pub fn measurement_is_read(qubit: usize, bit: usize, later: &[QuantumGate]) -> bool {
+    later.iter().any(|gate| {
+        (!matches!(gate, QuantumGate::Measurement { .. }) && gate.qubits().contains(&qubit)) || condition_bits(gate).contains(&bit)
+    })
+}
+
+/// Register bits a gate writes (a measurement) or reads (classical conditions)
+pub fn register_bits(gate: &QuantumGate) -> Vec<usize> {
+    match gate {
+        QuantumGate::Measurement { qubit, bit } => vec![bit.unwrap_or(*qubit)],
+        gate => condition_bits(gate),
+    }
+}
+
+/// Classical bits a gate is conditioned on, outermost first
+pub fn condition_bits(gate: &QuantumGate) -> Vec<usize> {
+    match gate {
+        QuantumGate::IfBit { bit, gate } => std::iter::once(*bit).chain(condition_bits(gate)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Qubit pairs to swap, in order, to carry out a permutation
+pub fn permutation_swaps(qubits: &[usize], pattern: &[usize]) -> Vec<(usize, usize)> {
+    // held[k] is the original position whose state currently sits at position k
+    let mut held: Vec<usize> = (0..qubits.len()).collect();
+    let mut swaps = Vec::new();
+    for (k, &source) in pattern.iter().enumerate() {
+        if let Some(j) = (k..held.len()).find(|&j| held[j] == source) {
+            if j != k {
+                held.swap(k, j);
+                swaps.push((qubits[k], qubits[j]));
             }
         }
-        prob
     }
+    swaps
+}
+
+/// Diagonal gate as Z-string rotations exp(i*a_S * Z_S), one per qubit subset S with a
+/// nonzero Walsh coefficient a_S; exact up to the global phase a_{} (the mean phase)
+pub fn diagonal_gates(qubits: &[usize], phases: &[f64]) -> Vec<QuantumGate> {
+    let mut gates = Vec::new();
+    for subset in 1..phases.len() {
+        let coefficient = phases
+            .iter()
+            .enumerate()
+            .map(|(k, phase)| if (k & subset).count_ones() & 1 == 0 { *phase } else { -phase })
+            .sum::<f64>()
+            / phases.len() as f64;
+        if coefficient.abs() < 1e-12 {
+            continue;
+        }
+        let pauli: String = (0..qubits.len()).map(|bit| if subset >> bit & 1 == 1 { 'Z' } else { 'I' }).collect();
+        gates.extend(pauli_rotation_gates(qubits, &pauli, -2.0 * coefficient));
+    }
+    gates
+}
+
+/// ECR as its defining sequence RZX(pi/4), X, RZX(-pi/4), for formats without a native ECR
+pub fn ecr_gates(qubit1: usize, qubit2: usize) -> Vec<QuantumGate> {
+    let quarter = std::f64::consts::FRAC_PI_4;
+    let mut gates = pauli_rotation_gates(&[qubit1, qubit2], "ZX", quarter);
+    gates.push(QuantumGate::PauliX { qubit: qubit1 });
+    gates.extend(pauli_rotation_gates(&[qubit1, qubit2], "ZX", -quarter));
+    gates
+}
+
+/// Givens rotation as exp(-i*angle/2 * (Y⊗X - X⊗Y)), written as its two commuting Pauli rotations
+pub fn givens_gates(qubit1: usize, qubit2: usize, angle: f64) -> Vec<QuantumGate> {
+    let mut gates = pauli_rotation_gates(&[qubit1, qubit2], "YX", angle);
+    gates.extend(pauli_rotation_gates(&[qubit1, qubit2], "XY", -angle));
+    gates
+}
+
+/// Number of non-identity factors in a Pauli string
+pub fn pauli_weight(pauli: &str) -> usize {
+    pauli.chars().filter(|&p| p != 'I').count()
+}
+
+/// exp(-i*angle/2 * P) as basis changes into Z, a CNOT parity ladder, one RZ and the ladder undone
+pub fn pauli_rotation_gates(qubits: &[usize], pauli: &str, angle: f64) -> Vec<QuantumGate> {
+    let active: Vec<(usize, char)> = qubits.iter().copied().zip(pauli.chars()).filter(|&(_, p)| p != 'I').collect();
+    let quarter_turn = std::f64::consts::FRAC_PI_2;
+    let basis = |sign: f64| -> Vec<QuantumGate> {
+        active
+            .iter()
+            .filter_map(|&(qubit, p)| match p {
+                'X' => Some(QuantumGate::Hadamard { qubit }),
+                // RX(pi/2) Y RX(-pi/2) = Z
+                'Y' => Some(QuantumGate::RotationX { qubit, angle: sign * quarter_turn }),
+                _ => None,
+            })
+            .collect()
+    };
+    let ladder: Vec<QuantumGate> = active
+        .windows(2)
+        .map(|pair| QuantumGate::CNOT { control: pair[0].0, target: pair[1].0 })
+        .collect();
+
+    let mut gates = basis(1.0);
+    gates.extend(ladder.iter().cloned());
+    if let Some(&(qubit, _)) = active.last() {
+        gates.push(QuantumGate::RotationZ { qubit, angle });
+    }
+    gates.extend(ladder.into_iter().rev());
+    gates.extend(basis(-1.0));
+    gates
+}
+
+fn distinct_qubits(qubits: &[usize]) -> bool {
+    let mut distinct = qubits.to_vec();
+    distinct.sort_unstable();
+    distinct.dedup();
+    !qubits.is_empty() && distinct.len() == qubits.len()
+}
+
+/// U3(theta, phi, lambda) angles equal to a single-qubit unitary up to global phase
+pub fn u3_angles(matrix: &[[Complex; 2]; 2]) -> (f64, f64, f64) {
+    let arg = |c: Complex| c.im.atan2(c.re);
+    let [[m00, m01], [m10, m11]] = *matrix;
+    let theta = 2.0 * m10.magnitude_squared().sqrt().atan2(m00.magnitude_squared().sqrt());
+    let minus_m01 = Complex::new(-m01.re, -m01.im);
+    if m10.magnitude_squared() < 1e-24 {
+        // Diagonal: only phi + lambda is defined
+        (theta, 0.0, arg(m11) - arg(m00))
+    } else if m00.magnitude_squared() < 1e-24 {
+        // Anti-diagonal: only lambda - phi is defined
+        (theta, 0.0, arg(minus_m01) - arg(m10))
+    } else {
+        let global = arg(m00);
+        (theta, arg(m10) - global, arg(minus_m01) - global)
+    }
+}
+
+/// Circuit definition file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitFormat {
+    Json,
+    Yaml,
+    Toml,
+    Qasm,
+    Quil,
+    Protobuf,
+    Stim,
+}
 
-    /// Get quantum state vector
-    pub fn get_state(&self) -> &[Complex] {
-        self.state.get_data()
+impl CircuitFormat {
+    /// Detect format from file extension (defaults to JSON)
+    pub fn from_path(path: &str) -> Self {
+        let extension = std::path::Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("yaml") | Some("yml") => CircuitFormat::Yaml,
+            Some("toml") => CircuitFormat::Toml,
+            Some("qasm") => CircuitFormat::Qasm,
+            Some("quil") => CircuitFormat::Quil,
+            Some("pb") => CircuitFormat::Protobuf,
+            Some("stim") => CircuitFormat::Stim,
+            _ => CircuitFormat::Json,
+        }
     }
 }
 
-/// Load quantum circuit from JSON file
+/// Load quantum circuit from a JSON, YAML, TOML, OpenQASM, Quil, protobuf or Stim file
 pub fn load_circuit(path: &str) -> Result<QuantumCircuit, Box<dyn Error>> {
-    let contents = fs::read_to_string(path)?;
-    let circuit: QuantumCircuit = serde_json::from_str(&contents)?;
+    match CircuitFormat::from_path(path) {
+        CircuitFormat::Protobuf => proto::decode_circuit(&fs::read(path)?),
+        format => parse_circuit(&fs::read_to_string(path)?, format),
+    }
+}
+
+/// Load a circuit without validating its gates, so the linter can report every problem
+pub fn load_circuit_unchecked(path: &str) -> Result<QuantumCircuit, Box<dyn Error>> {
+    match CircuitFormat::from_path(path) {
+        CircuitFormat::Protobuf => proto::decode_circuit_unchecked(&fs::read(path)?),
+        format => parse_circuit_unchecked(&fs::read_to_string(path)?, format),
+    }
+}
+
+/// Parse a quantum circuit definition in the given format
+pub fn parse_circuit(contents: &str, format: CircuitFormat) -> Result<QuantumCircuit, Box<dyn Error>> {
+    let circuit = parse_circuit_unchecked(contents, format)?;
+    validate_circuit(&circuit)?;
     Ok(circuit)
 }
 
-/// Save quantum circuit to JSON file
+/// Parse a circuit without validating its gates, so the linter can report every problem
+pub fn parse_circuit_unchecked(contents: &str, format: CircuitFormat) -> Result<QuantumCircuit, Box<dyn Error>> {
+    let circuit: QuantumCircuit = match format {
+        CircuitFormat::Json => from_document(serde_json::from_str(contents)?)?,
+        CircuitFormat::Yaml => from_document(serde_yaml::from_str(contents)?)?,
+        CircuitFormat::Toml => from_document(toml::from_str(contents)?)?,
+        CircuitFormat::Qasm => qasm::parse_qasm(contents)?,
+        CircuitFormat::Quil => quil::parse_quil(contents)?,
+        CircuitFormat::Stim => stim::parse_stim(contents)?,
+        CircuitFormat::Protobuf => return Err("protobuf is a binary format".into()),
+    };
+    Ok(circuit)
+}
+
+/// Build a circuit from a parsed JSON, YAML or TOML document, binding parameters, expanding
+/// macros and wrapping inline `noise` overrides
+fn from_document(mut document: serde_json::Value) -> Result<QuantumCircuit, Box<dyn Error>> {
+    noise::wrap_inline_overrides(&mut document)?;
+    if params::has_parameters(&document) {
+        params::instantiate(&document, &BTreeMap::new())
+    } else if macros::has_macros(&document) {
+        macros::expand(document)
+    } else {
+        Ok(serde_json::from_value(document)?)
+    }
+}
+
+/// Save quantum circuit to a JSON, YAML, TOML, OpenQASM, Quil or protobuf file
 pub fn save_circuit(circuit: &QuantumCircuit, path: &str) -> Result<(), Box<dyn Error>> {
-    let json = serde_json::to_string_pretty(circuit)?;
-    fs::write(path, json)?;
+    match CircuitFormat::from_path(path) {
+        CircuitFormat::Protobuf => fs::write(path, proto::encode_circuit(circuit))?,
+        format => fs::write(path, serialize_circuit(circuit, format)?)?,
+    }
     Ok(())
 }
 
+/// Serialize a quantum circuit into the given format
+pub fn serialize_circuit(circuit: &QuantumCircuit, format: CircuitFormat) -> Result<String, Box<dyn Error>> {
+    let contents = match format {
+        CircuitFormat::Json => serde_json::to_string_pretty(circuit)?,
+        CircuitFormat::Yaml => serde_yaml::to_string(circuit)?,
+        CircuitFormat::Toml => toml::to_string_pretty(circuit)?,
+        CircuitFormat::Qasm => qasm::to_qasm(circuit)?,
+        CircuitFormat::Quil => quil::to_quil(circuit)?,
+        CircuitFormat::Stim => stim::to_stim(circuit)?,
+        CircuitFormat::Protobuf => return Err("protobuf is a binary format".into()),
+    };
+    Ok(contents)
+}
+
 /// Optimize quantum circuit by removing redundant gates
 pub fn optimize(circuit: QuantumCircuit) -> QuantumCircuit {
     let mut optimized_gates = Vec::new();
@@ -215,6 +1481,7 @@ pub fn optimize(circuit: QuantumCircuit) -> QuantumCircuit {
     QuantumCircuit {
         num_qubits: circuit.num_qubits,
         gates: optimized_gates,
+        metadata: circuit.metadata,
     }
 }
 
@@ -226,6 +1493,7 @@ pub fn create_bell_state() -> QuantumCircuit {
             QuantumGate::Hadamard { qubit: 0 },
             QuantumGate::CNOT { control: 0, target: 1 },
         ],
+        metadata: BTreeMap::new(),
     }
 }
 
@@ -235,7 +1503,7 @@ pub fn create_ghz_state(num_qubits: usize) -> QuantumCircuit {
     for i in 1..num_qubits {
         gates.push(QuantumGate::CNOT { control: 0, target: i });
     }
-    QuantumCircuit { num_qubits, gates }
+    QuantumCircuit { num_qubits, gates, metadata: BTreeMap::new() }
 }
 
 /// Create quantum Fourier transform circuit
@@ -245,8 +1513,88 @@ pub fn create_qft_circuit(num_qubits: usize) -> QuantumCircuit {
         gates.push(QuantumGate::Hadamard { qubit: j });
         for k in (0..j).rev() {
             let angle = std::f64::consts::PI / 2.0_f64.powi((j - k) as i32);
-            gates.push(QuantumGate::Phase { qubit: j, angle });
+            gates.push(QuantumGate::CPhase { control: k, target: j, angle });
+        }
+    }
+    // Reverse the qubit order so the output matches the standard QFT ordering
+    for i in 0..num_qubits / 2 {
+        gates.push(QuantumGate::SWAP { qubit1: i, qubit2: num_qubits - 1 - i });
+    }
+    QuantumCircuit { num_qubits, gates, metadata: BTreeMap::new() }
+}
+
+/// Create a random circuit in the style of supremacy experiments: each cycle applies a
+/// random sqrt(X), sqrt(Y) or T to every qubit, then CZs on alternating neighbour pairs
+pub fn create_random_circuit<R: Rng>(num_qubits: usize, depth: usize, rng: &mut R) -> QuantumCircuit {
+    let mut gates = Vec::new();
+    for cycle in 0..depth {
+        for qubit in 0..num_qubits {
+            gates.push(match rng.gen_range(0..3) {
+                0 => QuantumGate::SX { qubit },
+                1 => QuantumGate::RotationY { qubit, angle: std::f64::consts::FRAC_PI_2 },
+                _ => QuantumGate::T { qubit },
+            });
+        }
+        for control in (cycle % 2..num_qubits.saturating_sub(1)).step_by(2) {
+            gates.push(QuantumGate::CZ { control, target: control + 1 });
+        }
+    }
+    let mut metadata = BTreeMap::new();
+    metadata.insert("name".to_string(), format!("random-{}x{}", num_qubits, depth));
+    QuantumCircuit { num_qubits, gates, metadata }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// X on qubit 0 if `flip`, measured into register bit 3, then X on qubit 1 conditioned on bit 3
+    fn feed_forward(flip: bool) -> QuantumCircuit {
+        let mut gates = Vec::new();
+        if flip {
+            gates.push(QuantumGate::PauliX { qubit: 0 });
+        }
+        gates.push(QuantumGate::Measurement { qubit: 0, bit: Some(3) });
+        gates.push(QuantumGate::IfBit { bit: 3, gate: Box::new(QuantumGate::PauliX { qubit: 1 }) });
+        QuantumCircuit { num_qubits: 2, gates, metadata: BTreeMap::new() }
+    }
+
+    #[test]
+    fn conditions_read_the_register_bit_a_measurement_stored_into() {
+        for flip in [false, true] {
+            let circuit = feed_forward(flip);
+            assert!(validate_circuit(&circuit).is_ok());
+            assert!(circuit.has_collapse());
+            let shots = sample_circuit(&circuit, 16, &mut StdRng::seed_from_u64(7));
+            let (outcome, register) = if flip { (0b11, 0b1000) } else { (0b00, 0) };
+            assert!(shots.iter().all(|shot| *shot == Shot { outcome, register }), "flip {}: {:?}", flip, shots);
+        }
+    }
+
+    #[test]
+    fn conditions_on_a_bit_no_measurement_stored_into_are_rejected() {
+        let mut circuit = feed_forward(true);
+        circuit.gates[2] = QuantumGate::IfBit { bit: 0, gate: Box::new(QuantumGate::PauliX { qubit: 1 }) };
+        let error = validate_circuit(&circuit).unwrap_err();
+        assert!(error.contains("conditioned on bit 0"), "{}", error);
+    }
+
+    #[test]
+    fn gates_on_missing_or_repeated_qubits_are_rejected() {
+        let rejected = [
+            (QuantumGate::Hadamard { qubit: 2 }, "uses qubit 2, but the circuit has 2 qubits"),
+            (QuantumGate::CNOT { control: 0, target: 5 }, "uses qubit 5"),
+            (QuantumGate::CNOT { control: 1, target: 1 }, "uses qubit 1 more than once"),
+            (QuantumGate::CZ { control: 0, target: 0 }, "more than once"),
+            (QuantumGate::SWAP { qubit1: 1, qubit2: 1 }, "more than once"),
+            (QuantumGate::Toffoli { control1: 0, control2: 1, target: 0 }, "uses qubit 0 more than once"),
+            (QuantumGate::MCX { controls: vec![0, 1, 0], target: 1 }, "more than once"),
+            (QuantumGate::Controlled { control: 1, gate: Box::new(QuantumGate::PauliX { qubit: 1 }) }, "more than once"),
+        ];
+        for (gate, expected) in rejected {
+            let error = validate_gate(0, &gate, 2).unwrap_err();
+            assert!(error.contains(expected), "{:?}: {}", gate, error);
         }
+        assert!(validate_gate(0, &QuantumGate::CNOT { control: 1, target: 0 }, 2).is_ok());
     }
-    QuantumCircuit { num_qubits, gates }
 }
@@ -0,0 +1,357 @@
+//! Quil Module
+//! Quil import and export for quantum circuits
+
+use std::collections::{BTreeMap, BTreeSet};
+use crate::qasm::{eval_angle, parse_metadata_comment};
+use crate::gpu_ops::RotationAxis;
+use crate::qsim::{self, QuantumCircuit, QuantumGate};
+
+/// Export a circuit as Quil source
+pub fn to_quil(circuit: &QuantumCircuit) -> Result<String, String> {
+    let mut out = String::new();
+    for (key, value) in &circuit.metadata {
+        out.push_str(&format!("# @{}: {}\n", key, value.replace('\n', " ")));
+    }
+    out.push_str(&format!("DECLARE ro BIT[{}]\n", circuit.num_clbits()));
+
+    let mut labels = 0;
+    for gate in &circuit.gates {
+        out.push_str(&statement(gate, &mut labels)?);
+        out.push('\n');
+    }
+
+    Ok(out)
+}
+
+/// Quil for one top-level gate; a classical condition jumps over the gate unless its bit is set
+fn statement(gate: &QuantumGate, labels: &mut usize) -> Result<String, String> {
+    match gate {
+        QuantumGate::IfBit { bit, gate } => {
+            let label = format!("@skip{}", labels);
+            *labels += 1;
+            let inner = statement(gate, labels)?;
+            Ok(format!("JUMP-UNLESS {} ro[{}]\n{}\nLABEL {}", label, bit, inner, label))
+        }
+        gate => gate_line(gate),
+    }
+}
+
+/// exp(-i*angle/2 * P⊗P) where `into` maps P to Z and `back` undoes it
+fn ising_lines(qubit1: usize, qubit2: usize, angle: f64, into: &str, back: &str) -> String {
+    let mut lines = Vec::new();
+    if !into.is_empty() {
+        lines.push(format!("{} {}\n{} {}", into, qubit1, into, qubit2));
+    }
+    lines.push(format!("CNOT {} {}\nRZ({}) {}\nCNOT {} {}", qubit1, qubit2, angle, qubit2, qubit1, qubit2));
+    if !back.is_empty() {
+        lines.push(format!("{} {}\n{} {}", back, qubit1, back, qubit2));
+    }
+    lines.join("\n")
+}
+
+/// Quil statement for one gate
+fn gate_line(gate: &QuantumGate) -> Result<String, String> {
+    let line = match gate {
+        QuantumGate::Hadamard { qubit } => format!("H {}", qubit),
+        QuantumGate::PauliX { qubit } => format!("X {}", qubit),
+        QuantumGate::PauliY { qubit } => format!("Y {}", qubit),
+        QuantumGate::PauliZ { qubit } => format!("Z {}", qubit),
+        QuantumGate::S { qubit } => format!("S {}", qubit),
+        QuantumGate::Sdg { qubit } => format!("DAGGER S {}", qubit),
+        QuantumGate::T { qubit } => format!("T {}", qubit),
+        QuantumGate::Tdg { qubit } => format!("DAGGER T {}", qubit),
+        // Quil has no SX; RX(+-pi/2) matches it up to global phase
+        QuantumGate::SX { qubit } => format!("RX(pi/2) {}", qubit),
+        QuantumGate::SXdg { qubit } => format!("RX(-pi/2) {}", qubit),
+        QuantumGate::Phase { qubit, angle } => format!("PHASE({}) {}", angle, qubit),
+        QuantumGate::CNOT { control, target } => format!("CNOT {} {}", control, target),
+        QuantumGate::CZ { control, target } => format!("CZ {} {}", control, target),
+        QuantumGate::CPhase { control, target, angle } => {
+            format!("CPHASE({}) {} {}", angle, control, target)
+        }
+        QuantumGate::SWAP { qubit1, qubit2 } => format!("SWAP {} {}", qubit1, qubit2),
+        QuantumGate::ISWAP { qubit1, qubit2 } => format!("ISWAP {} {}", qubit1, qubit2),
+        // XY(theta) is iSWAP at pi, so its square root is XY(pi/2)
+        QuantumGate::SqrtISWAP { qubit1, qubit2 } => format!("XY(pi/2) {} {}", qubit1, qubit2),
+        // Quil has no Ising gates; CNOT RZ CNOT in a rotated basis is exact
+        QuantumGate::RXX { qubit1, qubit2, angle } => ising_lines(*qubit1, *qubit2, *angle, "H", "H"),
+        QuantumGate::RYY { qubit1, qubit2, angle } => ising_lines(*qubit1, *qubit2, *angle, "RX(pi/2)", "RX(-pi/2)"),
+        QuantumGate::RZZ { qubit1, qubit2, angle } => ising_lines(*qubit1, *qubit2, *angle, "", ""),
+        QuantumGate::Toffoli { control1, control2, target } => {
+            format!("CCNOT {} {} {}", control1, control2, target)
+        }
+        QuantumGate::MCX { controls, target } => {
+            let qubits: Vec<String> = controls
+                .iter()
+                .chain(std::iter::once(target))
+                .map(|q| q.to_string())
+                .collect();
+            format!("{}X {}", "CONTROLLED ".repeat(controls.len()), qubits.join(" "))
+        }
+        QuantumGate::RotationX { qubit, angle } => format!("RX({}) {}", angle, qubit),
+        QuantumGate::RotationY { qubit, angle } => format!("RY({}) {}", angle, qubit),
+        QuantumGate::RotationZ { qubit, angle } => format!("RZ({}) {}", angle, qubit),
+        // Quil has no U3; RZ(phi) RY(theta) RZ(lambda) matches it up to global phase
+        QuantumGate::U { qubit, theta, phi, lambda } => {
+            format!("RZ({}) {}\nRY({}) {}\nRZ({}) {}", lambda, qubit, theta, qubit, phi, qubit)
+        }
+        QuantumGate::Unitary1Q { qubit, matrix } => {
+            let (theta, phi, lambda) = qsim::u3_angles(matrix);
+            format!("RZ({}) {}\nRY({}) {}\nRZ({}) {}", lambda, qubit, theta, qubit, phi, qubit)
+        }
+        QuantumGate::UnitaryNQ { qubits, .. } => {
+            return Err(format!("Quil export does not support a dense {}-qubit unitary", qubits.len()));
+        }
+        QuantumGate::GlobalHadamard { qubits } => {
+            qubits.iter().map(|q| format!("H {}", q)).collect::<Vec<_>>().join("\n")
+        }
+        QuantumGate::GlobalRotation { qubits, axis, angle } => {
+            let name = match axis {
+                RotationAxis::X => "RX",
+                RotationAxis::Y => "RY",
+                RotationAxis::Z => "RZ",
+            };
+            qubits.iter().map(|q| format!("{}({}) {}", name, angle, q)).collect::<Vec<_>>().join("\n")
+        }
+        QuantumGate::Permutation { qubits, pattern } => qsim::permutation_swaps(qubits, pattern)
+            .iter()
+            .map(|(q1, q2)| format!("SWAP {} {}", q1, q2))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        // Z-string rotations, exact up to global phase
+        QuantumGate::Diagonal { qubits, phases } => qsim::diagonal_gates(qubits, phases)
+            .iter()
+            .map(gate_line)
+            .collect::<Result<Vec<_>, _>>()?
+            .join("\n"),
+        // Quil has no ECR; its defining RZX(pi/4), X, RZX(-pi/4) sequence is exact
+        QuantumGate::ECR { qubit1, qubit2 } => qsim::ecr_gates(*qubit1, *qubit2)
+            .iter()
+            .map(gate_line)
+            .collect::<Result<Vec<_>, _>>()?
+            .join("\n"),
+        // Quil has no Givens rotation; its two commuting Pauli rotations are exact
+        QuantumGate::Givens { qubit1, qubit2, angle } => qsim::givens_gates(*qubit1, *qubit2, *angle)
+            .iter()
+            .map(gate_line)
+            .collect::<Result<Vec<_>, _>>()?
+            .join("\n"),
+        // Exact: basis changes, a CNOT parity ladder and one RZ
+        QuantumGate::PauliRotation { qubits, pauli, angle } => qsim::pauli_rotation_gates(qubits, pauli, *angle)
+            .iter()
+            .map(gate_line)
+            .collect::<Result<Vec<_>, _>>()?
+            .join("\n"),
+        // CONTROLLED takes the control as an extra leading operand
+        QuantumGate::Controlled { control, gate } => {
+            // RX(+-pi/2) matches SX only up to a global phase, which a control would expose
+            if let QuantumGate::SX { .. } | QuantumGate::SXdg { .. } = **gate {
+                return Err("Quil has no exact controlled SX".to_string());
+            }
+            if let QuantumGate::Diagonal { .. } = **gate {
+                return Err("Quil has no exact controlled Diagonal".to_string());
+            }
+            if let QuantumGate::Reset { .. } = **gate {
+                return Err("Quil cannot control a RESET".to_string());
+            }
+            let inner = gate_line(gate)?;
+            if inner.contains('\n') {
+                return Err("Quil export cannot control a gate written as several instructions".to_string());
+            }
+            let tokens: Vec<&str> = inner.split_whitespace().collect();
+            let first_qubit = tokens
+                .iter()
+                .rposition(|token| token.parse::<usize>().is_err())
+                .map_or(0, |i| i + 1);
+            format!(
+                "CONTROLLED {} {} {}",
+                tokens[..first_qubit].join(" "),
+                control,
+                tokens[first_qubit..].join(" ")
+            )
+        }
+        QuantumGate::Measurement { qubit, bit } => format!("MEASURE {} ro[{}]", qubit, bit.unwrap_or(*qubit)),
+        QuantumGate::Reset { qubit } => format!("RESET {}", qubit),
+        // Kept as a comment for readers; import skips it
+        QuantumGate::Snapshot { label } => format!("# snapshot {}", label),
+        // Quil has no way to annotate noise, so only the gate itself is written
+        QuantumGate::Noisy { gate, .. } => return gate_line(gate),
+        QuantumGate::IfBit { .. } => return Err("Quil cannot control a classically conditioned gate".to_string()),
+    };
+    Ok(line)
+}
+
+/// Parse Quil source into a circuit
+pub fn parse_quil(source: &str) -> Result<QuantumCircuit, String> {
+    let mut metadata = BTreeMap::new();
+    let mut num_qubits = 0;
+    let mut declared = 0;
+    let mut gates = Vec::new();
+    // Readout bits measured into so far, and the open JUMP-UNLESS blocks as
+    // (label, bit, index of the first gate they skip)
+    let mut measured_into: BTreeSet<usize> = BTreeSet::new();
+    let mut skips: Vec<(String, usize, usize)> = Vec::new();
+
+    for line in source.lines() {
+        let (line, comment) = match line.find('#') {
+            Some(pos) => (&line[..pos], Some(&line[pos + 1..])),
+            None => (line, None),
+        };
+        if let Some((key, value)) = comment.and_then(parse_metadata_comment) {
+            metadata.insert(key, value);
+        }
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("PRAGMA") {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("JUMP-UNLESS ") {
+            let mut operands = rest.split_whitespace();
+            let label = operands.next().unwrap_or("");
+            let readout = operands.next().and_then(readout_index);
+            let bit = readout
+                .filter(|readout| measured_into.contains(readout))
+                .ok_or_else(|| format!("'{}' does not test a measured ro bit", line))?;
+            skips.push((label.to_string(), bit, gates.len()));
+            continue;
+        }
+        if let Some(label) = line.strip_prefix("LABEL ") {
+            match skips.pop() {
+                Some((open, bit, start)) if open == label.trim() => {
+                    let skipped: Vec<QuantumGate> = gates.drain(start..).collect();
+                    gates.extend(skipped.into_iter().map(|gate| QuantumGate::IfBit { bit, gate: Box::new(gate) }));
+                }
+                _ => return Err(format!("'{}' does not close the innermost JUMP-UNLESS", line)),
+            }
+            continue;
+        }
+        // Each CONTROLLED modifier takes one more leading operand as a control
+        let (mut dagger, mut controls, mut line) = (false, 0, line);
+        loop {
+            if let Some(rest) = line.strip_prefix("DAGGER ") {
+                dagger = !dagger;
+                line = rest.trim_start();
+            } else if let Some(rest) = line.strip_prefix("CONTROLLED ") {
+                controls += 1;
+                line = rest.trim_start();
+            } else {
+                break;
+            }
+        }
+
+        // Parameter lists may contain spaces, so split after the closing parenthesis
+        let split_at = match (line.find('('), line.find(char::is_whitespace)) {
+            (Some(open), Some(space)) if open < space => {
+                line.find(')').map(|close| close + 1).unwrap_or(line.len())
+            }
+            (_, Some(space)) => space,
+            _ => line.len(),
+        };
+        let (head, rest) = (&line[..split_at], line[split_at..].trim());
+
+        if head == "DECLARE" {
+            // The readout register is written with one bit per qubit, or wider to fit the bits measured into
+            if let Some(size) = rest
+                .split(['[', ']'])
+                .nth(1)
+                .and_then(|size| size.trim().parse::<usize>().ok())
+            {
+                declared = declared.max(size);
+            }
+            continue;
+        }
+
+        let (name, angle) = match head.find('(') {
+            Some(open) => (&head[..open], Some(eval_angle(head[open + 1..head.len() - 1].trim())?)),
+            None => (head, None),
+        };
+        let operands: Vec<&str> = rest.split_whitespace().collect();
+        let operand = |i: usize| -> Result<usize, String> {
+            operands
+                .get(i)
+                .and_then(|q| q.parse::<usize>().ok())
+                .ok_or_else(|| format!("invalid qubit operand in '{}'", line))
+        };
+        // Gate operands follow the control operands
+        let qubit = |i: usize| operand(controls + i);
+        let angle = || angle.ok_or_else(|| format!("missing angle in '{}'", line));
+
+        if controls > 0 && name == "X" && !dagger {
+            let qubits = (0..=controls).map(operand).collect::<Result<Vec<_>, _>>()?;
+            let gate = QuantumGate::MCX { controls: qubits[..controls].to_vec(), target: qubits[controls] };
+            for q in gate.qubits() {
+                num_qubits = num_qubits.max(q + 1);
+            }
+            gates.push(gate);
+            continue;
+        }
+
+        let mut gate = match (name, dagger) {
+            ("S", false) => QuantumGate::S { qubit: qubit(0)? },
+            ("S", true) => QuantumGate::Sdg { qubit: qubit(0)? },
+            ("T", false) => QuantumGate::T { qubit: qubit(0)? },
+            ("T", true) => QuantumGate::Tdg { qubit: qubit(0)? },
+            (_, true) => return Err(format!("DAGGER is only supported for S and T, not '{}'", name)),
+            ("H", _) => QuantumGate::Hadamard { qubit: qubit(0)? },
+            ("X", _) => QuantumGate::PauliX { qubit: qubit(0)? },
+            ("Y", _) => QuantumGate::PauliY { qubit: qubit(0)? },
+            ("Z", _) => QuantumGate::PauliZ { qubit: qubit(0)? },
+            ("PHASE", _) => QuantumGate::Phase { qubit: qubit(0)?, angle: angle()? },
+            ("RX", _) => QuantumGate::RotationX { qubit: qubit(0)?, angle: angle()? },
+            ("RY", _) => QuantumGate::RotationY { qubit: qubit(0)?, angle: angle()? },
+            ("RZ", _) => QuantumGate::RotationZ { qubit: qubit(0)?, angle: angle()? },
+            ("CNOT", _) => QuantumGate::CNOT { control: qubit(0)?, target: qubit(1)? },
+            ("CZ", _) => QuantumGate::CZ { control: qubit(0)?, target: qubit(1)? },
+            ("CPHASE", _) => QuantumGate::CPhase { control: qubit(0)?, target: qubit(1)?, angle: angle()? },
+            ("SWAP", _) => QuantumGate::SWAP { qubit1: qubit(0)?, qubit2: qubit(1)? },
+            ("ISWAP", _) => QuantumGate::ISWAP { qubit1: qubit(0)?, qubit2: qubit(1)? },
+            ("XY", _) => {
+                let turns = angle()? / std::f64::consts::PI;
+                if (turns - 1.0).abs() < 1e-9 {
+                    QuantumGate::ISWAP { qubit1: qubit(0)?, qubit2: qubit(1)? }
+                } else if (turns - 0.5).abs() < 1e-9 {
+                    QuantumGate::SqrtISWAP { qubit1: qubit(0)?, qubit2: qubit(1)? }
+                } else {
+                    return Err(format!("only XY(pi) and XY(pi/2) are supported, in '{}'", line));
+                }
+            }
+            ("CCNOT", _) => QuantumGate::Toffoli {
+                control1: qubit(0)?,
+                control2: qubit(1)?,
+                target: qubit(2)?,
+            },
+            ("MEASURE", _) => {
+                let measured = qubit(0)?;
+                let readout = operands.get(1).and_then(|address| readout_index(address));
+                if let Some(readout) = readout {
+                    measured_into.insert(readout);
+                }
+                QuantumGate::Measurement { qubit: measured, bit: readout.filter(|&bit| bit != measured) }
+            }
+            ("RESET", _) => QuantumGate::Reset { qubit: qubit(0)? },
+            _ => return Err(format!("unsupported Quil instruction '{}'", name)),
+        };
+        // The outermost CONTROLLED owns the first operand
+        for i in (0..controls).rev() {
+            gate = QuantumGate::Controlled { control: operand(i)?, gate: Box::new(gate) };
+        }
+        for q in gate.qubits() {
+            num_qubits = num_qubits.max(q + 1);
+        }
+        gates.push(gate);
+    }
+    if let Some((label, ..)) = skips.pop() {
+        return Err(format!("JUMP-UNLESS {} has no matching LABEL", label));
+    }
+    let mut circuit = QuantumCircuit { num_qubits, gates, metadata };
+    // A register wider than the bits measured into it also covers idle trailing qubits
+    if declared > circuit.num_clbits() {
+        circuit.num_qubits = declared;
+    }
+
+    Ok(circuit)
+}
+
+/// Index `k` of a readout address `ro[k]`
+fn readout_index(address: &str) -> Option<usize> {
+    address.strip_prefix("ro[")?.strip_suffix(']')?.trim().parse().ok()
+}
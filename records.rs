@@ -0,0 +1,103 @@
+//! Records Module
+//! Bit-packed measurement records, streamed to and from disk in Stim's b8 format
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+/// Bytes per shot: each record is `ceil(width / 8)` bytes with classical bit k in bit
+/// k % 8 of byte k / 8, the layout Stim reads and writes with `--out_format b8`
+fn row_bytes(width: usize) -> usize {
+    width.div_ceil(8)
+}
+
+fn check_width(width: usize) -> io::Result<()> {
+    if width > usize::BITS as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("records of {} bits are wider than a {}-bit register", width, usize::BITS),
+        ));
+    }
+    Ok(())
+}
+
+/// Appends shot registers to a b8 stream, one fixed-size row per shot, without keeping them
+pub struct RecordWriter<W: Write> {
+    out: BufWriter<W>,
+    width: usize,
+    shots: usize,
+}
+
+impl RecordWriter<File> {
+    /// Create (or truncate) a records file
+    pub fn create(path: &str, width: usize) -> io::Result<Self> {
+        check_width(width)?;
+        Ok(Self::new(File::create(path)?, width))
+    }
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// Wrap a writer; `width` is the number of classical bits per shot (at most `usize::BITS`)
+    pub fn new(out: W, width: usize) -> Self {
+        Self { out: BufWriter::new(out), width, shots: 0 }
+    }
+
+    /// Write one shot's register, bit k holding classical bit k
+    pub fn push(&mut self, register: usize) -> io::Result<()> {
+        self.out.write_all(&register.to_le_bytes()[..row_bytes(self.width)])?;
+        self.shots += 1;
+        Ok(())
+    }
+
+    /// Flush buffered rows and return the number of shots written
+    pub fn finish(mut self) -> io::Result<usize> {
+        self.out.flush()?;
+        Ok(self.shots)
+    }
+}
+
+/// Iterates over the registers of a b8 stream, one row at a time
+pub struct RecordReader<R: Read> {
+    input: R,
+    row: Vec<u8>,
+}
+
+impl RecordReader<BufReader<File>> {
+    /// Open a records file written with `width` classical bits per shot
+    pub fn open(path: &str, width: usize) -> io::Result<Self> {
+        check_width(width)?;
+        Ok(Self::new(BufReader::new(File::open(path)?), width))
+    }
+}
+
+impl<R: Read> RecordReader<R> {
+    /// Wrap a reader of records `width` bits wide (at most `usize::BITS`)
+    pub fn new(input: R, width: usize) -> Self {
+        Self { input, row: vec![0; row_bytes(width)] }
+    }
+}
+
+impl<R: Read> Iterator for RecordReader<R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Zero-width records take no bytes, so there is no way to count them
+        if self.row.is_empty() {
+            return None;
+        }
+        let mut filled = 0;
+        while filled < self.row.len() {
+            match self.input.read(&mut self.row[filled..]) {
+                Ok(0) if filled == 0 => return None,
+                Ok(0) => {
+                    return Some(Err(io::Error::new(io::ErrorKind::UnexpectedEof, "records end in a partial shot")));
+                }
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        let mut bytes = [0u8; std::mem::size_of::<usize>()];
+        bytes[..self.row.len()].copy_from_slice(&self.row);
+        Some(Ok(usize::from_le_bytes(bytes)))
+    }
+}
@@ -0,0 +1,334 @@
+//! Results Module
+//! Shot sampling and machine-readable result formats
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use rand::Rng;
+use serde::ser::{SerializeMap, SerializeStruct, Serializer};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::archive::ArchiveRun;
+use crate::cli;
+use crate::manifest::Manifest;
+use crate::proto;
+use crate::qsim::{QuantumCircuit, Shot};
+use crate::records::RecordReader;
+
+/// Output format for simulation results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultFormat {
+    Probabilities,
+    Forest,
+    Memory,
+    Counts,
+}
+
+impl ResultFormat {
+    /// Parse a format name as given on the command line
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "probabilities" => Some(ResultFormat::Probabilities),
+            "forest" | "pyquil" => Some(ResultFormat::Forest),
+            "memory" => Some(ResultFormat::Memory),
+            "counts" => Some(ResultFormat::Counts),
+            _ => None,
+        }
+    }
+}
+
+/// Draw basis-state indices from a probability distribution
+pub fn sample_outcomes<R: Rng>(probabilities: &[f64], shots: usize, rng: &mut R) -> Vec<usize> {
+    let mut cumulative = Vec::with_capacity(probabilities.len());
+    let mut total = 0.0;
+    for p in probabilities {
+        total += p;
+        cumulative.push(total);
+    }
+
+    (0..shots)
+        .map(|_| {
+            let r = rng.gen::<f64>() * total;
+            cumulative
+                .partition_point(|&c| c <= r)
+                .min(probabilities.len() - 1)
+        })
+        .collect()
+}
+
+/// Qubits read out by a circuit: its measured qubits, or all qubits if it has no measurements
+pub fn measured_qubits(circuit: &QuantumCircuit) -> Vec<usize> {
+    let mut measured: Vec<usize> = circuit.readout().into_iter().map(|(qubit, _)| qubit).collect();
+    measured.sort_unstable();
+    measured.dedup();
+    measured
+}
+
+/// pyQuil/Forest readout: `{"ro": [[bit; num_clbits]; shots]}` with classical bit `b` in `ro[b]`
+pub fn forest_readout(circuit: &QuantumCircuit, shots: &[Shot]) -> Value {
+    let rows: Vec<Vec<u8>> = shots
+        .iter()
+        .map(|shot| (0..circuit.num_clbits()).map(|b| ((shot.register >> b) & 1) as u8).collect())
+        .collect();
+    json!({ "ro": rows })
+}
+
+/// Qiskit-style memory: the register of every shot as a bit string with bit 0 rightmost,
+/// plus the qubit each classical bit was measured from
+pub fn memory_readout(circuit: &QuantumCircuit, shots: &[Shot]) -> Value {
+    let width = circuit.num_clbits();
+    let memory: Vec<String> = shots
+        .iter()
+        .map(|shot| format!("{:0width$b}", shot.register, width = width))
+        .collect();
+    let classical_bits: Vec<Value> = circuit
+        .readout()
+        .iter()
+        .map(|&(qubit, bit)| json!({ "qubit": qubit, "bit": bit }))
+        .collect();
+    json!({ "classical_bits": classical_bits, "memory": memory })
+}
+
+/// Qiskit-style counts: each classical register value as a bit string with bit 0 rightmost,
+/// mapped to how often it was read, most frequent first
+pub struct Counts {
+    width: usize,
+    counts: Vec<(usize, usize)>,
+}
+
+impl Counts {
+    /// Order a tally of `width`-bit registers by frequency, then by value
+    pub fn new(width: usize, registers: &BTreeMap<usize, usize>) -> Self {
+        let mut counts: Vec<(usize, usize)> = registers.iter().map(|(&register, &count)| (register, count)).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        Self { width, counts }
+    }
+}
+
+/// Written as `{"counts": {"0110": 523, ...}, "shots": n}`, keeping the frequency order
+/// that a `Value` map would lose
+impl Serialize for Counts {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Ordered<'a>(&'a Counts);
+        impl Serialize for Ordered<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut map = serializer.serialize_map(Some(self.0.counts.len()))?;
+                for &(register, count) in &self.0.counts {
+                    map.serialize_entry(&format!("{:0width$b}", register, width = self.0.width), &count)?;
+                }
+                map.end()
+            }
+        }
+        let mut outer = serializer.serialize_struct("Counts", 2)?;
+        outer.serialize_field("counts", &Ordered(self))?;
+        outer.serialize_field("shots", &self.counts.iter().map(|&(_, count)| count).sum::<usize>())?;
+        outer.end()
+    }
+}
+
+/// Counts of noisy shots beside those of the same circuit without noise, for readout-error
+/// mitigation studies
+#[derive(Serialize)]
+pub struct ComparedCounts {
+    pub noisy: Counts,
+    pub ideal: Counts,
+}
+
+/// Widest Forest readout turned back into a dense distribution
+const MAX_READOUT_QUBITS: usize = 30;
+
+/// Results file written by `--output` and read back by `show`
+#[derive(Serialize, Deserialize)]
+pub struct SavedResults {
+    pub num_qubits: usize,
+    pub probabilities: Vec<f64>,
+    /// Shots behind an empirical distribution rebuilt from sampled readout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shots: Option<usize>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    pub timings: Vec<Timing>,
+    /// Build and environment the run came from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<Manifest>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Timing {
+    pub phase: String,
+    pub seconds: f64,
+}
+
+impl From<&ArchiveRun> for SavedResults {
+    fn from(run: &ArchiveRun) -> Self {
+        Self {
+            num_qubits: run.probabilities.len().trailing_zeros() as usize,
+            probabilities: run.probabilities.clone(),
+            shots: None,
+            metadata: run.metadata.clone(),
+            timings: run
+                .timings
+                .iter()
+                .map(|(phase, elapsed)| Timing { phase: phase.clone(), seconds: elapsed.as_secs_f64() })
+                .collect(),
+            manifest: Some(run.manifest.clone()),
+        }
+    }
+}
+
+pub fn save_results(path: &str, results: &SavedResults) -> Result<(), Box<dyn Error>> {
+    fs::write(path, cli::to_json_pretty(results)?)?;
+    Ok(())
+}
+
+/// Load a results file: `--output` files, API result or simulate responses, Forest and memory readout, or Qiskit-style counts
+pub fn load_results(path: &str) -> Result<SavedResults, Box<dyn Error>> {
+    let value: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if let Some(rows) = value.get("ro") {
+        return Ok(from_readout(serde_json::from_value(rows.clone())?)?);
+    }
+    if let Some(memory) = value.get("memory").filter(|_| value.get("probabilities").is_none()) {
+        let memory: Vec<String> = serde_json::from_value(memory.clone())?;
+        let rows = memory
+            .iter()
+            .map(|register| register.chars().rev().map(|c| (c == '1') as u8).collect())
+            .collect();
+        return Ok(from_readout(rows)?);
+    }
+
+    if let Some(counts) = value.get("counts").filter(|_| value.get("probabilities").is_none()) {
+        return Ok(from_counts(serde_json::from_value(counts.clone())?)?);
+    }
+
+    let probabilities: Vec<f64> = match value.get("probabilities") {
+        Some(probabilities) => serde_json::from_value(probabilities.clone())?,
+        None => return Err("no probabilities or readout found".into()),
+    };
+    if !probabilities.len().is_power_of_two() {
+        return Err(format!("{} probabilities is not a power of two", probabilities.len()).into());
+    }
+    let mut metadata: BTreeMap<String, String> = match value.get("metadata") {
+        Some(metadata) => serde_json::from_value(metadata.clone())?,
+        None => BTreeMap::new(),
+    };
+    // Catalog fields of a result fetched from the API server
+    for key in ["id", "name", "circuit_id", "created_at"] {
+        match value.get(key) {
+            Some(Value::String(text)) => metadata.insert(key.to_string(), text.clone()),
+            Some(Value::Number(number)) => metadata.insert(key.to_string(), number.to_string()),
+            _ => None,
+        };
+    }
+    if let Some(tags) = value.get("tags").and_then(|tags| tags.as_array()).filter(|tags| !tags.is_empty()) {
+        let tags: Vec<&str> = tags.iter().filter_map(|tag| tag.as_str()).collect();
+        metadata.insert("tags".to_string(), tags.join(","));
+    }
+
+    Ok(SavedResults {
+        num_qubits: probabilities.len().trailing_zeros() as usize,
+        probabilities,
+        shots: value.get("shots").and_then(|shots| shots.as_u64()).map(|shots| shots as usize),
+        metadata,
+        timings: match value.get("timings") {
+            Some(timings) => serde_json::from_value(timings.clone())?,
+            None => Vec::new(),
+        },
+        manifest: match value.get("manifest") {
+            Some(manifest) => Some(serde_json::from_value(manifest.clone())?),
+            None => None,
+        },
+    })
+}
+
+/// Empirical distribution of a bit-packed records file (see `records`) `width` bits wide,
+/// streamed one shot at a time
+/// Probabilities saved from a protobuf `MeasurementResult`, as the API server returns them
+/// for `Accept: application/x-protobuf`
+pub fn load_protobuf_results(path: &str) -> Result<SavedResults, Box<dyn Error>> {
+    let (num_qubits, probabilities) = proto::decode_results(&fs::read(path)?)?;
+    if 1usize.checked_shl(num_qubits as u32) != Some(probabilities.len()) {
+        return Err(format!("{} probabilities do not fit {} qubits", probabilities.len(), num_qubits).into());
+    }
+    Ok(SavedResults {
+        num_qubits,
+        probabilities,
+        shots: None,
+        metadata: BTreeMap::new(),
+        timings: Vec::new(),
+        manifest: None,
+    })
+}
+
+pub fn load_records(path: &str, width: usize) -> Result<SavedResults, Box<dyn Error>> {
+    if width > MAX_READOUT_QUBITS {
+        return Err(format!("records of {} bits are too wide to histogram", width).into());
+    }
+    let mut counts = vec![0usize; 1 << width];
+    let mut shots = 0;
+    for register in RecordReader::open(path, width)? {
+        counts[register?] += 1;
+        shots += 1;
+    }
+    if shots == 0 {
+        return Err("records file has no shots".into());
+    }
+    Ok(SavedResults {
+        num_qubits: width,
+        probabilities: counts.into_iter().map(|count| count as f64 / shots as f64).collect(),
+        shots: Some(shots),
+        metadata: BTreeMap::new(),
+        timings: Vec::new(),
+        manifest: None,
+    })
+}
+
+/// Empirical distribution of Qiskit-style counts, keyed by bit strings with bit 0 rightmost
+fn from_counts(counts: BTreeMap<String, usize>) -> Result<SavedResults, String> {
+    let num_qubits = counts.keys().next().map(|register| register.len()).ok_or("counts are empty")?;
+    if counts.keys().any(|register| register.len() != num_qubits) {
+        return Err("count keys have different lengths".to_string());
+    }
+    if num_qubits > MAX_READOUT_QUBITS {
+        return Err(format!("counts of {} bits are too wide to histogram", num_qubits));
+    }
+    let shots: usize = counts.values().sum();
+    let mut probabilities = vec![0.0; 1 << num_qubits];
+    for (register, &count) in &counts {
+        let outcome = usize::from_str_radix(register, 2).map_err(|_| format!("'{}' is not a bit string", register))?;
+        probabilities[outcome] += count as f64 / shots.max(1) as f64;
+    }
+    Ok(SavedResults {
+        num_qubits,
+        probabilities,
+        shots: Some(shots),
+        metadata: BTreeMap::new(),
+        timings: Vec::new(),
+        manifest: None,
+    })
+}
+
+/// Empirical distribution of per-shot registers, reading bit `q` of each row as qubit `q`
+fn from_readout(rows: Vec<Vec<u8>>) -> Result<SavedResults, String> {
+    let num_qubits = rows.first().map(|row| row.len()).ok_or("readout has no shots")?;
+    if rows.iter().any(|row| row.len() != num_qubits) {
+        return Err("readout rows have different lengths".to_string());
+    }
+    if num_qubits > MAX_READOUT_QUBITS {
+        return Err(format!("readout of {} qubits is too wide to histogram", num_qubits));
+    }
+    let mut probabilities = vec![0.0; 1 << num_qubits];
+    for row in &rows {
+        let outcome = row.iter().enumerate().fold(0, |acc, (q, &bit)| acc | ((bit as usize & 1) << q));
+        probabilities[outcome] += 1.0 / rows.len() as f64;
+    }
+    Ok(SavedResults {
+        num_qubits,
+        probabilities,
+        shots: Some(rows.len()),
+        metadata: BTreeMap::new(),
+        timings: Vec::new(),
+        // pyQuil readout has nowhere to carry one
+        manifest: None,
+    })
+}
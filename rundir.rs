@@ -0,0 +1,134 @@
+//! Run Directory Module
+//! Self-contained artifact layout for one simulation, created with `--run-dir`
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::cli::Flags;
+
+/// Snapshot of how a run was started, enough to start it again
+pub const CONFIG_FILE: &str = "config.json";
+
+/// Simulator configuration copied into the run when present in the working directory
+const SIMULATOR_CONFIG: &str = "config.toml";
+
+/// Options that describe where a run is written rather than what it computes
+const LOCATION_OPTIONS: &[&str] = &["run-dir"];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunConfig {
+    pub command: String,
+    /// Circuit copy, relative to the run directory
+    pub circuit: String,
+    /// Path the circuit was originally loaded from
+    pub source: String,
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+    #[serde(default)]
+    pub switches: Vec<String>,
+    pub version: String,
+    /// Unix time the run started, in seconds
+    pub created_at: u64,
+}
+
+impl RunConfig {
+    /// Command arguments that repeat the run on its saved circuit copy
+    pub fn args(&self, dir: &Path) -> Vec<String> {
+        let mut args = vec![dir.join(&self.circuit).to_string_lossy().into_owned()];
+        for (name, value) in &self.options {
+            args.push(format!("--{}", name));
+            args.push(value.clone());
+        }
+        args.extend(self.switches.iter().map(|name| format!("--{}", name)));
+        args
+    }
+}
+
+/// An open run directory:
+/// `config.json`, `circuit.<ext>`, `config.toml`, `logs/`, `results/` and `checkpoints/`
+pub struct RunDir {
+    root: PathBuf,
+    log: File,
+}
+
+impl RunDir {
+    /// Lay out a new run directory; an existing run is never overwritten
+    pub fn create(root: &str, command: &str, circuit_path: &str, flags: &Flags) -> Result<Self, Box<dyn Error>> {
+        let root = PathBuf::from(root);
+        if root.join(CONFIG_FILE).exists() {
+            return Err(format!("{} already holds a run", root.display()).into());
+        }
+        for dir in ["logs", "results", "checkpoints"] {
+            fs::create_dir_all(root.join(dir))?;
+        }
+
+        let circuit = match Path::new(circuit_path).extension() {
+            Some(extension) => format!("circuit.{}", extension.to_string_lossy()),
+            None => "circuit".to_string(),
+        };
+        fs::copy(circuit_path, root.join(&circuit))?;
+        if Path::new(SIMULATOR_CONFIG).is_file() {
+            fs::copy(SIMULATOR_CONFIG, root.join(SIMULATOR_CONFIG))?;
+        }
+
+        let config = RunConfig {
+            command: command.to_string(),
+            circuit,
+            source: circuit_path.to_string(),
+            options: flags
+                .options()
+                .filter(|(name, _)| !LOCATION_OPTIONS.contains(name))
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            switches: flags.switches().map(str::to_string).collect(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: unix_now(),
+        };
+        fs::write(root.join(CONFIG_FILE), serde_json::to_string_pretty(&config)?)?;
+
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(root.join("logs").join(format!("{}.log", command)))?;
+        let mut run = Self { root, log };
+        run.log(&format!("{} {} (QuantumMesh v{})", command, circuit_path, config.version));
+        Ok(run)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Append a timestamped line to the run log; logging never fails the run
+    pub fn log(&mut self, message: &str) {
+        let _ = writeln!(self.log, "[{}] {}", unix_now(), message);
+    }
+
+    /// Path of a file under `results/`
+    pub fn result_path(&self, name: &str) -> PathBuf {
+        self.root.join("results").join(name)
+    }
+
+    /// State vector file for a checkpoint taken after `gates` gates
+    pub fn checkpoint_path(&self, gates: usize) -> PathBuf {
+        self.root.join("checkpoints").join(format!("gate-{:06}.npy", gates))
+    }
+}
+
+/// Read the config snapshot of an existing run directory
+pub fn load_config(dir: &Path) -> Result<RunConfig, Box<dyn Error>> {
+    let contents = fs::read_to_string(dir.join(CONFIG_FILE))
+        .map_err(|e| format!("{} is not a run directory: {}", dir.display(), e))?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
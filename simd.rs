@@ -0,0 +1,108 @@
+//! SIMD Module
+//! AVX amplitude updates, two adjacent amplitudes per 256-bit register
+
+use crate::gpu_ops::Complex;
+
+/// Whether the CPU runs the AVX kernels; callers fall back to scalar kernels otherwise
+pub fn available() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        std::arch::is_x86_feature_detected!("avx")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+/// `[a, b] → m · [a, b]` for the amplitude pairs `(a[0], b[0])` and `(a[1], b[1])`
+///
+/// # Safety
+/// `a` and `b` must each point to two valid amplitudes that no other thread touches, and
+/// `available()` must hold
+pub unsafe fn butterfly(a: *mut Complex, b: *mut Complex, m: &[[Complex; 2]; 2]) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        avx::butterfly(a, b, m)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    for k in 0..2 {
+        let (x, y) = (*a.add(k), *b.add(k));
+        *a.add(k) = m[0][0] * x + m[0][1] * y;
+        *b.add(k) = m[1][0] * x + m[1][1] * y;
+    }
+}
+
+/// `butterfly` for a real matrix, as for H and RY, at half the multiplies
+///
+/// # Safety
+/// As for `butterfly`
+pub unsafe fn real_butterfly(a: *mut Complex, b: *mut Complex, m: &[[f64; 2]; 2]) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        avx::real_butterfly(a, b, m)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    for k in 0..2 {
+        let (x, y) = (*a.add(k), *b.add(k));
+        *a.add(k) = Complex::new(m[0][0] * x.re + m[0][1] * y.re, m[0][0] * x.im + m[0][1] * y.im);
+        *b.add(k) = Complex::new(m[1][0] * x.re + m[1][1] * y.re, m[1][0] * x.im + m[1][1] * y.im);
+    }
+}
+
+/// Multiply the two amplitudes at `a` by `factor`
+///
+/// # Safety
+/// As for `butterfly`
+pub unsafe fn scale(a: *mut Complex, factor: Complex) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        avx::scale(a, factor)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    for k in 0..2 {
+        *a.add(k) = factor * *a.add(k);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx {
+    use std::arch::x86_64::*;
+    use crate::gpu_ops::Complex;
+
+    // Complex is #[repr(C)], so two amplitudes load as re0, im0, re1, im1
+
+    /// Both amplitudes in `x` times `w`: (re·w.re - im·w.im, im·w.re + re·w.im)
+    #[target_feature(enable = "avx")]
+    unsafe fn mul(x: __m256d, w: Complex) -> __m256d {
+        let swapped = _mm256_permute_pd::<0b0101>(x);
+        _mm256_addsub_pd(_mm256_mul_pd(x, _mm256_set1_pd(w.re)), _mm256_mul_pd(swapped, _mm256_set1_pd(w.im)))
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn butterfly(a: *mut Complex, b: *mut Complex, m: &[[Complex; 2]; 2]) {
+        let x = _mm256_loadu_pd(a as *const f64);
+        let y = _mm256_loadu_pd(b as *const f64);
+        _mm256_storeu_pd(a as *mut f64, _mm256_add_pd(mul(x, m[0][0]), mul(y, m[0][1])));
+        _mm256_storeu_pd(b as *mut f64, _mm256_add_pd(mul(x, m[1][0]), mul(y, m[1][1])));
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn real_butterfly(a: *mut Complex, b: *mut Complex, m: &[[f64; 2]; 2]) {
+        let x = _mm256_loadu_pd(a as *const f64);
+        let y = _mm256_loadu_pd(b as *const f64);
+        _mm256_storeu_pd(a as *mut f64, real_row(x, y, &m[0]));
+        _mm256_storeu_pd(b as *mut f64, real_row(x, y, &m[1]));
+    }
+
+    /// r[0]·x + r[1]·y
+    #[target_feature(enable = "avx")]
+    unsafe fn real_row(x: __m256d, y: __m256d, r: &[f64; 2]) -> __m256d {
+        _mm256_add_pd(_mm256_mul_pd(x, _mm256_set1_pd(r[0])), _mm256_mul_pd(y, _mm256_set1_pd(r[1])))
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn scale(a: *mut Complex, factor: Complex) {
+        _mm256_storeu_pd(a as *mut f64, mul(_mm256_loadu_pd(a as *const f64), factor));
+    }
+}
@@ -0,0 +1,422 @@
+//! Simulator Backend Module
+//! State representations `QuantumSimulator` runs on, chosen at runtime by name
+
+use std::borrow::Cow;
+use rand::{Rng, RngCore};
+use crate::disk::DiskStateVector;
+use crate::gpu_ops::{Complex, GpuStateVector};
+use crate::mps::{self, MpsState};
+use crate::qsim::{self, QuantumGate};
+use crate::results;
+
+/// Representation `QuantumSimulator::new` uses
+pub const DEFAULT_REPRESENTATION: &str = "gpu";
+
+/// Names accepted by `create`, with one-line summaries
+pub const REPRESENTATIONS: &[(&str, &str)] = &[
+    ("gpu", "dense state vector on the GPU kernels"),
+    ("cpu", "dense state vector on portable reference kernels"),
+    ("density", "2^n x 2^n density matrix, for mixed states"),
+    ("mps", "matrix product state with a capped bond dimension"),
+    ("disk", "dense state vector paged through a scratch file, for registers larger than memory"),
+];
+
+const ZERO: Complex = Complex { re: 0.0, im: 0.0 };
+const PAULI_X: [[Complex; 2]; 2] = [[ZERO, Complex { re: 1.0, im: 0.0 }], [Complex { re: 1.0, im: 0.0 }, ZERO]];
+
+/// A state representation: the kernels `QuantumSimulator` drives on physical qubits, after
+/// it has resolved measurements, classical control and qubit layout. Implementations only
+/// need dense matrix application, measurement and read-out; the per-gate methods have
+/// defaults built on those, which fast representations override.
+pub trait SimulatorBackend: Send + Sync {
+    /// Name given to `create`, e.g. `density`
+    fn name(&self) -> &'static str;
+
+    fn num_qubits(&self) -> usize;
+
+    /// Boxed copy, so simulators holding a representation can be cloned
+    fn clone_box(&self) -> Box<dyn SimulatorBackend>;
+
+    /// Apply a 2x2 matrix to one qubit
+    fn apply_matrix(&mut self, qubit: usize, matrix: &[[Complex; 2]; 2]);
+
+    /// Apply a 2^k x 2^k matrix to `qubits`, `qubits[0]` the least significant bit of its indices
+    fn apply_matrix_n(&mut self, qubits: &[usize], matrix: &[Vec<Complex>]);
+
+    /// Projective measurement of one qubit, returning whether it read 1
+    fn measure(&mut self, qubit: usize, rng: &mut dyn RngCore) -> bool;
+
+    /// Probability of every basis state
+    fn probabilities(&self) -> Vec<f64>;
+
+    /// The state vector; borrowed when the representation stores one
+    fn amplitudes(&self) -> Cow<'_, [Complex]>;
+
+    /// Probability that each of `qubits` reads 1, all in one pass over the state. The
+    /// default streams `amplitudes`, so it allocates nothing for representations that
+    /// store a state vector; the others override it or build the vector to read.
+    fn one_probabilities(&self, qubits: &[usize]) -> Vec<f64> {
+        let mut ones = vec![0.0; qubits.len()];
+        add_one_probabilities(&self.amplitudes(), 0, qubits, &mut ones);
+        ones
+    }
+
+    /// Apply a unitary gate on physical qubits. The default applies the gate's dense
+    /// matrix, splitting layers and Pauli rotations into small gates first.
+    fn apply_gate(&mut self, gate: &QuantumGate) {
+        if let Some(gates) = split(gate) {
+            for gate in &gates {
+                self.apply_gate(gate);
+            }
+            return;
+        }
+        let qubits = gate.qubits();
+        let matrix = qsim::gate_matrix(gate, &qubits);
+        match qubits[..] {
+            [qubit] => self.apply_matrix(qubit, &[[matrix[0][0], matrix[0][1]], [matrix[1][0], matrix[1][1]]]),
+            _ => self.apply_matrix_n(&qubits, &matrix),
+        }
+    }
+
+    /// Reset a qubit to |0>, returning whether it was found in |1>
+    fn reset(&mut self, qubit: usize, rng: &mut dyn RngCore) -> bool {
+        let outcome = self.measure(qubit, rng);
+        if outcome {
+            self.apply_matrix(qubit, &PAULI_X);
+        }
+        outcome
+    }
+
+    /// Apply a channel given by its Kraus operators on `qubits`. The default follows one
+    /// trajectory: it picks operator K with probability ‖Kψ‖² and applies K/‖Kψ‖, so runs
+    /// average to the channel. Mixed-state representations apply Σ KρK† instead.
+    fn apply_kraus(&mut self, qubits: &[usize], kraus: &[Vec<Vec<Complex>>], rng: &mut dyn RngCore) {
+        let mut draw = rng.gen::<f64>();
+        // Scaled unitaries, as in Pauli channels, have ‖Kψ‖² = w for every state, and a
+        // diagonal K†K, as in damping channels, only weighs the outcomes of `qubits`; either
+        // way no branch needs simulating to weigh it
+        let weights = kraus.iter().map(|operator| unitary_weight(operator)).collect::<Option<Vec<f64>>>().or_else(|| {
+            let diagonals = kraus.iter().map(|operator| gram_diagonal(operator)).collect::<Option<Vec<Vec<f64>>>>()?;
+            let marginal = marginal(&self.probabilities(), qubits);
+            Some(diagonals.iter().map(|diagonal| diagonal.iter().zip(&marginal).map(|(d, p)| d * p).sum()).collect())
+        });
+        if let Some(weights) = weights {
+            let k = weights
+                .iter()
+                .position(|&weight| {
+                    draw -= weight;
+                    draw < 0.0
+                })
+                .or_else(|| weights.iter().rposition(|&weight| weight > 0.0))
+                .unwrap_or(kraus.len() - 1);
+            if !is_scaled_identity(&kraus[k]) {
+                self.apply_matrix_n(qubits, &scaled(&kraus[k], weights[k]));
+            }
+            return;
+        }
+        for (k, operator) in kraus.iter().enumerate() {
+            let mut branch = self.clone_box();
+            branch.apply_matrix_n(qubits, operator);
+            let weight: f64 = branch.probabilities().iter().sum();
+            if draw < weight || k + 1 == kraus.len() {
+                self.apply_matrix_n(qubits, &scaled(operator, weight));
+                return;
+            }
+            draw -= weight;
+        }
+    }
+
+    /// Sample basis-state indices; the default draws from `probabilities`
+    fn sample(&self, shots: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        let mut rng = rng;
+        results::sample_outcomes(&self.probabilities(), shots, &mut rng)
+    }
+}
+
+impl Clone for Box<dyn SimulatorBackend> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// |0...0> in the named representation
+pub fn create(name: &str, num_qubits: usize) -> Result<Box<dyn SimulatorBackend>, String> {
+    match name {
+        "gpu" => Ok(Box::new(GpuStateVector::new(num_qubits))),
+        "cpu" => Ok(Box::new(CpuStateVector::new(num_qubits))),
+        "density" => Ok(Box::new(DensityMatrix::new(num_qubits))),
+        "mps" => Ok(Box::new(MpsState::new(num_qubits, mps::DEFAULT_MAX_BOND))),
+        "disk" => Ok(Box::new(DiskStateVector::new(num_qubits)?)),
+        _ => {
+            let names: Vec<&str> = REPRESENTATIONS.iter().map(|&(name, _)| name).collect();
+            Err(format!("unknown state representation '{}' (available: {})", name, names.join(", ")))
+        }
+    }
+}
+
+/// Smaller gates equivalent to a gate that spans many qubits but acts on few at a time,
+/// so no representation builds its 2^n x 2^n matrix
+pub fn split(gate: &QuantumGate) -> Option<Vec<QuantumGate>> {
+    match gate {
+        QuantumGate::GlobalHadamard { qubits } => Some(qubits.iter().map(|&qubit| QuantumGate::Hadamard { qubit }).collect()),
+        QuantumGate::GlobalRotation { qubits, axis, angle } if qubits.len() > 1 => Some(
+            qubits
+                .iter()
+                .map(|&qubit| QuantumGate::GlobalRotation { qubits: vec![qubit], axis: *axis, angle: *angle })
+                .collect(),
+        ),
+        QuantumGate::PauliRotation { qubits, pauli, angle } => Some(qsim::pauli_rotation_gates(qubits, pauli, *angle)),
+        QuantumGate::Permutation { qubits, pattern } => Some(
+            qsim::permutation_swaps(qubits, pattern)
+                .into_iter()
+                .map(|(qubit1, qubit2)| QuantumGate::SWAP { qubit1, qubit2 })
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Apply a 2^k x 2^k matrix to the index bits `bits` of a vector, one gather, multiply and
+/// scatter per group of entries that differ only on those bits
+pub fn apply_to_bits(data: &mut [Complex], bits: &[usize], matrix: &[Vec<Complex>]) {
+    let mask = bits.iter().fold(0, |mask, &bit| mask | (1 << bit));
+    let offsets: Vec<usize> = (0..1usize << bits.len())
+        .map(|k| bits.iter().enumerate().fold(0, |offset, (i, &bit)| offset | (((k >> i) & 1) << bit)))
+        .collect();
+    let mut local = vec![ZERO; offsets.len()];
+    for base in (0..data.len()).filter(|index| index & mask == 0) {
+        for (slot, &offset) in local.iter_mut().zip(&offsets) {
+            *slot = data[base | offset];
+        }
+        for (row, &offset) in matrix.iter().zip(&offsets) {
+            data[base | offset] = row.iter().zip(&local).fold(ZERO, |sum, (&entry, &amplitude)| sum + entry * amplitude);
+        }
+    }
+}
+
+/// Add |amplitude|² of the amplitudes where each qubit is 1 to its entry of `ones`, for a
+/// slice of the state vector starting at basis index `first`
+pub fn add_one_probabilities(amplitudes: &[Complex], first: usize, qubits: &[usize], ones: &mut [f64]) {
+    for (index, amplitude) in (first..).zip(amplitudes) {
+        let probability = amplitude.magnitude_squared();
+        if probability == 0.0 {
+            continue;
+        }
+        for (one, &qubit) in ones.iter_mut().zip(qubits) {
+            if (index >> qubit) & 1 == 1 {
+                *one += probability;
+            }
+        }
+    }
+}
+
+/// w when K†K = wI, i.e. K is a unitary scaled by √w
+fn unitary_weight(operator: &[Vec<Complex>]) -> Option<f64> {
+    let weight: f64 = operator.iter().map(|row| row[0].magnitude_squared()).sum();
+    if weight < f64::EPSILON {
+        let zero = operator.iter().flatten().all(|entry| entry.magnitude_squared() < f64::EPSILON);
+        return zero.then_some(0.0);
+    }
+    qsim::is_unitary(&scaled(operator, weight), 1e-9).then_some(weight)
+}
+
+/// Diagonal of K†K when it has no off-diagonal entries
+fn gram_diagonal(operator: &[Vec<Complex>]) -> Option<Vec<f64>> {
+    let dim = operator.len();
+    let gram = |i: usize, j: usize| operator.iter().fold(ZERO, |sum, row| sum + row[i].conjugate() * row[j]);
+    let off_diagonal = (0..dim).any(|i| (0..dim).any(|j| i != j && gram(i, j).magnitude_squared() > 1e-18));
+    (!off_diagonal).then(|| (0..dim).map(|i| gram(i, i).re).collect())
+}
+
+/// Joint outcome probabilities of `qubits`, `qubits[0]` the least significant bit
+fn marginal(probabilities: &[f64], qubits: &[usize]) -> Vec<f64> {
+    let mut marginal = vec![0.0; 1 << qubits.len()];
+    for (index, probability) in probabilities.iter().enumerate() {
+        let outcome = qubits.iter().enumerate().fold(0, |outcome, (k, &qubit)| outcome | (((index >> qubit) & 1) << k));
+        marginal[outcome] += probability;
+    }
+    marginal
+}
+
+/// Whether a matrix is a multiple of the identity
+fn is_scaled_identity(operator: &[Vec<Complex>]) -> bool {
+    let diagonal = operator[0][0];
+    operator.iter().enumerate().all(|(row, entries)| {
+        entries.iter().enumerate().all(|(col, &entry)| {
+            let expected = if row == col { diagonal } else { ZERO };
+            Complex::new(entry.re - expected.re, entry.im - expected.im).magnitude_squared() < 1e-18
+        })
+    })
+}
+
+/// K/√w, the operator applied once branch K of weight w is picked
+fn scaled(operator: &[Vec<Complex>], weight: f64) -> Vec<Vec<Complex>> {
+    let scale = 1.0 / weight.max(f64::MIN_POSITIVE).sqrt();
+    operator.iter().map(|row| row.iter().map(|entry| Complex::new(entry.re * scale, entry.im * scale)).collect()).collect()
+}
+
+fn one_qubit_rows(matrix: &[[Complex; 2]; 2]) -> Vec<Vec<Complex>> {
+    matrix.iter().map(|row| row.to_vec()).collect()
+}
+
+/// Dense state vector with straightforward kernels: a reference to check the GPU kernels
+/// against, and a fallback where they are unavailable
+#[derive(Debug, Clone)]
+pub struct CpuStateVector {
+    data: Vec<Complex>,
+}
+
+impl CpuStateVector {
+    pub fn new(num_qubits: usize) -> Self {
+        let mut data = vec![ZERO; 1 << num_qubits];
+        data[0] = Complex::new(1.0, 0.0);
+        Self { data }
+    }
+}
+
+impl SimulatorBackend for CpuStateVector {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn num_qubits(&self) -> usize {
+        self.data.len().trailing_zeros() as usize
+    }
+
+    fn clone_box(&self) -> Box<dyn SimulatorBackend> {
+        Box::new(self.clone())
+    }
+
+    fn apply_matrix(&mut self, qubit: usize, matrix: &[[Complex; 2]; 2]) {
+        apply_to_bits(&mut self.data, &[qubit], &one_qubit_rows(matrix));
+    }
+
+    fn apply_matrix_n(&mut self, qubits: &[usize], matrix: &[Vec<Complex>]) {
+        apply_to_bits(&mut self.data, qubits, matrix);
+    }
+
+    fn measure(&mut self, qubit: usize, rng: &mut dyn RngCore) -> bool {
+        let mask = 1 << qubit;
+        let one: f64 = self.data.iter().enumerate().filter(|(i, _)| i & mask != 0).map(|(_, a)| a.magnitude_squared()).sum();
+        let outcome = rng.gen::<f64>() < one;
+        let scale = 1.0 / if outcome { one } else { 1.0 - one }.sqrt();
+        for (i, amplitude) in self.data.iter_mut().enumerate() {
+            *amplitude = if (i & mask != 0) == outcome { Complex::new(amplitude.re * scale, amplitude.im * scale) } else { ZERO };
+        }
+        outcome
+    }
+
+    fn probabilities(&self) -> Vec<f64> {
+        self.data.iter().map(|amplitude| amplitude.magnitude_squared()).collect()
+    }
+
+    fn amplitudes(&self) -> Cow<'_, [Complex]> {
+        Cow::Borrowed(&self.data)
+    }
+}
+
+/// Density matrix ρ of n qubits, stored row-major with entry (row, col) at `row << n | col`.
+/// Costs 4^n entries, so it suits small registers that will carry mixed states.
+#[derive(Debug, Clone)]
+pub struct DensityMatrix {
+    num_qubits: usize,
+    rho: Vec<Complex>,
+}
+
+impl DensityMatrix {
+    pub fn new(num_qubits: usize) -> Self {
+        let mut rho = vec![ZERO; 1 << (2 * num_qubits)];
+        rho[0] = Complex::new(1.0, 0.0);
+        Self { num_qubits, rho }
+    }
+
+    /// Entry ⟨row|ρ|col⟩
+    pub fn entry(&self, row: usize, col: usize) -> Complex {
+        self.rho[(row << self.num_qubits) | col]
+    }
+}
+
+impl SimulatorBackend for DensityMatrix {
+    fn name(&self) -> &'static str {
+        "density"
+    }
+
+    fn num_qubits(&self) -> usize {
+        self.num_qubits
+    }
+
+    fn clone_box(&self) -> Box<dyn SimulatorBackend> {
+        Box::new(self.clone())
+    }
+
+    fn apply_matrix(&mut self, qubit: usize, matrix: &[[Complex; 2]; 2]) {
+        self.apply_matrix_n(&[qubit], &one_qubit_rows(matrix));
+    }
+
+    /// ρ → UρU†: U on the row bits, and its conjugate on the column bits
+    fn apply_matrix_n(&mut self, qubits: &[usize], matrix: &[Vec<Complex>]) {
+        let rows: Vec<usize> = qubits.iter().map(|&qubit| qubit + self.num_qubits).collect();
+        apply_to_bits(&mut self.rho, &rows, matrix);
+        let conjugate: Vec<Vec<Complex>> = matrix.iter().map(|row| row.iter().map(|e| e.conjugate()).collect()).collect();
+        apply_to_bits(&mut self.rho, qubits, &conjugate);
+    }
+
+    fn measure(&mut self, qubit: usize, rng: &mut dyn RngCore) -> bool {
+        let n = self.num_qubits;
+        let one: f64 = (0..1usize << n).filter(|i| (i >> qubit) & 1 == 1).map(|i| self.entry(i, i).re).sum();
+        let outcome = rng.gen::<f64>() < one;
+        let scale = 1.0 / if outcome { one } else { 1.0 - one };
+        let (row_bit, col_bit) = (1 << (qubit + n), 1 << qubit);
+        for (index, entry) in self.rho.iter_mut().enumerate() {
+            let kept = (index & row_bit != 0) == outcome && (index & col_bit != 0) == outcome;
+            *entry = if kept { Complex::new(entry.re * scale, entry.im * scale) } else { ZERO };
+        }
+        outcome
+    }
+
+    /// ρ → Σ KρK†, exactly, so no randomness is drawn
+    fn apply_kraus(&mut self, qubits: &[usize], kraus: &[Vec<Vec<Complex>>], _rng: &mut dyn RngCore) {
+        let mut mixed = vec![ZERO; self.rho.len()];
+        for operator in kraus {
+            let mut branch = self.clone();
+            branch.apply_matrix_n(qubits, operator);
+            for (sum, entry) in mixed.iter_mut().zip(&branch.rho) {
+                *sum = *sum + *entry;
+            }
+        }
+        self.rho = mixed;
+    }
+
+    fn probabilities(&self) -> Vec<f64> {
+        (0..1usize << self.num_qubits).map(|i| self.entry(i, i).re.max(0.0)).collect()
+    }
+
+    /// Sums along the diagonal, which is right for mixed states too
+    fn one_probabilities(&self, qubits: &[usize]) -> Vec<f64> {
+        let mut ones = vec![0.0; qubits.len()];
+        for i in 0..1usize << self.num_qubits {
+            let probability = self.entry(i, i).re.max(0.0);
+            for (one, &qubit) in ones.iter_mut().zip(qubits) {
+                if (i >> qubit) & 1 == 1 {
+                    *one += probability;
+                }
+            }
+        }
+        ones
+    }
+
+    /// The state vector of a pure ρ = |ψ⟩⟨ψ|, read off its column of largest weight up to a
+    /// global phase. Exact for noiseless runs; for a mixed ρ it is only that column.
+    fn amplitudes(&self) -> Cow<'_, [Complex]> {
+        let dim = 1usize << self.num_qubits;
+        let k = (0..dim).max_by(|&a, &b| self.entry(a, a).re.total_cmp(&self.entry(b, b).re)).unwrap_or(0);
+        let norm = self.entry(k, k).re.max(f64::MIN_POSITIVE).sqrt();
+        let column = (0..dim)
+            .map(|i| {
+                let entry = self.entry(i, k);
+                Complex::new(entry.re / norm, entry.im / norm)
+            })
+            .collect();
+        Cow::Owned(column)
+    }
+}
@@ -0,0 +1,335 @@
+//! Sparse Module
+//! Hashmap state vector backend for circuits that keep few nonzero amplitudes
+
+use std::collections::HashMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::backends::StateBackend;
+use crate::gpu_ops::Complex;
+use crate::qsim::{self, QuantumCircuit, QuantumGate, QuantumSimulator, Shot};
+use crate::results;
+
+/// Fraction of nonzero amplitudes above which the run moves to the dense state vector
+pub const DENSITY_THRESHOLD: f64 = 0.125;
+
+/// Widest circuit the sparse state hands over to the dense state vector or expands into a
+/// probability vector; wider circuits stay sparse throughout
+pub const MAX_DENSE_QUBITS: usize = 30;
+
+/// Most qubits a generic gate may span; it is applied as one 2^k x 2^k block per group of
+/// amplitudes. X-type gates (CNOT, Toffoli, MCX) have no limit.
+const MAX_GATE_QUBITS: usize = 10;
+
+/// Amplitudes with a smaller squared magnitude are dropped as numerical zeros
+const PRUNE: f64 = 1e-24;
+
+const ZERO: Complex = Complex { re: 0.0, im: 0.0 };
+
+/// A state of n qubits as a map from basis-state index (qubit 0 the least significant bit)
+/// to amplitude, holding only the nonzero amplitudes
+#[derive(Debug, Clone)]
+pub struct SparseState {
+    num_qubits: usize,
+    amplitudes: HashMap<usize, Complex>,
+}
+
+impl SparseState {
+    /// |0...0> on `num_qubits` qubits
+    pub fn new(num_qubits: usize) -> Self {
+        Self { num_qubits, amplitudes: HashMap::from([(0, Complex::new(1.0, 0.0))]) }
+    }
+
+    /// Nonzero amplitudes held
+    pub fn nonzero(&self) -> usize {
+        self.amplitudes.len()
+    }
+
+    /// Fraction of the 2^n basis states with a nonzero amplitude
+    pub fn density(&self) -> f64 {
+        self.nonzero() as f64 / 2f64.powi(self.num_qubits as i32)
+    }
+
+    /// Flip `target` on every basis state whose `controls` bits are all set: X, CNOT,
+    /// Toffoli and MCX, moving amplitudes without arithmetic
+    pub fn flip(&mut self, controls: &[usize], target: usize) {
+        let mask = controls.iter().fold(0, |mask, &control| mask | (1 << control));
+        self.relabel(|index| if index & mask == mask { index ^ (1 << target) } else { index });
+    }
+
+    /// Move every amplitude to a new basis-state index; `f` must be a bijection
+    pub fn relabel<F: Fn(usize) -> usize>(&mut self, f: F) {
+        self.amplitudes = self.amplitudes.drain().map(|(index, amplitude)| (f(index), amplitude)).collect();
+    }
+
+    /// Apply a 2^k x 2^k matrix to `qubits`, `qubits[0]` the least significant bit, one
+    /// block per group of amplitudes that differ only on those qubits
+    pub fn apply_matrix(&mut self, qubits: &[usize], matrix: &[Vec<Complex>]) {
+        let mask = qubits.iter().fold(0, |mask, &qubit| mask | (1 << qubit));
+        let dim = 1 << qubits.len();
+        let mut groups: HashMap<usize, Vec<Complex>> = HashMap::new();
+        for (&index, &amplitude) in &self.amplitudes {
+            let local = qubits.iter().enumerate().fold(0, |local, (i, &qubit)| local | (((index >> qubit) & 1) << i));
+            groups.entry(index & !mask).or_insert_with(|| vec![ZERO; dim])[local] = amplitude;
+        }
+        let mut updated = HashMap::with_capacity(self.amplitudes.len());
+        for (rest, local) in groups {
+            for (row, entries) in matrix.iter().enumerate() {
+                let value = local
+                    .iter()
+                    .zip(entries)
+                    .filter(|&(&amplitude, _)| amplitude != ZERO)
+                    .fold(ZERO, |sum, (&amplitude, &entry)| sum + entry * amplitude);
+                if value.magnitude_squared() > PRUNE {
+                    let index = qubits.iter().enumerate().fold(rest, |index, (i, &qubit)| index | (((row >> i) & 1) << qubit));
+                    updated.insert(index, value);
+                }
+            }
+        }
+        self.amplitudes = updated;
+    }
+
+    /// Projective measurement of one qubit, returning the outcome
+    pub fn measure<R: Rng + ?Sized>(&mut self, qubit: usize, rng: &mut R) -> bool {
+        let (mut p0, mut p1) = (0.0, 0.0);
+        for (&index, amplitude) in &self.amplitudes {
+            if (index >> qubit) & 1 == 1 {
+                p1 += amplitude.magnitude_squared();
+            } else {
+                p0 += amplitude.magnitude_squared();
+            }
+        }
+        let outcome = rng.gen::<f64>() * (p0 + p1) < p1;
+        let norm = if outcome { p1 } else { p0 }.sqrt();
+        self.amplitudes.retain(|&index, _| ((index >> qubit) & 1 == 1) == outcome);
+        for amplitude in self.amplitudes.values_mut() {
+            *amplitude = Complex::new(amplitude.re / norm, amplitude.im / norm);
+        }
+        outcome
+    }
+
+    /// Reset a qubit to |0>, returning whether it was found in |1>
+    pub fn reset<R: Rng + ?Sized>(&mut self, qubit: usize, rng: &mut R) -> bool {
+        let outcome = self.measure(qubit, rng);
+        if outcome {
+            self.flip(&[], qubit);
+        }
+        outcome
+    }
+
+    /// The full 2^n amplitude vector
+    pub fn to_dense(&self) -> Vec<Complex> {
+        let mut dense = vec![ZERO; 1 << self.num_qubits];
+        for (&index, &amplitude) in &self.amplitudes {
+            dense[index] = amplitude;
+        }
+        dense
+    }
+
+    /// Nonzero amplitudes in basis-state order, so seeded sampling does not depend on the
+    /// map's iteration order
+    fn sorted(&self) -> Vec<(usize, Complex)> {
+        let mut entries: Vec<(usize, Complex)> = self.amplitudes.iter().map(|(&index, &amplitude)| (index, amplitude)).collect();
+        entries.sort_by_key(|&(index, _)| index);
+        entries
+    }
+
+    /// Sample basis states straight from the nonzero amplitudes
+    pub fn sample<R: Rng>(&self, shots: usize, rng: &mut R) -> Vec<usize> {
+        let entries = self.sorted();
+        let weights: Vec<f64> = entries.iter().map(|(_, amplitude)| amplitude.magnitude_squared()).collect();
+        results::sample_outcomes(&weights, shots, rng).into_iter().map(|k| entries[k].0).collect()
+    }
+}
+
+/// Where a run's amplitudes live: sparse until the density passes `DENSITY_THRESHOLD`
+enum Amplitudes {
+    Sparse(SparseState),
+    Dense(Box<QuantumSimulator>),
+}
+
+/// One run of a circuit, with the measurement bookkeeping the state vector simulator keeps
+struct Run {
+    amplitudes: Amplitudes,
+    /// Terminal measurements, read from the sampled basis state, as (qubit, register bit)
+    pending: Vec<(usize, usize)>,
+    /// Latest collapsed measurement in each register bit
+    classical: Vec<Option<bool>>,
+    register: usize,
+}
+
+impl Run {
+    fn new(circuit: &QuantumCircuit, rng: &mut StdRng) -> Result<Self, String> {
+        let mut run = Run {
+            amplitudes: Amplitudes::Sparse(SparseState::new(circuit.num_qubits)),
+            pending: Vec::new(),
+            classical: vec![None; circuit.num_clbits()],
+            register: 0,
+        };
+        for (index, gate) in circuit.gates.iter().enumerate() {
+            run.apply(gate, &circuit.gates[index + 1..], rng)?;
+            run.densify(circuit.num_qubits, rng);
+        }
+        Ok(run)
+    }
+
+    /// Hand the amplitudes to the dense state vector once too many are nonzero
+    fn densify(&mut self, num_qubits: usize, rng: &mut StdRng) {
+        if let Amplitudes::Sparse(state) = &self.amplitudes {
+            if num_qubits <= MAX_DENSE_QUBITS && state.density() > DENSITY_THRESHOLD {
+                let simulator = QuantumSimulator::from_state(state.to_dense()).expect("2^n amplitudes");
+                self.amplitudes = Amplitudes::Dense(Box::new(simulator.reseeded(rng.gen())));
+            }
+        }
+    }
+
+    fn apply(&mut self, gate: &QuantumGate, later: &[QuantumGate], rng: &mut StdRng) -> Result<(), String> {
+        match gate {
+            QuantumGate::Measurement { qubit, bit } => {
+                let bit = bit.unwrap_or(*qubit);
+                // Measurements no later gate depends on stay deferred, so the distribution is kept
+                if qsim::measurement_is_read(*qubit, bit, later) {
+                    let outcome = match &mut self.amplitudes {
+                        Amplitudes::Sparse(state) => state.measure(*qubit, rng),
+                        Amplitudes::Dense(simulator) => {
+                            simulator.apply_gate(gate);
+                            simulator.collapse_pending();
+                            simulator.classical_bits()[bit] == Some(true)
+                        }
+                    };
+                    self.classical[bit] = Some(outcome);
+                    self.register = (self.register & !(1 << bit)) | ((outcome as usize) << bit);
+                } else {
+                    self.classical[bit] = None;
+                    self.pending.push((*qubit, bit));
+                }
+            }
+            QuantumGate::IfBit { bit, gate } => {
+                if self.classical[*bit] == Some(true) {
+                    self.apply(gate, later, rng)?;
+                }
+            }
+            QuantumGate::Noisy { gate, .. } => self.apply(gate, later, rng)?,
+            _ => match &mut self.amplitudes {
+                Amplitudes::Dense(simulator) => simulator.apply_gate(gate),
+                Amplitudes::Sparse(state) => apply_sparse(state, gate, rng)?,
+            },
+        }
+        Ok(())
+    }
+
+    /// Classical register if the final state is measured as `outcome`
+    fn register_for(&self, outcome: usize) -> usize {
+        self.pending
+            .iter()
+            .fold(self.register, |register, &(qubit, bit)| (register & !(1 << bit)) | (((outcome >> qubit) & 1) << bit))
+    }
+
+    fn sample(&self, shots: usize, rng: &mut StdRng) -> Vec<usize> {
+        match &self.amplitudes {
+            Amplitudes::Sparse(state) => state.sample(shots, rng),
+            Amplitudes::Dense(simulator) => simulator.sample(shots, rng),
+        }
+    }
+}
+
+/// Apply a unitary gate, reset or relabeling to a sparse state
+fn apply_sparse(state: &mut SparseState, gate: &QuantumGate, rng: &mut StdRng) -> Result<(), String> {
+    match gate {
+        QuantumGate::PauliX { qubit } => state.flip(&[], *qubit),
+        QuantumGate::CNOT { control, target } => state.flip(&[*control], *target),
+        QuantumGate::Toffoli { control1, control2, target } => state.flip(&[*control1, *control2], *target),
+        QuantumGate::MCX { controls, target } => state.flip(controls, *target),
+        QuantumGate::SWAP { qubit1, qubit2 } => {
+            let (a, b) = (*qubit1, *qubit2);
+            state.relabel(|index| {
+                let (bit_a, bit_b) = ((index >> a) & 1, (index >> b) & 1);
+                (index & !((1 << a) | (1 << b))) | (bit_a << b) | (bit_b << a)
+            });
+        }
+        QuantumGate::Permutation { qubits, pattern } => {
+            let mask = qubits.iter().fold(0, |mask, &qubit| mask | (1 << qubit));
+            state.relabel(|index| {
+                qubits
+                    .iter()
+                    .zip(pattern)
+                    .fold(index & !mask, |moved, (&qubit, &source)| moved | (((index >> qubits[source]) & 1) << qubit))
+            });
+        }
+        QuantumGate::Reset { qubit } => {
+            state.reset(*qubit, rng);
+        }
+        // Snapshots copy a dense state vector, which a sparse run does not build
+        QuantumGate::Snapshot { .. } => {}
+        QuantumGate::GlobalHadamard { qubits } if qubits.len() > 1 => {
+            for &qubit in qubits {
+                apply_sparse(state, &QuantumGate::Hadamard { qubit }, rng)?;
+            }
+        }
+        QuantumGate::GlobalRotation { qubits, axis, angle } if qubits.len() > 1 => {
+            for &qubit in qubits {
+                apply_sparse(state, &QuantumGate::GlobalRotation { qubits: vec![qubit], axis: *axis, angle: *angle }, rng)?;
+            }
+        }
+        QuantumGate::PauliRotation { qubits, pauli, angle } => {
+            for gate in qsim::pauli_rotation_gates(qubits, pauli, *angle) {
+                apply_sparse(state, &gate, rng)?;
+            }
+        }
+        _ => {
+            let qubits = gate.qubits();
+            if qubits.len() > MAX_GATE_QUBITS {
+                return Err(format!(
+                    "the sparse backend applies gates on at most {} qubits; this one spans {}",
+                    MAX_GATE_QUBITS,
+                    qubits.len()
+                ));
+            }
+            state.apply_matrix(&qubits, &qsim::gate_matrix(gate, &qubits));
+        }
+    }
+    Ok(())
+}
+
+/// Sparse state vector backend, registered as `sparse`
+pub struct SparseBackend;
+
+impl StateBackend for SparseBackend {
+    fn name(&self) -> &str {
+        "sparse"
+    }
+
+    fn description(&self) -> &str {
+        "hashmap of nonzero amplitudes (oracles, arithmetic), dense once over 1/8 of them are nonzero"
+    }
+
+    fn probabilities(&self, circuit: &QuantumCircuit, seed: u64) -> Result<Vec<f64>, String> {
+        if circuit.num_qubits > MAX_DENSE_QUBITS {
+            return Err(format!(
+                "a probability vector of {} qubits has 2^{} entries; sample it with --shots instead (at most {} qubits)",
+                circuit.num_qubits, circuit.num_qubits, MAX_DENSE_QUBITS
+            ));
+        }
+        Ok(match Run::new(circuit, &mut StdRng::seed_from_u64(seed))?.amplitudes {
+            Amplitudes::Sparse(state) => state.to_dense().iter().map(|amplitude| amplitude.magnitude_squared()).collect(),
+            Amplitudes::Dense(simulator) => simulator.measure_all(),
+        })
+    }
+
+    fn sample(&self, circuit: &QuantumCircuit, shots: usize, seed: u64) -> Result<Vec<Shot>, String> {
+        if circuit.num_qubits > usize::BITS as usize {
+            return Err(format!("shot outcomes hold at most {} qubits", usize::BITS));
+        }
+        let mut rng = StdRng::seed_from_u64(seed);
+        if !circuit.has_collapse() {
+            let run = Run::new(circuit, &mut rng)?;
+            return Ok(qsim::terminal_shots(circuit, run.sample(shots, &mut rng)));
+        }
+        (0..shots)
+            .map(|_| {
+                let run = Run::new(circuit, &mut rng)?;
+                let outcome = run.sample(1, &mut rng)[0];
+                Ok(Shot { outcome, register: run.register_for(outcome) })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,758 @@
+//! Stabilizer Module
+//! Extended stabilizer backend: Clifford circuits with a few non-Clifford rotations, kept as
+//! a sum of stabilizer states in CH-form
+
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, FRAC_1_SQRT_2};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::backends::StateBackend;
+use crate::gpu_ops::{Complex, RotationAxis};
+use crate::qsim::{self, QuantumCircuit, QuantumGate, Shot};
+use crate::simulator_backend;
+
+/// Most stabilizer terms a run keeps. Each non-Clifford rotation on a qubit in superposition
+/// doubles the count, so this bounds such rotations at 16 (a Toffoli takes 7).
+pub const MAX_TERMS: usize = 1 << 16;
+
+/// Widest circuit: every row of the CH-form is one 64-bit word
+pub const MAX_QUBITS: usize = 64;
+
+/// Widest circuit whose full probability vector the backend builds; wider circuits are
+/// sampled with `--shots`
+pub const MAX_DENSE_QUBITS: usize = 24;
+
+/// Rotation angles within this of a multiple of pi/2 are applied as S gates, without
+/// splitting terms
+const CLIFFORD_TOLERANCE: f64 = 1e-9;
+
+const ZERO: Complex = Complex { re: 0.0, im: 0.0 };
+const ONE: Complex = Complex { re: 1.0, im: 0.0 };
+
+fn scale(c: Complex, factor: f64) -> Complex {
+    Complex::new(c.re * factor, c.im * factor)
+}
+
+/// i^k
+fn i_pow(k: u32) -> Complex {
+    match k % 4 {
+        0 => ONE,
+        1 => Complex::new(0.0, 1.0),
+        2 => Complex::new(-1.0, 0.0),
+        _ => Complex::new(0.0, -1.0),
+    }
+}
+
+fn parity(bits: u64) -> u32 {
+    bits.count_ones() & 1
+}
+
+fn sign(odd: bool) -> f64 {
+    if odd { -1.0 } else { 1.0 }
+}
+
+/// A stabilizer state ω U_C U_H |s⟩ in the CH-form of Bravyi et al. (arXiv:1808.00128):
+/// U_C a Clifford built from CNOT, CZ and S, U_H Hadamards on the qubits in `v`, and s a
+/// basis state. U_C is kept as its action on Paulis, one bit row per qubit:
+/// U_C† Z_p U_C = ∏ Z_j^G[p][j] and U_C† X_p U_C = i^γ[p] ∏ X_j^F[p][j] Z_j^M[p][j].
+#[derive(Debug, Clone)]
+struct ChForm {
+    g: Vec<u64>,
+    f: Vec<u64>,
+    m: Vec<u64>,
+    /// γ[p], mod 4
+    gamma: Vec<u32>,
+    v: u64,
+    s: u64,
+    omega: Complex,
+}
+
+impl ChForm {
+    /// |0...0>
+    fn new(num_qubits: usize) -> Self {
+        let identity: Vec<u64> = (0..num_qubits).map(|p| 1 << p).collect();
+        Self {
+            g: identity.clone(),
+            f: identity,
+            m: vec![0; num_qubits],
+            gamma: vec![0; num_qubits],
+            v: 0,
+            s: 0,
+            omega: ONE,
+        }
+    }
+
+    /// S on qubit `q` from the left: U_C → S U_C
+    fn s_left(&mut self, q: usize) {
+        self.m[q] ^= self.g[q];
+        self.gamma[q] = (self.gamma[q] + 3) % 4;
+    }
+
+    fn cz_left(&mut self, a: usize, b: usize) {
+        self.m[a] ^= self.g[b];
+        self.m[b] ^= self.g[a];
+    }
+
+    /// SWAP from the left only exchanges the two qubits' rows
+    fn swap_left(&mut self, a: usize, b: usize) {
+        self.g.swap(a, b);
+        self.f.swap(a, b);
+        self.m.swap(a, b);
+        self.gamma.swap(a, b);
+    }
+
+    fn cx_left(&mut self, control: usize, target: usize) {
+        let cross = 2 * parity(self.m[control] & self.f[target]);
+        self.gamma[control] = (self.gamma[control] + self.gamma[target] + cross) % 4;
+        self.g[target] ^= self.g[control];
+        self.f[control] ^= self.f[target];
+        self.m[control] ^= self.m[target];
+    }
+
+    /// S on qubit `q` from the right: U_C → U_C S, used while absorbing a sum of two basis states
+    fn s_right(&mut self, q: usize) {
+        for p in 0..self.g.len() {
+            if (self.f[p] >> q) & 1 == 1 {
+                self.m[p] ^= 1 << q;
+                self.gamma[p] = (self.gamma[p] + 3) % 4;
+            }
+        }
+    }
+
+    fn cz_right(&mut self, a: usize, b: usize) {
+        for p in 0..self.g.len() {
+            let (fa, fb) = ((self.f[p] >> a) & 1, (self.f[p] >> b) & 1);
+            self.m[p] ^= (fb << a) | (fa << b);
+            self.gamma[p] = (self.gamma[p] + 2 * (fa & fb) as u32) % 4;
+        }
+    }
+
+    fn cx_right(&mut self, control: usize, target: usize) {
+        for p in 0..self.g.len() {
+            self.g[p] ^= ((self.g[p] >> target) & 1) << control;
+            self.f[p] ^= ((self.f[p] >> control) & 1) << target;
+            self.m[p] ^= ((self.m[p] >> target) & 1) << control;
+        }
+    }
+
+    /// Absorb (-1)^α U_H (|t⟩ + i^δ |u⟩) / √2 back into CH-form (Proposition 4 of the paper)
+    fn update_sum(&mut self, t: u64, u: u64, delta: u32, alpha: u32) {
+        let alpha_sign = sign(alpha & 1 == 1);
+        if t == u {
+            self.s = t;
+            self.omega = self.omega * scale(ONE + i_pow(delta), alpha_sign * FRAC_1_SQRT_2);
+            return;
+        }
+        let differ = t ^ u;
+        let (set0, set1) = (differ & !self.v, differ & self.v);
+        // Clifford on the right that leaves t and u differing on one qubit q
+        let q = if set0 != 0 {
+            let q = set0.trailing_zeros() as usize;
+            for i in bits(set0).filter(|&i| i != q) {
+                self.cx_right(q, i);
+            }
+            for i in bits(set1) {
+                self.cz_right(q, i);
+            }
+            q
+        } else {
+            let q = set1.trailing_zeros() as usize;
+            for i in bits(set1).filter(|&i| i != q) {
+                self.cx_right(i, q);
+            }
+            q
+        };
+        let e = 1u64 << q;
+        let (y, z) = if t & e != 0 { (u ^ e, u) } else { (t, t ^ e) };
+        let (omega, a, b, c) = h_decompose(self.v & e != 0, y & e != 0, z & e != 0, delta);
+        self.s = (y & !e) | ((c as u64) << q);
+        self.omega = self.omega * scale(omega, alpha_sign);
+        if a {
+            self.s_right(q);
+        }
+        self.v = (self.v & !e) | ((b as u64) << q);
+    }
+
+    /// Hadamard on qubit `q` from the left
+    fn h_left(&mut self, q: usize) {
+        let (g, f, m, v, s) = (self.g[q], self.f[q], self.m[q], self.v, self.s);
+        let t = s ^ (g & v);
+        let u = s ^ (f & !v) ^ (m & v);
+        let alpha = parity(g & !v & s);
+        let beta = parity(m & !v & s) + parity(f & v & m) + parity(f & v & s);
+        let delta = (self.gamma[q] + 2 * (alpha + beta)) % 4;
+        self.update_sum(t, u, delta, alpha);
+    }
+
+    /// ⟨x|ψ⟩, qubit 0 the least significant bit of `x`
+    fn amplitude(&self, x: u64) -> Complex {
+        let mut mu = 0;
+        let mut u = 0u64;
+        for p in bits(x) {
+            mu += self.gamma[p];
+            u ^= self.f[p];
+            mu += 2 * parity(self.m[p] & u);
+        }
+        if (u ^ self.s) & !self.v != 0 {
+            return ZERO;
+        }
+        let magnitude = 0.5f64.powf(self.v.count_ones() as f64 / 2.0) * sign(parity(self.v & u & self.s) == 1);
+        self.omega * scale(i_pow(mu), magnitude)
+    }
+
+    /// Basis state U_C maps |w⟩ to, up to phase
+    fn image(&self, w: u64) -> u64 {
+        self.g.iter().enumerate().fold(0, |x, (p, &row)| x | ((parity(row & w) as u64) << p))
+    }
+
+    /// Basis state drawn uniformly from the support, where every amplitude has the same magnitude
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> u64 {
+        self.image((self.s & !self.v) | (rng.gen::<u64>() & self.v))
+    }
+
+    /// Every basis state with a nonzero amplitude, 2^|v| of them
+    fn support(&self) -> impl Iterator<Item = u64> + '_ {
+        let fixed = self.s & !self.v;
+        subsets(self.v).map(move |free| self.image(fixed | free))
+    }
+
+    /// Outcome of measuring qubit `q`, if it is certain
+    fn certain_outcome(&self, q: usize) -> Option<bool> {
+        (self.g[q] & self.v == 0).then(|| parity(self.g[q] & self.s) == 1)
+    }
+
+    /// Measure qubit `q` and collapse onto the outcome
+    fn measure<R: Rng + ?Sized>(&mut self, q: usize, rng: &mut R) -> bool {
+        let outcome = parity(self.g[q] & ((self.s & !self.v) | (rng.gen::<u64>() & self.v))) == 1;
+        let t = self.s;
+        let u = (self.g[q] & self.v) ^ self.s;
+        let delta = (2 * parity(self.g[q] & !self.v & self.s) + 2 * outcome as u32) % 4;
+        if t == u {
+            self.omega = scale(self.omega, FRAC_1_SQRT_2);
+        }
+        self.update_sum(t, u, delta, 0);
+        outcome
+    }
+}
+
+/// H^v (|y⟩ + i^δ |z⟩) / √2 = ω S^a H^b |c⟩ on one qubit, for y ≠ z
+fn h_decompose(v: bool, y: bool, z: bool, delta: u32) -> (Complex, bool, bool, bool) {
+    debug_assert!(y != z);
+    if !v {
+        let omega = i_pow(delta * y as u32);
+        let delta2 = if y { (4 - delta) % 4 } else { delta };
+        (omega, delta2 & 1 == 1, true, delta2 >> 1 == 1)
+    } else if delta & 1 == 0 {
+        let c = delta >> 1 == 1;
+        (scale(ONE, sign(c && y)), false, false, c)
+    } else {
+        (scale(ONE + i_pow(delta), FRAC_1_SQRT_2), true, true, (delta >> 1 == 1) == y)
+    }
+}
+
+/// Indices of the set bits
+fn bits(word: u64) -> impl Iterator<Item = usize> {
+    (0..u64::BITS as usize).filter(move |&i| (word >> i) & 1 == 1)
+}
+
+/// Every subset of the set bits of `mask`, starting with the empty one
+fn subsets(mask: u64) -> impl Iterator<Item = u64> {
+    let mut next = Some(0u64);
+    std::iter::from_fn(move || {
+        let current = next?;
+        let following = current.wrapping_sub(mask) & mask;
+        next = (following != 0).then_some(following);
+        Some(current)
+    })
+}
+
+/// Clifford+rotation steps every gate the backend takes is decomposed into; Pauli
+/// propagation pulls observables back through the same steps
+#[derive(Debug, Clone, Copy)]
+pub enum Step {
+    H(usize),
+    /// S applied this many times
+    S(usize, u32),
+    CX(usize, usize),
+    CZ(usize, usize),
+    /// Exchange of two qubits, a relabelling rather than three CX
+    Swap(usize, usize),
+    /// diag(1, e^(iθ)), which splits terms unless θ is a multiple of pi/2
+    Phase(usize, f64),
+}
+
+/// RX(θ), up to global phase
+fn rx(qubit: usize, angle: f64) -> Vec<Step> {
+    vec![Step::H(qubit), Step::Phase(qubit, angle), Step::H(qubit)]
+}
+
+/// RY(θ) = S RX(θ) S†, up to global phase
+fn ry(qubit: usize, angle: f64) -> Vec<Step> {
+    let mut steps = vec![Step::S(qubit, 3)];
+    steps.extend(rx(qubit, angle));
+    steps.push(Step::S(qubit, 1));
+    steps
+}
+
+/// U(θ, φ, λ) = P(φ) RY(θ) P(λ)
+fn u3(qubit: usize, theta: f64, phi: f64, lambda: f64) -> Vec<Step> {
+    let mut steps = vec![Step::Phase(qubit, lambda)];
+    steps.extend(ry(qubit, theta));
+    steps.push(Step::Phase(qubit, phi));
+    steps
+}
+
+/// ZZ rotation, up to global phase
+fn rzz(qubit1: usize, qubit2: usize, angle: f64) -> Vec<Step> {
+    vec![Step::CX(qubit1, qubit2), Step::Phase(qubit2, angle), Step::CX(qubit1, qubit2)]
+}
+
+fn cphase(control: usize, target: usize, angle: f64) -> Vec<Step> {
+    vec![
+        Step::Phase(target, angle / 2.0),
+        Step::CX(control, target),
+        Step::Phase(target, -angle / 2.0),
+        Step::CX(control, target),
+        Step::Phase(control, angle / 2.0),
+    ]
+}
+
+/// Toffoli from CNOTs, Hadamards and seven T gates
+fn toffoli(control1: usize, control2: usize, target: usize) -> Vec<Step> {
+    let (t, tdg) = (FRAC_PI_4, -FRAC_PI_4);
+    vec![
+        Step::H(target),
+        Step::CX(control2, target),
+        Step::Phase(target, tdg),
+        Step::CX(control1, target),
+        Step::Phase(target, t),
+        Step::CX(control2, target),
+        Step::Phase(target, tdg),
+        Step::CX(control1, target),
+        Step::Phase(control2, t),
+        Step::Phase(target, t),
+        Step::H(target),
+        Step::CX(control1, control2),
+        Step::Phase(control1, t),
+        Step::Phase(control2, tdg),
+        Step::CX(control1, control2),
+    ]
+}
+
+/// Angles (θ, φ, λ) with U(θ, φ, λ) equal to a 2x2 unitary up to global phase
+fn zyz(matrix: &[Vec<Complex>]) -> (f64, f64, f64) {
+    let arg = |c: Complex| c.im.atan2(c.re);
+    let magnitude = |c: Complex| c.magnitude_squared().sqrt();
+    let (m00, m01, m10, m11) = (matrix[0][0], matrix[0][1], matrix[1][0], matrix[1][1]);
+    let theta = 2.0 * magnitude(m10).atan2(magnitude(m00));
+    let neg_m01 = scale(m01, -1.0);
+    if magnitude(m00) > CLIFFORD_TOLERANCE {
+        let alpha = arg(m00);
+        if magnitude(m10) > CLIFFORD_TOLERANCE {
+            (theta, arg(m10) - alpha, arg(neg_m01) - alpha)
+        } else {
+            (theta, arg(m11) - alpha, 0.0)
+        }
+    } else {
+        (theta, arg(m10) - arg(neg_m01), 0.0)
+    }
+}
+
+/// Quarter turns of a Z rotation by `angle`, when it is a multiple of pi/2 and so a power of S
+pub fn clifford_turns(angle: f64) -> Option<u32> {
+    let quarter_turns = angle / FRAC_PI_2;
+    ((quarter_turns - quarter_turns.round()).abs() < CLIFFORD_TOLERANCE).then(|| quarter_turns.round().rem_euclid(4.0) as u32)
+}
+
+/// The steps of a unitary gate, up to global phase, splitting layers and Pauli rotations
+/// first; gates spanning several qubits without a Clifford+rotation form are refused
+pub fn gate_steps(gate: &QuantumGate) -> Result<Vec<Step>, String> {
+    match simulator_backend::split(gate) {
+        Some(gates) => Ok(gates.iter().map(gate_steps).collect::<Result<Vec<_>, _>>()?.concat()),
+        None => steps(gate),
+    }
+}
+
+fn steps(gate: &QuantumGate) -> Result<Vec<Step>, String> {
+    Ok(match gate {
+        QuantumGate::Hadamard { qubit } => vec![Step::H(*qubit)],
+        QuantumGate::PauliX { qubit } => vec![Step::H(*qubit), Step::S(*qubit, 2), Step::H(*qubit)],
+        QuantumGate::PauliY { qubit } => vec![Step::S(*qubit, 2), Step::H(*qubit), Step::S(*qubit, 2), Step::H(*qubit)],
+        QuantumGate::PauliZ { qubit } => vec![Step::S(*qubit, 2)],
+        QuantumGate::S { qubit } => vec![Step::S(*qubit, 1)],
+        QuantumGate::Sdg { qubit } => vec![Step::S(*qubit, 3)],
+        QuantumGate::T { qubit } => vec![Step::Phase(*qubit, FRAC_PI_4)],
+        QuantumGate::Tdg { qubit } => vec![Step::Phase(*qubit, -FRAC_PI_4)],
+        QuantumGate::SX { qubit } => vec![Step::H(*qubit), Step::S(*qubit, 1), Step::H(*qubit)],
+        QuantumGate::SXdg { qubit } => vec![Step::H(*qubit), Step::S(*qubit, 3), Step::H(*qubit)],
+        QuantumGate::Phase { qubit, angle } | QuantumGate::RotationZ { qubit, angle } => vec![Step::Phase(*qubit, *angle)],
+        QuantumGate::RotationX { qubit, angle } => rx(*qubit, *angle),
+        QuantumGate::RotationY { qubit, angle } => ry(*qubit, *angle),
+        QuantumGate::U { qubit, theta, phi, lambda } => u3(*qubit, *theta, *phi, *lambda),
+        QuantumGate::GlobalRotation { qubits, axis, angle } if qubits.len() == 1 => match axis {
+            RotationAxis::X => rx(qubits[0], *angle),
+            RotationAxis::Y => ry(qubits[0], *angle),
+            RotationAxis::Z => vec![Step::Phase(qubits[0], *angle)],
+        },
+        QuantumGate::CNOT { control, target } => vec![Step::CX(*control, *target)],
+        QuantumGate::CZ { control, target } => vec![Step::CZ(*control, *target)],
+        QuantumGate::CPhase { control, target, angle } => cphase(*control, *target, *angle),
+        QuantumGate::SWAP { qubit1, qubit2 } => vec![Step::Swap(*qubit1, *qubit2)],
+        // iSWAP = SWAP CZ (S ⊗ S)
+        QuantumGate::ISWAP { qubit1, qubit2 } => vec![
+            Step::S(*qubit1, 1),
+            Step::S(*qubit2, 1),
+            Step::CZ(*qubit1, *qubit2),
+            Step::Swap(*qubit1, *qubit2),
+        ],
+        // ECR = X(qubit1) CNOT(qubit1, qubit2) SX(qubit2) S(qubit1), up to a phase e^(-iπ/4)
+        QuantumGate::ECR { qubit1, qubit2 } => vec![
+            Step::S(*qubit1, 1),
+            Step::H(*qubit2),
+            Step::S(*qubit2, 1),
+            Step::H(*qubit2),
+            Step::CX(*qubit1, *qubit2),
+            Step::H(*qubit1),
+            Step::S(*qubit1, 2),
+            Step::H(*qubit1),
+        ],
+        QuantumGate::Permutation { qubits, pattern } => {
+            qsim::permutation_swaps(qubits, pattern).into_iter().map(|(a, b)| Step::Swap(a, b)).collect()
+        }
+        QuantumGate::RZZ { qubit1, qubit2, angle } => rzz(*qubit1, *qubit2, *angle),
+        QuantumGate::RXX { qubit1, qubit2, angle } => {
+            let basis = [Step::H(*qubit1), Step::H(*qubit2)];
+            [&basis[..], &rzz(*qubit1, *qubit2, *angle)[..], &basis[..]].concat()
+        }
+        QuantumGate::RYY { qubit1, qubit2, angle } => {
+            let into = [Step::S(*qubit1, 3), Step::S(*qubit2, 3), Step::H(*qubit1), Step::H(*qubit2)];
+            let back = [Step::H(*qubit1), Step::H(*qubit2), Step::S(*qubit1, 1), Step::S(*qubit2, 1)];
+            [&into[..], &rzz(*qubit1, *qubit2, *angle)[..], &back[..]].concat()
+        }
+        QuantumGate::Toffoli { control1, control2, target } => toffoli(*control1, *control2, *target),
+        QuantumGate::MCX { controls, target } => match controls[..] {
+            [] => steps(&QuantumGate::PauliX { qubit: *target })?,
+            [control] => vec![Step::CX(control, *target)],
+            [control1, control2] => toffoli(control1, control2, *target),
+            _ => return Err(format!("MCX is decomposed with at most 2 controls; this one has {}", controls.len())),
+        },
+        QuantumGate::Controlled { control, gate } => match gate.as_ref() {
+            QuantumGate::PauliX { qubit } => vec![Step::CX(*control, *qubit)],
+            QuantumGate::PauliZ { qubit } => vec![Step::CZ(*control, *qubit)],
+            QuantumGate::Phase { qubit, angle } => cphase(*control, *qubit, *angle),
+            gate => return Err(format!("a controlled {:?} has no decomposition into Clifford gates and Z rotations", gate)),
+        },
+        gate => match gate.qubits()[..] {
+            [qubit] => {
+                let (theta, phi, lambda) = zyz(&qsim::gate_matrix(gate, &[qubit]));
+                u3(qubit, theta, phi, lambda)
+            }
+            _ => return Err(format!("{:?} has no decomposition into Clifford gates and Z rotations", gate)),
+        },
+    })
+}
+
+/// A state as a weighted sum of CH-form stabilizer states, each weight folded into its ω.
+/// Clifford steps update every term in place; a rotation diag(1, e^(iθ)) = a I + b Z
+/// splits each term into an a-weighted copy and a b-weighted copy with Z applied.
+#[derive(Debug, Clone)]
+pub struct ExtendedStabilizer {
+    num_qubits: usize,
+    terms: Vec<ChForm>,
+}
+
+impl ExtendedStabilizer {
+    /// |0...0> on `num_qubits` qubits, at most `MAX_QUBITS`
+    pub fn new(num_qubits: usize) -> Self {
+        assert!(num_qubits <= MAX_QUBITS, "at most {} qubits", MAX_QUBITS);
+        Self { num_qubits, terms: vec![ChForm::new(num_qubits)] }
+    }
+
+    fn apply(&mut self, step: Step) -> Result<(), String> {
+        match step {
+            Step::H(q) => self.terms.iter_mut().for_each(|term| term.h_left(q)),
+            Step::S(q, times) => {
+                for term in &mut self.terms {
+                    (0..times % 4).for_each(|_| term.s_left(q));
+                }
+            }
+            Step::CX(control, target) => self.terms.iter_mut().for_each(|term| term.cx_left(control, target)),
+            Step::CZ(a, b) => self.terms.iter_mut().for_each(|term| term.cz_left(a, b)),
+            Step::Swap(a, b) => self.terms.iter_mut().for_each(|term| term.swap_left(a, b)),
+            Step::Phase(q, angle) => match clifford_turns(angle) {
+                Some(turns) => self.apply(Step::S(q, turns))?,
+                None => self.rotate(q, angle)?,
+            },
+        }
+        Ok(())
+    }
+
+    /// diag(1, e^(iθ)) on qubit `q` as a I + b Z; terms where the qubit is certain only pick
+    /// up a phase, the rest split in two
+    fn rotate(&mut self, q: usize, angle: f64) -> Result<(), String> {
+        let turn = Complex::from_polar(1.0, angle);
+        let a = scale(ONE + turn, 0.5);
+        let b = scale(ONE + scale(turn, -1.0), 0.5);
+        let splitting = self.terms.iter().filter(|term| term.certain_outcome(q).is_none()).count();
+        if self.terms.len() + splitting > MAX_TERMS {
+            return Err(format!(
+                "the circuit needs more than {} stabilizer terms; it has too many non-Clifford rotations on qubits in superposition",
+                MAX_TERMS
+            ));
+        }
+        let mut split = Vec::with_capacity(splitting);
+        for term in &mut self.terms {
+            match term.certain_outcome(q) {
+                Some(outcome) => term.omega = term.omega * if outcome { turn } else { ONE },
+                None => {
+                    let mut flipped = term.clone();
+                    flipped.s_left(q);
+                    flipped.s_left(q);
+                    flipped.omega = flipped.omega * b;
+                    term.omega = term.omega * a;
+                    split.push(flipped);
+                }
+            }
+        }
+        self.terms.extend(split);
+        Ok(())
+    }
+
+    /// Apply a unitary gate
+    pub fn apply_gate(&mut self, gate: &QuantumGate) -> Result<(), String> {
+        gate_steps(gate)?.into_iter().try_for_each(|step| self.apply(step))
+    }
+
+    /// Measure a qubit and collapse onto the outcome. Only a single stabilizer state, or a
+    /// sum whose terms all agree on the qubit, can be collapsed.
+    pub fn measure<R: Rng + ?Sized>(&mut self, qubit: usize, rng: &mut R) -> Result<bool, String> {
+        if let [term] = &mut self.terms[..] {
+            return Ok(term.measure(qubit, rng));
+        }
+        let outcomes: Vec<Option<bool>> = self.terms.iter().map(|term| term.certain_outcome(qubit)).collect();
+        match outcomes[0] {
+            Some(outcome) if outcomes.iter().all(|&o| o == Some(outcome)) => Ok(outcome),
+            _ => Err(format!(
+                "the stabilizer backend cannot measure qubit {} mid-circuit after non-Clifford rotations left {} terms",
+                qubit,
+                self.terms.len()
+            )),
+        }
+    }
+
+    /// Reset a qubit to |0>
+    pub fn reset<R: Rng + ?Sized>(&mut self, qubit: usize, rng: &mut R) -> Result<(), String> {
+        if self.measure(qubit, rng)? {
+            self.apply_gate(&QuantumGate::PauliX { qubit })?;
+        }
+        Ok(())
+    }
+
+    /// Probability of every basis state, from the amplitudes on each term's support; only
+    /// for circuits of at most `MAX_DENSE_QUBITS` qubits
+    pub fn probabilities(&self) -> Vec<f64> {
+        let mut amplitudes = vec![ZERO; 1 << self.num_qubits];
+        for term in &self.terms {
+            for x in term.support() {
+                amplitudes[x as usize] = amplitudes[x as usize] + term.amplitude(x);
+            }
+        }
+        let probabilities: Vec<f64> = amplitudes.iter().map(|amplitude| amplitude.magnitude_squared()).collect();
+        let total: f64 = probabilities.iter().sum();
+        probabilities.iter().map(|p| p / total).collect()
+    }
+
+    /// Draw basis states. One stabilizer state is sampled directly; a sum is sampled exactly
+    /// by rejection: pick a term by weight, draw from its support, and accept with
+    /// |Σ amplitudes|² / (terms · Σ |amplitude|²), which Cauchy-Schwarz bounds by 1. The
+    /// expected number of draws per shot grows with the number of terms.
+    pub fn sample<R: Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Vec<usize> {
+        if let [term] = &self.terms[..] {
+            return (0..shots).map(|_| term.sample(rng) as usize).collect();
+        }
+        let weights: Vec<f64> = self.terms.iter().map(|term| term.omega.magnitude_squared()).collect();
+        let total: f64 = weights.iter().sum();
+        let count = self.terms.len() as f64;
+        (0..shots)
+            .map(|_| loop {
+                let mut pick = rng.gen::<f64>() * total;
+                let index = weights.iter().position(|&w| {
+                    pick -= w;
+                    pick < 0.0
+                });
+                let x = self.terms[index.unwrap_or(self.terms.len() - 1)].sample(rng);
+                let (sum, weight) = self.terms.iter().fold((ZERO, 0.0), |(sum, weight), term| {
+                    let amplitude = term.amplitude(x);
+                    (sum + amplitude, weight + amplitude.magnitude_squared())
+                });
+                if rng.gen::<f64>() * count * weight < sum.magnitude_squared() {
+                    break x as usize;
+                }
+            })
+            .collect()
+    }
+}
+
+/// One run of a circuit, with the measurement bookkeeping the state vector simulator keeps
+struct Run {
+    state: ExtendedStabilizer,
+    /// Terminal measurements, read from the sampled basis state, as (qubit, register bit)
+    pending: Vec<(usize, usize)>,
+    /// Latest collapsed measurement in each register bit
+    classical: Vec<Option<bool>>,
+    register: usize,
+}
+
+impl Run {
+    fn new(circuit: &QuantumCircuit, rng: &mut StdRng) -> Result<Self, String> {
+        if circuit.num_qubits > MAX_QUBITS {
+            return Err(format!("the stabilizer backend simulates at most {} qubits", MAX_QUBITS));
+        }
+        let mut run = Run {
+            state: ExtendedStabilizer::new(circuit.num_qubits),
+            pending: Vec::new(),
+            classical: vec![None; circuit.num_clbits()],
+            register: 0,
+        };
+        for (index, gate) in circuit.gates.iter().enumerate() {
+            run.apply(gate, &circuit.gates[index + 1..], rng)?;
+        }
+        Ok(run)
+    }
+
+    fn apply(&mut self, gate: &QuantumGate, later: &[QuantumGate], rng: &mut StdRng) -> Result<(), String> {
+        match gate {
+            QuantumGate::Measurement { qubit, bit } => {
+                let bit = bit.unwrap_or(*qubit);
+                // Measurements no later gate depends on stay deferred, so the distribution is kept
+                if qsim::measurement_is_read(*qubit, bit, later) {
+                    let outcome = self.state.measure(*qubit, rng)?;
+                    self.classical[bit] = Some(outcome);
+                    self.register = (self.register & !(1 << bit)) | ((outcome as usize) << bit);
+                } else {
+                    self.classical[bit] = None;
+                    self.pending.push((*qubit, bit));
+                }
+            }
+            QuantumGate::Reset { qubit } => self.state.reset(*qubit, rng)?,
+            QuantumGate::IfBit { bit, gate } => {
+                if self.classical[*bit] == Some(true) {
+                    self.apply(gate, later, rng)?;
+                }
+            }
+            // Noiseless, like the state vector simulator
+            QuantumGate::Noisy { gate, .. } => self.apply(gate, later, rng)?,
+            // Snapshots copy a state vector, which this backend never builds
+            QuantumGate::Snapshot { .. } => {}
+            gate => self.state.apply_gate(gate)?,
+        }
+        Ok(())
+    }
+
+    /// Classical register if the final state is measured as `outcome`
+    fn register_for(&self, outcome: usize) -> usize {
+        self.pending
+            .iter()
+            .fold(self.register, |register, &(qubit, bit)| (register & !(1 << bit)) | (((outcome >> qubit) & 1) << bit))
+    }
+}
+
+/// Extended stabilizer backend, registered as `stabilizer`
+pub struct StabilizerBackend;
+
+impl StateBackend for StabilizerBackend {
+    fn name(&self) -> &str {
+        "stabilizer"
+    }
+
+    fn description(&self) -> &str {
+        "sum of CH-form stabilizer states; Clifford circuits with up to 16 T-type rotations, on up to 64 qubits"
+    }
+
+    fn probabilities(&self, circuit: &QuantumCircuit, seed: u64) -> Result<Vec<f64>, String> {
+        if circuit.num_qubits > MAX_DENSE_QUBITS {
+            return Err(format!(
+                "a probability vector of {} qubits has 2^{} entries; sample it with --shots instead (at most {} qubits)",
+                circuit.num_qubits, circuit.num_qubits, MAX_DENSE_QUBITS
+            ));
+        }
+        Ok(Run::new(circuit, &mut StdRng::seed_from_u64(seed))?.state.probabilities())
+    }
+
+    fn sample(&self, circuit: &QuantumCircuit, shots: usize, seed: u64) -> Result<Vec<Shot>, String> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        if !circuit.has_collapse() {
+            let run = Run::new(circuit, &mut rng)?;
+            return Ok(qsim::terminal_shots(circuit, run.state.sample(shots, &mut rng)));
+        }
+        (0..shots)
+            .map(|_| {
+                let run = Run::new(circuit, &mut rng)?;
+                let outcome = run.state.sample(1, &mut rng)[0];
+                Ok(Shot { outcome, register: run.register_for(outcome) })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The gate a step stands for, to rebuild a decomposition's matrix on the state vector
+    fn step_gate(step: Step) -> QuantumGate {
+        match step {
+            Step::H(qubit) => QuantumGate::Hadamard { qubit },
+            Step::S(qubit, times) => QuantumGate::Phase { qubit, angle: times as f64 * FRAC_PI_2 },
+            Step::CX(control, target) => QuantumGate::CNOT { control, target },
+            Step::CZ(control, target) => QuantumGate::CZ { control, target },
+            Step::Swap(qubit1, qubit2) => QuantumGate::SWAP { qubit1, qubit2 },
+            Step::Phase(qubit, angle) => QuantumGate::Phase { qubit, angle },
+        }
+    }
+
+    fn multiply(a: &[Vec<Complex>], b: &[Vec<Complex>]) -> Vec<Vec<Complex>> {
+        (0..a.len())
+            .map(|row| (0..b[0].len()).map(|col| (0..b.len()).fold(ZERO, |sum, k| sum + a[row][k] * b[k][col])).collect())
+            .collect()
+    }
+
+    /// Whether `a` = e^(iφ) `b` for some φ
+    fn equal_up_to_phase(a: &[Vec<Complex>], b: &[Vec<Complex>]) -> bool {
+        let (row, col) = (0..b.len() * b.len())
+            .map(|k| (k / b.len(), k % b.len()))
+            .max_by(|&(r1, c1), &(r2, c2)| b[r1][c1].magnitude_squared().total_cmp(&b[r2][c2].magnitude_squared()))
+            .unwrap();
+        // b[row][col] has modulus at least 1/dim, so dividing by it is safe
+        let phase = a[row][col] * b[row][col].conjugate();
+        let phase = scale(phase, 1.0 / b[row][col].magnitude_squared());
+        a.iter().flatten().zip(b.iter().flatten()).all(|(&x, &y)| {
+            let d = x + scale(phase * y, -1.0);
+            d.magnitude_squared() < 1e-18
+        })
+    }
+
+    #[test]
+    fn two_qubit_decompositions_match_gate_matrices() {
+        let gates = [
+            QuantumGate::CNOT { control: 1, target: 0 },
+            QuantumGate::CZ { control: 0, target: 1 },
+            QuantumGate::CPhase { control: 0, target: 1, angle: FRAC_PI_2 },
+            QuantumGate::SWAP { qubit1: 0, qubit2: 1 },
+            QuantumGate::ISWAP { qubit1: 0, qubit2: 1 },
+            QuantumGate::ECR { qubit1: 0, qubit2: 1 },
+            QuantumGate::ECR { qubit1: 1, qubit2: 0 },
+            QuantumGate::RZZ { qubit1: 0, qubit2: 1, angle: 0.3 },
+            QuantumGate::RXX { qubit1: 0, qubit2: 1, angle: -1.1 },
+            QuantumGate::RYY { qubit1: 0, qubit2: 1, angle: 0.7 },
+        ];
+        for gate in &gates {
+            let identity: Vec<Vec<Complex>> =
+                (0..4).map(|row| (0..4).map(|col| if row == col { ONE } else { ZERO }).collect()).collect();
+            let decomposed = gate_steps(gate)
+                .unwrap()
+                .into_iter()
+                .fold(identity, |matrix, step| multiply(&qsim::gate_matrix(&step_gate(step), &[0, 1]), &matrix));
+            assert!(equal_up_to_phase(&decomposed, &qsim::gate_matrix(gate, &[0, 1])), "{:?}", gate);
+        }
+    }
+}
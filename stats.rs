@@ -0,0 +1,195 @@
+//! Stats Module
+//! Goodness-of-fit tests for sampled bitstrings against ideal probabilities
+
+use std::collections::BTreeMap;
+use serde::Serialize;
+
+/// Smallest expected count a χ² bin may have; sparser states are pooled together
+const MIN_EXPECTED_COUNT: f64 = 5.0;
+
+/// Outcome of checking a set of samples against an ideal distribution
+#[derive(Debug, Clone, Serialize)]
+pub struct SampleTest {
+    pub shots: usize,
+    pub chi_squared: f64,
+    pub degrees_of_freedom: usize,
+    /// Probability of a χ² at least this large if the samples follow the ideal distribution
+    pub p_value: f64,
+    /// Linear cross-entropy benchmark fidelity: 2^n * mean p(x) - 1
+    pub linear_xeb: f64,
+    /// Fraction of samples on heavy states (ideal probability above the median)
+    pub heavy_output_probability: f64,
+    /// Heavy-output probability of exact sampling; about (1 + ln 2) / 2 for Porter-Thomas outputs
+    pub ideal_heavy_output_probability: f64,
+    /// Fidelity F of a depolarized sampler matching the observed heavy-output probability:
+    /// HOP = F * ideal HOP + (1 - F) / 2
+    pub effective_fidelity: f64,
+}
+
+/// Test `counts` (basis state -> times observed) against `probabilities`
+pub fn evaluate(probabilities: &[f64], counts: &BTreeMap<usize, usize>) -> Result<SampleTest, String> {
+    let shots: usize = counts.values().sum();
+    if shots == 0 {
+        return Err("no samples to test".to_string());
+    }
+    if let Some(&state) = counts.keys().find(|&&state| state >= probabilities.len()) {
+        return Err(format!("sampled state {} is outside the {} basis states", state, probabilities.len()));
+    }
+
+    let (chi_squared, degrees_of_freedom) = chi_squared(probabilities, counts, shots);
+    let mean_probability = counts
+        .iter()
+        .map(|(&state, &count)| probabilities[state] * count as f64)
+        .sum::<f64>()
+        / shots as f64;
+
+    let median = median(probabilities);
+    let heavy_output_probability = counts
+        .iter()
+        .filter(|(&state, _)| probabilities[state] > median)
+        .map(|(_, &count)| count as f64)
+        .sum::<f64>()
+        / shots as f64;
+    let ideal_heavy_output_probability: f64 = probabilities.iter().filter(|&&p| p > median).sum();
+    // Flat distributions have no heavy states, so nothing separates signal from noise
+    let effective_fidelity = if ideal_heavy_output_probability > 0.5 {
+        (heavy_output_probability - 0.5) / (ideal_heavy_output_probability - 0.5)
+    } else {
+        f64::NAN
+    };
+
+    Ok(SampleTest {
+        shots,
+        chi_squared,
+        degrees_of_freedom,
+        p_value: chi_squared_survival(chi_squared, degrees_of_freedom),
+        linear_xeb: probabilities.len() as f64 * mean_probability - 1.0,
+        heavy_output_probability,
+        ideal_heavy_output_probability,
+        effective_fidelity,
+    })
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable_by(f64::total_cmp);
+    let middle = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        sorted[middle]
+    } else {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    }
+}
+
+/// Tally sampled basis-state indices
+pub fn count_outcomes(outcomes: &[usize]) -> BTreeMap<usize, usize> {
+    let mut counts = BTreeMap::new();
+    for &outcome in outcomes {
+        *counts.entry(outcome).or_default() += 1;
+    }
+    counts
+}
+
+/// Pearson χ² with sparse states pooled into one bin
+fn chi_squared(probabilities: &[f64], counts: &BTreeMap<usize, usize>, shots: usize) -> (f64, usize) {
+    let mut statistic = 0.0;
+    let mut bins = 0;
+    let (mut pooled_expected, mut pooled_observed) = (0.0, 0.0);
+
+    for (state, &probability) in probabilities.iter().enumerate() {
+        let expected = probability * shots as f64;
+        let observed = counts.get(&state).copied().unwrap_or(0) as f64;
+        if expected >= MIN_EXPECTED_COUNT {
+            statistic += (observed - expected).powi(2) / expected;
+            bins += 1;
+        } else {
+            pooled_expected += expected;
+            pooled_observed += observed;
+        }
+    }
+    if pooled_expected > 0.0 {
+        statistic += (pooled_observed - pooled_expected).powi(2) / pooled_expected;
+        bins += 1;
+    } else if pooled_observed > 0.0 {
+        // Samples on states the ideal distribution rules out
+        statistic = f64::INFINITY;
+    }
+    (statistic, bins.max(2) - 1)
+}
+
+/// Upper tail of the χ² distribution, Q(k/2, x/2)
+fn chi_squared_survival(statistic: f64, degrees_of_freedom: usize) -> f64 {
+    if statistic.is_infinite() {
+        return 0.0;
+    }
+    regularized_gamma_q(degrees_of_freedom as f64 / 2.0, statistic / 2.0)
+}
+
+/// Regularized upper incomplete gamma function (series below a + 1, continued fraction above)
+fn regularized_gamma_q(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let log_prefactor = a * x.ln() - x - ln_gamma(a);
+    if x < a + 1.0 {
+        let (mut term, mut sum, mut n) = (1.0 / a, 1.0 / a, a);
+        for _ in 0..500 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        (1.0 - sum * log_prefactor.exp()).max(0.0)
+    } else {
+        // Lentz's method for the continued fraction
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..500 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            d = if d.abs() < tiny { tiny } else { d };
+            c = b + an / c;
+            c = if c.abs() < tiny { tiny } else { c };
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        (log_prefactor.exp() * h).min(1.0)
+    }
+}
+
+/// ln Γ(x) by the Lanczos approximation (g = 7)
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| sum + c / (x + i as f64 + 1.0));
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
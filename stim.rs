@@ -0,0 +1,363 @@
+//! Stim Module
+//! Import and export of Stim circuit text for Clifford and error-correction workloads
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::f64::consts::{FRAC_PI_2, PI};
+use crate::qsim::{QuantumCircuit, QuantumGate};
+
+/// Instructions that carry no quantum operation (annotations and noise channels)
+const IGNORED: &[&str] = &[
+    "TICK", "DETECTOR", "OBSERVABLE_INCLUDE", "QUBIT_COORDS", "SHIFT_COORDS",
+    "X_ERROR", "Y_ERROR", "Z_ERROR", "DEPOLARIZE1", "DEPOLARIZE2", "PAULI_CHANNEL_1",
+    "PAULI_CHANNEL_2", "E", "ELSE_CORRELATED_ERROR", "CORRELATED_ERROR",
+];
+
+/// Largest distance from a multiple of pi/2 at which a rotation still counts as Clifford
+const ANGLE_TOLERANCE: f64 = 1e-9;
+
+/// Export a Clifford circuit as Stim text
+///
+/// Rotations by multiples of pi/2 are written as their Clifford equivalents, which match up
+/// to global phase. A gate conditioned on a measured X, Y or Z becomes a `rec[-k]`
+/// controlled Pauli, and a gate with a `noise` error override is followed by a
+/// depolarizing channel of that strength. Anything else is rejected.
+pub fn to_stim(circuit: &QuantumCircuit) -> Result<String, String> {
+    let mut out = String::new();
+    for (key, value) in &circuit.metadata {
+        out.push_str(&format!("# @{}: {}\n", key, value.replace('\n', " ")));
+    }
+    let mut records = Vec::new();
+    for (index, gate) in circuit.gates.iter().enumerate() {
+        for line in stim_lines(gate, &mut records).map_err(|e| format!("gate {}: {}", index, e))? {
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    // Stim sizes a circuit by its highest target, so an idle last qubit is kept with an identity
+    let highest = circuit.gates.iter().flat_map(|gate| gate.qubits()).max();
+    if circuit.num_qubits > 0 && highest != Some(circuit.num_qubits - 1) {
+        out.push_str(&format!("I {}\n", circuit.num_qubits - 1));
+    }
+    Ok(out)
+}
+
+/// Stim instructions for one gate. `records` lists the register bit of every measurement so
+/// far, which is how `rec[-k]` targets are counted.
+fn stim_lines(gate: &QuantumGate, records: &mut Vec<usize>) -> Result<Vec<String>, String> {
+    let line = match gate {
+        QuantumGate::Hadamard { qubit } => format!("H {}", qubit),
+        QuantumGate::PauliX { qubit } => format!("X {}", qubit),
+        QuantumGate::PauliY { qubit } => format!("Y {}", qubit),
+        QuantumGate::PauliZ { qubit } => format!("Z {}", qubit),
+        QuantumGate::S { qubit } => format!("S {}", qubit),
+        QuantumGate::Sdg { qubit } => format!("S_DAG {}", qubit),
+        QuantumGate::SX { qubit } => format!("SQRT_X {}", qubit),
+        QuantumGate::SXdg { qubit } => format!("SQRT_X_DAG {}", qubit),
+        QuantumGate::Phase { qubit, angle } | QuantumGate::RotationZ { qubit, angle } => {
+            return Ok(rotation(*qubit, *angle, ["S", "Z", "S_DAG"])?.into_iter().collect());
+        }
+        QuantumGate::RotationX { qubit, angle } => {
+            return Ok(rotation(*qubit, *angle, ["SQRT_X", "X", "SQRT_X_DAG"])?.into_iter().collect());
+        }
+        QuantumGate::RotationY { qubit, angle } => {
+            return Ok(rotation(*qubit, *angle, ["SQRT_Y", "Y", "SQRT_Y_DAG"])?.into_iter().collect());
+        }
+        QuantumGate::CNOT { control, target } => format!("CX {} {}", control, target),
+        QuantumGate::CZ { control, target } => format!("CZ {} {}", control, target),
+        QuantumGate::SWAP { qubit1, qubit2 } => format!("SWAP {} {}", qubit1, qubit2),
+        QuantumGate::ISWAP { qubit1, qubit2 } => format!("ISWAP {} {}", qubit1, qubit2),
+        QuantumGate::GlobalHadamard { qubits } => {
+            format!("H {}", qubits.iter().map(|q| q.to_string()).collect::<Vec<_>>().join(" "))
+        }
+        QuantumGate::Controlled { control, gate } => match gate.as_ref() {
+            QuantumGate::PauliX { qubit } => format!("CX {} {}", control, qubit),
+            QuantumGate::PauliY { qubit } => format!("CY {} {}", control, qubit),
+            QuantumGate::PauliZ { qubit } => format!("CZ {} {}", control, qubit),
+            _ => return Err("Stim only controls X, Y and Z".to_string()),
+        },
+        QuantumGate::IfBit { bit, gate } => {
+            let back = records
+                .iter()
+                .rev()
+                .position(|&stored| stored == *bit)
+                .ok_or_else(|| format!("condition on bit {}, which no earlier measurement stores", bit))?;
+            match gate.as_ref() {
+                QuantumGate::PauliX { qubit } => format!("CX rec[-{}] {}", back + 1, qubit),
+                QuantumGate::PauliY { qubit } => format!("CY rec[-{}] {}", back + 1, qubit),
+                QuantumGate::PauliZ { qubit } => format!("CZ rec[-{}] {}", back + 1, qubit),
+                _ => return Err("Stim feedback can only apply X, Y or Z".to_string()),
+            }
+        }
+        QuantumGate::Noisy { noise, gate } => {
+            let mut lines = stim_lines(gate, records)?;
+            let qubits = gate.qubits();
+            let targets = qubits.iter().map(|q| q.to_string()).collect::<Vec<_>>().join(" ");
+            if let Some(error) = noise.error.filter(|&error| error > 0.0) {
+                match qubits.len() {
+                    1 => lines.push(format!("DEPOLARIZE1({}) {}", error, targets)),
+                    2 => lines.push(format!("DEPOLARIZE2({}) {}", error, targets)),
+                    count => return Err(format!("Stim has no {}-qubit depolarizing channel", count)),
+                }
+            }
+            return Ok(lines);
+        }
+        QuantumGate::Measurement { qubit, bit } => {
+            records.push(bit.unwrap_or(*qubit));
+            format!("M {}", qubit)
+        }
+        QuantumGate::Reset { qubit } => format!("R {}", qubit),
+        // Kept as a comment for readers; import skips it
+        QuantumGate::Snapshot { label } => format!("# snapshot {}", label),
+        other => {
+            let name = serde_json::to_value(other)
+                .ok()
+                .and_then(|gate| gate["type"].as_str().map(str::to_string))
+                .unwrap_or_default();
+            return Err(format!("{} is not a Clifford operation Stim can express", name));
+        }
+    };
+    Ok(vec![line])
+}
+
+/// Stim instruction for a rotation by a multiple of pi/2, given the names of the quarter,
+/// half and three-quarter turns; none for a whole turn
+fn rotation(qubit: usize, angle: f64, names: [&str; 3]) -> Result<Option<String>, String> {
+    let turns = angle / FRAC_PI_2;
+    if (turns - turns.round()).abs() * FRAC_PI_2 > ANGLE_TOLERANCE {
+        return Err(format!("a rotation by {} is not Clifford; Stim needs multiples of pi/2", angle));
+    }
+    let quarter_turns = turns.round().rem_euclid(4.0) as usize;
+    Ok(quarter_turns.checked_sub(1).map(|k| format!("{} {}", names[k], qubit)))
+}
+
+/// Parse Stim circuit text into a circuit
+pub fn parse_stim(source: &str) -> Result<QuantumCircuit, String> {
+    let lines: Vec<&str> = source
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .collect();
+    let instructions = expand_repeats(&lines)?;
+
+    let mut builder = StimBuilder::default();
+    for line in &instructions {
+        builder.apply(line)?;
+    }
+
+    // Metadata written by `to_stim` comes back from `# @key: value` comments
+    let mut metadata: BTreeMap<String, String> = source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("# @")?.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+    metadata.insert("source_format".to_string(), "stim".to_string());
+    if !builder.ignored.is_empty() {
+        let ignored: Vec<&str> = builder.ignored.iter().map(|s| s.as_str()).collect();
+        metadata.insert("stim_ignored".to_string(), ignored.join(","));
+    }
+
+    Ok(QuantumCircuit {
+        num_qubits: builder.num_qubits,
+        gates: builder.gates,
+        metadata,
+    })
+}
+
+/// Flatten `REPEAT n { ... }` blocks into a plain instruction list
+pub(crate) fn expand_repeats(lines: &[&str]) -> Result<Vec<String>, String> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if let Some(rest) = line.strip_prefix("REPEAT") {
+            let count = rest
+                .trim()
+                .trim_end_matches('{')
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| format!("invalid REPEAT count in '{}'", line))?;
+            let mut depth = 1;
+            let start = i + 1;
+            let mut end = start;
+            while end < lines.len() {
+                if lines[end].ends_with('{') {
+                    depth += 1;
+                } else if lines[end] == "}" {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                end += 1;
+            }
+            if depth != 0 {
+                return Err("unterminated REPEAT block".to_string());
+            }
+            let body = expand_repeats(&lines[start..end])?;
+            for _ in 0..count {
+                out.extend(body.iter().cloned());
+            }
+            i = end + 1;
+        } else if line == "}" {
+            return Err("unmatched '}'".to_string());
+        } else {
+            out.push(line.to_string());
+            i += 1;
+        }
+    }
+    Ok(out)
+}
+
+/// Operand of a Stim instruction: a qubit, or `rec[-k]`, the k-th most recent measurement
+enum Target {
+    Qubit(usize),
+    Record(usize),
+}
+
+#[derive(Default)]
+struct StimBuilder {
+    num_qubits: usize,
+    gates: Vec<QuantumGate>,
+    touched: HashSet<usize>,
+    ignored: BTreeSet<String>,
+    /// Qubit of every measurement so far, in record order
+    records: Vec<usize>,
+}
+
+impl StimBuilder {
+    fn push(&mut self, gate: QuantumGate) -> Result<(), String> {
+        for q in gate.qubits() {
+            self.touched.insert(q);
+            self.num_qubits = self.num_qubits.max(q + 1);
+        }
+        self.gates.push(gate);
+        Ok(())
+    }
+
+    fn measure(&mut self, qubit: usize) -> Result<(), String> {
+        self.records.push(qubit);
+        self.push(QuantumGate::Measurement { qubit, bit: None })
+    }
+
+    /// Resets of fresh qubits are dropped, since they already start in |0>
+    fn reset(&mut self, qubit: usize) -> Result<(), String> {
+        self.num_qubits = self.num_qubits.max(qubit + 1);
+        if self.touched.contains(&qubit) {
+            self.push(QuantumGate::Reset { qubit })?;
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, line: &str) -> Result<(), String> {
+        let (head, rest) = match line.find(char::is_whitespace) {
+            Some(pos) => (&line[..pos], line[pos..].trim()),
+            None => (line, ""),
+        };
+        let name = head.split('(').next().unwrap_or(head).to_uppercase();
+        if IGNORED.contains(&name.as_str()) {
+            self.ignored.insert(name);
+            return Ok(());
+        }
+
+        let targets = rest
+            .split_whitespace()
+            .map(|t| {
+                let record = t.strip_prefix("rec[-").and_then(|k| k.strip_suffix(']'));
+                match record {
+                    Some(k) => k.parse::<usize>().ok().filter(|&k| k > 0).map(Target::Record),
+                    None => t.trim_start_matches('!').parse::<usize>().ok().map(Target::Qubit),
+                }
+                .ok_or_else(|| format!("unsupported target '{}' in '{}'", t, line))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match name.as_str() {
+            "H" | "X" | "Y" | "Z" | "S" | "S_DAG" | "SQRT_Z" | "SQRT_Z_DAG" | "SQRT_X" | "SQRT_X_DAG"
+            | "SQRT_Y" | "SQRT_Y_DAG" | "M" | "MZ" | "R" | "RZ" | "MR" | "MRZ" | "I" => {
+                for target in &targets {
+                    match target {
+                        Target::Qubit(q) => self.single(&name, *q)?,
+                        Target::Record(_) => return Err(format!("'{}' cannot target a measurement record", line)),
+                    }
+                }
+            }
+            "CX" | "CNOT" | "ZCX" | "CY" | "ZCY" | "CZ" | "ZCZ" | "SWAP" | "ISWAP" => {
+                if targets.len() % 2 != 0 {
+                    return Err(format!("odd number of targets in '{}'", line));
+                }
+                for pair in targets.chunks(2) {
+                    match (&pair[0], &pair[1]) {
+                        (Target::Qubit(a), Target::Qubit(b)) => self.pair(&name, *a, *b)?,
+                        (Target::Record(back), Target::Qubit(q)) if name != "SWAP" && name != "ISWAP" => {
+                            self.feedback(&name, *back, *q)?
+                        }
+                        // CZ is symmetric, so the record may come second
+                        (Target::Qubit(q), Target::Record(back)) if name.ends_with("CZ") => self.feedback(&name, *back, *q)?,
+                        _ => return Err(format!("unsupported measurement record use in '{}'", line)),
+                    }
+                }
+            }
+            _ => return Err(format!("unsupported Stim instruction '{}'", name)),
+        }
+        Ok(())
+    }
+
+    /// Classically controlled Pauli from `CX rec[-k] q` and friends, conditioned on the register
+    /// bit of the record's measurement, which is its qubit's own
+    fn feedback(&mut self, name: &str, back: usize, qubit: usize) -> Result<(), String> {
+        let position = self
+            .records
+            .len()
+            .checked_sub(back)
+            .ok_or_else(|| format!("rec[-{}] comes before the first measurement", back))?;
+        let measured = self.records[position];
+        // A condition reads the latest measurement stored in a bit, so older records cannot be named
+        if self.records[position + 1..].contains(&measured) {
+            return Err(format!("rec[-{}] is an earlier measurement of qubit {}, which was measured again since", back, measured));
+        }
+        let gate = match name {
+            "CX" | "CNOT" | "ZCX" => QuantumGate::PauliX { qubit },
+            "CY" | "ZCY" => QuantumGate::PauliY { qubit },
+            _ => QuantumGate::PauliZ { qubit },
+        };
+        self.push(QuantumGate::IfBit { bit: measured, gate: Box::new(gate) })
+    }
+
+    fn single(&mut self, name: &str, qubit: usize) -> Result<(), String> {
+        match name {
+            "H" => self.push(QuantumGate::Hadamard { qubit }),
+            "X" => self.push(QuantumGate::PauliX { qubit }),
+            "Y" => self.push(QuantumGate::PauliY { qubit }),
+            "Z" => self.push(QuantumGate::PauliZ { qubit }),
+            "S" | "SQRT_Z" => self.push(QuantumGate::S { qubit }),
+            "S_DAG" | "SQRT_Z_DAG" => self.push(QuantumGate::Sdg { qubit }),
+            "SQRT_X" => self.push(QuantumGate::SX { qubit }),
+            "SQRT_X_DAG" => self.push(QuantumGate::SXdg { qubit }),
+            "SQRT_Y" => self.push(QuantumGate::RotationY { qubit, angle: PI / 2.0 }),
+            "SQRT_Y_DAG" => self.push(QuantumGate::RotationY { qubit, angle: -PI / 2.0 }),
+            "M" | "MZ" => self.measure(qubit),
+            "R" | "RZ" => self.reset(qubit),
+            "MR" | "MRZ" => {
+                self.measure(qubit)?;
+                self.reset(qubit)
+            }
+            // Identities only widen the register, keeping idle qubits from `to_stim`
+            "I" => {
+                self.num_qubits = self.num_qubits.max(qubit + 1);
+                Ok(())
+            }
+            _ => unreachable!("single-qubit instruction list is checked by the caller"),
+        }
+    }
+
+    fn pair(&mut self, name: &str, a: usize, b: usize) -> Result<(), String> {
+        match name {
+            "CX" | "CNOT" | "ZCX" => self.push(QuantumGate::CNOT { control: a, target: b }),
+            "CY" | "ZCY" => self.push(QuantumGate::Controlled { control: a, gate: Box::new(QuantumGate::PauliY { qubit: b }) }),
+            "CZ" | "ZCZ" => self.push(QuantumGate::CZ { control: a, target: b }),
+            "SWAP" => self.push(QuantumGate::SWAP { qubit1: a, qubit2: b }),
+            "ISWAP" => self.push(QuantumGate::ISWAP { qubit1: a, qubit2: b }),
+            _ => unreachable!("two-qubit instruction list is checked by the caller"),
+        }
+    }
+}
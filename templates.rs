@@ -0,0 +1,109 @@
+//! Circuit Templates Module
+//! User-defined rewrite rules (pattern circuit -> replacement circuit)
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use serde::{Deserialize, Serialize};
+use crate::qsim::{QuantumCircuit, QuantumGate};
+
+/// A rewrite rule; qubit indices in both circuits are template variables
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RewriteRule {
+    pub name: String,
+    pub pattern: Vec<QuantumGate>,
+    pub replacement: Vec<QuantumGate>,
+}
+
+impl RewriteRule {
+    /// Check that the rule is well formed
+    pub fn validate(&self) -> Result<(), String> {
+        if self.pattern.is_empty() {
+            return Err(format!("rule '{}' has an empty pattern", self.name));
+        }
+        let bound: Vec<usize> = self.pattern.iter().flat_map(|gate| gate.qubits()).collect();
+        for gate in &self.replacement {
+            if let Some(q) = gate.qubits().into_iter().find(|q| !bound.contains(q)) {
+                return Err(format!(
+                    "rule '{}' uses qubit variable {} in its replacement but not in its pattern",
+                    self.name, q
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Try to match the pattern against the gates starting at `start`
+    fn match_at(&self, gates: &[QuantumGate], start: usize) -> Option<HashMap<usize, usize>> {
+        let window = gates.get(start..start + self.pattern.len())?;
+        let mut bindings: HashMap<usize, usize> = HashMap::new();
+
+        for (pattern_gate, gate) in self.pattern.iter().zip(window) {
+            let pattern_qubits = pattern_gate.qubits();
+            let qubits = gate.qubits();
+            if pattern_qubits.len() != qubits.len() {
+                return None;
+            }
+            for (&var, &qubit) in pattern_qubits.iter().zip(&qubits) {
+                match bindings.get(&var) {
+                    Some(&bound) if bound != qubit => return None,
+                    Some(_) => {}
+                    None => {
+                        // Distinct variables must bind distinct qubits
+                        if bindings.values().any(|&q| q == qubit) {
+                            return None;
+                        }
+                        bindings.insert(var, qubit);
+                    }
+                }
+            }
+            if pattern_gate.map_qubits(|var| bindings[&var]) != *gate {
+                return None;
+            }
+        }
+
+        Some(bindings)
+    }
+}
+
+/// Load rewrite rules from a JSON or YAML file
+pub fn load_rules(path: &str) -> Result<Vec<RewriteRule>, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    let rules: Vec<RewriteRule> = if path.ends_with(".yaml") || path.ends_with(".yml") {
+        serde_yaml::from_str(&contents)?
+    } else {
+        serde_json::from_str(&contents)?
+    };
+    for rule in &rules {
+        rule.validate()?;
+    }
+    Ok(rules)
+}
+
+/// Apply rules left to right, substituting each non-overlapping match
+pub fn apply_rules(circuit: &QuantumCircuit, rules: &[RewriteRule]) -> QuantumCircuit {
+    let mut gates = Vec::with_capacity(circuit.gates.len());
+    let mut i = 0;
+
+    'scan: while i < circuit.gates.len() {
+        for rule in rules {
+            if let Some(bindings) = rule.match_at(&circuit.gates, i) {
+                gates.extend(
+                    rule.replacement
+                        .iter()
+                        .map(|gate| gate.map_qubits(|var| bindings[&var])),
+                );
+                i += rule.pattern.len();
+                continue 'scan;
+            }
+        }
+        gates.push(circuit.gates[i].clone());
+        i += 1;
+    }
+
+    QuantumCircuit {
+        num_qubits: circuit.num_qubits,
+        gates,
+        metadata: circuit.metadata.clone(),
+    }
+}
@@ -0,0 +1,201 @@
+//! Tomography Module
+//! Measurement circuits, expectation data and linear-inversion estimates for state tomography
+
+use std::collections::BTreeMap;
+use serde::Serialize;
+use crate::gpu_ops::Complex;
+use crate::observable::{PauliSum, PauliTerm};
+use crate::qsim::{self, QuantumCircuit, QuantumGate};
+
+/// Most qubits a tomography covers: 3^6 = 729 measurement settings and 4^6 Pauli strings
+pub const MAX_QUBITS: usize = 6;
+
+/// One measurement setting: the circuit with the tomography qubits rotated into `bases` and
+/// measured, `bases[k]` (`X`, `Y` or `Z`) being the basis of the k-th tomography qubit
+pub struct Setting {
+    pub bases: String,
+    pub circuit: QuantumCircuit,
+}
+
+/// Outcomes of one setting, keyed by bit string with the first tomography qubit rightmost
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingCounts {
+    pub bases: String,
+    pub counts: BTreeMap<String, usize>,
+}
+
+/// Expectation value of one Pauli string on the tomography qubits, pooled over every setting
+/// that measures it; `pauli[k]` acts on the k-th tomography qubit
+#[derive(Debug, Clone, Serialize)]
+pub struct PauliExpectation {
+    pub pauli: String,
+    pub mean: f64,
+    pub std_error: f64,
+    pub shots: usize,
+}
+
+/// Everything measured for a tomography of `qubits`
+#[derive(Debug, Clone, Serialize)]
+pub struct TomographyData {
+    pub qubits: Vec<usize>,
+    pub shots_per_setting: usize,
+    pub settings: Vec<SettingCounts>,
+    /// All 4^k Pauli strings, the identity first
+    pub expectations: Vec<PauliExpectation>,
+}
+
+/// Check that a qubit subset is non-empty, distinct, in range and small enough
+pub fn validate_qubits(qubits: &[usize], num_qubits: usize) -> Result<(), String> {
+    if qubits.is_empty() {
+        return Err("tomography needs at least one qubit".to_string());
+    }
+    if qubits.len() > MAX_QUBITS {
+        return Err(format!(
+            "tomography of {} qubits needs {} settings; at most {} qubits are supported",
+            qubits.len(),
+            3usize.pow(qubits.len() as u32),
+            MAX_QUBITS
+        ));
+    }
+    if let Some(qubit) = qubits.iter().find(|&&qubit| qubit >= num_qubits) {
+        return Err(format!("qubit {} is out of range for {} qubits", qubit, num_qubits));
+    }
+    if (1..qubits.len()).any(|i| qubits[..i].contains(&qubits[i])) {
+        return Err("the qubit list has a qubit twice".to_string());
+    }
+    Ok(())
+}
+
+/// The circuit without its terminal measurements, the state tomography measures. A final
+/// readout would otherwise collapse the qubits before the basis rotations.
+pub fn prepared(circuit: &QuantumCircuit) -> QuantumCircuit {
+    let gates = circuit
+        .gates
+        .iter()
+        .enumerate()
+        .filter(|(index, gate)| match gate {
+            QuantumGate::Measurement { qubit, bit } => circuit.gates[index + 1..]
+                .iter()
+                .any(|later| later.qubits().contains(qubit) || qsim::condition_bits(later).contains(&bit.unwrap_or(*qubit))),
+            _ => true,
+        })
+        .map(|(_, gate)| gate.clone())
+        .collect();
+    QuantumCircuit { gates, ..circuit.clone() }
+}
+
+/// Every basis assignment of `k` qubits over `alphabet`, the first qubit varying fastest
+fn labels(alphabet: &[char], k: usize) -> Vec<String> {
+    let count = alphabet.len().pow(k as u32);
+    (0..count)
+        .map(|index| (0..k).map(|j| alphabet[index / alphabet.len().pow(j as u32) % alphabet.len()]).collect())
+        .collect()
+}
+
+/// Gates rotating each tomography qubit from its basis in `bases` onto Z
+fn basis_change(qubits: &[usize], bases: &str, num_qubits: usize) -> Vec<QuantumGate> {
+    let mut pauli = vec!['I'; num_qubits];
+    for (&qubit, basis) in qubits.iter().zip(bases.chars()) {
+        pauli[qubit] = basis;
+    }
+    PauliTerm { coefficient: 1.0, pauli: pauli.into_iter().collect() }.basis_change()
+}
+
+/// The 3^k measurement circuits of a tomography of `qubits`: the prepared circuit, the basis
+/// rotations, then a measurement of tomography qubit k into classical bit k
+pub fn settings(circuit: &QuantumCircuit, qubits: &[usize]) -> Result<Vec<Setting>, String> {
+    validate_qubits(qubits, circuit.num_qubits)?;
+    let base = prepared(circuit);
+    let name = circuit.metadata.get("name").cloned().unwrap_or_else(|| "circuit".to_string());
+    Ok(labels(&['X', 'Y', 'Z'], qubits.len())
+        .into_iter()
+        .map(|bases| {
+            let mut setting = base.clone();
+            setting.gates.extend(basis_change(qubits, &bases, circuit.num_qubits));
+            setting.gates.extend(
+                qubits.iter().enumerate().map(|(bit, &qubit)| QuantumGate::Measurement { qubit, bit: Some(bit) }),
+            );
+            setting.metadata.insert("name".to_string(), format!("{}-tomography-{}", name, bases));
+            Setting { bases, circuit: setting }
+        })
+        .collect())
+}
+
+/// Measure every setting with `shots` shots and pool the outcomes into the expectation value
+/// of every Pauli string on `qubits`.
+///
+/// `sample(gates, n)` returns n logical basis-state outcomes of `prepared(circuit)` with
+/// `gates` appended, as `observable::sample_expectation` takes them.
+pub fn collect<F>(circuit: &QuantumCircuit, qubits: &[usize], shots: usize, mut sample: F) -> Result<TomographyData, String>
+where
+    F: FnMut(&[QuantumGate], usize) -> Vec<usize>,
+{
+    validate_qubits(qubits, circuit.num_qubits)?;
+    if shots == 0 {
+        return Err("tomography needs at least one shot per setting".to_string());
+    }
+    let k = qubits.len();
+    let bases = labels(&['X', 'Y', 'Z'], k);
+    // Outcome tallies of each setting, indexed by subsystem basis state
+    let tallies: Vec<Vec<usize>> = bases
+        .iter()
+        .map(|bases| {
+            let mut tally = vec![0; 1 << k];
+            for outcome in sample(&basis_change(qubits, bases, circuit.num_qubits), shots) {
+                let local = qubits.iter().enumerate().fold(0, |local, (i, &qubit)| local | (((outcome >> qubit) & 1) << i));
+                tally[local] += 1;
+            }
+            tally
+        })
+        .collect();
+
+    let expectations = labels(&['I', 'X', 'Y', 'Z'], k)
+        .into_iter()
+        .map(|pauli| {
+            let mask = pauli.chars().enumerate().fold(0, |mask, (i, p)| if p == 'I' { mask } else { mask | (1 << i) });
+            // A setting measures the string when it agrees on every non-identity factor
+            let (mut n, mut sum) = (0usize, 0i64);
+            for (setting, tally) in bases.iter().zip(&tallies) {
+                if !pauli.chars().zip(setting.chars()).all(|(p, basis)| p == 'I' || p == basis) {
+                    continue;
+                }
+                for (local, &count) in tally.iter().enumerate() {
+                    n += count;
+                    sum += if (local & mask).count_ones() % 2 == 1 { -(count as i64) } else { count as i64 };
+                }
+            }
+            let mean = if mask == 0 { 1.0 } else { sum as f64 / n.max(1) as f64 };
+            let std_error = if mask == 0 { 0.0 } else { ((1.0 - mean * mean) / n.max(1) as f64).sqrt() };
+            PauliExpectation { pauli, mean, std_error, shots: n }
+        })
+        .collect();
+
+    let settings = bases
+        .into_iter()
+        .zip(&tallies)
+        .map(|(bases, tally)| SettingCounts {
+            bases,
+            counts: tally
+                .iter()
+                .enumerate()
+                .filter(|&(_, &count)| count > 0)
+                .map(|(local, &count)| (format!("{:0width$b}", local, width = k), count))
+                .collect(),
+        })
+        .collect();
+    Ok(TomographyData { qubits: qubits.to_vec(), shots_per_setting: shots, settings, expectations })
+}
+
+/// Linear-inversion estimate ρ = 2^-k Σ ⟨P⟩ P of the tomography qubits' density matrix, with
+/// the first tomography qubit as the least significant bit. Shot noise can leave it slightly
+/// outside the physical states (a small negative eigenvalue).
+pub fn linear_inversion(data: &TomographyData) -> Vec<Vec<Complex>> {
+    let k = data.qubits.len();
+    let scale = 1.0 / (1usize << k) as f64;
+    let terms = data
+        .expectations
+        .iter()
+        .map(|expectation| PauliTerm { coefficient: expectation.mean * scale, pauli: expectation.pauli.clone() })
+        .collect();
+    PauliSum { terms }.to_matrix(k)
+}
@@ -0,0 +1,235 @@
+//! Parameter Tuning Module
+//! Terminal sliders for the named parameters of a variational circuit, re-simulated on every change
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::io::{self, IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+use serde_json::Value;
+use crate::incremental::IncrementalSimulator;
+use crate::observable::PauliSum;
+use crate::qsim::{self, QuantumCircuit};
+use crate::{outln, params};
+
+/// Largest circuit re-simulated on every key press
+pub const MAX_QUBITS: usize = 16;
+
+/// Basis states shown in the histogram
+const HISTOGRAM_ROWS: usize = 16;
+
+/// Width of a slider track in characters
+const SLIDER_WIDTH: usize = 21;
+
+/// Live state of a tuning session
+struct Tuner {
+    file_path: String,
+    document: Value,
+    defaults: BTreeMap<String, f64>,
+    values: BTreeMap<String, f64>,
+    selected: usize,
+    step: f64,
+    observable: PauliSum,
+    simulator: IncrementalSimulator,
+    error: Option<String>,
+}
+
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Char(char),
+}
+
+/// Keeps the terminal unbuffered and silent while alive, restoring its settings on drop
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    /// Switch the terminal to key-at-a-time input; `None` when stdin is not a terminal or
+    /// `stty` is unavailable, in which case keys arrive a line at a time
+    fn enable() -> Option<Self> {
+        if !io::stdin().is_terminal() {
+            return None;
+        }
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "min", "1"])?;
+        Some(Self { saved: saved.trim().to_string() })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Run an interactive tuning session on a parameterized circuit until the user quits
+pub fn run(file_path: &str, observable: Option<&str>, step: f64) -> Result<(), Box<dyn Error>> {
+    let document = params::read_document(file_path)?;
+    let defaults = params::defaults(&document)?;
+    if defaults.is_empty() {
+        return Err("circuit declares no parameters; add a \"parameters\" map of names to default values".into());
+    }
+    let circuit = params::instantiate(&document, &BTreeMap::new())?;
+    qsim::validate_circuit(&circuit)?;
+    if circuit.num_qubits > MAX_QUBITS {
+        return Err(format!("tune re-simulates on every key press and is limited to {} qubits", MAX_QUBITS).into());
+    }
+    let observable = match observable {
+        Some(text) => PauliSum::parse(text)?,
+        None => PauliSum::parse(&"Z".repeat(circuit.num_qubits))?,
+    };
+    observable.validate(circuit.num_qubits)?;
+
+    let mut tuner = Tuner {
+        file_path: file_path.to_string(),
+        document,
+        values: defaults.clone(),
+        defaults,
+        selected: 0,
+        step,
+        observable,
+        simulator: IncrementalSimulator::new(circuit.num_qubits),
+        error: None,
+    };
+    tuner.simulator.update(&circuit);
+
+    let raw = RawMode::enable();
+    tuner.draw(raw.is_some());
+    let mut input = io::stdin().lock().bytes();
+    while let Some(key) = read_key(&mut input)? {
+        if !tuner.handle(key) {
+            break;
+        }
+        tuner.draw(raw.is_some());
+    }
+    Ok(())
+}
+
+/// Next key press, decoding arrow-key escape sequences; `None` at end of input
+fn read_key<I: Iterator<Item = io::Result<u8>>>(input: &mut I) -> io::Result<Option<Key>> {
+    loop {
+        let byte = match input.next() {
+            Some(byte) => byte?,
+            None => return Ok(None),
+        };
+        match byte {
+            0x1b => {
+                if input.next().transpose()? != Some(b'[') {
+                    continue;
+                }
+                match input.next().transpose()? {
+                    Some(b'A') => return Ok(Some(Key::Up)),
+                    Some(b'B') => return Ok(Some(Key::Down)),
+                    Some(b'C') => return Ok(Some(Key::Right)),
+                    Some(b'D') => return Ok(Some(Key::Left)),
+                    Some(_) => continue,
+                    None => return Ok(None),
+                }
+            }
+            b'\n' | b'\r' | b' ' => continue,
+            byte => return Ok(Some(Key::Char(byte as char))),
+        }
+    }
+}
+
+impl Tuner {
+    /// Apply one key press, returning false when the session should end
+    fn handle(&mut self, key: Key) -> bool {
+        let names: Vec<String> = self.values.keys().cloned().collect();
+        let name = names[self.selected].clone();
+        match key {
+            Key::Up | Key::Char('k') => self.selected = (self.selected + names.len() - 1) % names.len(),
+            Key::Down | Key::Char('j') | Key::Char('\t') => self.selected = (self.selected + 1) % names.len(),
+            Key::Left | Key::Char('h') => self.adjust(&name, -self.step),
+            Key::Right | Key::Char('l') => self.adjust(&name, self.step),
+            Key::Char('[') => self.step = (self.step / 2.0).max(1e-6),
+            Key::Char(']') => self.step = (self.step * 2.0).min(PI),
+            Key::Char('0') => self.set(&name, 0.0),
+            Key::Char('r') => self.set(&name, self.defaults[&name]),
+            Key::Char('q') | Key::Char('\x03') | Key::Char('\x04') => return false,
+            Key::Char(_) => {}
+        }
+        true
+    }
+
+    /// Move a parameter by `delta`, wrapping around the (-pi, pi] slider
+    fn adjust(&mut self, name: &str, delta: f64) {
+        let mut value = self.values[name] + delta;
+        if value > PI + 1e-12 {
+            value -= 2.0 * PI;
+        } else if value <= -PI + 1e-12 {
+            value += 2.0 * PI;
+        }
+        self.set(name, value);
+    }
+
+    fn set(&mut self, name: &str, value: f64) {
+        self.values.insert(name.to_string(), value);
+        match self.circuit() {
+            Ok(circuit) => {
+                self.simulator.update(&circuit);
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    fn circuit(&self) -> Result<QuantumCircuit, Box<dyn Error>> {
+        let circuit = params::instantiate(&self.document, &self.values)?;
+        qsim::validate_circuit(&circuit)?;
+        Ok(circuit)
+    }
+
+    fn draw(&self, clear: bool) {
+        if clear {
+            print!("\x1b[H\x1b[2J");
+        }
+        let simulator = self.simulator.simulator();
+        let num_qubits = simulator.num_qubits;
+        outln!("┌─ Tune: {} ({} qubits, {} parameters)", self.file_path, num_qubits, self.values.len());
+        for (index, (name, value)) in self.values.iter().enumerate() {
+            let marker = if index == self.selected { '▸' } else { ' ' };
+            outln!("│  {} {:<10} [{}] {:>8.4}", marker, name, slider(*value), value);
+        }
+        if let Some(error) = &self.error {
+            outln!("├─ Error: {}", error);
+        }
+        outln!("├─ ⟨{}⟩ = {:+.6}", self.observable, simulator.expectation(&self.observable));
+
+        let probabilities = simulator.measure_all();
+        let mut shown: Vec<usize> = (0..probabilities.len()).collect();
+        if probabilities.len() > HISTOGRAM_ROWS {
+            shown.sort_by(|&a, &b| probabilities[b].total_cmp(&probabilities[a]));
+            shown.truncate(HISTOGRAM_ROWS);
+            shown.sort_unstable();
+            outln!("├─ Probabilities (the {} most likely of {} states)", HISTOGRAM_ROWS, probabilities.len());
+        } else {
+            outln!("├─ Probabilities");
+        }
+        for state in shown {
+            let probability = probabilities[state];
+            let bar = "█".repeat((probability * 40.0) as usize);
+            outln!("│  |{:0width$b}⟩ {:6.2}% {}", state, probability * 100.0, bar, width = num_qubits.max(1));
+        }
+        outln!(
+            "└─ up/down or k/j select · left/right or h/l adjust by {:.4} · [ ] step · 0 zero · r reset · q quit",
+            self.step
+        );
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Slider track over (-pi, pi] with a knob at `value`
+fn slider(value: f64) -> String {
+    let position = (((value + PI) / (2.0 * PI)).clamp(0.0, 1.0) * (SLIDER_WIDTH - 1) as f64).round() as usize;
+    (0..SLIDER_WIDTH).map(|i| if i == position { '●' } else { '─' }).collect()
+}
@@ -0,0 +1,74 @@
+//! Unitary Module
+//! Full 2^n x 2^n unitaries of small circuits, for checking decompositions
+
+use crate::gpu_ops::Complex;
+use crate::qsim::{QuantumCircuit, QuantumSimulator};
+use crate::tomography;
+
+/// Widest circuit whose unitary is composed: 4^10 entries, 16 MiB
+pub const MAX_QUBITS: usize = 10;
+
+/// Matrix of a whole circuit, `unitary[row][col]` = ⟨row|U|col⟩ with qubit 0 the least
+/// significant bit. Terminal measurements are dropped and snapshots ignored; resets and
+/// mid-circuit measurements have no unitary, so such circuits are rejected.
+pub fn circuit_unitary(circuit: &QuantumCircuit) -> Result<Vec<Vec<Complex>>, String> {
+    if circuit.num_qubits > MAX_QUBITS {
+        return Err(format!(
+            "the unitary of {} qubits has 4^{} entries; at most {} qubits are supported",
+            circuit.num_qubits, circuit.num_qubits, MAX_QUBITS
+        ));
+    }
+    if circuit.has_collapse() {
+        return Err("the circuit resets or measures qubits mid-circuit, so it has no unitary".to_string());
+    }
+    let unitary = tomography::prepared(circuit);
+    let dim = 1 << circuit.num_qubits;
+    // Column k is the image of basis state k, so every gate is applied to all 2^n columns
+    let columns: Vec<Vec<Complex>> = (0..dim)
+        .map(|col| {
+            let mut basis = vec![Complex::new(0.0, 0.0); dim];
+            basis[col] = Complex::new(1.0, 0.0);
+            let mut simulator = QuantumSimulator::from_state(basis)?;
+            for gate in &unitary.gates {
+                simulator.apply_gate(gate);
+            }
+            Ok(simulator.get_state().into_owned())
+        })
+        .collect::<Result<_, String>>()?;
+    Ok((0..dim).map(|row| (0..dim).map(|col| columns[col][row]).collect()).collect())
+}
+
+/// How closely two unitaries of the same size agree, ignoring global phase
+#[derive(Debug, Clone, Copy)]
+pub struct UnitaryDistance {
+    /// |tr(U†V)|² / d², 1 for unitaries equal up to global phase
+    pub process_fidelity: f64,
+    /// Largest |U_ij e^(iφ) - V_ij| with the phase φ that best aligns them
+    pub max_deviation: f64,
+}
+
+/// Compare two unitaries of the same dimension
+pub fn distance(a: &[Vec<Complex>], b: &[Vec<Complex>]) -> UnitaryDistance {
+    let dim = a.len() as f64;
+    let overlap = a
+        .iter()
+        .flatten()
+        .zip(b.iter().flatten())
+        .fold(Complex::new(0.0, 0.0), |sum, (x, y)| sum + x.conjugate() * *y);
+    let magnitude = overlap.magnitude_squared().sqrt();
+    let phase = if magnitude > 0.0 {
+        Complex::new(overlap.re / magnitude, overlap.im / magnitude)
+    } else {
+        Complex::new(1.0, 0.0)
+    };
+    let max_deviation = a
+        .iter()
+        .flatten()
+        .zip(b.iter().flatten())
+        .map(|(x, y)| {
+            let aligned = *x * phase;
+            Complex::new(aligned.re - y.re, aligned.im - y.im).magnitude_squared().sqrt()
+        })
+        .fold(0.0, f64::max);
+    UnitaryDistance { process_fidelity: (magnitude / dim).powi(2), max_deviation }
+}
@@ -0,0 +1,111 @@
+//! Workspace Module
+//! Single-file bundle of a server's circuits, results, noise models and configs
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::manifest::Manifest;
+use crate::noise::NoiseModel;
+use crate::qsim::QuantumCircuit;
+
+/// Value of the `format` field identifying a workspace archive
+pub const WORKSPACE_FORMAT: &str = "quantummesh-workspace";
+
+/// Archive layout version; bump when fields change incompatibly
+pub const WORKSPACE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct Workspace {
+    pub format: String,
+    pub version: u32,
+    /// Unix time of the export, in seconds
+    pub exported_at: u64,
+    #[serde(default)]
+    pub circuits: Vec<CircuitEntry>,
+    #[serde(default)]
+    pub results: Vec<ResultEntry>,
+    #[serde(default)]
+    pub noise_models: BTreeMap<String, NoiseModel>,
+    /// Config file name -> file contents
+    #[serde(default)]
+    pub configs: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CircuitEntry {
+    pub id: String,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub created_at: u64,
+    pub circuit: QuantumCircuit,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ResultEntry {
+    pub id: String,
+    pub circuit_id: Option<String>,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub created_at: u64,
+    pub num_qubits: usize,
+    pub probabilities: Vec<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<Manifest>,
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        Self {
+            format: WORKSPACE_FORMAT.to_string(),
+            version: WORKSPACE_VERSION,
+            exported_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or(0),
+            circuits: Vec::new(),
+            results: Vec::new(),
+            noise_models: BTreeMap::new(),
+            configs: BTreeMap::new(),
+        }
+    }
+
+    /// Reject archives from other tools or newer versions
+    pub fn validate(&self) -> Result<(), String> {
+        if self.format != WORKSPACE_FORMAT {
+            return Err(format!("not a workspace archive (format '{}')", self.format));
+        }
+        if self.version > WORKSPACE_VERSION {
+            return Err(format!(
+                "workspace version {} is newer than supported version {}",
+                self.version, WORKSPACE_VERSION
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} circuits, {} results, {} noise models, {} configs",
+            self.circuits.len(),
+            self.results.len(),
+            self.noise_models.len(),
+            self.configs.len()
+        )
+    }
+}
+
+/// Read and validate a workspace archive
+pub fn load_workspace(path: &str) -> Result<Workspace, Box<dyn Error>> {
+    let workspace: Workspace = serde_json::from_str(&fs::read_to_string(path)?)?;
+    workspace.validate()?;
+    Ok(workspace)
+}
+
+pub fn save_workspace(workspace: &Workspace, path: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(path, serde_json::to_string_pretty(workspace)?)?;
+    Ok(())
+}